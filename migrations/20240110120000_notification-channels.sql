@@ -0,0 +1,33 @@
+CREATE TABLE users_notifications_preferences (
+    id bigserial PRIMARY KEY,
+    user_id bigint REFERENCES users ON DELETE CASCADE NOT NULL,
+    channel varchar(64) NOT NULL,
+    -- NULL applies to all notification types without a more specific preference
+    notification_type varchar(64) NULL,
+    enabled boolean NOT NULL
+);
+CREATE UNIQUE INDEX users_notifications_preferences_unique
+    ON users_notifications_preferences (user_id, channel, COALESCE(notification_type, ''));
+
+CREATE TABLE users_push_subscriptions (
+    endpoint text PRIMARY KEY,
+    user_id bigint REFERENCES users ON DELETE CASCADE NOT NULL,
+    p256dh varchar(255) NOT NULL,
+    auth varchar(255) NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_used timestamptz NULL
+);
+CREATE INDEX users_push_subscriptions_user_id ON users_push_subscriptions (user_id);
+
+CREATE TABLE notifications_deliveries (
+    notification_id bigint REFERENCES notifications ON DELETE CASCADE NOT NULL,
+    user_id bigint REFERENCES users ON DELETE CASCADE NOT NULL,
+    channel varchar(64) NOT NULL,
+    status varchar(64) NOT NULL,
+    attempt_count integer NOT NULL DEFAULT 0,
+    next_attempt timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (notification_id, channel)
+);
+CREATE INDEX notifications_deliveries_pending
+    ON notifications_deliveries (next_attempt)
+    WHERE status = 'pending';
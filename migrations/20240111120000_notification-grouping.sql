@@ -0,0 +1,6 @@
+ALTER TABLE notifications ADD COLUMN group_key varchar(255) NULL;
+ALTER TABLE notifications ADD COLUMN group_items jsonb NOT NULL DEFAULT '[]'::jsonb;
+
+CREATE INDEX notifications_group_key
+    ON notifications (user_id, group_key)
+    WHERE group_key IS NOT NULL AND read = FALSE;
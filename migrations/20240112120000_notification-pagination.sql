@@ -0,0 +1,2 @@
+-- Keyset pagination over a user's notifications, newest first
+CREATE INDEX notifications_user_created_id ON notifications (user_id, created DESC, id DESC);
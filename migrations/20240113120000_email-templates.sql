@@ -0,0 +1,10 @@
+CREATE TABLE email_templates (
+    template varchar(64) NOT NULL,
+    locale varchar(16) NOT NULL,
+    subject varchar(255) NOT NULL,
+    title varchar(255) NOT NULL,
+    description text NOT NULL,
+    line_two text NOT NULL,
+    button_title varchar(255) NULL,
+    PRIMARY KEY (template, locale)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE users_notification_webhooks (
+    user_id bigint REFERENCES users ON DELETE CASCADE PRIMARY KEY,
+    url varchar(2048) NOT NULL,
+    -- Shared secret used to sign deliveries with HMAC-SHA256
+    secret varchar(64) NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_used timestamptz NULL
+);
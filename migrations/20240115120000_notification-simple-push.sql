@@ -0,0 +1,10 @@
+CREATE TABLE users_simple_push_targets (
+    user_id bigint REFERENCES users ON DELETE CASCADE PRIMARY KEY,
+    -- 'ntfy' for an ntfy topic URL, 'generic' for any endpoint accepting a JSON POST
+    provider varchar(64) NOT NULL,
+    url varchar(2048) NOT NULL,
+    -- Sent as a bearer token, if set
+    token varchar(255) NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_used timestamptz NULL
+);
@@ -0,0 +1,8 @@
+ALTER TABLE users
+    ADD COLUMN stripe_account_id text NULL,
+    -- Kept in sync with the connected account through Stripe's account.updated webhook
+    ADD COLUMN stripe_payouts_enabled boolean NOT NULL DEFAULT FALSE;
+
+CREATE UNIQUE INDEX users_stripe_account_id ON users (stripe_account_id);
+
+ALTER TABLE payouts ADD COLUMN failure_reason text NULL;
@@ -0,0 +1,9 @@
+CREATE TABLE payout_schedules (
+    user_id bigint REFERENCES users ON DELETE CASCADE PRIMARY KEY,
+    method varchar(64) NOT NULL,
+    method_id varchar(255) NOT NULL,
+    -- The balance is only withdrawn once it reaches this amount
+    threshold numeric(40, 30) NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_attempt timestamptz NULL
+);
@@ -0,0 +1,14 @@
+CREATE TABLE users_tax_forms (
+    user_id bigint REFERENCES users ON DELETE CASCADE PRIMARY KEY,
+    -- The form's ID at the tax form provider
+    provider_id varchar(255) NOT NULL UNIQUE,
+    -- Only known once the user has filled in the form, as the provider picks the form to fill
+    form_type varchar(64) NULL,
+    status varchar(64) NOT NULL,
+    -- Fraction of each payout withheld for taxes
+    withholding_rate numeric(40, 20) NOT NULL DEFAULT 0,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    completed timestamptz NULL
+);
+
+ALTER TABLE payouts ADD COLUMN withholding numeric(40, 20) NULL;
@@ -0,0 +1,6 @@
+-- The currency a payout was sent in, if it was converted from USD, and the amount received in
+-- that currency's minor unit
+ALTER TABLE payouts ADD COLUMN currency varchar(3) NULL;
+ALTER TABLE payouts ADD COLUMN currency_amount bigint NULL;
+-- How much one USD was worth in the payout's currency when it was quoted
+ALTER TABLE payouts ADD COLUMN fx_rate numeric(40, 20) NULL;
@@ -0,0 +1,15 @@
+-- Manual corrections to a user's balance, such as clawbacks of ad revenue which was reversed by
+-- the ad network or reversals of fraudulent revenue. Negative amounts are taken from the balance
+CREATE TABLE payouts_adjustments (
+    id bigserial PRIMARY KEY,
+    user_id bigint REFERENCES users NOT NULL,
+    mod_id bigint REFERENCES mods NULL,
+    amount numeric(96, 48) NOT NULL,
+    reason varchar(64) NOT NULL,
+    notes text NULL,
+    created_by bigint REFERENCES users NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX payouts_adjustments_user_id
+    ON payouts_adjustments (user_id);
@@ -0,0 +1,18 @@
+-- Creators must be enrolled in the monetization program to earn revenue. Users apply, and
+-- moderators review their application
+CREATE TABLE monetization_enrollments (
+    user_id bigint REFERENCES users ON DELETE CASCADE PRIMARY KEY,
+    status varchar(64) NOT NULL,
+    applied timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    reviewed timestamptz NULL,
+    reviewed_by bigint REFERENCES users NULL,
+    review_notes text NULL
+);
+
+CREATE INDEX monetization_enrollments_status
+    ON monetization_enrollments (status);
+
+-- Creators who have already earned revenue keep earning it
+INSERT INTO monetization_enrollments (user_id, status, reviewed)
+SELECT DISTINCT user_id, 'active', CURRENT_TIMESTAMP
+FROM payouts_values;
@@ -0,0 +1,30 @@
+-- Background work which must survive restarts. Workers claim jobs by leasing them until
+-- `locked_until`, so jobs held by a worker which died are picked up again once the lease ends
+CREATE TABLE jobs (
+    id bigserial PRIMARY KEY,
+    kind varchar(64) NOT NULL,
+    payload jsonb NOT NULL,
+    status varchar(64) NOT NULL,
+    -- At most one pending or running job exists per key
+    unique_key varchar(255) NULL,
+    attempt_count integer NOT NULL DEFAULT 0,
+    max_attempts integer NOT NULL,
+    timeout_seconds integer NOT NULL,
+    run_at timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    locked_until timestamptz NULL,
+    last_error text NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    finished timestamptz NULL
+);
+
+CREATE INDEX jobs_pending
+    ON jobs (run_at)
+    WHERE status = 'pending';
+CREATE INDEX jobs_running
+    ON jobs (locked_until)
+    WHERE status = 'running';
+CREATE INDEX jobs_status
+    ON jobs (status, created);
+CREATE UNIQUE INDEX jobs_unique_key
+    ON jobs (unique_key)
+    WHERE status IN ('pending', 'running');
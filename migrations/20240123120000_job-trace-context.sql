@@ -0,0 +1,2 @@
+-- The W3C trace context of the span which queued a job, so the job's spans join its trace
+ALTER TABLE jobs ADD COLUMN trace_context jsonb NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,18 @@
+-- Runtime toggles for features which are being rolled out
+CREATE TABLE feature_flags (
+    key varchar(64) PRIMARY KEY,
+    description text NOT NULL DEFAULT '',
+    enabled boolean NOT NULL DEFAULT FALSE,
+    -- The share of users the flag is enabled for, while it is enabled
+    rollout_percentage smallint NOT NULL DEFAULT 0 CHECK (rollout_percentage BETWEEN 0 AND 100),
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- Enables or disables a flag for a user, regardless of its rollout
+CREATE TABLE feature_flag_overrides (
+    flag_key varchar(64) NOT NULL REFERENCES feature_flags ON DELETE CASCADE,
+    user_id bigint NOT NULL REFERENCES users ON DELETE CASCADE,
+    enabled boolean NOT NULL,
+    PRIMARY KEY (flag_key, user_id)
+);
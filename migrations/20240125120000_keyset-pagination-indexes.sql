@@ -0,0 +1,3 @@
+-- Keyset pagination over the open reports and the moderation queue
+CREATE INDEX reports_open_created ON reports (created, id) WHERE closed = FALSE;
+CREATE INDEX mods_processing_queued ON mods (queued, id) WHERE status = 'processing';
@@ -0,0 +1,5 @@
+-- Jobs started by a user for a long operation, such as an export. They are kept once they
+-- complete, so the user can poll for their progress and where the result was put
+ALTER TABLE jobs ADD COLUMN user_id bigint NULL REFERENCES users ON DELETE CASCADE;
+ALTER TABLE jobs ADD COLUMN progress real NULL;
+ALTER TABLE jobs ADD COLUMN result_url varchar(2048) NULL;
@@ -0,0 +1,51 @@
+-- Projects a moderator is reviewing, so two moderators do not review the same project.
+-- Claims expire in case the moderator never finishes
+CREATE TABLE moderation_claims (
+    project_id bigint PRIMARY KEY REFERENCES mods ON DELETE CASCADE,
+    user_id bigint NOT NULL REFERENCES users ON DELETE CASCADE,
+    claimed timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires timestamptz NOT NULL
+);
+
+-- Notifies listeners of changes to the moderation queue, which are streamed to moderators.
+-- Notifications are sent when the transaction commits, so rolled back changes are not sent
+CREATE FUNCTION notify_moderation_queue() RETURNS trigger AS $$
+BEGIN
+    IF TG_TABLE_NAME = 'mods' THEN
+        IF TG_OP = 'DELETE' THEN
+            IF OLD.status = 'processing' THEN
+                PERFORM pg_notify('moderation_queue', json_build_object(
+                    'type', 'removed', 'project_id', OLD.id
+                )::text);
+            END IF;
+        ELSIF NEW.status = 'processing' AND (TG_OP = 'INSERT' OR OLD.status <> 'processing') THEN
+            PERFORM pg_notify('moderation_queue', json_build_object(
+                'type', 'submitted', 'project_id', NEW.id
+            )::text);
+        ELSIF TG_OP = 'UPDATE' AND OLD.status = 'processing' AND NEW.status <> 'processing' THEN
+            PERFORM pg_notify('moderation_queue', json_build_object(
+                'type', 'status_changed', 'project_id', NEW.id,
+                'old_status', OLD.status, 'new_status', NEW.status
+            )::text);
+        END IF;
+    ELSIF TG_OP = 'DELETE' THEN
+        PERFORM pg_notify('moderation_queue', json_build_object(
+            'type', 'unclaimed', 'project_id', OLD.project_id
+        )::text);
+    ELSE
+        PERFORM pg_notify('moderation_queue', json_build_object(
+            'type', 'claimed', 'project_id', NEW.project_id, 'user_id', NEW.user_id,
+            'expires', NEW.expires
+        )::text);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER mods_moderation_queue
+    AFTER INSERT OR UPDATE OF status OR DELETE ON mods
+    FOR EACH ROW EXECUTE FUNCTION notify_moderation_queue();
+
+CREATE TRIGGER moderation_claims_moderation_queue
+    AFTER INSERT OR UPDATE OR DELETE ON moderation_claims
+    FOR EACH ROW EXECUTE FUNCTION notify_moderation_queue();
@@ -0,0 +1,4 @@
+-- Incremented on every edit of a project or version. Its entity tag is derived from it, so
+-- edits made from an outdated copy are rejected instead of overwriting newer changes
+ALTER TABLE mods ADD COLUMN revision integer NOT NULL DEFAULT 1;
+ALTER TABLE versions ADD COLUMN revision integer NOT NULL DEFAULT 1;
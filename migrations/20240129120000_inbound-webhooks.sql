@@ -0,0 +1,14 @@
+-- Webhooks received from other services, stored once verified and processed by the job
+-- workers. Deliveries are unique per source, so retried or replayed deliveries are ignored
+CREATE TABLE inbound_webhooks (
+    id bigserial PRIMARY KEY,
+    source varchar(64) NOT NULL,
+    delivery_id varchar(255) NOT NULL,
+    payload text NOT NULL,
+    received timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    processed timestamptz NULL,
+    UNIQUE (source, delivery_id)
+);
+
+-- Links GitHub repositories to the projects whose source link points to them
+CREATE INDEX mods_links_url ON mods_links (lower(url));
@@ -0,0 +1,13 @@
+-- Users other than the owner who can edit a collection. Editors are invited and have to
+-- accept before they can edit, like team members
+CREATE TABLE collections_editors (
+    collection_id bigint NOT NULL REFERENCES collections ON DELETE CASCADE,
+    user_id bigint NOT NULL REFERENCES users ON DELETE CASCADE,
+    permissions bigint NOT NULL,
+    accepted boolean NOT NULL DEFAULT FALSE,
+    invited_by bigint NULL REFERENCES users ON DELETE SET NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (collection_id, user_id)
+);
+
+CREATE INDEX collections_editors_user ON collections_editors (user_id);
@@ -0,0 +1,10 @@
+CREATE TABLE collection_follows (
+    follower_id bigint NOT NULL REFERENCES users ON DELETE CASCADE,
+    collection_id bigint NOT NULL REFERENCES collections ON DELETE CASCADE,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (follower_id, collection_id)
+);
+
+CREATE INDEX collection_follows_collection ON collection_follows (collection_id);
+
+ALTER TABLE collections ADD COLUMN follows integer NOT NULL DEFAULT 0;
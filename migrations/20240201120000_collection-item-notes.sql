@@ -0,0 +1,4 @@
+-- Collections can be annotated guides: each project can have a note explaining why it is
+-- included, and projects are shown in an explicit order
+ALTER TABLE collections_mods ADD COLUMN note varchar(2048) NULL;
+ALTER TABLE collections_mods ADD COLUMN ordering bigint NOT NULL DEFAULT 0;
@@ -0,0 +1,19 @@
+-- A collection's projects pinned to specific versions at a point in time, so the collection
+-- can be used as a reproducible pack definition
+CREATE TABLE collection_snapshots (
+    id bigint PRIMARY KEY,
+    collection_id bigint NOT NULL REFERENCES collections ON DELETE CASCADE,
+    name varchar(255) NULL,
+    created_by bigint NULL REFERENCES users ON DELETE SET NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX collection_snapshots_collection ON collection_snapshots (collection_id);
+
+CREATE TABLE collection_snapshot_versions (
+    snapshot_id bigint NOT NULL REFERENCES collection_snapshots ON DELETE CASCADE,
+    project_id bigint NOT NULL REFERENCES mods ON DELETE CASCADE,
+    version_id bigint NOT NULL REFERENCES versions ON DELETE CASCADE,
+    ordering bigint NOT NULL DEFAULT 0,
+    PRIMARY KEY (snapshot_id, project_id)
+);
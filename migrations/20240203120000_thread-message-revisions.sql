@@ -0,0 +1,12 @@
+-- Thread messages can be edited by their authors. Each edit keeps the body it replaced,
+-- so participants and moderators can see what a message used to say
+ALTER TABLE threads_messages ADD COLUMN edited_at timestamptz NULL;
+
+CREATE TABLE threads_messages_revisions (
+    id bigserial PRIMARY KEY,
+    message_id bigint NOT NULL REFERENCES threads_messages ON DELETE CASCADE,
+    body jsonb NOT NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX threads_messages_revisions_message ON threads_messages_revisions (message_id);
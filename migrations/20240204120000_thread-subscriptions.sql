@@ -0,0 +1,11 @@
+-- Explicit per-thread notification choices. Without a row, participants of a thread are
+-- notified of new messages by default; a row overrides that either way
+CREATE TABLE threads_subscriptions (
+    thread_id bigint NOT NULL REFERENCES threads ON DELETE CASCADE,
+    user_id bigint NOT NULL REFERENCES users ON DELETE CASCADE,
+    -- FALSE if the user muted the thread
+    subscribed boolean NOT NULL,
+    PRIMARY KEY (thread_id, user_id)
+);
+
+CREATE INDEX threads_subscriptions_user ON threads_subscriptions (user_id);
@@ -0,0 +1,8 @@
+-- Canned responses moderators can post into threads, managed by admins
+CREATE TABLE moderation_templates (
+    name varchar(64) PRIMARY KEY,
+    title varchar(255) NOT NULL,
+    body text NOT NULL,
+    private boolean NOT NULL DEFAULT FALSE,
+    updated timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
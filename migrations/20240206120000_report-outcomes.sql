@@ -0,0 +1,4 @@
+-- How a report was resolved, shown to its reporter once it is closed
+ALTER TABLE reports ADD COLUMN outcome varchar(64) NULL;
+-- Set while moderators are waiting on the reporter to answer a request for more information
+ALTER TABLE reports ADD COLUMN awaiting_reporter boolean NOT NULL DEFAULT FALSE;
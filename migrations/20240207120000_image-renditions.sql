@@ -0,0 +1,3 @@
+-- Optimized copies of uploaded images, as a map of content type to URL
+ALTER TABLE mods ADD COLUMN icon_renditions jsonb NOT NULL DEFAULT '{}'::jsonb;
+ALTER TABLE mods_gallery ADD COLUMN renditions jsonb NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,5 @@
+-- Gallery images share the id space of uploaded images, so thumbnails can be requested for either
+ALTER TABLE mods_gallery ADD COLUMN image_id bigint NULL UNIQUE;
+-- Existing images get random ids in the range of eight character base62 ids
+UPDATE mods_gallery SET image_id = floor(random() * 218340105584896)::bigint;
+ALTER TABLE mods_gallery ALTER COLUMN image_id SET NOT NULL;
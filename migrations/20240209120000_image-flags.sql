@@ -0,0 +1,17 @@
+-- Uploaded images the content scanner flagged, waiting for a moderator to review them. Only
+-- the column for the kind of thing the image belongs to is set
+CREATE TABLE image_flags (
+    id bigserial PRIMARY KEY,
+    image_url varchar(2048) NOT NULL,
+    source_type varchar(64) NOT NULL,
+    project_id bigint NULL REFERENCES mods ON DELETE CASCADE,
+    user_id bigint NULL REFERENCES users ON DELETE CASCADE,
+    organization_id bigint NULL REFERENCES organizations ON DELETE CASCADE,
+    collection_id bigint NULL REFERENCES collections ON DELETE CASCADE,
+    -- The confidence of each label the scanner reported, from 0 to 1
+    labels jsonb NOT NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX image_flags_created
+    ON image_flags (created, id);
@@ -0,0 +1,3 @@
+-- Perceptual hashes of uploaded images, compared to find duplicated and reposted images
+ALTER TABLE mods ADD COLUMN icon_perceptual_hash bigint NULL;
+ALTER TABLE mods_gallery ADD COLUMN perceptual_hash bigint NULL;
@@ -0,0 +1,6 @@
+-- Seeded loader tables were inserted with explicit IDs, so their sequences need to catch up
+-- before rows can be created through the admin API
+SELECT setval('loaders_id_seq', (SELECT COALESCE(MAX(id), 0) + 1 FROM loaders), false);
+SELECT setval('loader_fields_id_seq', (SELECT COALESCE(MAX(id), 0) + 1 FROM loader_fields), false);
+SELECT setval('loader_field_enums_id_seq', (SELECT COALESCE(MAX(id), 0) + 1 FROM loader_field_enums), false);
+SELECT setval('loader_field_enum_values_id_seq', (SELECT COALESCE(MAX(id), 0) + 1 FROM loader_field_enum_values), false);
@@ -0,0 +1,2 @@
+-- Filtering a project's versions by loader field looks up each version's value of a field
+CREATE INDEX version_fields_version_id_field_id ON version_fields (version_id, field_id);
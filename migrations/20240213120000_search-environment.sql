@@ -0,0 +1,5 @@
+-- Documents indexed before the `environment` facet existed don't have it, so queue a full
+-- reindex for the job workers to pick up
+INSERT INTO jobs (kind, payload, status, unique_key, max_attempts, timeout_seconds)
+VALUES ('index_projects', '{"type": "index_projects"}', 'pending', 'index_projects', 3, 10800)
+ON CONFLICT (unique_key) WHERE status IN ('pending', 'running') DO NOTHING;
@@ -0,0 +1,8 @@
+-- Categories can be nested under another category of the same project type, such as
+-- "storage" under "technology". Searching for a category also finds projects in the
+-- categories nested under it
+ALTER TABLE categories ADD COLUMN parent_id integer NULL REFERENCES categories ON DELETE SET NULL;
+CREATE INDEX categories_parent_id ON categories (parent_id);
+
+-- Like loaders, seeded categories were inserted with explicit IDs
+SELECT setval('categories_id_seq', (SELECT COALESCE(MAX(id), 0) + 1 FROM categories), false);
@@ -0,0 +1,27 @@
+-- GitHub repositories projects import their releases from as versions
+CREATE TABLE project_github_links (
+    project_id bigint PRIMARY KEY REFERENCES mods ON DELETE CASCADE,
+    -- The repository as `owner/name`, lowercased
+    repository varchar(255) NOT NULL,
+    -- The member who linked the repository, who imported versions are authored by
+    user_id bigint NOT NULL REFERENCES users ON DELETE CASCADE,
+    -- The loaders and version fields given to imported versions, which releases don't say
+    loaders varchar(255)[] NOT NULL,
+    version_fields jsonb NOT NULL,
+    -- Whether new releases are imported as draft versions when GitHub sends them
+    sync boolean NOT NULL DEFAULT FALSE,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX project_github_links_repository
+    ON project_github_links (repository);
+
+-- Releases already imported into a project, so they aren't imported again. Kept after the
+-- version is deleted
+CREATE TABLE github_release_imports (
+    project_id bigint NOT NULL REFERENCES mods ON DELETE CASCADE,
+    release_id bigint NOT NULL,
+    version_id bigint NULL REFERENCES versions ON DELETE SET NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (project_id, release_id)
+);
@@ -0,0 +1,20 @@
+-- Discord roles granted to users by their relationship with a project or organization, which
+-- bots of the Discord server check. Only one of project_id and organization_id is set
+CREATE TABLE discord_role_links (
+    id bigserial PRIMARY KEY,
+    project_id bigint NULL REFERENCES mods ON DELETE CASCADE,
+    organization_id bigint NULL REFERENCES organizations ON DELETE CASCADE,
+    guild_id bigint NOT NULL,
+    role_id bigint NOT NULL,
+    -- Who is granted the role, such as `team_member` or `follower`
+    relationship varchar(64) NOT NULL,
+    -- Shared secret bots sign verification requests with
+    secret varchar(255) NOT NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK ((project_id IS NULL) <> (organization_id IS NULL))
+);
+
+CREATE INDEX discord_role_links_project_id
+    ON discord_role_links (project_id);
+CREATE INDEX discord_role_links_organization_id
+    ON discord_role_links (organization_id);
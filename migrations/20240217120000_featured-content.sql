@@ -0,0 +1,16 @@
+-- Projects staff feature on the homepage, such as featured projects, seasonal spotlights and
+-- homepage rows, which are shown between their start and end dates
+CREATE TABLE featured_rows (
+    id bigserial PRIMARY KEY,
+    -- Where the row is shown, such as `featured`, `spotlight` or `homepage_row`
+    kind varchar(64) NOT NULL,
+    title varchar(255) NOT NULL,
+    description varchar(2048) NOT NULL DEFAULT '',
+    -- The projects in the row, in the order they are shown
+    project_ids bigint[] NOT NULL DEFAULT '{}',
+    starts timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ends timestamptz NULL,
+    ordering integer NOT NULL DEFAULT 0,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (ends IS NULL OR ends > starts)
+);
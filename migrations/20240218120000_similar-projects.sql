@@ -0,0 +1,9 @@
+-- Projects similar to each project, computed periodically from shared categories and loaders
+-- and from users downloading or following both projects
+CREATE TABLE similar_projects (
+    project_id bigint NOT NULL REFERENCES mods ON DELETE CASCADE,
+    similar_id bigint NOT NULL REFERENCES mods ON DELETE CASCADE,
+    score real NOT NULL,
+    computed timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (project_id, similar_id)
+);
@@ -0,0 +1,12 @@
+-- Addresses which bounced permanently or marked an email as spam, which are not sent email
+-- until their owner asks for it again
+CREATE TABLE email_suppressions (
+    -- Lowercased
+    email varchar(2048) PRIMARY KEY,
+    -- Why sends stopped, such as `bounce` or `complaint`
+    reason varchar(64) NOT NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Users are asked to change their email once theirs can't be delivered to
+ALTER TABLE users ADD COLUMN email_undeliverable boolean NOT NULL DEFAULT FALSE;
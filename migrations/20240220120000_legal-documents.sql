@@ -0,0 +1,22 @@
+-- Published versions of the terms of service, privacy policy and content rules. Versions are
+-- never edited once published, so acceptances always refer to the text which was accepted
+CREATE TABLE legal_documents (
+    id bigserial PRIMARY KEY,
+    -- Which document this is a version of, such as `terms`, `privacy` or `rules`
+    kind varchar(64) NOT NULL,
+    version integer NOT NULL,
+    -- Markdown
+    content text NOT NULL,
+    -- Whether users have to accept this version before making more changes. Typo fixes and
+    -- other edits which don't change what users agree to are not material
+    material boolean NOT NULL DEFAULT TRUE,
+    published timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (kind, version)
+);
+
+CREATE TABLE legal_acceptances (
+    user_id bigint NOT NULL REFERENCES users ON DELETE CASCADE,
+    document_id bigint NOT NULL REFERENCES legal_documents ON DELETE CASCADE,
+    accepted timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, document_id)
+);
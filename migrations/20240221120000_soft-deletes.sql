@@ -0,0 +1,7 @@
+-- Deleted accounts and projects are hidden for a grace period in which they can be restored,
+-- before a job purges them
+ALTER TABLE mods ADD COLUMN deleted timestamptz NULL;
+ALTER TABLE users ADD COLUMN deleted timestamptz NULL;
+
+CREATE INDEX mods_deleted ON mods (deleted) WHERE deleted IS NOT NULL;
+CREATE INDEX users_deleted ON users (deleted) WHERE deleted IS NOT NULL;
@@ -0,0 +1,27 @@
+-- The autonomous system each account signed up from, to spot bursts of sign ups from one
+-- network. Accounts created before this was recorded have no row
+CREATE TABLE account_signups (
+    user_id bigint PRIMARY KEY REFERENCES users ON DELETE CASCADE,
+    asn bigint NOT NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX account_signups_asn
+    ON account_signups (asn, created);
+
+-- Accounts and projects whose spam score reached the review threshold, waiting for a moderator
+-- to review them. Only accounts have no project
+CREATE TABLE spam_flags (
+    id bigserial PRIMARY KEY,
+    user_id bigint NOT NULL REFERENCES users ON DELETE CASCADE,
+    project_id bigint NULL REFERENCES mods ON DELETE CASCADE,
+    score int NOT NULL,
+    -- The signals which added up to the score, with the points each added
+    signals jsonb NOT NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX spam_flags_created
+    ON spam_flags (created, id);
+CREATE INDEX spam_flags_project_id
+    ON spam_flags (project_id) WHERE project_id IS NOT NULL;
@@ -0,0 +1,14 @@
+-- Short links to projects and versions made by their teams, so links can be shortened without
+-- third party shorteners. Clicks are counted as the links redirect
+CREATE TABLE shortlinks (
+    id bigserial PRIMARY KEY,
+    code varchar(16) NOT NULL UNIQUE,
+    project_id bigint NOT NULL REFERENCES mods ON DELETE CASCADE,
+    version_id bigint NULL REFERENCES versions ON DELETE CASCADE,
+    created_by bigint NULL REFERENCES users ON DELETE SET NULL,
+    clicks bigint NOT NULL DEFAULT 0,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX shortlinks_project_id
+    ON shortlinks (project_id);
@@ -0,0 +1,12 @@
+-- The sections of user profiles. Users who never edited their profile have no row
+CREATE TABLE user_profiles (
+    user_id bigint PRIMARY KEY REFERENCES users ON DELETE CASCADE,
+    -- Markdown, sanitized when it is saved
+    about text NULL,
+    -- In the order they are shown
+    pinned_projects bigint[] NOT NULL DEFAULT '{}',
+    featured_collection bigint NULL REFERENCES collections ON DELETE SET NULL,
+    -- Each with a label, URL and when the URL was verified to link back to the profile
+    links jsonb NOT NULL DEFAULT '[]',
+    updated timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,7 @@
+-- The weekly reports sent to staff, so each week is only reported once however often the
+-- report job runs
+CREATE TABLE staff_reports (
+    week_start timestamptz PRIMARY KEY,
+    stats jsonb NOT NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,27 @@
+-- Read-only sessions admins open as another user to reproduce issues the user reported. Tokens
+-- are only shown to the admin once, so they are stored hashed
+CREATE TABLE impersonation_sessions (
+    id bigserial PRIMARY KEY,
+    token_hash text NOT NULL UNIQUE,
+    admin_id bigint NOT NULL REFERENCES users ON DELETE CASCADE,
+    user_id bigint NOT NULL REFERENCES users ON DELETE CASCADE,
+    reason text NOT NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires timestamptz NOT NULL,
+    revoked timestamptz NULL
+);
+
+CREATE INDEX impersonation_sessions_user_id
+    ON impersonation_sessions (user_id, created);
+
+-- Every request made with an impersonation session, which the impersonated user can see
+CREATE TABLE impersonation_requests (
+    id bigserial PRIMARY KEY,
+    session_id bigint NOT NULL REFERENCES impersonation_sessions ON DELETE CASCADE,
+    method text NOT NULL,
+    path text NOT NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX impersonation_requests_session_id
+    ON impersonation_requests (session_id, created);
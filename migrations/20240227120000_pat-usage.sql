@@ -0,0 +1,26 @@
+-- Requests made with each personal access token, counted by hour, so developers can see which
+-- of their tokens is using up their rate limit
+CREATE TABLE pat_usage (
+    pat_id bigint NOT NULL REFERENCES pats ON DELETE CASCADE,
+    hour timestamptz NOT NULL,
+    requests bigint NOT NULL DEFAULT 0,
+    errors bigint NOT NULL DEFAULT 0,
+    rate_limited bigint NOT NULL DEFAULT 0,
+    PRIMARY KEY (pat_id, hour)
+);
+
+CREATE INDEX pat_usage_hour
+    ON pat_usage (hour);
+
+-- A sample of the requests made with each token which failed, only the latest of which are kept
+CREATE TABLE pat_errors (
+    id bigserial PRIMARY KEY,
+    pat_id bigint NOT NULL REFERENCES pats ON DELETE CASCADE,
+    method text NOT NULL,
+    path text NOT NULL,
+    status smallint NOT NULL,
+    created timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX pat_errors_pat_id
+    ON pat_errors (pat_id, created);
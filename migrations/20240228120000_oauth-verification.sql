@@ -0,0 +1,14 @@
+-- Third party OAuth apps can apply to be verified. Staff review the application, and verified
+-- apps get a higher rate limit and can request sensitive scopes
+CREATE TABLE oauth_client_verifications (
+    client_id bigint REFERENCES oauth_clients ON DELETE CASCADE PRIMARY KEY,
+    status varchar(64) NOT NULL,
+    reason text NOT NULL,
+    applied timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    reviewed timestamptz NULL,
+    reviewed_by bigint REFERENCES users NULL,
+    review_notes text NULL
+);
+
+CREATE INDEX oauth_client_verifications_status
+    ON oauth_client_verifications (status);
@@ -0,0 +1,11 @@
+-- Tokens owned by an organization rather than one of its members. Each token acts as an account
+-- of its own, which is a member of the organization's team, so it keeps working when the member
+-- who created it leaves
+CREATE TABLE organization_tokens (
+    pat_id bigint PRIMARY KEY REFERENCES pats ON DELETE CASCADE,
+    organization_id bigint NOT NULL REFERENCES organizations ON DELETE CASCADE,
+    created_by bigint REFERENCES users ON DELETE SET NULL
+);
+
+CREATE INDEX organization_tokens_organization_id
+    ON organization_tokens (organization_id);
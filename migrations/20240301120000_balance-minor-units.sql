@@ -0,0 +1,4 @@
+-- Balances are kept in the minor unit of their currency, such as cents for USD. They stay
+-- decimals, as revenue accrues in fractions of the minor unit
+ALTER TABLE users ADD COLUMN balance_currency varchar(3) NOT NULL DEFAULT 'USD';
+UPDATE users SET balance = balance * 100;
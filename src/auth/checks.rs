@@ -305,13 +305,24 @@ pub async fn is_visible_collection(
 ) -> Result<bool, ApiError> {
     let mut authorized = !collection_data.status.is_hidden();
     if let Some(user) = &user_option {
-        if !authorized && (user.role.is_mod() || user.id == collection_data.user_id.into()) {
+        if !authorized && is_collection_member(collection_data, user) {
             authorized = true;
         }
     }
     Ok(authorized)
 }
 
+/// Whether the user owns, moderates or has been invited to edit the collection. Invited
+/// editors can see private collections before accepting, so they know what they're joining
+fn is_collection_member(collection: &Collection, user: &User) -> bool {
+    user.role.is_mod()
+        || user.id == collection.user_id.into()
+        || collection
+            .editors
+            .iter()
+            .any(|x| x.user_id == user.id.into())
+}
+
 pub async fn filter_visible_collections(
     collections: Vec<Collection>,
     user_option: &Option<User>,
@@ -333,9 +344,9 @@ pub async fn filter_visible_collections(
     }
 
     for collection in check_collections {
-        // Collections are simple- if we are the owner or a mod, we can see it
+        // Collections are simple- if we are the owner, an editor or a mod, we can see it
         if let Some(user) = user_option {
-            if user.role.is_mod() || user.id == collection.user_id.into() {
+            if is_collection_member(&collection, user) {
                 return_collections.push(collection.into());
             }
         }
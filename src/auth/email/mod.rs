@@ -1,9 +1,17 @@
+use crate::database::models::job_item::Job;
+use crate::database::models::DatabaseError;
+use crate::models::jobs::JobPayload;
+use chrono::Utc;
 use lettre::message::header::ContentType;
 use lettre::message::Mailbox;
 use lettre::transport::smtp::authentication::Credentials;
 use lettre::{Address, Message, SmtpTransport, Transport};
+use std::collections::HashMap;
+use template::{render_email, EmailTemplate};
 use thiserror::Error;
 
+pub mod template;
+
 #[derive(Error, Debug)]
 pub enum MailError {
     #[error("Environment Error")]
@@ -14,6 +22,10 @@ pub enum MailError {
     Address(#[from] lettre::address::AddressError),
     #[error("SMTP Error: {0}")]
     Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("Database Error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("Email template {0:?} is missing")]
+    MissingTemplate(EmailTemplate),
 }
 
 pub fn send_email_raw(to: String, subject: String, body: String) -> Result<(), MailError> {
@@ -39,30 +51,30 @@ pub fn send_email_raw(to: String, subject: String, body: String) -> Result<(), M
     Ok(())
 }
 
-pub fn send_email(
+/// Renders an email from a template in the locale closest to `locale`, and queues it to be
+/// sent by the job workers
+pub async fn send_email<'a, E>(
     to: String,
-    email_title: &str,
-    email_description: &str,
-    line_two: &str,
-    button_info: Option<(&str, &str)>,
-) -> Result<(), MailError> {
-    let mut email = if button_info.is_some() {
-        include_str!("button_notif.html")
-    } else {
-        include_str!("auth_notif.html")
-    }
-    .replace("{{ email_title }}", email_title)
-    .replace("{{ email_description }}", email_description)
-    .replace("{{ line_one }}", email_description)
-    .replace("{{ line_two }}", line_two);
-
-    if let Some((button_title, button_link)) = button_info {
-        email = email
-            .replace("{{ button_title }}", button_title)
-            .replace("{{ button_link }}", button_link);
-    }
+    template: EmailTemplate,
+    locale: &str,
+    variables: HashMap<String, String>,
+    exec: E,
+) -> Result<(), MailError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres> + Copy,
+{
+    let email = render_email(template, locale, &variables, exec).await?;
 
-    send_email_raw(to, email_title.to_string(), email)?;
+    Job::enqueue(
+        JobPayload::SendEmail {
+            to,
+            subject: email.subject,
+            body: email.body,
+        },
+        Utc::now(),
+        exec,
+    )
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,281 @@
+use super::MailError;
+use crate::database::models::email_template_item::EmailTemplateItem;
+use actix_web::HttpRequest;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The locale templates fall back to when none is stored for the recipient's locale
+pub const DEFAULT_LOCALE: &str = "en";
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTemplate {
+    VerifyEmail,
+    WelcomeVerifyEmail,
+    ResetPassword,
+    PasswordChanged,
+    PasswordRemoved,
+    EmailChanged,
+    AuthMethodAdded,
+    AuthMethodRemoved,
+    TwoFactorEnabled,
+    TwoFactorRemoved,
+    ModerationDecision,
+    PayoutReceipt,
+    AccountDeleted,
+}
+
+impl EmailTemplate {
+    pub fn iterator() -> impl Iterator<Item = EmailTemplate> {
+        [
+            EmailTemplate::VerifyEmail,
+            EmailTemplate::WelcomeVerifyEmail,
+            EmailTemplate::ResetPassword,
+            EmailTemplate::PasswordChanged,
+            EmailTemplate::PasswordRemoved,
+            EmailTemplate::EmailChanged,
+            EmailTemplate::AuthMethodAdded,
+            EmailTemplate::AuthMethodRemoved,
+            EmailTemplate::TwoFactorEnabled,
+            EmailTemplate::TwoFactorRemoved,
+            EmailTemplate::ModerationDecision,
+            EmailTemplate::PayoutReceipt,
+            EmailTemplate::AccountDeleted,
+        ]
+        .iter()
+        .copied()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailTemplate::VerifyEmail => "verify_email",
+            EmailTemplate::WelcomeVerifyEmail => "welcome_verify_email",
+            EmailTemplate::ResetPassword => "reset_password",
+            EmailTemplate::PasswordChanged => "password_changed",
+            EmailTemplate::PasswordRemoved => "password_removed",
+            EmailTemplate::EmailChanged => "email_changed",
+            EmailTemplate::AuthMethodAdded => "auth_method_added",
+            EmailTemplate::AuthMethodRemoved => "auth_method_removed",
+            EmailTemplate::TwoFactorEnabled => "two_factor_enabled",
+            EmailTemplate::TwoFactorRemoved => "two_factor_removed",
+            EmailTemplate::ModerationDecision => "moderation_decision",
+            EmailTemplate::PayoutReceipt => "payout_receipt",
+            EmailTemplate::AccountDeleted => "account_deleted",
+        }
+    }
+
+    pub fn from_string(string: &str) -> Option<EmailTemplate> {
+        EmailTemplate::iterator().find(|x| x.as_str() == string)
+    }
+}
+
+/// The localizable text of an email. Every field may reference variables as `{{ name }}`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EmailTemplateContent {
+    pub subject: String,
+    pub title: String,
+    pub description: String,
+    pub line_two: String,
+    /// Text of the email's button, which links to the `button_link` variable. Emails without
+    /// a button title have no button
+    pub button_title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub body: String,
+}
+
+lazy_static! {
+    static ref BUNDLED_TEMPLATES: HashMap<EmailTemplate, EmailTemplateContent> =
+        serde_json::from_str(include_str!("templates/en.json"))
+            .expect("Bundled email templates are invalid");
+}
+
+/// Gets the preferred locale of the user making a request from its `Accept-Language` header
+pub fn request_locale(req: &HttpRequest) -> String {
+    req.headers()
+        .get(actix_web::http::header::ACCEPT_LANGUAGE)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.split(',').next())
+        .map(|x| {
+            x.split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+        })
+        .filter(|x| !x.is_empty() && x != "*")
+        .unwrap_or_else(|| DEFAULT_LOCALE.to_string())
+}
+
+/// The locales to try for a requested locale, most specific first: `pt-br` becomes
+/// `[pt-br, pt, en]`
+fn locale_fallbacks(locale: &str) -> Vec<String> {
+    let locale = locale.to_lowercase();
+    let mut locales = vec![locale.clone()];
+
+    if let Some((language, _)) = locale.split_once('-') {
+        locales.push(language.to_string());
+    }
+    if !locales.iter().any(|x| x == DEFAULT_LOCALE) {
+        locales.push(DEFAULT_LOCALE.to_string());
+    }
+
+    locales
+}
+
+/// Gets the content of a template in the closest available locale. Templates stored in the
+/// database take priority over the bundled ones
+pub async fn get_template_content<'a, E>(
+    template: EmailTemplate,
+    locale: &str,
+    exec: E,
+) -> Result<EmailTemplateContent, MailError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let locales = locale_fallbacks(locale);
+    let stored = EmailTemplateItem::get_locales(template, &locales, exec).await?;
+
+    for locale in &locales {
+        if let Some(item) = stored.iter().find(|x| &x.locale == locale) {
+            return Ok(item.content.clone());
+        }
+    }
+
+    BUNDLED_TEMPLATES
+        .get(&template)
+        .cloned()
+        .ok_or(MailError::MissingTemplate(template))
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Replaces every `{{ name }}` in the text with the value of that variable, in a single pass
+/// so placeholders inside substituted values are left as they are. Unknown placeholders are
+/// kept.
+pub fn substitute(text: &str, variables: &HashMap<String, String>, escape: bool) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{ ") {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = rest[3..]
+            .find(" }}")
+            .and_then(|end| Some((end, variables.get(&rest[3..3 + end])?)));
+
+        match value {
+            Some((end, value)) => {
+                if escape {
+                    output.push_str(&escape_html(value));
+                } else {
+                    output.push_str(value);
+                }
+                rest = &rest[3 + end + 3..];
+            }
+            None => {
+                output.push_str("{{ ");
+                rest = &rest[3..];
+            }
+        }
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// Renders template content into a full HTML email, substituting the given variables
+pub fn render_template(
+    content: &EmailTemplateContent,
+    variables: &HashMap<String, String>,
+) -> RenderedEmail {
+    let button = content
+        .button_title
+        .as_ref()
+        .zip(variables.get("button_link"));
+
+    let mut parts = HashMap::from([
+        (
+            "email_title".to_string(),
+            substitute(&content.title, variables, true),
+        ),
+        (
+            "email_description".to_string(),
+            substitute(&content.description, variables, true),
+        ),
+        (
+            "line_one".to_string(),
+            substitute(&content.description, variables, true),
+        ),
+        (
+            "line_two".to_string(),
+            substitute(&content.line_two, variables, true),
+        ),
+    ]);
+
+    let skeleton = if let Some((button_title, button_link)) = button {
+        parts.insert(
+            "button_title".to_string(),
+            substitute(button_title, variables, true),
+        );
+        parts.insert("button_link".to_string(), escape_html(button_link));
+
+        include_str!("button_notif.html")
+    } else {
+        include_str!("auth_notif.html")
+    };
+
+    RenderedEmail {
+        subject: substitute(&content.subject, variables, false),
+        body: substitute(skeleton, &parts, false),
+    }
+}
+
+/// Renders a template in the closest available locale to the given one
+pub async fn render_email<'a, E>(
+    template: EmailTemplate,
+    locale: &str,
+    variables: &HashMap<String, String>,
+    exec: E,
+) -> Result<RenderedEmail, MailError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let content = get_template_content(template, locale, exec).await?;
+
+    Ok(render_template(&content, variables))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitute_is_single_pass() {
+        let variables = HashMap::from([
+            ("project".to_string(), "{{ status }}".to_string()),
+            ("status".to_string(), "<b>Approved</b>".to_string()),
+        ]);
+
+        assert_eq!(
+            substitute(
+                "{{ project }} is {{ status }} {{ unknown }}",
+                &variables,
+                true
+            ),
+            "{{ status }} is &lt;b&gt;Approved&lt;/b&gt; {{ unknown }}"
+        );
+    }
+}
@@ -13,6 +13,8 @@ pub use validate::{check_is_moderator_from_headers, get_user_from_headers};
 
 use crate::file_hosting::FileHostingError;
 use crate::models::error::ApiError;
+use crate::routes::ErrorCode;
+use crate::util::circuit_breaker::CircuitOpen;
 use actix_web::http::StatusCode;
 use actix_web::HttpResponse;
 use thiserror::Error;
@@ -47,6 +49,10 @@ pub enum AuthenticationError {
     SocketError,
     #[error("Invalid callback URL specified")]
     Url,
+    #[error("{0}")]
+    Unavailable(#[from] CircuitOpen),
+    #[error("Impersonation sessions can only be used to read")]
+    ReadOnlySession,
 }
 
 impl actix_web::ResponseError for AuthenticationError {
@@ -66,6 +72,8 @@ impl actix_web::ResponseError for AuthenticationError {
             AuthenticationError::FileHosting(..) => StatusCode::INTERNAL_SERVER_ERROR,
             AuthenticationError::DuplicateUser => StatusCode::BAD_REQUEST,
             AuthenticationError::SocketError => StatusCode::BAD_REQUEST,
+            AuthenticationError::Unavailable(..) => StatusCode::SERVICE_UNAVAILABLE,
+            AuthenticationError::ReadOnlySession => StatusCode::UNAUTHORIZED,
         }
     }
 
@@ -73,27 +81,34 @@ impl actix_web::ResponseError for AuthenticationError {
         HttpResponse::build(self.status_code()).json(ApiError {
             error: self.error_name(),
             description: &self.to_string(),
+            fields: Vec::new(),
         })
     }
 }
 
 impl AuthenticationError {
     pub fn error_name(&self) -> &'static str {
+        self.code().as_str()
+    }
+
+    pub fn code(&self) -> ErrorCode {
         match self {
-            AuthenticationError::Env(..) => "environment_error",
-            AuthenticationError::Sqlx(..) => "database_error",
-            AuthenticationError::Database(..) => "database_error",
-            AuthenticationError::SerDe(..) => "invalid_input",
-            AuthenticationError::Reqwest(..) => "network_error",
-            AuthenticationError::InvalidCredentials => "invalid_credentials",
-            AuthenticationError::Decoding(..) => "decoding_error",
-            AuthenticationError::Mail(..) => "mail_error",
-            AuthenticationError::InvalidAuthMethod => "invalid_auth_method",
-            AuthenticationError::InvalidClientId => "invalid_client_id",
-            AuthenticationError::Url => "url_error",
-            AuthenticationError::FileHosting(..) => "file_hosting",
-            AuthenticationError::DuplicateUser => "duplicate_user",
-            AuthenticationError::SocketError => "socket",
+            AuthenticationError::Env(..) => ErrorCode::EnvironmentError,
+            AuthenticationError::Sqlx(..) => ErrorCode::DatabaseError,
+            AuthenticationError::Database(..) => ErrorCode::DatabaseError,
+            AuthenticationError::SerDe(..) => ErrorCode::InvalidInput,
+            AuthenticationError::Reqwest(..) => ErrorCode::NetworkError,
+            AuthenticationError::InvalidCredentials => ErrorCode::InvalidCredentials,
+            AuthenticationError::Decoding(..) => ErrorCode::DecodingError,
+            AuthenticationError::Mail(..) => ErrorCode::MailError,
+            AuthenticationError::InvalidAuthMethod => ErrorCode::InvalidAuthMethod,
+            AuthenticationError::InvalidClientId => ErrorCode::InvalidClientId,
+            AuthenticationError::Url => ErrorCode::UrlError,
+            AuthenticationError::FileHosting(..) => ErrorCode::FileHosting,
+            AuthenticationError::DuplicateUser => ErrorCode::DuplicateUser,
+            AuthenticationError::SocketError => ErrorCode::SocketError,
+            AuthenticationError::Unavailable(..) => ErrorCode::ServiceUnavailable,
+            AuthenticationError::ReadOnlySession => ErrorCode::Unauthorized,
         }
     }
 }
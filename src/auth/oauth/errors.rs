@@ -61,6 +61,7 @@ impl actix_web::ResponseError for OAuthError {
             OAuthErrorType::AuthenticationError(_)
             | OAuthErrorType::FailedScopeParse(_)
             | OAuthErrorType::ScopesTooBroad
+            | OAuthErrorType::UnverifiedClientScopes
             | OAuthErrorType::AccessDenied => {
                 if self.valid_redirect_uri.is_some() {
                     StatusCode::OK
@@ -101,6 +102,7 @@ impl actix_web::ResponseError for OAuthError {
             HttpResponse::build(self.status_code()).json(ApiError {
                 error: &self.error_type.error_name(),
                 description: &self.error_type.to_string(),
+                fields: Vec::new(),
             })
         }
     }
@@ -122,6 +124,8 @@ pub enum OAuthErrorType {
         "The provided scope requested scopes broader than the developer app is configured with"
     )]
     ScopesTooBroad,
+    #[error("The provided scope requested sensitive scopes, which only verified developer apps can request")]
+    UnverifiedClientScopes,
     #[error("The provided flow id was invalid")]
     InvalidAcceptFlowId,
     #[error("The provided client id was invalid")]
@@ -164,7 +168,9 @@ impl OAuthErrorType {
             }
             Self::AuthenticationError(_) | Self::InvalidAcceptFlowId => "server_error",
             Self::RedirectUriChanged(_) | Self::MalformedId(_) => "invalid_request",
-            Self::FailedScopeParse(_) | Self::ScopesTooBroad => "invalid_scope",
+            Self::FailedScopeParse(_) | Self::ScopesTooBroad | Self::UnverifiedClientScopes => {
+                "invalid_scope"
+            }
             Self::InvalidClientId(_) | Self::ClientAuthenticationFailed => "invalid_client",
             Self::InvalidAuthCode | Self::OnlySupportsAuthorizationCodeGrant(_) => "invalid_grant",
             Self::UnauthorizedClient => "unauthorized_client",
@@ -53,6 +53,7 @@ pub struct OAuthClientAccessRequest {
     pub client_id: OAuthClientId,
     pub client_name: String,
     pub client_icon: Option<String>,
+    pub client_verified: bool,
     pub requested_scopes: Scopes,
 }
 
@@ -84,18 +85,22 @@ pub async fn init_oauth(
             client.id,
         )?;
 
-        let requested_scopes = oauth_info
-            .scope
-            .as_ref()
-            .map_or(Ok(client.max_scopes), |s| {
-                Scopes::parse_from_oauth_scopes(s).map_err(|e| {
-                    OAuthError::redirect(
-                        OAuthErrorType::FailedScopeParse(e),
-                        &oauth_info.state,
-                        &redirect_uri,
-                    )
-                })
-            })?;
+        // Apps have to be verified before they can request sensitive scopes
+        let allowed_scopes = if client.verified {
+            client.max_scopes
+        } else {
+            client.max_scopes - Scopes::sensitive()
+        };
+
+        let requested_scopes = oauth_info.scope.as_ref().map_or(Ok(allowed_scopes), |s| {
+            Scopes::parse_from_oauth_scopes(s).map_err(|e| {
+                OAuthError::redirect(
+                    OAuthErrorType::FailedScopeParse(e),
+                    &oauth_info.state,
+                    &redirect_uri,
+                )
+            })
+        })?;
 
         if !client.max_scopes.contains(requested_scopes) {
             return Err(OAuthError::redirect(
@@ -104,6 +109,13 @@ pub async fn init_oauth(
                 &redirect_uri,
             ));
         }
+        if !allowed_scopes.contains(requested_scopes) {
+            return Err(OAuthError::redirect(
+                OAuthErrorType::UnverifiedClientScopes,
+                &oauth_info.state,
+                &redirect_uri,
+            ));
+        }
 
         let existing_authorization =
             OAuthClientAuthorization::get(client.id, user.id.into(), &**pool)
@@ -143,6 +155,7 @@ pub async fn init_oauth(
                     client_id: client.id.into(),
                     client_name: client.name,
                     client_icon: client.icon_url,
+                    client_verified: client.verified,
                     flow_id,
                     requested_scopes,
                 };
@@ -264,6 +277,7 @@ pub async fn request_token(
                 last_used: None,
                 client_id,
                 user_id,
+                client_verified: false,
             }
             .insert(&mut *transaction)
             .await?;
@@ -6,7 +6,9 @@ use crate::models::pats::Scopes;
 use crate::models::users::{Role, User, UserId, UserPayoutData};
 use crate::queue::session::AuthQueue;
 use crate::routes::internal::session::get_session_metadata;
-use actix_web::HttpRequest;
+use crate::util::telemetry::record_user;
+use actix_web::http::Method;
+use actix_web::{HttpMessage, HttpRequest};
 use chrono::Utc;
 use reqwest::header::{HeaderValue, AUTHORIZATION};
 
@@ -48,12 +50,14 @@ where
         auth_providers.push(AuthProvider::PayPal)
     }
 
+    let balance = db_user.balance_money();
     let user = User {
         id: UserId::from(db_user.id),
         username: db_user.username,
         name: db_user.name,
         email: db_user.email,
         email_verified: Some(db_user.email_verified),
+        email_undeliverable: Some(db_user.email_undeliverable),
         avatar_url: db_user.avatar_url,
         bio: db_user.bio,
         created: db_user.created,
@@ -62,12 +66,14 @@ where
         auth_providers: Some(auth_providers),
         has_password: Some(db_user.password.is_some()),
         has_totp: Some(db_user.totp_secret.is_some()),
+        profile: None,
         github_id: None,
         payout_data: Some(UserPayoutData {
             paypal_address: db_user.paypal_email,
             paypal_country: db_user.paypal_country,
             venmo_handle: db_user.venmo_handle,
-            balance: db_user.balance,
+            stripe_payouts_enabled: db_user.stripe_payouts_enabled,
+            balance,
         }),
     };
 
@@ -82,6 +88,9 @@ where
     Ok((scopes, user))
 }
 
+/// Marks requests made with an impersonation session which were already recorded
+struct ImpersonationRecorded;
+
 pub async fn get_user_record_from_bearer_token<'a, 'b, E>(
     req: &HttpRequest,
     token: Option<&str>,
@@ -158,7 +167,44 @@ where
 
             session_queue.add_oauth_access_token(access_token.id).await;
 
-            user.map(|u| (access_token.scopes, u))
+            // Tokens of apps whose verification was revoked lose their sensitive scopes
+            let scopes = if access_token.client_verified {
+                access_token.scopes
+            } else {
+                access_token.scopes - Scopes::sensitive()
+            };
+
+            user.map(|u| (scopes, u))
+        }
+        Some(("mri", _)) => {
+            use crate::database::models::impersonation_item::ImpersonationSession;
+
+            if !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+                return Err(AuthenticationError::ReadOnlySession);
+            }
+
+            let session = ImpersonationSession::get_active(
+                &ImpersonationSession::hash_token(token),
+                executor,
+            )
+            .await?
+            .ok_or(AuthenticationError::InvalidCredentials)?;
+
+            // Requests are recorded once, however many times the user is read
+            if req.extensions().get::<ImpersonationRecorded>().is_none() {
+                ImpersonationSession::log_request(
+                    session.id,
+                    req.method().as_str(),
+                    req.path(),
+                    executor,
+                )
+                .await?;
+                req.extensions_mut().insert(ImpersonationRecorded);
+            }
+
+            let user = user_item::User::get_id(session.user_id, executor, redis).await?;
+
+            user.map(|u| (Scopes::impersonation(), u))
         }
         Some(("github", _)) | Some(("gho", _)) | Some(("ghp", _)) => {
             let user = AuthProvider::GitHub.get_user(token).await?;
@@ -175,6 +221,11 @@ where
         }
         _ => return Err(AuthenticationError::InvalidAuthMethod),
     };
+
+    if let Some((_, user)) = &possible_user {
+        record_user(req, UserId::from(user.id));
+    }
+
     Ok(possible_user)
 }
 
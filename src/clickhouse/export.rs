@@ -0,0 +1,124 @@
+use super::HTTP_CLIENT;
+use crate::{models::ids::ProjectId, routes::ApiError};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use clickhouse::error::Error as ClickhouseError;
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Parquet,
+}
+
+impl ExportFormat {
+    /// The ClickHouse output format generating this export
+    fn clickhouse_format(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSVWithNames",
+            ExportFormat::Parquet => "Parquet",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// Streams the daily views, downloads and playtime of a project, per version where the metric
+/// is recorded per version.
+///
+/// The clickhouse client only supports fetching typed rows, so this goes through ClickHouse's
+/// HTTP interface directly, over the clickhouse client's connections and with its credentials,
+/// and lets it encode the output, passing it through as it arrives.
+pub async fn export_project_metrics(
+    project: ProjectId,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    format: ExportFormat,
+) -> Result<impl Stream<Item = Result<Bytes, hyper::Error>>, ApiError> {
+    let query = format!(
+        "
+        SELECT
+            day,
+            version_id,
+            sum(views) AS views,
+            sum(downloads) AS downloads,
+            sum(playtime_seconds) AS playtime_seconds
+        FROM
+        (
+            SELECT toDate(recorded) AS day, toUInt64(0) AS version_id,
+                count(1) AS views, toUInt64(0) AS downloads, toUInt64(0) AS playtime_seconds
+            FROM views
+            WHERE project_id = {{project_id:UInt64}}
+                AND recorded BETWEEN {{start:Int64}} AND {{end:Int64}}
+            GROUP BY day
+
+            UNION ALL
+
+            SELECT toDate(recorded) AS day, version_id,
+                toUInt64(0) AS views, count(1) AS downloads, toUInt64(0) AS playtime_seconds
+            FROM downloads
+            WHERE project_id = {{project_id:UInt64}}
+                AND recorded BETWEEN {{start:Int64}} AND {{end:Int64}}
+            GROUP BY day, version_id
+
+            UNION ALL
+
+            SELECT toDate(recorded) AS day, version_id,
+                toUInt64(0) AS views, toUInt64(0) AS downloads, sum(seconds) AS playtime_seconds
+            FROM playtime
+            WHERE project_id = {{project_id:UInt64}}
+                AND recorded BETWEEN {{start:Int64}} AND {{end:Int64}}
+            GROUP BY day, version_id
+        )
+        GROUP BY day, version_id
+        ORDER BY day, version_id
+        FORMAT {}
+        ",
+        format.clickhouse_format()
+    );
+
+    let mut url = Url::parse(&dotenvy::var("CLICKHOUSE_URL")?)
+        .map_err(|err| ClickhouseError::InvalidParams(Box::new(err)))?;
+    url.query_pairs_mut()
+        .append_pair("database", &dotenvy::var("CLICKHOUSE_DATABASE")?)
+        .append_pair("param_project_id", &project.0.to_string())
+        .append_pair("param_start", &start_date.timestamp().to_string())
+        .append_pair("param_end", &end_date.timestamp().to_string());
+
+    let request = hyper::Request::post(url.as_str())
+        .header("X-ClickHouse-User", dotenvy::var("CLICKHOUSE_USER")?)
+        .header("X-ClickHouse-Key", dotenvy::var("CLICKHOUSE_PASSWORD")?)
+        .body(hyper::Body::from(query))
+        .map_err(|err| ClickhouseError::InvalidParams(Box::new(err)))?;
+
+    let response = HTTP_CLIENT
+        .request(request)
+        .await
+        .map_err(|err| ClickhouseError::Network(Box::new(err)))?;
+
+    if !response.status().is_success() {
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|err| ClickhouseError::Network(Box::new(err)))?;
+        return Err(
+            ClickhouseError::BadResponse(String::from_utf8_lossy(&body).to_string()).into(),
+        );
+    }
+
+    Ok(response.into_body())
+}
@@ -1,6 +1,10 @@
 use std::sync::Arc;
 
-use crate::{models::ids::ProjectId, routes::ApiError};
+use super::{Granularity, Metric};
+use crate::{
+    models::{analytics::AnalyticsDimension, ids::ProjectId},
+    routes::ApiError,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -11,6 +15,34 @@ pub struct ReturnIntervals {
     pub total: u64,
 }
 
+#[derive(clickhouse::Row, Serialize, Deserialize, Clone, Debug)]
+pub struct ReturnPlaytimeSessions {
+    pub time: u32,
+    pub id: u64,
+    pub active_instances: u64,
+    pub average_seconds: f64,
+}
+
+#[derive(clickhouse::Row, Serialize, Deserialize, Clone, Debug)]
+pub struct ReturnBreakdown {
+    pub value: String,
+    pub id: u64,
+    pub total: u64,
+}
+
+#[derive(clickhouse::Row, Serialize, Deserialize, Clone, Debug)]
+pub struct ReturnTotal {
+    pub id: u64,
+    pub total: u64,
+}
+
+#[derive(clickhouse::Row, Serialize, Deserialize, Clone, Debug)]
+pub struct ReturnPair {
+    pub id: u64,
+    pub other_id: u64,
+    pub total: u64,
+}
+
 #[derive(clickhouse::Row, Serialize, Deserialize, Clone, Debug)]
 pub struct ReturnCountry {
     pub country: String,
@@ -18,8 +50,7 @@ pub struct ReturnCountry {
     pub total: u64,
 }
 
-// Only one of project_id or version_id should be used
-// Fetches playtimes as a Vec of ReturnPlaytimes
+// Fetches playtimes as a Vec of ReturnIntervals
 pub async fn fetch_playtimes(
     projects: Vec<ProjectId>,
     start_date: DateTime<Utc>,
@@ -27,14 +58,33 @@ pub async fn fetch_playtimes(
     resolution_minute: u32,
     client: Arc<clickhouse::Client>,
 ) -> Result<Vec<ReturnIntervals>, ApiError> {
+    fetch_intervals(
+        Metric::Playtime,
+        projects,
+        start_date,
+        end_date,
+        resolution_minute,
+        client,
+    )
+    .await
+}
+
+// Fetches the daily active instances and average session length of projects
+pub async fn fetch_playtime_sessions(
+    projects: Vec<ProjectId>,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    client: Arc<clickhouse::Client>,
+) -> Result<Vec<ReturnPlaytimeSessions>, ApiError> {
     let query = client
         .query(
             "
             SELECT
-                toUnixTimestamp(toStartOfInterval(recorded, toIntervalMinute(?))) AS time,
+                toUnixTimestamp(toStartOfDay(recorded)) AS time,
                 project_id AS id,
-                SUM(seconds) AS total
-            FROM playtime
+                uniq(instance) AS active_instances,
+                avg(seconds) AS average_seconds
+            FROM playtime_sessions
             WHERE recorded BETWEEN ? AND ?
             AND project_id IN ?
             GROUP BY
@@ -42,7 +92,6 @@ pub async fn fetch_playtimes(
                 project_id
             ",
         )
-        .bind(resolution_minute)
         .bind(start_date.timestamp())
         .bind(end_date.timestamp())
         .bind(projects.iter().map(|x| x.0).collect::<Vec<_>>());
@@ -50,7 +99,7 @@ pub async fn fetch_playtimes(
     Ok(query.fetch_all().await?)
 }
 
-// Fetches views as a Vec of ReturnViews
+// Fetches views as a Vec of ReturnIntervals
 pub async fn fetch_views(
     projects: Vec<ProjectId>,
     start_date: DateTime<Utc>,
@@ -58,53 +107,124 @@ pub async fn fetch_views(
     resolution_minutes: u32,
     client: Arc<clickhouse::Client>,
 ) -> Result<Vec<ReturnIntervals>, ApiError> {
-    let query = client
-        .query(
-            "
-            SELECT  
-                toUnixTimestamp(toStartOfInterval(recorded, toIntervalMinute(?))) AS time,
-                project_id AS id,
-                count(1) AS total
-            FROM views
-            WHERE recorded BETWEEN ? AND ?
-                  AND project_id IN ?
-            GROUP BY
-            time, project_id
-            ",
-        )
-        .bind(resolution_minutes)
-        .bind(start_date.timestamp())
-        .bind(end_date.timestamp())
-        .bind(projects.iter().map(|x| x.0).collect::<Vec<_>>());
-
-    Ok(query.fetch_all().await?)
+    fetch_intervals(
+        Metric::Views,
+        projects,
+        start_date,
+        end_date,
+        resolution_minutes,
+        client,
+    )
+    .await
 }
 
-// Fetches downloads as a Vec of ReturnDownloads
+// Fetches downloads as a Vec of ReturnIntervals
 pub async fn fetch_downloads(
     projects: Vec<ProjectId>,
     start_date: DateTime<Utc>,
     end_date: DateTime<Utc>,
     resolution_minutes: u32,
     client: Arc<clickhouse::Client>,
+) -> Result<Vec<ReturnIntervals>, ApiError> {
+    fetch_intervals(
+        Metric::Downloads,
+        projects,
+        start_date,
+        end_date,
+        resolution_minutes,
+        client,
+    )
+    .await
+}
+
+// Fetches a metric per project and interval, from the raw events or the coarsest rollup
+// fitting the resolution
+async fn fetch_intervals(
+    metric: Metric,
+    projects: Vec<ProjectId>,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    resolution_minutes: u32,
+    client: Arc<clickhouse::Client>,
+) -> Result<Vec<ReturnIntervals>, ApiError> {
+    let granularity = Granularity::select(resolution_minutes, start_date);
+
+    // The metric and tables come from fixed lists, so they are safe to format in
+    let query = if let Some(table) = granularity.table() {
+        client
+            .query(&format!(
+                "
+                SELECT
+                    toUnixTimestamp(toStartOfInterval(bucket, toIntervalMinute(?))) AS time,
+                    project_id AS id,
+                    SUM(total) AS total
+                FROM {table} {final_modifier}
+                WHERE metric = ?
+                      AND bucket BETWEEN ? AND ?
+                      AND project_id IN ?
+                GROUP BY time, project_id
+                ",
+                final_modifier = if granularity.needs_final() {
+                    "FINAL"
+                } else {
+                    ""
+                },
+            ))
+            .bind(resolution_minutes)
+            .bind(metric.as_str())
+    } else {
+        client
+            .query(&format!(
+                "
+                SELECT
+                    toUnixTimestamp(toStartOfInterval(recorded, toIntervalMinute(?))) AS time,
+                    project_id AS id,
+                    {aggregate} AS total
+                FROM {table}
+                WHERE recorded BETWEEN ? AND ?
+                      AND project_id IN ?
+                GROUP BY time, project_id
+                ",
+                aggregate = metric.raw_aggregate(),
+                table = metric.raw_table(),
+            ))
+            .bind(resolution_minutes)
+    };
+
+    Ok(query
+        .bind(start_date.timestamp())
+        .bind(end_date.timestamp())
+        .bind(projects.iter().map(|x| x.0).collect::<Vec<_>>())
+        .fetch_all()
+        .await?)
+}
+
+// Fetches the downloads of each version of a project as a Vec of ReturnIntervals, with the
+// version as the id
+pub async fn fetch_version_downloads(
+    project: ProjectId,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    resolution_minutes: u32,
+    client: Arc<clickhouse::Client>,
 ) -> Result<Vec<ReturnIntervals>, ApiError> {
     let query = client
         .query(
             "
-            SELECT  
+            SELECT
                 toUnixTimestamp(toStartOfInterval(recorded, toIntervalMinute(?))) AS time,
-                project_id as id,
+                version_id AS id,
                 count(1) AS total
             FROM downloads
             WHERE recorded BETWEEN ? AND ?
-                  AND project_id IN ?
-            GROUP BY time, project_id
+                  AND project_id = ?
+            GROUP BY time, version_id
             ",
         )
         .bind(resolution_minutes)
         .bind(start_date.timestamp())
         .bind(end_date.timestamp())
-        .bind(projects.iter().map(|x| x.0).collect::<Vec<_>>());
+        .bind(project.0);
 
     Ok(query.fetch_all().await?)
 }
@@ -162,3 +282,150 @@ pub async fn fetch_countries_views(
 
     Ok(query.fetch_all().await?)
 }
+
+pub async fn fetch_downloads_breakdown(
+    projects: Vec<ProjectId>,
+    dimension: AnalyticsDimension,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    client: Arc<clickhouse::Client>,
+) -> Result<Vec<ReturnBreakdown>, ApiError> {
+    fetch_breakdown(
+        "downloads",
+        projects,
+        dimension,
+        start_date,
+        end_date,
+        client,
+    )
+    .await
+}
+
+pub async fn fetch_views_breakdown(
+    projects: Vec<ProjectId>,
+    dimension: AnalyticsDimension,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    client: Arc<clickhouse::Client>,
+) -> Result<Vec<ReturnBreakdown>, ApiError> {
+    fetch_breakdown("views", projects, dimension, start_date, end_date, client).await
+}
+
+async fn fetch_breakdown(
+    table: &'static str,
+    projects: Vec<ProjectId>,
+    dimension: AnalyticsDimension,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    client: Arc<clickhouse::Client>,
+) -> Result<Vec<ReturnBreakdown>, ApiError> {
+    // Both the table and column come from fixed lists, so they are safe to format in
+    let query = client
+        .query(&format!(
+            "
+            SELECT
+                {column} AS value,
+                project_id AS id,
+                count(1) AS total
+            FROM {table}
+            WHERE recorded BETWEEN ? AND ? AND project_id IN ?
+            GROUP BY
+                value,
+                project_id
+            ",
+            column = dimension.column(),
+        ))
+        .bind(start_date.timestamp())
+        .bind(end_date.timestamp())
+        .bind(projects.iter().map(|x| x.0).collect::<Vec<_>>());
+
+    Ok(query.fetch_all().await?)
+}
+
+// Fetches the projects a user downloaded most since a date, with the project as the id
+pub async fn fetch_user_downloaded_projects(
+    user_id: u64,
+    start_date: DateTime<Utc>,
+    limit: u32,
+    client: Arc<clickhouse::Client>,
+) -> Result<Vec<ReturnTotal>, ApiError> {
+    let query = client
+        .query(
+            "
+            SELECT
+                project_id AS id,
+                count(1) AS total
+            FROM downloads
+            WHERE recorded >= ? AND user_id = ? AND project_id != 0
+            GROUP BY project_id
+            ORDER BY total DESC
+            LIMIT ?
+            ",
+        )
+        .bind(start_date.timestamp())
+        .bind(user_id)
+        .bind(limit);
+
+    Ok(query.fetch_all().await?)
+}
+
+// Fetches the most downloaded projects since a date, with the project as the id
+pub async fn fetch_top_downloaded_projects(
+    start_date: DateTime<Utc>,
+    limit: u32,
+    client: Arc<clickhouse::Client>,
+) -> Result<Vec<ReturnTotal>, ApiError> {
+    let query = client
+        .query(
+            "
+            SELECT
+                project_id AS id,
+                count(1) AS total
+            FROM downloads
+            WHERE recorded >= ? AND project_id != 0
+            GROUP BY project_id
+            ORDER BY total DESC
+            LIMIT ?
+            ",
+        )
+        .bind(start_date.timestamp())
+        .bind(limit);
+
+    Ok(query.fetch_all().await?)
+}
+
+// Fetches how many users downloaded both projects of each pair since a date, for pairs
+// downloaded together by at least `min_users` users. Each pair is returned both ways
+pub async fn fetch_co_downloads(
+    start_date: DateTime<Utc>,
+    min_users: u64,
+    client: Arc<clickhouse::Client>,
+) -> Result<Vec<ReturnPair>, ApiError> {
+    let query = client
+        .query(
+            "
+            SELECT
+                a.project_id AS id,
+                b.project_id AS other_id,
+                count(1) AS total
+            FROM
+            (
+                SELECT DISTINCT user_id, project_id FROM downloads
+                WHERE recorded >= ? AND user_id != 0 AND project_id != 0
+            ) a
+            INNER JOIN
+            (
+                SELECT DISTINCT user_id, project_id FROM downloads
+                WHERE recorded >= ? AND user_id != 0 AND project_id != 0
+            ) b ON a.user_id = b.user_id
+            WHERE a.project_id != b.project_id
+            GROUP BY id, other_id
+            HAVING total >= ?
+            ",
+        )
+        .bind(start_date.timestamp())
+        .bind(start_date.timestamp())
+        .bind(min_users);
+
+    Ok(query.fetch_all().await?)
+}
@@ -1,9 +1,27 @@
 use hyper::client::HttpConnector;
 use hyper_tls::{native_tls, HttpsConnector};
+use lazy_static::lazy_static;
 
+mod export;
 mod fetch;
+mod rollups;
 
+pub use export::*;
 pub use fetch::*;
+pub use rollups::*;
+
+lazy_static! {
+    /// The HTTP client ClickHouse is reached through. It is shared by the typed client and the
+    /// queries going through ClickHouse's HTTP interface directly, so they share connections
+    static ref HTTP_CLIENT: hyper::Client<HttpsConnector<HttpConnector>> = {
+        let mut http_connector = HttpConnector::new();
+        http_connector.enforce_http(false); // allow https URLs
+
+        let tls_connector = native_tls::TlsConnector::builder().build().unwrap().into();
+        let https_connector = HttpsConnector::from((http_connector, tls_connector));
+        hyper::client::Client::builder().build(https_connector)
+    };
+}
 
 pub async fn init_client() -> clickhouse::error::Result<clickhouse::Client> {
     init_client_with_database(&dotenvy::var("CLICKHOUSE_DATABASE").unwrap()).await
@@ -12,19 +30,10 @@ pub async fn init_client() -> clickhouse::error::Result<clickhouse::Client> {
 pub async fn init_client_with_database(
     database: &str,
 ) -> clickhouse::error::Result<clickhouse::Client> {
-    let client = {
-        let mut http_connector = HttpConnector::new();
-        http_connector.enforce_http(false); // allow https URLs
-
-        let tls_connector = native_tls::TlsConnector::builder().build().unwrap().into();
-        let https_connector = HttpsConnector::from((http_connector, tls_connector));
-        let hyper_client = hyper::client::Client::builder().build(https_connector);
-
-        clickhouse::Client::with_http_client(hyper_client)
-            .with_url(dotenvy::var("CLICKHOUSE_URL").unwrap())
-            .with_user(dotenvy::var("CLICKHOUSE_USER").unwrap())
-            .with_password(dotenvy::var("CLICKHOUSE_PASSWORD").unwrap())
-    };
+    let client = clickhouse::Client::with_http_client(HTTP_CLIENT.clone())
+        .with_url(dotenvy::var("CLICKHOUSE_URL").unwrap())
+        .with_user(dotenvy::var("CLICKHOUSE_USER").unwrap())
+        .with_password(dotenvy::var("CLICKHOUSE_PASSWORD").unwrap());
 
     client
         .query(&format!("CREATE DATABASE IF NOT EXISTS {database}"))
@@ -43,9 +52,14 @@ pub async fn init_client_with_database(
                 user_id UInt64,
                 project_id UInt64,
 
-                ip IPv6,
                 country String,
-                user_agent String,
+                continent String,
+                platform String,
+                os String,
+                referrer String,
+                source String,
+                medium String,
+                campaign String,
                 headers Array(Tuple(String, String)),
             )
             ENGINE = MergeTree()
@@ -68,9 +82,14 @@ pub async fn init_client_with_database(
                 project_id UInt64,
                 version_id UInt64,
 
-                ip IPv6,
                 country String,
-                user_agent String,
+                continent String,
+                platform String,
+                os String,
+                referrer String,
+                source String,
+                medium String,
+                campaign String,
                 headers Array(Tuple(String, String)),
             )
             ENGINE = MergeTree()
@@ -80,6 +99,37 @@ pub async fn init_client_with_database(
         .execute()
         .await?;
 
+    // Columns added after the tables were first created
+    for table in ["views", "downloads"] {
+        for column in [
+            "continent",
+            "platform",
+            "os",
+            "referrer",
+            "source",
+            "medium",
+            "campaign",
+        ] {
+            client
+                .query(&format!(
+                    "ALTER TABLE {database}.{table} ADD COLUMN IF NOT EXISTS {column} String"
+                ))
+                .execute()
+                .await?;
+        }
+
+        // Raw IPs and user agents were stored before they were reduced to coarse dimensions
+        // at ingest, and are dropped along with the data already recorded in them
+        for column in ["ip", "user_agent"] {
+            client
+                .query(&format!(
+                    "ALTER TABLE {database}.{table} DROP COLUMN IF EXISTS {column}"
+                ))
+                .execute()
+                .await?;
+        }
+    }
+
     client
         .query(&format!(
             "
@@ -103,5 +153,29 @@ pub async fn init_client_with_database(
         .execute()
         .await?;
 
+    client
+        .query(&format!(
+            "
+            CREATE TABLE IF NOT EXISTS {database}.playtime_sessions
+            (
+                recorded DateTime64(4),
+                seconds UInt64,
+
+                instance UInt64,
+                project_id UInt64,
+                version_id UInt64,
+
+                loader String,
+                game_version String,
+            )
+            ENGINE = MergeTree()
+            PRIMARY KEY (project_id, recorded)
+            "
+        ))
+        .execute()
+        .await?;
+
+    init_rollups(&client, database).await?;
+
     Ok(client.with_database(database))
 }
@@ -0,0 +1,373 @@
+use crate::util::env::parse_var;
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+
+/// Raw events are always kept for at least this many days, as payouts and the breakdown
+/// endpoints are computed from them
+const MIN_RAW_RETENTION_DAYS: u32 = 30;
+
+/// Tables holding raw events, which raw data retention applies to
+const RAW_TABLES: &[&str] = &["views", "downloads", "playtime", "playtime_sessions"];
+
+/// The time series metrics which are rolled up
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Metric {
+    Views,
+    Downloads,
+    Playtime,
+}
+
+impl Metric {
+    pub fn iterator() -> impl Iterator<Item = Metric> {
+        [Metric::Views, Metric::Downloads, Metric::Playtime]
+            .iter()
+            .copied()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Metric::Views => "views",
+            Metric::Downloads => "downloads",
+            Metric::Playtime => "playtime",
+        }
+    }
+
+    /// The raw table the metric is computed from
+    pub fn raw_table(&self) -> &'static str {
+        match self {
+            Metric::Views => "views",
+            Metric::Downloads => "downloads",
+            Metric::Playtime => "playtime",
+        }
+    }
+
+    /// The version column of the raw table. Views are not recorded per version
+    fn version_column(&self) -> &'static str {
+        match self {
+            Metric::Views => "toUInt64(0)",
+            Metric::Downloads | Metric::Playtime => "version_id",
+        }
+    }
+
+    /// The aggregate computing the metric from raw rows
+    pub fn raw_aggregate(&self) -> &'static str {
+        match self {
+            Metric::Views | Metric::Downloads => "count(1)",
+            Metric::Playtime => "SUM(seconds)",
+        }
+    }
+}
+
+/// The granularity time series data is stored and queried at. Raw events are rolled up into
+/// hourly buckets as they are inserted, and scheduled jobs roll those into daily and monthly
+/// buckets.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Granularity {
+    Raw,
+    Hourly,
+    Daily,
+    Monthly,
+}
+
+impl Granularity {
+    pub fn table(&self) -> Option<&'static str> {
+        match self {
+            Granularity::Raw => None,
+            Granularity::Hourly => Some("analytics_hourly"),
+            Granularity::Daily => Some("analytics_daily"),
+            Granularity::Monthly => Some("analytics_monthly"),
+        }
+    }
+
+    /// Daily and monthly rollups are replaced when recomputed, so they must be queried with
+    /// FINAL to not count replaced rows which have not been merged away yet
+    pub fn needs_final(&self) -> bool {
+        matches!(self, Granularity::Daily | Granularity::Monthly)
+    }
+
+    fn coarser(&self) -> Granularity {
+        match self {
+            Granularity::Raw => Granularity::Hourly,
+            Granularity::Hourly => Granularity::Daily,
+            Granularity::Daily | Granularity::Monthly => Granularity::Monthly,
+        }
+    }
+
+    /// Selects the coarsest granularity whose buckets fit evenly into the requested
+    /// resolution, falling back to coarser data if the finer data starting at `start_date`
+    /// has already expired
+    pub fn select(resolution_minutes: u32, start_date: DateTime<Utc>) -> Granularity {
+        let mut granularity = if resolution_minutes >= 28 * 24 * 60 {
+            Granularity::Monthly
+        } else if resolution_minutes % (24 * 60) == 0 {
+            Granularity::Daily
+        } else if resolution_minutes % 60 == 0 {
+            Granularity::Hourly
+        } else {
+            Granularity::Raw
+        };
+
+        let retention = Retention::from_env();
+        while !retention.covers(granularity, start_date) {
+            granularity = granularity.coarser();
+        }
+
+        granularity
+    }
+}
+
+/// How many days of each granularity are kept. `None` keeps data forever
+#[derive(Copy, Clone, Debug)]
+pub struct Retention {
+    pub raw_days: Option<u32>,
+    pub hourly_days: Option<u32>,
+    pub daily_days: Option<u32>,
+}
+
+impl Retention {
+    pub fn from_env() -> Self {
+        let days = |var| parse_var::<u32>(var).filter(|x| *x > 0);
+
+        Retention {
+            raw_days: days("ANALYTICS_RAW_RETENTION_DAYS").map(|x| x.max(MIN_RAW_RETENTION_DAYS)),
+            hourly_days: days("ANALYTICS_HOURLY_RETENTION_DAYS"),
+            daily_days: days("ANALYTICS_DAILY_RETENTION_DAYS"),
+        }
+    }
+
+    fn days(&self, granularity: Granularity) -> Option<u32> {
+        match granularity {
+            Granularity::Raw => self.raw_days,
+            Granularity::Hourly => self.hourly_days,
+            Granularity::Daily => self.daily_days,
+            Granularity::Monthly => None,
+        }
+    }
+
+    fn covers(&self, granularity: Granularity, start_date: DateTime<Utc>) -> bool {
+        self.days(granularity)
+            .map(|days| start_date >= Utc::now() - Duration::days(days as i64))
+            .unwrap_or(true)
+    }
+}
+
+async fn is_empty(client: &clickhouse::Client, table: &str) -> clickhouse::error::Result<bool> {
+    let count = client
+        .query(&format!("SELECT count(1) FROM {table}"))
+        .fetch_one::<u64>()
+        .await?;
+
+    Ok(count == 0)
+}
+
+/// Creates the rollup tables and the materialized views feeding the hourly rollups, and
+/// applies the configured retention
+pub async fn init_rollups(
+    client: &clickhouse::Client,
+    database: &str,
+) -> clickhouse::error::Result<()> {
+    client
+        .query(&format!(
+            "
+            CREATE TABLE IF NOT EXISTS {database}.analytics_hourly
+            (
+                metric LowCardinality(String),
+                bucket DateTime,
+                project_id UInt64,
+                version_id UInt64,
+                total UInt64,
+            )
+            ENGINE = SummingMergeTree(total)
+            ORDER BY (metric, project_id, bucket, version_id)
+            "
+        ))
+        .execute()
+        .await?;
+
+    for table in ["analytics_daily", "analytics_monthly"] {
+        client
+            .query(&format!(
+                "
+                CREATE TABLE IF NOT EXISTS {database}.{table}
+                (
+                    metric LowCardinality(String),
+                    bucket DateTime,
+                    project_id UInt64,
+                    version_id UInt64,
+                    total UInt64,
+                    updated DateTime,
+                )
+                ENGINE = ReplacingMergeTree(updated)
+                ORDER BY (metric, project_id, bucket, version_id)
+                "
+            ))
+            .execute()
+            .await?;
+    }
+
+    let client = client.clone().with_database(database);
+    let backfill = is_empty(&client, "analytics_hourly").await?;
+
+    for metric in Metric::iterator() {
+        let select = format!(
+            "
+            SELECT
+                '{metric}' AS metric,
+                toStartOfHour(recorded) AS bucket,
+                project_id,
+                {version} AS version_id,
+                {aggregate} AS total
+            FROM {database}.{table}
+            GROUP BY bucket, project_id, version_id
+            ",
+            metric = metric.as_str(),
+            version = metric.version_column(),
+            aggregate = metric.raw_aggregate(),
+            table = metric.raw_table(),
+        );
+
+        // Events recorded before the rollups existed are only picked up once
+        if backfill {
+            client
+                .query(&format!("INSERT INTO {database}.analytics_hourly {select}"))
+                .execute()
+                .await?;
+        }
+
+        client
+            .query(&format!(
+                "
+                CREATE MATERIALIZED VIEW IF NOT EXISTS {database}.{metric}_hourly_mv
+                TO {database}.analytics_hourly
+                AS {select}
+                ",
+                metric = metric.as_str(),
+            ))
+            .execute()
+            .await?;
+    }
+
+    let retention = Retention::from_env();
+    if parse_var::<u32>("ANALYTICS_RAW_RETENTION_DAYS")
+        .map(|x| x > 0 && x < MIN_RAW_RETENTION_DAYS)
+        .unwrap_or(false)
+    {
+        warn!(
+            "ANALYTICS_RAW_RETENTION_DAYS is below the minimum of {} days, using the minimum",
+            MIN_RAW_RETENTION_DAYS
+        );
+    }
+
+    for table in RAW_TABLES {
+        apply_retention(&client, database, table, "recorded", retention.raw_days).await?;
+    }
+    apply_retention(
+        &client,
+        database,
+        "analytics_hourly",
+        "bucket",
+        retention.hourly_days,
+    )
+    .await?;
+    apply_retention(
+        &client,
+        database,
+        "analytics_daily",
+        "bucket",
+        retention.daily_days,
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn apply_retention(
+    client: &clickhouse::Client,
+    database: &str,
+    table: &str,
+    column: &str,
+    days: Option<u32>,
+) -> clickhouse::error::Result<()> {
+    if let Some(days) = days {
+        client
+            .query(&format!(
+                "ALTER TABLE {database}.{table} MODIFY TTL toDateTime({column}) + INTERVAL {days} DAY"
+            ))
+            .execute()
+            .await?;
+    } else {
+        let engine = client
+            .query("SELECT engine_full FROM system.tables WHERE database = ? AND name = ?")
+            .bind(database)
+            .bind(table)
+            .fetch_one::<String>()
+            .await?;
+
+        // Removing a TTL from a table without one is an error
+        if engine.contains(" TTL ") {
+            client
+                .query(&format!("ALTER TABLE {database}.{table} REMOVE TTL"))
+                .execute()
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Rolls hourly data up into days and daily data into months. Only the current and previous
+/// day and month are recomputed, unless the rollup is empty, in which case everything is.
+/// Recomputed buckets replace the previous ones, so this can safely run repeatedly.
+pub async fn run_rollups(client: &clickhouse::Client) -> clickhouse::error::Result<()> {
+    let daily_filter = if is_empty(client, "analytics_daily").await? {
+        ""
+    } else {
+        "WHERE bucket >= toStartOfDay(now()) - INTERVAL 1 DAY"
+    };
+
+    client
+        .query(&format!(
+            "
+            INSERT INTO analytics_daily
+            SELECT
+                metric,
+                toStartOfDay(bucket) AS day,
+                project_id,
+                version_id,
+                SUM(total) AS total,
+                now() AS updated
+            FROM analytics_hourly
+            {daily_filter}
+            GROUP BY metric, day, project_id, version_id
+            "
+        ))
+        .execute()
+        .await?;
+
+    let monthly_filter = if is_empty(client, "analytics_monthly").await? {
+        ""
+    } else {
+        "WHERE bucket >= toDateTime(toStartOfMonth(now()) - INTERVAL 1 MONTH)"
+    };
+
+    client
+        .query(&format!(
+            "
+            INSERT INTO analytics_monthly
+            SELECT
+                metric,
+                toDateTime(toStartOfMonth(bucket)) AS month,
+                project_id,
+                version_id,
+                SUM(total) AS total,
+                now() AS updated
+            FROM analytics_daily FINAL
+            {monthly_filter}
+            GROUP BY metric, month, project_id, version_id
+            "
+        ))
+        .execute()
+        .await?;
+
+    Ok(())
+}
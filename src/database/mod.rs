@@ -6,3 +6,5 @@ pub use models::Project;
 pub use models::Version;
 pub use postgres_database::check_for_migrations;
 pub use postgres_database::connect;
+pub use postgres_database::connect_read_replica;
+pub use postgres_database::ReadOnlyPool;
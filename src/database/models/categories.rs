@@ -21,6 +21,8 @@ pub struct Category {
     pub project_type: String,
     pub icon: String,
     pub header: String,
+    /// The name of the category this one is nested under, of the same project type
+    pub parent: Option<String>,
 }
 
 pub struct ReportType {
@@ -101,12 +103,14 @@ impl Category {
         }
 
         let result = sqlx::query!(
-            "
-            SELECT c.id id, c.category category, c.icon icon, c.header category_header, pt.name project_type
+            r#"
+            SELECT c.id id, c.category category, c.icon icon, c.header category_header, pt.name project_type,
+            p.category "parent?"
             FROM categories c
             INNER JOIN project_types pt ON c.project_type = pt.id
+            LEFT JOIN categories p ON c.parent_id = p.id
             ORDER BY c.ordering, c.category
-            "
+            "#
         )
         .fetch_many(exec)
         .try_filter_map(|e| async {
@@ -115,7 +119,8 @@ impl Category {
                 category: c.category,
                 project_type: c.project_type,
                 icon: c.icon,
-                header: c.category_header
+                header: c.category_header,
+                parent: c.parent,
             }))
         })
         .try_collect::<Vec<Category>>()
@@ -127,6 +132,179 @@ impl Category {
 
         Ok(result)
     }
+
+    /// Creates a category, or updates the category with the same name and project type
+    pub async fn upsert(
+        category: &str,
+        project_type: ProjectTypeId,
+        icon: &str,
+        header: &str,
+        ordering: i64,
+        parent: Option<CategoryId>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<CategoryId, DatabaseError> {
+        let updated = sqlx::query!(
+            "
+            UPDATE categories
+            SET icon = $3, header = $4, ordering = $5, parent_id = $6
+            WHERE category = $1 AND project_type = $2
+            RETURNING id
+            ",
+            category,
+            project_type as ProjectTypeId,
+            icon,
+            header,
+            ordering,
+            parent.map(|x| x.0),
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
+
+        if let Some(updated) = updated {
+            return Ok(CategoryId(updated.id));
+        }
+
+        let id = sqlx::query!(
+            "
+            INSERT INTO categories (category, project_type, icon, header, ordering, parent_id)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id
+            ",
+            category,
+            project_type as ProjectTypeId,
+            icon,
+            header,
+            ordering,
+            parent.map(|x| x.0),
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+        .id;
+
+        Ok(CategoryId(id))
+    }
+
+    /// Gets the IDs of the categories a category is nested under, from its parent upwards
+    pub async fn get_ancestor_ids<'a, E>(
+        id: CategoryId,
+        exec: E,
+    ) -> Result<Vec<CategoryId>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            WITH RECURSIVE ancestors AS (
+                SELECT parent_id FROM categories WHERE id = $1
+                UNION
+                SELECT c.parent_id FROM categories c
+                INNER JOIN ancestors a ON c.id = a.parent_id
+            )
+            SELECT parent_id FROM ancestors
+            WHERE parent_id IS NOT NULL
+            ",
+            id as CategoryId,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(result
+            .into_iter()
+            .filter_map(|x| x.parent_id.map(CategoryId))
+            .collect())
+    }
+
+    pub async fn is_used<'a, E>(id: CategoryId, exec: E) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT EXISTS(SELECT 1 FROM mods_categories WHERE joining_category_id = $1)
+            ",
+            id as CategoryId,
+        )
+        .fetch_one(exec)
+        .await?;
+
+        Ok(result.exists.unwrap_or(false))
+    }
+
+    /// Deletes a category. Categories nested under it are moved up to its own parent, and
+    /// categories used by projects can't be deleted
+    pub async fn remove(
+        id: CategoryId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        sqlx::query!(
+            "
+            UPDATE categories
+            SET parent_id = (SELECT parent_id FROM categories WHERE id = $1)
+            WHERE parent_id = $1
+            ",
+            id as CategoryId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let result = sqlx::query!(
+            "
+            DELETE FROM categories
+            WHERE id = $1
+            ",
+            id as CategoryId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    pub async fn clear_cache(redis: &RedisPool) -> Result<(), DatabaseError> {
+        let mut redis = redis.connect().await?;
+
+        redis
+            .delete_many([(TAGS_NAMESPACE, Some("category".to_string()))])
+            .await?;
+
+        Ok(())
+    }
+
+    /// Gets the names of the categories each category is nested under, keyed by project type
+    /// and category name. Categories that aren't nested under any are left out
+    pub fn get_ancestors(categories: &[Category]) -> HashMap<(String, String), Vec<String>> {
+        let parents: HashMap<_, _> = categories
+            .iter()
+            .filter_map(|x| {
+                Some((
+                    (x.project_type.clone(), x.category.clone()),
+                    x.parent.clone()?,
+                ))
+            })
+            .collect();
+
+        parents
+            .keys()
+            .map(|(project_type, category)| {
+                let mut ancestors = Vec::new();
+                let mut current = category;
+                while let Some(parent) = parents.get(&(project_type.clone(), current.clone())) {
+                    // Parents can't form cycles, but a bad row shouldn't hang indexing
+                    if parent == category || ancestors.contains(parent) {
+                        break;
+                    }
+                    ancestors.push(parent.clone());
+                    current = parent;
+                }
+
+                ((project_type.clone(), category.clone()), ancestors)
+            })
+            .collect()
+    }
 }
 
 impl LinkPlatform {
@@ -2,8 +2,9 @@ use super::ids::*;
 use crate::database::models;
 use crate::database::models::DatabaseError;
 use crate::database::redis::RedisPool;
-use crate::models::collections::CollectionStatus;
+use crate::models::collections::{CollectionPermissions, CollectionStatus};
 use chrono::{DateTime, Utc};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 const COLLECTIONS_NAMESPACE: &str = "collections";
@@ -33,7 +34,19 @@ impl CollectionBuilder {
             icon_url: None,
             color: None,
             status: self.status,
+            items: self
+                .projects
+                .iter()
+                .enumerate()
+                .map(|(ordering, project_id)| CollectionEntry {
+                    project_id: *project_id,
+                    note: None,
+                    ordering: ordering as i64,
+                })
+                .collect(),
             projects: self.projects,
+            editors: Vec::new(),
+            follows: 0,
         };
         collection_struct.insert(transaction).await?;
 
@@ -51,7 +64,29 @@ pub struct Collection {
     pub icon_url: Option<String>,
     pub color: Option<u32>,
     pub status: CollectionStatus,
+    /// The projects in the collection, in order
     pub projects: Vec<ProjectId>,
+    /// The same projects as `projects`, with their notes
+    #[serde(default)]
+    pub items: Vec<CollectionEntry>,
+    #[serde(default)]
+    pub editors: Vec<CollectionEditor>,
+    #[serde(default)]
+    pub follows: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionEntry {
+    pub project_id: ProjectId,
+    pub note: Option<String>,
+    pub ordering: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionEditor {
+    pub user_id: UserId,
+    pub permissions: CollectionPermissions,
+    pub accepted: bool,
 }
 
 impl Collection {
@@ -81,16 +116,66 @@ impl Collection {
         .execute(&mut **transaction)
         .await?;
 
-        let (collection_ids, project_ids): (Vec<_>, Vec<_>) =
-            self.projects.iter().map(|p| (self.id.0, p.0)).unzip();
+        Self::set_projects(self.id, &self.projects, transaction).await?;
+
+        Ok(())
+    }
+
+    /// Sets the projects in the collection, in order. Notes of projects which stay in the
+    /// collection are kept
+    pub async fn set_projects(
+        id: CollectionId,
+        projects: &[ProjectId],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        // A project listed twice is kept at its first position
+        let project_ids = projects.iter().map(|x| x.0).unique().collect::<Vec<_>>();
+
+        sqlx::query!(
+            "
+            DELETE FROM collections_mods
+            WHERE collection_id = $1 AND NOT (mod_id = ANY($2))
+            ",
+            id as CollectionId,
+            &project_ids[..],
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let collection_ids = project_ids.iter().map(|_| id.0).collect::<Vec<_>>();
+        let orderings = (0..project_ids.len() as i64).collect::<Vec<_>>();
         sqlx::query!(
             "
-                INSERT INTO collections_mods (collection_id, mod_id)
-                SELECT * FROM UNNEST($1::bigint[], $2::bigint[])
-                ON CONFLICT DO NOTHING
+            INSERT INTO collections_mods (collection_id, mod_id, ordering)
+            SELECT * FROM UNNEST($1::bigint[], $2::bigint[], $3::bigint[])
+            ON CONFLICT (collection_id, mod_id) DO UPDATE
+            SET ordering = EXCLUDED.ordering
             ",
             &collection_ids[..],
             &project_ids[..],
+            &orderings[..],
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn edit_note(
+        id: CollectionId,
+        project_id: ProjectId,
+        note: Option<&str>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            UPDATE collections_mods
+            SET note = $3
+            WHERE collection_id = $1 AND mod_id = $2
+            ",
+            id as CollectionId,
+            project_id as ProjectId,
+            note,
         )
         .execute(&mut **transaction)
         .await?;
@@ -134,6 +219,184 @@ impl Collection {
         }
     }
 
+    /// Invites a user to edit the collection, or updates the permissions of an existing invite
+    pub async fn upsert_editor(
+        id: CollectionId,
+        user_id: UserId,
+        permissions: CollectionPermissions,
+        invited_by: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO collections_editors (collection_id, user_id, permissions, invited_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (collection_id, user_id) DO UPDATE
+            SET permissions = EXCLUDED.permissions
+            ",
+            id as CollectionId,
+            user_id as UserId,
+            permissions.bits() as i64,
+            invited_by as UserId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn edit_editor_permissions(
+        id: CollectionId,
+        user_id: UserId,
+        permissions: CollectionPermissions,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            UPDATE collections_editors
+            SET permissions = $3
+            WHERE collection_id = $1 AND user_id = $2
+            ",
+            id as CollectionId,
+            user_id as UserId,
+            permissions.bits() as i64,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn accept_editor(
+        id: CollectionId,
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            UPDATE collections_editors
+            SET accepted = TRUE
+            WHERE collection_id = $1 AND user_id = $2
+            ",
+            id as CollectionId,
+            user_id as UserId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_editor(
+        id: CollectionId,
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            DELETE FROM collections_editors
+            WHERE collection_id = $1 AND user_id = $2
+            ",
+            id as CollectionId,
+            user_id as UserId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Follows the collection, returning false if the user was already following it
+    pub async fn follow(
+        id: CollectionId,
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<bool, DatabaseError> {
+        let inserted = sqlx::query!(
+            "
+            INSERT INTO collection_follows (follower_id, collection_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            ",
+            user_id as UserId,
+            id as CollectionId,
+        )
+        .execute(&mut **transaction)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if inserted {
+            sqlx::query!(
+                "
+                UPDATE collections
+                SET follows = follows + 1
+                WHERE id = $1
+                ",
+                id as CollectionId,
+            )
+            .execute(&mut **transaction)
+            .await?;
+        }
+
+        Ok(inserted)
+    }
+
+    /// Unfollows the collection, returning false if the user wasn't following it
+    pub async fn unfollow(
+        id: CollectionId,
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<bool, DatabaseError> {
+        let deleted = sqlx::query!(
+            "
+            DELETE FROM collection_follows
+            WHERE follower_id = $1 AND collection_id = $2
+            ",
+            user_id as UserId,
+            id as CollectionId,
+        )
+        .execute(&mut **transaction)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if deleted {
+            sqlx::query!(
+                "
+                UPDATE collections
+                SET follows = follows - 1
+                WHERE id = $1
+                ",
+                id as CollectionId,
+            )
+            .execute(&mut **transaction)
+            .await?;
+        }
+
+        Ok(deleted)
+    }
+
+    pub async fn get_followers(
+        id: CollectionId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Vec<UserId>, DatabaseError> {
+        let followers = sqlx::query!(
+            "
+            SELECT follower_id FROM collection_follows
+            WHERE collection_id = $1
+            ",
+            id as CollectionId,
+        )
+        .fetch_all(&mut **transaction)
+        .await?
+        .into_iter()
+        .map(|x| UserId(x.follower_id))
+        .collect();
+
+        Ok(followers)
+    }
+
     pub async fn get<'a, 'b, E>(
         id: CollectionId,
         executor: E,
@@ -192,10 +455,12 @@ impl Collection {
                 "
                 SELECT c.id id, c.name name, c.description description,
                 c.icon_url icon_url, c.color color, c.created created, c.user_id user_id,
-                c.updated updated, c.status status,
-                ARRAY_AGG(DISTINCT cm.mod_id) filter (where cm.mod_id is not null) mods
+                c.updated updated, c.status status, c.follows follows,
+                JSONB_AGG(DISTINCT jsonb_build_object('project_id', cm.mod_id, 'note', cm.note, 'ordering', cm.ordering)) filter (where cm.mod_id is not null) items,
+                JSONB_AGG(DISTINCT jsonb_build_object('user_id', ce.user_id, 'permissions', ce.permissions, 'accepted', ce.accepted)) filter (where ce.user_id is not null) editors
                 FROM collections c
                 LEFT JOIN collections_mods cm ON cm.collection_id = c.id
+                LEFT JOIN collections_editors ce ON ce.collection_id = c.id
                 WHERE c.id = ANY($1)
                 GROUP BY c.id;
                 ",
@@ -206,6 +471,12 @@ impl Collection {
                 Ok(e.right().map(|m| {
                     let id = m.id;
 
+                    let mut items: Vec<CollectionEntry> = m
+                        .items
+                        .and_then(|x| serde_json::from_value(x).ok())
+                        .unwrap_or_default();
+                    items.sort_by_key(|x| (x.ordering, x.project_id.0));
+
                     Collection {
                         id: CollectionId(id),
                         user_id: UserId(m.user_id),
@@ -216,12 +487,13 @@ impl Collection {
                         created: m.created,
                         updated: m.updated,
                         status: CollectionStatus::from_string(&m.status),
-                        projects: m
-                            .mods
-                            .unwrap_or_default()
-                            .into_iter()
-                            .map(ProjectId)
-                            .collect(),
+                        projects: items.iter().map(|x| x.project_id).collect(),
+                        items,
+                        editors: m
+                            .editors
+                            .and_then(|x| serde_json::from_value(x).ok())
+                            .unwrap_or_default(),
+                        follows: m.follows,
                     }
                 }))
             })
@@ -0,0 +1,172 @@
+use super::ids::*;
+use super::DatabaseError;
+use crate::models::projects::VersionStatus;
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// A collection's projects pinned to specific versions at a point in time
+pub struct CollectionSnapshot {
+    pub id: CollectionSnapshotId,
+    pub collection_id: CollectionId,
+    pub name: Option<String>,
+    pub created_by: Option<UserId>,
+    pub created: DateTime<Utc>,
+    /// The pinned version of each project, in the collection's order at the time
+    pub versions: Vec<(ProjectId, VersionId)>,
+}
+
+impl CollectionSnapshot {
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO collection_snapshots (id, collection_id, name, created_by, created)
+            VALUES ($1, $2, $3, $4, $5)
+            ",
+            self.id as CollectionSnapshotId,
+            self.collection_id as CollectionId,
+            self.name,
+            self.created_by.map(|x| x.0),
+            self.created,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let (project_ids, version_ids): (Vec<_>, Vec<_>) =
+            self.versions.iter().map(|(p, v)| (p.0, v.0)).unzip();
+        let snapshot_ids = self.versions.iter().map(|_| self.id.0).collect_vec();
+        let orderings = (0..self.versions.len() as i64).collect_vec();
+        sqlx::query!(
+            "
+            INSERT INTO collection_snapshot_versions (snapshot_id, project_id, version_id, ordering)
+            SELECT * FROM UNNEST($1::bigint[], $2::bigint[], $3::bigint[], $4::bigint[])
+            ",
+            &snapshot_ids[..],
+            &project_ids[..],
+            &version_ids[..],
+            &orderings[..],
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get<'a, E>(
+        id: CollectionSnapshotId,
+        exec: E,
+    ) -> Result<Option<CollectionSnapshot>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT s.id, s.collection_id, s.name, s.created_by, s.created,
+            ARRAY_AGG(sv.project_id ORDER BY sv.ordering) filter (where sv.project_id is not null) project_ids,
+            ARRAY_AGG(sv.version_id ORDER BY sv.ordering) filter (where sv.project_id is not null) version_ids
+            FROM collection_snapshots s
+            LEFT JOIN collection_snapshot_versions sv ON sv.snapshot_id = s.id
+            WHERE s.id = $1
+            GROUP BY s.id
+            ",
+            id as CollectionSnapshotId,
+        )
+        .fetch_optional(exec)
+        .await?;
+
+        Ok(result.map(|x| CollectionSnapshot {
+            id: CollectionSnapshotId(x.id),
+            collection_id: CollectionId(x.collection_id),
+            name: x.name,
+            created_by: x.created_by.map(UserId),
+            created: x.created,
+            versions: x
+                .project_ids
+                .unwrap_or_default()
+                .into_iter()
+                .map(ProjectId)
+                .zip(x.version_ids.unwrap_or_default().into_iter().map(VersionId))
+                .collect(),
+        }))
+    }
+
+    /// Gets the snapshots of a collection, newest first, without their versions
+    pub async fn get_collection<'a, E>(
+        collection_id: CollectionId,
+        exec: E,
+    ) -> Result<Vec<CollectionSnapshot>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT id, collection_id, name, created_by, created
+            FROM collection_snapshots
+            WHERE collection_id = $1
+            ORDER BY created DESC
+            ",
+            collection_id as CollectionId,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|x| CollectionSnapshot {
+                id: CollectionSnapshotId(x.id),
+                collection_id: CollectionId(x.collection_id),
+                name: x.name,
+                created_by: x.created_by.map(UserId),
+                created: x.created,
+                versions: Vec::new(),
+            })
+            .collect())
+    }
+
+    /// Gets the newest listed version of each project. Projects without one are left out
+    pub async fn get_latest_versions<'a, E>(
+        project_ids: &[ProjectId],
+        exec: E,
+    ) -> Result<HashMap<ProjectId, VersionId>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT DISTINCT ON (mod_id) mod_id, id
+            FROM versions
+            WHERE mod_id = ANY($1) AND status = $2
+            ORDER BY mod_id, date_published DESC
+            ",
+            &project_ids.iter().map(|x| x.0).collect_vec(),
+            VersionStatus::Listed.to_string(),
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|x| (ProjectId(x.mod_id), VersionId(x.id)))
+            .collect())
+    }
+
+    pub async fn remove(
+        id: CollectionSnapshotId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            DELETE FROM collection_snapshots
+            WHERE id = $1
+            ",
+            id as CollectionSnapshotId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
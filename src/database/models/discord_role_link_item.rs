@@ -0,0 +1,193 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use crate::models::discord::DiscordRelationship;
+use chrono::{DateTime, Utc};
+
+/// A Discord role granted to users by their relationship with a project or organization.
+/// Exactly one of `project_id` and `organization_id` is set
+#[derive(Clone, Debug)]
+pub struct DiscordRoleLink {
+    pub id: i64,
+    pub project_id: Option<ProjectId>,
+    pub organization_id: Option<OrganizationId>,
+    pub guild_id: i64,
+    pub role_id: i64,
+    pub relationship: DiscordRelationship,
+    /// Shared secret bots sign verification requests with
+    pub secret: String,
+    pub created: DateTime<Utc>,
+}
+
+impl DiscordRoleLink {
+    /// Inserts the link, returning its ID
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<i64, DatabaseError> {
+        let id = sqlx::query!(
+            "
+            INSERT INTO discord_role_links (
+                project_id, organization_id, guild_id, role_id, relationship, secret
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id
+            ",
+            self.project_id.map(|x| x.0),
+            self.organization_id.map(|x| x.0),
+            self.guild_id,
+            self.role_id,
+            self.relationship.as_str(),
+            self.secret,
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+        .id;
+
+        Ok(id)
+    }
+
+    pub async fn get<'a, E>(id: i64, exec: E) -> Result<Option<DiscordRoleLink>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let link = sqlx::query!(
+            "
+            SELECT id, project_id, organization_id, guild_id, role_id, relationship, secret,
+                created
+            FROM discord_role_links
+            WHERE id = $1
+            ",
+            id,
+        )
+        .fetch_optional(exec)
+        .await?
+        .map(|r| DiscordRoleLink {
+            id: r.id,
+            project_id: r.project_id.map(ProjectId),
+            organization_id: r.organization_id.map(OrganizationId),
+            guild_id: r.guild_id,
+            role_id: r.role_id,
+            relationship: DiscordRelationship::from_string(&r.relationship),
+            secret: r.secret,
+            created: r.created,
+        });
+
+        Ok(link)
+    }
+
+    /// Gets the links of a project or of an organization, oldest first
+    pub async fn get_many<'a, E>(
+        project_id: Option<ProjectId>,
+        organization_id: Option<OrganizationId>,
+        exec: E,
+    ) -> Result<Vec<DiscordRoleLink>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let links = sqlx::query!(
+            "
+            SELECT id, project_id, organization_id, guild_id, role_id, relationship, secret,
+                created
+            FROM discord_role_links
+            WHERE project_id = $1 OR organization_id = $2
+            ORDER BY created ASC, id ASC
+            ",
+            project_id.map(|x| x.0),
+            organization_id.map(|x| x.0),
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| DiscordRoleLink {
+            id: r.id,
+            project_id: r.project_id.map(ProjectId),
+            organization_id: r.organization_id.map(OrganizationId),
+            guild_id: r.guild_id,
+            role_id: r.role_id,
+            relationship: DiscordRelationship::from_string(&r.relationship),
+            secret: r.secret,
+            created: r.created,
+        })
+        .collect();
+
+        Ok(links)
+    }
+
+    pub async fn remove<'a, E>(id: i64, exec: E) -> Result<Option<()>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            DELETE FROM discord_role_links
+            WHERE id = $1
+            ",
+            id,
+        )
+        .execute(exec)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Whether the user with a Discord account linked to their Modrinth account is granted
+    /// the role. Team members are accepted members of the project's or organization's team,
+    /// or of the team of any of the organization's projects
+    pub async fn is_granted<'a, E>(&self, discord_id: i64, exec: E) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let granted = match self.relationship {
+            DiscordRelationship::TeamMember => {
+                sqlx::query!(
+                    "
+                    SELECT EXISTS(
+                        SELECT 1
+                        FROM users u
+                        INNER JOIN team_members tm ON tm.user_id = u.id AND tm.accepted
+                        LEFT JOIN mods m ON m.team_id = tm.team_id
+                        LEFT JOIN organizations o ON o.team_id = tm.team_id
+                        WHERE u.discord_id = $1 AND (
+                            m.id = $2
+                            OR o.id = $3
+                            OR m.organization_id = $3
+                            OR o.id = (SELECT organization_id FROM mods WHERE id = $2)
+                        )
+                    )
+                    ",
+                    discord_id,
+                    self.project_id.map(|x| x.0),
+                    self.organization_id.map(|x| x.0),
+                )
+                .fetch_one(exec)
+                .await?
+                .exists
+            }
+            DiscordRelationship::Follower => {
+                sqlx::query!(
+                    "
+                    SELECT EXISTS(
+                        SELECT 1
+                        FROM users u
+                        INNER JOIN mod_follows mf ON mf.follower_id = u.id
+                        INNER JOIN mods m ON m.id = mf.mod_id
+                        WHERE u.discord_id = $1 AND (m.id = $2 OR m.organization_id = $3)
+                    )
+                    ",
+                    discord_id,
+                    self.project_id.map(|x| x.0),
+                    self.organization_id.map(|x| x.0),
+                )
+                .fetch_one(exec)
+                .await?
+                .exists
+            }
+        };
+
+        Ok(granted.unwrap_or(false))
+    }
+}
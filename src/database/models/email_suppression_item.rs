@@ -0,0 +1,64 @@
+use crate::database::models::DatabaseError;
+
+/// Addresses email is not sent to, as they bounced permanently or marked an email as spam.
+/// Sending to them hurts the reputation of the address email is sent from
+pub struct EmailSuppression;
+
+impl EmailSuppression {
+    pub async fn insert_many(
+        emails: &[String],
+        reason: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO email_suppressions (email, reason)
+            SELECT lower(email), $2 FROM UNNEST($1::varchar[]) email
+            ON CONFLICT (email) DO UPDATE
+            SET reason = EXCLUDED.reason, created = CURRENT_TIMESTAMP
+            ",
+            emails,
+            reason,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn is_suppressed<'a, E>(email: &str, exec: E) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let suppressed = sqlx::query!(
+            "
+            SELECT EXISTS(SELECT 1 FROM email_suppressions WHERE email = lower($1))
+            ",
+            email,
+        )
+        .fetch_one(exec)
+        .await?
+        .exists
+        .unwrap_or(false);
+
+        Ok(suppressed)
+    }
+
+    /// Sends email to an address again, once its owner asks for email to be sent to it
+    pub async fn remove<'a, E>(email: &str, exec: E) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            DELETE FROM email_suppressions
+            WHERE email = lower($1)
+            ",
+            email,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+}
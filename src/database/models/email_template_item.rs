@@ -0,0 +1,145 @@
+use crate::auth::email::template::{EmailTemplate, EmailTemplateContent};
+use crate::database::models::DatabaseError;
+use serde::{Deserialize, Serialize};
+
+/// A localized email template stored in the database, overriding or adding to the templates
+/// bundled with labrinth
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EmailTemplateItem {
+    pub template: EmailTemplate,
+    pub locale: String,
+    pub content: EmailTemplateContent,
+}
+
+impl EmailTemplateItem {
+    pub async fn upsert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO email_templates (
+                template, locale, subject, title, description, line_two, button_title
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7
+            )
+            ON CONFLICT (template, locale) DO UPDATE
+            SET subject = EXCLUDED.subject, title = EXCLUDED.title,
+                description = EXCLUDED.description, line_two = EXCLUDED.line_two,
+                button_title = EXCLUDED.button_title
+            ",
+            self.template.as_str(),
+            self.locale,
+            self.content.subject,
+            self.content.title,
+            self.content.description,
+            self.content.line_two,
+            self.content.button_title,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove(
+        template: EmailTemplate,
+        locale: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM email_templates
+            WHERE template = $1 AND locale = $2
+            ",
+            template.as_str(),
+            locale,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Gets the stored variants of a template in any of the given locales
+    pub async fn get_locales<'a, E>(
+        template: EmailTemplate,
+        locales: &[String],
+        exec: E,
+    ) -> Result<Vec<EmailTemplateItem>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        use futures::TryStreamExt;
+
+        let templates = sqlx::query!(
+            "
+            SELECT template, locale, subject, title, description, line_two, button_title
+            FROM email_templates
+            WHERE template = $1 AND locale = ANY($2)
+            ",
+            template.as_str(),
+            locales,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().and_then(|r| {
+                Some(EmailTemplateItem {
+                    template: EmailTemplate::from_string(&r.template)?,
+                    locale: r.locale,
+                    content: EmailTemplateContent {
+                        subject: r.subject,
+                        title: r.title,
+                        description: r.description,
+                        line_two: r.line_two,
+                        button_title: r.button_title,
+                    },
+                })
+            }))
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+
+        Ok(templates)
+    }
+
+    pub async fn get_all<'a, E>(exec: E) -> Result<Vec<EmailTemplateItem>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        use futures::TryStreamExt;
+
+        let templates = sqlx::query!(
+            "
+            SELECT template, locale, subject, title, description, line_two, button_title
+            FROM email_templates
+            ORDER BY template, locale
+            "
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().and_then(|r| {
+                Some(EmailTemplateItem {
+                    template: EmailTemplate::from_string(&r.template)?,
+                    locale: r.locale,
+                    content: EmailTemplateContent {
+                        subject: r.subject,
+                        title: r.title,
+                        description: r.description,
+                        line_two: r.line_two,
+                        button_title: r.button_title,
+                    },
+                })
+            }))
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+
+        Ok(templates)
+    }
+}
@@ -0,0 +1,232 @@
+use crate::database::models::{DatabaseError, UserId};
+use crate::database::redis::RedisPool;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const FEATURE_FLAGS_NAMESPACE: &str = "feature_flags";
+
+/// A runtime toggle for a feature. Flags are few and read on many requests, so they are all
+/// cached together
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FeatureFlag {
+    pub key: String,
+    pub description: String,
+    pub enabled: bool,
+    pub rollout_percentage: i16,
+    /// Users the flag is enabled or disabled for regardless of its rollout
+    pub overrides: HashMap<UserId, bool>,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+impl FeatureFlag {
+    /// Whether the flag is on for a user. While a flag is rolled out, each user falls in a
+    /// fixed bucket, so they do not flip between having the feature and not. Requests without
+    /// a user only get the feature once it is rolled out to everyone
+    pub fn is_enabled_for(&self, user_id: Option<UserId>) -> bool {
+        if let Some(enabled) = user_id.and_then(|x| self.overrides.get(&x)) {
+            return *enabled;
+        }
+
+        if !self.enabled {
+            return false;
+        }
+
+        match user_id {
+            Some(user_id) => {
+                let digest = sha1::Sha1::from(format!("{}:{}", self.key, user_id.0))
+                    .digest()
+                    .bytes();
+                let bucket = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % 100;
+
+                (bucket as i16) < self.rollout_percentage
+            }
+            None => self.rollout_percentage >= 100,
+        }
+    }
+
+    /// Creates a flag, or updates the flag with the same key
+    pub async fn upsert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO feature_flags (key, description, enabled, rollout_percentage)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (key) DO UPDATE
+            SET description = EXCLUDED.description, enabled = EXCLUDED.enabled,
+                rollout_percentage = EXCLUDED.rollout_percentage, updated = CURRENT_TIMESTAMP
+            ",
+            self.key,
+            self.description,
+            self.enabled,
+            self.rollout_percentage,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove(
+        key: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM feature_flags
+            WHERE key = $1
+            ",
+            key,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    pub async fn set_override(
+        key: &str,
+        user_id: UserId,
+        enabled: bool,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO feature_flag_overrides (flag_key, user_id, enabled)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (flag_key, user_id) DO UPDATE
+            SET enabled = EXCLUDED.enabled
+            ",
+            key,
+            user_id as UserId,
+            enabled,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_override(
+        key: &str,
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM feature_flag_overrides
+            WHERE flag_key = $1 AND user_id = $2
+            ",
+            key,
+            user_id as UserId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    pub async fn get<'a, E>(
+        key: &str,
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<Option<FeatureFlag>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        Ok(Self::get_all(exec, redis)
+            .await?
+            .into_iter()
+            .find(|x| x.key == key))
+    }
+
+    pub async fn get_all<'a, E>(
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<Vec<FeatureFlag>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let mut redis = redis.connect().await?;
+
+        if let Some(flags) = redis
+            .get_deserialized_from_json(FEATURE_FLAGS_NAMESPACE, "all")
+            .await?
+        {
+            return Ok(flags);
+        }
+
+        let flags = sqlx::query!(
+            "
+            SELECT f.key, f.description, f.enabled, f.rollout_percentage, f.created, f.updated,
+                COALESCE(
+                    jsonb_object_agg(o.user_id, o.enabled) FILTER (WHERE o.user_id IS NOT NULL),
+                    '{}'
+                ) overrides
+            FROM feature_flags f
+            LEFT JOIN feature_flag_overrides o ON o.flag_key = f.key
+            GROUP BY f.key
+            ORDER BY f.key
+            "
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| FeatureFlag {
+            key: r.key,
+            description: r.description,
+            enabled: r.enabled,
+            rollout_percentage: r.rollout_percentage,
+            overrides: r
+                .overrides
+                .and_then(|x| serde_json::from_value::<HashMap<String, bool>>(x).ok())
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(user_id, enabled)| Some((UserId(user_id.parse().ok()?), enabled)))
+                .collect(),
+            created: r.created,
+            updated: r.updated,
+        })
+        .collect::<Vec<_>>();
+
+        redis
+            .set_serialized_to_json(FEATURE_FLAGS_NAMESPACE, "all", &flags, None)
+            .await?;
+
+        Ok(flags)
+    }
+
+    pub async fn clear_cache(redis: &RedisPool) -> Result<(), DatabaseError> {
+        let mut redis = redis.connect().await?;
+        redis.delete(FEATURE_FLAGS_NAMESPACE, "all").await?;
+
+        Ok(())
+    }
+
+    /// Whether a flag is on for a user. Flags which do not exist are off
+    pub async fn is_enabled<'a, E>(
+        key: &str,
+        user_id: Option<UserId>,
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        Ok(Self::get(key, exec, redis)
+            .await?
+            .map(|x| x.is_enabled_for(user_id))
+            .unwrap_or(false))
+    }
+}
@@ -0,0 +1,180 @@
+use super::ids::ProjectId;
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
+use crate::models::featured::FeaturedKind;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const FEATURED_NAMESPACE: &str = "featured";
+
+/// Projects curated by staff for the homepage. Rows are few and read on every homepage load,
+/// so they are all cached together, and which are active is worked out when they are read
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FeaturedRow {
+    pub id: i64,
+    pub kind: FeaturedKind,
+    pub title: String,
+    pub description: String,
+    pub project_ids: Vec<ProjectId>,
+    pub starts: DateTime<Utc>,
+    pub ends: Option<DateTime<Utc>>,
+    pub ordering: i32,
+    pub created: DateTime<Utc>,
+}
+
+impl FeaturedRow {
+    /// Whether the row is shown at a time
+    pub fn is_active_at(&self, time: DateTime<Utc>) -> bool {
+        self.starts <= time && self.ends.map_or(true, |x| time < x)
+    }
+
+    /// Inserts the row, returning its ID
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<i64, DatabaseError> {
+        let id = sqlx::query!(
+            "
+            INSERT INTO featured_rows (
+                kind, title, description, project_ids, starts, ends, ordering
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+            ",
+            self.kind.as_str(),
+            self.title,
+            self.description,
+            &self.project_ids.iter().map(|x| x.0).collect::<Vec<_>>(),
+            self.starts,
+            self.ends,
+            self.ordering,
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+        .id;
+
+        Ok(id)
+    }
+
+    pub async fn update(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            UPDATE featured_rows
+            SET kind = $2, title = $3, description = $4, project_ids = $5, starts = $6,
+                ends = $7, ordering = $8
+            WHERE id = $1
+            ",
+            self.id,
+            self.kind.as_str(),
+            self.title,
+            self.description,
+            &self.project_ids.iter().map(|x| x.0).collect::<Vec<_>>(),
+            self.starts,
+            self.ends,
+            self.ordering,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    pub async fn remove(
+        id: i64,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM featured_rows
+            WHERE id = $1
+            ",
+            id,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Gets every row, including scheduled and ended ones, by kind and ordering
+    pub async fn get_all<'a, E>(
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<Vec<FeaturedRow>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let mut redis = redis.connect().await?;
+
+        if let Some(rows) = redis
+            .get_deserialized_from_json(FEATURED_NAMESPACE, "all")
+            .await?
+        {
+            return Ok(rows);
+        }
+
+        let rows = sqlx::query!(
+            "
+            SELECT id, kind, title, description, project_ids, starts, ends, ordering, created
+            FROM featured_rows
+            ORDER BY kind, ordering, id
+            "
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| FeaturedRow {
+            id: r.id,
+            kind: FeaturedKind::from_string(&r.kind),
+            title: r.title,
+            description: r.description,
+            project_ids: r.project_ids.into_iter().map(ProjectId).collect(),
+            starts: r.starts,
+            ends: r.ends,
+            ordering: r.ordering,
+            created: r.created,
+        })
+        .collect::<Vec<_>>();
+
+        redis
+            .set_serialized_to_json(FEATURED_NAMESPACE, "all", &rows, None)
+            .await?;
+
+        Ok(rows)
+    }
+
+    /// Gets the rows which are shown now
+    pub async fn get_active<'a, E>(
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<Vec<FeaturedRow>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let now = Utc::now();
+
+        Ok(Self::get_all(exec, redis)
+            .await?
+            .into_iter()
+            .filter(|x| x.is_active_at(now))
+            .collect())
+    }
+
+    pub async fn clear_cache(redis: &RedisPool) -> Result<(), DatabaseError> {
+        let mut redis = redis.connect().await?;
+        redis.delete(FEATURED_NAMESPACE, "all").await?;
+
+        Ok(())
+    }
+}
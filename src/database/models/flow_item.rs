@@ -36,6 +36,9 @@ pub enum Flow {
         confirm_email: String,
     },
     MinecraftAuth,
+    RestoreAccount {
+        user_id: UserId,
+    },
     InitOAuthAppApproval {
         user_id: UserId,
         client_id: OAuthClientId,
@@ -0,0 +1,187 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A GitHub repository a project imports its releases from as versions
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GitHubLink {
+    pub project_id: ProjectId,
+    /// The repository as `owner/name`, lowercased
+    pub repository: String,
+    /// The member who linked the repository, who imported versions are authored by
+    pub user_id: UserId,
+    /// The loaders and version fields imported versions get, which releases don't say
+    pub loaders: Vec<String>,
+    pub version_fields: HashMap<String, serde_json::Value>,
+    /// Whether new releases are imported as draft versions when GitHub sends them
+    pub sync: bool,
+    pub created: DateTime<Utc>,
+}
+
+impl GitHubLink {
+    /// Links a repository, replacing the repository the project linked before
+    pub async fn upsert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO project_github_links (
+                project_id, repository, user_id, loaders, version_fields, sync
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (project_id) DO UPDATE
+            SET repository = EXCLUDED.repository, user_id = EXCLUDED.user_id,
+                loaders = EXCLUDED.loaders,
+                version_fields = EXCLUDED.version_fields, sync = EXCLUDED.sync
+            ",
+            self.project_id as ProjectId,
+            self.repository,
+            self.user_id as UserId,
+            &self.loaders,
+            serde_json::to_value(&self.version_fields)?,
+            self.sync,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get<'a, E>(
+        project_id: ProjectId,
+        exec: E,
+    ) -> Result<Option<GitHubLink>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let link = sqlx::query!(
+            "
+            SELECT project_id, repository, user_id, loaders, version_fields, sync, created
+            FROM project_github_links
+            WHERE project_id = $1
+            ",
+            project_id as ProjectId,
+        )
+        .fetch_optional(exec)
+        .await?
+        .map(|r| GitHubLink {
+            project_id: ProjectId(r.project_id),
+            repository: r.repository,
+            user_id: UserId(r.user_id),
+            loaders: r.loaders,
+            version_fields: serde_json::from_value(r.version_fields).unwrap_or_default(),
+            sync: r.sync,
+            created: r.created,
+        });
+
+        Ok(link)
+    }
+
+    /// Gets the projects which sync the releases of a repository
+    pub async fn get_synced<'a, E>(
+        repository: &str,
+        exec: E,
+    ) -> Result<Vec<GitHubLink>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let links = sqlx::query!(
+            "
+            SELECT project_id, repository, user_id, loaders, version_fields, sync, created
+            FROM project_github_links
+            WHERE repository = LOWER($1) AND sync
+            ",
+            repository,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| GitHubLink {
+            project_id: ProjectId(r.project_id),
+            repository: r.repository,
+            user_id: UserId(r.user_id),
+            loaders: r.loaders,
+            version_fields: serde_json::from_value(r.version_fields).unwrap_or_default(),
+            sync: r.sync,
+            created: r.created,
+        })
+        .collect();
+
+        Ok(links)
+    }
+
+    pub async fn remove<'a, E>(project_id: ProjectId, exec: E) -> Result<Option<()>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            DELETE FROM project_github_links
+            WHERE project_id = $1
+            ",
+            project_id as ProjectId,
+        )
+        .execute(exec)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Whether a release was already imported into a project
+    pub async fn is_imported<'a, E>(
+        project_id: ProjectId,
+        release_id: u64,
+        exec: E,
+    ) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let imported = sqlx::query!(
+            "
+            SELECT EXISTS(
+                SELECT 1 FROM github_release_imports
+                WHERE project_id = $1 AND release_id = $2
+            )
+            ",
+            project_id as ProjectId,
+            release_id as i64,
+        )
+        .fetch_one(exec)
+        .await?
+        .exists
+        .unwrap_or(false);
+
+        Ok(imported)
+    }
+
+    /// Records that a release was imported, as the given version if it had any files which
+    /// could be imported
+    pub async fn mark_imported(
+        project_id: ProjectId,
+        release_id: u64,
+        version_id: Option<VersionId>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO github_release_imports (project_id, release_id, version_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (project_id, release_id) DO NOTHING
+            ",
+            project_id as ProjectId,
+            release_id as i64,
+            version_id.map(|x| x.0),
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
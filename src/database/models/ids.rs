@@ -12,9 +12,9 @@ macro_rules! generate_ids {
         $vis async fn $function_name(
             con: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         ) -> Result<$return_type, DatabaseError> {
-            let mut rng = rand::thread_rng();
+            // The thread RNG isn't `Send`, so it isn't kept across the awaits below
             let length = $id_length;
-            let mut id = random_base62_rng(&mut rng, length);
+            let mut id = random_base62_rng(&mut rand::thread_rng(), length);
             let mut retry_count = 0;
             let censor = Censor::Standard + Censor::Sex;
 
@@ -25,7 +25,7 @@ macro_rules! generate_ids {
                     .await?;
 
                 if results.exists.unwrap_or(true) || censor.check(&*to_base62(id)) {
-                    id = random_base62_rng(&mut rng, length);
+                    id = random_base62_rng(&mut rand::thread_rng(), length);
                 } else {
                     break;
                 }
@@ -148,7 +148,7 @@ generate_ids!(
     pub generate_image_id,
     ImageId,
     8,
-    "SELECT EXISTS(SELECT 1 FROM uploaded_images WHERE id=$1)",
+    "SELECT EXISTS(SELECT 1 FROM uploaded_images WHERE id=$1 UNION ALL SELECT 1 FROM mods_gallery WHERE image_id=$1)",
     ImageId
 );
 
@@ -184,6 +184,14 @@ generate_ids!(
     OAuthAccessTokenId
 );
 
+generate_ids!(
+    pub generate_collection_snapshot_id,
+    CollectionSnapshotId,
+    8,
+    "SELECT EXISTS(SELECT 1 FROM collection_snapshots WHERE id=$1)",
+    CollectionSnapshotId
+);
+
 generate_ids!(
     pub generate_payout_id,
     PayoutId,
@@ -232,7 +240,7 @@ pub struct VersionId(pub i64);
 #[derive(Copy, Clone, Debug, Type, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[sqlx(transparent)]
 pub struct LoaderId(pub i32);
-#[derive(Copy, Clone, Debug, Type, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Type, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[sqlx(transparent)]
 pub struct CategoryId(pub i32);
 
@@ -311,6 +319,10 @@ pub struct OAuthAccessTokenId(pub i64);
 #[sqlx(transparent)]
 pub struct PayoutId(pub i64);
 
+#[derive(Copy, Clone, Debug, Type, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[sqlx(transparent)]
+pub struct CollectionSnapshotId(pub i64);
+
 use crate::models::ids;
 
 impl From<ids::ProjectId> for ProjectId {
@@ -454,6 +466,17 @@ impl From<OAuthClientAuthorizationId> for ids::OAuthClientAuthorizationId {
     }
 }
 
+impl From<ids::CollectionSnapshotId> for CollectionSnapshotId {
+    fn from(id: ids::CollectionSnapshotId) -> Self {
+        CollectionSnapshotId(id.0 as i64)
+    }
+}
+impl From<CollectionSnapshotId> for ids::CollectionSnapshotId {
+    fn from(id: CollectionSnapshotId) -> Self {
+        ids::CollectionSnapshotId(id.0 as u64)
+    }
+}
+
 impl From<ids::PayoutId> for PayoutId {
     fn from(id: ids::PayoutId) -> Self {
         PayoutId(id.0 as i64)
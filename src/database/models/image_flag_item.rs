@@ -0,0 +1,145 @@
+use super::DatabaseError;
+use crate::models::ids::{CollectionId, OrganizationId, ProjectId, UserId};
+use crate::models::moderation::ImageSource;
+use crate::util::cursor::Cursor;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// An uploaded image the content scanner flagged, waiting for a moderator to review it
+#[derive(Clone, Debug)]
+pub struct ImageFlag {
+    pub id: i64,
+    pub image_url: String,
+    pub source: ImageSource,
+    /// The confidence of each label the scanner reported, from 0 to 1
+    pub labels: HashMap<String, f32>,
+    pub created: DateTime<Utc>,
+}
+
+impl ImageFlag {
+    /// Adds an image to the queue, returning the flag's ID
+    pub async fn insert<'a, E>(
+        image_url: &str,
+        source: ImageSource,
+        labels: &HashMap<String, f32>,
+        exec: E,
+    ) -> Result<i64, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let (mut project_id, mut user_id, mut organization_id, mut collection_id) =
+            (None, None, None, None);
+        match source {
+            ImageSource::ProjectIcon { project_id: id }
+            | ImageSource::GalleryImage { project_id: id } => project_id = Some(id.0 as i64),
+            ImageSource::UserAvatar { user_id: id } => user_id = Some(id.0 as i64),
+            ImageSource::OrganizationIcon {
+                organization_id: id,
+            } => organization_id = Some(id.0 as i64),
+            ImageSource::CollectionIcon { collection_id: id } => collection_id = Some(id.0 as i64),
+        }
+
+        let id = sqlx::query!(
+            "
+            INSERT INTO image_flags (
+                image_url, source_type, project_id, user_id, organization_id, collection_id,
+                labels
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+            ",
+            image_url,
+            source.as_str(),
+            project_id,
+            user_id,
+            organization_id,
+            collection_id,
+            serde_json::to_value(labels)?,
+        )
+        .fetch_one(exec)
+        .await?
+        .id;
+
+        Ok(id)
+    }
+
+    /// Gets a page of the queue, longest flagged first, starting after `cursor`
+    pub async fn get_page<'a, E>(
+        cursor: Option<Cursor>,
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<ImageFlag>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT id, image_url, source_type, project_id, user_id, organization_id,
+                collection_id, labels, created
+            FROM image_flags
+            WHERE $1::timestamptz IS NULL OR (created, id) > ($1, $2)
+            ORDER BY created ASC, id ASC
+            LIMIT $3
+            ",
+            cursor.map(|x| x.timestamp),
+            cursor.map(|x| x.id),
+            limit,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .filter_map(|x| {
+                let source = match &*x.source_type {
+                    "project_icon" => ImageSource::ProjectIcon {
+                        project_id: ProjectId(x.project_id? as u64),
+                    },
+                    "gallery_image" => ImageSource::GalleryImage {
+                        project_id: ProjectId(x.project_id? as u64),
+                    },
+                    "user_avatar" => ImageSource::UserAvatar {
+                        user_id: UserId(x.user_id? as u64),
+                    },
+                    "organization_icon" => ImageSource::OrganizationIcon {
+                        organization_id: OrganizationId(x.organization_id? as u64),
+                    },
+                    "collection_icon" => ImageSource::CollectionIcon {
+                        collection_id: CollectionId(x.collection_id? as u64),
+                    },
+                    _ => return None,
+                };
+
+                Some(ImageFlag {
+                    id: x.id,
+                    image_url: x.image_url,
+                    source,
+                    labels: serde_json::from_value(x.labels).unwrap_or_default(),
+                    created: x.created,
+                })
+            })
+            .collect())
+    }
+
+    /// Takes an image out of the queue once a moderator reviewed it
+    pub async fn remove<'a, E>(id: i64, exec: E) -> Result<Option<()>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            DELETE FROM image_flags
+            WHERE id = $1
+            ",
+            id,
+        )
+        .execute(exec)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+}
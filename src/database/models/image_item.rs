@@ -1,10 +1,13 @@
 use super::ids::*;
-use crate::database::redis::RedisPool;
+use crate::database::redis::{RedisConnection, RedisPool};
 use crate::{database::models::DatabaseError, models::images::ImageContext};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 const IMAGES_NAMESPACE: &str = "images";
+const IMAGE_THUMBNAILS_NAMESPACE: &str = "image_thumbnails";
+// Thumbnails are kept until their image is deleted, so their URLs can be cached for long
+const IMAGE_THUMBNAILS_EXPIRY: i64 = 60 * 60 * 24 * 7;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Image {
@@ -250,6 +253,52 @@ impl Image {
         let mut redis = redis.connect().await?;
 
         redis.delete(IMAGES_NAMESPACE, id.0).await?;
+        Self::clear_thumbnail_cache(id, &mut redis).await?;
         Ok(())
     }
+
+    /// Gets the URL of the thumbnail of an uploaded or gallery image, if it was generated
+    pub async fn get_thumbnail_url(
+        id: ImageId,
+        size: u32,
+        redis: &RedisPool,
+    ) -> Result<Option<String>, DatabaseError> {
+        let mut redis = redis.connect().await?;
+
+        redis
+            .get(IMAGE_THUMBNAILS_NAMESPACE, &format!("{}_{}", id.0, size))
+            .await
+    }
+
+    pub async fn set_thumbnail_url(
+        id: ImageId,
+        size: u32,
+        url: &str,
+        redis: &RedisPool,
+    ) -> Result<(), DatabaseError> {
+        let mut redis = redis.connect().await?;
+
+        redis
+            .set(
+                IMAGE_THUMBNAILS_NAMESPACE,
+                &format!("{}_{}", id.0, size),
+                url,
+                Some(IMAGE_THUMBNAILS_EXPIRY),
+            )
+            .await
+    }
+
+    pub async fn clear_thumbnail_cache(
+        id: ImageId,
+        redis: &mut RedisConnection,
+    ) -> Result<(), DatabaseError> {
+        redis
+            .delete_many(crate::util::img::THUMBNAIL_SIZES.iter().map(|size| {
+                (
+                    IMAGE_THUMBNAILS_NAMESPACE,
+                    Some(format!("{}_{}", id.0, size)),
+                )
+            }))
+            .await
+    }
 }
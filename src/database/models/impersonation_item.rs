@@ -0,0 +1,201 @@
+use super::ids::UserId;
+use super::DatabaseError;
+use chrono::{DateTime, Utc};
+use sha2::Digest;
+
+/// A read-only session an admin opened as another user
+#[derive(Clone, Debug)]
+pub struct ImpersonationSession {
+    pub id: i64,
+    pub admin_id: UserId,
+    pub user_id: UserId,
+    pub reason: String,
+    pub created: DateTime<Utc>,
+    pub expires: DateTime<Utc>,
+    pub revoked: Option<DateTime<Utc>>,
+}
+
+/// A request made with an impersonation session
+#[derive(Clone, Debug)]
+pub struct ImpersonationRequest {
+    pub session_id: i64,
+    pub method: String,
+    pub path: String,
+    pub created: DateTime<Utc>,
+}
+
+impl ImpersonationSession {
+    /// Opens a session, returning its ID
+    pub async fn insert(
+        token_hash: &str,
+        admin_id: UserId,
+        user_id: UserId,
+        reason: &str,
+        expires: DateTime<Utc>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<i64, DatabaseError> {
+        let id = sqlx::query!(
+            "
+            INSERT INTO impersonation_sessions (token_hash, admin_id, user_id, reason, expires)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            ",
+            token_hash,
+            admin_id as UserId,
+            user_id as UserId,
+            reason,
+            expires,
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+        .id;
+
+        Ok(id)
+    }
+
+    /// Gets the session with a token, unless it expired or was revoked
+    pub async fn get_active<'a, E>(
+        token_hash: &str,
+        exec: E,
+    ) -> Result<Option<ImpersonationSession>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT id, admin_id, user_id, reason, created, expires, revoked
+            FROM impersonation_sessions
+            WHERE token_hash = $1 AND expires > NOW() AND revoked IS NULL
+            ",
+            token_hash,
+        )
+        .fetch_optional(exec)
+        .await?;
+
+        Ok(result.map(|x| ImpersonationSession {
+            id: x.id,
+            admin_id: UserId(x.admin_id),
+            user_id: UserId(x.user_id),
+            reason: x.reason,
+            created: x.created,
+            expires: x.expires,
+            revoked: x.revoked,
+        }))
+    }
+
+    /// Gets the sessions opened as a user, latest first
+    pub async fn get_many_user<'a, E>(
+        user_id: UserId,
+        exec: E,
+    ) -> Result<Vec<ImpersonationSession>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT id, admin_id, user_id, reason, created, expires, revoked
+            FROM impersonation_sessions
+            WHERE user_id = $1
+            ORDER BY created DESC
+            ",
+            user_id as UserId,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|x| ImpersonationSession {
+                id: x.id,
+                admin_id: UserId(x.admin_id),
+                user_id: UserId(x.user_id),
+                reason: x.reason,
+                created: x.created,
+                expires: x.expires,
+                revoked: x.revoked,
+            })
+            .collect())
+    }
+
+    /// Ends a session before it expires. Returns `None` if there is no open session with the ID
+    pub async fn revoke<'a, E>(id: i64, exec: E) -> Result<Option<()>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            UPDATE impersonation_sessions
+            SET revoked = NOW()
+            WHERE id = $1 AND revoked IS NULL AND expires > NOW()
+            ",
+            id,
+        )
+        .execute(exec)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    pub fn hash_token(token: &str) -> String {
+        format!("{:x}", sha2::Sha512::digest(token.as_bytes()))
+    }
+
+    pub async fn log_request<'a, E>(
+        session_id: i64,
+        method: &str,
+        path: &str,
+        exec: E,
+    ) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            INSERT INTO impersonation_requests (session_id, method, path)
+            VALUES ($1, $2, $3)
+            ",
+            session_id,
+            method,
+            path,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the requests made with sessions, oldest first
+    pub async fn get_requests<'a, E>(
+        session_ids: &[i64],
+        exec: E,
+    ) -> Result<Vec<ImpersonationRequest>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT session_id, method, path, created
+            FROM impersonation_requests
+            WHERE session_id = ANY($1)
+            ORDER BY created ASC, id ASC
+            ",
+            session_ids,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|x| ImpersonationRequest {
+                session_id: x.session_id,
+                method: x.method,
+                path: x.path,
+                created: x.created,
+            })
+            .collect())
+    }
+}
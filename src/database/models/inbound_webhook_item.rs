@@ -0,0 +1,84 @@
+use super::DatabaseError;
+use chrono::{DateTime, Utc};
+
+/// A webhook received from another service, stored once verified so it can be processed by
+/// the job workers
+pub struct InboundWebhook {
+    pub id: i64,
+    /// The service which sent the webhook, such as `github` or `payout_paypal`
+    pub source: String,
+    /// The sender's ID for the delivery, which stays the same when a delivery is retried
+    pub delivery_id: String,
+    pub payload: String,
+    pub received: DateTime<Utc>,
+    pub processed: Option<DateTime<Utc>>,
+}
+
+impl InboundWebhook {
+    /// Stores a delivery. Returns `None` if the delivery was already received
+    pub async fn insert(
+        source: &str,
+        delivery_id: &str,
+        payload: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<i64>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            INSERT INTO inbound_webhooks (source, delivery_id, payload)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (source, delivery_id) DO NOTHING
+            RETURNING id
+            ",
+            source,
+            delivery_id,
+            payload,
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
+
+        Ok(result.map(|x| x.id))
+    }
+
+    pub async fn get<'a, E>(id: i64, exec: E) -> Result<Option<InboundWebhook>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT id, source, delivery_id, payload, received, processed
+            FROM inbound_webhooks
+            WHERE id = $1
+            ",
+            id
+        )
+        .fetch_optional(exec)
+        .await?;
+
+        Ok(result.map(|x| InboundWebhook {
+            id: x.id,
+            source: x.source,
+            delivery_id: x.delivery_id,
+            payload: x.payload,
+            received: x.received,
+            processed: x.processed,
+        }))
+    }
+
+    pub async fn mark_processed<'a, E>(id: i64, exec: E) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            UPDATE inbound_webhooks
+            SET processed = CURRENT_TIMESTAMP
+            WHERE id = $1
+            ",
+            id
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+}
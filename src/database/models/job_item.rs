@@ -0,0 +1,403 @@
+use crate::database::models::{DatabaseError, UserId};
+use crate::models::jobs::{JobPayload, JobStatus};
+use crate::util::telemetry::current_context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A unit of background work, run by the workers in `queue::jobs`. Jobs are queued in
+/// Postgres so they survive restarts, and can be queued in the same transaction as the change
+/// which caused them.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Job {
+    pub id: i64,
+    pub kind: String,
+    /// `None` if the payload is of a kind this version does not know
+    pub payload: Option<JobPayload>,
+    pub status: JobStatus,
+    pub unique_key: Option<String>,
+    pub attempt_count: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    /// The trace context of the span which queued the job
+    pub trace_context: HashMap<String, String>,
+    /// The user who started the job. Their jobs are kept once completed, so they can see the
+    /// result
+    pub user_id: Option<UserId>,
+    /// How far along the job is, from 0 to 1, for jobs which report it
+    pub progress: Option<f32>,
+    /// Where the result of a completed job can be downloaded, for jobs which produce a file
+    pub result_url: Option<String>,
+}
+
+impl Job {
+    /// Queues a job to run at `run_at`. Returns `false` if it was not queued because a job
+    /// with the same unique key is already pending or running
+    pub async fn enqueue<'a, E>(
+        payload: JobPayload,
+        run_at: DateTime<Utc>,
+        exec: E,
+    ) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            INSERT INTO jobs (
+                kind, payload, status, unique_key, max_attempts, timeout_seconds, run_at,
+                trace_context
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8
+            )
+            ON CONFLICT (unique_key) WHERE status IN ('pending', 'running') DO NOTHING
+            ",
+            payload.kind(),
+            serde_json::to_value(&payload)?,
+            JobStatus::Pending.as_str(),
+            payload.unique_key(),
+            payload.max_attempts(),
+            payload.timeout_seconds(),
+            run_at,
+            serde_json::to_value(current_context())?,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Claims up to `limit` jobs which are due, or whose worker's lease ran out, and leases
+    /// them for their timeout. Rows locked by another worker are skipped, so several
+    /// instances can run jobs concurrently.
+    pub async fn claim<'a, E>(limit: i64, exec: E) -> Result<Vec<Job>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let jobs = sqlx::query!(
+            "
+            UPDATE jobs
+            SET status = $1, attempt_count = attempt_count + 1,
+                locked_until = CURRENT_TIMESTAMP + timeout_seconds * INTERVAL '1 second'
+            WHERE id IN (
+                SELECT id FROM jobs
+                WHERE (status = $2 AND run_at <= CURRENT_TIMESTAMP)
+                OR (status = $1 AND locked_until < CURRENT_TIMESTAMP)
+                ORDER BY run_at ASC
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, kind, payload, status, unique_key, attempt_count, max_attempts,
+                run_at, last_error, created, finished, trace_context, user_id, progress,
+                result_url
+            ",
+            JobStatus::Running.as_str(),
+            JobStatus::Pending.as_str(),
+            limit,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| Job {
+            id: r.id,
+            kind: r.kind,
+            payload: serde_json::from_value(r.payload).ok(),
+            status: JobStatus::from_string(&r.status),
+            unique_key: r.unique_key,
+            attempt_count: r.attempt_count,
+            max_attempts: r.max_attempts,
+            run_at: r.run_at,
+            last_error: r.last_error,
+            created: r.created,
+            finished: r.finished,
+            trace_context: serde_json::from_value(r.trace_context).unwrap_or_default(),
+            user_id: r.user_id.map(UserId),
+            progress: r.progress,
+            result_url: r.result_url,
+        })
+        .collect();
+
+        Ok(jobs)
+    }
+
+    /// Queues a job started by a user to run right away, returning its ID so they can follow
+    /// its progress
+    pub async fn enqueue_for_user<'a, E>(
+        payload: JobPayload,
+        user_id: UserId,
+        exec: E,
+    ) -> Result<i64, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let id = sqlx::query!(
+            "
+            INSERT INTO jobs (
+                kind, payload, status, unique_key, max_attempts, timeout_seconds, run_at,
+                trace_context, user_id
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, CURRENT_TIMESTAMP, $7, $8
+            )
+            RETURNING id
+            ",
+            payload.kind(),
+            serde_json::to_value(&payload)?,
+            JobStatus::Pending.as_str(),
+            payload.unique_key(),
+            payload.max_attempts(),
+            payload.timeout_seconds(),
+            serde_json::to_value(current_context())?,
+            user_id as UserId,
+        )
+        .fetch_one(exec)
+        .await?
+        .id;
+
+        Ok(id)
+    }
+
+    pub async fn get<'a, E>(id: i64, exec: E) -> Result<Option<Job>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let job = sqlx::query!(
+            "
+            SELECT id, kind, payload, status, unique_key, attempt_count, max_attempts,
+                run_at, last_error, created, finished, trace_context, user_id, progress,
+                result_url
+            FROM jobs
+            WHERE id = $1
+            ",
+            id,
+        )
+        .fetch_optional(exec)
+        .await?
+        .map(|r| Job {
+            id: r.id,
+            kind: r.kind,
+            payload: serde_json::from_value(r.payload).ok(),
+            status: JobStatus::from_string(&r.status),
+            unique_key: r.unique_key,
+            attempt_count: r.attempt_count,
+            max_attempts: r.max_attempts,
+            run_at: r.run_at,
+            last_error: r.last_error,
+            created: r.created,
+            finished: r.finished,
+            trace_context: serde_json::from_value(r.trace_context).unwrap_or_default(),
+            user_id: r.user_id.map(UserId),
+            progress: r.progress,
+            result_url: r.result_url,
+        });
+
+        Ok(job)
+    }
+
+    /// Records how far along a running job is, from 0 to 1
+    pub async fn set_progress<'a, E>(id: i64, progress: f32, exec: E) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            UPDATE jobs
+            SET progress = $1
+            WHERE id = $2
+            ",
+            progress.clamp(0.0, 1.0),
+            id,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Finishes a job which ran successfully. Jobs started by a user are kept with where their
+    /// result was put, while the rest are removed
+    pub async fn complete<'a, E>(
+        id: i64,
+        result_url: Option<String>,
+        exec: E,
+    ) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            WITH removed AS (
+                DELETE FROM jobs
+                WHERE id = $1 AND user_id IS NULL
+            )
+            UPDATE jobs
+            SET status = $2, progress = 1, result_url = $3, finished = CURRENT_TIMESTAMP,
+                locked_until = NULL
+            WHERE id = $1 AND user_id IS NOT NULL
+            ",
+            id,
+            JobStatus::Completed.as_str(),
+            result_url,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records a failed run. The job runs again at `retry_at`, or is moved to the dead letters
+    /// if it is `None`
+    pub async fn fail<'a, E>(
+        id: i64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+        exec: E,
+    ) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let (status, finished) = match retry_at {
+            Some(_) => (JobStatus::Pending, None),
+            None => (JobStatus::Dead, Some(Utc::now())),
+        };
+
+        sqlx::query!(
+            "
+            UPDATE jobs
+            SET status = $1, run_at = COALESCE($2, run_at), last_error = $3, finished = $4,
+                locked_until = NULL
+            WHERE id = $5
+            ",
+            status.as_str(),
+            retry_at,
+            error,
+            finished,
+            id,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the jobs with a status, oldest first
+    pub async fn get_by_status<'a, E>(
+        status: JobStatus,
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<Job>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let jobs = sqlx::query!(
+            "
+            SELECT id, kind, payload, status, unique_key, attempt_count, max_attempts,
+                run_at, last_error, created, finished, trace_context, user_id, progress,
+                result_url
+            FROM jobs
+            WHERE status = $1
+            ORDER BY created ASC
+            LIMIT $2
+            ",
+            status.as_str(),
+            limit,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| Job {
+            id: r.id,
+            kind: r.kind,
+            payload: serde_json::from_value(r.payload).ok(),
+            status: JobStatus::from_string(&r.status),
+            unique_key: r.unique_key,
+            attempt_count: r.attempt_count,
+            max_attempts: r.max_attempts,
+            run_at: r.run_at,
+            last_error: r.last_error,
+            created: r.created,
+            finished: r.finished,
+            trace_context: serde_json::from_value(r.trace_context).unwrap_or_default(),
+            user_id: r.user_id.map(UserId),
+            progress: r.progress,
+            result_url: r.result_url,
+        })
+        .collect();
+
+        Ok(jobs)
+    }
+
+    /// Puts running jobs back in the queue to run right away, undoing the attempt they were
+    /// claimed for
+    pub async fn release<'a, E>(ids: &[i64], exec: E) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            UPDATE jobs
+            SET status = $1, attempt_count = GREATEST(attempt_count - 1, 0),
+                run_at = CURRENT_TIMESTAMP, locked_until = NULL
+            WHERE id = ANY($2) AND status = $3
+            ",
+            JobStatus::Pending.as_str(),
+            ids,
+            JobStatus::Running.as_str(),
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Counts the jobs with each status
+    pub async fn count_by_status<'a, E>(exec: E) -> Result<Vec<(JobStatus, i64)>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let counts = sqlx::query!(
+            "
+            SELECT status, COUNT(*) count
+            FROM jobs
+            GROUP BY status
+            "
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| (JobStatus::from_string(&r.status), r.count.unwrap_or(0)))
+        .collect();
+
+        Ok(counts)
+    }
+
+    /// Queues a dead job again with its attempts reset. Returns `false` if the job is not dead,
+    /// or a job with the same unique key is already queued
+    pub async fn retry<'a, E>(id: i64, exec: E) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            UPDATE jobs
+            SET status = $1, attempt_count = 0, run_at = CURRENT_TIMESTAMP, finished = NULL
+            WHERE id = $2 AND status = $3
+            AND NOT EXISTS (
+                SELECT 1 FROM jobs queued
+                WHERE queued.unique_key = jobs.unique_key AND queued.status IN ($1, $4)
+            )
+            ",
+            JobStatus::Pending.as_str(),
+            id,
+            JobStatus::Dead.as_str(),
+            JobStatus::Running.as_str(),
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
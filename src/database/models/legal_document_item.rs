@@ -0,0 +1,306 @@
+use super::ids::UserId;
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
+use crate::models::legal::LegalDocumentKind;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const LEGAL_DOCUMENTS_NAMESPACE: &str = "legal_documents";
+
+/// A published version of the terms, privacy policy or rules
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LegalDocument {
+    pub id: i64,
+    pub kind: LegalDocumentKind,
+    pub version: i32,
+    pub content: String,
+    pub material: bool,
+    pub published: DateTime<Utc>,
+}
+
+/// A version of a document a user accepted
+#[derive(Clone, Debug)]
+pub struct LegalAcceptance {
+    pub document_id: i64,
+    pub kind: LegalDocumentKind,
+    pub version: i32,
+    pub accepted: DateTime<Utc>,
+}
+
+impl LegalDocument {
+    /// Publishes a new version of a document, numbered after its latest version
+    pub async fn insert(
+        kind: LegalDocumentKind,
+        content: &str,
+        material: bool,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<LegalDocument, DatabaseError> {
+        let document = sqlx::query!(
+            "
+            INSERT INTO legal_documents (kind, version, content, material)
+            SELECT $1::varchar, COALESCE(MAX(version), 0) + 1, $2, $3
+            FROM legal_documents
+            WHERE kind = $1
+            RETURNING id, version, published
+            ",
+            kind.as_str(),
+            content,
+            material,
+        )
+        .fetch_one(&mut **transaction)
+        .await?;
+
+        Ok(LegalDocument {
+            id: document.id,
+            kind,
+            version: document.version,
+            content: content.to_string(),
+            material,
+            published: document.published,
+        })
+    }
+
+    pub async fn get<'a, E>(id: i64, exec: E) -> Result<Option<LegalDocument>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let document = sqlx::query!(
+            "
+            SELECT id, kind, version, content, material, published
+            FROM legal_documents
+            WHERE id = $1
+            ",
+            id,
+        )
+        .fetch_optional(exec)
+        .await?
+        .map(|r| LegalDocument {
+            id: r.id,
+            kind: LegalDocumentKind::from_string(&r.kind),
+            version: r.version,
+            content: r.content,
+            material: r.material,
+            published: r.published,
+        });
+
+        Ok(document)
+    }
+
+    /// Gets every version of every document, newest first
+    pub async fn get_all<'a, E>(exec: E) -> Result<Vec<LegalDocument>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let documents = sqlx::query!(
+            "
+            SELECT id, kind, version, content, material, published
+            FROM legal_documents
+            ORDER BY published DESC, id DESC
+            "
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| LegalDocument {
+            id: r.id,
+            kind: LegalDocumentKind::from_string(&r.kind),
+            version: r.version,
+            content: r.content,
+            material: r.material,
+            published: r.published,
+        })
+        .collect();
+
+        Ok(documents)
+    }
+
+    /// Gets the latest version of each document, and the latest material version of each if
+    /// it is older. These are read by every change users make, so they are cached together
+    async fn get_current<'a, E>(
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<Vec<LegalDocument>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let mut redis = redis.connect().await?;
+
+        if let Some(documents) = redis
+            .get_deserialized_from_json(LEGAL_DOCUMENTS_NAMESPACE, "current")
+            .await?
+        {
+            return Ok(documents);
+        }
+
+        let documents = sqlx::query!(
+            "
+            SELECT id, kind, version, content, material, published
+            FROM legal_documents d
+            WHERE version = (SELECT MAX(version) FROM legal_documents WHERE kind = d.kind)
+            OR version = (
+                SELECT MAX(version) FROM legal_documents WHERE kind = d.kind AND material
+            )
+            ORDER BY kind, version
+            "
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| LegalDocument {
+            id: r.id,
+            kind: LegalDocumentKind::from_string(&r.kind),
+            version: r.version,
+            content: r.content,
+            material: r.material,
+            published: r.published,
+        })
+        .collect::<Vec<_>>();
+
+        redis
+            .set_serialized_to_json(LEGAL_DOCUMENTS_NAMESPACE, "current", &documents, None)
+            .await?;
+
+        Ok(documents)
+    }
+
+    /// Gets the latest version of each document
+    pub async fn get_latest<'a, E>(
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<Vec<LegalDocument>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        Ok(latest_by_kind(Self::get_current(exec, redis).await?))
+    }
+
+    /// Gets the latest material version of each document, which users have to have accepted
+    pub async fn get_required<'a, E>(
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<Vec<LegalDocument>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        Ok(latest_by_kind(
+            Self::get_current(exec, redis)
+                .await?
+                .into_iter()
+                .filter(|x| x.material)
+                .collect(),
+        ))
+    }
+
+    /// Gets the documents a user still has to accept. Users accepted the versions which were
+    /// in force when they signed up, and accepting a version also covers the older ones
+    pub async fn get_pending<'a, E>(
+        user_id: UserId,
+        user_created: DateTime<Utc>,
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<Vec<LegalDocument>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres> + Copy,
+    {
+        let required = Self::get_required(exec, redis)
+            .await?
+            .into_iter()
+            .filter(|x| x.published > user_created)
+            .collect::<Vec<_>>();
+        if required.is_empty() {
+            return Ok(required);
+        }
+
+        let mut accepted: HashMap<LegalDocumentKind, i32> = HashMap::new();
+        for acceptance in Self::get_acceptances(user_id, exec).await? {
+            let version = accepted.entry(acceptance.kind).or_default();
+            *version = (*version).max(acceptance.version);
+        }
+
+        Ok(required
+            .into_iter()
+            .filter(|x| accepted.get(&x.kind).map_or(true, |y| *y < x.version))
+            .collect())
+    }
+
+    /// Records that a user accepted a version. Accepting it again keeps the first acceptance
+    pub async fn accept<'a, E>(
+        user_id: UserId,
+        document_id: i64,
+        exec: E,
+    ) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            INSERT INTO legal_acceptances (user_id, document_id)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id, document_id) DO NOTHING
+            ",
+            user_id as UserId,
+            document_id,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the versions a user accepted, most recently accepted first
+    pub async fn get_acceptances<'a, E>(
+        user_id: UserId,
+        exec: E,
+    ) -> Result<Vec<LegalAcceptance>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let acceptances = sqlx::query!(
+            "
+            SELECT la.document_id, d.kind, d.version, la.accepted
+            FROM legal_acceptances la
+            INNER JOIN legal_documents d ON d.id = la.document_id
+            WHERE la.user_id = $1
+            ORDER BY la.accepted DESC
+            ",
+            user_id as UserId,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| LegalAcceptance {
+            document_id: r.document_id,
+            kind: LegalDocumentKind::from_string(&r.kind),
+            version: r.version,
+            accepted: r.accepted,
+        })
+        .collect();
+
+        Ok(acceptances)
+    }
+
+    pub async fn clear_cache(redis: &RedisPool) -> Result<(), DatabaseError> {
+        let mut redis = redis.connect().await?;
+        redis.delete(LEGAL_DOCUMENTS_NAMESPACE, "current").await?;
+
+        Ok(())
+    }
+}
+
+/// Keeps the highest version of each kind of document
+fn latest_by_kind(documents: Vec<LegalDocument>) -> Vec<LegalDocument> {
+    let mut latest: HashMap<LegalDocumentKind, LegalDocument> = HashMap::new();
+    for document in documents {
+        match latest.get(&document.kind) {
+            Some(x) if x.version >= document.version => {}
+            _ => {
+                latest.insert(document.kind, document);
+            }
+        }
+    }
+
+    let mut latest = latest.into_values().collect::<Vec<_>>();
+    latest.sort_by_key(|x| x.kind.as_str());
+    latest
+}
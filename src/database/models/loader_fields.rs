@@ -178,6 +178,161 @@ impl Loader {
 
         Ok(result)
     }
+
+    /// Creates a loader, or updates the loader with the same name, returning its ID
+    pub async fn upsert(
+        loader: &str,
+        icon: &str,
+        metadata: &serde_json::Value,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<LoaderId, DatabaseError> {
+        let id = sqlx::query!(
+            "
+            INSERT INTO loaders (loader, icon, metadata)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (loader) DO UPDATE
+            SET icon = EXCLUDED.icon, metadata = EXCLUDED.metadata
+            RETURNING id
+            ",
+            loader,
+            icon,
+            metadata,
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+        .id;
+
+        Ok(LoaderId(id))
+    }
+
+    /// Replaces the project types a loader supports, each of them in every one of `games`
+    pub async fn set_project_types(
+        id: LoaderId,
+        project_types: &[ProjectTypeId],
+        games: &[GameId],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            DELETE FROM loaders_project_types_games
+            WHERE loader_id = $1
+            ",
+            id as LoaderId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM loaders_project_types
+            WHERE joining_loader_id = $1
+            ",
+            id as LoaderId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO loaders_project_types (joining_loader_id, joining_project_type_id)
+            SELECT $1, * FROM UNNEST($2::integer[])
+            ",
+            id as LoaderId,
+            &project_types.iter().map(|x| x.0).collect_vec(),
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let (project_type_ids, game_ids): (Vec<_>, Vec<_>) = project_types
+            .iter()
+            .cartesian_product(games)
+            .map(|(project_type, game)| (project_type.0, game.0))
+            .unzip();
+        sqlx::query!(
+            "
+            INSERT INTO loaders_project_types_games (loader_id, project_type_id, game_id)
+            SELECT $1, * FROM UNNEST($2::integer[], $3::integer[])
+            ",
+            id as LoaderId,
+            &project_type_ids[..],
+            &game_ids[..],
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Whether any version is published for the loader
+    pub async fn is_used<'a, E>(id: LoaderId, exec: E) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT EXISTS(SELECT 1 FROM loaders_versions WHERE loader_id = $1)
+            ",
+            id as LoaderId,
+        )
+        .fetch_one(exec)
+        .await?;
+
+        Ok(result.exists.unwrap_or(false))
+    }
+
+    /// Deletes a loader along with the project types and fields it supports. Loaders with
+    /// versions can't be deleted
+    pub async fn remove(
+        id: LoaderId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        Self::set_project_types(id, &[], &[], transaction).await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM loader_fields_loaders
+            WHERE loader_id = $1
+            ",
+            id as LoaderId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let result = sqlx::query!(
+            "
+            DELETE FROM loaders
+            WHERE id = $1
+            ",
+            id as LoaderId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Clears the cached loaders, and the cached fields of a loader
+    pub async fn clear_cache(
+        id: LoaderId,
+        name: &str,
+        redis: &RedisPool,
+    ) -> Result<(), DatabaseError> {
+        let mut redis = redis.connect().await?;
+
+        redis
+            .delete_many([
+                (LOADERS_LIST_NAMESPACE, Some("all".to_string())),
+                (LOADER_ID, Some(name.to_string())),
+                (LOADER_FIELDS_NAMESPACE, Some(id.0.to_string())),
+            ])
+            .await?;
+
+        Ok(())
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -190,7 +345,7 @@ pub struct LoaderField {
     pub max_val: Option<i32>,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum LoaderFieldType {
     Integer,
     Text,
@@ -500,6 +655,134 @@ impl LoaderField {
 
         Ok(result)
     }
+
+    /// Creates a loader field, or updates the field with the same name, returning its ID
+    pub async fn upsert(
+        field: &str,
+        field_type: &LoaderFieldType,
+        optional: bool,
+        min_val: Option<i32>,
+        max_val: Option<i32>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<LoaderFieldId, DatabaseError> {
+        let enum_type = match field_type {
+            LoaderFieldType::Enum(id) | LoaderFieldType::ArrayEnum(id) => Some(id.0),
+            _ => None,
+        };
+
+        let id = sqlx::query!(
+            "
+            INSERT INTO loader_fields (field, field_type, enum_type, optional, min_val, max_val)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (field) DO UPDATE
+            SET field_type = EXCLUDED.field_type, enum_type = EXCLUDED.enum_type,
+                optional = EXCLUDED.optional, min_val = EXCLUDED.min_val,
+                max_val = EXCLUDED.max_val
+            RETURNING id
+            ",
+            field,
+            field_type.to_str(),
+            enum_type,
+            optional,
+            min_val,
+            max_val,
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+        .id;
+
+        Ok(LoaderFieldId(id))
+    }
+
+    /// Replaces the loaders a field is available for
+    pub async fn set_loaders(
+        id: LoaderFieldId,
+        loader_ids: &[LoaderId],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            DELETE FROM loader_fields_loaders
+            WHERE loader_field_id = $1
+            ",
+            id as LoaderFieldId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO loader_fields_loaders (loader_id, loader_field_id)
+            SELECT *, $2 FROM UNNEST($1::integer[])
+            ",
+            &loader_ids.iter().map(|x| x.0).collect_vec(),
+            id as LoaderFieldId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Whether any version has a value for the field
+    pub async fn is_used<'a, E>(id: LoaderFieldId, exec: E) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT EXISTS(SELECT 1 FROM version_fields WHERE field_id = $1)
+            ",
+            id as LoaderFieldId,
+        )
+        .fetch_one(exec)
+        .await?;
+
+        Ok(result.exists.unwrap_or(false))
+    }
+
+    /// Deletes a loader field. Fields with values on versions can't be deleted
+    pub async fn remove(
+        id: LoaderFieldId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        Self::set_loaders(id, &[], transaction).await?;
+
+        let result = sqlx::query!(
+            "
+            DELETE FROM loader_fields
+            WHERE id = $1
+            ",
+            id as LoaderFieldId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Clears the cached fields of every loader
+    pub async fn clear_cache(
+        loader_ids: &[LoaderId],
+        redis: &RedisPool,
+    ) -> Result<(), DatabaseError> {
+        let mut redis = redis.connect().await?;
+
+        redis
+            .delete_many(
+                loader_ids
+                    .iter()
+                    .map(|x| (LOADER_FIELDS_NAMESPACE, Some(x.0.to_string())))
+                    .chain([(LOADER_FIELDS_NAMESPACE_ALL, Some(String::new()))]),
+            )
+            .await?;
+
+        Ok(())
+    }
 }
 impl LoaderFieldEnum {
     pub async fn get<'a, E>(
@@ -543,6 +826,66 @@ impl LoaderFieldEnum {
 
         Ok(result)
     }
+
+    /// Creates an enum, or updates the enum with the same name, returning its ID
+    pub async fn upsert(
+        enum_name: &str,
+        ordering: Option<i32>,
+        hidable: bool,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<LoaderFieldEnumId, DatabaseError> {
+        let updated = sqlx::query!(
+            "
+            UPDATE loader_field_enums
+            SET ordering = $2, hidable = $3
+            WHERE enum_name = $1
+            RETURNING id
+            ",
+            enum_name,
+            ordering,
+            hidable,
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
+
+        if let Some(updated) = updated {
+            return Ok(LoaderFieldEnumId(updated.id));
+        }
+
+        let id = sqlx::query!(
+            "
+            INSERT INTO loader_field_enums (enum_name, ordering, hidable)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            ",
+            enum_name,
+            ordering,
+            hidable,
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+        .id;
+
+        Ok(LoaderFieldEnumId(id))
+    }
+
+    /// Clears the cached enum and its values
+    pub async fn clear_cache(
+        id: LoaderFieldEnumId,
+        enum_name: &str,
+        redis: &RedisPool,
+    ) -> Result<(), DatabaseError> {
+        let mut redis = redis.connect().await?;
+
+        redis
+            .delete_many([
+                (LOADER_FIELD_ENUMS_ID_NAMESPACE, Some(enum_name.to_string())),
+                (LOADER_FIELD_ENUM_VALUES_NAMESPACE, Some(id.0.to_string())),
+            ])
+            .await?;
+
+        Ok(())
+    }
 }
 
 impl LoaderFieldEnumValue {
@@ -664,6 +1007,84 @@ impl LoaderFieldEnumValue {
         Ok(cachable_enum_sets)
     }
 
+    /// Creates a value of an enum, or updates the value with the same name
+    pub async fn upsert(
+        enum_id: LoaderFieldEnumId,
+        value: &str,
+        ordering: Option<i32>,
+        metadata: &serde_json::Value,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<LoaderFieldEnumValueId, DatabaseError> {
+        let id = sqlx::query!(
+            "
+            INSERT INTO loader_field_enum_values (enum_id, value, ordering, metadata)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (enum_id, value) DO UPDATE
+            SET ordering = EXCLUDED.ordering, metadata = EXCLUDED.metadata
+            RETURNING id
+            ",
+            enum_id as LoaderFieldEnumId,
+            value,
+            ordering,
+            metadata,
+        )
+        .fetch_one(&mut **transaction)
+        .await?
+        .id;
+
+        Ok(LoaderFieldEnumValueId(id))
+    }
+
+    /// Whether any version has the value of an enum set
+    pub async fn is_used<'a, E>(
+        enum_id: LoaderFieldEnumId,
+        value: &str,
+        exec: E,
+    ) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT EXISTS(
+                SELECT 1 FROM version_fields vf
+                INNER JOIN loader_field_enum_values lfev ON lfev.id = vf.enum_value
+                WHERE lfev.enum_id = $1 AND lfev.value = $2
+            )
+            ",
+            enum_id as LoaderFieldEnumId,
+            value,
+        )
+        .fetch_one(exec)
+        .await?;
+
+        Ok(result.exists.unwrap_or(false))
+    }
+
+    /// Deletes a value of an enum. Values set on versions can't be deleted
+    pub async fn remove(
+        enum_id: LoaderFieldEnumId,
+        value: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM loader_field_enum_values
+            WHERE enum_id = $1 AND value = $2
+            ",
+            enum_id as LoaderFieldEnumId,
+            value,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
     // Matches filter against metadata of enum values
     pub async fn list_filter<'a, E>(
         loader_field_enum_id: LoaderFieldEnumId,
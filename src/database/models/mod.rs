@@ -2,24 +2,55 @@ use thiserror::Error;
 
 pub mod categories;
 pub mod collection_item;
+pub mod collection_snapshot_item;
+pub mod discord_role_link_item;
+pub mod email_suppression_item;
+pub mod email_template_item;
+pub mod feature_flag_item;
+pub mod featured_item;
 pub mod flow_item;
+pub mod github_link_item;
 pub mod ids;
+pub mod image_flag_item;
 pub mod image_item;
+pub mod impersonation_item;
+pub mod inbound_webhook_item;
+pub mod job_item;
 pub mod legacy_loader_fields;
+pub mod legal_document_item;
 pub mod loader_fields;
+pub mod moderation_claim_item;
+pub mod moderation_template_item;
+pub mod monetization_enrollment_item;
+pub mod notification_delivery_item;
 pub mod notification_item;
+pub mod notification_preference_item;
+pub mod notification_webhook_item;
 pub mod oauth_client_authorization_item;
 pub mod oauth_client_item;
+pub mod oauth_client_verification_item;
 pub mod oauth_token_item;
 pub mod organization_item;
+pub mod organization_token_item;
 pub mod pat_item;
+pub mod pat_usage_item;
+pub mod payout_adjustment_item;
 pub mod payout_item;
+pub mod payout_quote_item;
+pub mod payout_schedule_item;
 pub mod project_item;
+pub mod push_subscription_item;
 pub mod report_item;
 pub mod session_item;
+pub mod shortlink_item;
+pub mod similar_project_item;
+pub mod simple_push_item;
+pub mod spam_flag_item;
+pub mod tax_form_item;
 pub mod team_item;
 pub mod thread_item;
 pub mod user_item;
+pub mod user_profile_item;
 pub mod version_item;
 
 pub use collection_item::Collection;
@@ -34,6 +65,9 @@ pub use thread_item::{Thread, ThreadMessage};
 pub use user_item::User;
 pub use version_item::Version;
 
+/// How many days deleted accounts and projects can be restored for before they are purged
+pub const DELETION_GRACE_DAYS: i32 = 30;
+
 #[derive(Error, Debug)]
 pub enum DatabaseError {
     #[error("Error while interacting with the database: {0}")]
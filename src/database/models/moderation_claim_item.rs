@@ -0,0 +1,92 @@
+use crate::database::models::{DatabaseError, ProjectId, UserId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A project a moderator is reviewing. Claims expire, so a project is not held forever by a
+/// moderator who never finishes
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ModerationClaim {
+    pub project_id: ProjectId,
+    pub user_id: UserId,
+    pub claimed: DateTime<Utc>,
+    pub expires: DateTime<Utc>,
+}
+
+impl ModerationClaim {
+    /// Claims a project until `expires`. Returns `false` if another moderator holds a claim on
+    /// it which has not expired. Claiming a project again extends the claim
+    pub async fn claim<'a, E>(
+        project_id: ProjectId,
+        user_id: UserId,
+        expires: DateTime<Utc>,
+        exec: E,
+    ) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            INSERT INTO moderation_claims (project_id, user_id, expires)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (project_id) DO UPDATE
+            SET user_id = EXCLUDED.user_id, claimed = CURRENT_TIMESTAMP, expires = EXCLUDED.expires
+            WHERE moderation_claims.user_id = EXCLUDED.user_id
+                OR moderation_claims.expires <= CURRENT_TIMESTAMP
+            ",
+            project_id as ProjectId,
+            user_id as UserId,
+            expires,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn remove<'a, E>(project_id: ProjectId, exec: E) -> Result<Option<()>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            DELETE FROM moderation_claims
+            WHERE project_id = $1
+            ",
+            project_id as ProjectId,
+        )
+        .execute(exec)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    /// Gets the claims which have not expired
+    pub async fn get_active<'a, E>(exec: E) -> Result<Vec<ModerationClaim>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let claims = sqlx::query!(
+            "
+            SELECT project_id, user_id, claimed, expires
+            FROM moderation_claims
+            WHERE expires > CURRENT_TIMESTAMP
+            "
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| ModerationClaim {
+            project_id: ProjectId(r.project_id),
+            user_id: UserId(r.user_id),
+            claimed: r.claimed,
+            expires: r.expires,
+        })
+        .collect();
+
+        Ok(claims)
+    }
+}
@@ -0,0 +1,114 @@
+use crate::database::models::DatabaseError;
+use chrono::{DateTime, Utc};
+
+/// A canned response moderators can post into threads. The body may reference variables
+/// as `{{ name }}`
+#[derive(Clone, Debug)]
+pub struct ModerationTemplate {
+    pub name: String,
+    pub title: String,
+    pub body: String,
+    /// Whether messages posted from the template are only visible to moderators
+    pub private: bool,
+    pub updated: DateTime<Utc>,
+}
+
+impl ModerationTemplate {
+    pub async fn upsert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO moderation_templates (name, title, body, private, updated)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (name) DO UPDATE
+            SET title = EXCLUDED.title, body = EXCLUDED.body,
+                private = EXCLUDED.private, updated = EXCLUDED.updated
+            ",
+            self.name,
+            self.title,
+            self.body,
+            self.private,
+            self.updated,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove(
+        name: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM moderation_templates
+            WHERE name = $1
+            ",
+            name,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+
+    pub async fn get<'a, E>(
+        name: &str,
+        exec: E,
+    ) -> Result<Option<ModerationTemplate>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT name, title, body, private, updated
+            FROM moderation_templates
+            WHERE name = $1
+            ",
+            name,
+        )
+        .fetch_optional(exec)
+        .await?;
+
+        Ok(result.map(|r| ModerationTemplate {
+            name: r.name,
+            title: r.title,
+            body: r.body,
+            private: r.private,
+            updated: r.updated,
+        }))
+    }
+
+    pub async fn get_all<'a, E>(exec: E) -> Result<Vec<ModerationTemplate>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT name, title, body, private, updated
+            FROM moderation_templates
+            ORDER BY name
+            "
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| ModerationTemplate {
+                name: r.name,
+                title: r.title,
+                body: r.body,
+                private: r.private,
+                updated: r.updated,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,127 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use crate::models::payouts::EnrollmentStatus;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A user's enrollment in the monetization program. Only users with an active enrollment
+/// earn revenue from their projects
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MonetizationEnrollment {
+    pub user_id: UserId,
+    pub status: EnrollmentStatus,
+    pub applied: DateTime<Utc>,
+    pub reviewed: Option<DateTime<Utc>>,
+    pub reviewed_by: Option<UserId>,
+    pub review_notes: Option<String>,
+}
+
+impl MonetizationEnrollment {
+    /// Records an application, replacing a previous rejected one
+    pub async fn apply(
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO monetization_enrollments (user_id, status)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET status = EXCLUDED.status, applied = CURRENT_TIMESTAMP, reviewed = NULL,
+                reviewed_by = NULL, review_notes = NULL
+            ",
+            user_id as UserId,
+            EnrollmentStatus::Pending.as_str(),
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get<'a, E>(
+        user_id: UserId,
+        exec: E,
+    ) -> Result<Option<MonetizationEnrollment>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let enrollment = sqlx::query!(
+            "
+            SELECT user_id, status, applied, reviewed, reviewed_by, review_notes
+            FROM monetization_enrollments
+            WHERE user_id = $1
+            ",
+            user_id as UserId,
+        )
+        .fetch_optional(exec)
+        .await?
+        .map(|r| MonetizationEnrollment {
+            user_id: UserId(r.user_id),
+            status: EnrollmentStatus::from_string(&r.status),
+            applied: r.applied,
+            reviewed: r.reviewed,
+            reviewed_by: r.reviewed_by.map(UserId),
+            review_notes: r.review_notes,
+        });
+
+        Ok(enrollment)
+    }
+
+    /// Gets the enrollments with a status, oldest applications first
+    pub async fn get_by_status<'a, E>(
+        status: EnrollmentStatus,
+        exec: E,
+    ) -> Result<Vec<MonetizationEnrollment>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let enrollments = sqlx::query!(
+            "
+            SELECT user_id, status, applied, reviewed, reviewed_by, review_notes
+            FROM monetization_enrollments
+            WHERE status = $1
+            ORDER BY applied ASC
+            ",
+            status.as_str(),
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| MonetizationEnrollment {
+            user_id: UserId(r.user_id),
+            status: EnrollmentStatus::from_string(&r.status),
+            applied: r.applied,
+            reviewed: r.reviewed,
+            reviewed_by: r.reviewed_by.map(UserId),
+            review_notes: r.review_notes,
+        })
+        .collect();
+
+        Ok(enrollments)
+    }
+
+    pub async fn review(
+        user_id: UserId,
+        status: EnrollmentStatus,
+        reviewed_by: UserId,
+        review_notes: Option<&str>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            UPDATE monetization_enrollments
+            SET status = $1, reviewed = CURRENT_TIMESTAMP, reviewed_by = $2, review_notes = $3
+            WHERE user_id = $4
+            ",
+            status.as_str(),
+            reviewed_by as UserId,
+            review_notes,
+            user_id as UserId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use super::ids::*;
+use super::notification_item::Notification;
+use super::notification_preference_item::NotificationPreference;
+use crate::database::models::DatabaseError;
+use crate::models::notifications::{NotificationChannel, NotificationDeliveryStatus};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A pending or completed attempt to deliver a notification through an out-of-band channel.
+/// Deliveries are queued in the same transaction as the notification itself and processed
+/// by `queue::notifications`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NotificationDelivery {
+    pub notification_id: NotificationId,
+    pub user_id: UserId,
+    pub channel: NotificationChannel,
+    pub status: NotificationDeliveryStatus,
+    pub attempt_count: i32,
+    pub next_attempt: DateTime<Utc>,
+}
+
+impl NotificationDelivery {
+    /// Queues deliveries for every channel the recipients have set up and enabled. Deliveries
+    /// of notifications which were already delivered are queued again, for coalesced updates
+    pub async fn insert_for_notifications(
+        notifications: &[Notification],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        if notifications.is_empty() {
+            return Ok(());
+        }
+
+        let user_ids = notifications
+            .iter()
+            .map(|x| x.user_id)
+            .unique()
+            .collect::<Vec<_>>();
+
+        let preferences =
+            NotificationPreference::get_many_user(&user_ids, &mut **transaction).await?;
+
+        let mut deliveries = Vec::new();
+        for channel in NotificationChannel::iterator().filter(|x| x.is_delivered()) {
+            let configured_users =
+                Self::get_configured_users(channel, &user_ids, transaction).await?;
+
+            for notification in notifications {
+                if configured_users.contains(&notification.user_id)
+                    && NotificationPreference::is_enabled(
+                        &preferences,
+                        notification.user_id,
+                        channel,
+                        notification.body.notification_type(),
+                    )
+                {
+                    deliveries.push(NotificationDelivery {
+                        notification_id: notification.id,
+                        user_id: notification.user_id,
+                        channel,
+                        status: NotificationDeliveryStatus::Pending,
+                        attempt_count: 0,
+                        next_attempt: Utc::now(),
+                    });
+                }
+            }
+        }
+
+        Self::insert_many(&deliveries, transaction).await
+    }
+
+    /// Returns the subset of users which have set up a destination for this channel
+    async fn get_configured_users(
+        channel: NotificationChannel,
+        user_ids: &[UserId],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<HashSet<UserId>, DatabaseError> {
+        let user_ids_parsed = user_ids.iter().map(|x| x.0).collect::<Vec<_>>();
+
+        let users = match channel {
+            NotificationChannel::Site => user_ids.iter().copied().collect(),
+            NotificationChannel::WebPush => {
+                sqlx::query!(
+                    "
+                    SELECT DISTINCT user_id
+                    FROM users_push_subscriptions
+                    WHERE user_id = ANY($1)
+                    ",
+                    &user_ids_parsed,
+                )
+                .fetch_many(&mut **transaction)
+                .try_filter_map(|e| async { Ok(e.right().map(|x| UserId(x.user_id))) })
+                .try_collect::<HashSet<UserId>>()
+                .await?
+            }
+            NotificationChannel::Webhook => {
+                sqlx::query!(
+                    "
+                    SELECT user_id
+                    FROM users_notification_webhooks
+                    WHERE user_id = ANY($1)
+                    ",
+                    &user_ids_parsed,
+                )
+                .fetch_many(&mut **transaction)
+                .try_filter_map(|e| async { Ok(e.right().map(|x| UserId(x.user_id))) })
+                .try_collect::<HashSet<UserId>>()
+                .await?
+            }
+            NotificationChannel::SimplePush => {
+                sqlx::query!(
+                    "
+                    SELECT user_id
+                    FROM users_simple_push_targets
+                    WHERE user_id = ANY($1)
+                    ",
+                    &user_ids_parsed,
+                )
+                .fetch_many(&mut **transaction)
+                .try_filter_map(|e| async { Ok(e.right().map(|x| UserId(x.user_id))) })
+                .try_collect::<HashSet<UserId>>()
+                .await?
+            }
+        };
+
+        Ok(users)
+    }
+
+    pub async fn insert_many(
+        deliveries: &[NotificationDelivery],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        if deliveries.is_empty() {
+            return Ok(());
+        }
+
+        let notification_ids = deliveries.iter().map(|x| x.notification_id.0).collect_vec();
+        let user_ids = deliveries.iter().map(|x| x.user_id.0).collect_vec();
+        let channels = deliveries
+            .iter()
+            .map(|x| x.channel.as_str().to_string())
+            .collect_vec();
+        let statuses = deliveries
+            .iter()
+            .map(|x| x.status.as_str().to_string())
+            .collect_vec();
+        let attempt_counts = deliveries.iter().map(|x| x.attempt_count).collect_vec();
+        let next_attempts = deliveries.iter().map(|x| x.next_attempt).collect_vec();
+
+        sqlx::query!(
+            "
+            INSERT INTO notifications_deliveries (
+                notification_id, user_id, channel, status, attempt_count, next_attempt
+            )
+            SELECT * FROM UNNEST($1::bigint[], $2::bigint[], $3::varchar[], $4::varchar[], $5::integer[], $6::timestamptz[])
+            ON CONFLICT (notification_id, channel) DO UPDATE
+            SET status = EXCLUDED.status, attempt_count = EXCLUDED.attempt_count, next_attempt = EXCLUDED.next_attempt
+            ",
+            &notification_ids[..],
+            &user_ids[..],
+            &channels[..],
+            &statuses[..],
+            &attempt_counts[..],
+            &next_attempts[..],
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Claims up to `limit` deliveries which are due by moving their next attempt to
+    /// `leased_until`, so no other worker picks them up while they are being sent. Deliveries
+    /// whose worker stops before recording the outcome become due again once the lease ends.
+    pub async fn claim_due(
+        limit: i64,
+        leased_until: DateTime<Utc>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Vec<NotificationDelivery>, DatabaseError> {
+        let deliveries = sqlx::query!(
+            "
+            UPDATE notifications_deliveries nd
+            SET next_attempt = $3
+            FROM (
+                SELECT notification_id, channel
+                FROM notifications_deliveries
+                WHERE status = $1 AND next_attempt <= CURRENT_TIMESTAMP
+                ORDER BY next_attempt ASC
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            ) due
+            WHERE nd.notification_id = due.notification_id AND nd.channel = due.channel
+            RETURNING nd.notification_id, nd.user_id, nd.channel, nd.status, nd.attempt_count, nd.next_attempt
+            ",
+            NotificationDeliveryStatus::Pending.as_str(),
+            limit,
+            leased_until,
+        )
+        .fetch_many(&mut **transaction)
+        .try_filter_map(|e| async {
+            Ok(e.right().and_then(|r| {
+                Some(NotificationDelivery {
+                    notification_id: NotificationId(r.notification_id),
+                    user_id: UserId(r.user_id),
+                    channel: NotificationChannel::from_string(&r.channel)?,
+                    status: NotificationDeliveryStatus::from_string(&r.status),
+                    attempt_count: r.attempt_count,
+                    next_attempt: r.next_attempt,
+                })
+            }))
+        })
+        .try_collect::<Vec<NotificationDelivery>>()
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Records the outcome of a claimed delivery. Nothing is changed if the delivery was queued
+    /// again after it was claimed, so the new attempt is not lost
+    pub async fn update(
+        &self,
+        leased_until: DateTime<Utc>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            UPDATE notifications_deliveries
+            SET status = $3, attempt_count = $4, next_attempt = $5
+            WHERE notification_id = $1 AND channel = $2 AND next_attempt = $6
+            ",
+            self.notification_id as NotificationId,
+            self.channel.as_str(),
+            self.status.as_str(),
+            self.attempt_count,
+            self.next_attempt,
+            leased_until,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1,12 +1,19 @@
 use super::ids::*;
+use super::notification_delivery_item::NotificationDelivery;
+use super::notification_preference_item::NotificationPreference;
 use crate::database::{models::DatabaseError, redis::RedisPool};
-use crate::models::notifications::NotificationBody;
+use crate::models::notifications::{
+    NotificationBody, NotificationChannel, NotificationGroupItem, NotificationType,
+};
+use crate::util::cursor::Cursor;
 use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 const USER_NOTIFICATIONS_NAMESPACE: &str = "user_notifications";
+/// The most notifications coalesced into one. Later ones start a new group
+const MAX_GROUP_ITEMS: i32 = 50;
 
 pub struct NotificationBuilder {
     pub body: NotificationBody,
@@ -19,6 +26,31 @@ pub struct Notification {
     pub body: NotificationBody,
     pub read: bool,
     pub created: DateTime<Utc>,
+    /// Notifications sharing a group key are coalesced while the existing one is unread
+    #[serde(default)]
+    pub group_key: Option<String>,
+    /// Every notification coalesced into this one, including the first. Empty if this
+    /// notification has never been grouped
+    #[serde(default)]
+    pub group_items: Vec<NotificationGroupItem>,
+}
+
+/// Narrows down which of a user's notifications are listed, read or deleted. Unset fields
+/// match every notification
+#[derive(Default)]
+pub struct NotificationFilter {
+    pub types: Option<Vec<NotificationType>>,
+    pub read: Option<bool>,
+    /// Only matches notifications created before this time
+    pub before: Option<DateTime<Utc>>,
+}
+
+impl NotificationFilter {
+    fn types(&self) -> Option<Vec<String>> {
+        self.types
+            .as_ref()
+            .map(|x| x.iter().map(|x| x.as_str().to_string()).collect())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -46,6 +78,37 @@ impl NotificationBuilder {
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         redis: &RedisPool,
     ) -> Result<(), DatabaseError> {
+        // Skip users who have turned off this type of notification entirely
+        let preferences = NotificationPreference::get_many_user(&users, &mut **transaction).await?;
+        let notification_type = self.body.notification_type();
+        let users = users
+            .into_iter()
+            .filter(|user| {
+                NotificationPreference::is_enabled(
+                    &preferences,
+                    *user,
+                    NotificationChannel::Site,
+                    notification_type,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let (group_key, users) = if let Some((group_key, window)) = self.body.coalescing_rule() {
+            let grouped_users =
+                Notification::coalesce(&group_key, window, &self.body, &users, transaction, redis)
+                    .await?;
+
+            (
+                Some(group_key),
+                users
+                    .into_iter()
+                    .filter(|x| !grouped_users.contains(x))
+                    .collect(),
+            )
+        } else {
+            (None, users)
+        };
+
         let mut notifications = Vec::new();
         for user in users {
             let id = generate_notification_id(&mut *transaction).await?;
@@ -56,6 +119,8 @@ impl NotificationBuilder {
                 body: self.body.clone(),
                 read: false,
                 created: Utc::now(),
+                group_key: group_key.clone(),
+                group_items: Vec::new(),
             });
         }
 
@@ -77,22 +142,29 @@ impl Notification {
             .iter()
             .map(|n| Ok(serde_json::value::to_value(n.body.clone())?))
             .collect::<Result<Vec<_>, DatabaseError>>()?;
+        let group_keys = notifications
+            .iter()
+            .map(|n| n.group_key.clone())
+            .collect_vec();
         sqlx::query!(
             "
             INSERT INTO notifications (
-                id, user_id, body
+                id, user_id, body, group_key
             )
-            SELECT * FROM UNNEST($1::bigint[], $2::bigint[], $3::jsonb[])
+            SELECT * FROM UNNEST($1::bigint[], $2::bigint[], $3::jsonb[], $4::varchar[])
             ",
             &notification_ids[..],
             &user_ids[..],
             &bodies[..],
+            &group_keys[..] as &[Option<String>],
         )
         .execute(&mut **transaction)
         .await?;
 
+        NotificationDelivery::insert_for_notifications(notifications, transaction).await?;
+
         Notification::clear_user_notifications_cache(
-            notifications.iter().map(|n| &n.user_id),
+            &notifications.iter().map(|n| n.user_id).collect_vec(),
             redis,
         )
         .await?;
@@ -100,6 +172,69 @@ impl Notification {
         Ok(())
     }
 
+    /// Merges `body` into the users' unread notifications with the same group key whose first
+    /// item was created within `window`, returning the users whose notifications were
+    /// coalesced. Deliveries are queued again for the updated notifications
+    pub async fn coalesce(
+        group_key: &str,
+        window: chrono::Duration,
+        body: &NotificationBody,
+        users: &[UserId],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        redis: &RedisPool,
+    ) -> Result<Vec<UserId>, DatabaseError> {
+        let user_ids = users.iter().map(|x| x.0).collect_vec();
+
+        let grouped = sqlx::query!(
+            "
+            UPDATE notifications
+            SET
+                group_items = (
+                    CASE WHEN jsonb_array_length(group_items) = 0
+                    THEN jsonb_build_array(jsonb_build_object('body', body, 'created', created))
+                    ELSE group_items END
+                ) || jsonb_build_array(jsonb_build_object('body', $4::jsonb, 'created', CURRENT_TIMESTAMP)),
+                body = $4,
+                created = CURRENT_TIMESTAMP
+            WHERE id IN (
+                SELECT DISTINCT ON (user_id) id
+                FROM notifications
+                WHERE user_id = ANY($1) AND group_key = $2 AND read = FALSE
+                AND COALESCE((group_items -> 0 ->> 'created')::timestamptz, created) > $3
+                AND jsonb_array_length(group_items) < $5
+                ORDER BY user_id, created DESC
+            )
+            RETURNING id, user_id, created, group_items
+            ",
+            &user_ids[..],
+            group_key,
+            Utc::now() - window,
+            serde_json::to_value(body)?,
+            MAX_GROUP_ITEMS,
+        )
+        .fetch_many(&mut **transaction)
+        .try_filter_map(|e| async {
+            Ok(e.right().map(|x| Notification {
+                id: NotificationId(x.id),
+                user_id: UserId(x.user_id),
+                body: body.clone(),
+                read: false,
+                created: x.created,
+                group_key: Some(group_key.to_string()),
+                group_items: serde_json::from_value(x.group_items).unwrap_or_default(),
+            }))
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+
+        NotificationDelivery::insert_for_notifications(&grouped, transaction).await?;
+
+        let grouped_users = grouped.iter().map(|x| x.user_id).collect_vec();
+        Notification::clear_user_notifications_cache(grouped_users.iter(), redis).await?;
+
+        Ok(grouped_users)
+    }
+
     pub async fn get<'a, 'b, E>(
         id: NotificationId,
         executor: E,
@@ -122,7 +257,7 @@ impl Notification {
         let notification_ids_parsed: Vec<i64> = notification_ids.iter().map(|x| x.0).collect();
         sqlx::query!(
             "
-            SELECT n.id, n.user_id, n.name, n.text, n.link, n.created, n.read, n.type notification_type, n.body,
+            SELECT n.id, n.user_id, n.name, n.text, n.link, n.created, n.read, n.type notification_type, n.body, n.group_key, n.group_items,
             JSONB_AGG(DISTINCT jsonb_build_object('id', na.id, 'notification_id', na.notification_id, 'name', na.name, 'action_route_method', na.action_route_method, 'action_route', na.action_route)) filter (where na.id is not null) actions
             FROM notifications n
             LEFT OUTER JOIN notifications_actions na on n.id = na.notification_id
@@ -136,12 +271,17 @@ impl Notification {
             .try_filter_map(|e| async {
                 Ok(e.right().map(|row| {
                     let id = NotificationId(row.id);
+                    // The legacy body below takes the rest of the row
+                    let group_key = row.group_key.clone();
+                    let group_items = serde_json::from_value(row.group_items.clone()).unwrap_or_default();
 
                     Notification {
                         id,
                         user_id: UserId(row.user_id),
                         read: row.read,
                         created: row.created,
+                        group_key,
+                        group_items,
                         body: row.body.clone().and_then(|x| serde_json::from_value(x).ok()).unwrap_or_else(|| {
                             if let Some(name) = row.name {
                                 NotificationBody::LegacyMarkdown {
@@ -186,7 +326,7 @@ impl Notification {
 
         let db_notifications = sqlx::query!(
             "
-            SELECT n.id, n.user_id, n.name, n.text, n.link, n.created, n.read, n.type notification_type, n.body,
+            SELECT n.id, n.user_id, n.name, n.text, n.link, n.created, n.read, n.type notification_type, n.body, n.group_key, n.group_items,
             JSONB_AGG(DISTINCT jsonb_build_object('id', na.id, 'notification_id', na.notification_id, 'name', na.name, 'action_route_method', na.action_route_method, 'action_route', na.action_route)) filter (where na.id is not null) actions
             FROM notifications n
             LEFT OUTER JOIN notifications_actions na on n.id = na.notification_id
@@ -199,12 +339,17 @@ impl Notification {
             .try_filter_map(|e| async {
                 Ok(e.right().map(|row| {
                     let id = NotificationId(row.id);
+                    // The legacy body below takes the rest of the row
+                    let group_key = row.group_key.clone();
+                    let group_items = serde_json::from_value(row.group_items.clone()).unwrap_or_default();
 
                     Notification {
                         id,
                         user_id: UserId(row.user_id),
                         read: row.read,
                         created: row.created,
+                        group_key,
+                        group_items,
                         body: row.body.clone().and_then(|x| serde_json::from_value(x).ok()).unwrap_or_else(|| {
                             if let Some(name) = row.name {
                                 NotificationBody::LegacyMarkdown {
@@ -240,6 +385,128 @@ impl Notification {
         Ok(db_notifications)
     }
 
+    /// Gets one page of a user's notifications, newest first, starting after `cursor`. Returns
+    /// the cursor of the next page alongside the notifications, if there is one
+    pub async fn get_page_user<'a, E>(
+        user_id: UserId,
+        filter: &NotificationFilter,
+        cursor: Option<Cursor>,
+        limit: i64,
+        exec: E,
+    ) -> Result<(Vec<Notification>, Option<Cursor>), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres> + Copy,
+    {
+        let types = filter.types();
+        let mut keys = sqlx::query!(
+            "
+            SELECT n.id, n.created
+            FROM notifications n
+            WHERE n.user_id = $1
+            AND ($2::timestamptz IS NULL OR (n.created, n.id) < ($2, $3))
+            AND ($4::text[] IS NULL OR COALESCE(n.body->>'type', n.type) = ANY($4))
+            AND ($5::boolean IS NULL OR n.read = $5)
+            AND ($6::timestamptz IS NULL OR n.created < $6)
+            ORDER BY n.created DESC, n.id DESC
+            LIMIT $7
+            ",
+            user_id as UserId,
+            cursor.map(|x| x.timestamp),
+            cursor.map(|x| x.id),
+            types.as_deref(),
+            filter.read,
+            filter.before,
+            // Fetch one extra row to know whether there is a next page
+            limit + 1,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async { Ok(e.right().map(|x| Cursor::new(x.created, x.id))) })
+        .try_collect::<Vec<Cursor>>()
+        .await?;
+
+        let next_cursor = if keys.len() as i64 > limit {
+            keys.truncate(limit as usize);
+            keys.last().copied()
+        } else {
+            None
+        };
+
+        let mut notifications = Notification::get_many(
+            &keys.iter().map(|x| NotificationId(x.id)).collect_vec(),
+            exec,
+        )
+        .await?;
+        notifications.sort_by(|a, b| (b.created, b.id.0).cmp(&(a.created, a.id.0)));
+
+        Ok((notifications, next_cursor))
+    }
+
+    /// Marks all of a user's notifications matching the filter as read, returning how many
+    /// were changed
+    pub async fn read_many_user(
+        user_id: UserId,
+        filter: &NotificationFilter,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        redis: &RedisPool,
+    ) -> Result<u64, DatabaseError> {
+        let types = filter.types();
+        let result = sqlx::query!(
+            "
+            UPDATE notifications n
+            SET read = TRUE
+            WHERE n.user_id = $1 AND n.read = FALSE
+            AND ($2::text[] IS NULL OR COALESCE(n.body->>'type', n.type) = ANY($2))
+            AND ($3::timestamptz IS NULL OR n.created < $3)
+            ",
+            user_id as UserId,
+            types.as_deref(),
+            filter.before,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Notification::clear_user_notifications_cache([&user_id], redis).await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes all of a user's notifications matching the filter, returning how many were
+    /// deleted
+    pub async fn remove_many_user(
+        user_id: UserId,
+        filter: &NotificationFilter,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        redis: &RedisPool,
+    ) -> Result<u64, DatabaseError> {
+        let types = filter.types();
+        let notification_ids = sqlx::query!(
+            "
+            SELECT n.id
+            FROM notifications n
+            WHERE n.user_id = $1
+            AND ($2::text[] IS NULL OR COALESCE(n.body->>'type', n.type) = ANY($2))
+            AND ($3::boolean IS NULL OR n.read = $3)
+            AND ($4::timestamptz IS NULL OR n.created < $4)
+            ",
+            user_id as UserId,
+            types.as_deref(),
+            filter.read,
+            filter.before,
+        )
+        .fetch_many(&mut **transaction)
+        .try_filter_map(|e| async { Ok(e.right().map(|x| NotificationId(x.id))) })
+        .try_collect::<Vec<NotificationId>>()
+        .await?;
+
+        if notification_ids.is_empty() {
+            return Ok(0);
+        }
+
+        Notification::remove_many(&notification_ids, transaction, redis).await?;
+
+        Ok(notification_ids.len() as u64)
+    }
+
     pub async fn read(
         id: NotificationId,
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
@@ -323,13 +590,12 @@ impl Notification {
     ) -> Result<(), DatabaseError> {
         let mut redis = redis.connect().await?;
 
-        redis
-            .delete_many(
-                user_ids
-                    .into_iter()
-                    .map(|id| (USER_NOTIFICATIONS_NAMESPACE, Some(id.0.to_string()))),
-            )
-            .await?;
+        // Collected first, as the futures of some callers must be `Send`
+        let keys = user_ids
+            .into_iter()
+            .map(|id| (USER_NOTIFICATIONS_NAMESPACE, Some(id.0.to_string())))
+            .collect_vec();
+        redis.delete_many(keys).await?;
 
         Ok(())
     }
@@ -0,0 +1,113 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use crate::models::notifications::{NotificationChannel, NotificationType};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NotificationPreference {
+    pub user_id: UserId,
+    pub channel: NotificationChannel,
+    pub notification_type: Option<NotificationType>,
+    pub enabled: bool,
+}
+
+impl NotificationPreference {
+    /// Inserts this preference, replacing any existing preference for the same
+    /// user, channel and notification type
+    pub async fn upsert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            DELETE FROM users_notifications_preferences
+            WHERE user_id = $1 AND channel = $2 AND COALESCE(notification_type, '') = COALESCE($3, '')
+            ",
+            self.user_id as UserId,
+            self.channel.as_str(),
+            self.notification_type.map(|x| x.as_str()),
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO users_notifications_preferences (
+                user_id, channel, notification_type, enabled
+            )
+            VALUES (
+                $1, $2, $3, $4
+            )
+            ",
+            self.user_id as UserId,
+            self.channel.as_str(),
+            self.notification_type.map(|x| x.as_str()),
+            self.enabled,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_many_user<'a, E>(
+        user_ids: &[UserId],
+        exec: E,
+    ) -> Result<Vec<NotificationPreference>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        use futures::TryStreamExt;
+
+        let user_ids_parsed: Vec<i64> = user_ids.iter().map(|x| x.0).collect();
+        let preferences = sqlx::query!(
+            "
+            SELECT user_id, channel, notification_type, enabled
+            FROM users_notifications_preferences
+            WHERE user_id = ANY($1)
+            ",
+            &user_ids_parsed,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().and_then(|r| {
+                Some(NotificationPreference {
+                    user_id: UserId(r.user_id),
+                    channel: NotificationChannel::from_string(&r.channel)?,
+                    notification_type: r
+                        .notification_type
+                        .map(|x| NotificationType::from_string(&x)),
+                    enabled: r.enabled,
+                })
+            }))
+        })
+        .try_collect::<Vec<NotificationPreference>>()
+        .await?;
+
+        Ok(preferences)
+    }
+
+    /// Resolves whether a channel is enabled for a notification type, preferring a preference
+    /// for the exact type, then a preference for all types, and finally the channel default
+    pub fn is_enabled(
+        preferences: &[NotificationPreference],
+        user_id: UserId,
+        channel: NotificationChannel,
+        notification_type: NotificationType,
+    ) -> bool {
+        let mut user_preferences = preferences
+            .iter()
+            .filter(|x| x.user_id == user_id && x.channel == channel);
+
+        if let Some(preference) = user_preferences
+            .clone()
+            .find(|x| x.notification_type == Some(notification_type))
+        {
+            preference.enabled
+        } else if let Some(preference) = user_preferences.find(|x| x.notification_type.is_none()) {
+            preference.enabled
+        } else {
+            channel.enabled_by_default()
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A user's personal webhook, such as a Discord or Slack webhook URL, which receives their
+/// notifications
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NotificationWebhook {
+    pub user_id: UserId,
+    pub url: String,
+    /// Shared secret deliveries are signed with
+    pub secret: String,
+    pub created: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+impl NotificationWebhook {
+    /// Inserts this webhook, replacing the user's existing webhook if they have one
+    pub async fn upsert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO users_notification_webhooks (
+                user_id, url, secret
+            )
+            VALUES (
+                $1, $2, $3
+            )
+            ON CONFLICT (user_id) DO UPDATE
+            SET url = EXCLUDED.url, secret = EXCLUDED.secret,
+                created = CURRENT_TIMESTAMP, last_used = NULL
+            ",
+            self.user_id as UserId,
+            self.url,
+            self.secret,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_many_user<'a, E>(
+        user_ids: &[UserId],
+        exec: E,
+    ) -> Result<Vec<NotificationWebhook>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        use futures::TryStreamExt;
+
+        let user_ids_parsed: Vec<i64> = user_ids.iter().map(|x| x.0).collect();
+        let webhooks = sqlx::query!(
+            "
+            SELECT user_id, url, secret, created, last_used
+            FROM users_notification_webhooks
+            WHERE user_id = ANY($1)
+            ",
+            &user_ids_parsed,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().map(|r| NotificationWebhook {
+                user_id: UserId(r.user_id),
+                url: r.url,
+                secret: r.secret,
+                created: r.created,
+                last_used: r.last_used,
+            }))
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    pub async fn mark_used(
+        user_ids: &[UserId],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        if user_ids.is_empty() {
+            return Ok(());
+        }
+
+        let user_ids_parsed: Vec<i64> = user_ids.iter().map(|x| x.0).collect();
+        sqlx::query!(
+            "
+            UPDATE users_notification_webhooks
+            SET last_used = CURRENT_TIMESTAMP
+            WHERE user_id = ANY($1)
+            ",
+            &user_ids_parsed,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove(
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM users_notification_webhooks
+            WHERE user_id = $1
+            ",
+            user_id as UserId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+}
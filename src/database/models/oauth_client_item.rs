@@ -25,6 +25,8 @@ pub struct OAuthClient {
     pub created_by: UserId,
     pub url: Option<String>,
     pub description: Option<String>,
+    /// Stored separately inside oauth_client_verifications table
+    pub verified: bool,
 }
 
 struct ClientQueryResult {
@@ -37,6 +39,7 @@ struct ClientQueryResult {
     created_by: i64,
     url: Option<String>,
     description: Option<String>,
+    verified: bool,
     uri_ids: Option<Vec<i64>>,
     uri_vals: Option<Vec<String>>,
 }
@@ -59,6 +62,7 @@ macro_rules! select_clients_with_predicate {
                 clients.created_by as "created_by!",
                 clients.url as "url?",
                 clients.description as "description?",
+                COALESCE(verifications.status = 'verified', FALSE) as "verified!",
                 uris.uri_ids as "uri_ids?",
                 uris.uri_vals as "uri_vals?"
             FROM oauth_clients clients
@@ -67,6 +71,8 @@ macro_rules! select_clients_with_predicate {
                 FROM oauth_client_redirect_uris
                 GROUP BY client_id
             ) uris ON clients.id = uris.client_id
+            LEFT JOIN oauth_client_verifications verifications
+            ON clients.id = verifications.client_id
             "#
                 + $predicate,
             $param
@@ -250,6 +256,7 @@ impl From<ClientQueryResult> for OAuthClient {
             created_by: UserId(r.created_by),
             url: r.url,
             description: r.description,
+            verified: r.verified,
         }
     }
 }
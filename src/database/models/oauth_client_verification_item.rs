@@ -0,0 +1,132 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use crate::models::oauth_clients::OAuthClientVerificationStatus;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// An OAuth app's verification. Only verified apps get the approved app rate limit and can
+/// request sensitive scopes
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OAuthClientVerification {
+    pub client_id: OAuthClientId,
+    pub status: OAuthClientVerificationStatus,
+    pub reason: String,
+    pub applied: DateTime<Utc>,
+    pub reviewed: Option<DateTime<Utc>>,
+    pub reviewed_by: Option<UserId>,
+    pub review_notes: Option<String>,
+}
+
+impl OAuthClientVerification {
+    /// Records an application, replacing a previous rejected one
+    pub async fn apply(
+        client_id: OAuthClientId,
+        reason: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO oauth_client_verifications (client_id, status, reason)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (client_id) DO UPDATE
+            SET status = EXCLUDED.status, reason = EXCLUDED.reason, applied = CURRENT_TIMESTAMP,
+                reviewed = NULL, reviewed_by = NULL, review_notes = NULL
+            ",
+            client_id as OAuthClientId,
+            OAuthClientVerificationStatus::Pending.as_str(),
+            reason,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get<'a, E>(
+        client_id: OAuthClientId,
+        exec: E,
+    ) -> Result<Option<OAuthClientVerification>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let verification = sqlx::query!(
+            "
+            SELECT client_id, status, reason, applied, reviewed, reviewed_by, review_notes
+            FROM oauth_client_verifications
+            WHERE client_id = $1
+            ",
+            client_id as OAuthClientId,
+        )
+        .fetch_optional(exec)
+        .await?
+        .map(|r| OAuthClientVerification {
+            client_id: OAuthClientId(r.client_id),
+            status: OAuthClientVerificationStatus::from_string(&r.status),
+            reason: r.reason,
+            applied: r.applied,
+            reviewed: r.reviewed,
+            reviewed_by: r.reviewed_by.map(UserId),
+            review_notes: r.review_notes,
+        });
+
+        Ok(verification)
+    }
+
+    /// Gets the verifications with a status, oldest applications first
+    pub async fn get_by_status<'a, E>(
+        status: OAuthClientVerificationStatus,
+        exec: E,
+    ) -> Result<Vec<OAuthClientVerification>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let verifications = sqlx::query!(
+            "
+            SELECT client_id, status, reason, applied, reviewed, reviewed_by, review_notes
+            FROM oauth_client_verifications
+            WHERE status = $1
+            ORDER BY applied ASC
+            ",
+            status.as_str(),
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| OAuthClientVerification {
+            client_id: OAuthClientId(r.client_id),
+            status: OAuthClientVerificationStatus::from_string(&r.status),
+            reason: r.reason,
+            applied: r.applied,
+            reviewed: r.reviewed,
+            reviewed_by: r.reviewed_by.map(UserId),
+            review_notes: r.review_notes,
+        })
+        .collect();
+
+        Ok(verifications)
+    }
+
+    pub async fn review(
+        client_id: OAuthClientId,
+        status: OAuthClientVerificationStatus,
+        reviewed_by: UserId,
+        review_notes: Option<&str>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            UPDATE oauth_client_verifications
+            SET status = $1, reviewed = CURRENT_TIMESTAMP, reviewed_by = $2, review_notes = $3
+            WHERE client_id = $4
+            ",
+            status.as_str(),
+            reviewed_by as UserId,
+            review_notes,
+            client_id as OAuthClientId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
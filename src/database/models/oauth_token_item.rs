@@ -1,9 +1,15 @@
 use super::{DatabaseError, OAuthAccessTokenId, OAuthClientAuthorizationId, OAuthClientId, UserId};
+use crate::database::redis::RedisPool;
 use crate::models::pats::Scopes;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
 
+const OAUTH_ACCESS_TOKENS_NAMESPACE: &str = "oauth_access_tokens";
+/// How long looked up tokens are cached for, in seconds. Cached tokens are only used where
+/// acting on a token for a short while after it was revoked does no harm, such as rate limiting
+const OAUTH_ACCESS_TOKENS_EXPIRY: i64 = 5 * 60;
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct OAuthAccessToken {
     pub id: OAuthAccessTokenId,
@@ -17,6 +23,9 @@ pub struct OAuthAccessToken {
     // Stored separately inside oauth_client_authorizations table
     pub client_id: OAuthClientId,
     pub user_id: UserId,
+
+    // Stored separately inside oauth_client_verifications table
+    pub client_verified: bool,
 }
 
 impl OAuthAccessToken {
@@ -25,7 +34,7 @@ impl OAuthAccessToken {
         exec: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
     ) -> Result<Option<OAuthAccessToken>, DatabaseError> {
         let value = sqlx::query!(
-            "
+            r#"
             SELECT
                 tokens.id,
                 tokens.authorization_id,
@@ -35,12 +44,15 @@ impl OAuthAccessToken {
                 tokens.expires,
                 tokens.last_used,
                 auths.client_id,
-                auths.user_id
+                auths.user_id,
+                COALESCE(verifications.status = 'verified', FALSE) as "client_verified!"
             FROM oauth_access_tokens tokens
             JOIN oauth_client_authorizations auths
             ON tokens.authorization_id = auths.id
+            LEFT JOIN oauth_client_verifications verifications
+            ON auths.client_id = verifications.client_id
             WHERE tokens.token_hash = $1
-            ",
+            "#,
             token_hash
         )
         .fetch_optional(exec)
@@ -56,9 +68,44 @@ impl OAuthAccessToken {
             last_used: r.last_used,
             client_id: OAuthClientId(r.client_id),
             user_id: UserId(r.user_id),
+            client_verified: r.client_verified,
         }))
     }
 
+    /// Gets a token through a short lived cache. Revoked tokens may still be returned until
+    /// the cache expires, so this must not be used to authenticate requests
+    pub async fn get_cached(
+        token_hash: String,
+        exec: impl sqlx::Executor<'_, Database = sqlx::Postgres>,
+        redis: &RedisPool,
+    ) -> Result<Option<OAuthAccessToken>, DatabaseError> {
+        let mut redis = redis.connect().await?;
+
+        if let Some(token) = redis
+            .get_deserialized_from_json::<OAuthAccessToken>(
+                OAUTH_ACCESS_TOKENS_NAMESPACE,
+                &token_hash,
+            )
+            .await?
+        {
+            return Ok(Some(token));
+        }
+
+        let token = Self::get(token_hash, exec).await?;
+        if let Some(token) = &token {
+            redis
+                .set_serialized_to_json(
+                    OAUTH_ACCESS_TOKENS_NAMESPACE,
+                    &token.token_hash,
+                    token,
+                    Some(OAUTH_ACCESS_TOKENS_EXPIRY),
+                )
+                .await?;
+        }
+
+        Ok(token)
+    }
+
     /// Inserts and returns the time until the token expires
     pub async fn insert(
         &self,
@@ -97,6 +97,7 @@ impl Organization {
         Self::get_many(&ids, exec, redis).await
     }
 
+    #[tracing::instrument(skip_all, fields(count = organization_strings.len()))]
     pub async fn get_many<'a, E, T: ToString>(
         organization_strings: &[T],
         exec: E,
@@ -138,6 +139,9 @@ impl Organization {
                 .flatten()
                 .collect(),
         );
+        // An organization asked for by both its ID and slug is only returned once
+        organization_ids.sort_unstable();
+        organization_ids.dedup();
 
         if !organization_ids.is_empty() {
             let organizations = redis
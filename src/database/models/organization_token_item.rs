@@ -0,0 +1,88 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use serde::{Deserialize, Serialize};
+
+/// Links a personal access token to the organization owning it. The token's user is the
+/// token's own account, which is a member of the organization's team
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OrganizationToken {
+    pub pat_id: PatId,
+    pub organization_id: OrganizationId,
+    pub created_by: Option<UserId>,
+}
+
+impl OrganizationToken {
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO organization_tokens (pat_id, organization_id, created_by)
+            VALUES ($1, $2, $3)
+            ",
+            self.pat_id as PatId,
+            self.organization_id as OrganizationId,
+            self.created_by.map(|x| x.0),
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get<'a, E>(
+        pat_id: PatId,
+        exec: E,
+    ) -> Result<Option<OrganizationToken>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let token = sqlx::query!(
+            "
+            SELECT pat_id, organization_id, created_by
+            FROM organization_tokens
+            WHERE pat_id = $1
+            ",
+            pat_id as PatId,
+        )
+        .fetch_optional(exec)
+        .await?
+        .map(|r| OrganizationToken {
+            pat_id: PatId(r.pat_id),
+            organization_id: OrganizationId(r.organization_id),
+            created_by: r.created_by.map(UserId),
+        });
+
+        Ok(token)
+    }
+
+    pub async fn get_many_organization<'a, E>(
+        organization_id: OrganizationId,
+        exec: E,
+    ) -> Result<Vec<OrganizationToken>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let tokens = sqlx::query!(
+            "
+            SELECT pat_id, organization_id, created_by
+            FROM organization_tokens
+            WHERE organization_id = $1
+            ORDER BY pat_id
+            ",
+            organization_id as OrganizationId,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| OrganizationToken {
+            pat_id: PatId(r.pat_id),
+            organization_id: OrganizationId(r.organization_id),
+            created_by: r.created_by.map(UserId),
+        })
+        .collect();
+
+        Ok(tokens)
+    }
+}
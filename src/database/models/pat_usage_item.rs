@@ -0,0 +1,177 @@
+use super::ids::PatId;
+use super::DatabaseError;
+use chrono::{DateTime, Utc};
+
+/// The requests made with a personal access token in one hour
+#[derive(Clone, Debug)]
+pub struct PatUsageHour {
+    pub hour: DateTime<Utc>,
+    pub requests: i64,
+    pub errors: i64,
+    pub rate_limited: i64,
+}
+
+/// A request made with a personal access token which failed
+#[derive(Clone, Debug)]
+pub struct PatError {
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub created: DateTime<Utc>,
+}
+
+impl PatUsageHour {
+    /// Adds requests to the counts of the current hour
+    pub async fn add(
+        pat_id: PatId,
+        requests: i64,
+        errors: i64,
+        rate_limited: i64,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO pat_usage (pat_id, hour, requests, errors, rate_limited)
+            VALUES ($1, date_trunc('hour', NOW()), $2, $3, $4)
+            ON CONFLICT (pat_id, hour) DO UPDATE
+            SET requests = pat_usage.requests + EXCLUDED.requests,
+                errors = pat_usage.errors + EXCLUDED.errors,
+                rate_limited = pat_usage.rate_limited + EXCLUDED.rate_limited
+            ",
+            pat_id as PatId,
+            requests,
+            errors,
+            rate_limited,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the counts of hours before `before`
+    pub async fn remove_before(
+        before: DateTime<Utc>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            DELETE FROM pat_usage
+            WHERE hour < $1
+            ",
+            before,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the hours since `since` a token was used in, oldest first
+    pub async fn get_many<'a, E>(
+        pat_id: PatId,
+        since: DateTime<Utc>,
+        exec: E,
+    ) -> Result<Vec<PatUsageHour>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT hour, requests, errors, rate_limited
+            FROM pat_usage
+            WHERE pat_id = $1 AND hour >= $2
+            ORDER BY hour
+            ",
+            pat_id as PatId,
+            since,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|x| PatUsageHour {
+                hour: x.hour,
+                requests: x.requests,
+                errors: x.errors,
+                rate_limited: x.rate_limited,
+            })
+            .collect())
+    }
+}
+
+impl PatError {
+    /// Records failed requests, keeping only the latest `keep` of the token's
+    pub async fn insert_many(
+        pat_id: PatId,
+        errors: &[PatError],
+        keep: i64,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        let methods = errors.iter().map(|x| x.method.clone()).collect::<Vec<_>>();
+        let paths = errors.iter().map(|x| x.path.clone()).collect::<Vec<_>>();
+        let statuses = errors.iter().map(|x| x.status as i16).collect::<Vec<_>>();
+        let created = errors.iter().map(|x| x.created).collect::<Vec<_>>();
+
+        sqlx::query!(
+            "
+            INSERT INTO pat_errors (pat_id, method, path, status, created)
+            SELECT $1, * FROM UNNEST($2::text[], $3::text[], $4::smallint[], $5::timestamptz[])
+            ",
+            pat_id as PatId,
+            &methods[..],
+            &paths[..],
+            &statuses[..],
+            &created[..],
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM pat_errors
+            WHERE pat_id = $1 AND id NOT IN (
+                SELECT id FROM pat_errors
+                WHERE pat_id = $1
+                ORDER BY created DESC
+                LIMIT $2
+            )
+            ",
+            pat_id as PatId,
+            keep,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the failed requests kept for a token, latest first
+    pub async fn get_many<'a, E>(pat_id: PatId, exec: E) -> Result<Vec<PatError>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT method, path, status, created
+            FROM pat_errors
+            WHERE pat_id = $1
+            ORDER BY created DESC
+            ",
+            pat_id as PatId,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|x| PatError {
+                method: x.method,
+                path: x.path,
+                status: x.status as u16,
+                created: x.created,
+            })
+            .collect())
+    }
+}
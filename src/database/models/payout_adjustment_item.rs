@@ -0,0 +1,102 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use crate::models::payouts::AdjustmentReason;
+use crate::util::money::{base_to_minor_units, from_minor_units};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// A correction to a user's balance, recorded in the payouts ledger next to the revenue and
+/// payouts making up the balance
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PayoutAdjustment {
+    pub id: i64,
+    pub user_id: UserId,
+    pub project_id: Option<ProjectId>,
+    /// Negative amounts are taken from the balance
+    pub amount: Decimal,
+    pub reason: AdjustmentReason,
+    pub notes: Option<String>,
+    pub created_by: Option<UserId>,
+    pub created: DateTime<Utc>,
+}
+
+impl PayoutAdjustment {
+    /// Inserts the adjustment and applies it to the user's balance, returning the new balance as
+    /// a decimal amount
+    pub async fn insert(
+        &mut self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Decimal, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            INSERT INTO payouts_adjustments (
+                user_id, mod_id, amount, reason, notes, created_by, created
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7
+            )
+            RETURNING id
+            ",
+            self.user_id as UserId,
+            self.project_id.map(|x| x.0),
+            self.amount,
+            self.reason.as_str(),
+            self.notes,
+            self.created_by.map(|x| x.0),
+            self.created,
+        )
+        .fetch_one(&mut **transaction)
+        .await?;
+        self.id = result.id;
+
+        let user = sqlx::query!(
+            "
+            UPDATE users
+            SET balance = balance + $1
+            WHERE id = $2
+            RETURNING balance, balance_currency
+            ",
+            base_to_minor_units(self.amount),
+            self.user_id as UserId,
+        )
+        .fetch_one(&mut **transaction)
+        .await?;
+
+        Ok(from_minor_units(user.balance, &user.balance_currency).unwrap_or_default())
+    }
+
+    pub async fn get_all_for_user<'a, E>(
+        user_id: UserId,
+        exec: E,
+    ) -> Result<Vec<PayoutAdjustment>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let adjustments = sqlx::query!(
+            "
+            SELECT id, user_id, mod_id, amount, reason, notes, created_by, created
+            FROM payouts_adjustments
+            WHERE user_id = $1
+            ORDER BY created DESC
+            ",
+            user_id as UserId,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| PayoutAdjustment {
+            id: r.id,
+            user_id: UserId(r.user_id),
+            project_id: r.mod_id.map(ProjectId),
+            amount: r.amount,
+            reason: AdjustmentReason::from_string(&r.reason),
+            notes: r.notes,
+            created_by: r.created_by.map(UserId),
+            created: r.created,
+        })
+        .collect();
+
+        Ok(adjustments)
+    }
+}
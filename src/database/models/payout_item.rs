@@ -14,9 +14,15 @@ pub struct Payout {
     pub amount: Decimal,
 
     pub fee: Option<Decimal>,
+    pub withholding: Option<Decimal>,
     pub method: Option<PayoutMethodType>,
     pub method_address: Option<String>,
     pub platform_id: Option<String>,
+    pub failure_reason: Option<String>,
+
+    pub currency: Option<String>,
+    pub currency_amount: Option<i64>,
+    pub fx_rate: Option<Decimal>,
 }
 
 impl Payout {
@@ -27,10 +33,11 @@ impl Payout {
         sqlx::query!(
             "
             INSERT INTO payouts (
-                id, amount, fee, user_id, status, method, method_address, platform_id
+                id, amount, fee, user_id, status, method, method_address, platform_id,
+                failure_reason, withholding, currency, currency_amount, fx_rate
             )
             VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13
             )
             ",
             self.id.0,
@@ -41,6 +48,11 @@ impl Payout {
             self.method.map(|x| x.as_str()),
             self.method_address,
             self.platform_id,
+            self.failure_reason,
+            self.withholding,
+            self.currency,
+            self.currency_amount,
+            self.fx_rate,
         )
         .execute(&mut **transaction)
         .await?;
@@ -68,7 +80,8 @@ impl Payout {
 
         let results = sqlx::query!(
             "
-            SELECT id, user_id, created, amount, status, method, method_address, platform_id, fee
+            SELECT id, user_id, created, amount, status, method, method_address, platform_id, fee,
+                failure_reason, withholding, currency, currency_amount, fx_rate
             FROM payouts
             WHERE id = ANY($1)
             ",
@@ -86,6 +99,11 @@ impl Payout {
                 method_address: r.method_address,
                 platform_id: r.platform_id,
                 fee: r.fee,
+                withholding: r.withholding,
+                failure_reason: r.failure_reason,
+                currency: r.currency,
+                currency_amount: r.currency_amount,
+                fx_rate: r.fx_rate,
             }))
         })
         .try_collect::<Vec<Payout>>()
@@ -0,0 +1,71 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
+use crate::models::payouts::PayoutMethodType;
+use chrono::{DateTime, Duration, Utc};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+const PAYOUT_QUOTES_NAMESPACE: &str = "payout_quotes";
+
+/// A withdrawal quoted in a currency other than USD. The exchange rate is locked in until the
+/// quote expires, and the quote can be used for a single withdrawal.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PayoutQuote {
+    pub user_id: UserId,
+    pub method: PayoutMethodType,
+    pub method_id: String,
+    /// Taken from the balance, in USD
+    pub amount: Decimal,
+    pub fee: Decimal,
+    pub withholding: Decimal,
+    /// Sent to the user, in USD
+    pub transfer: Decimal,
+    pub currency: String,
+    /// The transfer converted into `currency`, in its minor unit
+    pub destination_amount: i64,
+    pub fx_rate: Decimal,
+    pub expires: DateTime<Utc>,
+}
+
+impl PayoutQuote {
+    pub async fn insert(&self, redis: &RedisPool) -> Result<String, DatabaseError> {
+        let mut redis = redis.connect().await?;
+
+        let id = ChaCha20Rng::from_entropy()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect::<String>();
+
+        let expiry = (self.expires - Utc::now()).max(Duration::seconds(1));
+        redis
+            .set_serialized_to_json(
+                PAYOUT_QUOTES_NAMESPACE,
+                &id,
+                &self,
+                Some(expiry.num_seconds()),
+            )
+            .await?;
+        Ok(id)
+    }
+
+    pub async fn get(id: &str, redis: &RedisPool) -> Result<Option<PayoutQuote>, DatabaseError> {
+        let mut redis = redis.connect().await?;
+
+        redis
+            .get_deserialized_from_json(PAYOUT_QUOTES_NAMESPACE, id)
+            .await
+    }
+
+    pub async fn remove(id: &str, redis: &RedisPool) -> Result<Option<()>, DatabaseError> {
+        let mut redis = redis.connect().await?;
+
+        redis.delete(PAYOUT_QUOTES_NAMESPACE, id).await?;
+        Ok(Some(()))
+    }
+}
@@ -0,0 +1,151 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use crate::models::payouts::PayoutMethodType;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// A user's opt-in to have their balance withdrawn automatically each month, once it reaches
+/// the threshold
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PayoutSchedule {
+    pub user_id: UserId,
+    pub method: PayoutMethodType,
+    pub method_id: String,
+    pub threshold: Decimal,
+    pub created: DateTime<Utc>,
+    pub last_attempt: Option<DateTime<Utc>>,
+}
+
+impl PayoutSchedule {
+    /// Inserts this schedule, replacing the user's existing schedule if they have one
+    pub async fn upsert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO payout_schedules (
+                user_id, method, method_id, threshold
+            )
+            VALUES (
+                $1, $2, $3, $4
+            )
+            ON CONFLICT (user_id) DO UPDATE
+            SET method = EXCLUDED.method, method_id = EXCLUDED.method_id,
+                threshold = EXCLUDED.threshold
+            ",
+            self.user_id as UserId,
+            self.method.as_str(),
+            self.method_id,
+            self.threshold,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get<'a, E>(
+        user_id: UserId,
+        exec: E,
+    ) -> Result<Option<PayoutSchedule>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let schedule = sqlx::query!(
+            "
+            SELECT user_id, method, method_id, threshold, created, last_attempt
+            FROM payout_schedules
+            WHERE user_id = $1
+            ",
+            user_id as UserId,
+        )
+        .fetch_optional(exec)
+        .await?
+        .map(|r| PayoutSchedule {
+            user_id: UserId(r.user_id),
+            method: PayoutMethodType::from_string(&r.method),
+            method_id: r.method_id,
+            threshold: r.threshold,
+            created: r.created,
+            last_attempt: r.last_attempt,
+        });
+
+        Ok(schedule)
+    }
+
+    /// Gets the schedules which have not been attempted since `since`
+    pub async fn get_due<'a, E>(
+        since: DateTime<Utc>,
+        exec: E,
+    ) -> Result<Vec<PayoutSchedule>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        use futures::TryStreamExt;
+
+        let schedules = sqlx::query!(
+            "
+            SELECT user_id, method, method_id, threshold, created, last_attempt
+            FROM payout_schedules
+            WHERE last_attempt IS NULL OR last_attempt < $1
+            ",
+            since,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().map(|r| PayoutSchedule {
+                user_id: UserId(r.user_id),
+                method: PayoutMethodType::from_string(&r.method),
+                method_id: r.method_id,
+                threshold: r.threshold,
+                created: r.created,
+                last_attempt: r.last_attempt,
+            }))
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+
+        Ok(schedules)
+    }
+
+    pub async fn mark_attempted(
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            UPDATE payout_schedules
+            SET last_attempt = CURRENT_TIMESTAMP
+            WHERE user_id = $1
+            ",
+            user_id as UserId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove(
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM payout_schedules
+            WHERE user_id = $1
+            ",
+            user_id as UserId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+}
@@ -4,7 +4,7 @@ use super::loader_fields::{
 use super::{ids::*, User};
 use crate::database::models;
 use crate::database::models::DatabaseError;
-use crate::database::redis::RedisPool;
+use crate::database::redis::{Cached, RedisConnection, RedisPool};
 use crate::models::ids::base62_impl::{parse_base62, to_base62};
 use crate::models::projects::{MonetizationStatus, ProjectStatus};
 use chrono::{DateTime, Utc};
@@ -12,6 +12,8 @@ use dashmap::{DashMap, DashSet};
 use futures::TryStreamExt;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Instant;
 
 pub const PROJECTS_NAMESPACE: &str = "projects";
 pub const PROJECTS_SLUGS_NAMESPACE: &str = "projects_slugs";
@@ -55,12 +57,21 @@ impl LinkUrl {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GalleryItem {
+    /// Generated when the item is inserted if not set
+    #[serde(default)]
+    pub image_id: Option<ImageId>,
     pub image_url: String,
     pub featured: bool,
     pub name: Option<String>,
     pub description: Option<String>,
     pub created: DateTime<Utc>,
     pub ordering: i64,
+    /// Optimized copies of the image, by content type
+    #[serde(default)]
+    pub renditions: HashMap<String, String>,
+    /// See `util::img::perceptual_hash`. Missing if the image could not be hashed
+    #[serde(default)]
+    pub perceptual_hash: Option<i64>,
 }
 
 impl GalleryItem {
@@ -68,8 +79,27 @@ impl GalleryItem {
         items: Vec<Self>,
         project_id: ProjectId,
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-    ) -> Result<(), sqlx::error::Error> {
-        let (project_ids, image_urls, featureds, names, descriptions, orderings): (
+    ) -> Result<(), DatabaseError> {
+        let mut image_ids = Vec::with_capacity(items.len());
+        for item in &items {
+            image_ids.push(match item.image_id {
+                Some(id) => id.0,
+                None => generate_image_id(transaction).await?.0,
+            });
+        }
+
+        let (
+            project_ids,
+            image_urls,
+            featureds,
+            names,
+            descriptions,
+            orderings,
+            renditions,
+            perceptual_hashes,
+        ): (
+            Vec<_>,
+            Vec<_>,
             Vec<_>,
             Vec<_>,
             Vec<_>,
@@ -86,28 +116,115 @@ impl GalleryItem {
                     gi.name,
                     gi.description,
                     gi.ordering,
+                    serde_json::json!(gi.renditions),
+                    gi.perceptual_hash,
                 )
             })
             .multiunzip();
         sqlx::query!(
             "
             INSERT INTO mods_gallery (
-                mod_id, image_url, featured, name, description, ordering
+                mod_id, image_url, featured, name, description, ordering, renditions, image_id,
+                perceptual_hash
             )
-            SELECT * FROM UNNEST ($1::bigint[], $2::varchar[], $3::bool[], $4::varchar[], $5::varchar[], $6::bigint[])
+            SELECT * FROM UNNEST ($1::bigint[], $2::varchar[], $3::bool[], $4::varchar[], $5::varchar[], $6::bigint[], $7::jsonb[], $8::bigint[], $9::bigint[])
             ",
             &project_ids[..],
             &image_urls[..],
             &featureds[..],
             &names[..] as &[Option<String>],
             &descriptions[..] as &[Option<String>],
-            &orderings[..]
+            &orderings[..],
+            &renditions[..],
+            &image_ids[..],
+            &perceptual_hashes[..] as &[Option<i64>],
         )
         .execute(&mut **transaction)
         .await?;
 
         Ok(())
     }
+
+    /// Gets the URL of the gallery image with an id
+    pub async fn get_url<'a, E>(image_id: ImageId, exec: E) -> Result<Option<String>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT image_url FROM mods_gallery
+            WHERE image_id = $1
+            ",
+            image_id as ImageId,
+        )
+        .fetch_optional(exec)
+        .await?;
+
+        Ok(result.map(|x| x.image_url))
+    }
+}
+
+/// An icon or gallery image which looks like another image
+#[derive(Clone, Debug)]
+pub struct SimilarImage {
+    pub project_id: ProjectId,
+    pub image_url: String,
+    /// Whether the image is the project's icon rather than one of its gallery images
+    pub icon: bool,
+    /// How many bits the image's perceptual hash differs by
+    pub distance: i64,
+}
+
+impl SimilarImage {
+    /// Finds the icons and gallery images of projects other than `exclude` whose perceptual
+    /// hash is at most `max_distance` bits away from `hash`, closest first
+    pub async fn get_many<'a, E>(
+        hash: i64,
+        max_distance: i64,
+        exclude: ProjectId,
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<SimilarImage>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            r#"
+            SELECT mod_id as "mod_id!", image_url as "image_url!", icon as "icon!",
+                distance as "distance!"
+            FROM (
+                SELECT id mod_id, icon_url image_url, TRUE icon,
+                    bit_count((icon_perceptual_hash # $1)::bit(64)) distance
+                FROM mods
+                WHERE icon_perceptual_hash IS NOT NULL AND icon_url IS NOT NULL AND id != $3
+                UNION ALL
+                SELECT mod_id, image_url, FALSE icon,
+                    bit_count((perceptual_hash # $1)::bit(64)) distance
+                FROM mods_gallery
+                WHERE perceptual_hash IS NOT NULL AND mod_id != $3
+            ) images
+            WHERE distance <= $2
+            ORDER BY distance ASC
+            LIMIT $4
+            "#,
+            hash,
+            max_distance,
+            exclude as ProjectId,
+            limit,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|x| SimilarImage {
+                project_id: ProjectId(x.mod_id),
+                image_url: x.image_url,
+                icon: x.icon,
+                distance: x.distance,
+            })
+            .collect())
+    }
 }
 
 #[derive(derive_new::new)]
@@ -151,6 +268,8 @@ pub struct ProjectBuilder {
     pub summary: String,
     pub description: String,
     pub icon_url: Option<String>,
+    pub icon_renditions: HashMap<String, String>,
+    pub icon_perceptual_hash: Option<i64>,
     pub license_url: Option<String>,
     pub categories: Vec<CategoryId>,
     pub additional_categories: Vec<CategoryId>,
@@ -190,6 +309,8 @@ impl ProjectBuilder {
             downloads: 0,
             follows: 0,
             icon_url: self.icon_url,
+            icon_renditions: self.icon_renditions,
+            icon_perceptual_hash: self.icon_perceptual_hash,
             license_url: self.license_url,
             license: self.license,
             slug: self.slug,
@@ -197,8 +318,10 @@ impl ProjectBuilder {
             moderation_message_body: None,
             webhook_sent: false,
             color: self.color,
+            revision: 1,
             monetization_status: self.monetization_status,
             loaders: vec![],
+            deleted: None,
         };
         project_struct.insert(&mut *transaction).await?;
 
@@ -251,15 +374,27 @@ pub struct Project {
     pub downloads: i32,
     pub follows: i32,
     pub icon_url: Option<String>,
+    /// Optimized copies of the icon, by content type
+    #[serde(default)]
+    pub icon_renditions: HashMap<String, String>,
+    /// See `util::img::perceptual_hash`. Missing if the icon could not be hashed
+    #[serde(default)]
+    pub icon_perceptual_hash: Option<i64>,
     pub license_url: Option<String>,
     pub license: String,
     pub slug: Option<String>,
     pub moderation_message: Option<String>,
     pub moderation_message_body: Option<String>,
     pub webhook_sent: bool,
+    /// Incremented on every edit, so edits made from an older revision can be rejected
+    pub revision: i32,
     pub color: Option<u32>,
     pub monetization_status: MonetizationStatus,
     pub loaders: Vec<String>,
+    /// When the project was deleted, if it is waiting to be purged. Deleted projects are left
+    /// out of `get_many`, so they are hidden everywhere until they are restored
+    #[serde(default)]
+    pub deleted: Option<DateTime<Utc>>,
 }
 
 impl Project {
@@ -273,13 +408,15 @@ impl Project {
                 id, team_id, name, summary, description,
                 published, downloads, icon_url, status, requested_status,
                 license_url, license,
-                slug, color, monetization_status, organization_id
+                slug, color, monetization_status, organization_id, icon_renditions,
+                icon_perceptual_hash
             )
             VALUES (
                 $1, $2, $3, $4, $5, $6, 
                 $7, $8, $9, $10, 
                 $11, $12, 
-                LOWER($13), $14, $15, $16
+                LOWER($13), $14, $15, $16, $17,
+                $18
             )
             ",
             self.id as ProjectId,
@@ -298,6 +435,8 @@ impl Project {
             self.color.map(|x| x as i32),
             self.monetization_status.as_str(),
             self.organization_id.map(|x| x.0 as i64),
+            serde_json::json!(self.icon_renditions),
+            self.icon_perceptual_hash,
         )
         .execute(&mut **transaction)
         .await?;
@@ -305,12 +444,120 @@ impl Project {
         Ok(())
     }
 
+    /// Starts an edit by moving the project to its next revision, which locks it until the
+    /// transaction ends. Returns the new revision, or `None` if the project is not at one of
+    /// the `expected` revisions
+    pub async fn bump_revision(
+        id: ProjectId,
+        expected: Option<&[i32]>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<i32>, DatabaseError> {
+        let revision = sqlx::query!(
+            "
+            UPDATE mods
+            SET revision = revision + 1
+            WHERE id = $1 AND ($2::integer[] IS NULL OR revision = ANY($2))
+            RETURNING revision
+            ",
+            id as ProjectId,
+            expected,
+        )
+        .fetch_optional(&mut **transaction)
+        .await?
+        .map(|x| x.revision);
+
+        Ok(revision)
+    }
+
+    /// Deletes a project, hiding it until it is restored or purged after the grace period
+    pub async fn soft_remove(
+        id: ProjectId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        redis: &RedisPool,
+    ) -> Result<Option<()>, DatabaseError> {
+        let slug = sqlx::query!(
+            "
+            UPDATE mods
+            SET deleted = CURRENT_TIMESTAMP
+            WHERE id = $1 AND deleted IS NULL
+            RETURNING slug
+            ",
+            id as ProjectId,
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
+
+        match slug {
+            Some(row) => {
+                Project::clear_cache(id, row.slug, Some(true), redis).await?;
+                Ok(Some(()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Restores a deleted project which has not been purged yet
+    pub async fn restore(
+        id: ProjectId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        redis: &RedisPool,
+    ) -> Result<Option<()>, DatabaseError> {
+        let slug = sqlx::query!(
+            "
+            UPDATE mods
+            SET deleted = NULL
+            WHERE id = $1 AND deleted > CURRENT_TIMESTAMP - make_interval(days => $2)
+            RETURNING slug
+            ",
+            id as ProjectId,
+            super::DELETION_GRACE_DAYS,
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
+
+        match slug {
+            Some(row) => {
+                Project::clear_cache(id, row.slug, Some(true), redis).await?;
+                Ok(Some(()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the deleted projects whose grace period is over
+    pub async fn get_purgeable<'a, E>(exec: E) -> Result<Vec<ProjectId>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let ids = sqlx::query!(
+            "
+            SELECT id FROM mods
+            WHERE deleted <= CURRENT_TIMESTAMP - make_interval(days => $1)
+            ",
+            super::DELETION_GRACE_DAYS,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|x| ProjectId(x.id))
+        .collect();
+
+        Ok(ids)
+    }
+
     pub async fn remove(
         id: ProjectId,
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         redis: &RedisPool,
     ) -> Result<Option<()>, DatabaseError> {
-        let project = Self::get_id(id, &mut **transaction, redis).await?;
+        let project = Self::get_many_including_deleted(
+            &[crate::models::ids::ProjectId::from(id)],
+            &mut **transaction,
+            redis,
+        )
+        .await?
+        .into_iter()
+        .next();
 
         if let Some(project) = project {
             Project::clear_cache(id, project.inner.slug, Some(true), redis).await?;
@@ -510,6 +757,24 @@ impl Project {
         exec: E,
         redis: &RedisPool,
     ) -> Result<Vec<QueryProject>, DatabaseError>
+    where
+        E: sqlx::Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut projects =
+            Project::get_many_including_deleted(project_strings, exec, redis).await?;
+        projects.retain(|x| x.inner.deleted.is_none());
+
+        Ok(projects)
+    }
+
+    /// Gets projects including the deleted ones which are waiting to be purged, for restoring
+    /// and purging them
+    #[tracing::instrument(skip_all, fields(count = project_strings.len()))]
+    pub async fn get_many_including_deleted<'a, E, T: ToString>(
+        project_strings: &[T],
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<Vec<QueryProject>, DatabaseError>
     where
         E: sqlx::Acquire<'a, Database = sqlx::Postgres>,
     {
@@ -526,169 +791,242 @@ impl Project {
         let mut redis = redis.connect().await?;
         let mut exec = exec.acquire().await?;
 
+        let (cached_projects, remaining_strings) =
+            Self::get_cached_many(&project_strings, &mut redis).await?;
+
+        // Projects due to expire are refreshed by whichever request takes their lock, while
+        // the others keep using the cached copy
+        let refresh = redis
+            .try_lock_many(
+                PROJECTS_NAMESPACE,
+                &cached_projects
+                    .iter()
+                    .filter(|x| x.should_refresh())
+                    .map(|x| x.value.inner.id.0.to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .await?;
         let mut found_projects = Vec::new();
-        let mut remaining_strings = project_strings.clone();
+        for project in cached_projects {
+            if !refresh.contains(&project.value.inner.id.0.to_string()) {
+                found_projects.push(project.value);
+            }
+        }
 
-        let mut project_ids = project_strings
-            .iter()
-            .flat_map(|x| parse_base62(&x.to_string()).map(|x| x as i64))
-            .collect::<Vec<_>>();
+        // Locks are taken by ID, so requests for a project by its ID and by its slug wait on
+        // each other
+        let mut remaining_ids = Self::resolve_ids(&remaining_strings, &mut exec).await?;
 
-        project_ids.append(
-            &mut redis
-                .multi_get::<i64>(
-                    PROJECTS_SLUGS_NAMESPACE,
-                    project_strings.iter().map(|x| x.to_string().to_lowercase()),
-                )
-                .await?
-                .into_iter()
-                .flatten()
-                .collect(),
-        );
-        if !project_ids.is_empty() {
-            let projects = redis
-                .multi_get::<String>(
+        let mut locked = Vec::new();
+        if !remaining_ids.is_empty() {
+            locked = redis
+                .lock_or_wait(
                     PROJECTS_NAMESPACE,
-                    project_ids.iter().map(|x| x.to_string()),
+                    &remaining_ids
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>(),
                 )
                 .await?;
-            for project in projects {
-                if let Some(project) =
-                    project.and_then(|x| serde_json::from_str::<QueryProject>(&x).ok())
-                {
-                    remaining_strings.retain(|x| {
-                        &to_base62(project.inner.id.0 as u64) != x
-                            && project.inner.slug.as_ref().map(|x| x.to_lowercase())
-                                != Some(x.to_lowercase())
-                    });
-                    found_projects.push(project);
-                    continue;
+
+            // Projects another request was loading may be cached by now
+            let waited = remaining_ids
+                .iter()
+                .filter(|x| !locked.contains(&x.to_string()))
+                .map(|x| to_base62(*x as u64))
+                .collect::<Vec<_>>();
+            if !waited.is_empty() {
+                let (cached_projects, _) = Self::get_cached_many(&waited, &mut redis).await?;
+                remaining_ids.retain(|x| {
+                    locked.contains(&x.to_string())
+                        || !cached_projects.iter().any(|y| y.value.inner.id.0 == *x)
+                });
+                found_projects.extend(cached_projects.into_iter().map(|x| x.value));
+            }
+        }
+        remaining_ids.extend(refresh.iter().flat_map(|x| x.parse::<i64>().ok()));
+        locked.extend(refresh);
+
+        if !remaining_ids.is_empty() {
+            // The locks are released even if loading fails, rather than making other requests
+            // wait for them to expire
+            let loaded = async {
+                let started = Instant::now();
+                let db_projects = Self::load_many(remaining_ids, Vec::new(), &mut exec).await?;
+
+                let compute_time = started.elapsed();
+                for project in &db_projects {
+                    redis
+                        .set_cached(
+                            PROJECTS_NAMESPACE,
+                            project.inner.id.0,
+                            project,
+                            compute_time,
+                        )
+                        .await?;
+                    if let Some(slug) = &project.inner.slug {
+                        redis
+                            .set(
+                                PROJECTS_SLUGS_NAMESPACE,
+                                &slug.to_lowercase(),
+                                &project.inner.id.0.to_string(),
+                                None,
+                            )
+                            .await?;
+                    }
                 }
+
+                Ok::<_, DatabaseError>(db_projects)
             }
+            .await;
+
+            redis.unlock_many(PROJECTS_NAMESPACE, &locked).await?;
+            found_projects.extend(loaded?);
         }
-        if !remaining_strings.is_empty() {
-            let project_ids_parsed: Vec<i64> = remaining_strings
-                .iter()
-                .flat_map(|x| parse_base62(&x.to_string()).ok())
-                .map(|x| x as i64)
-                .collect();
-            let slugs = remaining_strings
-                .into_iter()
-                .map(|x| x.to_lowercase())
-                .collect::<Vec<_>>();
 
-            let all_version_ids = DashSet::new();
-            let versions: DashMap<ProjectId, Vec<(VersionId, DateTime<Utc>)>> = sqlx::query!(
-                "
+        Ok(found_projects)
+    }
+
+    /// Gets projects straight from the database, without reading or writing the cache. This is
+    /// for bulk reads from the read replica, whose rows may lag behind and must not be cached
+    pub async fn get_many_ids_uncached<'a, E>(
+        project_ids: &[ProjectId],
+        exec: E,
+    ) -> Result<Vec<QueryProject>, DatabaseError>
+    where
+        E: sqlx::Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut exec = exec.acquire().await?;
+
+        let mut projects = Self::load_many(
+            project_ids.iter().map(|x| x.0).unique().collect(),
+            Vec::new(),
+            &mut exec,
+        )
+        .await?;
+        projects.retain(|x| x.inner.deleted.is_none());
+
+        Ok(projects)
+    }
+
+    /// Loads projects by ID or slug from the database
+    async fn load_many(
+        project_ids_parsed: Vec<i64>,
+        slugs: Vec<String>,
+        exec: &mut sqlx::PgConnection,
+    ) -> Result<Vec<QueryProject>, DatabaseError> {
+        let all_version_ids = DashSet::new();
+        let versions: DashMap<ProjectId, Vec<(VersionId, DateTime<Utc>)>> = sqlx::query!(
+            "
                 SELECT DISTINCT mod_id, v.id as id, date_published
                 FROM mods m
                 INNER JOIN versions v ON m.id = v.mod_id AND v.status = ANY($3)
                 WHERE m.id = ANY($1) OR m.slug = ANY($2)
                 ",
-                &project_ids_parsed,
-                &slugs,
-                &*crate::models::projects::VersionStatus::iterator()
-                    .filter(|x| x.is_listed())
-                    .map(|x| x.to_string())
-                    .collect::<Vec<String>>()
-            )
-            .fetch(&mut *exec)
-            .try_fold(
-                DashMap::new(),
-                |acc: DashMap<ProjectId, Vec<(VersionId, DateTime<Utc>)>>, m| {
-                    let version_id = VersionId(m.id);
-                    let date_published = m.date_published;
-                    all_version_ids.insert(version_id);
-                    acc.entry(ProjectId(m.mod_id))
-                        .or_default()
-                        .push((version_id, date_published));
-                    async move { Ok(acc) }
-                },
-            )
-            .await?;
+            &project_ids_parsed,
+            &slugs,
+            &*crate::models::projects::VersionStatus::iterator()
+                .filter(|x| x.is_listed())
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+        )
+        .fetch(&mut *exec)
+        .try_fold(
+            DashMap::new(),
+            |acc: DashMap<ProjectId, Vec<(VersionId, DateTime<Utc>)>>, m| {
+                let version_id = VersionId(m.id);
+                let date_published = m.date_published;
+                all_version_ids.insert(version_id);
+                acc.entry(ProjectId(m.mod_id))
+                    .or_default()
+                    .push((version_id, date_published));
+                async move { Ok(acc) }
+            },
+        )
+        .await?;
 
-            let loader_field_ids = DashSet::new();
-            let loader_field_enum_value_ids = DashSet::new();
-            let version_fields: DashMap<ProjectId, Vec<QueryVersionField>> = sqlx::query!(
-                "
+        let loader_field_ids = DashSet::new();
+        let loader_field_enum_value_ids = DashSet::new();
+        let version_fields: DashMap<ProjectId, Vec<QueryVersionField>> = sqlx::query!(
+            "
                 SELECT DISTINCT mod_id, version_id, field_id, int_value, enum_value, string_value
                 FROM versions v
                 INNER JOIN version_fields vf ON v.id = vf.version_id
                 WHERE v.id = ANY($1)
                 ",
-                &all_version_ids.iter().map(|x| x.0).collect::<Vec<_>>()
-            )
-            .fetch(&mut *exec)
-            .try_fold(
-                DashMap::new(),
-                |acc: DashMap<ProjectId, Vec<QueryVersionField>>, m| {
-                    let qvf = QueryVersionField {
-                        version_id: VersionId(m.version_id),
-                        field_id: LoaderFieldId(m.field_id),
-                        int_value: m.int_value,
-                        enum_value: m.enum_value.map(LoaderFieldEnumValueId),
-                        string_value: m.string_value,
-                    };
-
-                    loader_field_ids.insert(LoaderFieldId(m.field_id));
-                    if let Some(enum_value) = m.enum_value {
-                        loader_field_enum_value_ids.insert(LoaderFieldEnumValueId(enum_value));
-                    }
+            &all_version_ids.iter().map(|x| x.0).collect::<Vec<_>>()
+        )
+        .fetch(&mut *exec)
+        .try_fold(
+            DashMap::new(),
+            |acc: DashMap<ProjectId, Vec<QueryVersionField>>, m| {
+                let qvf = QueryVersionField {
+                    version_id: VersionId(m.version_id),
+                    field_id: LoaderFieldId(m.field_id),
+                    int_value: m.int_value,
+                    enum_value: m.enum_value.map(LoaderFieldEnumValueId),
+                    string_value: m.string_value,
+                };
+
+                loader_field_ids.insert(LoaderFieldId(m.field_id));
+                if let Some(enum_value) = m.enum_value {
+                    loader_field_enum_value_ids.insert(LoaderFieldEnumValueId(enum_value));
+                }
 
-                    acc.entry(ProjectId(m.mod_id)).or_default().push(qvf);
-                    async move { Ok(acc) }
-                },
-            )
-            .await?;
+                acc.entry(ProjectId(m.mod_id)).or_default().push(qvf);
+                async move { Ok(acc) }
+            },
+        )
+        .await?;
 
-            let loader_fields: Vec<QueryLoaderField> = sqlx::query!(
-                "
+        let loader_fields: Vec<QueryLoaderField> = sqlx::query!(
+            "
                 SELECT DISTINCT id, field, field_type, enum_type, min_val, max_val, optional
                 FROM loader_fields lf
                 WHERE id = ANY($1)  
                 ",
-                &loader_field_ids.iter().map(|x| x.0).collect::<Vec<_>>()
-            )
-            .fetch(&mut *exec)
-            .map_ok(|m| QueryLoaderField {
-                id: LoaderFieldId(m.id),
-                field: m.field,
-                field_type: m.field_type,
-                enum_type: m.enum_type.map(LoaderFieldEnumId),
-                min_val: m.min_val,
-                max_val: m.max_val,
-                optional: m.optional,
-            })
-            .try_collect()
-            .await?;
+            &loader_field_ids.iter().map(|x| x.0).collect::<Vec<_>>()
+        )
+        .fetch(&mut *exec)
+        .map_ok(|m| QueryLoaderField {
+            id: LoaderFieldId(m.id),
+            field: m.field,
+            field_type: m.field_type,
+            enum_type: m.enum_type.map(LoaderFieldEnumId),
+            min_val: m.min_val,
+            max_val: m.max_val,
+            optional: m.optional,
+        })
+        .try_collect()
+        .await?;
 
-            let loader_field_enum_values: Vec<QueryLoaderFieldEnumValue> = sqlx::query!(
-                "
+        let loader_field_enum_values: Vec<QueryLoaderFieldEnumValue> = sqlx::query!(
+            "
                 SELECT DISTINCT id, enum_id, value, ordering, created, metadata
                 FROM loader_field_enum_values lfev
                 WHERE id = ANY($1)  
                 ORDER BY enum_id, ordering, created DESC
                 ",
-                &loader_field_enum_value_ids
-                    .iter()
-                    .map(|x| x.0)
-                    .collect::<Vec<_>>()
-            )
-            .fetch(&mut *exec)
-            .map_ok(|m| QueryLoaderFieldEnumValue {
-                id: LoaderFieldEnumValueId(m.id),
-                enum_id: LoaderFieldEnumId(m.enum_id),
-                value: m.value,
-                ordering: m.ordering,
-                created: m.created,
-                metadata: m.metadata,
-            })
-            .try_collect()
-            .await?;
+            &loader_field_enum_value_ids
+                .iter()
+                .map(|x| x.0)
+                .collect::<Vec<_>>()
+        )
+        .fetch(&mut *exec)
+        .map_ok(|m| QueryLoaderFieldEnumValue {
+            id: LoaderFieldEnumValueId(m.id),
+            enum_id: LoaderFieldEnumId(m.enum_id),
+            value: m.value,
+            ordering: m.ordering,
+            created: m.created,
+            metadata: m.metadata,
+        })
+        .try_collect()
+        .await?;
 
-            let mods_gallery: DashMap<ProjectId, Vec<GalleryItem>> = sqlx::query!(
+        let mods_gallery: DashMap<ProjectId, Vec<GalleryItem>> = sqlx::query!(
                 "
-                SELECT DISTINCT mod_id, mg.image_url, mg.featured, mg.name, mg.description, mg.created, mg.ordering
+                SELECT DISTINCT mod_id, mg.image_id, mg.image_url, mg.featured, mg.name, mg.description, mg.created, mg.ordering, mg.renditions, mg.perceptual_hash
                 FROM mods_gallery mg
                 INNER JOIN mods m ON mg.mod_id = m.id
                 WHERE m.id = ANY($1) OR m.slug = ANY($2)
@@ -700,18 +1038,21 @@ impl Project {
                     acc.entry(ProjectId(m.mod_id))
                     .or_default()
                     .push(GalleryItem {
+                        image_id: Some(ImageId(m.image_id)),
                         image_url: m.image_url,
                         featured: m.featured.unwrap_or(false),
                         name: m.name,
                         description: m.description,
                         created: m.created,
                         ordering: m.ordering,
+                        renditions: serde_json::from_value(m.renditions).unwrap_or_default(),
+                        perceptual_hash: m.perceptual_hash,
                     });
                     async move { Ok(acc) }
                 }
             ).await?;
 
-            let links: DashMap<ProjectId, Vec<LinkUrl>> = sqlx::query!(
+        let links: DashMap<ProjectId, Vec<LinkUrl>> = sqlx::query!(
                 "
                 SELECT DISTINCT joining_mod_id as mod_id, joining_platform_id as platform_id, lp.name as platform_name, url, lp.donation as donation
                 FROM mods_links ml
@@ -735,8 +1076,8 @@ impl Project {
                 }
             ).await?;
 
-            type StringTriple = (Vec<String>, Vec<String>, Vec<String>);
-            let loaders_ptypes_games: DashMap<ProjectId, StringTriple> = sqlx::query!(
+        type StringTriple = (Vec<String>, Vec<String>, Vec<String>);
+        let loaders_ptypes_games: DashMap<ProjectId, StringTriple> = sqlx::query!(
                 "
                 SELECT DISTINCT mod_id,
                     ARRAY_AGG(DISTINCT l.loader) filter (where l.loader is not null) loaders,
@@ -765,14 +1106,14 @@ impl Project {
                 }
             ).try_collect().await?;
 
-            let db_projects: Vec<QueryProject> = sqlx::query!(
+        let projects: Vec<QueryProject> = sqlx::query!(
                 "
                 SELECT m.id id, m.name name, m.summary summary, m.downloads downloads, m.follows follows,
-                m.icon_url icon_url, m.description description, m.published published,
+                m.icon_url icon_url, m.icon_renditions icon_renditions, m.icon_perceptual_hash, m.description description, m.published published,
                 m.updated updated, m.approved approved, m.queued, m.status status, m.requested_status requested_status,
                 m.license_url license_url,
                 m.team_id team_id, m.organization_id organization_id, m.license license, m.slug slug, m.moderation_message moderation_message, m.moderation_message_body moderation_message_body,
-                m.webhook_sent, m.color,
+                m.webhook_sent, m.color, m.revision, m.deleted,
                 t.id thread_id, m.monetization_status monetization_status,
                 ARRAY_AGG(DISTINCT c.category) filter (where c.category is not null and mc.is_additional is false) categories,
                 ARRAY_AGG(DISTINCT c.category) filter (where c.category is not null and mc.is_additional is true) additional_categories
@@ -805,6 +1146,8 @@ impl Project {
                             summary: m.summary.clone(),
                             downloads: m.downloads,
                             icon_url: m.icon_url.clone(),
+                            icon_renditions: serde_json::from_value(m.icon_renditions).unwrap_or_default(),
+                            icon_perceptual_hash: m.icon_perceptual_hash,
                             published: m.published,
                             updated: m.updated,
                             license_url: m.license_url.clone(),
@@ -823,11 +1166,13 @@ impl Project {
                             approved: m.approved,
                             webhook_sent: m.webhook_sent,
                             color: m.color.map(|x| x as u32),
+                            revision: m.revision,
                             queued: m.queued,
                             monetization_status: MonetizationStatus::from_string(
                                 &m.monetization_status,
                             ),
                             loaders,
+                            deleted: m.deleted,
                         },
                         categories: m.categories.unwrap_or_default(),
                         additional_categories: m.additional_categories.unwrap_or_default(),
@@ -850,25 +1195,95 @@ impl Project {
                 .try_collect::<Vec<QueryProject>>()
                 .await?;
 
-            for project in db_projects {
-                redis
-                    .set_serialized_to_json(PROJECTS_NAMESPACE, project.inner.id.0, &project, None)
-                    .await?;
-                if let Some(slug) = &project.inner.slug {
-                    redis
-                        .set(
-                            PROJECTS_SLUGS_NAMESPACE,
-                            &slug.to_lowercase(),
-                            &project.inner.id.0.to_string(),
-                            None,
-                        )
-                        .await?;
-                }
+        Ok(projects)
+    }
+
+    /// Finds the IDs of the projects with the given IDs or slugs
+    async fn resolve_ids(
+        project_strings: &[String],
+        exec: &mut sqlx::PgConnection,
+    ) -> Result<Vec<i64>, DatabaseError> {
+        if project_strings.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let project_ids = project_strings
+            .iter()
+            .flat_map(|x| parse_base62(x).ok())
+            .map(|x| x as i64)
+            .collect::<Vec<_>>();
+        let slugs = project_strings
+            .iter()
+            .map(|x| x.to_lowercase())
+            .collect::<Vec<_>>();
+
+        let ids = sqlx::query!(
+            "
+            SELECT id FROM mods
+            WHERE id = ANY($1) OR slug = ANY($2)
+            ",
+            &project_ids,
+            &slugs,
+        )
+        .fetch_all(&mut *exec)
+        .await?
+        .into_iter()
+        .map(|x| x.id)
+        .collect();
+
+        Ok(ids)
+    }
+
+    /// Reads projects from the cache, along with the strings of the projects not cached
+    async fn get_cached_many(
+        project_strings: &[String],
+        redis: &mut RedisConnection,
+    ) -> Result<(Vec<Cached<QueryProject>>, Vec<String>), DatabaseError> {
+        let mut found_projects = Vec::new();
+        let mut remaining_strings = project_strings.to_vec();
+
+        if project_strings.is_empty() {
+            return Ok((found_projects, remaining_strings));
+        }
+
+        let mut project_ids = project_strings
+            .iter()
+            .flat_map(|x| parse_base62(&x.to_string()).map(|x| x as i64))
+            .collect::<Vec<_>>();
+
+        project_ids.append(
+            &mut redis
+                .multi_get::<i64>(
+                    PROJECTS_SLUGS_NAMESPACE,
+                    project_strings.iter().map(|x| x.to_string().to_lowercase()),
+                )
+                .await?
+                .into_iter()
+                .flatten()
+                .collect(),
+        );
+        // A project asked for by both its ID and slug is only returned once
+        project_ids.sort_unstable();
+        project_ids.dedup();
+
+        if !project_ids.is_empty() {
+            let projects = redis
+                .multi_get_cached::<QueryProject>(
+                    PROJECTS_NAMESPACE,
+                    project_ids.iter().map(|x| x.to_string()),
+                )
+                .await?;
+            for project in projects.into_iter().flatten() {
+                remaining_strings.retain(|x| {
+                    &to_base62(project.value.inner.id.0 as u64) != x
+                        && project.value.inner.slug.as_ref().map(|x| x.to_lowercase())
+                            != Some(x.to_lowercase())
+                });
                 found_projects.push(project);
             }
         }
 
-        Ok(found_projects)
+        Ok((found_projects, remaining_strings))
     }
 
     pub async fn get_dependencies<'a, E>(
@@ -0,0 +1,123 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A browser Web Push subscription, as returned by `PushManager.subscribe()`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PushSubscription {
+    pub endpoint: String,
+    pub user_id: UserId,
+    /// The client's P-256 ECDH public key, base64url encoded
+    pub p256dh: String,
+    /// The client's authentication secret, base64url encoded
+    pub auth: String,
+    pub created: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+impl PushSubscription {
+    /// Stores the subscription, refreshing its keys if the user already registered the endpoint.
+    /// Returns false if the endpoint belongs to another user, in which case nothing is changed
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            INSERT INTO users_push_subscriptions (
+                endpoint, user_id, p256dh, auth
+            )
+            VALUES (
+                $1, $2, $3, $4
+            )
+            ON CONFLICT (endpoint) DO UPDATE
+            SET p256dh = EXCLUDED.p256dh, auth = EXCLUDED.auth
+            WHERE users_push_subscriptions.user_id = EXCLUDED.user_id
+            ",
+            self.endpoint,
+            self.user_id as UserId,
+            self.p256dh,
+            self.auth,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_many_user<'a, E>(
+        user_ids: &[UserId],
+        exec: E,
+    ) -> Result<Vec<PushSubscription>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        use futures::TryStreamExt;
+
+        let user_ids_parsed: Vec<i64> = user_ids.iter().map(|x| x.0).collect();
+        let subscriptions = sqlx::query!(
+            "
+            SELECT endpoint, user_id, p256dh, auth, created, last_used
+            FROM users_push_subscriptions
+            WHERE user_id = ANY($1)
+            ORDER BY created DESC
+            ",
+            &user_ids_parsed,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().map(|r| PushSubscription {
+                endpoint: r.endpoint,
+                user_id: UserId(r.user_id),
+                p256dh: r.p256dh,
+                auth: r.auth,
+                created: r.created,
+                last_used: r.last_used,
+            }))
+        })
+        .try_collect::<Vec<PushSubscription>>()
+        .await?;
+
+        Ok(subscriptions)
+    }
+
+    pub async fn mark_used(
+        endpoints: &[String],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            UPDATE users_push_subscriptions
+            SET last_used = CURRENT_TIMESTAMP
+            WHERE endpoint = ANY($1)
+            ",
+            endpoints,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes subscriptions by endpoint. If `user_id` is set, only subscriptions owned by
+    /// that user are removed
+    pub async fn remove_many(
+        endpoints: &[String],
+        user_id: Option<UserId>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            DELETE FROM users_push_subscriptions
+            WHERE endpoint = ANY($1) AND ($2::bigint IS NULL OR user_id = $2)
+            ",
+            endpoints,
+            user_id.map(|x| x.0),
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use super::ids::*;
+use crate::models::reports::ReportOutcome;
 use chrono::{DateTime, Utc};
 
 pub struct Report {
@@ -24,6 +25,8 @@ pub struct QueryReport {
     pub created: DateTime<Utc>,
     pub closed: bool,
     pub thread_id: ThreadId,
+    pub outcome: Option<ReportOutcome>,
+    pub awaiting_reporter: bool,
 }
 
 impl Report {
@@ -77,7 +80,7 @@ impl Report {
         let report_ids_parsed: Vec<i64> = report_ids.iter().map(|x| x.0).collect();
         let reports = sqlx::query!(
             "
-            SELECT r.id, rt.name, r.mod_id, r.version_id, r.user_id, r.body, r.reporter, r.created, t.id thread_id, r.closed
+            SELECT r.id, rt.name, r.mod_id, r.version_id, r.user_id, r.body, r.reporter, r.created, t.id thread_id, r.closed, r.outcome, r.awaiting_reporter
             FROM reports r
             INNER JOIN report_types rt ON rt.id = r.report_type_id
             INNER JOIN threads t ON t.report_id = r.id
@@ -98,7 +101,9 @@ impl Report {
                 reporter: UserId(x.reporter),
                 created: x.created,
                 closed: x.closed,
-                thread_id: ThreadId(x.thread_id)
+                thread_id: ThreadId(x.thread_id),
+                outcome: x.outcome.as_deref().and_then(ReportOutcome::from_string),
+                awaiting_reporter: x.awaiting_reporter,
             }))
         })
         .try_collect::<Vec<QueryReport>>()
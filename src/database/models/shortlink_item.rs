@@ -0,0 +1,154 @@
+use super::ids::{ProjectId, UserId, VersionId};
+use super::DatabaseError;
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+/// The length of generated codes
+const CODE_LENGTH: usize = 7;
+
+/// A short link to a project or one of its versions
+#[derive(Clone, Debug)]
+pub struct Shortlink {
+    pub id: i64,
+    pub code: String,
+    pub project_id: ProjectId,
+    pub version_id: Option<VersionId>,
+    pub created_by: Option<UserId>,
+    pub clicks: i64,
+    pub created: DateTime<Utc>,
+}
+
+impl Shortlink {
+    /// Creates a link with a new random code
+    pub async fn insert(
+        project_id: ProjectId,
+        version_id: Option<VersionId>,
+        created_by: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Shortlink, DatabaseError> {
+        let mut rng = ChaCha20Rng::from_entropy();
+
+        loop {
+            let code = (&mut rng)
+                .sample_iter(&Alphanumeric)
+                .take(CODE_LENGTH)
+                .map(char::from)
+                .collect::<String>();
+
+            // Codes are only retried in the rare case one is taken
+            let result = sqlx::query!(
+                "
+                INSERT INTO shortlinks (code, project_id, version_id, created_by)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (code) DO NOTHING
+                RETURNING id, created
+                ",
+                code,
+                project_id as ProjectId,
+                version_id.map(|x| x.0),
+                created_by as UserId,
+            )
+            .fetch_optional(&mut **transaction)
+            .await?;
+
+            if let Some(result) = result {
+                return Ok(Shortlink {
+                    id: result.id,
+                    code,
+                    project_id,
+                    version_id,
+                    created_by: Some(created_by),
+                    clicks: 0,
+                    created: result.created,
+                });
+            }
+        }
+    }
+
+    /// Gets the links of a project, newest first
+    pub async fn get_many_project<'a, E>(
+        project_id: ProjectId,
+        exec: E,
+    ) -> Result<Vec<Shortlink>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT id, code, project_id, version_id, created_by, clicks, created
+            FROM shortlinks
+            WHERE project_id = $1
+            ORDER BY created DESC, id DESC
+            ",
+            project_id as ProjectId,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|x| Shortlink {
+                id: x.id,
+                code: x.code,
+                project_id: ProjectId(x.project_id),
+                version_id: x.version_id.map(VersionId),
+                created_by: x.created_by.map(UserId),
+                clicks: x.clicks,
+                created: x.created,
+            })
+            .collect())
+    }
+
+    /// Counts a click of a link, returning the project and version it links to
+    pub async fn click<'a, E>(
+        code: &str,
+        exec: E,
+    ) -> Result<Option<(ProjectId, Option<VersionId>)>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            UPDATE shortlinks
+            SET clicks = clicks + 1
+            WHERE code = $1
+            RETURNING project_id, version_id
+            ",
+            code,
+        )
+        .fetch_optional(exec)
+        .await?;
+
+        Ok(result.map(|x| (ProjectId(x.project_id), x.version_id.map(VersionId))))
+    }
+
+    /// Removes a link of a project
+    pub async fn remove<'a, E>(
+        code: &str,
+        project_id: ProjectId,
+        exec: E,
+    ) -> Result<Option<()>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            DELETE FROM shortlinks
+            WHERE code = $1 AND project_id = $2
+            ",
+            code,
+            project_id as ProjectId,
+        )
+        .execute(exec)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+}
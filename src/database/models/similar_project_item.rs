@@ -0,0 +1,79 @@
+use super::ids::ProjectId;
+use crate::database::models::DatabaseError;
+
+/// A project similar to another, with a score from 0 to 1
+#[derive(Clone, Debug)]
+pub struct SimilarProject {
+    pub project_id: ProjectId,
+    pub similar_id: ProjectId,
+    pub score: f32,
+}
+
+impl SimilarProject {
+    /// Replaces every computed similarity with a new set
+    pub async fn replace_all(
+        similarities: &[SimilarProject],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            DELETE FROM similar_projects
+            "
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO similar_projects (project_id, similar_id, score)
+            SELECT * FROM UNNEST($1::bigint[], $2::bigint[], $3::real[])
+            ",
+            &similarities
+                .iter()
+                .map(|x| x.project_id.0)
+                .collect::<Vec<_>>(),
+            &similarities
+                .iter()
+                .map(|x| x.similar_id.0)
+                .collect::<Vec<_>>(),
+            &similarities.iter().map(|x| x.score).collect::<Vec<_>>(),
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the projects most similar to a project, most similar first
+    pub async fn get_many<'a, E>(
+        project_id: ProjectId,
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<SimilarProject>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let similar = sqlx::query!(
+            "
+            SELECT project_id, similar_id, score
+            FROM similar_projects
+            WHERE project_id = $1
+            ORDER BY score DESC, similar_id
+            LIMIT $2
+            ",
+            project_id as ProjectId,
+            limit,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|r| SimilarProject {
+            project_id: ProjectId(r.project_id),
+            similar_id: ProjectId(r.similar_id),
+            score: r.score,
+        })
+        .collect();
+
+        Ok(similar)
+    }
+}
@@ -0,0 +1,128 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use crate::models::notifications::SimplePushProvider;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A user's simple push target: an ntfy topic or any URL accepting a JSON POST, which receives
+/// their notifications as short alerts
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimplePushTarget {
+    pub user_id: UserId,
+    pub provider: SimplePushProvider,
+    pub url: String,
+    pub token: Option<String>,
+    pub created: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+impl SimplePushTarget {
+    /// Inserts this target, replacing the user's existing target if they have one
+    pub async fn upsert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO users_simple_push_targets (
+                user_id, provider, url, token
+            )
+            VALUES (
+                $1, $2, $3, $4
+            )
+            ON CONFLICT (user_id) DO UPDATE
+            SET provider = EXCLUDED.provider, url = EXCLUDED.url, token = EXCLUDED.token,
+                created = CURRENT_TIMESTAMP, last_used = NULL
+            ",
+            self.user_id as UserId,
+            self.provider.as_str(),
+            self.url,
+            self.token,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_many_user<'a, E>(
+        user_ids: &[UserId],
+        exec: E,
+    ) -> Result<Vec<SimplePushTarget>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        use futures::TryStreamExt;
+
+        let user_ids_parsed: Vec<i64> = user_ids.iter().map(|x| x.0).collect();
+        let targets = sqlx::query!(
+            "
+            SELECT user_id, provider, url, token, created, last_used
+            FROM users_simple_push_targets
+            WHERE user_id = ANY($1)
+            ",
+            &user_ids_parsed,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right().and_then(|r| {
+                Some(SimplePushTarget {
+                    user_id: UserId(r.user_id),
+                    provider: SimplePushProvider::from_string(&r.provider)?,
+                    url: r.url,
+                    token: r.token,
+                    created: r.created,
+                    last_used: r.last_used,
+                })
+            }))
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+
+        Ok(targets)
+    }
+
+    pub async fn mark_used(
+        user_ids: &[UserId],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        if user_ids.is_empty() {
+            return Ok(());
+        }
+
+        let user_ids_parsed: Vec<i64> = user_ids.iter().map(|x| x.0).collect();
+        sqlx::query!(
+            "
+            UPDATE users_simple_push_targets
+            SET last_used = CURRENT_TIMESTAMP
+            WHERE user_id = ANY($1)
+            ",
+            &user_ids_parsed,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove(
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<()>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM users_simple_push_targets
+            WHERE user_id = $1
+            ",
+            user_id as UserId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use super::ids::{ProjectId, UserId};
+use super::DatabaseError;
+use crate::models::moderation::SpamScore;
+use crate::util::cursor::Cursor;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// An account or project which scored as likely spam, waiting for a moderator to review it
+#[derive(Clone, Debug)]
+pub struct SpamFlag {
+    pub id: i64,
+    pub user_id: UserId,
+    pub project_id: Option<ProjectId>,
+    pub score: SpamScore,
+    pub created: DateTime<Utc>,
+}
+
+impl SpamFlag {
+    /// Adds an account, or a project if one is given, to the queue, returning the flag's ID
+    pub async fn insert<'a, E>(
+        user_id: UserId,
+        project_id: Option<ProjectId>,
+        score: &SpamScore,
+        exec: E,
+    ) -> Result<i64, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let id = sqlx::query!(
+            "
+            INSERT INTO spam_flags (user_id, project_id, score, signals)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            ",
+            user_id as UserId,
+            project_id.map(|x| x.0),
+            score.score,
+            serde_json::to_value(&score.signals)?,
+        )
+        .fetch_one(exec)
+        .await?
+        .id;
+
+        Ok(id)
+    }
+
+    /// Gets a page of the queue, longest flagged first, starting after `cursor`
+    pub async fn get_page<'a, E>(
+        cursor: Option<Cursor>,
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<SpamFlag>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT id, user_id, project_id, score, signals, created
+            FROM spam_flags
+            WHERE $1::timestamptz IS NULL OR (created, id) > ($1, $2)
+            ORDER BY created ASC, id ASC
+            LIMIT $3
+            ",
+            cursor.map(|x| x.timestamp),
+            cursor.map(|x| x.id),
+            limit,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|x| SpamFlag {
+                id: x.id,
+                user_id: UserId(x.user_id),
+                project_id: x.project_id.map(ProjectId),
+                score: SpamScore {
+                    score: x.score,
+                    signals: serde_json::from_value(x.signals).unwrap_or_default(),
+                },
+                created: x.created,
+            })
+            .collect())
+    }
+
+    /// Gets the latest score of each of the projects which were flagged, by project
+    pub async fn get_many_projects<'a, E>(
+        project_ids: &[ProjectId],
+        exec: E,
+    ) -> Result<HashMap<ProjectId, SpamScore>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let results = sqlx::query!(
+            "
+            SELECT DISTINCT ON (project_id) project_id, score, signals
+            FROM spam_flags
+            WHERE project_id = ANY($1)
+            ORDER BY project_id, created DESC
+            ",
+            &project_ids.iter().map(|x| x.0).collect::<Vec<_>>(),
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .filter_map(|x| {
+                Some((
+                    ProjectId(x.project_id?),
+                    SpamScore {
+                        score: x.score,
+                        signals: serde_json::from_value(x.signals).unwrap_or_default(),
+                    },
+                ))
+            })
+            .collect())
+    }
+
+    /// Takes an account or project out of the queue once a moderator reviewed it
+    pub async fn remove<'a, E>(id: i64, exec: E) -> Result<Option<()>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            DELETE FROM spam_flags
+            WHERE id = $1
+            ",
+            id,
+        )
+        .execute(exec)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(()))
+        }
+    }
+}
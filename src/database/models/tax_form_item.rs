@@ -0,0 +1,107 @@
+use super::ids::*;
+use crate::database::models::DatabaseError;
+use crate::models::payouts::{TaxFormStatus, TaxFormType};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// The tax form a user filled in through the tax form provider. Payouts are only made to users
+/// with a complete form
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TaxForm {
+    pub user_id: UserId,
+    pub provider_id: String,
+    pub form_type: Option<TaxFormType>,
+    pub status: TaxFormStatus,
+    pub withholding_rate: Decimal,
+    pub created: DateTime<Utc>,
+    pub completed: Option<DateTime<Utc>>,
+}
+
+impl TaxForm {
+    /// Inserts this form, replacing the user's existing form if they have one
+    pub async fn upsert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO users_tax_forms (
+                user_id, provider_id, form_type, status, withholding_rate, completed
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6
+            )
+            ON CONFLICT (user_id) DO UPDATE
+            SET provider_id = EXCLUDED.provider_id, form_type = EXCLUDED.form_type,
+                status = EXCLUDED.status, withholding_rate = EXCLUDED.withholding_rate,
+                created = CURRENT_TIMESTAMP, completed = EXCLUDED.completed
+            ",
+            self.user_id as UserId,
+            self.provider_id,
+            self.form_type.map(|x| x.as_str()),
+            self.status.as_str(),
+            self.withholding_rate,
+            self.completed,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get<'a, E>(user_id: UserId, exec: E) -> Result<Option<TaxForm>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let form = sqlx::query!(
+            "
+            SELECT user_id, provider_id, form_type, status, withholding_rate, created, completed
+            FROM users_tax_forms
+            WHERE user_id = $1
+            ",
+            user_id as UserId,
+        )
+        .fetch_optional(exec)
+        .await?
+        .map(|r| TaxForm {
+            user_id: UserId(r.user_id),
+            provider_id: r.provider_id,
+            form_type: r.form_type.map(|x| TaxFormType::from_string(&x)),
+            status: TaxFormStatus::from_string(&r.status),
+            withholding_rate: r.withholding_rate,
+            created: r.created,
+            completed: r.completed,
+        });
+
+        Ok(form)
+    }
+
+    /// Records the result of the user filling in the form at the provider. Returns the user the
+    /// form belongs to, if it exists
+    pub async fn update_status(
+        provider_id: &str,
+        form_type: Option<TaxFormType>,
+        status: TaxFormStatus,
+        withholding_rate: Decimal,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<UserId>, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            UPDATE users_tax_forms
+            SET form_type = $1, status = $2, withholding_rate = $3,
+                completed = CASE WHEN $2::varchar = 'complete' THEN CURRENT_TIMESTAMP ELSE NULL END
+            WHERE provider_id = $4
+            RETURNING user_id
+            ",
+            form_type.map(|x| x.as_str()),
+            status.as_str(),
+            withholding_rate,
+            provider_id,
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
+
+        Ok(result.map(|x| UserId(x.user_id)))
+    }
+}
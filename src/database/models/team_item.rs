@@ -269,9 +269,7 @@ impl TeamMember {
             .try_collect::<Vec<TeamMember>>()
             .await?;
 
-            for (id, members) in &teams.into_iter().group_by(|x| x.team_id) {
-                let mut members = members.collect::<Vec<_>>();
-
+            for (id, mut members) in teams.into_iter().into_group_map_by(|x| x.team_id) {
                 redis
                     .set_serialized_to_json(TEAMS_NAMESPACE, id.0, &members, None)
                     .await?;
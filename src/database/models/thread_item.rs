@@ -2,6 +2,7 @@ use super::ids::*;
 use crate::database::models::DatabaseError;
 use crate::models::threads::{MessageBody, ThreadType};
 use chrono::{DateTime, Utc};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 pub struct ThreadBuilder {
@@ -22,6 +23,11 @@ pub struct Thread {
     pub messages: Vec<ThreadMessage>,
     pub members: Vec<UserId>,
     pub show_in_mod_inbox: bool,
+
+    /// Users who explicitly subscribed to new messages in the thread
+    pub subscribers: Vec<UserId>,
+    /// Users who muted the thread, and are never notified of new messages in it
+    pub muted: Vec<UserId>,
 }
 
 pub struct ThreadMessageBuilder {
@@ -37,6 +43,16 @@ pub struct ThreadMessage {
     pub author_id: Option<UserId>,
     pub body: MessageBody,
     pub created: DateTime<Utc>,
+    #[serde(default)]
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+/// A body a thread message had before it was edited
+pub struct ThreadMessageRevision {
+    pub message_id: ThreadMessageId,
+    pub body: MessageBody,
+    /// When this body was replaced
+    pub created: DateTime<Utc>,
 }
 
 impl ThreadMessageBuilder {
@@ -133,10 +149,13 @@ impl Thread {
             "
             SELECT t.id, t.thread_type, t.mod_id, t.report_id, t.show_in_mod_inbox,
             ARRAY_AGG(DISTINCT tm.user_id) filter (where tm.user_id is not null) members,
-            JSONB_AGG(DISTINCT jsonb_build_object('id', tmsg.id, 'author_id', tmsg.author_id, 'thread_id', tmsg.thread_id, 'body', tmsg.body, 'created', tmsg.created)) filter (where tmsg.id is not null) messages
+            JSONB_AGG(DISTINCT jsonb_build_object('id', tmsg.id, 'author_id', tmsg.author_id, 'thread_id', tmsg.thread_id, 'body', tmsg.body, 'created', tmsg.created, 'edited_at', tmsg.edited_at)) filter (where tmsg.id is not null) messages,
+            ARRAY_AGG(DISTINCT ts.user_id) filter (where ts.subscribed) subscribers,
+            ARRAY_AGG(DISTINCT ts.user_id) filter (where not ts.subscribed) muted
             FROM threads t
             LEFT OUTER JOIN threads_messages tmsg ON tmsg.thread_id = t.id
             LEFT OUTER JOIN threads_members tm ON tm.thread_id = t.id
+            LEFT OUTER JOIN threads_subscriptions ts ON ts.thread_id = t.id
             WHERE t.id = ANY($1)
             GROUP BY t.id
             ",
@@ -160,6 +179,8 @@ impl Thread {
                 },
                 members: x.members.unwrap_or_default().into_iter().map(UserId).collect(),
                 show_in_mod_inbox: x.show_in_mod_inbox,
+                subscribers: x.subscribers.unwrap_or_default().into_iter().map(UserId).collect(),
+                muted: x.muted.unwrap_or_default().into_iter().map(UserId).collect(),
             }))
         })
         .try_collect::<Vec<Thread>>()
@@ -168,6 +189,62 @@ impl Thread {
         Ok(threads)
     }
 
+    /// Whether the user is notified of new messages in this thread. Anyone who has posted in
+    /// the thread is subscribed automatically, unless they muted it
+    pub fn is_subscribed(&self, user_id: UserId) -> bool {
+        !self.muted.contains(&user_id)
+            && (self.subscribers.contains(&user_id)
+                || self.messages.iter().any(|x| x.author_id == Some(user_id)))
+    }
+
+    /// Gets every user that is subscribed to the thread, explicitly or automatically
+    pub fn get_subscribers(&self) -> Vec<UserId> {
+        self.subscribers
+            .iter()
+            .copied()
+            .chain(self.messages.iter().filter_map(|x| x.author_id))
+            .unique()
+            .filter(|x| !self.muted.contains(x))
+            .collect()
+    }
+
+    /// Sets whether the user is subscribed to (`Some(true)`) or muted (`Some(false)`) the thread.
+    /// `None` clears the choice, so the automatic rules apply again
+    pub async fn set_subscription(
+        id: ThreadId,
+        user_id: UserId,
+        subscribed: Option<bool>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        if let Some(subscribed) = subscribed {
+            sqlx::query!(
+                "
+                INSERT INTO threads_subscriptions (thread_id, user_id, subscribed)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (thread_id, user_id) DO UPDATE SET subscribed = EXCLUDED.subscribed
+                ",
+                id as ThreadId,
+                user_id as UserId,
+                subscribed,
+            )
+            .execute(&mut **transaction)
+            .await?;
+        } else {
+            sqlx::query!(
+                "
+                DELETE FROM threads_subscriptions
+                WHERE thread_id = $1 AND user_id = $2
+                ",
+                id as ThreadId,
+                user_id as UserId,
+            )
+            .execute(&mut **transaction)
+            .await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn remove_full(
         id: ThreadId,
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
@@ -229,7 +306,7 @@ impl ThreadMessage {
         let message_ids_parsed: Vec<i64> = message_ids.iter().map(|x| x.0).collect();
         let messages = sqlx::query!(
             "
-            SELECT tm.id, tm.author_id, tm.thread_id, tm.body, tm.created
+            SELECT tm.id, tm.author_id, tm.thread_id, tm.body, tm.created, tm.edited_at
             FROM threads_messages tm
             WHERE tm.id = ANY($1)
             ",
@@ -244,6 +321,7 @@ impl ThreadMessage {
                 body: serde_json::from_value(x.body)
                     .unwrap_or(MessageBody::Deleted { private: false }),
                 created: x.created,
+                edited_at: x.edited_at,
             }))
         })
         .try_collect::<Vec<ThreadMessage>>()
@@ -252,6 +330,69 @@ impl ThreadMessage {
         Ok(messages)
     }
 
+    /// Replaces the body of a message, keeping the previous body as a revision
+    pub async fn edit(
+        &self,
+        body: MessageBody,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO threads_messages_revisions (message_id, body)
+            VALUES ($1, $2)
+            ",
+            self.id as ThreadMessageId,
+            serde_json::to_value(&self.body)?,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            UPDATE threads_messages
+            SET body = $2, edited_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            ",
+            self.id as ThreadMessageId,
+            serde_json::to_value(body)?,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the previous bodies of a message, oldest first
+    pub async fn get_revisions<'a, E>(
+        id: ThreadMessageId,
+        exec: E,
+    ) -> Result<Vec<ThreadMessageRevision>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let revisions = sqlx::query!(
+            "
+            SELECT message_id, body, created
+            FROM threads_messages_revisions
+            WHERE message_id = $1
+            ORDER BY created ASC, id ASC
+            ",
+            id as ThreadMessageId,
+        )
+        .fetch_all(exec)
+        .await?;
+
+        Ok(revisions
+            .into_iter()
+            .map(|x| ThreadMessageRevision {
+                message_id: ThreadMessageId(x.message_id),
+                body: serde_json::from_value(x.body)
+                    .unwrap_or(MessageBody::Deleted { private: false }),
+                created: x.created,
+            })
+            .collect())
+    }
+
     pub async fn remove_full(
         id: ThreadMessageId,
         private: bool,
@@ -269,6 +410,17 @@ impl ThreadMessage {
         .execute(&mut **transaction)
         .await?;
 
+        // A deleted message shouldn't live on through its history
+        sqlx::query!(
+            "
+            DELETE FROM threads_messages_revisions
+            WHERE message_id = $1
+            ",
+            id as ThreadMessageId,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
         Ok(Some(()))
     }
 }
@@ -4,6 +4,7 @@ use crate::database::models::{DatabaseError, OrganizationId};
 use crate::database::redis::RedisPool;
 use crate::models::ids::base62_impl::{parse_base62, to_base62};
 use crate::models::users::Badges;
+use crate::util::money::{from_minor_units, Money};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
@@ -28,6 +29,8 @@ pub struct User {
     pub paypal_country: Option<String>,
     pub paypal_email: Option<String>,
     pub venmo_handle: Option<String>,
+    pub stripe_account_id: Option<String>,
+    pub stripe_payouts_enabled: bool,
 
     pub totp_secret: Option<String>,
 
@@ -35,16 +38,39 @@ pub struct User {
     pub name: Option<String>,
     pub email: Option<String>,
     pub email_verified: bool,
+    /// Whether email to the user's address bounced or was marked as spam
+    #[serde(default)]
+    pub email_undeliverable: bool,
     pub avatar_url: Option<String>,
     pub bio: Option<String>,
     pub created: DateTime<Utc>,
     pub role: String,
     pub badges: Badges,
 
+    /// The balance in the minor unit of `balance_currency`, such as cents. Fractions of the minor
+    /// unit are kept, as revenue accrues in them
     pub balance: Decimal,
+    pub balance_currency: String,
+    /// When the account was deleted, if it is waiting to be purged. Deleted accounts are left
+    /// out of `get_many`, so they can't sign in and are hidden everywhere until restored
+    #[serde(default)]
+    pub deleted: Option<DateTime<Utc>>,
 }
 
 impl User {
+    /// The balance as a decimal amount of `balance_currency`, such as dollars
+    pub fn balance_amount(&self) -> Decimal {
+        from_minor_units(self.balance, &self.balance_currency).unwrap_or_default()
+    }
+
+    /// The balance in whole minor units, as it is shown to the user
+    pub fn balance_money(&self) -> Money {
+        Money::from_minor_units(self.balance, &self.balance_currency).unwrap_or_else(|| Money {
+            amount: 0,
+            currency: self.balance_currency.clone(),
+        })
+    }
+
     pub async fn insert(
         &self,
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
@@ -137,6 +163,23 @@ impl User {
         exec: E,
         redis: &RedisPool,
     ) -> Result<Vec<User>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let mut users = User::get_many_including_deleted(users_strings, exec, redis).await?;
+        users.retain(|x| x.deleted.is_none());
+
+        Ok(users)
+    }
+
+    /// Gets users including the deleted ones which are waiting to be purged, for restoring and
+    /// purging them
+    #[tracing::instrument(skip_all, fields(count = users_strings.len()))]
+    pub async fn get_many_including_deleted<'a, E, T: ToString>(
+        users_strings: &[T],
+        exec: E,
+        redis: &RedisPool,
+    ) -> Result<Vec<User>, DatabaseError>
     where
         E: sqlx::Executor<'a, Database = sqlx::Postgres>,
     {
@@ -170,6 +213,9 @@ impl User {
                 .flatten()
                 .collect(),
         );
+        // A user asked for by both their ID and username is only returned once
+        user_ids.sort_unstable();
+        user_ids.dedup();
 
         if !user_ids.is_empty() {
             let users = redis
@@ -198,10 +244,11 @@ impl User {
                 SELECT id, name, email,
                     avatar_url, username, bio,
                     created, role, badges,
-                    balance,
+                    balance, balance_currency,
                     github_id, discord_id, gitlab_id, google_id, steam_id, microsoft_id,
-                    email_verified, password, totp_secret, paypal_id, paypal_country, paypal_email,
-                    venmo_handle
+                    email_verified, email_undeliverable, password, totp_secret,
+                    paypal_id, paypal_country, paypal_email,
+                    venmo_handle, stripe_account_id, stripe_payouts_enabled, deleted
                 FROM users
                 WHERE id = ANY($1) OR LOWER(username) = ANY($2)
                 ",
@@ -224,6 +271,7 @@ impl User {
                     name: u.name,
                     email: u.email,
                     email_verified: u.email_verified,
+                    email_undeliverable: u.email_undeliverable,
                     avatar_url: u.avatar_url,
                     username: u.username,
                     bio: u.bio,
@@ -231,12 +279,16 @@ impl User {
                     role: u.role,
                     badges: Badges::from_bits(u.badges as u64).unwrap_or_default(),
                     balance: u.balance,
+                    balance_currency: u.balance_currency,
                     password: u.password,
                     paypal_id: u.paypal_id,
                     paypal_country: u.paypal_country,
                     paypal_email: u.paypal_email,
                     venmo_handle: u.venmo_handle,
+                    stripe_account_id: u.stripe_account_id,
+                    stripe_payouts_enabled: u.stripe_payouts_enabled,
                     totp_secret: u.totp_secret,
+                    deleted: u.deleted,
                 }))
             })
             .try_collect::<Vec<User>>()
@@ -432,12 +484,115 @@ impl User {
         Ok(())
     }
 
+    /// Deletes an account, signing it out everywhere and hiding it until it is restored or
+    /// purged after the grace period
+    pub async fn soft_remove(
+        id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        redis: &RedisPool,
+    ) -> Result<Option<()>, DatabaseError> {
+        let username = sqlx::query!(
+            "
+            UPDATE users
+            SET deleted = CURRENT_TIMESTAMP
+            WHERE id = $1 AND deleted IS NULL
+            RETURNING username
+            ",
+            id as UserId,
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
+
+        let Some(username) = username else {
+            return Ok(None);
+        };
+
+        let sessions = sqlx::query!(
+            "
+            DELETE FROM sessions
+            WHERE user_id = $1
+            RETURNING id, session
+            ",
+            id as UserId,
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+
+        super::session_item::Session::clear_cache(
+            sessions
+                .into_iter()
+                .map(|x| (Some(super::SessionId(x.id)), Some(x.session), Some(id)))
+                .collect(),
+            redis,
+        )
+        .await?;
+        User::clear_caches(&[(id, Some(username.username))], redis).await?;
+
+        Ok(Some(()))
+    }
+
+    /// Restores a deleted account which has not been purged yet
+    pub async fn restore(
+        id: UserId,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        redis: &RedisPool,
+    ) -> Result<Option<()>, DatabaseError> {
+        let username = sqlx::query!(
+            "
+            UPDATE users
+            SET deleted = NULL
+            WHERE id = $1 AND deleted > CURRENT_TIMESTAMP - make_interval(days => $2)
+            RETURNING username
+            ",
+            id as UserId,
+            super::DELETION_GRACE_DAYS,
+        )
+        .fetch_optional(&mut **transaction)
+        .await?;
+
+        match username {
+            Some(row) => {
+                User::clear_caches(&[(id, Some(row.username))], redis).await?;
+                Ok(Some(()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the deleted accounts whose grace period is over
+    pub async fn get_purgeable<'a, E>(exec: E) -> Result<Vec<UserId>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let ids = sqlx::query!(
+            "
+            SELECT id FROM users
+            WHERE deleted <= CURRENT_TIMESTAMP - make_interval(days => $1)
+            ",
+            super::DELETION_GRACE_DAYS,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|x| UserId(x.id))
+        .collect();
+
+        Ok(ids)
+    }
+
     pub async fn remove(
         id: UserId,
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         redis: &RedisPool,
     ) -> Result<Option<()>, DatabaseError> {
-        let user = Self::get_id(id, &mut **transaction, redis).await?;
+        let user = Self::get_many_including_deleted(
+            &[crate::models::ids::UserId::from(id)],
+            &mut **transaction,
+            redis,
+        )
+        .await?
+        .into_iter()
+        .next();
 
         if let Some(delete_user) = user {
             User::clear_caches(&[(id, Some(delete_user.username))], redis).await?;
@@ -0,0 +1,111 @@
+use super::ids::{CollectionId, ProjectId, UserId};
+use super::DatabaseError;
+use crate::models::users::ProfileLink;
+use chrono::{DateTime, Utc};
+
+/// The sections of a user's profile
+#[derive(Clone, Debug)]
+pub struct UserProfile {
+    pub user_id: UserId,
+    pub about: Option<String>,
+    pub pinned_projects: Vec<ProjectId>,
+    pub featured_collection: Option<CollectionId>,
+    pub links: Vec<ProfileLink>,
+}
+
+impl UserProfile {
+    /// Gets a user's profile. Users who never edited theirs get an empty one
+    pub async fn get<'a, E>(user_id: UserId, exec: E) -> Result<UserProfile, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "
+            SELECT about, pinned_projects, featured_collection, links
+            FROM user_profiles
+            WHERE user_id = $1
+            ",
+            user_id as UserId,
+        )
+        .fetch_optional(exec)
+        .await?;
+
+        Ok(match result {
+            Some(x) => UserProfile {
+                user_id,
+                about: x.about,
+                pinned_projects: x.pinned_projects.into_iter().map(ProjectId).collect(),
+                featured_collection: x.featured_collection.map(CollectionId),
+                links: serde_json::from_value(x.links).unwrap_or_default(),
+            },
+            None => UserProfile {
+                user_id,
+                about: None,
+                pinned_projects: Vec::new(),
+                featured_collection: None,
+                links: Vec::new(),
+            },
+        })
+    }
+
+    pub async fn upsert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            INSERT INTO user_profiles (
+                user_id, about, pinned_projects, featured_collection, links
+            )
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id) DO UPDATE
+            SET about = EXCLUDED.about, pinned_projects = EXCLUDED.pinned_projects,
+                featured_collection = EXCLUDED.featured_collection, links = EXCLUDED.links,
+                updated = CURRENT_TIMESTAMP
+            ",
+            self.user_id as UserId,
+            self.about.as_deref(),
+            &self.pinned_projects.iter().map(|x| x.0).collect::<Vec<_>>(),
+            self.featured_collection.map(|x| x.0),
+            serde_json::to_value(&self.links)?,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Marks the links of a user going to `url` as verified. Links edited since are left alone
+    pub async fn set_link_verified<'a, E>(
+        user_id: UserId,
+        url: &str,
+        verified: DateTime<Utc>,
+        exec: E,
+    ) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            UPDATE user_profiles
+            SET links = (
+                SELECT COALESCE(jsonb_agg(
+                    CASE WHEN link->>'url' = $2
+                    THEN jsonb_set(link, '{verified}', to_jsonb($3::timestamptz))
+                    ELSE link END
+                    ORDER BY i
+                ), '[]')
+                FROM jsonb_array_elements(links) WITH ORDINALITY AS l(link, i)
+            )
+            WHERE user_id = $1
+            ",
+            user_id as UserId,
+            url,
+            verified,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use super::DatabaseError;
 use crate::database::models::loader_fields::{
     QueryLoaderField, QueryLoaderFieldEnumValue, QueryVersionField,
 };
-use crate::database::redis::RedisPool;
+use crate::database::redis::{Cached, RedisConnection, RedisPool};
 use crate::models::projects::{FileType, VersionStatus};
 use chrono::{DateTime, Utc};
 use dashmap::{DashMap, DashSet};
@@ -13,6 +13,7 @@ use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::iter;
+use std::time::Instant;
 
 pub const VERSIONS_NAMESPACE: &str = "versions";
 const VERSION_FILES_NAMESPACE: &str = "versions_files";
@@ -192,6 +193,7 @@ impl VersionBuilder {
             status: self.status,
             requested_status: self.requested_status,
             ordering: self.ordering,
+            revision: 1,
         };
 
         version.insert(transaction).await?;
@@ -278,6 +280,8 @@ pub struct Version {
     pub status: VersionStatus,
     pub requested_status: Option<VersionStatus>,
     pub ordering: Option<i32>,
+    /// Incremented on every edit, so edits made from an older revision can be rejected
+    pub revision: i32,
 }
 
 impl Version {
@@ -317,6 +321,31 @@ impl Version {
         Ok(())
     }
 
+    /// Starts an edit by moving the version to its next revision, which locks it until the
+    /// transaction ends. Returns the new revision, or `None` if the version is not at one of
+    /// the `expected` revisions
+    pub async fn bump_revision(
+        id: VersionId,
+        expected: Option<&[i32]>,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<i32>, DatabaseError> {
+        let revision = sqlx::query!(
+            "
+            UPDATE versions
+            SET revision = revision + 1
+            WHERE id = $1 AND ($2::integer[] IS NULL OR revision = ANY($2))
+            RETURNING revision
+            ",
+            id as VersionId,
+            expected,
+        )
+        .fetch_optional(&mut **transaction)
+        .await?
+        .map(|x| x.revision);
+
+        Ok(revision)
+    }
+
     pub async fn remove_full(
         id: VersionId,
         redis: &RedisPool,
@@ -461,6 +490,7 @@ impl Version {
             .map(|x| x.into_iter().next())
     }
 
+    #[tracing::instrument(skip_all, fields(count = version_ids.len()))]
     pub async fn get_many<'a, E>(
         version_ids: &[VersionId],
         exec: E,
@@ -475,8 +505,6 @@ impl Version {
             .copied()
             .collect::<Vec<VersionId>>();
 
-        use futures::stream::TryStreamExt;
-
         if version_ids.is_empty() {
             return Ok(Vec::new());
         }
@@ -484,331 +512,482 @@ impl Version {
         let mut exec = exec.acquire().await?;
         let mut redis = redis.connect().await?;
 
-        let mut version_ids_parsed: Vec<i64> = version_ids.iter().map(|x| x.0).collect();
-
-        let mut found_versions = Vec::new();
+        let (cached_versions, mut version_ids_parsed) = Self::get_cached_many(
+            &version_ids.iter().map(|x| x.0).collect::<Vec<_>>(),
+            &mut redis,
+        )
+        .await?;
 
-        let versions = redis
-            .multi_get::<String>(
+        // Versions due to expire are refreshed by whichever request takes their lock, while
+        // the others keep using the cached copy
+        let refresh = redis
+            .try_lock_many(
                 VERSIONS_NAMESPACE,
-                version_ids_parsed
-                    .clone()
+                &cached_versions
                     .iter()
-                    .map(|x| x.to_string())
+                    .filter(|x| x.should_refresh())
+                    .map(|x| x.value.inner.id.0.to_string())
                     .collect::<Vec<_>>(),
             )
             .await?;
+        let mut found_versions = Vec::new();
+        for version in cached_versions {
+            if !refresh.contains(&version.value.inner.id.0.to_string()) {
+                found_versions.push(version.value);
+            }
+        }
 
-        for version in versions {
-            if let Some(version) =
-                version.and_then(|x| serde_json::from_str::<QueryVersion>(&x).ok())
-            {
-                version_ids_parsed.retain(|x| &version.inner.id.0 != x);
-                found_versions.push(version);
-                continue;
+        let mut locked = Vec::new();
+        if !version_ids_parsed.is_empty() {
+            locked = redis
+                .lock_or_wait(
+                    VERSIONS_NAMESPACE,
+                    &version_ids_parsed
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>(),
+                )
+                .await?;
+
+            // Versions another request was loading may be cached by now
+            let waited = version_ids_parsed
+                .iter()
+                .filter(|x| !locked.contains(&x.to_string()))
+                .copied()
+                .collect::<Vec<_>>();
+            if !waited.is_empty() {
+                let (cached_versions, uncached_ids) =
+                    Self::get_cached_many(&waited, &mut redis).await?;
+                found_versions.extend(cached_versions.into_iter().map(|x| x.value));
+                version_ids_parsed
+                    .retain(|x| locked.contains(&x.to_string()) || uncached_ids.contains(x));
             }
         }
+        version_ids_parsed.extend(refresh.iter().flat_map(|x| x.parse::<i64>().ok()));
+        locked.extend(refresh);
 
         if !version_ids_parsed.is_empty() {
-            let loader_field_ids = DashSet::new();
-            let loader_field_enum_value_ids = DashSet::new();
-            let version_fields: DashMap<VersionId, Vec<QueryVersionField>> = sqlx::query!(
-                "
-                SELECT version_id, field_id, int_value, enum_value, string_value
-                FROM version_fields
-                WHERE version_id = ANY($1)
-                ",
-                &version_ids_parsed
-            )
-            .fetch(&mut *exec)
-            .try_fold(
-                DashMap::new(),
-                |acc: DashMap<VersionId, Vec<QueryVersionField>>, m| {
-                    let qvf = QueryVersionField {
-                        version_id: VersionId(m.version_id),
-                        field_id: LoaderFieldId(m.field_id),
-                        int_value: m.int_value,
-                        enum_value: m.enum_value.map(LoaderFieldEnumValueId),
-                        string_value: m.string_value,
-                    };
-
-                    loader_field_ids.insert(LoaderFieldId(m.field_id));
-                    if let Some(enum_value) = m.enum_value {
-                        loader_field_enum_value_ids.insert(LoaderFieldEnumValueId(enum_value));
-                    }
+            // The locks are released even if loading fails, rather than making other requests
+            // wait for them to expire
+            let loaded = async {
+                let started = Instant::now();
+                let db_versions = Self::load_many(&version_ids_parsed, &mut exec).await?;
 
-                    acc.entry(VersionId(m.version_id)).or_default().push(qvf);
-                    async move { Ok(acc) }
-                },
-            )
-            .await?;
+                redis
+                    .set_cached_many(
+                        VERSIONS_NAMESPACE,
+                        db_versions.iter().map(|x| (x.inner.id.0, x)),
+                        started.elapsed(),
+                    )
+                    .await?;
 
-            let loader_fields: Vec<QueryLoaderField> = sqlx::query!(
-                "
-                SELECT DISTINCT id, field, field_type, enum_type, min_val, max_val, optional
-                FROM loader_fields lf
-                WHERE id = ANY($1)  
-                ",
-                &loader_field_ids.iter().map(|x| x.0).collect::<Vec<_>>()
-            )
-            .fetch(&mut *exec)
-            .map_ok(|m| QueryLoaderField {
-                id: LoaderFieldId(m.id),
-                field: m.field,
-                field_type: m.field_type,
-                enum_type: m.enum_type.map(LoaderFieldEnumId),
-                min_val: m.min_val,
-                max_val: m.max_val,
-                optional: m.optional,
-            })
-            .try_collect()
-            .await?;
+                Ok::<_, DatabaseError>(db_versions)
+            }
+            .await;
 
-            let loader_field_enum_values: Vec<QueryLoaderFieldEnumValue> = sqlx::query!(
-                "
-                SELECT DISTINCT id, enum_id, value, ordering, created, metadata
-                FROM loader_field_enum_values lfev
-                WHERE id = ANY($1)  
-                ORDER BY enum_id, ordering, created ASC
-                ",
-                &loader_field_enum_value_ids
-                    .iter()
-                    .map(|x| x.0)
-                    .collect::<Vec<_>>()
-            )
-            .fetch(&mut *exec)
-            .map_ok(|m| QueryLoaderFieldEnumValue {
-                id: LoaderFieldEnumValueId(m.id),
-                enum_id: LoaderFieldEnumId(m.enum_id),
-                value: m.value,
-                ordering: m.ordering,
-                created: m.created,
-                metadata: m.metadata,
-            })
-            .try_collect()
-            .await?;
+            redis.unlock_many(VERSIONS_NAMESPACE, &locked).await?;
+            found_versions.extend(loaded?);
+        }
+
+        Ok(found_versions)
+    }
 
-            type StringTriple = (Vec<String>, Vec<String>, Vec<String>);
-            let loaders_ptypes_games: DashMap<VersionId, StringTriple> = sqlx::query!(
+    /// Gets versions straight from the database, without reading or writing the cache. This is
+    /// for bulk reads from the read replica, whose rows may lag behind and must not be cached
+    pub async fn get_many_uncached<'a, E>(
+        version_ids: &[VersionId],
+        exec: E,
+    ) -> Result<Vec<QueryVersion>, DatabaseError>
+    where
+        E: sqlx::Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut exec = exec.acquire().await?;
+
+        Self::load_many(
+            &version_ids.iter().map(|x| x.0).unique().collect::<Vec<_>>(),
+            &mut exec,
+        )
+        .await
+    }
+
+    /// Loads versions from the database
+    async fn load_many(
+        version_ids: &[i64],
+        exec: &mut sqlx::PgConnection,
+    ) -> Result<Vec<QueryVersion>, DatabaseError> {
+        let mut version_fields = Self::load_version_fields(version_ids, exec).await?;
+        let mut loaders_ptypes_games = Self::load_loaders(version_ids, exec).await?;
+        let mut files = Self::load_files(version_ids, exec).await?;
+        let mut dependencies = Self::load_dependencies(version_ids, exec).await?;
+
+        let versions: Vec<QueryVersion> = sqlx::query!(
                 "
-                SELECT DISTINCT version_id,
-                    ARRAY_AGG(DISTINCT l.loader) filter (where l.loader is not null) loaders,
-                    ARRAY_AGG(DISTINCT pt.name) filter (where pt.name is not null) project_types,
-                    ARRAY_AGG(DISTINCT g.slug) filter (where g.slug is not null) games
+                SELECT v.id id, v.mod_id mod_id, v.author_id author_id, v.name version_name, v.version_number version_number,
+                v.changelog changelog, v.date_published date_published, v.downloads downloads,
+                v.version_type version_type, v.featured featured, v.status status, v.requested_status requested_status, v.ordering ordering,
+                v.revision revision
                 FROM versions v
-                INNER JOIN loaders_versions lv ON v.id = lv.version_id
-                INNER JOIN loaders l ON lv.loader_id = l.id
-                INNER JOIN loaders_project_types lpt ON lpt.joining_loader_id = l.id
-                INNER JOIN project_types pt ON pt.id = lpt.joining_project_type_id
-                INNER JOIN loaders_project_types_games lptg ON lptg.loader_id = l.id AND lptg.project_type_id = pt.id
-                INNER JOIN games g ON lptg.game_id = g.id
                 WHERE v.id = ANY($1)
-                GROUP BY version_id
+                ORDER BY v.ordering ASC NULLS LAST, v.date_published ASC;
                 ",
-                &version_ids_parsed
-            ).fetch(&mut *exec)
-            .map_ok(|m| {
-                let version_id = VersionId(m.version_id);
-                let loaders = m.loaders.unwrap_or_default();
-                    let project_types = m.project_types.unwrap_or_default();
-                    let games = m.games.unwrap_or_default();
-
-                    (version_id, (loaders, project_types, games))
-
+                version_ids
+            )
+            .fetch_all(&mut *exec)
+            .await?
+            .into_iter()
+            .map(|v| {
+                let version_id = VersionId(v.id);
+                let (loaders, project_types, games) =
+                    loaders_ptypes_games.remove(&version_id).unwrap_or_default();
+
+                QueryVersion {
+                    inner: Version {
+                        id: version_id,
+                        project_id: ProjectId(v.mod_id),
+                        author_id: UserId(v.author_id),
+                        name: v.version_name,
+                        version_number: v.version_number,
+                        changelog: v.changelog,
+                        date_published: v.date_published,
+                        downloads: v.downloads,
+                        version_type: v.version_type,
+                        featured: v.featured,
+                        status: VersionStatus::from_string(&v.status),
+                        requested_status: v
+                            .requested_status
+                            .map(|x| VersionStatus::from_string(&x)),
+                        ordering: v.ordering,
+                        revision: v.revision,
+                    },
+                    files: files.remove(&version_id).unwrap_or_default(),
+                    version_fields: version_fields.remove(&version_id).unwrap_or_default(),
+                    loaders,
+                    project_types,
+                    games,
+                    dependencies: dependencies.remove(&version_id).unwrap_or_default(),
                 }
-            ).try_collect().await?;
+            })
+            .collect();
 
-            #[derive(Deserialize)]
-            struct Hash {
-                pub file_id: FileId,
-                pub algorithm: String,
-                pub hash: String,
-            }
+        Ok(versions)
+    }
 
-            #[derive(Deserialize)]
-            struct File {
-                pub id: FileId,
-                pub url: String,
-                pub filename: String,
-                pub primary: bool,
-                pub size: u32,
-                pub file_type: Option<FileType>,
-            }
+    /// Loads the version fields of many versions, keyed by version. Like the other loaders
+    /// below, this runs the same number of queries however many versions are loaded, so
+    /// versions are always loaded in batches rather than one by one
+    async fn load_version_fields(
+        version_ids: &[i64],
+        exec: &mut sqlx::PgConnection,
+    ) -> Result<HashMap<VersionId, Vec<VersionField>>, DatabaseError> {
+        use futures::stream::TryStreamExt;
 
-            let file_ids = DashSet::new();
-            let reverse_file_map = DashMap::new();
-            let files : DashMap<VersionId, Vec<File>> = sqlx::query!(
-                "
-                SELECT DISTINCT version_id, f.id, f.url, f.filename, f.is_primary, f.size, f.file_type
-                FROM files f
-                WHERE f.version_id = ANY($1)
-                ",
-                &version_ids_parsed
-            ).fetch(&mut *exec)
-            .try_fold(DashMap::new(), |acc : DashMap<VersionId, Vec<File>>, m| {
-                    let file = File {
-                        id: FileId(m.id),
-                        url: m.url,
-                        filename: m.filename,
-                        primary: m.is_primary,
-                        size: m.size as u32,
-                        file_type: m.file_type.map(|x| FileType::from_string(&x)),
-                    };
-
-                    file_ids.insert(FileId(m.id));
-                    reverse_file_map.insert(FileId(m.id), VersionId(m.version_id));
-
-                    acc.entry(VersionId(m.version_id))
-                    .or_default()
-                    .push(file);
-                    async move { Ok(acc) }
+        let loader_field_ids = DashSet::new();
+        let loader_field_enum_value_ids = DashSet::new();
+        let version_fields: DashMap<VersionId, Vec<QueryVersionField>> = sqlx::query!(
+            "
+            SELECT version_id, field_id, int_value, enum_value, string_value
+            FROM version_fields
+            WHERE version_id = ANY($1)
+            ",
+            version_ids
+        )
+        .fetch(&mut *exec)
+        .try_fold(
+            DashMap::new(),
+            |acc: DashMap<VersionId, Vec<QueryVersionField>>, m| {
+                let qvf = QueryVersionField {
+                    version_id: VersionId(m.version_id),
+                    field_id: LoaderFieldId(m.field_id),
+                    int_value: m.int_value,
+                    enum_value: m.enum_value.map(LoaderFieldEnumValueId),
+                    string_value: m.string_value,
+                };
+
+                loader_field_ids.insert(LoaderFieldId(m.field_id));
+                if let Some(enum_value) = m.enum_value {
+                    loader_field_enum_value_ids.insert(LoaderFieldEnumValueId(enum_value));
                 }
-            ).await?;
 
-            let hashes: DashMap<VersionId, Vec<Hash>> = sqlx::query!(
-                "
-                SELECT DISTINCT file_id, algorithm, encode(hash, 'escape') hash
-                FROM hashes
-                WHERE file_id = ANY($1)
-                ",
-                &file_ids.iter().map(|x| x.0).collect::<Vec<_>>()
-            )
-            .fetch(&mut *exec)
-            .try_fold(DashMap::new(), |acc: DashMap<VersionId, Vec<Hash>>, m| {
-                if let Some(found_hash) = m.hash {
-                    let hash = Hash {
-                        file_id: FileId(m.file_id),
-                        algorithm: m.algorithm,
-                        hash: found_hash,
-                    };
-
-                    if let Some(version_id) = reverse_file_map.get(&FileId(m.file_id)) {
-                        acc.entry(*version_id).or_default().push(hash);
-                    }
-                }
+                acc.entry(VersionId(m.version_id)).or_default().push(qvf);
                 async move { Ok(acc) }
+            },
+        )
+        .await?;
+
+        let loader_fields: Vec<QueryLoaderField> = sqlx::query!(
+            "
+            SELECT DISTINCT id, field, field_type, enum_type, min_val, max_val, optional
+            FROM loader_fields lf
+            WHERE id = ANY($1)  
+            ",
+            &loader_field_ids.iter().map(|x| x.0).collect::<Vec<_>>()
+        )
+        .fetch(&mut *exec)
+        .map_ok(|m| QueryLoaderField {
+            id: LoaderFieldId(m.id),
+            field: m.field,
+            field_type: m.field_type,
+            enum_type: m.enum_type.map(LoaderFieldEnumId),
+            min_val: m.min_val,
+            max_val: m.max_val,
+            optional: m.optional,
+        })
+        .try_collect()
+        .await?;
+
+        let loader_field_enum_values: Vec<QueryLoaderFieldEnumValue> = sqlx::query!(
+            "
+            SELECT DISTINCT id, enum_id, value, ordering, created, metadata
+            FROM loader_field_enum_values lfev
+            WHERE id = ANY($1)  
+            ORDER BY enum_id, ordering, created ASC
+            ",
+            &loader_field_enum_value_ids
+                .iter()
+                .map(|x| x.0)
+                .collect::<Vec<_>>()
+        )
+        .fetch(&mut *exec)
+        .map_ok(|m| QueryLoaderFieldEnumValue {
+            id: LoaderFieldEnumValueId(m.id),
+            enum_id: LoaderFieldEnumId(m.enum_id),
+            value: m.value,
+            ordering: m.ordering,
+            created: m.created,
+            metadata: m.metadata,
+        })
+        .try_collect()
+        .await?;
+
+        Ok(version_fields
+            .into_iter()
+            .map(|(version_id, version_fields)| {
+                (
+                    version_id,
+                    VersionField::from_query_json(
+                        version_fields,
+                        &loader_fields,
+                        &loader_field_enum_values,
+                        false,
+                    ),
+                )
             })
-            .await?;
+            .collect())
+    }
 
-            let dependencies : DashMap<VersionId, Vec<QueryDependency>> = sqlx::query!(
-                "
-                SELECT DISTINCT dependent_id as version_id, d.mod_dependency_id as dependency_project_id, d.dependency_id as dependency_version_id, d.dependency_file_name as file_name, d.dependency_type as dependency_type
-                FROM dependencies d
-                WHERE dependent_id = ANY($1)
-                ",
-                &version_ids_parsed
-            ).fetch(&mut *exec)
-            .try_fold(DashMap::new(), |acc : DashMap<_,Vec<QueryDependency>>, m| {
-                    let dependency = QueryDependency {
-                        project_id: m.dependency_project_id.map(ProjectId),
-                        version_id: m.dependency_version_id.map(VersionId),
-                        file_name: m.file_name,
-                        dependency_type: m.dependency_type,
-                    };
-
-                    acc.entry(VersionId(m.version_id))
-                    .or_default()
-                    .push(dependency);
-                    async move { Ok(acc) }
+    /// Loads the loaders, project types and games of many versions, keyed by version
+    async fn load_loaders(
+        version_ids: &[i64],
+        exec: &mut sqlx::PgConnection,
+    ) -> Result<HashMap<VersionId, (Vec<String>, Vec<String>, Vec<String>)>, DatabaseError> {
+        use futures::stream::TryStreamExt;
+
+        sqlx::query!(
+            "
+            SELECT DISTINCT version_id,
+                ARRAY_AGG(DISTINCT l.loader) filter (where l.loader is not null) loaders,
+                ARRAY_AGG(DISTINCT pt.name) filter (where pt.name is not null) project_types,
+                ARRAY_AGG(DISTINCT g.slug) filter (where g.slug is not null) games
+            FROM versions v
+            INNER JOIN loaders_versions lv ON v.id = lv.version_id
+            INNER JOIN loaders l ON lv.loader_id = l.id
+            INNER JOIN loaders_project_types lpt ON lpt.joining_loader_id = l.id
+            INNER JOIN project_types pt ON pt.id = lpt.joining_project_type_id
+            INNER JOIN loaders_project_types_games lptg ON lptg.loader_id = l.id AND lptg.project_type_id = pt.id
+            INNER JOIN games g ON lptg.game_id = g.id
+            WHERE v.id = ANY($1)
+            GROUP BY version_id
+            ",
+            version_ids
+        ).fetch(&mut *exec)
+        .map_ok(|m| {
+            let version_id = VersionId(m.version_id);
+            let loaders = m.loaders.unwrap_or_default();
+                let project_types = m.project_types.unwrap_or_default();
+                let games = m.games.unwrap_or_default();
+
+                (version_id, (loaders, project_types, games))
+
+            }
+        ).try_collect().await.map_err(Into::into)
+    }
+
+    /// Loads the files of many versions along with their hashes, keyed by version. The primary
+    /// file of each version comes first
+    async fn load_files(
+        version_ids: &[i64],
+        exec: &mut sqlx::PgConnection,
+    ) -> Result<HashMap<VersionId, Vec<QueryFile>>, DatabaseError> {
+        use futures::stream::TryStreamExt;
+
+        #[derive(Deserialize)]
+        struct Hash {
+            pub file_id: FileId,
+            pub algorithm: String,
+            pub hash: String,
+        }
+
+        #[derive(Deserialize)]
+        struct File {
+            pub id: FileId,
+            pub url: String,
+            pub filename: String,
+            pub primary: bool,
+            pub size: u32,
+            pub file_type: Option<FileType>,
+        }
+
+        let file_ids = DashSet::new();
+        let reverse_file_map = DashMap::new();
+        let files: DashMap<VersionId, Vec<File>> = sqlx::query!(
+            "
+            SELECT DISTINCT version_id, f.id, f.url, f.filename, f.is_primary, f.size, f.file_type
+            FROM files f
+            WHERE f.version_id = ANY($1)
+            ",
+            version_ids
+        )
+        .fetch(&mut *exec)
+        .try_fold(DashMap::new(), |acc: DashMap<VersionId, Vec<File>>, m| {
+            let file = File {
+                id: FileId(m.id),
+                url: m.url,
+                filename: m.filename,
+                primary: m.is_primary,
+                size: m.size as u32,
+                file_type: m.file_type.map(|x| FileType::from_string(&x)),
+            };
+
+            file_ids.insert(FileId(m.id));
+            reverse_file_map.insert(FileId(m.id), VersionId(m.version_id));
+
+            acc.entry(VersionId(m.version_id)).or_default().push(file);
+            async move { Ok(acc) }
+        })
+        .await?;
+
+        let hashes: DashMap<VersionId, Vec<Hash>> = sqlx::query!(
+            "
+            SELECT DISTINCT file_id, algorithm, encode(hash, 'escape') hash
+            FROM hashes
+            WHERE file_id = ANY($1)
+            ",
+            &file_ids.iter().map(|x| x.0).collect::<Vec<_>>()
+        )
+        .fetch(&mut *exec)
+        .try_fold(DashMap::new(), |acc: DashMap<VersionId, Vec<Hash>>, m| {
+            if let Some(found_hash) = m.hash {
+                let hash = Hash {
+                    file_id: FileId(m.file_id),
+                    algorithm: m.algorithm,
+                    hash: found_hash,
+                };
+
+                if let Some(version_id) = reverse_file_map.get(&FileId(m.file_id)) {
+                    acc.entry(*version_id).or_default().push(hash);
                 }
-            ).await?;
+            }
+            async move { Ok(acc) }
+        })
+        .await?;
 
-            let db_versions: Vec<QueryVersion> = sqlx::query!(
-                "
-                SELECT v.id id, v.mod_id mod_id, v.author_id author_id, v.name version_name, v.version_number version_number,
-                v.changelog changelog, v.date_published date_published, v.downloads downloads,
-                v.version_type version_type, v.featured featured, v.status status, v.requested_status requested_status, v.ordering ordering
-                FROM versions v
-                WHERE v.id = ANY($1)
-                ORDER BY v.ordering ASC NULLS LAST, v.date_published ASC;
-                ",
-                &version_ids_parsed
-            )
-                .fetch_many(&mut *exec)
-                .try_filter_map(|e| async {
-                    Ok(e.right().map(|v|
-                        {
-                        let version_id = VersionId(v.id);
-                        let (loaders, project_types, games) = loaders_ptypes_games.remove(&version_id).map(|x|x.1).unwrap_or_default();
-                        let files = files.remove(&version_id).map(|x|x.1).unwrap_or_default();
-                        let hashes = hashes.remove(&version_id).map(|x|x.1).unwrap_or_default();
-                        let version_fields = version_fields.remove(&version_id).map(|x|x.1).unwrap_or_default();
-                        let dependencies = dependencies.remove(&version_id).map(|x|x.1).unwrap_or_default();
-
-                        QueryVersion {
-                            inner: Version {
-                                id: VersionId(v.id),
-                                project_id: ProjectId(v.mod_id),
-                                author_id: UserId(v.author_id),
-                                name: v.version_name,
-                                version_number: v.version_number,
-                                changelog: v.changelog,
-                                date_published: v.date_published,
-                                downloads: v.downloads,
-                                version_type: v.version_type,
-                                featured: v.featured,
-                                status: VersionStatus::from_string(&v.status),
-                                requested_status: v.requested_status
-                                    .map(|x| VersionStatus::from_string(&x)),
-                                ordering: v.ordering,
-                            },
-                            files: {
-                                let mut files = files.into_iter().map(|x| {
-                                    let mut file_hashes = HashMap::new();
-
-                                    for hash in hashes.iter() {
-                                        if hash.file_id == x.id {
-                                            file_hashes.insert(
-                                                hash.algorithm.clone(),
-                                                hash.hash.clone(),
-                                            );
-                                        }
-                                    }
-
-                                    QueryFile {
-                                        id: x.id,
-                                        url: x.url.clone(),
-                                        filename: x.filename.clone(),
-                                        hashes: file_hashes,
-                                        primary: x.primary,
-                                        size: x.size,
-                                        file_type: x.file_type,
-                                    }
-                                }).collect::<Vec<_>>();
-
-                                files.sort_by(|a, b| {
-                                    if a.primary {
-                                        Ordering::Less
-                                    } else if b.primary {
-                                        Ordering::Greater
-                                    } else {
-                                        a.filename.cmp(&b.filename)
-                                    }
-                                });
-
-                                files
-                            },
-                            version_fields: VersionField::from_query_json(version_fields, &loader_fields, &loader_field_enum_values, false),
-                            loaders,
-                            project_types,
-                            games,
-                            dependencies,
+        Ok(files
+            .into_iter()
+            .map(|(version_id, files)| {
+                let hashes = hashes.remove(&version_id).map(|x| x.1).unwrap_or_default();
+
+                let mut files = files
+                    .into_iter()
+                    .map(|x| {
+                        let mut file_hashes = HashMap::new();
+
+                        for hash in hashes.iter() {
+                            if hash.file_id == x.id {
+                                file_hashes.insert(hash.algorithm.clone(), hash.hash.clone());
+                            }
                         }
-                }))
-                })
-                .try_collect::<Vec<QueryVersion>>()
-                .await?;
 
-            for version in db_versions {
-                redis
-                    .set_serialized_to_json(VERSIONS_NAMESPACE, version.inner.id.0, &version, None)
-                    .await?;
+                        QueryFile {
+                            id: x.id,
+                            url: x.url,
+                            filename: x.filename,
+                            hashes: file_hashes,
+                            primary: x.primary,
+                            size: x.size,
+                            file_type: x.file_type,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                files.sort_by(|a, b| {
+                    if a.primary {
+                        Ordering::Less
+                    } else if b.primary {
+                        Ordering::Greater
+                    } else {
+                        a.filename.cmp(&b.filename)
+                    }
+                });
+
+                (version_id, files)
+            })
+            .collect())
+    }
 
-                found_versions.push(version);
+    /// Loads the dependencies of many versions, keyed by version
+    async fn load_dependencies(
+        version_ids: &[i64],
+        exec: &mut sqlx::PgConnection,
+    ) -> Result<HashMap<VersionId, Vec<QueryDependency>>, DatabaseError> {
+        use futures::stream::TryStreamExt;
+
+        let dependencies : DashMap<VersionId, Vec<QueryDependency>> = sqlx::query!(
+            "
+            SELECT DISTINCT dependent_id as version_id, d.mod_dependency_id as dependency_project_id, d.dependency_id as dependency_version_id, d.dependency_file_name as file_name, d.dependency_type as dependency_type
+            FROM dependencies d
+            WHERE dependent_id = ANY($1)
+            ",
+            version_ids
+        ).fetch(&mut *exec)
+        .try_fold(DashMap::new(), |acc : DashMap<_,Vec<QueryDependency>>, m| {
+                let dependency = QueryDependency {
+                    project_id: m.dependency_project_id.map(ProjectId),
+                    version_id: m.dependency_version_id.map(VersionId),
+                    file_name: m.file_name,
+                    dependency_type: m.dependency_type,
+                };
+
+                acc.entry(VersionId(m.version_id))
+                .or_default()
+                .push(dependency);
+                async move { Ok(acc) }
             }
+        ).await?;
+
+        Ok(dependencies.into_iter().collect())
+    }
+
+    /// Reads versions from the cache, along with the IDs of the versions not cached
+    async fn get_cached_many(
+        version_ids: &[i64],
+        redis: &mut RedisConnection,
+    ) -> Result<(Vec<Cached<QueryVersion>>, Vec<i64>), DatabaseError> {
+        let mut remaining_ids = version_ids.to_vec();
+
+        if version_ids.is_empty() {
+            return Ok((Vec::new(), remaining_ids));
         }
 
-        Ok(found_versions)
+        let found_versions = redis
+            .multi_get_cached::<QueryVersion>(VERSIONS_NAMESPACE, version_ids.iter())
+            .await?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        remaining_ids.retain(|x| !found_versions.iter().any(|v| &v.value.inner.id.0 == x));
+
+        Ok((found_versions, remaining_ids))
     }
 
     pub async fn get_file_from_hash<'a, 'b, E>(
@@ -944,6 +1123,101 @@ impl Version {
         Ok(found_files)
     }
 
+    /// Gets the versions of a project with any of the values of each filter. Versions without
+    /// a field aren't filtered by it
+    pub async fn get_ids_matching_fields<'a, E>(
+        project_id: ProjectId,
+        filters: &[VersionFieldFilter],
+        exec: E,
+    ) -> Result<Vec<VersionId>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres> + Copy,
+    {
+        let mut matching: Option<Vec<VersionId>> = None;
+
+        for filter in filters {
+            let patterns = filter
+                .values
+                .iter()
+                .flat_map(|x| VersionFieldFilter::patterns(x))
+                .collect_vec();
+            let numbers = filter
+                .values
+                .iter()
+                .filter_map(|x| match &**x {
+                    "true" => Some(1),
+                    "false" => Some(0),
+                    x => x.parse::<i32>().ok(),
+                })
+                .collect_vec();
+
+            let ids = sqlx::query!(
+                "
+                SELECT v.id FROM versions v
+                WHERE v.mod_id = $1 AND (
+                    NOT EXISTS (
+                        SELECT 1 FROM version_fields vf
+                        WHERE vf.version_id = v.id AND vf.field_id = $2
+                    )
+                    OR EXISTS (
+                        SELECT 1 FROM version_fields vf
+                        LEFT JOIN loader_field_enum_values lfev ON lfev.id = vf.enum_value
+                        WHERE vf.version_id = v.id AND vf.field_id = $2 AND (
+                            lfev.value LIKE ANY($3) OR vf.string_value LIKE ANY($3)
+                            OR vf.int_value = ANY($4)
+                        )
+                    )
+                )
+                ",
+                project_id as ProjectId,
+                filter.field_id as LoaderFieldId,
+                &patterns[..],
+                &numbers[..],
+            )
+            .fetch_all(exec)
+            .await?
+            .into_iter()
+            .map(|x| VersionId(x.id))
+            .collect_vec();
+
+            matching = Some(match matching {
+                Some(matching) => matching.into_iter().filter(|x| ids.contains(x)).collect(),
+                None => ids,
+            });
+        }
+
+        Ok(matching.unwrap_or_default())
+    }
+
+    /// Filters versions to the ones ordered after a cursor, which are published before it or at
+    /// the same time with a lower ID
+    pub async fn get_ids_before<'a, E>(
+        version_ids: &[VersionId],
+        published: DateTime<Utc>,
+        id: i64,
+        exec: E,
+    ) -> Result<Vec<VersionId>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let ids = sqlx::query!(
+            "
+            SELECT id FROM versions
+            WHERE id = ANY($1) AND (date_published, id) < ($2, $3)
+            ",
+            &version_ids.iter().map(|x| x.0).collect::<Vec<_>>(),
+            published,
+            id
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|x| VersionId(x.id))
+        .collect();
+
+        Ok(ids)
+    }
+
     pub async fn clear_cache(
         version: &QueryVersion,
         redis: &RedisPool,
@@ -965,6 +1239,31 @@ impl Version {
     }
 }
 
+/// A filter on the values of a loader field
+pub struct VersionFieldFilter {
+    pub field_id: LoaderFieldId,
+    /// Versions with any of these values match. A value ending in `.x` matches itself and
+    /// anything under it, so `1.21.x` matches `1.21` and `1.21.4`
+    pub values: Vec<String>,
+}
+
+impl VersionFieldFilter {
+    /// The `LIKE` patterns matching a value
+    fn patterns(value: &str) -> Vec<String> {
+        let escape = |x: &str| {
+            x.replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        };
+
+        if let Some(prefix) = value.strip_suffix(".x") {
+            vec![escape(prefix), format!("{}.%", escape(prefix))]
+        } else {
+            vec![escape(value)]
+        }
+    }
+}
+
 #[derive(Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct QueryVersion {
     pub inner: Version,
@@ -1085,6 +1384,7 @@ mod tests {
             featured: Default::default(),
             status: VersionStatus::Listed,
             requested_status: Default::default(),
+            revision: 1,
         }
     }
 }
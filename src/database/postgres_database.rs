@@ -7,7 +7,32 @@ use std::time::Duration;
 pub async fn connect() -> Result<PgPool, sqlx::Error> {
     info!("Initializing database connection");
     let database_url = dotenvy::var("DATABASE_URL").expect("`DATABASE_URL` not in .env");
-    let pool = PgPoolOptions::new()
+    let pool = pool_options().connect(&database_url).await?;
+
+    Ok(pool)
+}
+
+/// A pool for heavy read-only queries, such as search indexing and analytics. It may point at
+/// a read replica lagging slightly behind the primary, so nothing read from it should be
+/// written back.
+#[derive(Clone)]
+pub struct ReadOnlyPool(pub PgPool);
+
+/// Connects to the read replica at `READ_REPLICA_DATABASE_URL`, or shares the primary's pool if
+/// none is configured. The replica is connected to once it is first used.
+pub fn connect_read_replica(primary: &PgPool) -> Result<ReadOnlyPool, sqlx::Error> {
+    let Ok(database_url) = dotenvy::var("READ_REPLICA_DATABASE_URL") else {
+        return Ok(ReadOnlyPool(primary.clone()));
+    };
+
+    info!("Initializing read replica connection");
+    let pool = pool_options().connect_lazy(&database_url)?;
+
+    Ok(ReadOnlyPool(pool))
+}
+
+fn pool_options() -> PgPoolOptions {
+    PgPoolOptions::new()
         .min_connections(
             dotenvy::var("DATABASE_MIN_CONNECTIONS")
                 .ok()
@@ -21,11 +46,8 @@ pub async fn connect() -> Result<PgPool, sqlx::Error> {
                 .unwrap_or(16),
         )
         .max_lifetime(Some(Duration::from_secs(60 * 60)))
-        .connect(&database_url)
-        .await?;
-
-    Ok(pool)
 }
+
 pub async fn check_for_migrations() -> Result<(), sqlx::Error> {
     let uri = dotenvy::var("DATABASE_URL").expect("`DATABASE_URL` not in .env");
     let uri = uri.as_str();
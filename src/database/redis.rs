@@ -1,20 +1,54 @@
 use super::models::DatabaseError;
-use deadpool_redis::{Config, Runtime};
+use crate::util::env::parse_strings_from_var;
+use crate::util::metrics::CACHE_REQUESTS;
+use chrono::Utc;
+use deadpool_redis::{Config, Manager, Runtime};
 use itertools::Itertools;
-use redis::{cmd, Cmd, FromRedisValue};
+use log::warn;
+use redis::{cmd, Arg, Cmd, ErrorKind, FromRedisValue, Pipeline, RedisError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info_span, Instrument};
 
 const DEFAULT_EXPIRY: i64 = 1800; // 30 minutes
 
+/// How long a recomputation lock is held at most, in case its holder never releases it
+const LOCK_EXPIRY_MS: i64 = 10_000;
+/// How long a request waits for another one to recompute an entry before doing it itself
+const LOCK_WAIT: Duration = Duration::from_secs(3);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(25);
+/// Higher values refresh entries earlier before they expire
+const EARLY_EXPIRY_BETA: f64 = 1.0;
+
 #[derive(Clone)]
 pub struct RedisPool {
-    pub pool: deadpool_redis::Pool,
+    backend: RedisBackend,
     meta_namespace: String,
 }
 
+#[derive(Clone)]
+enum RedisBackend {
+    Single(deadpool_redis::Pool),
+    Cluster(deadpool_redis::cluster::Pool),
+    Sentinel(Arc<SentinelPool>),
+}
+
 pub struct RedisConnection {
-    pub connection: deadpool_redis::Connection,
+    connection: Connection,
     meta_namespace: String,
+    /// Set for connections to a Sentinel-managed master, which is resolved again after errors
+    /// caused by a failover
+    sentinel: Option<Arc<SentinelPool>>,
+}
+
+enum Connection {
+    Single(deadpool_redis::Connection),
+    Cluster(deadpool_redis::cluster::Connection),
 }
 
 impl RedisPool {
@@ -22,31 +56,171 @@ impl RedisPool {
     // testing pool uses a hashmap to mimic redis behaviour for very small data sizes (ie: tests)
     // PANICS: production pool will panic if redis url is not set
     pub fn new(meta_namespace: Option<String>) -> Self {
-        let redis_pool = Config::from_url(dotenvy::var("REDIS_URL").expect("Redis URL not set"))
-            .builder()
-            .expect("Error building Redis pool")
-            .max_size(
-                dotenvy::var("DATABASE_MAX_CONNECTIONS")
-                    .ok()
-                    .and_then(|x| x.parse().ok())
-                    .unwrap_or(10000),
-            )
-            .runtime(Runtime::Tokio1)
-            .build()
-            .expect("Redis connection failed");
+        let max_size = dotenvy::var("DATABASE_MAX_CONNECTIONS")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(10000);
+
+        let backend = match dotenvy::var("REDIS_MODE").as_deref() {
+            Ok("cluster") => {
+                let urls = parse_strings_from_var("REDIS_CLUSTER_URLS")
+                    .expect("Redis cluster URLs not set");
+
+                RedisBackend::Cluster(
+                    deadpool_redis::cluster::Config::from_urls(urls)
+                        .builder()
+                        .expect("Error building Redis cluster pool")
+                        .max_size(max_size)
+                        .runtime(Runtime::Tokio1)
+                        .build()
+                        .expect("Redis cluster connection failed"),
+                )
+            }
+            Ok("sentinel") => RedisBackend::Sentinel(Arc::new(SentinelPool {
+                sentinels: parse_strings_from_var("REDIS_SENTINEL_URLS")
+                    .expect("Redis Sentinel URLs not set"),
+                master_name: dotenvy::var("REDIS_SENTINEL_MASTER")
+                    .expect("Redis Sentinel master not set"),
+                max_size,
+                master: RwLock::new(None),
+                stale: AtomicBool::new(false),
+            })),
+            _ => RedisBackend::Single(
+                Config::from_url(dotenvy::var("REDIS_URL").expect("Redis URL not set"))
+                    .builder()
+                    .expect("Error building Redis pool")
+                    .max_size(max_size)
+                    .runtime(Runtime::Tokio1)
+                    .build()
+                    .expect("Redis connection failed"),
+            ),
+        };
 
         RedisPool {
-            pool: redis_pool,
+            backend,
             meta_namespace: meta_namespace.unwrap_or("".to_string()),
         }
     }
 
     pub async fn connect(&self) -> Result<RedisConnection, DatabaseError> {
+        let (connection, sentinel) = match &self.backend {
+            RedisBackend::Single(pool) => (Connection::Single(pool.get().await?), None),
+            RedisBackend::Cluster(pool) => (Connection::Cluster(pool.get().await?), None),
+            RedisBackend::Sentinel(sentinel) => (
+                Connection::Single(sentinel.get().await?),
+                Some(sentinel.clone()),
+            ),
+        };
+
         Ok(RedisConnection {
-            connection: self.pool.get().await?,
+            connection,
             meta_namespace: self.meta_namespace.clone(),
+            sentinel,
         })
     }
+
+    /// Closes the pool. Connections in use are closed once they are returned
+    pub async fn close(&self) {
+        match &self.backend {
+            RedisBackend::Single(pool) => pool.close(),
+            RedisBackend::Cluster(pool) => pool.close(),
+            RedisBackend::Sentinel(sentinel) => {
+                if let Some(pool) = &*sentinel.master.read().await {
+                    pool.close();
+                }
+            }
+        }
+    }
+}
+
+/// A pool of connections to the master of a Sentinel-managed deployment. The master is looked
+/// up from the sentinels on first use, and again once a connection to it fails, so a failover
+/// only fails the requests made while it happens.
+struct SentinelPool {
+    sentinels: Vec<String>,
+    master_name: String,
+    max_size: usize,
+    master: RwLock<Option<deadpool_redis::Pool>>,
+    stale: AtomicBool,
+}
+
+impl SentinelPool {
+    async fn get(&self) -> Result<deadpool_redis::Connection, DatabaseError> {
+        let pool = self.master_pool().await?;
+
+        pool.get().await.map_err(|err| {
+            self.stale.store(true, Ordering::Release);
+            err.into()
+        })
+    }
+
+    async fn master_pool(&self) -> Result<deadpool_redis::Pool, DatabaseError> {
+        if !self.stale.load(Ordering::Acquire) {
+            if let Some(pool) = &*self.master.read().await {
+                return Ok(pool.clone());
+            }
+        }
+
+        let mut master = self.master.write().await;
+
+        // Another connection may have looked the master up while this one waited
+        if let Some(pool) = &*master {
+            if !self.stale.load(Ordering::Acquire) {
+                return Ok(pool.clone());
+            }
+        }
+
+        let pool = self.resolve_master().await?;
+        *master = Some(pool.clone());
+        self.stale.store(false, Ordering::Release);
+
+        Ok(pool)
+    }
+
+    async fn resolve_master(&self) -> Result<deadpool_redis::Pool, RedisError> {
+        for sentinel in &self.sentinels {
+            let address = async {
+                let mut connection = redis::Client::open(sentinel.as_str())?
+                    .get_async_connection()
+                    .await?;
+
+                cmd("SENTINEL")
+                    .arg("get-master-addr-by-name")
+                    .arg(&self.master_name)
+                    .query_async::<_, Option<(String, u16)>>(&mut connection)
+                    .await
+            }
+            .await;
+
+            match address {
+                Ok(Some((host, port))) => {
+                    let manager = Manager::new(format!("redis://{}:{}", host, port))?;
+
+                    return deadpool_redis::Pool::builder(manager)
+                        .max_size(self.max_size)
+                        .runtime(Runtime::Tokio1)
+                        .build()
+                        .map_err(|err| {
+                            RedisError::from((
+                                ErrorKind::InvalidClientConfig,
+                                "Error building Redis pool",
+                                err.to_string(),
+                            ))
+                        });
+                }
+                Ok(None) => warn!(
+                    "Redis Sentinel {} does not know master {}",
+                    sentinel, self.master_name
+                ),
+                Err(err) => warn!("Redis Sentinel {} is unreachable: {}", sentinel, err),
+            }
+        }
+
+        Err(RedisError::from((
+            ErrorKind::MasterDown,
+            "No Redis Sentinel could resolve the master",
+        )))
+    }
 }
 
 impl RedisConnection {
@@ -61,17 +235,22 @@ impl RedisConnection {
         redis_args(
             &mut cmd,
             vec![
-                format!("{}_{}:{}", self.meta_namespace, namespace, id),
+                self.key(namespace, id),
                 data.to_string(),
                 "EX".to_string(),
                 expiry.unwrap_or(DEFAULT_EXPIRY).to_string(),
             ]
             .as_slice(),
         );
-        redis_execute(&mut cmd, &mut self.connection).await?;
+        self.execute::<()>(&cmd).await?;
         Ok(())
     }
 
+    /// Checks that Redis answers commands
+    pub async fn ping(&mut self) -> Result<(), DatabaseError> {
+        self.execute::<()>(&cmd("PING")).await
+    }
+
     pub async fn set_serialized_to_json<Id, D>(
         &mut self,
         namespace: &str,
@@ -98,12 +277,10 @@ impl RedisConnection {
         id: &str,
     ) -> Result<Option<String>, DatabaseError> {
         let mut cmd = cmd("GET");
-        redis_args(
-            &mut cmd,
-            vec![format!("{}_{}:{}", self.meta_namespace, namespace, id)].as_slice(),
-        );
-        let res = redis_execute(&mut cmd, &mut self.connection).await?;
-        Ok(res)
+        redis_args(&mut cmd, vec![self.key(namespace, id)].as_slice());
+        let value: Option<String> = self.execute(&cmd).await?;
+        record_cache_reads(namespace, std::slice::from_ref(&value));
+        Ok(value)
     }
 
     pub async fn get_deserialized_from_json<R>(
@@ -128,16 +305,25 @@ impl RedisConnection {
     where
         R: FromRedisValue,
     {
-        let mut cmd = cmd("MGET");
+        let keys = ids
+            .into_iter()
+            .map(|x| self.key(namespace, x))
+            .collect_vec();
 
-        let ids = ids.into_iter().map(|x| x.to_string()).collect_vec();
-        redis_args(
-            &mut cmd,
-            &ids.into_iter()
-                .map(|x| format!("{}_{}:{}", self.meta_namespace, namespace, x))
-                .collect_vec(),
-        );
-        Ok(redis_execute(&mut cmd, &mut self.connection).await?)
+        let mut values = keys.iter().map(|_| None).collect_vec();
+        for group in self.slot_groups(&keys) {
+            let mut cmd = cmd("MGET");
+            for index in &group {
+                cmd.arg(&keys[*index]);
+            }
+            let group_values: Vec<Option<R>> = self.execute(&cmd).await?;
+            for (index, value) in group.into_iter().zip(group_values) {
+                values[index] = value;
+            }
+        }
+
+        record_cache_reads(namespace, &values);
+        Ok(values)
     }
 
     pub async fn delete<T1>(&mut self, namespace: &str, id: T1) -> Result<(), DatabaseError>
@@ -145,11 +331,8 @@ impl RedisConnection {
         T1: Display,
     {
         let mut cmd = cmd("DEL");
-        redis_args(
-            &mut cmd,
-            vec![format!("{}_{}:{}", self.meta_namespace, namespace, id)].as_slice(),
-        );
-        redis_execute(&mut cmd, &mut self.connection).await?;
+        redis_args(&mut cmd, vec![self.key(namespace, id)].as_slice());
+        self.execute::<()>(&cmd).await?;
         Ok(())
     }
 
@@ -157,24 +340,430 @@ impl RedisConnection {
         &mut self,
         iter: impl IntoIterator<Item = (&str, Option<String>)>,
     ) -> Result<(), DatabaseError> {
-        let mut cmd = cmd("DEL");
-        let mut any = false;
-        for (namespace, id) in iter {
-            if let Some(id) = id {
-                redis_args(
-                    &mut cmd,
-                    [format!("{}_{}:{}", self.meta_namespace, namespace, id)].as_slice(),
-                );
-                any = true;
+        let keys = iter
+            .into_iter()
+            .filter_map(|(namespace, id)| id.map(|id| self.key(namespace, id)))
+            .collect_vec();
+
+        for group in self.slot_groups(&keys) {
+            let mut cmd = cmd("DEL");
+            for index in group {
+                cmd.arg(&keys[index]);
             }
+            self.execute::<()>(&cmd).await?;
         }
 
-        if any {
-            redis_execute(&mut cmd, &mut self.connection).await?;
+        Ok(())
+    }
+
+    /// Sets many keys in a namespace at once, all with the same expiry
+    pub async fn set_many(
+        &mut self,
+        namespace: &str,
+        entries: impl IntoIterator<Item = (impl Display, String)>,
+        expiry: Option<i64>,
+    ) -> Result<(), DatabaseError> {
+        let (keys, values): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .map(|(id, data)| (self.key(namespace, id), data))
+            .unzip();
+
+        for group in self.slot_groups(&keys) {
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            for index in group {
+                pipe.cmd("SET")
+                    .arg(&keys[index])
+                    .arg(&values[index])
+                    .arg("EX")
+                    .arg(expiry.unwrap_or(DEFAULT_EXPIRY));
+            }
+            self.execute_pipeline::<()>(&pipe).await?;
         }
 
         Ok(())
     }
+
+    /// Increments many counters in a namespace at once. Missing counters are created with the
+    /// given expiry, which later increments do not extend. Returns the new counts
+    pub async fn incr_many(
+        &mut self,
+        namespace: &str,
+        ids: impl IntoIterator<Item = impl Display>,
+        expiry: i64,
+    ) -> Result<Vec<i64>, DatabaseError> {
+        let keys = ids
+            .into_iter()
+            .map(|x| self.key(namespace, x))
+            .collect_vec();
+
+        let mut counts = vec![0; keys.len()];
+        for group in self.slot_groups(&keys) {
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            for index in &group {
+                pipe.cmd("SET")
+                    .arg(&keys[*index])
+                    .arg(0)
+                    .arg("EX")
+                    .arg(expiry)
+                    .arg("NX")
+                    .ignore()
+                    .cmd("INCR")
+                    .arg(&keys[*index]);
+            }
+
+            let group_counts: Vec<i64> = self.execute_pipeline(&pipe).await?;
+            for (index, count) in group.into_iter().zip(group_counts) {
+                counts[index] = count;
+            }
+        }
+
+        Ok(counts)
+    }
+
+    /// Adds `amount` to the counter of the fixed window of `window` seconds containing `now`.
+    /// Returns the counts of this window and the one before it
+    pub async fn incr_window(
+        &mut self,
+        namespace: &str,
+        id: &str,
+        amount: i64,
+        window: i64,
+        now: i64,
+    ) -> Result<(i64, i64), DatabaseError> {
+        let index = now / window;
+        // The id is the hash tag of the windows' keys, so they are in the same cluster slot
+        // and can be read and written in one pipeline
+        let current = self.key(namespace, format!("{{{}}}:{}", id, index));
+        let previous = self.key(namespace, format!("{{{}}}:{}", id, index - 1));
+
+        let mut pipe = redis::pipe();
+        pipe.cmd("INCRBY")
+            .arg(&current)
+            .arg(amount)
+            .cmd("EXPIRE")
+            .arg(&current)
+            .arg(window * 2)
+            .ignore()
+            .cmd("GET")
+            .arg(&previous);
+
+        let (current, previous): (i64, Option<i64>) = self.execute_pipeline(&pipe).await?;
+
+        Ok((current, previous.unwrap_or(0)))
+    }
+
+    /// Gets entries stored with `set_cached`
+    pub async fn multi_get_cached<R>(
+        &mut self,
+        namespace: &str,
+        ids: impl IntoIterator<Item = impl Display>,
+    ) -> Result<Vec<Option<Cached<R>>>, DatabaseError>
+    where
+        R: DeserializeOwned,
+    {
+        Ok(self
+            .multi_get::<String>(namespace, ids)
+            .await?
+            .into_iter()
+            .map(|x| x.and_then(|x| serde_json::from_str(&x).ok()))
+            .collect())
+    }
+
+    /// Caches an entry along with how long it took to compute, which is used to decide when
+    /// to refresh it early
+    pub async fn set_cached<Id, D>(
+        &mut self,
+        namespace: &str,
+        id: Id,
+        data: D,
+        compute_time: Duration,
+    ) -> Result<(), DatabaseError>
+    where
+        Id: Display,
+        D: Serialize,
+    {
+        self.set_serialized_to_json(
+            namespace,
+            id,
+            Cached {
+                value: data,
+                delta: compute_time.as_millis() as i64,
+                expiry: Utc::now().timestamp_millis() + DEFAULT_EXPIRY * 1000,
+            },
+            Some(DEFAULT_EXPIRY),
+        )
+        .await
+    }
+
+    /// Caches many entries at once, like `set_cached`
+    pub async fn set_cached_many<Id, D>(
+        &mut self,
+        namespace: &str,
+        entries: impl IntoIterator<Item = (Id, D)>,
+        compute_time: Duration,
+    ) -> Result<(), DatabaseError>
+    where
+        Id: Display,
+        D: Serialize,
+    {
+        let expiry = Utc::now().timestamp_millis() + DEFAULT_EXPIRY * 1000;
+        let entries = entries
+            .into_iter()
+            .map(|(id, data)| {
+                serde_json::to_string(&Cached {
+                    value: data,
+                    delta: compute_time.as_millis() as i64,
+                    expiry,
+                })
+                .map(|x| (id, x))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        self.set_many(namespace, entries, Some(DEFAULT_EXPIRY))
+            .await
+    }
+
+    /// Takes the locks guarding the recomputation of entries which are free. Returns the ids
+    /// whose locks were taken
+    pub async fn try_lock_many(
+        &mut self,
+        namespace: &str,
+        ids: &[String],
+    ) -> Result<Vec<String>, DatabaseError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let lock_namespace = format!("{}_lock", namespace);
+        let keys = ids
+            .iter()
+            .map(|id| self.key(&lock_namespace, id))
+            .collect_vec();
+
+        let mut locked = Vec::new();
+        for group in self.slot_groups(&keys) {
+            let mut pipe = redis::pipe();
+            for index in &group {
+                pipe.cmd("SET")
+                    .arg(&keys[*index])
+                    .arg(1)
+                    .arg("NX")
+                    .arg("PX")
+                    .arg(LOCK_EXPIRY_MS);
+            }
+
+            let taken: Vec<Option<String>> = self.execute_pipeline(&pipe).await?;
+            locked.extend(
+                group
+                    .into_iter()
+                    .zip(taken)
+                    .filter(|(_, taken)| taken.is_some())
+                    .map(|(index, _)| index),
+            );
+        }
+
+        // Keeps the order of `ids`
+        locked.sort_unstable();
+        Ok(locked.into_iter().map(|index| ids[index].clone()).collect())
+    }
+
+    /// Takes the locks guarding the recomputation of missing entries, so only one request
+    /// queries the database for each of them. Entries locked by another request are waited on,
+    /// so the caller can read them from the cache once computed. Returns the ids whose locks
+    /// were taken, which must be released with `unlock_many` once their entries are cached
+    pub async fn lock_or_wait(
+        &mut self,
+        namespace: &str,
+        ids: &[String],
+    ) -> Result<Vec<String>, DatabaseError> {
+        let locked = self.try_lock_many(namespace, ids).await?;
+
+        let lock_namespace = format!("{}_lock", namespace);
+        let mut waiting = ids
+            .iter()
+            .filter(|x| !locked.contains(x))
+            .cloned()
+            .collect_vec();
+
+        // Gives up waiting after a while, in which case the caller computes them itself
+        let started = Instant::now();
+        while !waiting.is_empty() && started.elapsed() < LOCK_WAIT {
+            actix_rt::time::sleep(LOCK_POLL_INTERVAL).await;
+
+            let held = self
+                .multi_get::<String>(&lock_namespace, waiting.iter())
+                .await?;
+            waiting = waiting
+                .into_iter()
+                .zip(held)
+                .filter(|(_, held)| held.is_some())
+                .map(|(id, _)| id)
+                .collect();
+        }
+
+        Ok(locked)
+    }
+
+    pub async fn unlock_many(
+        &mut self,
+        namespace: &str,
+        ids: &[String],
+    ) -> Result<(), DatabaseError> {
+        let lock_namespace = format!("{}_lock", namespace);
+        self.delete_many(ids.iter().map(|id| (&*lock_namespace, Some(id.clone()))))
+            .await
+    }
+
+    fn is_cluster(&self) -> bool {
+        matches!(self.connection, Connection::Cluster(_))
+    }
+
+    fn key(&self, namespace: &str, id: impl Display) -> String {
+        format!("{}_{}:{}", self.meta_namespace, namespace, id)
+    }
+
+    /// Splits keys into groups of keys in the same slot, as a cluster only runs commands with
+    /// many keys, and transactions, on keys of one slot. Outside a cluster all keys are in one
+    /// group. Groups hold the positions of their keys in `keys`
+    fn slot_groups(&self, keys: &[String]) -> Vec<Vec<usize>> {
+        if keys.is_empty() {
+            Vec::new()
+        } else if self.is_cluster() {
+            keys.iter()
+                .enumerate()
+                .map(|(index, key)| (key_slot(key), index))
+                .into_group_map()
+                .into_values()
+                .collect()
+        } else {
+            vec![(0..keys.len()).collect()]
+        }
+    }
+
+    async fn execute<T>(&mut self, cmd: &Cmd) -> Result<T, DatabaseError>
+    where
+        T: FromRedisValue,
+    {
+        let span = info_span!(
+            "redis",
+            db.system = "redis",
+            db.operation = %command_name(cmd)
+        );
+        let result = match &mut self.connection {
+            Connection::Single(connection) => cmd.query_async(connection).instrument(span).await,
+            Connection::Cluster(connection) => cmd.query_async(connection).instrument(span).await,
+        };
+
+        self.check_error(result)
+    }
+
+    async fn execute_pipeline<T>(&mut self, pipe: &Pipeline) -> Result<T, DatabaseError>
+    where
+        T: FromRedisValue,
+    {
+        let span = info_span!(
+            "redis",
+            db.system = "redis",
+            db.operation = "PIPELINE",
+            db.redis.commands = pipe.cmd_iter().count()
+        );
+        let result = match &mut self.connection {
+            Connection::Single(connection) => pipe.query_async(connection).instrument(span).await,
+            Connection::Cluster(connection) => pipe.query_async(connection).instrument(span).await,
+        };
+
+        self.check_error(result)
+    }
+
+    /// Marks a Sentinel-managed master to be looked up again if an error shows it may have
+    /// failed over
+    fn check_error<T>(&self, result: Result<T, RedisError>) -> Result<T, DatabaseError> {
+        if let (Err(err), Some(sentinel)) = (&result, &self.sentinel) {
+            if err.is_io_error() || err.is_connection_dropped() || err.kind() == ErrorKind::ReadOnly
+            {
+                sentinel.stale.store(true, Ordering::Release);
+            }
+        }
+
+        Ok(result?)
+    }
+}
+
+/// The cluster slot of a key. Only the key's hash tag, the part between its first `{` and the
+/// next `}`, is hashed if it has one
+fn key_slot(key: &str) -> u16 {
+    let key = key.as_bytes();
+    let hashed = key
+        .iter()
+        .position(|x| *x == b'{')
+        .and_then(|open| {
+            let tag = &key[open + 1..];
+            tag.iter()
+                .position(|x| *x == b'}')
+                .filter(|len| *len > 0)
+                .map(|len| &tag[..len])
+        })
+        .unwrap_or(key);
+
+    // CRC16-XMODEM, as used by Redis Cluster
+    let crc = hashed.iter().fold(0u16, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    });
+
+    crc % 16384
+}
+
+fn record_cache_reads<T>(namespace: &str, values: &[Option<T>]) {
+    let hits = values.iter().filter(|x| x.is_some()).count() as u64;
+    CACHE_REQUESTS
+        .with_label_values(&[namespace, "hit"])
+        .inc_by(hits);
+    CACHE_REQUESTS
+        .with_label_values(&[namespace, "miss"])
+        .inc_by(values.len() as u64 - hits);
+}
+
+/// The name of a command, such as `GET`, for naming its spans
+fn command_name(cmd: &Cmd) -> String {
+    match cmd.args_iter().next() {
+        Some(Arg::Simple(name)) => String::from_utf8_lossy(name).to_uppercase(),
+        _ => String::new(),
+    }
+}
+
+/// A cached entry, stored with what is needed to expire it early
+#[derive(Serialize, Deserialize)]
+pub struct Cached<T> {
+    pub value: T,
+    /// How long computing the value took, in milliseconds
+    delta: i64,
+    /// When the entry expires, in milliseconds since the epoch
+    expiry: i64,
+}
+
+impl<T> Cached<T> {
+    /// Whether the caller should recompute the entry ahead of its expiry. The chance rises as
+    /// the expiry nears, and sooner for entries which are slow to compute, so a hot entry is
+    /// usually refreshed by a single request rather than by every request once it expires
+    /// (probabilistic early expiration)
+    pub fn should_refresh(&self) -> bool {
+        let now = Utc::now().timestamp_millis() as f64;
+
+        now - self.delta as f64 * EARLY_EXPIRY_BETA * rand::random::<f64>().ln()
+            >= self.expiry as f64
+    }
 }
 
 pub fn redis_args(cmd: &mut Cmd, args: &[String]) {
@@ -183,13 +772,19 @@ pub fn redis_args(cmd: &mut Cmd, args: &[String]) {
     }
 }
 
-pub async fn redis_execute<T>(
-    cmd: &mut Cmd,
-    redis: &mut deadpool_redis::Connection,
-) -> Result<T, deadpool_redis::PoolError>
-where
-    T: redis::FromRedisValue,
-{
-    let res = cmd.query_async::<_, T>(redis).await?;
-    Ok(res)
+#[cfg(test)]
+mod tests {
+    use super::key_slot;
+
+    #[test]
+    fn key_slots_match_redis() {
+        assert_eq!(key_slot("foo"), 12182);
+        assert_eq!(key_slot("123456789"), 12739);
+        assert_eq!(
+            key_slot("{user1000}.following"),
+            key_slot("{user1000}.followers")
+        );
+        // Empty hash tags are not hash tags
+        assert_ne!(key_slot("{}foo"), key_slot("{}bar"));
+    }
 }
@@ -7,6 +7,7 @@ use sha2::Digest;
 
 mod authorization;
 mod delete;
+mod download;
 mod upload;
 
 pub struct BackblazeHost {
@@ -81,6 +82,10 @@ impl FileHost for BackblazeHost {
             file_name: delete_data.file_name,
         })
     }
+
+    async fn download_file(&self, file_name: &str) -> Result<Bytes, FileHostingError> {
+        download::download_file(&self.authorization_data, file_name).await
+    }
 }
 
 pub async fn process_response<T>(response: Response) -> Result<T, FileHostingError>
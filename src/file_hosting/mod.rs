@@ -56,4 +56,12 @@ pub trait FileHost {
         file_id: &str,
         file_name: &str,
     ) -> Result<DeleteFileData, FileHostingError>;
+
+    async fn download_file(&self, file_name: &str) -> Result<Bytes, FileHostingError>;
+
+    /// Checks that the host can be reached. Hosts which cannot be checked cheaply are assumed
+    /// to be reachable
+    async fn health_check(&self) -> Result<(), FileHostingError> {
+        Ok(())
+    }
 }
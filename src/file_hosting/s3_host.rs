@@ -90,4 +90,32 @@ impl FileHost for S3Host {
             file_name: file_name.to_string(),
         })
     }
+
+    async fn download_file(&self, file_name: &str) -> Result<Bytes, FileHostingError> {
+        let response = self
+            .bucket
+            .get_object(format!("/{file_name}"))
+            .await
+            .map_err(|_| {
+                FileHostingError::S3Error("Error while downloading file from S3".to_string())
+            })?;
+
+        if response.status_code() != 200 {
+            return Err(FileHostingError::S3Error(format!(
+                "Error while downloading file from S3: status {}",
+                response.status_code()
+            )));
+        }
+
+        Ok(Bytes::from(response.bytes().to_vec()))
+    }
+
+    async fn health_check(&self) -> Result<(), FileHostingError> {
+        self.bucket
+            .list_page(String::new(), None, None, None, Some(1))
+            .await
+            .map_err(|_| FileHostingError::S3Error("Error while listing S3 bucket".to_string()))?;
+
+        Ok(())
+    }
 }
@@ -1,10 +1,12 @@
 use std::sync::Arc;
 
 use actix_web::web;
+use chrono::Utc;
 use database::redis::RedisPool;
 use log::{info, warn};
 use queue::{
-    analytics::AnalyticsQueue, payouts::PayoutsQueue, session::AuthQueue, socket::ActiveSockets,
+    analytics::AnalyticsQueue, moderation::ModerationEvents, payouts::PayoutsQueue,
+    session::AuthQueue, socket::ActiveSockets,
 };
 use scheduler::Scheduler;
 use sqlx::Postgres;
@@ -15,9 +17,12 @@ use clickhouse_crate::Client;
 use util::cors::default_cors;
 
 use crate::{
-    queue::payouts::process_payout,
-    search::indexing::index_projects,
+    database::models::job_item::Job,
+    models::jobs::JobPayload,
+    queue::jobs::JobContext,
+    queue::payouts::{process_automatic_payouts, process_payout, sync_payout_statuses},
     util::env::{parse_strings_from_var, parse_var},
+    util::metrics::{result_label, PAYOUT_RUNS},
 };
 
 pub mod auth;
@@ -25,6 +30,7 @@ pub mod clickhouse;
 pub mod database;
 pub mod file_hosting;
 pub mod models;
+pub mod payouts;
 pub mod queue;
 pub mod ratelimit;
 pub mod routes;
@@ -32,6 +38,7 @@ pub mod scheduler;
 pub mod search;
 pub mod util;
 pub mod validate;
+pub mod webhooks;
 
 #[derive(Clone)]
 pub struct Pepper {
@@ -41,6 +48,7 @@ pub struct Pepper {
 #[derive(Clone)]
 pub struct LabrinthConfig {
     pub pool: sqlx::Pool<Postgres>,
+    pub read_pool: database::ReadOnlyPool,
     pub redis_pool: RedisPool,
     pub clickhouse: Client,
     pub file_host: Arc<dyn file_hosting::FileHost + Send + Sync>,
@@ -52,6 +60,7 @@ pub struct LabrinthConfig {
     pub payouts_queue: web::Data<PayoutsQueue>,
     pub analytics_queue: Arc<AnalyticsQueue>,
     pub active_sockets: web::Data<RwLock<ActiveSockets>>,
+    pub moderation_events: web::Data<ModerationEvents>,
 }
 
 pub fn app_setup(
@@ -67,6 +76,8 @@ pub fn app_setup(
         dotenvy::var("BIND_ADDR").unwrap()
     );
 
+    let read_pool = database::connect_read_replica(&pool).expect("Read replica connection failed");
+
     let mut scheduler = scheduler::Scheduler::new();
 
     // The interval in seconds at which the local database is indexed
@@ -75,19 +86,54 @@ pub fn app_setup(
         std::time::Duration::from_secs(parse_var("LOCAL_INDEX_INTERVAL").unwrap_or(3600));
 
     let pool_ref = pool.clone();
-    let search_config_ref = search_config.clone();
-    let redis_pool_ref = redis_pool.clone();
     scheduler.run(local_index_interval, move || {
         let pool_ref = pool_ref.clone();
-        let redis_pool_ref = redis_pool_ref.clone();
-        let search_config_ref = search_config_ref.clone();
         async move {
-            info!("Indexing local database");
-            let result = index_projects(pool_ref, redis_pool_ref.clone(), &search_config_ref).await;
+            info!("Queueing local database indexing");
+            let result = Job::enqueue(JobPayload::IndexProjects, Utc::now(), &pool_ref).await;
+            if let Err(e) = result {
+                warn!("Queueing local project indexing failed: {:?}", e);
+            }
+        }
+    });
+
+    // Purges data kept past its retention
+    let pool_ref = pool.clone();
+    scheduler.run(std::time::Duration::from_secs(60 * 60 * 24), move || {
+        let pool_ref = pool_ref.clone();
+        async move {
+            info!("Queueing purge of expired data");
+            let result = Job::enqueue(JobPayload::PurgeExpiredData, Utc::now(), &pool_ref).await;
+            if let Err(e) = result {
+                warn!("Queueing purge of expired data failed: {:?}", e);
+            }
+        }
+    });
+
+    // Regenerates the sitemaps crawlers are pointed to
+    let pool_ref = pool.clone();
+    scheduler.run(std::time::Duration::from_secs(60 * 60 * 24), move || {
+        let pool_ref = pool_ref.clone();
+        async move {
+            info!("Queueing sitemap generation");
+            let result = Job::enqueue(JobPayload::GenerateSitemaps, Utc::now(), &pool_ref).await;
             if let Err(e) = result {
-                warn!("Local project indexing failed: {:?}", e);
+                warn!("Queueing sitemap generation failed: {:?}", e);
+            }
+        }
+    });
+
+    // Sends staff the weekly report. The job checks daily whether last week's was sent, so the
+    // report goes out soon after the week ends however often the server restarts
+    let pool_ref = pool.clone();
+    scheduler.run(std::time::Duration::from_secs(60 * 60 * 24), move || {
+        let pool_ref = pool_ref.clone();
+        async move {
+            info!("Queueing staff report");
+            let result = Job::enqueue(JobPayload::SendStaffReport, Utc::now(), &pool_ref).await;
+            if let Err(e) = result {
+                warn!("Queueing staff report failed: {:?}", e);
             }
-            info!("Done indexing local database");
         }
     });
 
@@ -133,7 +179,7 @@ pub fn app_setup(
         }
     });
 
-    scheduler::schedule_versions(&mut scheduler, pool.clone(), redis_pool.clone());
+    scheduler::schedule_versions(&mut scheduler, pool.clone());
 
     let session_queue = web::Data::new(AuthQueue::new());
 
@@ -201,6 +247,44 @@ pub fn app_setup(
         });
     }
 
+    {
+        let client_ref = clickhouse.clone();
+        scheduler.run(std::time::Duration::from_secs(60 * 60), move || {
+            let client_ref = client_ref.clone();
+
+            async move {
+                info!("Rolling up analytics");
+                let result = crate::clickhouse::run_rollups(&client_ref).await;
+                if let Err(e) = result {
+                    warn!("Rolling up analytics failed: {:?}", e);
+                }
+                info!("Done rolling up analytics");
+            }
+        });
+    }
+
+    {
+        let pool_ref = pool.clone();
+        let client_ref = clickhouse.clone();
+        scheduler.run(std::time::Duration::from_secs(60 * 60 * 24), move || {
+            let pool_ref = pool_ref.clone();
+            let client_ref = client_ref.clone();
+
+            async move {
+                info!("Computing similar projects");
+                let result = crate::queue::similar_projects::compute_similar_projects(
+                    &pool_ref,
+                    Arc::new(client_ref),
+                )
+                .await;
+                if let Err(e) = result {
+                    warn!("Computing similar projects failed: {:?}", e);
+                }
+                info!("Done computing similar projects");
+            }
+        });
+    }
+
     {
         let pool_ref = pool.clone();
         let redis_ref = redis_pool.clone();
@@ -213,6 +297,9 @@ pub fn app_setup(
             async move {
                 info!("Started running payouts");
                 let result = process_payout(&pool_ref, &redis_ref, &client_ref).await;
+                PAYOUT_RUNS
+                    .with_label_values(&["process_payout", result_label(&result)])
+                    .inc();
                 if let Err(e) = result {
                     warn!("Payouts run failed: {:?}", e);
                 }
@@ -221,15 +308,118 @@ pub fn app_setup(
         });
     }
 
+    let payouts_queue = web::Data::new(PayoutsQueue::new());
+    {
+        let pool_ref = pool.clone();
+        scheduler.run(std::time::Duration::from_secs(60 * 60), move || {
+            let pool_ref = pool_ref.clone();
+
+            async move {
+                info!("Queueing automatic payouts");
+                let result = process_automatic_payouts(&pool_ref).await;
+                PAYOUT_RUNS
+                    .with_label_values(&["automatic_payouts", result_label(&result)])
+                    .inc();
+                if let Err(e) = result {
+                    warn!("Queueing automatic payouts failed: {:?}", e);
+                }
+            }
+        });
+    }
+
+    {
+        let pool_ref = pool.clone();
+        let redis_ref = redis_pool.clone();
+        let payouts_queue_ref = payouts_queue.clone();
+        scheduler.run(std::time::Duration::from_secs(60 * 60), move || {
+            let pool_ref = pool_ref.clone();
+            let redis_ref = redis_ref.clone();
+            let payouts_queue_ref = payouts_queue_ref.clone();
+
+            async move {
+                info!("Started syncing payout statuses");
+                let result = sync_payout_statuses(&pool_ref, &redis_ref, &payouts_queue_ref).await;
+                PAYOUT_RUNS
+                    .with_label_values(&["sync_payout_statuses", result_label(&result)])
+                    .inc();
+                if let Err(e) = result {
+                    warn!("Syncing payout statuses failed: {:?}", e);
+                }
+                info!("Done syncing payout statuses");
+            }
+        });
+    }
+
+    // Each worker runs due jobs one at a time
+    let job_context = JobContext {
+        pool: pool.clone(),
+        read_pool: read_pool.clone(),
+        redis: redis_pool.clone(),
+        search_config: search_config.clone(),
+        payouts_queue: payouts_queue.clone(),
+        file_host: file_host.clone(),
+        shutdown: scheduler.shutdown_signal(),
+    };
+    for _ in 0..parse_var::<usize>("JOB_WORKERS").unwrap_or(4) {
+        let job_context = job_context.clone();
+        scheduler.run(std::time::Duration::from_secs(5), move || {
+            let job_context = job_context.clone();
+
+            async move {
+                let result = queue::jobs::run_jobs(&job_context).await;
+                if let Err(e) = result {
+                    warn!("Running jobs failed: {:?}", e);
+                }
+            }
+        });
+    }
+
+    {
+        let pool_ref = pool.clone();
+        scheduler.run(std::time::Duration::from_secs(15), move || {
+            let pool_ref = pool_ref.clone();
+
+            async move {
+                let result = queue::notifications::process_notification_deliveries(&pool_ref).await;
+                if let Err(e) = result {
+                    warn!("Delivering notifications failed: {:?}", e);
+                }
+            }
+        });
+    }
+
+    {
+        let pool_ref = pool.clone();
+        let read_pool_ref = read_pool.clone();
+        scheduler.run(std::time::Duration::from_secs(15), move || {
+            let pool_ref = pool_ref.clone();
+            let read_pool_ref = read_pool_ref.clone();
+
+            async move {
+                let result = util::metrics::sample(&pool_ref, &read_pool_ref.0).await;
+                if let Err(e) = result {
+                    warn!("Sampling metrics failed: {:?}", e);
+                }
+            }
+        });
+    }
+
+    let moderation_events = web::Data::new(ModerationEvents::new());
+    {
+        let pool_ref = pool.clone();
+        let moderation_events_ref = moderation_events.clone();
+        scheduler.spawn(async move { moderation_events_ref.listen(pool_ref).await });
+    }
+
     let ip_salt = Pepper {
         pepper: models::ids::Base62Id(models::ids::random_base62(11)).to_string(),
     };
 
-    let payouts_queue = web::Data::new(PayoutsQueue::new());
     let active_sockets = web::Data::new(RwLock::new(ActiveSockets::default()));
 
     LabrinthConfig {
         pool,
+        read_pool,
         redis_pool,
         clickhouse: clickhouse.clone(),
         file_host,
@@ -241,6 +431,7 @@ pub fn app_setup(
         payouts_queue,
         analytics_queue,
         active_sockets,
+        moderation_events,
     }
 }
 
@@ -258,11 +449,18 @@ pub fn app_config(cfg: &mut web::ServiceConfig, labrinth_config: LabrinthConfig)
             .error_handler(|err, _req| routes::ApiError::Validation(err.to_string()).into()),
     )
     .app_data(
+        // Bodies are limited by route by `util::body_limit::BodyLimits`, this only caps JSON
+        // bodies where it isn't used
         web::JsonConfig::default()
-            .error_handler(|err, _req| routes::ApiError::Validation(err.to_string()).into()),
+            .limit(util::body_limit::MAX_JSON_LIMIT)
+            .error_handler(|err, req| {
+                util::body_limit::json_overflow_error(&err, req)
+                    .unwrap_or_else(|| routes::ApiError::Validation(err.to_string()).into())
+            }),
     )
     .app_data(web::Data::new(labrinth_config.redis_pool.clone()))
     .app_data(web::Data::new(labrinth_config.pool.clone()))
+    .app_data(web::Data::new(labrinth_config.read_pool.clone()))
     .app_data(web::Data::new(labrinth_config.file_host.clone()))
     .app_data(web::Data::new(labrinth_config.search_config.clone()))
     .app_data(labrinth_config.session_queue.clone())
@@ -272,6 +470,7 @@ pub fn app_config(cfg: &mut web::ServiceConfig, labrinth_config: LabrinthConfig)
     .app_data(web::Data::new(labrinth_config.clickhouse.clone()))
     .app_data(web::Data::new(labrinth_config.maxmind.clone()))
     .app_data(labrinth_config.active_sockets.clone())
+    .app_data(labrinth_config.moderation_events.clone())
     .configure(routes::v2::config)
     .configure(routes::v3::config)
     .configure(routes::internal::config)
@@ -297,12 +496,43 @@ pub fn check_env_vars() -> bool {
 
     failed |= check_var::<String>("SITE_URL");
     failed |= check_var::<String>("CDN_URL");
+    failed |= check_var::<String>("SHORTLINK_URL");
     failed |= check_var::<String>("LABRINTH_ADMIN_KEY");
     failed |= check_var::<String>("RATE_LIMIT_IGNORE_KEY");
+    failed |= check_var::<i64>("RATE_LIMIT_ANONYMOUS");
+    failed |= check_var::<i64>("RATE_LIMIT_AUTHENTICATED");
+    failed |= check_var::<i64>("RATE_LIMIT_APPROVED_APP");
+    if parse_strings_from_var("RATE_LIMIT_APPROVED_OAUTH_CLIENTS").is_none() {
+        warn!("Variable `RATE_LIMIT_APPROVED_OAUTH_CLIENTS` missing in dotenv or not a json array of strings");
+        failed |= true;
+    }
     failed |= check_var::<String>("DATABASE_URL");
     failed |= check_var::<String>("MEILISEARCH_ADDR");
     failed |= check_var::<String>("MEILISEARCH_KEY");
-    failed |= check_var::<String>("REDIS_URL");
+
+    match dotenvy::var("REDIS_MODE").as_deref() {
+        Ok("cluster") => {
+            if parse_strings_from_var("REDIS_CLUSTER_URLS").is_none() {
+                warn!("Variable `REDIS_CLUSTER_URLS` missing in dotenv or not a json array of strings");
+                failed |= true;
+            }
+        }
+        Ok("sentinel") => {
+            if parse_strings_from_var("REDIS_SENTINEL_URLS").is_none() {
+                warn!("Variable `REDIS_SENTINEL_URLS` missing in dotenv or not a json array of strings");
+                failed |= true;
+            }
+            failed |= check_var::<String>("REDIS_SENTINEL_MASTER");
+        }
+        Ok("single") | Err(_) => {
+            failed |= check_var::<String>("REDIS_URL");
+        }
+        Ok(mode) => {
+            warn!("Variable `REDIS_MODE` contains an invalid value: {}. Expected \"single\", \"cluster\", or \"sentinel\".", mode);
+            failed |= true;
+        }
+    }
+
     failed |= check_var::<String>("BIND_ADDR");
     failed |= check_var::<String>("SELF_ADDR");
 
@@ -336,6 +566,12 @@ pub fn check_env_vars() -> bool {
     }
 
     failed |= check_var::<usize>("LOCAL_INDEX_INTERVAL");
+    failed |= check_var::<i64>("INDEXING_CHUNK_SIZE");
+    failed |= check_var::<usize>("JOB_WORKERS");
+    failed |= check_var::<u64>("SHUTDOWN_TIMEOUT");
+    failed |= check_var::<bool>("MAINTENANCE_MODE");
+    failed |= check_var::<u32>("CIRCUIT_BREAKER_THRESHOLD");
+    failed |= check_var::<i64>("CIRCUIT_BREAKER_COOLDOWN");
     failed |= check_var::<usize>("VERSION_INDEX_INTERVAL");
 
     if parse_strings_from_var("WHITELISTED_MODPACK_DOMAINS").is_none() {
@@ -343,6 +579,11 @@ pub fn check_env_vars() -> bool {
         failed |= true;
     }
 
+    if parse_strings_from_var("REMOTE_FILE_DOMAINS").is_none() {
+        warn!("Variable `REMOTE_FILE_DOMAINS` missing in dotenv or not a json array of strings");
+        failed |= true;
+    }
+
     if parse_strings_from_var("ALLOWED_CALLBACK_URLS").is_none() {
         warn!("Variable `ALLOWED_CALLBACK_URLS` missing in dotenv or not a json array of strings");
         failed |= true;
@@ -350,6 +591,7 @@ pub fn check_env_vars() -> bool {
 
     failed |= check_var::<String>("GITHUB_CLIENT_ID");
     failed |= check_var::<String>("GITHUB_CLIENT_SECRET");
+    failed |= check_var::<String>("GITHUB_WEBHOOK_SECRET");
     failed |= check_var::<String>("GITLAB_CLIENT_ID");
     failed |= check_var::<String>("GITLAB_CLIENT_SECRET");
     failed |= check_var::<String>("DISCORD_CLIENT_ID");
@@ -369,14 +611,34 @@ pub fn check_env_vars() -> bool {
     failed |= check_var::<String>("PAYPAL_CLIENT_ID");
     failed |= check_var::<String>("PAYPAL_CLIENT_SECRET");
 
+    failed |= check_var::<String>("STRIPE_API_URL");
+    failed |= check_var::<String>("STRIPE_API_KEY");
+    failed |= check_var::<String>("STRIPE_WEBHOOK_SECRET");
+    failed |= check_var::<String>("STRIPE_CONNECT_WEBHOOK_SECRET");
+
+    failed |= check_var::<i64>("MONETIZATION_MIN_ACCOUNT_AGE_DAYS");
+    failed |= check_var::<u32>("AUTOMATIC_PAYOUTS_DAY");
+
+    failed |= check_var::<String>("TAX_API_URL");
+    failed |= check_var::<String>("TAX_API_KEY");
+    failed |= check_var::<String>("TAX_WEBHOOK_SECRET");
+
+    failed |= check_var::<String>("FX_API_URL");
+    failed |= check_var::<String>("FX_API_KEY");
+    failed |= check_var::<i64>("FX_QUOTE_LOCK_MINUTES");
+
     failed |= check_var::<String>("TURNSTILE_SECRET");
 
     failed |= check_var::<String>("SMTP_USERNAME");
     failed |= check_var::<String>("SMTP_PASSWORD");
     failed |= check_var::<String>("SMTP_HOST");
+    failed |= check_var::<String>("EMAIL_WEBHOOK_SECRET");
 
     failed |= check_var::<String>("SITE_VERIFY_EMAIL_PATH");
     failed |= check_var::<String>("SITE_RESET_PASSWORD_PATH");
+    failed |= check_var::<String>("SITE_RESTORE_ACCOUNT_PATH");
+    failed |= check_var::<String>("SITE_STRIPE_ONBOARDING_PATH");
+    failed |= check_var::<String>("SITE_TAX_FORM_PATH");
 
     failed |= check_var::<String>("BEEHIIV_PUBLICATION_ID");
     failed |= check_var::<String>("BEEHIIV_API_KEY");
@@ -394,8 +656,25 @@ pub fn check_env_vars() -> bool {
     failed |= check_var::<String>("CLICKHOUSE_DATABASE");
 
     failed |= check_var::<String>("MAXMIND_LICENSE_KEY");
+    failed |= check_var::<String>("ANALYTICS_SALT");
+    failed |= check_var::<u64>("DOWNLOAD_DEDUP_TTL");
+    failed |= check_var::<u32>("ANALYTICS_RAW_RETENTION_DAYS");
+    failed |= check_var::<u32>("ANALYTICS_HOURLY_RETENTION_DAYS");
+    failed |= check_var::<u32>("ANALYTICS_DAILY_RETENTION_DAYS");
+    failed |= check_var::<i32>("RETENTION_EXPIRED_SESSIONS_DAYS");
+    failed |= check_var::<i32>("RETENTION_EXPIRED_OAUTH_TOKENS_DAYS");
+    failed |= check_var::<i32>("RETENTION_UNATTACHED_IMAGES_DAYS");
+    failed |= check_var::<i32>("RETENTION_DEAD_JOBS_DAYS");
+    failed |= check_var::<i32>("RETENTION_COMPLETED_JOBS_DAYS");
+    failed |= check_var::<i32>("RETENTION_PROCESSED_WEBHOOKS_DAYS");
 
     failed |= check_var::<u64>("PAYOUTS_BUDGET");
 
+    failed |= check_var::<String>("VAPID_PUBLIC_KEY");
+    failed |= check_var::<String>("VAPID_PRIVATE_KEY");
+    failed |= check_var::<String>("VAPID_SUBJECT");
+
+    failed |= check_var::<f64>("OTEL_TRACES_SAMPLE_RATIO");
+
     failed
 }
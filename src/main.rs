@@ -1,16 +1,22 @@
 use actix_web::{App, HttpServer};
 use actix_web_prom::PrometheusMetricsBuilder;
-use env_logger::Env;
 use labrinth::database::redis::RedisPool;
 use labrinth::file_hosting::S3Host;
 use labrinth::ratelimit::errors::ARError;
-use labrinth::ratelimit::memory::{MemoryStore, MemoryStoreActor};
 use labrinth::ratelimit::middleware::RateLimiter;
 use labrinth::search;
+use labrinth::util::body_limit::BodyLimits;
 use labrinth::util::env::parse_var;
+use labrinth::util::maintenance::ReadOnlyMode;
+use labrinth::util::metrics;
+use labrinth::util::pat_usage::PatUsageTracker;
+use labrinth::util::reload::reload;
+use labrinth::util::telemetry;
 use labrinth::{check_env_vars, clickhouse, database, file_hosting, queue};
-use log::{error, info};
+use log::{error, info, warn};
 use std::sync::Arc;
+use std::time::Duration;
+use tracing_actix_web::TracingLogger;
 
 #[derive(Clone)]
 pub struct Pepper {
@@ -21,7 +27,7 @@ pub struct Pepper {
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
     dotenvy::dotenv().ok();
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let tracing_enabled = telemetry::init();
 
     if check_env_vars() {
         error!("Some environment variables are missing!");
@@ -38,6 +44,9 @@ async fn main() -> std::io::Result<()> {
         info!("Enabled Sentry integration");
         std::env::set_var("RUST_BACKTRACE", "1");
     }
+    if tracing_enabled {
+        info!("Enabled OpenTelemetry trace exporting");
+    }
 
     info!(
         "Starting Labrinth on {}",
@@ -86,9 +95,8 @@ async fn main() -> std::io::Result<()> {
 
     let maxmind_reader = Arc::new(queue::maxmind::MaxMindIndexer::new().await.unwrap());
 
-    let store = MemoryStore::new();
-
     let prometheus = PrometheusMetricsBuilder::new("labrinth")
+        .registry(metrics::registry())
         .endpoint("/metrics")
         .build()
         .expect("Failed to create prometheus metrics middleware");
@@ -105,13 +113,40 @@ async fn main() -> std::io::Result<()> {
         maxmind_reader.clone(),
     );
 
+    // Reloads the configuration which can be changed without a restart on SIGHUP
+    #[cfg(unix)]
+    {
+        let redis_pool = redis_pool.clone();
+        actix_rt::spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut hangups = match signal(SignalKind::hangup()) {
+                Ok(hangups) => hangups,
+                Err(e) => {
+                    warn!("Failed to listen for SIGHUP: {}", e);
+                    return;
+                }
+            };
+
+            while hangups.recv().await.is_some() {
+                match reload(&redis_pool).await {
+                    Ok(changed) => info!("Reloaded configuration, changed: {:?}", changed),
+                    Err(e) => warn!("Failed to reload configuration: {}", e),
+                }
+            }
+        });
+    }
+
+    let shutdown_timeout = parse_var("SHUTDOWN_TIMEOUT").unwrap_or(30);
+    let shutdown_config = labrinth_config.clone();
+
     // Init App
-    HttpServer::new(move || {
+    let result = HttpServer::new(move || {
         App::new()
             .wrap(prometheus.clone())
             .wrap(actix_web::middleware::Compress::default())
             .wrap(
-                RateLimiter::new(MemoryStoreActor::from(store.clone()).start())
+                RateLimiter::new(pool.clone(), redis_pool.clone())
                     .with_identifier(|req| {
                         let connection_info = req.connection_info();
                         let ip =
@@ -127,14 +162,58 @@ async fn main() -> std::io::Result<()> {
 
                         Ok(ip)
                     })
-                    .with_interval(std::time::Duration::from_secs(60))
-                    .with_max_requests(300)
                     .with_ignore_key(dotenvy::var("RATE_LIMIT_IGNORE_KEY").ok()),
             )
+            .wrap(PatUsageTracker)
+            .wrap(ReadOnlyMode::new(redis_pool.clone()))
+            .wrap(BodyLimits)
             .wrap(sentry_actix::Sentry::new())
+            .wrap_fn(telemetry::with_request_id)
+            .wrap(TracingLogger::<telemetry::RequestRootSpan>::new())
             .configure(|cfg| labrinth::app_config(cfg, labrinth_config.clone()))
     })
+    // On SIGTERM, new connections are refused and in-flight requests are given this long to
+    // finish
+    .shutdown_timeout(shutdown_timeout)
     .bind(dotenvy::var("BIND_ADDR").unwrap())?
     .run()
-    .await
+    .await;
+
+    info!("Stopping background tasks");
+    let finished = shutdown_config
+        .scheduler
+        .shutdown(Duration::from_secs(shutdown_timeout))
+        .await;
+    if !finished {
+        // Jobs still running are left to their leases rather than handed back, so they can't
+        // run twice at once
+        warn!("Background tasks did not finish in time");
+    }
+
+    info!("Flushing queued analytics and sessions");
+    let analytics_result = shutdown_config
+        .analytics_queue
+        .index(
+            shutdown_config.clickhouse.clone(),
+            &shutdown_config.redis_pool,
+            &shutdown_config.pool,
+        )
+        .await;
+    if let Err(e) = analytics_result {
+        warn!("Flushing analytics queue failed: {:?}", e);
+    }
+    let session_result = shutdown_config
+        .session_queue
+        .index(&shutdown_config.pool, &shutdown_config.redis_pool)
+        .await;
+    if let Err(e) = session_result {
+        warn!("Flushing sessions queue failed: {:?}", e);
+    }
+
+    shutdown_config.pool.close().await;
+    shutdown_config.read_pool.0.close().await;
+    shutdown_config.redis_pool.close().await;
+    telemetry::shutdown();
+
+    result
 }
@@ -1,8 +1,24 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 /// An error returned by the API
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct ApiError<'a> {
+    /// A machine readable name for the kind of error, such as `not_found` or `invalid_input`.
+    /// See `ErrorCode` for the full list
     pub error: &'a str,
+    /// A human readable description of what went wrong
     pub description: &'a str,
+    /// The individual fields of the request body that failed validation, if any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+/// A single field of a request that failed validation
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct FieldError {
+    /// The path to the field, such as `title` or `gallery[0].description`
+    pub field: String,
+    /// The rule the field broke, such as `length`, `url` or `range`
+    pub code: String,
 }
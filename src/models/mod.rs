@@ -4,8 +4,15 @@ pub mod v3;
 
 pub use v3::analytics;
 pub use v3::collections;
+pub use v3::discord;
+pub use v3::feature_flags;
+pub use v3::featured;
+pub use v3::feed;
 pub use v3::ids;
 pub use v3::images;
+pub use v3::jobs;
+pub use v3::legal;
+pub use v3::moderation;
 pub use v3::notifications;
 pub use v3::oauth_clients;
 pub use v3::organizations;
@@ -15,6 +22,8 @@ pub use v3::payouts;
 pub use v3::projects;
 pub use v3::reports;
 pub use v3::sessions;
+pub use v3::shortlinks;
 pub use v3::teams;
 pub use v3::threads;
+pub use v3::uploads;
 pub use v3::users;
@@ -83,6 +83,18 @@ impl LegacyNotification {
             NotificationBody::OrganizationInvite { .. } => Some("organization_invite".to_string()),
             NotificationBody::StatusChange { .. } => Some("status_change".to_string()),
             NotificationBody::ModeratorMessage { .. } => Some("moderator_message".to_string()),
+            NotificationBody::TeamMembershipChange { .. } => {
+                Some("team_membership_change".to_string())
+            }
+            NotificationBody::PayoutSent { .. } => Some("payout_sent".to_string()),
+            NotificationBody::PayoutFailed { .. } => Some("payout_failed".to_string()),
+            NotificationBody::PayoutAdjusted { .. } => Some("payout_adjusted".to_string()),
+            NotificationBody::GitHubRelease { .. } => Some("github_release".to_string()),
+            NotificationBody::CollectionInvite { .. } => Some("collection_invite".to_string()),
+            NotificationBody::CollectionUpdate { .. } => Some("collection_update".to_string()),
+            NotificationBody::ThreadMessage { .. } => Some("thread_message".to_string()),
+            NotificationBody::ReportClosed { .. } => Some("report_closed".to_string()),
+            NotificationBody::DependencyUpdate { .. } => Some("dependency_update".to_string()),
             NotificationBody::LegacyMarkdown {
                 notification_type, ..
             } => notification_type.clone(),
@@ -152,6 +164,25 @@ impl LegacyNotification {
                 link,
                 actions,
             },
+            // V2 has no structured body for membership changes, payouts, releases, collections,
+            // subscribed threads, closed reports or dependency updates, so send the rendered text
+            // instead
+            NotificationBody::TeamMembershipChange { .. }
+            | NotificationBody::PayoutSent { .. }
+            | NotificationBody::PayoutFailed { .. }
+            | NotificationBody::PayoutAdjusted { .. }
+            | NotificationBody::GitHubRelease { .. }
+            | NotificationBody::CollectionInvite { .. }
+            | NotificationBody::CollectionUpdate { .. }
+            | NotificationBody::ThreadMessage { .. }
+            | NotificationBody::ReportClosed { .. }
+            | NotificationBody::DependencyUpdate { .. } => LegacyNotificationBody::LegacyMarkdown {
+                notification_type: type_.clone(),
+                title: notification.name.clone(),
+                text: notification.text.clone(),
+                link: notification.link.clone(),
+                actions: vec![],
+            },
             NotificationBody::Unknown => LegacyNotificationBody::Unknown,
         };
 
@@ -41,7 +41,9 @@ pub struct LegacyResultSearchProject {
 // TODO: In other PR, when these are merged, make sure the v2 search testing functions use these
 impl LegacyResultSearchProject {
     pub fn from(result_search_project: ResultSearchProject) -> Self {
+        let ancestor_categories = &result_search_project.ancestor_categories;
         let mut categories = result_search_project.categories;
+        categories.retain(|c| !ancestor_categories.contains(c));
         if categories.contains(&"mrpack".to_string()) {
             if let Some(mrpack_loaders) = result_search_project.loader_fields.get("mrpack_loaders")
             {
@@ -1,7 +1,6 @@
 use clickhouse::Row;
 use serde::{Deserialize, Serialize};
 use std::hash::Hash;
-use std::net::Ipv6Addr;
 
 #[derive(Row, Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 pub struct Download {
@@ -17,10 +16,18 @@ pub struct Download {
     pub version_id: u64,
 
     // The below information is used exclusively for data aggregation and fraud detection
-    // (ex: download botting).
-    pub ip: Ipv6Addr,
+    // (ex: download botting). The IP and raw user agent are only used at ingest and never
+    // stored.
     pub country: String,
-    pub user_agent: String,
+    pub continent: String,
+    // Client platform (launcher/web) and operating system, derived from the user agent
+    pub platform: String,
+    pub os: String,
+    // Sanitized traffic source: referrer host and campaign tags
+    pub referrer: String,
+    pub source: String,
+    pub medium: String,
+    pub campaign: String,
     pub headers: Vec<(String, String)>,
 }
 
@@ -36,13 +43,50 @@ pub struct PageView {
     pub project_id: u64,
 
     // The below information is used exclusively for data aggregation and fraud detection
-    // (ex: page view botting).
-    pub ip: Ipv6Addr,
+    // (ex: page view botting). The IP and raw user agent are only used at ingest and never
+    // stored.
     pub country: String,
-    pub user_agent: String,
+    pub continent: String,
+    pub platform: String,
+    pub os: String,
+    // Sanitized traffic source: referrer host and campaign tags
+    pub referrer: String,
+    pub source: String,
+    pub medium: String,
+    pub campaign: String,
     pub headers: Vec<(String, String)>,
 }
 
+/// A dimension downloads and views can be broken down by in analytics
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsDimension {
+    Country,
+    Continent,
+    Platform,
+    Os,
+    Referrer,
+    Source,
+    Medium,
+    Campaign,
+}
+
+impl AnalyticsDimension {
+    /// The column storing this dimension in the views and downloads tables
+    pub fn column(&self) -> &'static str {
+        match self {
+            AnalyticsDimension::Country => "country",
+            AnalyticsDimension::Continent => "continent",
+            AnalyticsDimension::Platform => "platform",
+            AnalyticsDimension::Os => "os",
+            AnalyticsDimension::Referrer => "referrer",
+            AnalyticsDimension::Source => "source",
+            AnalyticsDimension::Medium => "medium",
+            AnalyticsDimension::Campaign => "campaign",
+        }
+    }
+}
+
 #[derive(Row, Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Playtime {
     pub recorded: i64,
@@ -60,3 +104,20 @@ pub struct Playtime {
     /// Parent modpack this playtime was recorded in
     pub parent: u64,
 }
+
+#[derive(Row, Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PlaytimeSession {
+    pub recorded: i64,
+    pub seconds: u64,
+
+    // Salted hash of the launcher's randomly generated instance ID. The salt rotates daily, so
+    // sessions can be counted per day but not linked across days or back to a user
+    pub instance: u64,
+    // Modrinth Project ID
+    pub project_id: u64,
+    // Modrinth Version ID
+    pub version_id: u64,
+
+    pub loader: String,
+    pub game_version: String,
+}
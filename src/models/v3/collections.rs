@@ -1,17 +1,65 @@
 use super::{
-    ids::{Base62Id, ProjectId},
+    ids::{Base62Id, ProjectId, VersionId},
     users::UserId,
 };
+use crate::bitflags_serde_impl;
 use crate::database;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 /// The ID of a specific collection, encoded as base62 for usage in the API
-#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
 #[serde(from = "Base62Id")]
 #[serde(into = "Base62Id")]
 pub struct CollectionId(pub u64);
 
+/// The ID of a collection snapshot, encoded as base62 for usage in the API
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[serde(from = "Base62Id")]
+#[serde(into = "Base62Id")]
+pub struct CollectionSnapshotId(pub u64);
+
+/// A collection's projects pinned to specific versions at a point in time. Unlike the
+/// collection itself, a snapshot doesn't change, so it can be used as a reproducible pack
+/// definition
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CollectionSnapshot {
+    pub id: CollectionSnapshotId,
+    pub collection_id: CollectionId,
+    pub name: Option<String>,
+    pub created_by: Option<UserId>,
+    pub created: DateTime<Utc>,
+    /// The pinned version of each project, in the collection's order when the snapshot was
+    /// taken. Empty when listing snapshots
+    pub versions: Vec<PinnedVersion>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PinnedVersion {
+    pub project_id: ProjectId,
+    pub version_id: VersionId,
+}
+
+impl From<database::models::collection_snapshot_item::CollectionSnapshot> for CollectionSnapshot {
+    fn from(data: database::models::collection_snapshot_item::CollectionSnapshot) -> Self {
+        Self {
+            id: data.id.into(),
+            collection_id: data.collection_id.into(),
+            name: data.name,
+            created_by: data.created_by.map(|x| x.into()),
+            created: data.created,
+            versions: data
+                .versions
+                .into_iter()
+                .map(|(project_id, version_id)| PinnedVersion {
+                    project_id: project_id.into(),
+                    version_id: version_id.into(),
+                })
+                .collect(),
+        }
+    }
+}
+
 /// A collection returned from the API
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Collection {
@@ -38,8 +86,92 @@ pub struct Collection {
     /// The date at which the collection was updated.
     pub updated: DateTime<Utc>,
 
-    /// A list of ProjectIds that are in this collection.
+    /// A list of ProjectIds that are in this collection, in order.
     pub projects: Vec<ProjectId>,
+    /// The projects in this collection with their notes, in the same order as `projects`
+    pub items: Vec<CollectionItem>,
+
+    /// The users other than the owner who can edit this collection. Pending invites are not
+    /// included
+    pub editors: Vec<CollectionEditor>,
+
+    /// The number of users following this collection
+    pub followers: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CollectionItem {
+    pub project_id: ProjectId,
+    /// Why the project is included, or how it is best set up
+    pub note: Option<String>,
+}
+
+impl From<database::models::collection_item::CollectionEntry> for CollectionItem {
+    fn from(data: database::models::collection_item::CollectionEntry) -> Self {
+        Self {
+            project_id: data.project_id.into(),
+            note: data.note,
+        }
+    }
+}
+
+/// A user other than the owner who can edit a collection
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CollectionEditor {
+    pub user_id: UserId,
+    pub permissions: CollectionPermissions,
+    /// Whether the user accepted their invite. Only accepted editors can edit the collection
+    pub accepted: bool,
+}
+
+impl From<database::models::collection_item::CollectionEditor> for CollectionEditor {
+    fn from(data: database::models::collection_item::CollectionEditor) -> Self {
+        Self {
+            user_id: data.user_id.into(),
+            permissions: data.permissions,
+            accepted: data.accepted,
+        }
+    }
+}
+
+bitflags::bitflags! {
+    /// What an editor can change in a collection. The owner can change everything, and is the
+    /// only one who can change the collection's status or delete it
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct CollectionPermissions: u64 {
+        /// The name, description and icon
+        const EDIT_DETAILS = 1 << 0;
+        const EDIT_PROJECTS = 1 << 1;
+        /// Inviting, editing and removing other editors, with at most the editor's own
+        /// permissions
+        const MANAGE_EDITORS = 1 << 2;
+    }
+}
+
+bitflags_serde_impl!(CollectionPermissions, u64);
+
+impl Default for CollectionPermissions {
+    fn default() -> CollectionPermissions {
+        CollectionPermissions::EDIT_PROJECTS
+    }
+}
+
+impl CollectionPermissions {
+    /// The permissions a user has in a collection, if any
+    pub fn get_permissions(
+        user: &crate::models::users::User,
+        collection: &database::models::Collection,
+    ) -> Option<Self> {
+        if user.role.is_mod() || collection.user_id == user.id.into() {
+            return Some(CollectionPermissions::all());
+        }
+
+        collection
+            .editors
+            .iter()
+            .find(|x| x.accepted && x.user_id == user.id.into())
+            .map(|x| x.permissions)
+    }
 }
 
 impl From<database::models::Collection> for Collection {
@@ -52,6 +184,14 @@ impl From<database::models::Collection> for Collection {
             description: c.description,
             updated: c.updated,
             projects: c.projects.into_iter().map(|x| x.into()).collect(),
+            items: c.items.into_iter().map(|x| x.into()).collect(),
+            editors: c
+                .editors
+                .into_iter()
+                .filter(|x| x.accepted)
+                .map(|x| x.into())
+                .collect(),
+            followers: c.follows as u32,
             icon_url: c.icon_url,
             color: c.color,
             status: c.status,
@@ -110,6 +250,11 @@ impl CollectionStatus {
         }
     }
 
+    /// Whether the collection can be found through search
+    pub fn is_searchable(&self) -> bool {
+        matches!(self, CollectionStatus::Listed)
+    }
+
     pub fn is_approved(&self) -> bool {
         match self {
             CollectionStatus::Listed => true,
@@ -0,0 +1,57 @@
+use super::ids::{OrganizationId, ProjectId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Who a Discord role is granted to
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscordRelationship {
+    /// Members of the project's or organization's team, such as a "contributor" role
+    TeamMember,
+    /// Followers of the project, or of any of the organization's projects, such as a
+    /// "supporter" role
+    Follower,
+}
+
+impl DiscordRelationship {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiscordRelationship::TeamMember => "team_member",
+            DiscordRelationship::Follower => "follower",
+        }
+    }
+
+    pub fn from_string(string: &str) -> DiscordRelationship {
+        match string {
+            "team_member" => DiscordRelationship::TeamMember,
+            _ => DiscordRelationship::Follower,
+        }
+    }
+}
+
+/// A Discord role granted to users by their relationship with a project or organization.
+/// Discord IDs are sent as strings, as they are too large for some JSON parsers
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DiscordRoleLink {
+    pub id: i64,
+    pub project_id: Option<ProjectId>,
+    pub organization_id: Option<OrganizationId>,
+    pub guild_id: String,
+    pub role_id: String,
+    pub relationship: DiscordRelationship,
+    pub created: DateTime<Utc>,
+}
+
+impl From<crate::database::models::discord_role_link_item::DiscordRoleLink> for DiscordRoleLink {
+    fn from(data: crate::database::models::discord_role_link_item::DiscordRoleLink) -> Self {
+        Self {
+            id: data.id,
+            project_id: data.project_id.map(|x| x.into()),
+            organization_id: data.organization_id.map(|x| x.into()),
+            guild_id: (data.guild_id as u64).to_string(),
+            role_id: (data.role_id as u64).to_string(),
+            relationship: data.relationship,
+            created: data.created,
+        }
+    }
+}
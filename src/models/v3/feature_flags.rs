@@ -0,0 +1,36 @@
+use super::ids::UserId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A runtime toggle for a feature which is being rolled out
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FeatureFlag {
+    pub key: String,
+    pub description: String,
+    pub enabled: bool,
+    /// The share of users the flag is on for while it is enabled, from 0 to 100
+    pub rollout_percentage: i16,
+    /// Users the flag is on or off for regardless of its rollout
+    pub overrides: HashMap<UserId, bool>,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+impl From<crate::database::models::feature_flag_item::FeatureFlag> for FeatureFlag {
+    fn from(data: crate::database::models::feature_flag_item::FeatureFlag) -> Self {
+        Self {
+            key: data.key,
+            description: data.description,
+            enabled: data.enabled,
+            rollout_percentage: data.rollout_percentage,
+            overrides: data
+                .overrides
+                .into_iter()
+                .map(|(user_id, enabled)| (user_id.into(), enabled))
+                .collect(),
+            created: data.created,
+            updated: data.updated,
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use super::ids::ProjectId;
+use super::projects::Project;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Where staff-curated projects are shown
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FeaturedKind {
+    /// The projects featured at the top of the homepage
+    Featured,
+    /// A seasonal or themed spotlight
+    Spotlight,
+    /// A row of projects further down the homepage
+    HomepageRow,
+}
+
+impl FeaturedKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeaturedKind::Featured => "featured",
+            FeaturedKind::Spotlight => "spotlight",
+            FeaturedKind::HomepageRow => "homepage_row",
+        }
+    }
+
+    pub fn from_string(string: &str) -> FeaturedKind {
+        match string {
+            "featured" => FeaturedKind::Featured,
+            "spotlight" => FeaturedKind::Spotlight,
+            _ => FeaturedKind::HomepageRow,
+        }
+    }
+}
+
+/// A set of projects curated by staff, as managed by staff
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FeaturedRow {
+    pub id: i64,
+    pub kind: FeaturedKind,
+    pub title: String,
+    pub description: String,
+    pub project_ids: Vec<ProjectId>,
+    pub starts: DateTime<Utc>,
+    /// When the row stops being shown, if ever
+    pub ends: Option<DateTime<Utc>>,
+    /// Rows of the same kind are shown lowest ordering first
+    pub ordering: i32,
+    pub created: DateTime<Utc>,
+}
+
+impl From<crate::database::models::featured_item::FeaturedRow> for FeaturedRow {
+    fn from(data: crate::database::models::featured_item::FeaturedRow) -> Self {
+        Self {
+            id: data.id,
+            kind: data.kind,
+            title: data.title,
+            description: data.description,
+            project_ids: data.project_ids.into_iter().map(|x| x.into()).collect(),
+            starts: data.starts,
+            ends: data.ends,
+            ordering: data.ordering,
+            created: data.created,
+        }
+    }
+}
+
+/// A row which is being shown, with the projects in it which are visible
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ActiveFeaturedRow {
+    pub id: i64,
+    pub kind: FeaturedKind,
+    pub title: String,
+    pub description: String,
+    pub projects: Vec<Project>,
+    pub ends: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,36 @@
+use super::ids::VersionId;
+use super::projects::Project;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Why an item is in a user's home feed
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedSource {
+    /// A new version of a project the user follows
+    FollowedUpdate,
+    /// A project sharing categories with projects the user downloaded recently
+    Similar,
+    /// A project which is downloaded a lot lately
+    Trending,
+}
+
+/// How the items of a home feed are ordered
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedRanking {
+    /// Weighs each source and how strong its signal is, favoring recent items
+    #[default]
+    Blended,
+    /// Newest first, regardless of source
+    Chronological,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FeedItem {
+    pub source: FeedSource,
+    pub project: Project,
+    /// The new version, for updates to followed projects
+    pub version_id: Option<VersionId>,
+    pub date: DateTime<Utc>,
+}
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-pub use super::collections::CollectionId;
+pub use super::collections::{CollectionId, CollectionSnapshotId};
 pub use super::images::ImageId;
 pub use super::notifications::NotificationId;
 pub use super::oauth_clients::OAuthClientAuthorizationId;
@@ -129,6 +129,7 @@ base62_id_impl!(OAuthClientId, OAuthClientId);
 base62_id_impl!(OAuthRedirectUriId, OAuthRedirectUriId);
 base62_id_impl!(OAuthClientAuthorizationId, OAuthClientAuthorizationId);
 base62_id_impl!(PayoutId, PayoutId);
+base62_id_impl!(CollectionSnapshotId, CollectionSnapshotId);
 
 pub mod base62_impl {
     use serde::de::{self, Deserializer, Visitor};
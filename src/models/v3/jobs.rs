@@ -0,0 +1,307 @@
+use super::ids::{ProjectId, UserId, VersionId};
+use super::moderation::ImageSource;
+use super::notifications::DependencyChange;
+use crate::clickhouse::ExportFormat;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Work done in the background by the job workers in `queue::jobs`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobPayload {
+    IndexProjects,
+    SendEmail {
+        to: String,
+        subject: String,
+        body: String,
+    },
+    DiscordWebhook {
+        project_id: ProjectId,
+        channel: DiscordChannel,
+        message: Option<String>,
+    },
+    AutomaticPayout {
+        user_id: UserId,
+    },
+    PurgeExpiredData,
+    /// Exports the daily metrics of a project to a file, for exports too large to stream
+    ExportProjectAnalytics {
+        project_id: ProjectId,
+        format: ExportFormat,
+        start_date: DateTime<Utc>,
+        end_date: DateTime<Utc>,
+    },
+    /// Processes a webhook received from another service, stored in `inbound_webhooks`
+    ProcessWebhook {
+        webhook_id: i64,
+    },
+    /// Checks an uploaded image for unwanted content, flagging it for moderators if found
+    ScanImage {
+        image_url: String,
+        source: ImageSource,
+    },
+    /// Adds new game versions from the upstream manifests of games
+    SyncGameVersions,
+    /// Copies the icon, gallery and optionally the files of a CurseForge project into the
+    /// draft project it was imported as
+    ImportCurseForgeProject {
+        project_id: ProjectId,
+        user_id: UserId,
+        curseforge_id: u32,
+        rehost_files: bool,
+    },
+    /// Imports the releases of the GitHub repository a project links as versions. A single
+    /// release sent by GitHub is imported as a draft, and all the existing releases otherwise
+    ImportGitHubReleases {
+        project_id: ProjectId,
+        release_id: Option<u64>,
+    },
+    /// Generates the sitemaps of public pages into the file host
+    GenerateSitemaps,
+    /// Builds an archive of a project's metadata, description and images for its authors to
+    /// keep as a backup
+    ExportProjectBackup {
+        project_id: ProjectId,
+    },
+    /// Checks whether the unverified links on a user's profile link back to it
+    VerifyProfileLinks {
+        user_id: UserId,
+    },
+    /// Sends staff the report of the last full week, unless it was already sent
+    SendStaffReport,
+    /// Notifies the teams of the projects requiring a project that it released or yanked a
+    /// version
+    NotifyDependents {
+        project_id: ProjectId,
+        version_id: VersionId,
+        change: DependencyChange,
+    },
+}
+
+impl JobPayload {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JobPayload::IndexProjects => "index_projects",
+            JobPayload::SendEmail { .. } => "send_email",
+            JobPayload::DiscordWebhook { .. } => "discord_webhook",
+            JobPayload::AutomaticPayout { .. } => "automatic_payout",
+            JobPayload::PurgeExpiredData => "purge_expired_data",
+            JobPayload::ExportProjectAnalytics { .. } => "export_project_analytics",
+            JobPayload::ProcessWebhook { .. } => "process_webhook",
+            JobPayload::ScanImage { .. } => "scan_image",
+            JobPayload::SyncGameVersions => "sync_game_versions",
+            JobPayload::ImportCurseForgeProject { .. } => "import_curseforge_project",
+            JobPayload::ImportGitHubReleases { .. } => "import_github_releases",
+            JobPayload::GenerateSitemaps => "generate_sitemaps",
+            JobPayload::ExportProjectBackup { .. } => "export_project_backup",
+            JobPayload::VerifyProfileLinks { .. } => "verify_profile_links",
+            JobPayload::SendStaffReport => "send_staff_report",
+            JobPayload::NotifyDependents { .. } => "notify_dependents",
+        }
+    }
+
+    /// Jobs sharing a key are not queued while one of them is pending or running
+    pub fn unique_key(&self) -> Option<String> {
+        match self {
+            JobPayload::IndexProjects => Some("index_projects".to_string()),
+            JobPayload::AutomaticPayout { user_id } => Some(format!("automatic_payout:{user_id}")),
+            JobPayload::PurgeExpiredData => Some("purge_expired_data".to_string()),
+            JobPayload::ProcessWebhook { webhook_id } => {
+                Some(format!("process_webhook:{webhook_id}"))
+            }
+            JobPayload::ScanImage { image_url, .. } => Some(format!("scan_image:{image_url}")),
+            JobPayload::SyncGameVersions => Some("sync_game_versions".to_string()),
+            JobPayload::ImportCurseForgeProject { project_id, .. } => {
+                Some(format!("import_curseforge_project:{project_id}"))
+            }
+            JobPayload::ImportGitHubReleases {
+                project_id,
+                release_id: None,
+            } => Some(format!("import_github_releases:{project_id}")),
+            JobPayload::ImportGitHubReleases {
+                project_id,
+                release_id: Some(release_id),
+            } => Some(format!("import_github_releases:{project_id}:{release_id}")),
+            JobPayload::GenerateSitemaps => Some("generate_sitemaps".to_string()),
+            JobPayload::VerifyProfileLinks { user_id } => {
+                Some(format!("verify_profile_links:{user_id}"))
+            }
+            JobPayload::SendStaffReport => Some("send_staff_report".to_string()),
+            JobPayload::NotifyDependents {
+                version_id, change, ..
+            } => Some(format!(
+                "notify_dependents:{version_id}:{}",
+                change.as_str()
+            )),
+            JobPayload::SendEmail { .. }
+            | JobPayload::DiscordWebhook { .. }
+            | JobPayload::ExportProjectAnalytics { .. }
+            | JobPayload::ExportProjectBackup { .. } => None,
+        }
+    }
+
+    /// Whether a run interrupted by shutdown may be cancelled and handed straight back to the
+    /// queue. Payouts can't be stopped partway through, so they run to completion or wait for
+    /// their lease to run out
+    pub fn is_releasable(&self) -> bool {
+        !matches!(self, JobPayload::AutomaticPayout { .. })
+    }
+
+    /// Attempts made before the job is moved to the dead letters
+    pub fn max_attempts(&self) -> i32 {
+        match self {
+            JobPayload::IndexProjects => 3,
+            JobPayload::SendEmail { .. } => 8,
+            JobPayload::DiscordWebhook { .. } => 5,
+            JobPayload::AutomaticPayout { .. } => 5,
+            JobPayload::PurgeExpiredData => 3,
+            JobPayload::ExportProjectAnalytics { .. } => 3,
+            JobPayload::ProcessWebhook { .. } => 8,
+            JobPayload::ScanImage { .. } => 5,
+            JobPayload::SyncGameVersions => 3,
+            JobPayload::ImportCurseForgeProject { .. } => 3,
+            JobPayload::ImportGitHubReleases { .. } => 5,
+            JobPayload::GenerateSitemaps => 3,
+            JobPayload::ExportProjectBackup { .. } => 3,
+            JobPayload::VerifyProfileLinks { .. } => 3,
+            JobPayload::SendStaffReport => 5,
+            JobPayload::NotifyDependents { .. } => 5,
+        }
+    }
+
+    /// How long a worker may hold the job before it is assumed to have died
+    pub fn timeout_seconds(&self) -> i32 {
+        match self {
+            JobPayload::IndexProjects => 60 * 60 * 3,
+            JobPayload::SendEmail { .. } | JobPayload::DiscordWebhook { .. } => 60 * 5,
+            JobPayload::AutomaticPayout { .. } => 60 * 15,
+            JobPayload::PurgeExpiredData => 60 * 30,
+            JobPayload::ExportProjectAnalytics { .. } => 60 * 30,
+            JobPayload::ProcessWebhook { .. } => 60 * 5,
+            JobPayload::ScanImage { .. } => 60 * 5,
+            JobPayload::SyncGameVersions => 60 * 10,
+            JobPayload::ImportCurseForgeProject { .. } => 60 * 60,
+            JobPayload::ImportGitHubReleases { .. } => 60 * 60,
+            JobPayload::GenerateSitemaps => 60 * 30,
+            JobPayload::ExportProjectBackup { .. } => 60 * 30,
+            JobPayload::VerifyProfileLinks { .. } => 60 * 5,
+            JobPayload::SendStaffReport => 60 * 5,
+            JobPayload::NotifyDependents { .. } => 60 * 15,
+        }
+    }
+}
+
+/// The Discord webhooks project updates are posted to
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscordChannel {
+    Public,
+    Moderation,
+}
+
+impl DiscordChannel {
+    /// The variable holding the webhook's URL. URLs are read when the job runs rather than
+    /// stored with it
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            DiscordChannel::Public => "PUBLIC_DISCORD_WEBHOOK",
+            DiscordChannel::Moderation => "MODERATION_DISCORD_WEBHOOK",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    /// Ran successfully. Only jobs started by a user are kept once completed, while the rest
+    /// are removed
+    Completed,
+    /// Failed permanently or ran out of attempts. Dead jobs are kept until retried
+    Dead,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Dead => "dead",
+        }
+    }
+
+    pub fn from_string(string: &str) -> JobStatus {
+        match string {
+            "running" => JobStatus::Running,
+            "completed" => JobStatus::Completed,
+            "dead" => JobStatus::Dead,
+            _ => JobStatus::Pending,
+        }
+    }
+}
+
+/// A job as shown to admins. Payloads are left out, as they can hold emails sent to users
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Job {
+    pub id: i64,
+    pub kind: String,
+    pub status: JobStatus,
+    pub unique_key: Option<String>,
+    pub attempt_count: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+}
+
+impl From<crate::database::models::job_item::Job> for Job {
+    fn from(data: crate::database::models::job_item::Job) -> Self {
+        Self {
+            id: data.id,
+            kind: data.kind,
+            status: data.status,
+            unique_key: data.unique_key,
+            attempt_count: data.attempt_count,
+            max_attempts: data.max_attempts,
+            run_at: data.run_at,
+            last_error: data.last_error,
+            created: data.created,
+            finished: data.finished,
+        }
+    }
+}
+
+/// A job as shown to the user who started it, polled to follow a long operation
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UserJob {
+    pub id: i64,
+    pub kind: String,
+    pub status: JobStatus,
+    /// How far along the job is, from 0 to 1, for jobs which report it
+    pub progress: Option<f32>,
+    /// Where the result can be downloaded once the job is completed
+    pub result_url: Option<String>,
+    /// Why the job failed, once it is dead
+    pub error: Option<String>,
+    pub created: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+}
+
+impl From<crate::database::models::job_item::Job> for UserJob {
+    fn from(data: crate::database::models::job_item::Job) -> Self {
+        let dead = data.status == JobStatus::Dead;
+        Self {
+            id: data.id,
+            kind: data.kind,
+            status: data.status,
+            progress: data.progress,
+            result_url: data.result_url,
+            error: data.last_error.filter(|_| dead),
+            created: data.created,
+            finished: data.finished,
+        }
+    }
+}
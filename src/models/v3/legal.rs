@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A document users agree to when using Modrinth
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LegalDocumentKind {
+    Terms,
+    Privacy,
+    Rules,
+}
+
+impl LegalDocumentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LegalDocumentKind::Terms => "terms",
+            LegalDocumentKind::Privacy => "privacy",
+            LegalDocumentKind::Rules => "rules",
+        }
+    }
+
+    pub fn from_string(string: &str) -> LegalDocumentKind {
+        match string {
+            "terms" => LegalDocumentKind::Terms,
+            "privacy" => LegalDocumentKind::Privacy,
+            _ => LegalDocumentKind::Rules,
+        }
+    }
+}
+
+/// A published version of a legal document
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LegalDocument {
+    pub id: i64,
+    pub kind: LegalDocumentKind,
+    /// Counts up from 1 for each kind of document
+    pub version: i32,
+    /// The document, as Markdown
+    pub content: String,
+    /// Whether users have to accept this version again before making changes
+    pub material: bool,
+    pub published: DateTime<Utc>,
+}
+
+impl From<crate::database::models::legal_document_item::LegalDocument> for LegalDocument {
+    fn from(data: crate::database::models::legal_document_item::LegalDocument) -> Self {
+        Self {
+            id: data.id,
+            kind: data.kind,
+            version: data.version,
+            content: data.content,
+            material: data.material,
+            published: data.published,
+        }
+    }
+}
+
+/// A version of a legal document a user accepted
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LegalAcceptance {
+    pub document_id: i64,
+    pub kind: LegalDocumentKind,
+    pub version: i32,
+    pub accepted: DateTime<Utc>,
+}
+
+impl From<crate::database::models::legal_document_item::LegalAcceptance> for LegalAcceptance {
+    fn from(data: crate::database::models::legal_document_item::LegalAcceptance) -> Self {
+        Self {
+            document_id: data.document_id,
+            kind: data.kind,
+            version: data.version,
+            accepted: data.accepted,
+        }
+    }
+}
+
+/// The body of the 403 returned for changes made by users who have not accepted the latest
+/// material version of a document. Accepting the documents lets the change through
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LegalAcceptanceRequired {
+    /// Always `legal_acceptance_required`
+    pub error: String,
+    pub description: String,
+    /// The versions to accept
+    pub documents: Vec<LegalDocument>,
+}
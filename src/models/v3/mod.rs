@@ -1,7 +1,14 @@
 pub mod analytics;
 pub mod collections;
+pub mod discord;
+pub mod feature_flags;
+pub mod featured;
+pub mod feed;
 pub mod ids;
 pub mod images;
+pub mod jobs;
+pub mod legal;
+pub mod moderation;
 pub mod notifications;
 pub mod oauth_clients;
 pub mod organizations;
@@ -11,6 +18,8 @@ pub mod payouts;
 pub mod projects;
 pub mod reports;
 pub mod sessions;
+pub mod shortlinks;
 pub mod teams;
 pub mod threads;
+pub mod uploads;
 pub mod users;
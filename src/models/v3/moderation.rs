@@ -0,0 +1,187 @@
+use super::ids::{CollectionId, OrganizationId, ProjectId, UserId};
+use super::projects::ProjectStatus;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A change to the moderation queue, streamed to moderators so their view of the queue stays
+/// current without polling it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModerationEvent {
+    /// A project was submitted for review
+    Submitted { project_id: ProjectId },
+    /// A moderator claimed a project to review it
+    Claimed {
+        project_id: ProjectId,
+        user_id: UserId,
+        expires: DateTime<Utc>,
+    },
+    /// A project's claim was released
+    Unclaimed { project_id: ProjectId },
+    /// A project under review was approved, rejected, withheld or taken back by its owner,
+    /// which takes it out of the queue
+    StatusChanged {
+        project_id: ProjectId,
+        old_status: ProjectStatus,
+        new_status: ProjectStatus,
+    },
+    /// A project under review was deleted
+    Removed { project_id: ProjectId },
+    /// Events were missed because the client fell behind, so the queue should be fetched again
+    Lagged,
+}
+
+/// A canned response moderators can post into threads, so common requests are worded the same
+/// way every time. The body may reference variables as `{{ name }}`. `project_name`,
+/// `project_slug` and `project_id` are filled in automatically for project threads
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ModerationTemplate {
+    pub name: String,
+    pub title: String,
+    pub body: String,
+    /// Whether messages posted from the template are only visible to moderators
+    pub private: bool,
+    pub updated: DateTime<Utc>,
+}
+
+impl From<crate::database::models::moderation_template_item::ModerationTemplate>
+    for ModerationTemplate
+{
+    fn from(data: crate::database::models::moderation_template_item::ModerationTemplate) -> Self {
+        Self {
+            name: data.name,
+            title: data.title,
+            body: data.body,
+            private: data.private,
+            updated: data.updated,
+        }
+    }
+}
+
+/// What an uploaded image is used as, so moderators can find it
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    ProjectIcon { project_id: ProjectId },
+    GalleryImage { project_id: ProjectId },
+    UserAvatar { user_id: UserId },
+    OrganizationIcon { organization_id: OrganizationId },
+    CollectionIcon { collection_id: CollectionId },
+}
+
+impl ImageSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageSource::ProjectIcon { .. } => "project_icon",
+            ImageSource::GalleryImage { .. } => "gallery_image",
+            ImageSource::UserAvatar { .. } => "user_avatar",
+            ImageSource::OrganizationIcon { .. } => "organization_icon",
+            ImageSource::CollectionIcon { .. } => "collection_icon",
+        }
+    }
+}
+
+/// An uploaded image the content scanner flagged, waiting in the image moderation queue
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ImageFlag {
+    pub id: i64,
+    pub image_url: String,
+    pub source: ImageSource,
+    /// The confidence of each label the scanner reported, from 0 to 1
+    pub labels: HashMap<String, f32>,
+    pub created: DateTime<Utc>,
+}
+
+impl From<crate::database::models::image_flag_item::ImageFlag> for ImageFlag {
+    fn from(data: crate::database::models::image_flag_item::ImageFlag) -> Self {
+        Self {
+            id: data.id,
+            image_url: data.image_url,
+            source: data.source,
+            labels: data.labels,
+            created: data.created,
+        }
+    }
+}
+
+/// An image of a project which looks like an image of another project, which can mean one of
+/// them reposted the other
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimilarImage {
+    /// The image of the project which was looked up
+    pub image_url: String,
+    pub matched_project_id: ProjectId,
+    pub matched_image_url: String,
+    /// Whether the matched image is its project's icon rather than one of its gallery images
+    pub matched_icon: bool,
+    /// How many bits the perceptual hashes of the images differ by, where 0 means they look
+    /// the same
+    pub distance: i64,
+}
+
+/// Something about an account or project which makes it look like spam
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SpamSignalKind {
+    /// The account's email address is from a disposable email provider
+    DisposableEmail,
+    /// Many accounts signed up from the same network around the same time
+    SignupBurst,
+    /// The account created many projects right after signing up
+    MassSubmissions,
+    /// The project's summary and description are mostly links
+    LinkDensity,
+}
+
+/// A signal which added to a spam score, and why it did
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpamSignal {
+    pub kind: SpamSignalKind,
+    /// The points the signal added to the score
+    pub points: i32,
+    /// What was seen, for moderators
+    pub detail: String,
+}
+
+/// How likely an account or project is spam, from 0, made up of the signals which added to it
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SpamScore {
+    pub score: i32,
+    pub signals: Vec<SpamSignal>,
+}
+
+impl SpamScore {
+    pub fn add(&mut self, kind: SpamSignalKind, points: i32, detail: String) {
+        self.score += points;
+        self.signals.push(SpamSignal {
+            kind,
+            points,
+            detail,
+        });
+    }
+}
+
+/// An account or project which scored as likely spam, waiting in the spam moderation queue
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpamFlag {
+    pub id: i64,
+    pub user_id: UserId,
+    /// The project which was scored. Absent when the account was scored as it signed up
+    pub project_id: Option<ProjectId>,
+    #[serde(flatten)]
+    pub score: SpamScore,
+    pub created: DateTime<Utc>,
+}
+
+impl From<crate::database::models::spam_flag_item::SpamFlag> for SpamFlag {
+    fn from(data: crate::database::models::spam_flag_item::SpamFlag) -> Self {
+        Self {
+            id: data.id,
+            user_id: data.user_id.into(),
+            project_id: data.project_id.map(Into::into),
+            score: data.score,
+            created: data.created,
+        }
+    }
+}
@@ -3,9 +3,18 @@ use super::ids::OrganizationId;
 use super::users::UserId;
 use crate::database::models::notification_item::Notification as DBNotification;
 use crate::database::models::notification_item::NotificationAction as DBNotificationAction;
-use crate::models::ids::{ProjectId, ReportId, TeamId, ThreadId, ThreadMessageId, VersionId};
+use crate::database::models::notification_preference_item::NotificationPreference as DBNotificationPreference;
+use crate::database::models::notification_webhook_item::NotificationWebhook as DBNotificationWebhook;
+use crate::database::models::push_subscription_item::PushSubscription as DBPushSubscription;
+use crate::database::models::simple_push_item::SimplePushTarget as DBSimplePushTarget;
+use crate::models::ids::{
+    CollectionId, PayoutId, ProjectId, ReportId, TeamId, ThreadId, ThreadMessageId, VersionId,
+};
+use crate::models::payouts::AdjustmentReason;
 use crate::models::projects::ProjectStatus;
+use crate::models::reports::ReportOutcome;
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -20,6 +29,9 @@ pub struct Notification {
     pub read: bool,
     pub created: DateTime<Utc>,
     pub body: NotificationBody,
+    /// Set if several notifications have been coalesced into this one. `body` is the most
+    /// recent of the grouped items
+    pub group: Option<NotificationGroup>,
 
     pub name: String,
     pub text: String,
@@ -27,6 +39,18 @@ pub struct Notification {
     pub actions: Vec<NotificationAction>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NotificationGroup {
+    pub count: usize,
+    pub items: Vec<NotificationGroupItem>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NotificationGroupItem {
+    pub body: NotificationBody,
+    pub created: DateTime<Utc>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum NotificationBody {
@@ -58,6 +82,72 @@ pub enum NotificationBody {
         project_id: Option<ProjectId>,
         report_id: Option<ReportId>,
     },
+    /// Sent to the member concerned and to the team's member managers
+    TeamMembershipChange {
+        team_id: TeamId,
+        project_id: Option<ProjectId>,
+        organization_id: Option<OrganizationId>,
+        member_id: UserId,
+        changed_by: UserId,
+        change: MembershipChange,
+    },
+    /// An automatic payout was sent
+    PayoutSent {
+        payout_id: PayoutId,
+        #[serde(with = "rust_decimal::serde::float")]
+        amount: Decimal,
+    },
+    /// An automatic payout could not be made, and needs the user's attention
+    PayoutFailed {
+        reason: String,
+    },
+    /// The user's balance was adjusted, such as for a clawback of ad revenue
+    PayoutAdjusted {
+        #[serde(with = "rust_decimal::serde::float")]
+        amount: Decimal,
+        reason: AdjustmentReason,
+        /// The balance after the adjustment, which may be negative
+        #[serde(with = "rust_decimal::serde::float")]
+        balance: Decimal,
+    },
+    /// A release was published in the GitHub repository linked as the project's source
+    GitHubRelease {
+        project_id: ProjectId,
+        tag_name: String,
+        url: String,
+    },
+    /// An invite to become an editor of a collection
+    CollectionInvite {
+        collection_id: CollectionId,
+        invited_by: UserId,
+    },
+    /// Projects were added to or removed from a collection the user follows
+    CollectionUpdate {
+        collection_id: CollectionId,
+        added_projects: Vec<ProjectId>,
+        removed_projects: Vec<ProjectId>,
+    },
+    /// A new message in a thread the user is subscribed to
+    ThreadMessage {
+        thread_id: ThreadId,
+        message_id: ThreadMessageId,
+
+        project_id: Option<ProjectId>,
+        report_id: Option<ReportId>,
+    },
+    /// A report the user made was closed by a moderator
+    ReportClosed {
+        report_id: ReportId,
+        thread_id: ThreadId,
+        outcome: Option<ReportOutcome>,
+    },
+    /// A project which one of the team's projects requires released or yanked a version
+    DependencyUpdate {
+        project_id: ProjectId,
+        dependency_id: ProjectId,
+        version_id: VersionId,
+        change: DependencyChange,
+    },
     LegacyMarkdown {
         notification_type: Option<String>,
         name: String,
@@ -68,10 +158,362 @@ pub enum NotificationBody {
     Unknown,
 }
 
+impl NotificationBody {
+    /// The key and time window within which notifications of this kind are coalesced into
+    /// a single grouped notification, if they are grouped at all
+    pub fn coalescing_rule(&self) -> Option<(String, chrono::Duration)> {
+        let window = chrono::Duration::seconds(
+            crate::util::env::parse_var("NOTIFICATION_GROUPING_WINDOW").unwrap_or(3600),
+        );
+
+        match self {
+            NotificationBody::ProjectUpdate { project_id, .. } => {
+                Some((format!("project_update:{project_id}"), window))
+            }
+            NotificationBody::ModeratorMessage { thread_id, .. } => {
+                Some((format!("moderator_message:{thread_id}"), window))
+            }
+            NotificationBody::CollectionUpdate { collection_id, .. } => {
+                Some((format!("collection_update:{collection_id}"), window))
+            }
+            NotificationBody::ThreadMessage { thread_id, .. } => {
+                Some((format!("thread_message:{thread_id}"), window))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn notification_type(&self) -> NotificationType {
+        match self {
+            NotificationBody::ProjectUpdate { .. } => NotificationType::ProjectUpdate,
+            NotificationBody::TeamInvite { .. } => NotificationType::TeamInvite,
+            NotificationBody::OrganizationInvite { .. } => NotificationType::OrganizationInvite,
+            NotificationBody::StatusChange { .. } => NotificationType::StatusChange,
+            NotificationBody::ModeratorMessage { .. } => NotificationType::ModeratorMessage,
+            NotificationBody::TeamMembershipChange { .. } => NotificationType::TeamMembershipChange,
+            NotificationBody::PayoutSent { .. } => NotificationType::PayoutSent,
+            NotificationBody::PayoutFailed { .. } => NotificationType::PayoutFailed,
+            NotificationBody::PayoutAdjusted { .. } => NotificationType::PayoutAdjusted,
+            NotificationBody::GitHubRelease { .. } => NotificationType::GitHubRelease,
+            NotificationBody::CollectionInvite { .. } => NotificationType::CollectionInvite,
+            NotificationBody::CollectionUpdate { .. } => NotificationType::CollectionUpdate,
+            NotificationBody::ThreadMessage { .. } => NotificationType::ThreadMessage,
+            NotificationBody::ReportClosed { .. } => NotificationType::ReportClosed,
+            NotificationBody::DependencyUpdate { .. } => NotificationType::DependencyUpdate,
+            NotificationBody::LegacyMarkdown { .. } | NotificationBody::Unknown => {
+                NotificationType::Unknown
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MembershipChange {
+    /// The member accepted an invite
+    Joined,
+    /// The member was added without an invite, as they are part of the project's organization
+    Added,
+    Left,
+    Removed,
+    PermissionsChanged,
+    /// The member became the owner of the team
+    OwnershipTransferred,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyChange {
+    /// The version was published, or a hidden version was made visible
+    Released,
+    /// The version was deleted or hidden
+    Yanked,
+}
+
+impl DependencyChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DependencyChange::Released => "released",
+            DependencyChange::Yanked => "yanked",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationType {
+    ProjectUpdate,
+    TeamInvite,
+    OrganizationInvite,
+    StatusChange,
+    ModeratorMessage,
+    TeamMembershipChange,
+    PayoutSent,
+    PayoutFailed,
+    PayoutAdjusted,
+    GitHubRelease,
+    CollectionInvite,
+    CollectionUpdate,
+    ThreadMessage,
+    ReportClosed,
+    DependencyUpdate,
+    Unknown,
+}
+
+impl NotificationType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationType::ProjectUpdate => "project_update",
+            NotificationType::TeamInvite => "team_invite",
+            NotificationType::OrganizationInvite => "organization_invite",
+            NotificationType::StatusChange => "status_change",
+            NotificationType::ModeratorMessage => "moderator_message",
+            NotificationType::TeamMembershipChange => "team_membership_change",
+            NotificationType::PayoutSent => "payout_sent",
+            NotificationType::PayoutFailed => "payout_failed",
+            NotificationType::PayoutAdjusted => "payout_adjusted",
+            NotificationType::GitHubRelease => "github_release",
+            NotificationType::CollectionInvite => "collection_invite",
+            NotificationType::CollectionUpdate => "collection_update",
+            NotificationType::ThreadMessage => "thread_message",
+            NotificationType::ReportClosed => "report_closed",
+            NotificationType::DependencyUpdate => "dependency_update",
+            NotificationType::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_string(string: &str) -> NotificationType {
+        match string {
+            "project_update" => NotificationType::ProjectUpdate,
+            "team_invite" => NotificationType::TeamInvite,
+            "organization_invite" => NotificationType::OrganizationInvite,
+            "status_change" => NotificationType::StatusChange,
+            "moderator_message" => NotificationType::ModeratorMessage,
+            "team_membership_change" => NotificationType::TeamMembershipChange,
+            "payout_sent" => NotificationType::PayoutSent,
+            "payout_failed" => NotificationType::PayoutFailed,
+            "payout_adjusted" => NotificationType::PayoutAdjusted,
+            "github_release" => NotificationType::GitHubRelease,
+            "collection_invite" => NotificationType::CollectionInvite,
+            "collection_update" => NotificationType::CollectionUpdate,
+            "thread_message" => NotificationType::ThreadMessage,
+            "report_closed" => NotificationType::ReportClosed,
+            "dependency_update" => NotificationType::DependencyUpdate,
+            _ => NotificationType::Unknown,
+        }
+    }
+}
+
+/// An out-of-band channel notifications can be delivered through, in addition to
+/// the notification list on the site itself
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationChannel {
+    /// The notification list on the site itself
+    Site,
+    WebPush,
+    Webhook,
+    SimplePush,
+}
+
+impl NotificationChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationChannel::Site => "site",
+            NotificationChannel::WebPush => "web_push",
+            NotificationChannel::Webhook => "webhook",
+            NotificationChannel::SimplePush => "simple_push",
+        }
+    }
+
+    pub fn from_string(string: &str) -> Option<NotificationChannel> {
+        match string {
+            "site" => Some(NotificationChannel::Site),
+            "web_push" => Some(NotificationChannel::WebPush),
+            "webhook" => Some(NotificationChannel::Webhook),
+            "simple_push" => Some(NotificationChannel::SimplePush),
+            _ => None,
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = NotificationChannel> {
+        [
+            NotificationChannel::Site,
+            NotificationChannel::WebPush,
+            NotificationChannel::Webhook,
+            NotificationChannel::SimplePush,
+        ]
+        .iter()
+        .copied()
+    }
+
+    /// Whether notifications are sent out through this channel by the delivery queue, rather
+    /// than only being stored
+    pub fn is_delivered(&self) -> bool {
+        match self {
+            NotificationChannel::Site => false,
+            NotificationChannel::WebPush
+            | NotificationChannel::Webhook
+            | NotificationChannel::SimplePush => true,
+        }
+    }
+
+    /// Whether this channel delivers notifications when the user has not set a preference for it
+    pub fn enabled_by_default(&self) -> bool {
+        match self {
+            NotificationChannel::Site => true,
+            NotificationChannel::WebPush => true,
+            NotificationChannel::Webhook => true,
+            NotificationChannel::SimplePush => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationDeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl NotificationDeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationDeliveryStatus::Pending => "pending",
+            NotificationDeliveryStatus::Delivered => "delivered",
+            NotificationDeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_string(string: &str) -> NotificationDeliveryStatus {
+        match string {
+            "delivered" => NotificationDeliveryStatus::Delivered,
+            "failed" => NotificationDeliveryStatus::Failed,
+            _ => NotificationDeliveryStatus::Pending,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NotificationPreference {
+    pub channel: NotificationChannel,
+    /// The notification type this preference applies to. If empty, it applies to all types
+    /// which do not have a more specific preference set
+    pub notification_type: Option<NotificationType>,
+    pub enabled: bool,
+}
+
+impl From<DBNotificationPreference> for NotificationPreference {
+    fn from(pref: DBNotificationPreference) -> Self {
+        Self {
+            channel: pref.channel,
+            notification_type: pref.notification_type,
+            enabled: pref.enabled,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PushSubscription {
+    pub endpoint: String,
+    pub created: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+impl From<DBPushSubscription> for PushSubscription {
+    fn from(sub: DBPushSubscription) -> Self {
+        Self {
+            endpoint: sub.endpoint,
+            created: sub.created,
+            last_used: sub.last_used,
+        }
+    }
+}
+
+/// A user's notification webhook. The signing secret is only returned when the webhook is
+/// created
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NotificationWebhook {
+    pub url: String,
+    pub created: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+impl From<DBNotificationWebhook> for NotificationWebhook {
+    fn from(webhook: DBNotificationWebhook) -> Self {
+        Self {
+            url: webhook.url,
+            created: webhook.created,
+            last_used: webhook.last_used,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SimplePushProvider {
+    /// An ntfy topic URL, such as `https://ntfy.sh/my-topic`
+    Ntfy,
+    /// Any URL accepting a JSON POST with the notification's title, body and link, signed with
+    /// the target's token like notification webhooks
+    Generic,
+}
+
+impl SimplePushProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SimplePushProvider::Ntfy => "ntfy",
+            SimplePushProvider::Generic => "generic",
+        }
+    }
+
+    pub fn from_string(string: &str) -> Option<SimplePushProvider> {
+        match string {
+            "ntfy" => Some(SimplePushProvider::Ntfy),
+            "generic" => Some(SimplePushProvider::Generic),
+            _ => None,
+        }
+    }
+}
+
+/// A user's simple push target. The token is never returned
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SimplePushTarget {
+    pub provider: SimplePushProvider,
+    pub url: String,
+    pub has_token: bool,
+    pub created: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+impl From<DBSimplePushTarget> for SimplePushTarget {
+    fn from(target: DBSimplePushTarget) -> Self {
+        Self {
+            provider: target.provider,
+            url: target.url,
+            has_token: target.token.is_some(),
+            created: target.created,
+            last_used: target.last_used,
+        }
+    }
+}
+
 impl From<DBNotification> for Notification {
     fn from(notif: DBNotification) -> Self {
+        let group_count = notif.group_items.len();
+
         let (name, text, link, actions) = {
             match &notif.body {
+                NotificationBody::ProjectUpdate { project_id, .. } if group_count > 1 => (
+                    "A project you follow has been updated!".to_string(),
+                    format!(
+                        "The project {} has released {} new versions",
+                        project_id, group_count
+                    ),
+                    format!("/project/{}/versions", project_id),
+                    vec![],
+                ),
                 NotificationBody::ProjectUpdate {
                     project_id,
                     version_id,
@@ -166,6 +608,236 @@ impl From<DBNotification> for Notification {
                     },
                     vec![],
                 ),
+                NotificationBody::TeamMembershipChange {
+                    project_id,
+                    organization_id,
+                    member_id,
+                    change,
+                    ..
+                } => {
+                    let team = if organization_id.is_some() {
+                        "an organization"
+                    } else {
+                        "a project team"
+                    };
+                    let (name, text) = if UserId::from(notif.user_id) == *member_id {
+                        match change {
+                            MembershipChange::Joined | MembershipChange::Added => (
+                                "You have joined a team!".to_string(),
+                                format!("You are now a member of {team}"),
+                            ),
+                            MembershipChange::Left => (
+                                "You have left a team".to_string(),
+                                format!("You are no longer a member of {team}"),
+                            ),
+                            MembershipChange::Removed => (
+                                "You have been removed from a team".to_string(),
+                                format!("You have been removed from {team}"),
+                            ),
+                            MembershipChange::PermissionsChanged => (
+                                "Your team permissions have changed".to_string(),
+                                format!("Your role or permissions in {team} have been changed"),
+                            ),
+                            MembershipChange::OwnershipTransferred => (
+                                "You are now the owner of a team".to_string(),
+                                format!("Ownership of {team} has been transferred to you"),
+                            ),
+                        }
+                    } else {
+                        let action = match change {
+                            MembershipChange::Joined => "joined",
+                            MembershipChange::Added => "been added to",
+                            MembershipChange::Left => "left",
+                            MembershipChange::Removed => "been removed from",
+                            MembershipChange::PermissionsChanged => {
+                                "had their permissions changed in"
+                            }
+                            MembershipChange::OwnershipTransferred => "become the owner of",
+                        };
+                        (
+                            "A team you manage has changed".to_string(),
+                            format!("The user {member_id} has {action} {team}"),
+                        )
+                    };
+
+                    (
+                        name,
+                        text,
+                        if let Some(organization_id) = organization_id {
+                            format!("/organization/{}", organization_id)
+                        } else if let Some(project_id) = project_id {
+                            format!("/project/{}", project_id)
+                        } else {
+                            "#".to_string()
+                        },
+                        vec![],
+                    )
+                }
+                NotificationBody::PayoutSent { amount, .. } => (
+                    "Your automatic payout has been sent!".to_string(),
+                    format!("${} is on its way to you", amount.round_dp(2)),
+                    "/dashboard/revenue".to_string(),
+                    vec![],
+                ),
+                NotificationBody::PayoutFailed { reason } => (
+                    "Your automatic payout could not be made".to_string(),
+                    reason.clone(),
+                    "/dashboard/revenue".to_string(),
+                    vec![],
+                ),
+                NotificationBody::PayoutAdjusted {
+                    amount,
+                    reason,
+                    balance,
+                } => {
+                    let mut text = if amount.is_sign_negative() {
+                        format!(
+                            "${} was taken from your balance. Reason: {}",
+                            amount.abs().round_dp(2),
+                            reason.description()
+                        )
+                    } else {
+                        format!(
+                            "${} was added to your balance. Reason: {}",
+                            amount.round_dp(2),
+                            reason.description()
+                        )
+                    };
+                    if balance.is_sign_negative() {
+                        text.push_str(&format!(
+                            ". Your balance is now -${}, which will be repaid from future revenue before you can withdraw again",
+                            balance.abs().round_dp(2)
+                        ));
+                    }
+
+                    (
+                        "Your balance has been adjusted".to_string(),
+                        text,
+                        "/dashboard/revenue".to_string(),
+                        vec![],
+                    )
+                }
+                NotificationBody::GitHubRelease {
+                    project_id,
+                    tag_name,
+                    url,
+                } => (
+                    "A new release was published on GitHub".to_string(),
+                    format!("{tag_name} was released at {url}. Upload it to your project as a new version?"),
+                    format!("/project/{}/versions", project_id),
+                    vec![],
+                ),
+                NotificationBody::CollectionInvite { collection_id, .. } => (
+                    "You have been invited to edit a collection!".to_string(),
+                    "An invite has been sent for you to help maintain a collection".to_string(),
+                    format!("/collection/{}", collection_id),
+                    vec![
+                        NotificationAction {
+                            name: "Accept".to_string(),
+                            action_route: (
+                                "POST".to_string(),
+                                format!("collection/{collection_id}/editors/join"),
+                            ),
+                        },
+                        NotificationAction {
+                            name: "Deny".to_string(),
+                            action_route: (
+                                "DELETE".to_string(),
+                                format!(
+                                    "collection/{collection_id}/editors/{}",
+                                    UserId::from(notif.user_id)
+                                ),
+                            ),
+                        },
+                    ],
+                ),
+                NotificationBody::CollectionUpdate { collection_id, .. } if group_count > 1 => (
+                    "A collection you follow has been updated!".to_string(),
+                    format!(
+                        "The projects in the collection {} have changed {} times",
+                        collection_id, group_count
+                    ),
+                    format!("/collection/{}", collection_id),
+                    vec![],
+                ),
+                NotificationBody::CollectionUpdate {
+                    collection_id,
+                    added_projects,
+                    removed_projects,
+                } => (
+                    "A collection you follow has been updated!".to_string(),
+                    format!(
+                        "{} projects were added to and {} projects were removed from the collection {}",
+                        added_projects.len(),
+                        removed_projects.len(),
+                        collection_id
+                    ),
+                    format!("/collection/{}", collection_id),
+                    vec![],
+                ),
+                NotificationBody::ThreadMessage {
+                    project_id,
+                    report_id,
+                    ..
+                } => (
+                    if group_count > 1 {
+                        format!("There are {} new messages in a thread you follow", group_count)
+                    } else {
+                        "There is a new message in a thread you follow".to_string()
+                    },
+                    "Click on the link to read more.".to_string(),
+                    if let Some(project_id) = project_id {
+                        format!("/project/{}/moderation", project_id)
+                    } else if let Some(report_id) = report_id {
+                        format!("/dashboard/report/{}", report_id)
+                    } else {
+                        "#".to_string()
+                    },
+                    vec![],
+                ),
+                NotificationBody::ReportClosed {
+                    report_id, outcome, ..
+                } => (
+                    "Your report has been resolved".to_string(),
+                    if let Some(outcome) = outcome {
+                        format!(
+                            "A moderator closed your report: {}.",
+                            outcome.as_friendly_str().to_lowercase()
+                        )
+                    } else {
+                        "A moderator closed your report.".to_string()
+                    },
+                    format!("/dashboard/report/{}", report_id),
+                    vec![],
+                ),
+                NotificationBody::DependencyUpdate {
+                    project_id,
+                    dependency_id,
+                    version_id,
+                    change: DependencyChange::Released,
+                } => (
+                    "A dependency of your project has been updated".to_string(),
+                    format!(
+                        "The project {}, which {} requires, has released a new version: {}",
+                        dependency_id, project_id, version_id
+                    ),
+                    format!("/project/{}/version/{}", dependency_id, version_id),
+                    vec![],
+                ),
+                NotificationBody::DependencyUpdate {
+                    project_id,
+                    dependency_id,
+                    version_id,
+                    change: DependencyChange::Yanked,
+                } => (
+                    "A dependency of your project has yanked a version".to_string(),
+                    format!(
+                        "The project {}, which {} requires, has removed the version {}. Check that your project still works with its other versions",
+                        dependency_id, project_id, version_id
+                    ),
+                    format!("/project/{}/versions", dependency_id),
+                    vec![],
+                ),
                 NotificationBody::LegacyMarkdown {
                     name,
                     text,
@@ -188,6 +860,14 @@ impl From<DBNotification> for Notification {
             id: notif.id.into(),
             user_id: notif.user_id.into(),
             body: notif.body,
+            group: if group_count > 1 {
+                Some(NotificationGroup {
+                    count: group_count,
+                    items: notif.group_items,
+                })
+            } else {
+                None
+            },
             read: notif.read,
             created: notif.created,
 
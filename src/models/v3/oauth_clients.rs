@@ -9,6 +9,7 @@ use serde_with::serde_as;
 use crate::database::models::oauth_client_authorization_item::OAuthClientAuthorization as DBOAuthClientAuthorization;
 use crate::database::models::oauth_client_item::OAuthClient as DBOAuthClient;
 use crate::database::models::oauth_client_item::OAuthRedirectUri as DBOAuthRedirectUri;
+use crate::database::models::oauth_client_verification_item::OAuthClientVerification as DBOAuthClientVerification;
 
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(from = "Base62Id")]
@@ -61,6 +62,10 @@ pub struct OAuthClient {
     // (optional) Metadata about the client
     pub url: Option<String>,
     pub description: Option<String>,
+
+    // Whether staff have verified the client, which gives it a higher rate limit and lets it
+    // request sensitive scopes
+    pub verified: bool,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -98,6 +103,7 @@ impl From<DBOAuthClient> for OAuthClient {
             created: value.created,
             url: value.url,
             description: value.description,
+            verified: value.verified,
         }
     }
 }
@@ -123,3 +129,97 @@ impl From<DBOAuthClientAuthorization> for OAuthClientAuthorization {
         }
     }
 }
+
+/// Where an OAuth app is in verification. Developers apply and are reviewed by staff, after
+/// which the app is either verified or rejected. Verification can be revoked and reinstated
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuthClientVerificationStatus {
+    /// Applied and waiting for review
+    Pending,
+    Verified,
+    Rejected,
+    /// No longer verified until reinstated
+    Revoked,
+    Unknown,
+}
+
+impl std::fmt::Display for OAuthClientVerificationStatus {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}", self.as_str())
+    }
+}
+
+impl OAuthClientVerificationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OAuthClientVerificationStatus::Pending => "pending",
+            OAuthClientVerificationStatus::Verified => "verified",
+            OAuthClientVerificationStatus::Rejected => "rejected",
+            OAuthClientVerificationStatus::Revoked => "revoked",
+            OAuthClientVerificationStatus::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_string(string: &str) -> OAuthClientVerificationStatus {
+        match string {
+            "pending" => OAuthClientVerificationStatus::Pending,
+            "verified" => OAuthClientVerificationStatus::Verified,
+            "rejected" => OAuthClientVerificationStatus::Rejected,
+            "revoked" => OAuthClientVerificationStatus::Revoked,
+            _ => OAuthClientVerificationStatus::Unknown,
+        }
+    }
+
+    /// Whether a review can move a verification from this status to `status`
+    pub fn can_review_to(&self, status: OAuthClientVerificationStatus) -> bool {
+        matches!(
+            (self, status),
+            (
+                OAuthClientVerificationStatus::Pending,
+                OAuthClientVerificationStatus::Verified
+            ) | (
+                OAuthClientVerificationStatus::Pending,
+                OAuthClientVerificationStatus::Rejected
+            ) | (
+                OAuthClientVerificationStatus::Verified,
+                OAuthClientVerificationStatus::Revoked
+            ) | (
+                OAuthClientVerificationStatus::Revoked,
+                OAuthClientVerificationStatus::Verified
+            )
+        )
+    }
+
+    /// Whether the developer of an app in this status can apply again
+    pub fn can_apply(&self) -> bool {
+        matches!(
+            self,
+            OAuthClientVerificationStatus::Rejected | OAuthClientVerificationStatus::Unknown
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OAuthClientVerification {
+    pub client_id: OAuthClientId,
+    pub status: OAuthClientVerificationStatus,
+    /// What the developer told staff the app is for
+    pub reason: String,
+    pub applied: DateTime<Utc>,
+    pub reviewed: Option<DateTime<Utc>>,
+    pub review_notes: Option<String>,
+}
+
+impl From<DBOAuthClientVerification> for OAuthClientVerification {
+    fn from(value: DBOAuthClientVerification) -> Self {
+        Self {
+            client_id: value.client_id.into(),
+            status: value.status,
+            reason: value.reason,
+            applied: value.applied,
+            reviewed: value.reviewed,
+            review_notes: value.review_notes,
+        }
+    }
+}
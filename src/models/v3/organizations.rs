@@ -1,11 +1,14 @@
 use super::{
     ids::{Base62Id, TeamId},
-    teams::TeamMember,
+    pats::{PatId, Scopes},
+    teams::{ProjectPermissions, TeamMember},
+    users::UserId,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 /// The ID of a team
-#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
 #[serde(from = "Base62Id")]
 #[serde(into = "Base62Id")]
 pub struct OrganizationId(pub u64);
@@ -50,3 +53,48 @@ impl Organization {
         }
     }
 }
+
+/// A token owned by an organization rather than one of its members, for automation such as CI
+/// pipelines. Each token acts as an account of its own, which is a member of the organization
+/// with the token's project permissions, so it keeps working when its creator leaves
+#[derive(Serialize, Deserialize)]
+pub struct OrganizationToken {
+    pub id: PatId,
+    pub name: String,
+    /// Only returned when the token is created
+    pub access_token: Option<String>,
+    pub scopes: Scopes,
+    pub permissions: ProjectPermissions,
+    /// The account the token acts as
+    pub user_id: UserId,
+    pub created_by: Option<UserId>,
+    pub created: DateTime<Utc>,
+    pub expires: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+impl OrganizationToken {
+    pub fn from(
+        data: crate::database::models::pat_item::PersonalAccessToken,
+        token: crate::database::models::organization_token_item::OrganizationToken,
+        permissions: ProjectPermissions,
+        include_token: bool,
+    ) -> Self {
+        Self {
+            id: data.id.into(),
+            name: data.name,
+            access_token: if include_token {
+                Some(data.access_token)
+            } else {
+                None
+            },
+            scopes: data.scopes,
+            permissions,
+            user_id: data.user_id.into(),
+            created_by: token.created_by.map(Into::into),
+            created: data.created,
+            expires: data.expires,
+            last_used: data.last_used,
+        }
+    }
+}
@@ -127,6 +127,37 @@ impl Scopes {
             | Scopes::PERFORM_ANALYTICS
     }
 
+    /// The scopes of impersonation sessions, which read what the user can see, except their
+    /// email, payouts and credentials
+    pub fn impersonation() -> Scopes {
+        Scopes::USER_READ
+            | Scopes::NOTIFICATION_READ
+            | Scopes::ANALYTICS
+            | Scopes::PROJECT_READ
+            | Scopes::VERSION_READ
+            | Scopes::REPORT_READ
+            | Scopes::THREAD_READ
+            | Scopes::COLLECTION_READ
+            | Scopes::ORGANIZATION_READ
+    }
+
+    /// The scopes organization tokens can be given, which only reach the organization's
+    /// projects
+    pub fn organization_token() -> Scopes {
+        Scopes::PROJECT_READ
+            | Scopes::PROJECT_WRITE
+            | Scopes::VERSION_CREATE
+            | Scopes::VERSION_READ
+            | Scopes::VERSION_WRITE
+            | Scopes::VERSION_DELETE
+            | Scopes::ORGANIZATION_READ
+    }
+
+    /// The scopes only verified OAuth apps can request, which read private data or move money
+    pub fn sensitive() -> Scopes {
+        Scopes::USER_READ_EMAIL | Scopes::PAYOUTS_READ | Scopes::PAYOUTS_WRITE | Scopes::ANALYTICS
+    }
+
     pub fn is_restricted(&self) -> bool {
         self.intersects(Self::restricted())
     }
@@ -239,3 +270,54 @@ mod test {
         );
     }
 }
+
+/// The requests made with a personal access token, so developers can see which of their tokens
+/// is using up their rate limit
+#[derive(Serialize, Deserialize)]
+pub struct PatUsage {
+    pub requests: i64,
+    pub errors: i64,
+    pub rate_limited: i64,
+    /// Requests by hour, oldest first. Hours the token was not used in are left out
+    pub hours: Vec<PatUsageHour>,
+    /// A sample of the latest requests which failed, latest first
+    pub recent_errors: Vec<PatError>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PatUsageHour {
+    pub hour: DateTime<Utc>,
+    pub requests: i64,
+    pub errors: i64,
+    pub rate_limited: i64,
+}
+
+impl From<crate::database::models::pat_usage_item::PatUsageHour> for PatUsageHour {
+    fn from(data: crate::database::models::pat_usage_item::PatUsageHour) -> Self {
+        Self {
+            hour: data.hour,
+            requests: data.requests,
+            errors: data.errors,
+            rate_limited: data.rate_limited,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PatError {
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub created: DateTime<Utc>,
+}
+
+impl From<crate::database::models::pat_usage_item::PatError> for PatError {
+    fn from(data: crate::database::models::pat_usage_item::PatError) -> Self {
+        Self {
+            method: data.method,
+            path: data.path,
+            status: data.status,
+            created: data.created,
+        }
+    }
+}
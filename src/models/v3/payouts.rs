@@ -1,4 +1,5 @@
-use crate::models::ids::{Base62Id, UserId};
+use crate::models::ids::{Base62Id, ProjectId, UserId};
+use crate::util::money::{Money, BASE_CURRENCY};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -19,10 +20,20 @@ pub struct Payout {
 
     #[serde(with = "rust_decimal::serde::float_option")]
     pub fee: Option<Decimal>,
+    /// Amount withheld for taxes, on top of the amount paid out
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub withholding: Option<Decimal>,
     pub method: Option<PayoutMethodType>,
     /// the address this payout was sent to: ex: email, paypal email, venmo handle
     pub method_address: Option<String>,
     pub platform_id: Option<String>,
+    /// Why the payout failed, if the provider reported a reason
+    pub failure_reason: Option<String>,
+    /// The amount received, if the payout was converted out of USD
+    pub destination_amount: Option<Money>,
+    /// How much one USD was worth in the destination currency
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub fx_rate: Option<Decimal>,
 }
 
 impl Payout {
@@ -34,9 +45,16 @@ impl Payout {
             created: data.created,
             amount: data.amount,
             fee: data.fee,
+            withholding: data.withholding,
             method: data.method,
             method_address: data.method_address,
             platform_id: data.platform_id,
+            failure_reason: data.failure_reason,
+            destination_amount: data
+                .currency
+                .zip(data.currency_amount)
+                .map(|(currency, amount)| Money { amount, currency }),
+            fx_rate: data.fx_rate,
         }
     }
 }
@@ -47,6 +65,7 @@ pub enum PayoutMethodType {
     Venmo,
     PayPal,
     Tremendous,
+    Stripe,
     Unknown,
 }
 
@@ -62,6 +81,7 @@ impl PayoutMethodType {
             PayoutMethodType::Venmo => "venmo",
             PayoutMethodType::PayPal => "paypal",
             PayoutMethodType::Tremendous => "tremendous",
+            PayoutMethodType::Stripe => "stripe",
             PayoutMethodType::Unknown => "unknown",
         }
     }
@@ -71,6 +91,7 @@ impl PayoutMethodType {
             "venmo" => PayoutMethodType::Venmo,
             "paypal" => PayoutMethodType::PayPal,
             "tremendous" => PayoutMethodType::Tremendous,
+            "stripe" => PayoutMethodType::Stripe,
             _ => PayoutMethodType::Unknown,
         }
     }
@@ -124,6 +145,9 @@ pub struct PayoutMethod {
     pub type_: PayoutMethodType,
     pub name: String,
     pub supported_countries: Vec<String>,
+    /// Currencies the method can pay out in. Amounts are always withdrawn in USD, and converted
+    /// at a quoted rate for other currencies
+    pub supported_currencies: Vec<String>,
     pub image_url: Option<String>,
     pub interval: PayoutInterval,
     pub fee: PayoutMethodFee,
@@ -139,6 +163,16 @@ pub struct PayoutMethodFee {
     pub max: Option<Decimal>,
 }
 
+impl PayoutMethodFee {
+    /// The fee charged for withdrawing `amount`
+    pub fn compute(&self, amount: Decimal) -> Decimal {
+        std::cmp::min(
+            std::cmp::max(self.min, self.percentage * amount),
+            self.max.unwrap_or(Decimal::MAX),
+        )
+    }
+}
+
 #[derive(Clone)]
 pub struct PayoutDecimal(pub Decimal);
 
@@ -174,3 +208,366 @@ pub enum PayoutInterval {
         values: Vec<PayoutDecimal>,
     },
 }
+
+/// A user's automatic monthly payout settings
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayoutSchedule {
+    pub method: PayoutMethodType,
+    pub method_id: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub threshold: Decimal,
+    pub created: DateTime<Utc>,
+    pub last_attempt: Option<DateTime<Utc>>,
+}
+
+impl From<crate::database::models::payout_schedule_item::PayoutSchedule> for PayoutSchedule {
+    fn from(data: crate::database::models::payout_schedule_item::PayoutSchedule) -> Self {
+        Self {
+            method: data.method,
+            method_id: data.method_id,
+            threshold: data.threshold,
+            created: data.created,
+            last_attempt: data.last_attempt,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxFormType {
+    /// US persons
+    W9,
+    /// Individuals outside of the US
+    W8Ben,
+    /// Entities outside of the US
+    W8BenE,
+    Unknown,
+}
+
+impl TaxFormType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaxFormType::W9 => "w9",
+            TaxFormType::W8Ben => "w8ben",
+            TaxFormType::W8BenE => "w8bene",
+            TaxFormType::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_string(string: &str) -> TaxFormType {
+        match string {
+            "w9" => TaxFormType::W9,
+            "w8ben" => TaxFormType::W8Ben,
+            "w8bene" => TaxFormType::W8BenE,
+            _ => TaxFormType::Unknown,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxFormStatus {
+    /// The form has been requested, but not filled in yet
+    Pending,
+    Complete,
+    /// The provider could not verify the form, and it must be filled in again
+    Rejected,
+    Unknown,
+}
+
+impl TaxFormStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaxFormStatus::Pending => "pending",
+            TaxFormStatus::Complete => "complete",
+            TaxFormStatus::Rejected => "rejected",
+            TaxFormStatus::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_string(string: &str) -> TaxFormStatus {
+        match string {
+            "pending" => TaxFormStatus::Pending,
+            "complete" => TaxFormStatus::Complete,
+            "rejected" => TaxFormStatus::Rejected,
+            _ => TaxFormStatus::Unknown,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TaxForm {
+    pub form_type: Option<TaxFormType>,
+    pub status: TaxFormStatus,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub withholding_rate: Decimal,
+    pub created: DateTime<Utc>,
+    pub completed: Option<DateTime<Utc>>,
+}
+
+impl From<crate::database::models::tax_form_item::TaxForm> for TaxForm {
+    fn from(data: crate::database::models::tax_form_item::TaxForm) -> Self {
+        Self {
+            form_type: data.form_type,
+            status: data.status,
+            withholding_rate: data.withholding_rate,
+            created: data.created,
+            completed: data.completed,
+        }
+    }
+}
+
+/// A user's payouts over a calendar year, for year-end tax reporting
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TaxYearSummary {
+    pub year: i32,
+    pub payouts: i64,
+    /// Everything taken from the balance: the amount paid out, fees and withholding
+    #[serde(with = "rust_decimal::serde::float")]
+    pub gross: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub fees: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub withheld: Decimal,
+}
+
+/// A user's balance, in USD and optionally converted into another currency
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayoutBalance {
+    pub available: Money,
+    pub converted: Option<Money>,
+    #[serde(with = "rust_decimal::serde::float_option")]
+    pub fx_rate: Option<Decimal>,
+}
+
+/// A withdrawal quoted in another currency, with the exchange rate locked until it expires
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayoutQuote {
+    pub id: String,
+    pub method: PayoutMethodType,
+    pub method_id: String,
+    /// Taken from the balance, in USD
+    #[serde(with = "rust_decimal::serde::float")]
+    pub amount: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub fee: Decimal,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub withholding: Decimal,
+    /// What is sent after fees and withholding, before conversion
+    pub transfer: Money,
+    /// What is received
+    pub destination: Money,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub fx_rate: Decimal,
+    pub expires: DateTime<Utc>,
+}
+
+impl PayoutQuote {
+    pub fn from(id: String, data: crate::database::models::payout_quote_item::PayoutQuote) -> Self {
+        Self {
+            id,
+            method: data.method,
+            method_id: data.method_id,
+            amount: data.amount,
+            fee: data.fee,
+            withholding: data.withholding,
+            transfer: Money::from_decimal(data.transfer, BASE_CURRENCY).unwrap_or(Money {
+                amount: 0,
+                currency: BASE_CURRENCY.to_string(),
+            }),
+            destination: Money {
+                amount: data.destination_amount,
+                currency: data.currency,
+            },
+            fx_rate: data.fx_rate,
+            expires: data.expires,
+        }
+    }
+}
+
+/// Why a user's balance was adjusted
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AdjustmentReason {
+    /// Ad revenue which was reversed by the ad network after being distributed
+    AdRevenueClawback,
+    /// Revenue earned through fraudulent downloads or views
+    FraudReversal,
+    /// A disputed payment which was charged back
+    Chargeback,
+    /// A correction of a mistake in the balance, in either direction
+    Correction,
+    Unknown,
+}
+
+impl std::fmt::Display for AdjustmentReason {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}", self.as_str())
+    }
+}
+
+impl AdjustmentReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdjustmentReason::AdRevenueClawback => "ad_revenue_clawback",
+            AdjustmentReason::FraudReversal => "fraud_reversal",
+            AdjustmentReason::Chargeback => "chargeback",
+            AdjustmentReason::Correction => "correction",
+            AdjustmentReason::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_string(string: &str) -> AdjustmentReason {
+        match string {
+            "ad_revenue_clawback" => AdjustmentReason::AdRevenueClawback,
+            "fraud_reversal" => AdjustmentReason::FraudReversal,
+            "chargeback" => AdjustmentReason::Chargeback,
+            "correction" => AdjustmentReason::Correction,
+            _ => AdjustmentReason::Unknown,
+        }
+    }
+
+    /// A human readable description, used in notifications and statements
+    pub fn description(&self) -> &'static str {
+        match self {
+            AdjustmentReason::AdRevenueClawback => "Ad revenue clawback",
+            AdjustmentReason::FraudReversal => "Reversal of fraudulent revenue",
+            AdjustmentReason::Chargeback => "Chargeback",
+            AdjustmentReason::Correction => "Balance correction",
+            AdjustmentReason::Unknown => "Balance adjustment",
+        }
+    }
+}
+
+/// A correction made to a user's balance. Negative amounts were taken from it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayoutAdjustment {
+    pub id: i64,
+    pub user_id: UserId,
+    pub project_id: Option<ProjectId>,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub amount: Decimal,
+    pub reason: AdjustmentReason,
+    pub notes: Option<String>,
+    pub created: DateTime<Utc>,
+}
+
+impl From<crate::database::models::payout_adjustment_item::PayoutAdjustment> for PayoutAdjustment {
+    fn from(data: crate::database::models::payout_adjustment_item::PayoutAdjustment) -> Self {
+        Self {
+            id: data.id,
+            user_id: data.user_id.into(),
+            project_id: data.project_id.map(|x| x.into()),
+            amount: data.amount,
+            reason: data.reason,
+            notes: data.notes,
+            created: data.created,
+        }
+    }
+}
+
+/// Where a user is in the monetization program. Users apply and are reviewed, after which they
+/// are either active or rejected. Active users can be suspended and reinstated
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EnrollmentStatus {
+    /// Applied and waiting for review
+    Pending,
+    /// Enrolled, and earning revenue
+    Active,
+    Rejected,
+    /// No longer earning revenue until reinstated
+    Suspended,
+    Unknown,
+}
+
+impl std::fmt::Display for EnrollmentStatus {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}", self.as_str())
+    }
+}
+
+impl EnrollmentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnrollmentStatus::Pending => "pending",
+            EnrollmentStatus::Active => "active",
+            EnrollmentStatus::Rejected => "rejected",
+            EnrollmentStatus::Suspended => "suspended",
+            EnrollmentStatus::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_string(string: &str) -> EnrollmentStatus {
+        match string {
+            "pending" => EnrollmentStatus::Pending,
+            "active" => EnrollmentStatus::Active,
+            "rejected" => EnrollmentStatus::Rejected,
+            "suspended" => EnrollmentStatus::Suspended,
+            _ => EnrollmentStatus::Unknown,
+        }
+    }
+
+    /// Whether a review can move an enrollment from this status to `status`
+    pub fn can_review_to(&self, status: EnrollmentStatus) -> bool {
+        matches!(
+            (self, status),
+            (EnrollmentStatus::Pending, EnrollmentStatus::Active)
+                | (EnrollmentStatus::Pending, EnrollmentStatus::Rejected)
+                | (EnrollmentStatus::Active, EnrollmentStatus::Suspended)
+                | (EnrollmentStatus::Suspended, EnrollmentStatus::Active)
+        )
+    }
+
+    /// Whether a user with an enrollment in this status can apply again
+    pub fn can_apply(&self) -> bool {
+        matches!(self, EnrollmentStatus::Rejected | EnrollmentStatus::Unknown)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MonetizationEnrollment {
+    pub user_id: UserId,
+    pub status: EnrollmentStatus,
+    pub applied: DateTime<Utc>,
+    pub reviewed: Option<DateTime<Utc>>,
+    pub review_notes: Option<String>,
+}
+
+impl From<crate::database::models::monetization_enrollment_item::MonetizationEnrollment>
+    for MonetizationEnrollment
+{
+    fn from(
+        data: crate::database::models::monetization_enrollment_item::MonetizationEnrollment,
+    ) -> Self {
+        Self {
+            user_id: data.user_id.into(),
+            status: data.status,
+            applied: data.applied,
+            reviewed: data.reviewed,
+            review_notes: data.review_notes,
+        }
+    }
+}
+
+/// Which of the requirements to join the monetization program a user meets
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MonetizationEligibility {
+    /// The account is older than `MONETIZATION_MIN_ACCOUNT_AGE_DAYS`
+    pub account_age: bool,
+    pub two_factor: bool,
+    /// The user has a complete tax form
+    pub tax_form: bool,
+}
+
+impl MonetizationEligibility {
+    pub fn is_eligible(&self) -> bool {
+        self.account_age && self.two_factor && self.tax_form
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EnrollmentInfo {
+    pub enrollment: Option<MonetizationEnrollment>,
+    pub eligibility: MonetizationEligibility,
+}
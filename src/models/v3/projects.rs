@@ -1,7 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
 use super::ids::base62_impl::parse_base62;
-use super::ids::{Base62Id, OrganizationId};
+use super::ids::{Base62Id, ImageId, OrganizationId};
 use super::teams::TeamId;
 use super::users::UserId;
 use crate::database::models::loader_fields::VersionField;
@@ -12,10 +12,11 @@ use crate::search::ResultSearchProject;
 use chrono::{DateTime, Utc};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use validator::Validate;
 
 /// The ID of a specific project, encoded as base62 for usage in the API
-#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, Debug)]
 #[serde(from = "Base62Id")]
 #[serde(into = "Base62Id")]
 pub struct ProjectId(pub u64);
@@ -27,9 +28,10 @@ pub struct ProjectId(pub u64);
 pub struct VersionId(pub u64);
 
 /// A project returned from the API
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct Project {
     /// The ID of the project, encoded as a base62 string.
+    #[schema(value_type = String)]
     pub id: ProjectId,
     /// The slug of a project, used for vanity URLs
     pub slug: Option<String>,
@@ -38,8 +40,10 @@ pub struct Project {
     /// The aggregated games of the versions of this project
     pub games: Vec<String>,
     /// The team of people that has ownership of this project.
+    #[schema(value_type = String)]
     pub team_id: TeamId,
     /// The optional organization of people that have ownership of this project.
+    #[schema(value_type = Option<String>)]
     pub organization: Option<OrganizationId>,
     /// The title or name of the project.
     pub name: String,
@@ -86,9 +90,15 @@ pub struct Project {
     pub loaders: Vec<String>,
 
     /// A list of ids for versions of the project.
+    #[schema(value_type = Vec<String>)]
     pub versions: Vec<VersionId>,
-    /// The URL of the icon of the project
+    /// The URL of the icon of the project. When the client's `Accept` header lists one of the
+    /// icon's renditions, this is the URL of that rendition
     pub icon_url: Option<String>,
+    /// The URLs of optimized copies of the icon, by content type. Animated icons instead only
+    /// have a still `image/png` copy of their first frame
+    #[serde(default)]
+    pub icon_renditions: HashMap<String, String>,
 
     /// A collection of links to the project's various pages.
     pub link_urls: HashMap<String, Link>,
@@ -100,6 +110,7 @@ pub struct Project {
     pub color: Option<u32>,
 
     /// The thread of the moderation messages of the project
+    #[schema(value_type = String)]
     pub thread_id: ThreadId,
 
     /// The monetization status of this project
@@ -107,6 +118,7 @@ pub struct Project {
 
     /// Aggregated loader-fields across its myriad of versions
     #[serde(flatten)]
+    #[schema(value_type = Object)]
     pub fields: HashMap<String, Vec<serde_json::Value>>,
 }
 
@@ -208,6 +220,7 @@ impl From<QueryProject> for Project {
             loaders: m.loaders,
             versions: data.versions.into_iter().map(|v| v.into()).collect(),
             icon_url: m.icon_url,
+            icon_renditions: m.icon_renditions,
             link_urls: data
                 .urls
                 .into_iter()
@@ -217,12 +230,14 @@ impl From<QueryProject> for Project {
                 .gallery_items
                 .into_iter()
                 .map(|x| GalleryItem {
+                    image_id: x.image_id.map(|x| x.into()),
                     url: x.image_url,
                     featured: x.featured,
                     name: x.name,
                     description: x.description,
                     created: x.created,
                     ordering: x.ordering,
+                    renditions: x.renditions,
                 })
                 .collect(),
             color: m.color,
@@ -290,8 +305,10 @@ impl Project {
         }
 
         // Additional categories
+        let ancestor_categories = &m.ancestor_categories;
         let mut additional_categories = m.categories.clone();
         additional_categories.retain(|c| !categories.contains(c));
+        additional_categories.retain(|c| !ancestor_categories.contains(c));
         additional_categories.retain(|c| !loaders.contains(c));
         if let Some(ref mrpack_loaders) = mrpack_loaders_strings {
             additional_categories.retain(|l| !mrpack_loaders.contains(l));
@@ -315,12 +332,14 @@ impl Project {
             .gallery_items
             .into_iter()
             .map(|x| GalleryItem {
+                image_id: x.image_id.map(|x| x.into()),
                 url: x.image_url,
                 featured: x.featured,
                 name: x.name,
                 description: x.description,
                 created: x.created,
                 ordering: x.ordering,
+                renditions: x.renditions,
             })
             .collect();
 
@@ -369,6 +388,7 @@ impl Project {
             loaders,
             versions,
             icon_url,
+            icon_renditions: HashMap::new(),
             link_urls,
             gallery,
             color: m.color,
@@ -382,17 +402,25 @@ impl Project {
         })
     }
 }
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct GalleryItem {
+    /// The id of the image, which thumbnails of it can be requested with
+    #[schema(value_type = Option<String>)]
+    pub image_id: Option<ImageId>,
+    /// The URL of the image. When the client's `Accept` header lists one of the image's
+    /// renditions, this is the URL of that rendition
     pub url: String,
     pub featured: bool,
     pub name: Option<String>,
     pub description: Option<String>,
     pub created: DateTime<Utc>,
     pub ordering: i64,
+    /// The URLs of optimized copies of the image, by content type
+    #[serde(default)]
+    pub renditions: HashMap<String, String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct ModeratorMessage {
     pub message: String,
     pub body: Option<String>,
@@ -400,14 +428,14 @@ pub struct ModeratorMessage {
 
 pub const DEFAULT_LICENSE_ID: &str = "LicenseRef-All-Rights-Reserved";
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct License {
     pub id: String,
     pub name: String,
     pub url: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Validate, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Validate, Clone, Eq, PartialEq, ToSchema)]
 pub struct Link {
     pub platform: String,
     pub donation: bool,
@@ -427,6 +455,31 @@ impl From<LinkUrl> for Link {
     }
 }
 
+/// A GitHub repository a project imports its releases from as versions
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GitHubLink {
+    /// The repository as `owner/name`
+    pub repository: String,
+    /// The loaders and version fields imported versions get
+    pub loaders: Vec<Loader>,
+    pub fields: HashMap<String, serde_json::Value>,
+    /// Whether new releases are imported as draft versions when they are published
+    pub sync: bool,
+    pub created: DateTime<Utc>,
+}
+
+impl From<crate::database::models::github_link_item::GitHubLink> for GitHubLink {
+    fn from(data: crate::database::models::github_link_item::GitHubLink) -> Self {
+        Self {
+            repository: data.repository,
+            loaders: data.loaders.into_iter().map(Loader).collect(),
+            fields: data.version_fields,
+            sync: data.sync,
+            created: data.created,
+        }
+    }
+}
+
 /// A status decides the visibility of a project in search, URLs, and the whole site itself.
 /// Approved - Project is displayed on search, and accessible by URL
 /// Rejected - Project is not displayed on search, and not accessible by URL (Temporary state, project can reapply)
@@ -436,7 +489,7 @@ impl From<LinkUrl> for Link {
 /// Processing - Project is not displayed on search, and not accessible by URL (Temporary state, project under review)
 /// Scheduled - Project is scheduled to be released in the future
 /// Private - Project is approved, but is not viewable to the public
-#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum ProjectStatus {
     Approved,
@@ -568,7 +621,7 @@ impl ProjectStatus {
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, ToSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum MonetizationStatus {
     ForceDemonetized,
@@ -602,13 +655,16 @@ impl MonetizationStatus {
 }
 
 /// A specific version of a project
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct Version {
     /// The ID of the version, encoded as a base62 string.
+    #[schema(value_type = String)]
     pub id: VersionId,
     /// The ID of the project this version is for.
+    #[schema(value_type = String)]
     pub project_id: ProjectId,
     /// The ID of the author who published this version
+    #[schema(value_type = String)]
     pub author_id: UserId,
     /// Whether the version is featured or not
     pub featured: bool,
@@ -648,6 +704,7 @@ pub struct Version {
     // These are flattened during serialization
     #[serde(deserialize_with = "skip_nulls")]
     #[serde(flatten)]
+    #[schema(value_type = Object)]
     pub fields: HashMap<String, serde_json::Value>,
 }
 
@@ -725,7 +782,7 @@ impl From<QueryVersion> for Version {
 /// Draft - Version is not displayed on project, and not accessible by URL
 /// Unlisted - Version is not displayed on project, and accessible by URL
 /// Scheduled - Version is scheduled to be released in the future
-#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum VersionStatus {
     Listed,
@@ -809,7 +866,7 @@ impl VersionStatus {
 }
 
 /// A single project file, with a url for the file and the file's hash
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct VersionFile {
     /// A map of hashes of the file.  The key is the hashing algorithm
     /// and the value is the string version of the hash.
@@ -828,11 +885,13 @@ pub struct VersionFile {
 
 /// A dendency which describes what versions are required, break support, or are optional to the
 /// version's functionality
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
 pub struct Dependency {
     /// The specific version id that the dependency uses
+    #[schema(value_type = Option<String>)]
     pub version_id: Option<VersionId>,
     /// The project ID that the dependency is synced with and auto-updated
+    #[schema(value_type = Option<String>)]
     pub project_id: Option<ProjectId>,
     /// The filename of the dependency. Used exclusively for external mods on modpacks
     pub file_name: Option<String>,
@@ -840,7 +899,7 @@ pub struct Dependency {
     pub dependency_type: DependencyType,
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum VersionType {
     Release,
@@ -865,7 +924,7 @@ impl VersionType {
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum DependencyType {
     Required,
@@ -902,7 +961,7 @@ impl DependencyType {
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, ToSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum FileType {
     RequiredResourcePack,
@@ -937,7 +996,7 @@ impl FileType {
 }
 
 /// A project loader
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
 #[serde(transparent)]
 pub struct Loader(pub String);
 
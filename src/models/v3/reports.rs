@@ -20,6 +20,52 @@ pub struct Report {
     pub created: DateTime<Utc>,
     pub closed: bool,
     pub thread_id: ThreadId,
+    /// How the report was resolved, once it is closed
+    pub outcome: Option<ReportOutcome>,
+    /// Whether moderators asked the reporter for more information and are waiting on a reply
+    pub awaiting_reporter: bool,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportOutcome {
+    /// The reported content was moderated
+    ActionTaken,
+    NoViolation,
+    /// The issue was already reported
+    Duplicate,
+    /// The report could not be verified with the information given
+    InsufficientEvidence,
+}
+
+impl ReportOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportOutcome::ActionTaken => "action_taken",
+            ReportOutcome::NoViolation => "no_violation",
+            ReportOutcome::Duplicate => "duplicate",
+            ReportOutcome::InsufficientEvidence => "insufficient_evidence",
+        }
+    }
+
+    pub fn from_string(string: &str) -> Option<ReportOutcome> {
+        match string {
+            "action_taken" => Some(ReportOutcome::ActionTaken),
+            "no_violation" => Some(ReportOutcome::NoViolation),
+            "duplicate" => Some(ReportOutcome::Duplicate),
+            "insufficient_evidence" => Some(ReportOutcome::InsufficientEvidence),
+            _ => None,
+        }
+    }
+
+    pub fn as_friendly_str(&self) -> &'static str {
+        match self {
+            ReportOutcome::ActionTaken => "Action was taken",
+            ReportOutcome::NoViolation => "No rules were broken",
+            ReportOutcome::Duplicate => "The issue was already reported",
+            ReportOutcome::InsufficientEvidence => "There was not enough evidence",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -68,6 +114,8 @@ impl From<DBReport> for Report {
             created: x.created,
             closed: x.closed,
             thread_id: x.thread_id.into(),
+            outcome: x.outcome,
+            awaiting_reporter: x.awaiting_reporter,
         }
     }
 }
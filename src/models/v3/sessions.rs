@@ -58,3 +58,53 @@ impl Session {
         }
     }
 }
+
+/// A read-only session an admin opened as a user, to reproduce an issue the user reported
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Impersonation {
+    pub id: i64,
+    pub admin_id: UserId,
+    pub user_id: UserId,
+    pub reason: String,
+    /// The token of the session, only returned when it is opened
+    pub session: Option<String>,
+    pub created: DateTime<Utc>,
+    pub expires: DateTime<Utc>,
+    pub revoked: Option<DateTime<Utc>>,
+    pub requests: Vec<ImpersonationRequest>,
+}
+
+/// A request made with an impersonation session
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ImpersonationRequest {
+    pub method: String,
+    pub path: String,
+    pub created: DateTime<Utc>,
+}
+
+impl Impersonation {
+    pub fn from(
+        data: crate::database::models::impersonation_item::ImpersonationSession,
+        session: Option<String>,
+        requests: Vec<crate::database::models::impersonation_item::ImpersonationRequest>,
+    ) -> Self {
+        Impersonation {
+            id: data.id,
+            admin_id: data.admin_id.into(),
+            user_id: data.user_id.into(),
+            reason: data.reason,
+            session,
+            created: data.created,
+            expires: data.expires,
+            revoked: data.revoked,
+            requests: requests
+                .into_iter()
+                .map(|x| ImpersonationRequest {
+                    method: x.method,
+                    path: x.path,
+                    created: x.created,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use super::ids::{ProjectId, VersionId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A short link to a project or one of its versions, made by its team
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Shortlink {
+    pub code: String,
+    /// The short URL to share, which redirects to the project or version
+    pub url: String,
+    pub project_id: ProjectId,
+    /// The version linked to. Absent for links to the project itself
+    pub version_id: Option<VersionId>,
+    /// How many times the link was followed
+    pub clicks: i64,
+    pub created: DateTime<Utc>,
+}
+
+impl From<crate::database::models::shortlink_item::Shortlink> for Shortlink {
+    fn from(data: crate::database::models::shortlink_item::Shortlink) -> Self {
+        Self {
+            url: format!(
+                "{}/{}",
+                dotenvy::var("SHORTLINK_URL").unwrap_or_default(),
+                data.code
+            ),
+            code: data.code,
+            project_id: data.project_id.into(),
+            version_id: data.version_id.map(Into::into),
+            clicks: data.clicks,
+            created: data.created,
+        }
+    }
+}
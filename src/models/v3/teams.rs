@@ -3,6 +3,7 @@ use crate::bitflags_serde_impl;
 use crate::models::users::User;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 /// The ID of a team
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -127,9 +128,10 @@ impl OrganizationPermissions {
 }
 
 /// A member of a team
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct TeamMember {
     /// The ID of the team this team member is a member of
+    #[schema(value_type = String)]
     pub team_id: TeamId,
     /// The user associated with the member
     pub user: User,
@@ -141,10 +143,12 @@ pub struct TeamMember {
     /// In an organization-controlled project, these are the unique overriding permissions for the user's role for any project in the organization, if they exist.
     /// In an organization, these are the default project permissions for any project in the organization.
     /// Not optional- only None if they are being hidden from the user.
+    #[schema(value_type = Option<u64>)]
     pub permissions: Option<ProjectPermissions>,
 
     /// A bitset containing the user's permissions in this organization.
     /// In a project team, this is None.
+    #[schema(value_type = Option<u64>)]
     pub organization_permissions: Option<OrganizationPermissions>,
 
     /// Whether the user has joined the team or is just invited to it
@@ -153,6 +157,7 @@ pub struct TeamMember {
     #[serde(with = "rust_decimal::serde::float_option")]
     /// Payouts split. This is a weighted average. For example. if a team has two members with this
     /// value set to 25.0 for both members, they split revenue 50/50
+    #[schema(value_type = Option<f64>)]
     pub payouts_split: Option<Decimal>,
     /// Ordering of the member in the list
     pub ordering: i64,
@@ -24,6 +24,10 @@ pub struct Thread {
     pub report_id: Option<ReportId>,
     pub messages: Vec<ThreadMessage>,
     pub members: Vec<User>,
+    /// Whether the current user is notified of new messages in the thread
+    pub subscribed: bool,
+    /// Whether the current user muted the thread
+    pub muted: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -32,6 +36,25 @@ pub struct ThreadMessage {
     pub author_id: Option<UserId>,
     pub body: MessageBody,
     pub created: DateTime<Utc>,
+    /// When the message was last edited by its author, if ever
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+/// A body a thread message had before it was edited
+#[derive(Serialize, Deserialize)]
+pub struct ThreadMessageRevision {
+    pub body: MessageBody,
+    /// When this body was replaced by a newer one
+    pub created: DateTime<Utc>,
+}
+
+impl From<crate::database::models::thread_item::ThreadMessageRevision> for ThreadMessageRevision {
+    fn from(data: crate::database::models::thread_item::ThreadMessageRevision) -> Self {
+        ThreadMessageRevision {
+            body: data.body,
+            created: data.created,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -94,6 +117,8 @@ impl ThreadType {
 impl Thread {
     pub fn from(data: crate::database::models::Thread, users: Vec<User>, user: &User) -> Self {
         let thread_type = data.type_;
+        let subscribed = data.is_subscribed(user.id.into());
+        let muted = data.muted.contains(&user.id.into());
 
         Thread {
             id: data.id.into(),
@@ -126,12 +151,15 @@ impl Thread {
                     },
                     body: x.body,
                     created: x.created,
+                    edited_at: x.edited_at,
                 })
                 .collect(),
             members: users
                 .into_iter()
                 .filter(|x| !x.role.is_mod() || user.role.is_mod())
                 .collect(),
+            subscribed,
+            muted,
         }
     }
 }
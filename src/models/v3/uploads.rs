@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Where a file is in being processed after it was uploaded. Files are received, scanned for
+/// their content and duplicates, and validated for the loaders they are for. Once every file
+/// has been processed the upload is published
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadStatus {
+    Received,
+    Scanned,
+    Validated,
+    /// The whole upload was saved, and the project or version can be seen
+    Published,
+    /// The whole upload was refused, and nothing was saved
+    Failed,
+}
+
+impl UploadStatus {
+    /// Whether no more events follow this one
+    pub fn is_final(&self) -> bool {
+        matches!(self, UploadStatus::Published | UploadStatus::Failed)
+    }
+}
+
+/// An event sent as an upload is processed
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UploadProgress {
+    pub upload_id: String,
+    /// The file the event is for, or none if it is for the whole upload
+    pub file_name: Option<String>,
+    pub status: UploadStatus,
+    /// Why the upload failed
+    pub error: Option<String>,
+    pub created: DateTime<Utc>,
+}
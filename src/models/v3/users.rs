@@ -1,8 +1,10 @@
-use super::ids::Base62Id;
+use super::collections::CollectionId;
+use super::ids::{Base62Id, ProjectId};
+use crate::util::money::Money;
 use crate::{auth::AuthProvider, bitflags_serde_impl};
 use chrono::{DateTime, Utc};
-use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Debug, Hash)]
 #[serde(from = "Base62Id")]
@@ -36,8 +38,9 @@ impl Default for Badges {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct User {
+    #[schema(value_type = String)]
     pub id: UserId,
     pub username: String,
     pub name: Option<String>,
@@ -45,26 +48,63 @@ pub struct User {
     pub bio: Option<String>,
     pub created: DateTime<Utc>,
     pub role: Role,
+    #[schema(value_type = u64)]
     pub badges: Badges,
 
+    #[schema(value_type = Option<Vec<String>>)]
     pub auth_providers: Option<Vec<AuthProvider>>,
     pub email: Option<String>,
     pub email_verified: Option<bool>,
+    /// Whether email to the user's address bounced or was marked as spam, in which case
+    /// clients should ask the user to change it
+    pub email_undeliverable: Option<bool>,
     pub has_password: Option<bool>,
     pub has_totp: Option<bool>,
     pub payout_data: Option<UserPayoutData>,
+    /// The user's profile sections. Only returned when getting a single user
+    #[serde(default)]
+    pub profile: Option<UserProfile>,
 
     // DEPRECATED. Always returns None
     pub github_id: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// The sections of a user's profile page
+#[derive(Serialize, Deserialize, Clone, Debug, Default, ToSchema)]
+pub struct UserProfile {
+    /// Sanitized Markdown about the user
+    pub about: Option<String>,
+    /// Projects of the user shown first on their profile, in order. Projects which are not
+    /// public are left out
+    #[schema(value_type = Vec<String>)]
+    pub pinned_projects: Vec<ProjectId>,
+    /// A public collection of the user shown on their profile
+    #[schema(value_type = Option<String>)]
+    pub featured_collection: Option<CollectionId>,
+    pub links: Vec<ProfileLink>,
+}
+
+/// A link on a user's profile. Links are verified once the page they go to links back to the
+/// profile
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct ProfileLink {
+    pub label: String,
+    pub url: String,
+    /// When the link was verified, if it has been
+    pub verified: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct UserPayoutData {
     pub paypal_address: Option<String>,
     pub paypal_country: Option<String>,
     pub venmo_handle: Option<String>,
-    #[serde(with = "rust_decimal::serde::float")]
-    pub balance: Decimal,
+    /// Whether the user has finished onboarding their Stripe account and can be paid out to it
+    #[serde(default)]
+    pub stripe_payouts_enabled: bool,
+    /// The balance in the minor unit of its currency, such as cents. Fractions of the minor unit
+    /// are left out
+    pub balance: Money,
 }
 
 use crate::database::models::user_item::User as DBUser;
@@ -76,6 +116,7 @@ impl From<DBUser> for User {
             name: data.name,
             email: None,
             email_verified: None,
+            email_undeliverable: None,
             avatar_url: data.avatar_url,
             bio: data.bio,
             created: data.created,
@@ -85,12 +126,13 @@ impl From<DBUser> for User {
             auth_providers: None,
             has_password: None,
             has_totp: None,
+            profile: None,
             github_id: None,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
     Developer,
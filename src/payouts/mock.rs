@@ -0,0 +1,146 @@
+use super::{PayoutEvent, PayoutProvider, Recipient, Transfer};
+use crate::database::models::User;
+use crate::database::redis::RedisPool;
+use crate::models::payouts::{
+    PayoutInterval, PayoutMethod, PayoutMethodFee, PayoutMethodType, PayoutStatus,
+};
+use crate::routes::ApiError;
+use crate::util::money::BASE_CURRENCY;
+use actix_web::http::header::HeaderMap;
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::Mutex;
+
+/// A transfer made through the mock provider
+#[derive(Debug, Clone)]
+pub struct MockTransfer {
+    pub platform_id: String,
+    pub recipient: String,
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+/// Pays out without contacting anyone, recording the transfers made so tests can check them.
+/// Webhooks are accepted unsigned, as `{"platform_id": "...", "status": "success"}`, and
+/// identified by their body
+pub struct MockProvider {
+    method_types: Vec<PayoutMethodType>,
+    transfers: Mutex<Vec<MockTransfer>>,
+}
+
+impl MockProvider {
+    /// Creates a provider which pays out through a method type, taking it over from the
+    /// provider which normally handles it
+    pub fn new(method_type: PayoutMethodType) -> Self {
+        MockProvider {
+            method_types: vec![method_type],
+            transfers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn transfers(&self) -> Vec<MockTransfer> {
+        self.transfers.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl PayoutProvider for MockProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn method_types(&self) -> &[PayoutMethodType] {
+        &self.method_types
+    }
+
+    async fn payout_methods(&self) -> Result<Vec<PayoutMethod>, ApiError> {
+        Ok(self
+            .method_types
+            .iter()
+            .map(|method_type| PayoutMethod {
+                id: format!("mock_{}", method_type.as_str()),
+                type_: *method_type,
+                name: "Mock".to_string(),
+                supported_countries: rust_iso3166::ALL
+                    .iter()
+                    .map(|x| x.alpha2.to_string())
+                    .collect(),
+                supported_currencies: vec![BASE_CURRENCY.to_string()],
+                image_url: None,
+                interval: PayoutInterval::Standard {
+                    min: Decimal::ZERO,
+                    max: Decimal::from(100_000),
+                },
+                fee: PayoutMethodFee {
+                    percentage: Decimal::ZERO,
+                    min: Decimal::ZERO,
+                    max: None,
+                },
+            })
+            .collect())
+    }
+
+    async fn recipient(&self, user: &User, _method: &PayoutMethod) -> Result<Recipient, ApiError> {
+        Ok(Recipient {
+            address: user.username.clone(),
+            display_address: user.username.clone(),
+        })
+    }
+
+    async fn execute_transfer(&self, transfer: &Transfer<'_>) -> Result<Option<String>, ApiError> {
+        let platform_id = format!("mock-{}", transfer.payout_id);
+
+        self.transfers.lock().unwrap().push(MockTransfer {
+            platform_id: platform_id.clone(),
+            recipient: transfer.recipient.address.clone(),
+            amount: transfer.amount,
+            currency: transfer.currency.to_string(),
+        });
+
+        Ok(Some(platform_id))
+    }
+
+    async fn poll_status(&self, platform_id: &str) -> Result<Option<PayoutEvent>, ApiError> {
+        Ok(Some(PayoutEvent::Succeeded {
+            platform_id: platform_id.to_string(),
+        }))
+    }
+
+    async fn cancel_transfer(&self, _platform_id: &str) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    async fn verify_webhook(&self, _headers: &HeaderMap, body: &str) -> Result<String, ApiError> {
+        Ok(hex::encode(Sha256::digest(body.as_bytes())))
+    }
+
+    async fn handle_webhook(
+        &self,
+        body: &str,
+        _pool: &PgPool,
+        _redis: &RedisPool,
+    ) -> Result<Vec<PayoutEvent>, ApiError> {
+        #[derive(Deserialize)]
+        struct MockWebhook {
+            pub platform_id: String,
+            pub status: PayoutStatus,
+        }
+
+        let webhook = serde_json::from_str::<MockWebhook>(body)?;
+
+        Ok(match webhook.status {
+            PayoutStatus::Success => vec![PayoutEvent::Succeeded {
+                platform_id: webhook.platform_id,
+            }],
+            PayoutStatus::Failed | PayoutStatus::Cancelled => vec![PayoutEvent::Failed {
+                platform_id: webhook.platform_id,
+                status: webhook.status,
+                reason: None,
+            }],
+            _ => vec![],
+        })
+    }
+}
@@ -0,0 +1,157 @@
+use crate::database::models::User;
+use crate::database::redis::RedisPool;
+use crate::models::ids::PayoutId;
+use crate::models::payouts::{PayoutMethod, PayoutMethodType, PayoutStatus};
+use crate::routes::ApiError;
+use actix_web::http::header::HeaderMap;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+
+#[cfg(any(test, feature = "mock-payouts"))]
+mod mock;
+mod paypal;
+mod stripe;
+mod tremendous;
+
+#[cfg(any(test, feature = "mock-payouts"))]
+pub use mock::MockProvider;
+pub use paypal::PayPalProvider;
+pub use stripe::{StripeProvider, STRIPE_COUNTRIES};
+pub use tremendous::TremendousProvider;
+
+/// Where a provider sends a user's payouts
+#[derive(Debug, Clone)]
+pub struct Recipient {
+    /// The provider's identifier for the recipient, such as a PayPal ID or a Stripe account
+    pub address: String,
+    /// The address shown to the user in their payout history
+    pub display_address: String,
+}
+
+#[derive(Clone)]
+pub struct Transfer<'a> {
+    pub payout_id: PayoutId,
+    pub method: &'a PayoutMethod,
+    pub recipient: &'a Recipient,
+    /// The name of the user being paid
+    pub recipient_name: &'a str,
+    /// The amount to send, in `currency`
+    pub amount: Decimal,
+    pub currency: &'a str,
+}
+
+/// A change in the status of payouts, reported by a provider's webhook or when polled
+#[derive(Debug, Clone)]
+pub enum PayoutEvent {
+    Succeeded {
+        platform_id: String,
+    },
+    /// The transfer failed or was cancelled before completing. The payout is refunded if it is
+    /// still in transit
+    Failed {
+        platform_id: String,
+        status: PayoutStatus,
+        reason: Option<String>,
+    },
+    /// A completed transfer was taken back by the provider. The payout is refunded whether it
+    /// is in transit or has succeeded
+    Reversed {
+        platform_id: String,
+        reason: String,
+    },
+    /// Every in-transit payout to the recipient made before `before` reached the user
+    RecipientSettled {
+        method: PayoutMethodType,
+        recipient: String,
+        before: DateTime<Utc>,
+    },
+    /// The provider could not pass the recipient's funds on to the user. The payouts stay in
+    /// transit, as the funds are kept until the user fixes their details
+    RecipientSettlementFailed {
+        method: PayoutMethodType,
+        recipient: String,
+        before: DateTime<Utc>,
+        reason: String,
+    },
+}
+
+/// An integration which sends payouts to users. Providers only talk to their platform, while
+/// `PayoutsQueue` handles balances, locking and recording payouts
+#[async_trait]
+pub trait PayoutProvider: Send + Sync {
+    /// Used in the provider's webhook URL, `/payout/_{name}`
+    fn name(&self) -> &'static str;
+
+    /// The method types the provider pays out through
+    fn method_types(&self) -> &[PayoutMethodType];
+
+    async fn payout_methods(&self) -> Result<Vec<PayoutMethod>, ApiError>;
+
+    /// The fee charged for withdrawing `amount` through one of the provider's methods
+    fn quote_fee(&self, method: &PayoutMethod, amount: Decimal) -> Decimal {
+        method.fee.compute(amount)
+    }
+
+    /// Sets the user up to receive payouts from the provider, returning a URL where the user
+    /// finishes setting up
+    async fn create_recipient(
+        &self,
+        _user: &User,
+        _country: &str,
+        _pool: &PgPool,
+        _redis: &RedisPool,
+    ) -> Result<String, ApiError> {
+        Err(ApiError::InvalidInput(
+            "This payment method does not need to be set up!".to_string(),
+        ))
+    }
+
+    /// Gets where to send the user's payouts through a method, checking that they can receive
+    /// them
+    async fn recipient(&self, user: &User, method: &PayoutMethod) -> Result<Recipient, ApiError>;
+
+    /// Sends a transfer, returning the provider's ID for it if it has one
+    async fn execute_transfer(&self, transfer: &Transfer<'_>) -> Result<Option<String>, ApiError>;
+
+    /// Gets the status of a transfer, `None` if it has not changed from in transit
+    async fn poll_status(&self, platform_id: &str) -> Result<Option<PayoutEvent>, ApiError>;
+
+    /// Cancels a transfer which has not completed yet
+    async fn cancel_transfer(&self, _platform_id: &str) -> Result<(), ApiError> {
+        Err(ApiError::InvalidInput(
+            "Payout cannot be cancelled!".to_string(),
+        ))
+    }
+
+    /// Verifies a webhook sent by the provider, returning the provider's ID for the delivery,
+    /// which stays the same when the delivery is retried
+    async fn verify_webhook(&self, headers: &HeaderMap, body: &str) -> Result<String, ApiError>;
+
+    /// Reads the payout events from a verified webhook. Events about anything other than
+    /// payouts are handled by the provider itself
+    async fn handle_webhook(
+        &self,
+        body: &str,
+        pool: &PgPool,
+        redis: &RedisPool,
+    ) -> Result<Vec<PayoutEvent>, ApiError>;
+}
+
+/// Gets the ID of a webhook's event from a top level field of its body
+fn event_id(body: &str, field: &str) -> Result<String, ApiError> {
+    serde_json::from_str::<serde_json::Value>(body)?
+        .get(field)
+        .and_then(|x| x.as_str())
+        .map(|x| x.to_string())
+        .ok_or_else(|| ApiError::InvalidInput(format!("missing webhook {field}")))
+}
+
+/// Gets a header a webhook must be sent with
+fn required_header<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str, ApiError> {
+    headers
+        .get(name)
+        .and_then(|x| x.to_str().ok())
+        .ok_or_else(|| ApiError::InvalidInput(format!("missing {name} header")))
+}
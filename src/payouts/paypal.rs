@@ -0,0 +1,504 @@
+use super::{event_id, required_header, PayoutEvent, PayoutProvider, Recipient, Transfer};
+use crate::database::models::User;
+use crate::database::redis::RedisPool;
+use crate::models::payouts::{
+    PayoutInterval, PayoutMethod, PayoutMethodFee, PayoutMethodType, PayoutStatus,
+};
+use crate::routes::ApiError;
+use crate::util::circuit_breaker::breaker;
+use crate::util::money::BASE_CURRENCY;
+use actix_web::http::header::HeaderMap;
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Method;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+/// Currencies PayPal can send international payouts in
+const PAYPAL_CURRENCIES: &[&str] = &[
+    "USD", "EUR", "GBP", "CAD", "AUD", "NZD", "CHF", "SEK", "NOK", "DKK", "PLN", "CZK", "HUF",
+    "BRL", "MXN", "PHP", "SGD", "HKD", "JPY",
+];
+
+/// Pays out to PayPal and Venmo accounts through PayPal Payouts
+pub struct PayPalProvider {
+    credential: RwLock<Option<PayPalCredentials>>,
+}
+
+#[derive(Clone)]
+struct PayPalCredentials {
+    access_token: String,
+    token_type: String,
+    expires: DateTime<Utc>,
+}
+
+impl Default for PayPalProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PayPalProvider {
+    pub fn new() -> Self {
+        PayPalProvider {
+            credential: RwLock::new(None),
+        }
+    }
+
+    async fn refresh_token(&self) -> Result<PayPalCredentials, ApiError> {
+        let mut creds = self.credential.write().await;
+        let client = reqwest::Client::new();
+
+        let combined_key = format!(
+            "{}:{}",
+            dotenvy::var("PAYPAL_CLIENT_ID")?,
+            dotenvy::var("PAYPAL_CLIENT_SECRET")?
+        );
+        let formatted_key = format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(combined_key)
+        );
+
+        let mut form = HashMap::new();
+        form.insert("grant_type", "client_credentials");
+
+        #[derive(Deserialize)]
+        struct PaypalCredential {
+            access_token: String,
+            token_type: String,
+            expires_in: i64,
+        }
+
+        let credential: PaypalCredential = client
+            .post(&format!("{}oauth2/token", dotenvy::var("PAYPAL_API_URL")?))
+            .header("Accept", "application/json")
+            .header("Accept-Language", "en_US")
+            .header("Authorization", formatted_key)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|_| ApiError::Payments("Error while authenticating with PayPal".to_string()))?
+            .json()
+            .await
+            .map_err(|_| {
+                ApiError::Payments(
+                    "Error while authenticating with PayPal (deser error)".to_string(),
+                )
+            })?;
+
+        let new_creds = PayPalCredentials {
+            access_token: credential.access_token,
+            token_type: credential.token_type,
+            expires: Utc::now() + Duration::seconds(credential.expires_in),
+        };
+
+        *creds = Some(new_creds.clone());
+
+        Ok(new_creds)
+    }
+
+    pub async fn make_paypal_request<T: Serialize, X: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<T>,
+        raw_text: Option<String>,
+        no_api_prefix: Option<bool>,
+    ) -> Result<X, ApiError> {
+        let read = self.credential.read().await;
+        let credentials = if let Some(credentials) = read.as_ref() {
+            if credentials.expires < Utc::now() {
+                drop(read);
+                self.refresh_token().await.map_err(|_| {
+                    ApiError::Payments("Error while authenticating with PayPal".to_string())
+                })?
+            } else {
+                credentials.clone()
+            }
+        } else {
+            drop(read);
+            self.refresh_token().await.map_err(|_| {
+                ApiError::Payments("Error while authenticating with PayPal".to_string())
+            })?
+        };
+
+        let client = reqwest::Client::new();
+        let mut request = client
+            .request(
+                method,
+                if no_api_prefix.unwrap_or(false) {
+                    path.to_string()
+                } else {
+                    format!("{}{path}", dotenvy::var("PAYPAL_API_URL")?)
+                },
+            )
+            .header(
+                "Authorization",
+                format!("{} {}", credentials.token_type, credentials.access_token),
+            );
+
+        if let Some(body) = body {
+            request = request.json(&body);
+        } else if let Some(body) = raw_text {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body);
+        }
+
+        let breaker = breaker("paypal");
+        breaker.check()?;
+
+        let resp = request.send().await.map_err(|_| {
+            breaker.record_failure();
+            ApiError::Payments("could not communicate with PayPal".to_string())
+        })?;
+
+        let status = resp.status();
+        if status.is_server_error() {
+            breaker.record_failure();
+        } else {
+            breaker.record_success();
+        }
+
+        let value = resp.json::<Value>().await.map_err(|_| {
+            ApiError::Payments("could not retrieve PayPal response body".to_string())
+        })?;
+
+        if !status.is_success() {
+            #[derive(Deserialize)]
+            struct PayPalError {
+                pub name: String,
+                pub message: String,
+            }
+
+            #[derive(Deserialize)]
+            struct PayPalIdentityError {
+                pub error: String,
+                pub error_description: String,
+            }
+
+            if let Ok(error) = serde_json::from_value::<PayPalError>(value.clone()) {
+                return Err(ApiError::Payments(format!(
+                    "error name: {}, message: {}",
+                    error.name, error.message
+                )));
+            }
+
+            if let Ok(error) = serde_json::from_value::<PayPalIdentityError>(value) {
+                return Err(ApiError::Payments(format!(
+                    "error name: {}, message: {}",
+                    error.error, error.error_description
+                )));
+            }
+
+            return Err(ApiError::Payments(
+                "could not retrieve PayPal error body".to_string(),
+            ));
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// Maps the status of a payout item to the event it causes, `None` while it is in transit
+fn status_event(platform_id: String, status: &str) -> Option<PayoutEvent> {
+    match status {
+        "SUCCESS" | "SUCCEEDED" => Some(PayoutEvent::Succeeded { platform_id }),
+        "BLOCKED" | "DENIED" | "FAILED" | "REFUNDED" | "RETURNED" | "REVERSED" => {
+            Some(PayoutEvent::Failed {
+                platform_id,
+                status: PayoutStatus::Failed,
+                reason: None,
+            })
+        }
+        "CANCELED" => Some(PayoutEvent::Failed {
+            platform_id,
+            status: PayoutStatus::Cancelled,
+            reason: None,
+        }),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl PayoutProvider for PayPalProvider {
+    fn name(&self) -> &'static str {
+        "paypal"
+    }
+
+    fn method_types(&self) -> &[PayoutMethodType] {
+        &[PayoutMethodType::PayPal, PayoutMethodType::Venmo]
+    }
+
+    async fn payout_methods(&self) -> Result<Vec<PayoutMethod>, ApiError> {
+        let paypal_us = PayoutMethod {
+            id: "paypal_us".to_string(),
+            type_: PayoutMethodType::PayPal,
+            name: "PayPal".to_string(),
+            supported_countries: vec!["US".to_string()],
+            supported_currencies: vec![BASE_CURRENCY.to_string()],
+            image_url: None,
+            interval: PayoutInterval::Standard {
+                min: Decimal::from(1) / Decimal::from(4),
+                max: Decimal::from(100_000),
+            },
+            fee: PayoutMethodFee {
+                percentage: Decimal::from(2) / Decimal::from(100),
+                min: Decimal::from(1) / Decimal::from(4),
+                max: Some(Decimal::from(1)),
+            },
+        };
+
+        let mut venmo = paypal_us.clone();
+        venmo.id = "venmo".to_string();
+        venmo.name = "Venmo".to_string();
+        venmo.type_ = PayoutMethodType::Venmo;
+
+        let paypal_in = PayoutMethod {
+            id: "paypal_in".to_string(),
+            type_: PayoutMethodType::PayPal,
+            name: "PayPal".to_string(),
+            supported_countries: rust_iso3166::ALL
+                .iter()
+                .filter(|x| x.alpha2 != "US")
+                .map(|x| x.alpha2.to_string())
+                .collect(),
+            supported_currencies: PAYPAL_CURRENCIES.iter().map(|x| x.to_string()).collect(),
+            image_url: None,
+            interval: PayoutInterval::Standard {
+                min: Decimal::from(1) / Decimal::from(4),
+                max: Decimal::from(100_000),
+            },
+            fee: PayoutMethodFee {
+                percentage: Decimal::from(2) / Decimal::from(100),
+                min: Decimal::ZERO,
+                max: Some(Decimal::from(20)),
+            },
+        };
+
+        Ok(vec![paypal_us, venmo, paypal_in])
+    }
+
+    async fn recipient(&self, user: &User, method: &PayoutMethod) -> Result<Recipient, ApiError> {
+        if method.type_ == PayoutMethodType::Venmo {
+            return if let Some(venmo) = &user.venmo_handle {
+                Ok(Recipient {
+                    address: venmo.clone(),
+                    display_address: venmo.clone(),
+                })
+            } else {
+                Err(ApiError::InvalidInput(
+                    "Venmo address has not been set for account!".to_string(),
+                ))
+            };
+        }
+
+        let Some(paypal_id) = &user.paypal_id else {
+            return Err(ApiError::InvalidInput(
+                "You have not linked a PayPal account!".to_string(),
+            ));
+        };
+        let Some(paypal_country) = &user.paypal_country else {
+            return Err(ApiError::InvalidInput(
+                "Please re-link your PayPal account!".to_string(),
+            ));
+        };
+
+        if &**paypal_country == "US" && &*method.id != "paypal_us" {
+            return Err(ApiError::InvalidInput(
+                "Please use the US PayPal transfer option!".to_string(),
+            ));
+        } else if &**paypal_country != "US" && &*method.id == "paypal_us" {
+            return Err(ApiError::InvalidInput(
+                "Please use the International PayPal transfer option!".to_string(),
+            ));
+        }
+
+        Ok(Recipient {
+            address: paypal_id.clone(),
+            display_address: user
+                .paypal_email
+                .clone()
+                .unwrap_or_else(|| paypal_id.clone()),
+        })
+    }
+
+    async fn execute_transfer(&self, transfer: &Transfer<'_>) -> Result<Option<String>, ApiError> {
+        let (wallet, wallet_type) = if transfer.method.type_ == PayoutMethodType::Venmo {
+            ("Venmo", "user_handle")
+        } else {
+            ("PayPal", "paypal_id")
+        };
+
+        #[derive(Deserialize)]
+        struct PayPalLink {
+            href: String,
+        }
+
+        #[derive(Deserialize)]
+        struct PayoutsResponse {
+            pub links: Vec<PayPalLink>,
+        }
+
+        let res: PayoutsResponse = self.make_paypal_request(
+            Method::POST,
+            "payments/payouts",
+            Some(
+                json! ({
+                    "sender_batch_header": {
+                        "sender_batch_id": format!("{}-payouts", Utc::now().to_rfc3339()),
+                        "email_subject": "You have received a payment from Modrinth!",
+                        "email_message": "Thank you for creating projects on Modrinth. Please claim this payment within 30 days.",
+                    },
+                    "items": [{
+                        "amount": {
+                            "currency": transfer.currency,
+                            "value": transfer.amount.to_string()
+                        },
+                        "receiver": transfer.recipient.address,
+                        "note": "Payment from Modrinth creator monetization program",
+                        "recipient_type": wallet_type,
+                        "recipient_wallet": wallet,
+                        "sender_item_id": transfer.payout_id,
+                    }]
+                })
+            ),
+            None,
+            None
+        ).await?;
+
+        let Some(link) = res.links.first() else {
+            return Ok(None);
+        };
+
+        #[derive(Deserialize)]
+        struct PayoutItem {
+            pub payout_item_id: String,
+        }
+
+        #[derive(Deserialize)]
+        struct PayoutData {
+            pub items: Vec<PayoutItem>,
+        }
+
+        // The payout is made at this point, so failing to get its ID only means the payout
+        // won't be updated by webhooks
+        let platform_id = self
+            .make_paypal_request::<(), PayoutData>(Method::GET, &link.href, None, None, Some(true))
+            .await
+            .ok()
+            .and_then(|x| x.items.into_iter().next())
+            .map(|x| x.payout_item_id);
+
+        Ok(platform_id)
+    }
+
+    async fn poll_status(&self, platform_id: &str) -> Result<Option<PayoutEvent>, ApiError> {
+        #[derive(Deserialize)]
+        struct PayoutItem {
+            pub transaction_status: String,
+        }
+
+        let item: PayoutItem = self
+            .make_paypal_request::<(), _>(
+                Method::GET,
+                &format!("payments/payouts-item/{platform_id}"),
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        Ok(status_event(
+            platform_id.to_string(),
+            &item.transaction_status,
+        ))
+    }
+
+    async fn cancel_transfer(&self, platform_id: &str) -> Result<(), ApiError> {
+        self.make_paypal_request::<(), ()>(
+            Method::POST,
+            &format!("payments/payouts-item/{}/cancel", platform_id),
+            None,
+            None,
+            None,
+        )
+        .await
+    }
+
+    async fn verify_webhook(&self, headers: &HeaderMap, body: &str) -> Result<String, ApiError> {
+        let auth_algo = required_header(headers, "PAYPAL-AUTH-ALGO")?;
+        let cert_url = required_header(headers, "PAYPAL-CERT-URL")?;
+        let transmission_id = required_header(headers, "PAYPAL-TRANSMISSION-ID")?;
+        let transmission_sig = required_header(headers, "PAYPAL-TRANSMISSION-SIG")?;
+        let transmission_time = required_header(headers, "PAYPAL-TRANSMISSION-TIME")?;
+
+        #[derive(Deserialize)]
+        struct WebHookResponse {
+            verification_status: String,
+        }
+
+        let webhook_res = self
+            .make_paypal_request::<(), WebHookResponse>(
+                Method::POST,
+                "notifications/verify-webhook-signature",
+                None,
+                // This is needed as serde re-orders fields, which causes the validation to fail for PayPal.
+                Some(format!(
+                    "{{
+                        \"auth_algo\": \"{auth_algo}\",
+                        \"cert_url\": \"{cert_url}\",
+                        \"transmission_id\": \"{transmission_id}\",
+                        \"transmission_sig\": \"{transmission_sig}\",
+                        \"transmission_time\": \"{transmission_time}\",
+                        \"webhook_id\": \"{}\",
+                        \"webhook_event\": {body}
+                    }}",
+                    dotenvy::var("PAYPAL_WEBHOOK_ID")?
+                )),
+                None,
+            )
+            .await?;
+
+        if &webhook_res.verification_status != "SUCCESS" {
+            return Err(ApiError::InvalidInput(
+                "Invalid webhook signature".to_string(),
+            ));
+        }
+
+        event_id(body, "id")
+    }
+
+    async fn handle_webhook(
+        &self,
+        body: &str,
+        _pool: &PgPool,
+        _redis: &RedisPool,
+    ) -> Result<Vec<PayoutEvent>, ApiError> {
+        #[derive(Deserialize)]
+        struct PayPalResource {
+            pub payout_item_id: String,
+        }
+
+        #[derive(Deserialize)]
+        struct PayPalWebhook {
+            pub event_type: String,
+            pub resource: PayPalResource,
+        }
+
+        let webhook = serde_json::from_str::<PayPalWebhook>(body)?;
+
+        let event = match webhook.event_type.strip_prefix("PAYMENT.PAYOUTS-ITEM.") {
+            Some(status) => status_event(webhook.resource.payout_item_id, status),
+            None => None,
+        };
+
+        Ok(event.into_iter().collect())
+    }
+}
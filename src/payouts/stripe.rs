@@ -0,0 +1,420 @@
+use super::{event_id, required_header, PayoutEvent, PayoutProvider, Recipient, Transfer};
+use crate::database::models::User;
+use crate::database::redis::RedisPool;
+use crate::models::payouts::{PayoutInterval, PayoutMethod, PayoutMethodFee, PayoutMethodType};
+use crate::routes::ApiError;
+use crate::util::money::{Money, BASE_CURRENCY};
+use actix_web::http::header::HeaderMap;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hex::ToHex;
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::Method;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::PgPool;
+
+/// Countries Stripe Connect Express accounts can receive cross-border transfers in
+pub const STRIPE_COUNTRIES: &[&str] = &[
+    "US", "AT", "AU", "BE", "BG", "CA", "CH", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GB",
+    "GR", "HR", "HU", "IE", "IT", "LI", "LT", "LU", "LV", "MT", "NL", "NO", "NZ", "PL", "PT", "RO",
+    "SE", "SI", "SK",
+];
+
+/// How old a Stripe webhook may be before it is rejected, to prevent replays
+const STRIPE_WEBHOOK_TOLERANCE_SECONDS: i64 = 300;
+
+/// Pays out to bank accounts through Stripe Connect Express accounts
+#[derive(Default)]
+pub struct StripeProvider(());
+
+impl StripeProvider {
+    pub fn new() -> Self {
+        StripeProvider(())
+    }
+
+    /// Stripe takes form encoded parameters, with nested fields written as `metadata[key]`
+    pub async fn make_stripe_request<X: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(&str, String)],
+        idempotency_key: Option<String>,
+    ) -> Result<X, ApiError> {
+        let client = reqwest::Client::new();
+        let mut request = client
+            .request(method, format!("{}{path}", dotenvy::var("STRIPE_API_URL")?))
+            .bearer_auth(dotenvy::var("STRIPE_API_KEY")?)
+            .form(params);
+
+        // Prevents a retried request from creating a second transfer
+        if let Some(idempotency_key) = idempotency_key {
+            request = request.header("Idempotency-Key", idempotency_key);
+        }
+
+        let resp = request
+            .send()
+            .await
+            .map_err(|_| ApiError::Payments("could not communicate with Stripe".to_string()))?;
+
+        let status = resp.status();
+
+        let value = resp.json::<Value>().await.map_err(|_| {
+            ApiError::Payments("could not retrieve Stripe response body".to_string())
+        })?;
+
+        if !status.is_success() {
+            #[derive(Deserialize)]
+            struct StripeError {
+                message: String,
+            }
+
+            #[derive(Deserialize)]
+            struct StripeErrorBody {
+                error: StripeError,
+            }
+
+            let err = serde_json::from_value::<StripeErrorBody>(value).map_err(|_| {
+                ApiError::Payments("could not retrieve Stripe error body".to_string())
+            })?;
+
+            return Err(ApiError::Payments(err.error.message));
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+#[async_trait]
+impl PayoutProvider for StripeProvider {
+    fn name(&self) -> &'static str {
+        "stripe"
+    }
+
+    fn method_types(&self) -> &[PayoutMethodType] {
+        &[PayoutMethodType::Stripe]
+    }
+
+    async fn payout_methods(&self) -> Result<Vec<PayoutMethod>, ApiError> {
+        Ok(vec![PayoutMethod {
+            id: "stripe".to_string(),
+            type_: PayoutMethodType::Stripe,
+            name: "Bank transfer (Stripe)".to_string(),
+            supported_countries: STRIPE_COUNTRIES.iter().map(|x| x.to_string()).collect(),
+            // Transfers are made in USD and converted by Stripe into the account's currency
+            supported_currencies: vec![BASE_CURRENCY.to_string()],
+            image_url: None,
+            interval: PayoutInterval::Standard {
+                min: Decimal::from(1),
+                max: Decimal::from(100_000),
+            },
+            fee: PayoutMethodFee {
+                percentage: Decimal::from(1) / Decimal::from(400),
+                min: Decimal::from(1) / Decimal::from(4),
+                max: None,
+            },
+        }])
+    }
+
+    /// Creates a Connect Express account for the user if they do not have one yet, and returns
+    /// a link to Stripe's hosted onboarding for it
+    async fn create_recipient(
+        &self,
+        user: &User,
+        country: &str,
+        pool: &PgPool,
+        redis: &RedisPool,
+    ) -> Result<String, ApiError> {
+        let account_id = if let Some(account_id) = &user.stripe_account_id {
+            account_id.clone()
+        } else {
+            if !STRIPE_COUNTRIES.contains(&country) {
+                return Err(ApiError::InvalidInput(
+                    "Stripe payouts are not available in your country!".to_string(),
+                ));
+            }
+
+            #[derive(Deserialize)]
+            struct StripeAccount {
+                pub id: String,
+            }
+
+            let mut params = vec![
+                ("type", "express".to_string()),
+                ("country", country.to_string()),
+                ("capabilities[transfers][requested]", "true".to_string()),
+                (
+                    "metadata[user_id]",
+                    crate::models::ids::UserId::from(user.id).to_string(),
+                ),
+            ];
+            // Accounts outside of the US can only receive transfers under the recipient agreement
+            if country != "US" {
+                params.push(("tos_acceptance[service_agreement]", "recipient".to_string()));
+            }
+            if user.email_verified {
+                if let Some(email) = &user.email {
+                    params.push(("email", email.clone()));
+                }
+            }
+
+            let account: StripeAccount = self
+                .make_stripe_request(Method::POST, "accounts", &params, None)
+                .await?;
+
+            let mut transaction = pool.begin().await?;
+            sqlx::query!(
+                "
+                UPDATE users
+                SET stripe_account_id = $1, stripe_payouts_enabled = FALSE
+                WHERE id = $2
+                ",
+                account.id,
+                user.id as crate::database::models::ids::UserId
+            )
+            .execute(&mut *transaction)
+            .await?;
+            transaction.commit().await?;
+            User::clear_caches(&[(user.id, None)], redis).await?;
+
+            account.id
+        };
+
+        let return_url = format!(
+            "{}/{}",
+            dotenvy::var("SITE_URL")?,
+            dotenvy::var("SITE_STRIPE_ONBOARDING_PATH")?
+        );
+
+        #[derive(Deserialize)]
+        struct StripeAccountLink {
+            pub url: String,
+        }
+
+        let link: StripeAccountLink = self
+            .make_stripe_request(
+                Method::POST,
+                "account_links",
+                &[
+                    ("account", account_id),
+                    ("refresh_url", return_url.clone()),
+                    ("return_url", return_url),
+                    ("type", "account_onboarding".to_string()),
+                ],
+                None,
+            )
+            .await?;
+
+        Ok(link.url)
+    }
+
+    async fn recipient(&self, user: &User, _method: &PayoutMethod) -> Result<Recipient, ApiError> {
+        match &user.stripe_account_id {
+            Some(account_id) if user.stripe_payouts_enabled => Ok(Recipient {
+                address: account_id.clone(),
+                display_address: account_id.clone(),
+            }),
+            Some(_) => Err(ApiError::InvalidInput(
+                "You must finish setting up your Stripe account to proceed!".to_string(),
+            )),
+            None => Err(ApiError::InvalidInput(
+                "You have not connected a Stripe account!".to_string(),
+            )),
+        }
+    }
+
+    async fn execute_transfer(&self, transfer: &Transfer<'_>) -> Result<Option<String>, ApiError> {
+        #[derive(Deserialize)]
+        struct StripeTransfer {
+            pub id: String,
+        }
+
+        // Stripe amounts are in the currency's minor unit
+        let amount = Money::from_decimal(transfer.amount, transfer.currency)
+            .ok_or_else(|| ApiError::InvalidInput("Invalid amount specified!".to_string()))?;
+
+        let payout_id = transfer.payout_id.to_string();
+        let res = self
+            .make_stripe_request::<StripeTransfer>(
+                Method::POST,
+                "transfers",
+                &[
+                    ("amount", amount.amount.to_string()),
+                    ("currency", amount.currency.to_lowercase()),
+                    ("destination", transfer.recipient.address.clone()),
+                    ("metadata[payout_id]", payout_id.clone()),
+                ],
+                Some(format!("payout-{payout_id}")),
+            )
+            .await?;
+
+        Ok(Some(res.id))
+    }
+
+    /// Transfers reach the connected account instantly, and are settled when the account pays
+    /// out to the user's bank, which is reported through webhooks
+    async fn poll_status(&self, _platform_id: &str) -> Result<Option<PayoutEvent>, ApiError> {
+        Ok(None)
+    }
+
+    /// Receives both account events (transfers) and Connect events (connected accounts and
+    /// their payouts), which Stripe signs with different secrets
+    async fn verify_webhook(&self, headers: &HeaderMap, body: &str) -> Result<String, ApiError> {
+        let header = required_header(headers, "Stripe-Signature")?;
+
+        let mut timestamp = None;
+        let mut signatures = Vec::new();
+        for part in header.split(',') {
+            match part.split_once('=') {
+                Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+                Some(("v1", value)) => signatures.push(value),
+                _ => {}
+            }
+        }
+
+        let timestamp = timestamp
+            .ok_or_else(|| ApiError::InvalidInput("missing webhook timestamp".to_string()))?;
+        if (Utc::now().timestamp() - timestamp).abs() > STRIPE_WEBHOOK_TOLERANCE_SECONDS {
+            return Err(ApiError::InvalidInput(
+                "Webhook timestamp is outside of the tolerance".to_string(),
+            ));
+        }
+
+        let mut verified = false;
+        for secret in [
+            dotenvy::var("STRIPE_WEBHOOK_SECRET")?,
+            dotenvy::var("STRIPE_CONNECT_WEBHOOK_SECRET")?,
+        ] {
+            let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret.as_bytes())
+                .map_err(|_| ApiError::Payments("error initializing HMAC".to_string()))?;
+            mac.update(format!("{timestamp}.{body}").as_bytes());
+            let request_signature = mac.finalize().into_bytes().encode_hex::<String>();
+
+            verified |= signatures.contains(&&*request_signature);
+        }
+
+        if !verified {
+            return Err(ApiError::InvalidInput(
+                "Invalid webhook signature".to_string(),
+            ));
+        }
+
+        event_id(body, "id")
+    }
+
+    async fn handle_webhook(
+        &self,
+        body: &str,
+        pool: &PgPool,
+        redis: &RedisPool,
+    ) -> Result<Vec<PayoutEvent>, ApiError> {
+        #[derive(Deserialize)]
+        struct StripeEventData {
+            pub object: Value,
+        }
+
+        #[derive(Deserialize)]
+        struct StripeWebhook {
+            #[serde(rename = "type")]
+            pub type_: String,
+            /// The connected account, for Connect events
+            pub account: Option<String>,
+            pub data: StripeEventData,
+        }
+
+        let webhook = serde_json::from_str::<StripeWebhook>(body)?;
+
+        match &*webhook.type_ {
+            "account.updated" => {
+                #[derive(Deserialize)]
+                struct StripeAccount {
+                    pub id: String,
+                    pub payouts_enabled: bool,
+                }
+
+                let account = serde_json::from_value::<StripeAccount>(webhook.data.object)?;
+
+                let mut transaction = pool.begin().await?;
+                let result = sqlx::query!(
+                    "
+                    UPDATE users
+                    SET stripe_payouts_enabled = $1
+                    WHERE stripe_account_id = $2
+                    RETURNING id
+                    ",
+                    account.payouts_enabled,
+                    account.id
+                )
+                .fetch_optional(&mut *transaction)
+                .await?;
+                transaction.commit().await?;
+
+                if let Some(result) = result {
+                    User::clear_caches(
+                        &[(crate::database::models::UserId(result.id), None)],
+                        redis,
+                    )
+                    .await?;
+                }
+
+                Ok(vec![])
+            }
+            // Transfers move funds to the connected account instantly, so a reversal is the only
+            // way one can fail after being created
+            "transfer.reversed" => {
+                #[derive(Deserialize)]
+                struct StripeTransfer {
+                    pub id: String,
+                    pub amount: i64,
+                    pub amount_reversed: i64,
+                }
+
+                let transfer = serde_json::from_value::<StripeTransfer>(webhook.data.object)?;
+                if transfer.amount_reversed < transfer.amount {
+                    return Ok(vec![]);
+                }
+
+                Ok(vec![PayoutEvent::Reversed {
+                    platform_id: transfer.id,
+                    reason: "The transfer was reversed by Stripe".to_string(),
+                }])
+            }
+            // Connected accounts pay out their balance to their bank on their own schedule, so a
+            // payout of the connected account settles every transfer made before it
+            "payout.paid" | "payout.failed" => {
+                #[derive(Deserialize)]
+                struct StripePayout {
+                    pub created: i64,
+                    pub failure_message: Option<String>,
+                }
+
+                let Some(account) = webhook.account else {
+                    return Ok(vec![]);
+                };
+                let payout = serde_json::from_value::<StripePayout>(webhook.data.object)?;
+                let before = DateTime::from_timestamp(payout.created, 0).unwrap_or_else(Utc::now);
+
+                Ok(vec![if &*webhook.type_ == "payout.paid" {
+                    PayoutEvent::RecipientSettled {
+                        method: PayoutMethodType::Stripe,
+                        recipient: account,
+                        before,
+                    }
+                } else {
+                    PayoutEvent::RecipientSettlementFailed {
+                        method: PayoutMethodType::Stripe,
+                        recipient: account,
+                        before,
+                        reason: payout.failure_message.unwrap_or_else(|| {
+                            "Stripe could not pay out to your bank account".to_string()
+                        }),
+                    }
+                }])
+            }
+            _ => Ok(vec![]),
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use super::{event_id, required_header, PayoutEvent, PayoutProvider, Recipient, Transfer};
+use crate::database::models::User;
+use crate::database::redis::RedisPool;
+use crate::models::payouts::{
+    PayoutDecimal, PayoutInterval, PayoutMethod, PayoutMethodFee, PayoutMethodType, PayoutStatus,
+};
+use crate::routes::ApiError;
+use crate::util::circuit_breaker::breaker;
+use crate::util::money::{currency_exponent, BASE_CURRENCY};
+use actix_web::http::header::HeaderMap;
+use async_trait::async_trait;
+use hex::ToHex;
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::Method;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::PgPool;
+
+/// Pays out as gift cards, prepaid cards and bank transfers through Tremendous
+#[derive(Default)]
+pub struct TremendousProvider(());
+
+impl TremendousProvider {
+    pub fn new() -> Self {
+        TremendousProvider(())
+    }
+
+    pub async fn make_tremendous_request<T: Serialize, X: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<T>,
+    ) -> Result<X, ApiError> {
+        let client = reqwest::Client::new();
+        let mut request = client
+            .request(
+                method,
+                format!("{}{path}", dotenvy::var("TREMENDOUS_API_URL")?),
+            )
+            .header(
+                "Authorization",
+                format!("Bearer {}", dotenvy::var("TREMENDOUS_API_KEY")?),
+            );
+
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let breaker = breaker("tremendous");
+        breaker.check()?;
+
+        let resp = request.send().await.map_err(|_| {
+            breaker.record_failure();
+            ApiError::Payments("could not communicate with Tremendous".to_string())
+        })?;
+
+        let status = resp.status();
+        if status.is_server_error() {
+            breaker.record_failure();
+        } else {
+            breaker.record_success();
+        }
+
+        let value = resp.json::<Value>().await.map_err(|_| {
+            ApiError::Payments("could not retrieve Tremendous response body".to_string())
+        })?;
+
+        if !status.is_success() {
+            if let Some(obj) = value.as_object() {
+                if let Some(array) = obj.get("errors") {
+                    #[derive(Deserialize)]
+                    struct TremendousError {
+                        message: String,
+                    }
+
+                    let err =
+                        serde_json::from_value::<TremendousError>(array.clone()).map_err(|_| {
+                            ApiError::Payments(
+                                "could not retrieve Tremendous error json body".to_string(),
+                            )
+                        })?;
+
+                    return Err(ApiError::Payments(err.message));
+                }
+
+                return Err(ApiError::Payments(
+                    "could not retrieve Tremendous error body".to_string(),
+                ));
+            }
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+#[async_trait]
+impl PayoutProvider for TremendousProvider {
+    fn name(&self) -> &'static str {
+        "tremendous"
+    }
+
+    fn method_types(&self) -> &[PayoutMethodType] {
+        &[PayoutMethodType::Tremendous]
+    }
+
+    async fn payout_methods(&self) -> Result<Vec<PayoutMethod>, ApiError> {
+        let mut methods = Vec::new();
+
+        #[derive(Deserialize)]
+        pub struct Sku {
+            pub min: Decimal,
+            pub max: Decimal,
+        }
+
+        #[derive(Deserialize, Eq, PartialEq)]
+        #[serde(rename_all = "snake_case")]
+        pub enum ProductImageType {
+            Card,
+            Logo,
+        }
+
+        #[derive(Deserialize)]
+        pub struct ProductImage {
+            pub src: String,
+            #[serde(rename = "type")]
+            pub type_: ProductImageType,
+        }
+
+        #[derive(Deserialize)]
+        pub struct ProductCountry {
+            pub abbr: String,
+        }
+
+        #[derive(Deserialize)]
+        pub struct Product {
+            pub id: String,
+            pub category: String,
+            pub name: String,
+            pub description: String,
+            pub disclosure: String,
+            pub skus: Vec<Sku>,
+            pub currency_codes: Vec<String>,
+            pub countries: Vec<ProductCountry>,
+            pub images: Vec<ProductImage>,
+        }
+
+        #[derive(Deserialize)]
+        pub struct TremendousResponse {
+            pub products: Vec<Product>,
+        }
+
+        let response = self
+            .make_tremendous_request::<(), TremendousResponse>(Method::GET, "products", None)
+            .await?;
+
+        for product in response.products {
+            const BLACKLISTED_IDS: &[&str] = &[
+                // physical visa
+                "A2J05SWPI2QG",
+                // crypto
+                "1UOOSHUUYTAM",
+                "5EVJN47HPDFT",
+                "NI9M4EVAVGFJ",
+                "VLY29QHTMNGT",
+                "7XU98H109Y3A",
+                "0CGEDFP2UIKV",
+                "PDYLQU0K073Y",
+                "HCS5Z7O2NV5G",
+                "IY1VMST1MOXS",
+                "VRPZLJ7HCA8X",
+                // bitcard (crypto)
+                "GWQQS5RM8IZS",
+                "896MYD4SGOGZ",
+                "PWLEN1VZGMZA",
+                "A2VRM96J5K5W",
+                "HV9ICIM3JT7P",
+                "K2KLSPVWC2Q4",
+                "HRBRQLLTDF95",
+                "UUBYLZVK7QAB",
+                "BH8W3XEDEOJN",
+                "7WGE043X1RYQ",
+                "2B13MHUZZVTF",
+                "JN6R44P86EYX",
+                "DA8H43GU84SO",
+                "QK2XAQHSDEH4",
+                "J7K1IQFS76DK",
+                "NL4JQ2G7UPRZ",
+                "OEFTMSBA5ELH",
+                "A3CQK6UHNV27",
+            ];
+            const SUPPORTED_METHODS: &[&str] =
+                &["merchant_cards", "visa", "bank", "ach", "visa_card"];
+
+            if !SUPPORTED_METHODS.contains(&&*product.category)
+                || BLACKLISTED_IDS.contains(&&*product.id)
+            {
+                continue;
+            };
+
+            let mut method = PayoutMethod {
+                id: product.id,
+                type_: PayoutMethodType::Tremendous,
+                name: product.name.clone(),
+                supported_countries: product.countries.into_iter().map(|x| x.abbr).collect(),
+                supported_currencies: product
+                    .currency_codes
+                    .iter()
+                    .filter(|x| currency_exponent(x).is_some())
+                    .cloned()
+                    .collect(),
+                image_url: product
+                    .images
+                    .into_iter()
+                    .find(|x| x.type_ == ProductImageType::Card)
+                    .map(|x| x.src),
+                interval: if product.skus.len() > 1 {
+                    let mut values = product
+                        .skus
+                        .into_iter()
+                        .map(|x| PayoutDecimal(x.min))
+                        .collect::<Vec<_>>();
+                    values.sort_by(|a, b| a.0.cmp(&b.0));
+
+                    PayoutInterval::Fixed { values }
+                } else if let Some(first) = product.skus.first() {
+                    PayoutInterval::Standard {
+                        min: first.min,
+                        max: first.max,
+                    }
+                } else {
+                    PayoutInterval::Standard {
+                        min: Decimal::ZERO,
+                        max: Decimal::from(5_000),
+                    }
+                },
+                fee: if product.category == "ach" {
+                    PayoutMethodFee {
+                        percentage: Decimal::from(4) / Decimal::from(100),
+                        min: Decimal::from(1) / Decimal::from(4),
+                        max: None,
+                    }
+                } else {
+                    PayoutMethodFee {
+                        percentage: Default::default(),
+                        min: Default::default(),
+                        max: None,
+                    }
+                },
+            };
+
+            // we do not support interval gift cards with non US based currencies since we cannot do currency conversions properly
+            if let PayoutInterval::Fixed { .. } = method.interval {
+                if !product.currency_codes.contains(&"USD".to_string()) {
+                    continue;
+                }
+
+                // Fixed denominations are in USD, so they cannot be converted
+                method.supported_currencies = vec![BASE_CURRENCY.to_string()];
+            }
+
+            methods.push(method);
+        }
+
+        const UPRANK_IDS: &[&str] = &["ET0ZVETV5ILN", "Q24BD9EZ332JT", "UIL1ZYJU5MKN"];
+        const DOWNRANK_IDS: &[&str] = &["EIPF8Q00EMM1", "OU2MWXYWPNWQ"];
+
+        methods.sort_by(|a, b| {
+            let a_top = UPRANK_IDS.contains(&&*a.id);
+            let a_bottom = DOWNRANK_IDS.contains(&&*a.id);
+            let b_top = UPRANK_IDS.contains(&&*b.id);
+            let b_bottom = DOWNRANK_IDS.contains(&&*b.id);
+
+            match (a_top, a_bottom, b_top, b_bottom) {
+                (true, _, true, _) => a.name.cmp(&b.name), // Both in top_priority: sort alphabetically
+                (_, true, _, true) => a.name.cmp(&b.name), // Both in bottom_priority: sort alphabetically
+                (true, _, _, _) => std::cmp::Ordering::Less, // a in top_priority: a comes first
+                (_, _, true, _) => std::cmp::Ordering::Greater, // b in top_priority: b comes first
+                (_, true, _, _) => std::cmp::Ordering::Greater, // a in bottom_priority: b comes first
+                (_, _, _, true) => std::cmp::Ordering::Less, // b in bottom_priority: a comes first
+                (_, _, _, _) => a.name.cmp(&b.name), // Neither in priority: sort alphabetically
+            }
+        });
+
+        Ok(methods)
+    }
+
+    async fn recipient(&self, user: &User, _method: &PayoutMethod) -> Result<Recipient, ApiError> {
+        match &user.email {
+            Some(email) if user.email_verified => Ok(Recipient {
+                address: email.clone(),
+                display_address: email.clone(),
+            }),
+            Some(_) => Err(ApiError::InvalidInput(
+                "You must verify your account email to proceed!".to_string(),
+            )),
+            None => Err(ApiError::InvalidInput(
+                "You must add an email to your account to proceed!".to_string(),
+            )),
+        }
+    }
+
+    async fn execute_transfer(&self, transfer: &Transfer<'_>) -> Result<Option<String>, ApiError> {
+        #[derive(Deserialize)]
+        struct Reward {
+            pub id: String,
+        }
+
+        #[derive(Deserialize)]
+        struct Order {
+            pub rewards: Vec<Reward>,
+        }
+
+        #[derive(Deserialize)]
+        struct TremendousResponse {
+            pub order: Order,
+        }
+
+        let res: TremendousResponse = self
+            .make_tremendous_request(
+                Method::POST,
+                "orders",
+                Some(json! ({
+                    "payment": {
+                        "funding_source_id": "BALANCE",
+                    },
+                    "rewards": [{
+                        "value": {
+                            "denomination": transfer.amount,
+                            "currency_code": transfer.currency,
+                        },
+                        "delivery": {
+                            "method": "EMAIL"
+                        },
+                        "recipient": {
+                            "name": transfer.recipient_name,
+                            "email": transfer.recipient.address
+                        },
+                        "products": [
+                            &transfer.method.id,
+                        ],
+                        "campaign_id": dotenvy::var("TREMENDOUS_CAMPAIGN_ID")?,
+                    }]
+                })),
+            )
+            .await?;
+
+        Ok(res.order.rewards.into_iter().next().map(|x| x.id))
+    }
+
+    async fn poll_status(&self, platform_id: &str) -> Result<Option<PayoutEvent>, ApiError> {
+        #[derive(Deserialize)]
+        struct Delivery {
+            pub status: String,
+        }
+
+        #[derive(Deserialize)]
+        struct Reward {
+            pub delivery: Delivery,
+        }
+
+        #[derive(Deserialize)]
+        struct RewardResponse {
+            pub reward: Reward,
+        }
+
+        let res: RewardResponse = self
+            .make_tremendous_request::<(), _>(Method::GET, &format!("rewards/{platform_id}"), None)
+            .await?;
+
+        let platform_id = platform_id.to_string();
+        Ok(match &*res.reward.delivery.status {
+            "SUCCEEDED" => Some(PayoutEvent::Succeeded { platform_id }),
+            "FAILED" => Some(PayoutEvent::Failed {
+                platform_id,
+                status: PayoutStatus::Failed,
+                reason: None,
+            }),
+            _ => None,
+        })
+    }
+
+    async fn cancel_transfer(&self, platform_id: &str) -> Result<(), ApiError> {
+        self.make_tremendous_request::<(), ()>(
+            Method::POST,
+            &format!("rewards/{}/cancel", platform_id),
+            None,
+        )
+        .await
+    }
+
+    async fn verify_webhook(&self, headers: &HeaderMap, body: &str) -> Result<String, ApiError> {
+        let signature = required_header(headers, "Tremendous-Webhook-Signature")?;
+        let signature = signature.split('=').next_back().unwrap_or(signature);
+
+        let mut mac: Hmac<Sha256> =
+            Hmac::new_from_slice(dotenvy::var("TREMENDOUS_PRIVATE_KEY")?.as_bytes())
+                .map_err(|_| ApiError::Payments("error initializing HMAC".to_string()))?;
+        mac.update(body.as_bytes());
+        let request_signature = mac.finalize().into_bytes().encode_hex::<String>();
+
+        if &*request_signature != signature {
+            return Err(ApiError::InvalidInput(
+                "Invalid webhook signature".to_string(),
+            ));
+        }
+
+        event_id(body, "uuid")
+    }
+
+    async fn handle_webhook(
+        &self,
+        body: &str,
+        _pool: &PgPool,
+        _redis: &RedisPool,
+    ) -> Result<Vec<PayoutEvent>, ApiError> {
+        #[derive(Deserialize)]
+        pub struct TremendousResource {
+            pub id: String,
+        }
+
+        #[derive(Deserialize)]
+        struct TremendousPayload {
+            pub resource: TremendousResource,
+        }
+
+        #[derive(Deserialize)]
+        struct TremendousWebhook {
+            pub event: String,
+            pub payload: TremendousPayload,
+        }
+
+        let webhook = serde_json::from_str::<TremendousWebhook>(body)?;
+        let platform_id = webhook.payload.resource.id;
+
+        Ok(match &*webhook.event {
+            "REWARDS.CANCELED" => vec![PayoutEvent::Failed {
+                platform_id,
+                status: PayoutStatus::Cancelled,
+                reason: None,
+            }],
+            "REWARDS.DELIVERY.FAILED" => vec![PayoutEvent::Failed {
+                platform_id,
+                status: PayoutStatus::Failed,
+                reason: None,
+            }],
+            "REWARDS.DELIVERY.SUCCEEDED" => vec![PayoutEvent::Succeeded { platform_id }],
+            _ => vec![],
+        })
+    }
+}
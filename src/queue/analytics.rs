@@ -1,17 +1,65 @@
-use crate::database::models::DatabaseError;
 use crate::database::redis::RedisPool;
-use crate::models::analytics::{Download, PageView, Playtime};
+use crate::models::analytics::{Download, PageView, Playtime, PlaytimeSession};
 use crate::routes::ApiError;
+use crate::util::env::parse_var;
+use crate::util::user_agent::user_agent_class;
 use dashmap::{DashMap, DashSet};
-use redis::cmd;
+use sha2::Digest;
 use sqlx::PgPool;
+use std::net::Ipv6Addr;
 
 const DOWNLOADS_NAMESPACE: &str = "downloads";
+/// The default length of the download deduplication window, in seconds
+const DEFAULT_DOWNLOAD_DEDUP_TTL: u64 = 6 * 60 * 60;
+/// How many downloads per key are counted within the dedup window
+const DOWNLOAD_DEDUP_ALLOWANCE: i64 = 6;
+
+/// Whether downloads from the given autonomous system should not be counted. Excluded systems
+/// (mostly CI providers and datacenters) are almost never real players
+pub fn is_excluded_download_asn(asn: u32) -> bool {
+    crate::util::env::var("DOWNLOAD_EXCLUDED_ASNS")
+        .ok()
+        .and_then(|x| serde_json::from_str::<Vec<u32>>(&x).ok())
+        .map(|x| x.contains(&asn))
+        .unwrap_or(false)
+}
+
+/// Downloads are deduplicated by the hashed IP, the version and the class of the user agent
+/// downloading it. Only the first few downloads per key count within the dedup window.
+///
+/// IPv6 addresses are reduced to their /64 prefix, as a single user commonly cycles through
+/// many addresses of it. Including the user agent class avoids undercounting different users
+/// behind the same NAT.
+fn download_dedup_key(download: &Download, ip: Ipv6Addr, user_agent: &str) -> String {
+    let ip = if let Some(ip) = ip.to_ipv4_mapped() {
+        ip.to_string()
+    } else {
+        let segments = ip.segments();
+        format!(
+            "{:x}:{:x}:{:x}:{:x}::/64",
+            segments[0], segments[1], segments[2], segments[3]
+        )
+    };
+
+    let hash = sha2::Sha256::digest(
+        format!(
+            "{}:{}:{}:{}",
+            dotenvy::var("ANALYTICS_SALT").unwrap_or_default(),
+            ip,
+            download.version_id,
+            user_agent_class(user_agent)
+        )
+        .as_bytes(),
+    );
+
+    hex::encode(&hash[..16])
+}
 
 pub struct AnalyticsQueue {
     views_queue: DashSet<PageView>,
     downloads_queue: DashMap<String, Download>,
     playtime_queue: DashSet<Playtime>,
+    playtime_sessions_queue: DashSet<PlaytimeSession>,
 }
 
 impl Default for AnalyticsQueue {
@@ -27,6 +75,7 @@ impl AnalyticsQueue {
             views_queue: DashSet::with_capacity(1000),
             downloads_queue: DashMap::with_capacity(1000),
             playtime_queue: DashSet::with_capacity(1000),
+            playtime_sessions_queue: DashSet::with_capacity(1000),
         }
     }
 
@@ -34,22 +83,21 @@ impl AnalyticsQueue {
         self.views_queue.insert(page_view);
     }
 
-    pub fn add_download(&self, download: Download) {
-        let ip_stripped = if let Some(ip) = download.ip.to_ipv4_mapped() {
-            let octets = ip.octets();
-            u64::from_be_bytes([0, 0, 0, 0, octets[0], octets[1], octets[2], octets[3]])
-        } else {
-            let octets = download.ip.octets();
-            u64::from_be_bytes([0, 0, 0, 0, octets[0], octets[1], octets[2], octets[3]])
-        };
+    /// Queues a download. The IP and user agent are only used to deduplicate downloads and are
+    /// not stored
+    pub fn add_download(&self, download: Download, ip: Ipv6Addr, user_agent: &str) {
         self.downloads_queue
-            .insert(format!("{}-{}", ip_stripped, download.project_id), download);
+            .insert(download_dedup_key(&download, ip, user_agent), download);
     }
 
     pub fn add_playtime(&self, playtime: Playtime) {
         self.playtime_queue.insert(playtime);
     }
 
+    pub fn add_playtime_session(&self, session: PlaytimeSession) {
+        self.playtime_sessions_queue.insert(session);
+    }
+
     pub async fn index(
         &self,
         client: clickhouse::Client,
@@ -65,6 +113,9 @@ impl AnalyticsQueue {
         let playtime_queue = self.playtime_queue.clone();
         self.playtime_queue.clear();
 
+        let playtime_sessions_queue = self.playtime_sessions_queue.clone();
+        self.playtime_sessions_queue.clear();
+
         if !views_queue.is_empty() {
             let mut views = client.insert("views")?;
 
@@ -85,6 +136,16 @@ impl AnalyticsQueue {
             playtimes.end().await?;
         }
 
+        if !playtime_sessions_queue.is_empty() {
+            let mut sessions = client.insert("playtime_sessions")?;
+
+            for session in playtime_sessions_queue {
+                sessions.write(&session).await?;
+            }
+
+            sessions.end().await?;
+        }
+
         if !downloads_queue.is_empty() {
             let mut downloads_keys = Vec::new();
             let raw_downloads = DashMap::new();
@@ -94,43 +155,22 @@ impl AnalyticsQueue {
                 raw_downloads.insert(index, download);
             }
 
-            let mut redis = redis.pool.get().await.map_err(DatabaseError::RedisPool)?;
-
-            let results = cmd("MGET")
-                .arg(
-                    downloads_keys
-                        .iter()
-                        .map(|x| format!("{}:{}", DOWNLOADS_NAMESPACE, x))
-                        .collect::<Vec<_>>(),
-                )
-                .query_async::<_, Vec<Option<u32>>>(&mut redis)
-                .await
-                .map_err(DatabaseError::CacheError)?;
-
-            let mut pipe = redis::pipe();
-            for (idx, count) in results.into_iter().enumerate() {
-                let key = &downloads_keys[idx];
-
-                let new_count = if let Some(count) = count {
-                    if count > 5 {
-                        raw_downloads.remove(&idx);
-                        continue;
-                    }
-
-                    count + 1
-                } else {
-                    1
-                };
-
-                pipe.atomic().set_ex(
-                    format!("{}:{}", DOWNLOADS_NAMESPACE, key),
-                    new_count,
-                    6 * 60 * 60,
-                );
+            let mut redis = redis.connect().await?;
+
+            let dedup_ttl =
+                parse_var::<u64>("DOWNLOAD_DEDUP_TTL").unwrap_or(DEFAULT_DOWNLOAD_DEDUP_TTL);
+
+            // The window is not extended by later downloads, so repeated downloads are counted
+            // again once it expires
+            let counts = redis
+                .incr_many(DOWNLOADS_NAMESPACE, downloads_keys.iter(), dedup_ttl as i64)
+                .await?;
+
+            for (idx, count) in counts.into_iter().enumerate() {
+                if count > DOWNLOAD_DEDUP_ALLOWANCE {
+                    raw_downloads.remove(&idx);
+                }
             }
-            pipe.query_async(&mut *redis)
-                .await
-                .map_err(DatabaseError::CacheError)?;
 
             let version_ids = raw_downloads
                 .iter()
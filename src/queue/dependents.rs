@@ -0,0 +1,67 @@
+use crate::database::models::ids::{ProjectId, UserId};
+use crate::database::models::notification_item::NotificationBuilder;
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
+use crate::models::ids::VersionId;
+use crate::models::notifications::{DependencyChange, NotificationBody};
+use crate::models::projects::DependencyType;
+use futures::TryStreamExt;
+use itertools::Itertools;
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+/// Notifies the members of every project with a version requiring `project_id`, either by
+/// the project or by one of its versions, that it released or yanked a version. Members can
+/// turn these off through their `dependency_update` preferences
+pub async fn notify_dependents(
+    project_id: crate::models::ids::ProjectId,
+    version_id: VersionId,
+    change: DependencyChange,
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<(), DatabaseError> {
+    let project_id: ProjectId = project_id.into();
+
+    let mut transaction = pool.begin().await?;
+
+    let members = sqlx::query!(
+        "
+        SELECT DISTINCT m.id project_id, tm.user_id
+        FROM dependencies d
+        INNER JOIN versions v ON v.id = d.dependent_id
+        INNER JOIN mods m ON m.id = v.mod_id
+        INNER JOIN team_members tm ON tm.team_id = m.team_id AND tm.accepted
+        LEFT JOIN versions dv ON dv.id = d.dependency_id
+        WHERE d.dependency_type = $2 AND (d.mod_dependency_id = $1 OR dv.mod_id = $1)
+            AND m.id != $1
+        ORDER BY project_id
+        ",
+        project_id as ProjectId,
+        DependencyType::Required.as_str(),
+    )
+    .fetch_many(&mut *transaction)
+    .try_filter_map(|e| async {
+        Ok(e.right()
+            .map(|x| (ProjectId(x.project_id), UserId(x.user_id))))
+    })
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    let members: HashMap<ProjectId, Vec<UserId>> = members.into_iter().into_group_map();
+    for (dependent_id, members) in members {
+        NotificationBuilder {
+            body: NotificationBody::DependencyUpdate {
+                project_id: dependent_id.into(),
+                dependency_id: project_id.into(),
+                version_id,
+                change,
+            },
+        }
+        .insert_many(members, &mut transaction, redis)
+        .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
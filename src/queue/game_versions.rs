@@ -0,0 +1,230 @@
+use crate::database::models::legacy_loader_fields::MinecraftGameVersion;
+use crate::database::models::loader_fields::LoaderFieldEnum;
+use crate::database::redis::RedisPool;
+use crate::util::env::var;
+use crate::util::webhook::send_discord_message;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::HashSet;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VersionIndexingError {
+    #[error("Network error while updating game versions list: {0}")]
+    NetworkError(#[from] reqwest::Error),
+    #[error("Database error while updating game versions list: {0}")]
+    DatabaseError(#[from] crate::database::models::DatabaseError),
+}
+
+/// A version read from a game's upstream manifest
+struct UpstreamVersion {
+    name: String,
+    version_type: &'static str,
+    created: DateTime<Utc>,
+}
+
+/// Where the versions of a game come from. Games without an entry here have their versions
+/// added through the admin API
+#[derive(Clone, Copy, Debug)]
+enum VersionSource {
+    MinecraftJava,
+}
+
+impl VersionSource {
+    const ALL: [VersionSource; 1] = [VersionSource::MinecraftJava];
+
+    async fn fetch(&self) -> Result<Vec<UpstreamVersion>, VersionIndexingError> {
+        match self {
+            VersionSource::MinecraftJava => fetch_minecraft_java_versions().await,
+        }
+    }
+}
+
+/// Adds the versions of every game with an upstream manifest, and updates the ones already
+/// known. Staff are told about new versions in `GAME_VERSIONS_DISCORD_WEBHOOK`, if it is set
+pub async fn sync_game_versions(
+    pool: &sqlx::Pool<sqlx::Postgres>,
+    redis: &RedisPool,
+) -> Result<(), VersionIndexingError> {
+    let mut new_versions = Vec::new();
+
+    for source in VersionSource::ALL {
+        info!("Indexing game versions list for {:?}", source);
+        let versions = source.fetch().await?;
+
+        // Every source only has Minecraft: Java Edition's versions for now, which are stored
+        // in its legacy loader field
+        let known = MinecraftGameVersion::list(None, None, pool, redis)
+            .await?
+            .into_iter()
+            .map(|x| x.version)
+            .collect::<HashSet<_>>();
+
+        for version in versions {
+            MinecraftGameVersion::builder()
+                .version(&version.name)?
+                .version_type(version.version_type)?
+                .created(&version.created)
+                .insert(pool, redis)
+                .await?;
+
+            if !known.contains(&version.name) {
+                new_versions.push(version.name);
+            }
+        }
+
+        if let Some(game_versions) =
+            LoaderFieldEnum::get(MinecraftGameVersion::FIELD_NAME, pool, redis).await?
+        {
+            LoaderFieldEnum::clear_cache(game_versions.id, &game_versions.enum_name, redis).await?;
+        }
+    }
+
+    if !new_versions.is_empty() {
+        info!("Added game versions: {}", new_versions.join(", "));
+
+        if let Some(webhook_url) = var("GAME_VERSIONS_DISCORD_WEBHOOK")
+            .ok()
+            .filter(|x| !x.is_empty())
+        {
+            let result = send_discord_message(
+                &webhook_url,
+                format!("New game versions were added: {}", new_versions.join(", ")),
+            )
+            .await;
+            if let Err(e) = result {
+                warn!("Announcing new game versions failed: {}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct InputFormat<'a> {
+    // latest: LatestFormat,
+    versions: Vec<VersionFormat<'a>>,
+}
+#[derive(Deserialize)]
+struct VersionFormat<'a> {
+    id: String,
+    #[serde(rename = "type")]
+    type_: std::borrow::Cow<'a, str>,
+    #[serde(rename = "releaseTime")]
+    release_time: DateTime<Utc>,
+}
+
+async fn fetch_minecraft_java_versions() -> Result<Vec<UpstreamVersion>, VersionIndexingError> {
+    let input = reqwest::get("https://piston-meta.mojang.com/mc/game/version_manifest_v2.json")
+        .await?
+        .json::<InputFormat>()
+        .await?;
+
+    let mut skipped_versions_count = 0u32;
+    // A list of version names that contains spaces.
+    // Generated using the command
+    // ```sh
+    // curl https://launchermeta.mojang.com/mc/game/version_manifest.json \
+    //      | jq '[.versions[].id | select(contains(" "))]'
+    // ```
+    const HALL_OF_SHAME: [(&str, &str); 12] = [
+        ("1.14.2 Pre-Release 4", "1.14.2-pre4"),
+        ("1.14.2 Pre-Release 3", "1.14.2-pre3"),
+        ("1.14.2 Pre-Release 2", "1.14.2-pre2"),
+        ("1.14.2 Pre-Release 1", "1.14.2-pre1"),
+        ("1.14.1 Pre-Release 2", "1.14.1-pre2"),
+        ("1.14.1 Pre-Release 1", "1.14.1-pre1"),
+        ("1.14 Pre-Release 5", "1.14-pre5"),
+        ("1.14 Pre-Release 4", "1.14-pre4"),
+        ("1.14 Pre-Release 3", "1.14-pre3"),
+        ("1.14 Pre-Release 2", "1.14-pre2"),
+        ("1.14 Pre-Release 1", "1.14-pre1"),
+        ("3D Shareware v1.34", "3D-Shareware-v1.34"),
+    ];
+
+    lazy_static::lazy_static! {
+        /// Mojank for some reason has versions released at the same DateTime. This hardcodes them to fix this,
+        /// as most of our ordering logic is with DateTime
+        static ref HALL_OF_SHAME_2: [(&'static str, chrono::DateTime<chrono::Utc>); 4] = [
+            (
+                "1.4.5",
+                chrono::DateTime::parse_from_rfc3339("2012-12-19T22:00:00+00:00")
+                    .unwrap()
+                    .into(),
+            ),
+            (
+                "1.4.6",
+                chrono::DateTime::parse_from_rfc3339("2012-12-19T22:00:01+00:00")
+                    .unwrap()
+                    .into(),
+            ),
+            (
+                "1.6.3",
+                chrono::DateTime::parse_from_rfc3339("2013-09-13T10:54:41+00:00")
+                    .unwrap()
+                    .into(),
+            ),
+            (
+                "13w37b",
+                chrono::DateTime::parse_from_rfc3339("2013-09-13T10:54:42+00:00")
+                    .unwrap()
+                    .into(),
+            ),
+        ];
+    }
+
+    let mut versions = Vec::new();
+    for version in input.versions.into_iter() {
+        let mut name = version.id;
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        {
+            if let Some((_, alternate)) = HALL_OF_SHAME.iter().find(|(version, _)| name == *version)
+            {
+                name = String::from(*alternate);
+            } else {
+                // We'll deal with these manually
+                skipped_versions_count += 1;
+                continue;
+            }
+        }
+
+        let version_type = match &*version.type_ {
+            "release" => "release",
+            "snapshot" => "snapshot",
+            "old_alpha" => "alpha",
+            "old_beta" => "beta",
+            _ => "other",
+        };
+
+        let created = if let Some((_, alternate)) =
+            HALL_OF_SHAME_2.iter().find(|(version, _)| name == *version)
+        {
+            *alternate
+        } else {
+            version.release_time
+        };
+
+        versions.push(UpstreamVersion {
+            name,
+            version_type,
+            created,
+        });
+    }
+
+    if skipped_versions_count > 0 {
+        // This will currently always trigger due to 1.14 pre releases
+        // and the shareware april fools update. We could set a threshold
+        // that accounts for those versions and update it whenever we
+        // manually fix another version.
+        warn!(
+            "Skipped {} game versions; check for new versions and add them manually",
+            skipped_versions_count
+        );
+    }
+
+    Ok(versions)
+}
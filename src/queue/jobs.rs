@@ -0,0 +1,384 @@
+use crate::auth::email::{send_email_raw, MailError};
+use crate::clickhouse::export_project_metrics;
+use crate::database::models::email_suppression_item::EmailSuppression;
+use crate::database::models::image_flag_item::ImageFlag;
+use crate::database::models::job_item::Job;
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
+use crate::database::ReadOnlyPool;
+use crate::file_hosting::FileHost;
+use crate::models::jobs::JobPayload;
+use crate::queue::dependents::notify_dependents;
+use crate::queue::game_versions::sync_game_versions;
+use crate::queue::payouts::{run_automatic_payout, PayoutsQueue};
+use crate::queue::profile_links::verify_profile_links;
+use crate::queue::project_backup::export_project_backup;
+use crate::queue::project_import::{
+    import_curseforge_project, import_github_releases, ImportError,
+};
+use crate::queue::retention::purge_expired_data;
+use crate::queue::sitemaps::generate_sitemaps;
+use crate::queue::staff_report::send_staff_report;
+use crate::scheduler::ShutdownSignal;
+use crate::search::indexing::index_projects;
+use crate::search::SearchConfig;
+use crate::util::circuit_breaker::breaker;
+use crate::util::curseforge::CurseForgeError;
+use crate::util::image_scan::{self, ImageScanError};
+use crate::util::metrics::{result_label, JOBS_PROCESSED, WEBHOOK_DELIVERIES};
+use crate::util::telemetry::set_parent;
+use crate::util::webhook::send_discord_webhook;
+use crate::webhooks;
+use actix_web::web;
+use bytes::BytesMut;
+use chrono::{Duration, Utc};
+use futures::future::Either;
+use futures::TryStreamExt;
+use log::{info, warn};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::{info_span, Instrument};
+
+/// The longest a failed job waits before its next attempt
+const MAX_BACKOFF_MINUTES: i64 = 6 * 60;
+
+/// Everything jobs need to run
+#[derive(Clone)]
+pub struct JobContext {
+    pub pool: PgPool,
+    pub read_pool: ReadOnlyPool,
+    pub redis: RedisPool,
+    pub search_config: SearchConfig,
+    pub payouts_queue: web::Data<PayoutsQueue>,
+    pub file_host: Arc<dyn FileHost + Send + Sync>,
+    pub shutdown: ShutdownSignal,
+}
+
+#[derive(Debug)]
+enum JobError {
+    /// Failed in a way that may succeed on a later attempt
+    Retry(String),
+    /// Failed in a way that will never succeed, such as an invalid email address
+    Fatal(String),
+}
+
+/// Runs jobs one at a time until none are due. Several workers run side by side, and jobs are
+/// leased while they run, so jobs held by a worker which died are picked up again
+pub async fn run_jobs(ctx: &JobContext) -> Result<(), DatabaseError> {
+    loop {
+        if ctx.shutdown.is_set() {
+            return Ok(());
+        }
+
+        let Some(job) = Job::claim(1, &ctx.pool).await?.into_iter().next() else {
+            return Ok(());
+        };
+
+        let span = info_span!(
+            "job",
+            job.id = job.id,
+            job.kind = %job.kind,
+            job.attempt = job.attempt_count,
+            request_id = tracing::field::Empty
+        );
+        set_parent(&span, &job.trace_context);
+
+        let result = match &job.payload {
+            None => Err(JobError::Fatal(format!("unknown job kind {}", job.kind))),
+            // Only happens if the job's lease ran out on every attempt
+            Some(_) if job.attempt_count > job.max_attempts => Err(JobError::Fatal(
+                "the job timed out too many times".to_string(),
+            )),
+            Some(payload) if payload.is_releasable() => {
+                // On shutdown the run is cancelled, and only once it has stopped is the job
+                // handed back for another instance to run
+                let run = Box::pin(run_job(job.id, payload, ctx).instrument(span));
+                match futures::future::select(run, Box::pin(ctx.shutdown.wait())).await {
+                    Either::Left((result, _)) => result,
+                    Either::Right((_, run)) => {
+                        drop(run);
+                        warn!("Handing back job {} ({}) on shutdown", job.id, job.kind);
+                        Job::release(&[job.id], &ctx.pool).await?;
+                        return Ok(());
+                    }
+                }
+            }
+            Some(payload) => run_job(job.id, payload, ctx).instrument(span).await,
+        };
+
+        match result {
+            Ok(result_url) => {
+                JOBS_PROCESSED
+                    .with_label_values(&[&job.kind, "success"])
+                    .inc();
+                Job::complete(job.id, result_url, &ctx.pool).await?
+            }
+            Err(JobError::Retry(reason)) if job.attempt_count < job.max_attempts => {
+                JOBS_PROCESSED
+                    .with_label_values(&[&job.kind, "retry"])
+                    .inc();
+                warn!("Job {} ({}) failed, retrying: {}", job.id, job.kind, reason);
+                let backoff =
+                    std::cmp::min(2i64.pow(job.attempt_count as u32), MAX_BACKOFF_MINUTES);
+                Job::fail(
+                    job.id,
+                    &reason,
+                    Some(Utc::now() + Duration::minutes(backoff)),
+                    &ctx.pool,
+                )
+                .await?;
+            }
+            Err(JobError::Retry(reason)) | Err(JobError::Fatal(reason)) => {
+                JOBS_PROCESSED.with_label_values(&[&job.kind, "dead"]).inc();
+                warn!(
+                    "Job {} ({}) failed permanently: {}",
+                    job.id, job.kind, reason
+                );
+                Job::fail(job.id, &reason, None, &ctx.pool).await?;
+            }
+        }
+    }
+}
+
+/// Runs a job, returning where its result was put for jobs which produce a file
+async fn run_job(
+    id: i64,
+    payload: &JobPayload,
+    ctx: &JobContext,
+) -> Result<Option<String>, JobError> {
+    match payload {
+        JobPayload::IndexProjects => index_projects(
+            ctx.pool.clone(),
+            ctx.read_pool.clone(),
+            ctx.redis.clone(),
+            &ctx.search_config,
+        )
+        .await
+        .map(|_| None)
+        .map_err(|err| JobError::Retry(err.to_string())),
+        JobPayload::SendEmail { to, subject, body } => {
+            if EmailSuppression::is_suppressed(to, &ctx.pool)
+                .await
+                .map_err(|err| JobError::Retry(err.to_string()))?
+            {
+                info!("Not sending email {} to suppressed address", id);
+                return Ok(None);
+            }
+            let (to, subject, body) = (to.clone(), subject.clone(), body.clone());
+
+            // While the mail server is down, emails wait in the queue without trying it
+            let breaker = breaker("smtp");
+            breaker
+                .check()
+                .map_err(|err| JobError::Retry(err.to_string()))?;
+
+            // Sending over SMTP blocks, so it is kept off of the workers' thread
+            let result = actix_rt::task::spawn_blocking(move || send_email_raw(to, subject, body))
+                .await
+                .map_err(|err| JobError::Retry(err.to_string()))?;
+
+            result.map_err(|err| match err {
+                MailError::Address(_) => JobError::Fatal(err.to_string()),
+                err => {
+                    breaker.record_failure();
+                    JobError::Retry(err.to_string())
+                }
+            })?;
+            breaker.record_success();
+
+            Ok(None)
+        }
+        JobPayload::DiscordWebhook {
+            project_id,
+            channel,
+            message,
+        } => {
+            let webhook_url = dotenvy::var(channel.env_var())
+                .map_err(|_| JobError::Fatal(format!("{} is not configured", channel.env_var())))?;
+
+            let result = send_discord_webhook(
+                *project_id,
+                &ctx.pool,
+                &ctx.redis,
+                webhook_url,
+                message.clone(),
+            )
+            .await;
+            WEBHOOK_DELIVERIES
+                .with_label_values(&["discord", result_label(&result)])
+                .inc();
+
+            result
+                .map(|_| None)
+                .map_err(|err| JobError::Retry(err.to_string()))
+        }
+        JobPayload::AutomaticPayout { user_id } => {
+            run_automatic_payout((*user_id).into(), &ctx.pool, &ctx.redis, &ctx.payouts_queue)
+                .await
+                .map(|_| None)
+                .map_err(|err| JobError::Retry(err.to_string()))
+        }
+        JobPayload::PurgeExpiredData => {
+            // Purging goes through the cached getters generic over `sqlx::Acquire`, whose futures
+            // can't be proven `Send`, so it runs as a local task on the worker's arbiter instead
+            let (pool, redis, file_host) = (
+                ctx.pool.clone(),
+                ctx.redis.clone(),
+                ctx.file_host.clone(),
+            );
+            actix_rt::spawn(async move {
+                purge_expired_data(&pool, &redis, &file_host)
+                    .await
+                    .map_err(|err| err.to_string())
+            })
+            .await
+            .map_err(|err| JobError::Retry(err.to_string()))?
+            .map(|_| None)
+            .map_err(JobError::Retry)
+        }
+        JobPayload::ExportProjectAnalytics {
+            project_id,
+            format,
+            start_date,
+            end_date,
+        } => {
+            let stream = export_project_metrics(*project_id, *start_date, *end_date, *format)
+                .await
+                .map_err(|err| JobError::Retry(err.to_string()))?;
+            let bytes = stream
+                .try_fold(BytesMut::new(), |mut bytes, chunk| async move {
+                    bytes.extend_from_slice(&chunk);
+                    Ok(bytes)
+                })
+                .await
+                .map_err(|err| JobError::Retry(err.to_string()))?;
+            Job::set_progress(id, 0.5, &ctx.pool)
+                .await
+                .map_err(|err| JobError::Retry(err.to_string()))?;
+
+            // The random part keeps exports from being found by guessing their URL
+            let key = ChaCha20Rng::from_entropy()
+                .sample_iter(&Alphanumeric)
+                .take(32)
+                .map(char::from)
+                .collect::<String>();
+            let upload_data = ctx
+                .file_host
+                .upload_file(
+                    format.content_type(),
+                    &format!(
+                        "data/exports/{}/{}-{}.{}",
+                        key,
+                        project_id,
+                        end_date.format("%Y-%m-%d"),
+                        format.extension()
+                    ),
+                    bytes.freeze(),
+                )
+                .await
+                .map_err(|err| JobError::Retry(err.to_string()))?;
+
+            let cdn_url =
+                dotenvy::var("CDN_URL").map_err(|err| JobError::Fatal(err.to_string()))?;
+            Ok(Some(format!("{}/{}", cdn_url, upload_data.file_name)))
+        }
+        JobPayload::ProcessWebhook { webhook_id } => {
+            webhooks::process(*webhook_id, &ctx.pool, &ctx.redis, &ctx.payouts_queue)
+                .await
+                .map(|_| None)
+                .map_err(|err| JobError::Retry(err.to_string()))
+        }
+        JobPayload::ScanImage { image_url, source } => {
+            let labels = image_scan::scan_image(image_url)
+                .await
+                .map_err(|err| match err {
+                    ImageScanError::NotConfigured => JobError::Fatal(err.to_string()),
+                    err => JobError::Retry(err.to_string()),
+                })?;
+
+            if image_scan::should_flag(&labels) {
+                ImageFlag::insert(image_url, *source, &labels, &ctx.pool)
+                    .await
+                    .map_err(|err| JobError::Retry(err.to_string()))?;
+            }
+
+            Ok(None)
+        }
+        JobPayload::SyncGameVersions => sync_game_versions(&ctx.pool, &ctx.redis)
+            .await
+            .map(|_| None)
+            .map_err(|err| JobError::Retry(err.to_string())),
+        JobPayload::ImportCurseForgeProject {
+            project_id,
+            user_id,
+            curseforge_id,
+            rehost_files,
+        } => import_curseforge_project(
+            *project_id,
+            *user_id,
+            *curseforge_id,
+            *rehost_files,
+            &ctx.pool,
+            &ctx.redis,
+            &*ctx.file_host,
+        )
+        .await
+        .map(|_| None)
+        .map_err(|err| match err {
+            ImportError::CurseForge(CurseForgeError::NotConfigured) => {
+                JobError::Fatal(err.to_string())
+            }
+            err => JobError::Retry(err.to_string()),
+        }),
+        JobPayload::ImportGitHubReleases {
+            project_id,
+            release_id,
+        } => import_github_releases(
+            *project_id,
+            *release_id,
+            &ctx.pool,
+            &ctx.redis,
+            &*ctx.file_host,
+        )
+        .await
+        .map(|_| None)
+        .map_err(|err| JobError::Retry(err.to_string())),
+        JobPayload::GenerateSitemaps => generate_sitemaps(&ctx.pool, &ctx.file_host)
+            .await
+            .map(|_| None)
+            .map_err(|err| JobError::Retry(err.to_string())),
+        JobPayload::ExportProjectBackup { project_id } => {
+            let result_url =
+                export_project_backup(id, *project_id, &ctx.pool, &ctx.redis, &*ctx.file_host)
+                    .await
+                    .map_err(|err| JobError::Retry(err.to_string()))?;
+
+            match result_url {
+                Some(url) => Ok(Some(url)),
+                None => Err(JobError::Fatal("the project no longer exists".to_string())),
+            }
+        }
+        JobPayload::VerifyProfileLinks { user_id } => {
+            verify_profile_links((*user_id).into(), &ctx.pool, &ctx.redis)
+                .await
+                .map(|_| None)
+                .map_err(|err| JobError::Retry(err.to_string()))
+        }
+        JobPayload::SendStaffReport => send_staff_report(&ctx.pool)
+            .await
+            .map(|_| None)
+            .map_err(|err| JobError::Retry(err.to_string())),
+        JobPayload::NotifyDependents {
+            project_id,
+            version_id,
+            change,
+        } => notify_dependents(*project_id, *version_id, *change, &ctx.pool, &ctx.redis)
+            .await
+            .map(|_| None)
+            .map_err(|err| JobError::Retry(err.to_string())),
+    }
+}
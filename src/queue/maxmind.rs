@@ -1,41 +1,70 @@
 use flate2::read::GzDecoder;
 use log::warn;
-use maxminddb::geoip2::Country;
+use maxminddb::geoip2::{Asn, Country};
 use std::io::{Cursor, Read};
 use std::net::Ipv6Addr;
 use tar::Archive;
 use tokio::sync::RwLock;
 
+#[derive(Default, Clone, Debug)]
+pub struct GeoLocation {
+    /// ISO 3166-1 alpha-2 country code
+    pub country: String,
+    /// Two letter continent code
+    pub continent: String,
+}
+
+const COUNTRY_EDITION: &str = "GeoLite2-Country";
+const ASN_EDITION: &str = "GeoLite2-ASN";
+
 pub struct MaxMindIndexer {
     pub reader: RwLock<Option<maxminddb::Reader<Vec<u8>>>>,
+    pub asn_reader: RwLock<Option<maxminddb::Reader<Vec<u8>>>>,
 }
 
 impl MaxMindIndexer {
     pub async fn new() -> Result<Self, reqwest::Error> {
-        let reader = MaxMindIndexer::inner_index(false).await.ok().flatten();
+        let reader = MaxMindIndexer::inner_index(COUNTRY_EDITION, false)
+            .await
+            .ok()
+            .flatten();
+        let asn_reader = MaxMindIndexer::inner_index(ASN_EDITION, false)
+            .await
+            .ok()
+            .flatten();
 
         Ok(MaxMindIndexer {
             reader: RwLock::new(reader),
+            asn_reader: RwLock::new(asn_reader),
         })
     }
 
     pub async fn index(&self) -> Result<(), reqwest::Error> {
-        let reader = MaxMindIndexer::inner_index(false).await?;
+        let reader = MaxMindIndexer::inner_index(COUNTRY_EDITION, false).await?;
 
         if let Some(reader) = reader {
             let mut reader_new = self.reader.write().await;
             *reader_new = Some(reader);
         }
 
+        let asn_reader = MaxMindIndexer::inner_index(ASN_EDITION, false).await?;
+
+        if let Some(asn_reader) = asn_reader {
+            let mut reader_new = self.asn_reader.write().await;
+            *reader_new = Some(asn_reader);
+        }
+
         Ok(())
     }
 
     async fn inner_index(
+        edition: &str,
         should_panic: bool,
     ) -> Result<Option<maxminddb::Reader<Vec<u8>>>, reqwest::Error> {
         let response = reqwest::get(
             format!(
-                "https://download.maxmind.com/app/geoip_download?edition_id=GeoLite2-Country&license_key={}&suffix=tar.gz",
+                "https://download.maxmind.com/app/geoip_download?edition_id={}&license_key={}&suffix=tar.gz",
+                edition,
                 dotenvy::var("MAXMIND_LICENSE_KEY").unwrap()
             )
         ).await?.bytes().await.unwrap().to_vec();
@@ -61,20 +90,45 @@ impl MaxMindIndexer {
         if should_panic {
             panic!("Unable to download maxmind database- did you get a license key?")
         } else {
-            warn!("Unable to download maxmind database.");
+            warn!("Unable to download maxmind {} database.", edition);
 
             Ok(None)
         }
     }
 
-    pub async fn query(&self, ip: Ipv6Addr) -> Option<String> {
+    /// Looks up the coarse location of an IP. Unknown parts of the location are left empty
+    pub async fn query(&self, ip: Ipv6Addr) -> GeoLocation {
         let maxmind = self.reader.read().await;
 
+        let country = if let Some(ref maxmind) = *maxmind {
+            maxmind.lookup::<Country>(ip.into()).ok()
+        } else {
+            None
+        };
+
+        GeoLocation {
+            country: country
+                .as_ref()
+                .and_then(|x| x.country.as_ref().and_then(|x| x.iso_code))
+                .unwrap_or_default()
+                .to_string(),
+            continent: country
+                .as_ref()
+                .and_then(|x| x.continent.as_ref().and_then(|x| x.code))
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+
+    /// Looks up the number of the autonomous system (network operator) an IP belongs to
+    pub async fn query_asn(&self, ip: Ipv6Addr) -> Option<u32> {
+        let maxmind = self.asn_reader.read().await;
+
         if let Some(ref maxmind) = *maxmind {
             maxmind
-                .lookup::<Country>(ip.into())
+                .lookup::<Asn>(ip.into())
                 .ok()
-                .and_then(|x| x.country.and_then(|x| x.iso_code.map(|x| x.to_string())))
+                .and_then(|x| x.autonomous_system_number)
         } else {
             None
         }
@@ -1,5 +1,17 @@
 pub mod analytics;
+pub mod dependents;
+pub mod game_versions;
+pub mod jobs;
 pub mod maxmind;
+pub mod moderation;
+pub mod notifications;
 pub mod payouts;
+pub mod profile_links;
+pub mod project_backup;
+pub mod project_import;
+pub mod retention;
 pub mod session;
+pub mod similar_projects;
+pub mod sitemaps;
 pub mod socket;
+pub mod staff_report;
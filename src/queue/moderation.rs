@@ -0,0 +1,126 @@
+use crate::models::ids::{ProjectId, UserId};
+use crate::models::moderation::ModerationEvent;
+use crate::models::projects::ProjectStatus;
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::Deserialize;
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use tokio::sync::broadcast;
+
+/// The Postgres channel the moderation queue triggers notify
+const CHANNEL: &str = "moderation_queue";
+/// Events kept for moderators who are slow to read them, before they miss some
+const CAPACITY: usize = 256;
+/// How long to wait before listening again after the connection was lost
+const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Fans out changes to the moderation queue to the moderators watching it. Changes are
+/// notified by database triggers, so every instance sees the changes made by all of them
+pub struct ModerationEvents {
+    sender: broadcast::Sender<ModerationEvent>,
+}
+
+impl Default for ModerationEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A notification as sent by the `notify_moderation_queue` trigger, with numeric IDs
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Notification {
+    Submitted {
+        project_id: i64,
+    },
+    Claimed {
+        project_id: i64,
+        user_id: i64,
+        expires: DateTime<Utc>,
+    },
+    Unclaimed {
+        project_id: i64,
+    },
+    StatusChanged {
+        project_id: i64,
+        old_status: String,
+        new_status: String,
+    },
+    Removed {
+        project_id: i64,
+    },
+}
+
+impl From<Notification> for ModerationEvent {
+    fn from(notification: Notification) -> Self {
+        let project = |id: i64| ProjectId(id as u64);
+        match notification {
+            Notification::Submitted { project_id } => ModerationEvent::Submitted {
+                project_id: project(project_id),
+            },
+            Notification::Claimed {
+                project_id,
+                user_id,
+                expires,
+            } => ModerationEvent::Claimed {
+                project_id: project(project_id),
+                user_id: UserId(user_id as u64),
+                expires,
+            },
+            Notification::Unclaimed { project_id } => ModerationEvent::Unclaimed {
+                project_id: project(project_id),
+            },
+            Notification::StatusChanged {
+                project_id,
+                old_status,
+                new_status,
+            } => ModerationEvent::StatusChanged {
+                project_id: project(project_id),
+                old_status: ProjectStatus::from_string(&old_status),
+                new_status: ProjectStatus::from_string(&new_status),
+            },
+            Notification::Removed { project_id } => ModerationEvent::Removed {
+                project_id: project(project_id),
+            },
+        }
+    }
+}
+
+impl ModerationEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        ModerationEvents { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ModerationEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Listens for changes to the queue for as long as the instance runs, listening again
+    /// whenever the connection is lost
+    pub async fn listen(&self, pool: PgPool) {
+        loop {
+            if let Err(e) = self.listen_once(&pool).await {
+                warn!("Listening for moderation queue changes failed: {:?}", e);
+            }
+            actix_rt::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn listen_once(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(CHANNEL).await?;
+
+        loop {
+            let notification = listener.recv().await?;
+            match serde_json::from_str::<Notification>(notification.payload()) {
+                // Sending only fails when no moderator is watching
+                Ok(notification) => {
+                    let _ = self.sender.send(notification.into());
+                }
+                Err(e) => warn!("Invalid moderation queue notification: {:?}", e),
+            }
+        }
+    }
+}
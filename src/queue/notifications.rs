@@ -0,0 +1,252 @@
+use crate::database::models::notification_delivery_item::NotificationDelivery;
+use crate::database::models::notification_item::Notification as DBNotification;
+use crate::database::models::notification_webhook_item::NotificationWebhook;
+use crate::database::models::push_subscription_item::PushSubscription;
+use crate::database::models::simple_push_item::SimplePushTarget;
+use crate::database::models::{DatabaseError, NotificationId};
+use crate::models::notifications::{Notification, NotificationChannel, NotificationDeliveryStatus};
+use crate::util::metrics::{result_label, WEBHOOK_DELIVERIES};
+use crate::util::public_url::public_client;
+use crate::util::simple_push::send_simple_push;
+use crate::util::webhook::send_notification_webhook;
+use crate::util::webpush::{send_web_push, WebPushError, WebPushMessage};
+use chrono::{Duration, Utc};
+use itertools::Itertools;
+use log::warn;
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+/// Maximum number of deliveries processed per run
+const DELIVERY_BATCH_SIZE: i64 = 100;
+/// How long claimed deliveries are held by a run before another run may retry them
+const DELIVERY_LEASE_MINUTES: i64 = 10;
+/// Deliveries are abandoned after this many failed attempts
+const MAX_DELIVERY_ATTEMPTS: i32 = 8;
+
+#[derive(Debug)]
+enum DeliveryOutcome {
+    Delivered,
+    /// Failed in a way that may succeed on a later attempt
+    Retry(String),
+    /// Failed in a way that will never succeed, such as a revoked destination
+    Abandon(String),
+}
+
+/// Sends all due notification deliveries through their channels, rescheduling failed
+/// deliveries with exponential backoff. Deliveries are claimed in a short transaction and
+/// sent outside of it, so no rows stay locked while waiting on remote servers
+pub async fn process_notification_deliveries(pool: &PgPool) -> Result<(), DatabaseError> {
+    // Deliveries go to user-supplied URLs, so they must only reach public addresses
+    let client = match public_client() {
+        Ok(client) => client,
+        Err(err) => {
+            warn!("Could not build notification delivery client: {}", err);
+            return Ok(());
+        }
+    };
+
+    let mut transaction = pool.begin().await?;
+    let deliveries = NotificationDelivery::claim_due(
+        DELIVERY_BATCH_SIZE,
+        Utc::now() + Duration::minutes(DELIVERY_LEASE_MINUTES),
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    if deliveries.is_empty() {
+        return Ok(());
+    }
+
+    let notification_ids = deliveries
+        .iter()
+        .map(|x| x.notification_id)
+        .unique_by(|x| x.0)
+        .collect::<Vec<NotificationId>>();
+    let notifications: HashMap<i64, Notification> =
+        DBNotification::get_many(&notification_ids, pool)
+            .await?
+            .into_iter()
+            .map(|x| (x.id.0, Notification::from(x)))
+            .collect();
+
+    let web_push_users = deliveries
+        .iter()
+        .filter(|x| x.channel == NotificationChannel::WebPush)
+        .map(|x| x.user_id)
+        .unique()
+        .collect::<Vec<_>>();
+    let push_subscriptions = PushSubscription::get_many_user(&web_push_users, pool).await?;
+
+    let webhook_users = deliveries
+        .iter()
+        .filter(|x| x.channel == NotificationChannel::Webhook)
+        .map(|x| x.user_id)
+        .unique()
+        .collect::<Vec<_>>();
+    let webhooks = NotificationWebhook::get_many_user(&webhook_users, pool).await?;
+
+    let simple_push_users = deliveries
+        .iter()
+        .filter(|x| x.channel == NotificationChannel::SimplePush)
+        .map(|x| x.user_id)
+        .unique()
+        .collect::<Vec<_>>();
+    let simple_push_targets = SimplePushTarget::get_many_user(&simple_push_users, pool).await?;
+
+    let mut used_endpoints = Vec::new();
+    let mut expired_endpoints = Vec::new();
+    let mut used_webhooks = Vec::new();
+    let mut used_simple_push_targets = Vec::new();
+    let mut outcomes = Vec::with_capacity(deliveries.len());
+
+    for mut delivery in deliveries {
+        let leased_until = delivery.next_attempt;
+        let outcome = if let Some(notification) = notifications.get(&delivery.notification_id.0) {
+            match delivery.channel {
+                NotificationChannel::Site => {
+                    DeliveryOutcome::Abandon("site notifications are not delivered".to_string())
+                }
+                NotificationChannel::WebPush => {
+                    deliver_web_push(
+                        &client,
+                        notification,
+                        push_subscriptions
+                            .iter()
+                            .filter(|x| x.user_id == delivery.user_id),
+                        &mut used_endpoints,
+                        &mut expired_endpoints,
+                    )
+                    .await
+                }
+                NotificationChannel::Webhook => {
+                    if let Some(webhook) = webhooks.iter().find(|x| x.user_id == delivery.user_id) {
+                        let result =
+                            send_notification_webhook(&client, webhook, notification).await;
+                        WEBHOOK_DELIVERIES
+                            .with_label_values(&["notification", result_label(&result)])
+                            .inc();
+
+                        match result {
+                            Ok(()) => {
+                                used_webhooks.push(webhook.user_id);
+                                DeliveryOutcome::Delivered
+                            }
+                            Err(err) if err.is_retryable() => {
+                                DeliveryOutcome::Retry(err.to_string())
+                            }
+                            Err(err) => DeliveryOutcome::Abandon(err.to_string()),
+                        }
+                    } else {
+                        DeliveryOutcome::Abandon("user has no webhook".to_string())
+                    }
+                }
+                NotificationChannel::SimplePush => {
+                    if let Some(target) = simple_push_targets
+                        .iter()
+                        .find(|x| x.user_id == delivery.user_id)
+                    {
+                        match send_simple_push(&client, target, notification).await {
+                            Ok(()) => {
+                                used_simple_push_targets.push(target.user_id);
+                                DeliveryOutcome::Delivered
+                            }
+                            Err(err) if err.is_retryable() => {
+                                DeliveryOutcome::Retry(err.to_string())
+                            }
+                            Err(err) => DeliveryOutcome::Abandon(err.to_string()),
+                        }
+                    } else {
+                        DeliveryOutcome::Abandon("user has no simple push target".to_string())
+                    }
+                }
+            }
+        } else {
+            DeliveryOutcome::Abandon("notification no longer exists".to_string())
+        };
+
+        delivery.attempt_count += 1;
+        match outcome {
+            DeliveryOutcome::Delivered => {
+                delivery.status = NotificationDeliveryStatus::Delivered;
+            }
+            DeliveryOutcome::Retry(reason) if delivery.attempt_count < MAX_DELIVERY_ATTEMPTS => {
+                warn!(
+                    "Delivering notification {} through {} failed, retrying: {}",
+                    delivery.notification_id.0,
+                    delivery.channel.as_str(),
+                    reason
+                );
+                delivery.next_attempt =
+                    Utc::now() + Duration::minutes(2i64.pow(delivery.attempt_count as u32));
+            }
+            DeliveryOutcome::Retry(reason) | DeliveryOutcome::Abandon(reason) => {
+                warn!(
+                    "Delivering notification {} through {} failed permanently: {}",
+                    delivery.notification_id.0,
+                    delivery.channel.as_str(),
+                    reason
+                );
+                delivery.status = NotificationDeliveryStatus::Failed;
+            }
+        }
+
+        outcomes.push((delivery, leased_until));
+    }
+
+    let mut transaction = pool.begin().await?;
+    for (delivery, leased_until) in outcomes {
+        delivery.update(leased_until, &mut transaction).await?;
+    }
+
+    PushSubscription::mark_used(&used_endpoints, &mut transaction).await?;
+    PushSubscription::remove_many(&expired_endpoints, None, &mut transaction).await?;
+    NotificationWebhook::mark_used(&used_webhooks, &mut transaction).await?;
+    SimplePushTarget::mark_used(&used_simple_push_targets, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+async fn deliver_web_push(
+    client: &reqwest::Client,
+    notification: &Notification,
+    subscriptions: impl Iterator<Item = &PushSubscription>,
+    used_endpoints: &mut Vec<String>,
+    expired_endpoints: &mut Vec<String>,
+) -> DeliveryOutcome {
+    let message = WebPushMessage {
+        notification_id: notification.id.to_string(),
+        title: notification.name.clone(),
+        body: notification.text.clone(),
+        url: format!(
+            "{}{}",
+            dotenvy::var("SITE_URL").unwrap_or_default(),
+            notification.link
+        ),
+    };
+
+    let mut delivered = false;
+    let mut last_error = None;
+    for subscription in subscriptions {
+        match send_web_push(client, subscription, &message).await {
+            Ok(()) => {
+                delivered = true;
+                used_endpoints.push(subscription.endpoint.clone());
+            }
+            Err(WebPushError::Expired | WebPushError::UnknownService) => {
+                expired_endpoints.push(subscription.endpoint.clone());
+            }
+            Err(err) => last_error = Some(err.to_string()),
+        }
+    }
+
+    if delivered {
+        DeliveryOutcome::Delivered
+    } else if let Some(err) = last_error {
+        DeliveryOutcome::Retry(err)
+    } else {
+        DeliveryOutcome::Abandon("user has no active push subscriptions".to_string())
+    }
+}
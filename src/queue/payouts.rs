@@ -1,15 +1,28 @@
+use crate::auth::email::send_email;
+use crate::auth::email::template::{EmailTemplate, DEFAULT_LOCALE};
+use crate::database::models::job_item::Job;
+use crate::database::models::notification_item::NotificationBuilder;
+use crate::database::models::payout_schedule_item::PayoutSchedule;
 use crate::models::ids::UserId;
+use crate::models::jobs::JobPayload;
+use crate::models::notifications::NotificationBody;
 use crate::models::payouts::{
-    PayoutDecimal, PayoutInterval, PayoutMethod, PayoutMethodFee, PayoutMethodType,
+    EnrollmentStatus, PayoutDecimal, PayoutInterval, PayoutMethod, PayoutMethodType, PayoutStatus,
 };
+use crate::payouts::{
+    PayPalProvider, PayoutEvent, PayoutProvider, StripeProvider, TremendousProvider,
+};
+use crate::routes::v3::payouts::{withdraw, Withdrawal};
 use crate::routes::ApiError;
 use crate::util::env::parse_var;
+use crate::util::money::{base_to_minor_units, currency_exponent, Money, BASE_CURRENCY};
 use crate::{database::redis::RedisPool, models::projects::MonetizationStatus};
-use base64::Engine;
 use chrono::{DateTime, Datelike, Duration, Utc, Weekday};
 use dashmap::DashMap;
+use itertools::Itertools;
+use log::warn;
 use reqwest::Method;
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -20,21 +33,22 @@ use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
 
 pub struct PayoutsQueue {
-    credential: RwLock<Option<PayPalCredentials>>,
+    providers: Vec<Arc<dyn PayoutProvider>>,
     payout_options: RwLock<Option<PayoutMethods>>,
+    fx_rates: RwLock<Option<FxRates>>,
     payouts_locks: DashMap<UserId, Arc<Mutex<()>>>,
 }
 
 #[derive(Clone)]
-struct PayPalCredentials {
-    access_token: String,
-    token_type: String,
+struct PayoutMethods {
+    options: Vec<PayoutMethod>,
     expires: DateTime<Utc>,
 }
 
+/// How much one USD is worth in each currency
 #[derive(Clone)]
-struct PayoutMethods {
-    options: Vec<PayoutMethod>,
+struct FxRates {
+    rates: HashMap<String, Decimal>,
     expires: DateTime<Utc>,
 }
 
@@ -46,216 +60,154 @@ impl Default for PayoutsQueue {
 // Batches payouts and handles token refresh
 impl PayoutsQueue {
     pub fn new() -> Self {
+        Self::with_providers(vec![
+            Arc::new(PayPalProvider::new()),
+            Arc::new(StripeProvider::new()),
+            Arc::new(TremendousProvider::new()),
+        ])
+    }
+
+    /// Creates a queue paying out through the given providers. Payout methods are listed in
+    /// the order of their providers
+    pub fn with_providers(providers: Vec<Arc<dyn PayoutProvider>>) -> Self {
         PayoutsQueue {
-            credential: RwLock::new(None),
+            providers,
             payout_options: RwLock::new(None),
+            fx_rates: RwLock::new(None),
             payouts_locks: DashMap::new(),
         }
     }
 
-    async fn refresh_token(&self) -> Result<PayPalCredentials, ApiError> {
-        let mut creds = self.credential.write().await;
-        let client = reqwest::Client::new();
-
-        let combined_key = format!(
-            "{}:{}",
-            dotenvy::var("PAYPAL_CLIENT_ID")?,
-            dotenvy::var("PAYPAL_CLIENT_SECRET")?
-        );
-        let formatted_key = format!(
-            "Basic {}",
-            base64::engine::general_purpose::STANDARD.encode(combined_key)
-        );
-
-        let mut form = HashMap::new();
-        form.insert("grant_type", "client_credentials");
-
-        #[derive(Deserialize)]
-        struct PaypalCredential {
-            access_token: String,
-            token_type: String,
-            expires_in: i64,
-        }
-
-        let credential: PaypalCredential = client
-            .post(&format!("{}oauth2/token", dotenvy::var("PAYPAL_API_URL")?))
-            .header("Accept", "application/json")
-            .header("Accept-Language", "en_US")
-            .header("Authorization", formatted_key)
-            .form(&form)
-            .send()
-            .await
-            .map_err(|_| ApiError::Payments("Error while authenticating with PayPal".to_string()))?
-            .json()
-            .await
-            .map_err(|_| {
-                ApiError::Payments(
-                    "Error while authenticating with PayPal (deser error)".to_string(),
-                )
-            })?;
-
-        let new_creds = PayPalCredentials {
-            access_token: credential.access_token,
-            token_type: credential.token_type,
-            expires: Utc::now() + Duration::seconds(credential.expires_in),
-        };
-
-        *creds = Some(new_creds.clone());
+    /// Gets the provider paying out through a method type. Later providers take precedence, so
+    /// tests can replace a default provider
+    pub fn provider(&self, method_type: PayoutMethodType) -> Option<Arc<dyn PayoutProvider>> {
+        self.providers
+            .iter()
+            .rev()
+            .find(|x| x.method_types().contains(&method_type))
+            .cloned()
+    }
 
-        Ok(new_creds)
+    pub fn provider_by_name(&self, name: &str) -> Option<Arc<dyn PayoutProvider>> {
+        self.providers.iter().find(|x| x.name() == name).cloned()
     }
 
-    pub async fn make_paypal_request<T: Serialize, X: DeserializeOwned>(
+    pub async fn make_tax_request<T: Serialize, X: DeserializeOwned>(
         &self,
         method: Method,
         path: &str,
         body: Option<T>,
-        raw_text: Option<String>,
-        no_api_prefix: Option<bool>,
     ) -> Result<X, ApiError> {
-        let read = self.credential.read().await;
-        let credentials = if let Some(credentials) = read.as_ref() {
-            if credentials.expires < Utc::now() {
-                drop(read);
-                self.refresh_token().await.map_err(|_| {
-                    ApiError::Payments("Error while authenticating with PayPal".to_string())
-                })?
-            } else {
-                credentials.clone()
-            }
-        } else {
-            drop(read);
-            self.refresh_token().await.map_err(|_| {
-                ApiError::Payments("Error while authenticating with PayPal".to_string())
-            })?
-        };
-
         let client = reqwest::Client::new();
         let mut request = client
-            .request(
-                method,
-                if no_api_prefix.unwrap_or(false) {
-                    path.to_string()
-                } else {
-                    format!("{}{path}", dotenvy::var("PAYPAL_API_URL")?)
-                },
-            )
-            .header(
-                "Authorization",
-                format!("{} {}", credentials.token_type, credentials.access_token),
-            );
+            .request(method, format!("{}{path}", dotenvy::var("TAX_API_URL")?))
+            .bearer_auth(dotenvy::var("TAX_API_KEY")?);
 
         if let Some(body) = body {
             request = request.json(&body);
-        } else if let Some(body) = raw_text {
-            request = request
-                .header(reqwest::header::CONTENT_TYPE, "application/json")
-                .body(body);
         }
 
-        let resp = request
-            .send()
-            .await
-            .map_err(|_| ApiError::Payments("could not communicate with PayPal".to_string()))?;
+        let resp = request.send().await.map_err(|_| {
+            ApiError::Payments("could not communicate with the tax form provider".to_string())
+        })?;
 
         let status = resp.status();
 
         let value = resp.json::<Value>().await.map_err(|_| {
-            ApiError::Payments("could not retrieve PayPal response body".to_string())
+            ApiError::Payments("could not retrieve tax form provider response body".to_string())
         })?;
 
         if !status.is_success() {
             #[derive(Deserialize)]
-            struct PayPalError {
-                pub name: String,
-                pub message: String,
-            }
-
-            #[derive(Deserialize)]
-            struct PayPalIdentityError {
-                pub error: String,
-                pub error_description: String,
-            }
-
-            if let Ok(error) = serde_json::from_value::<PayPalError>(value.clone()) {
-                return Err(ApiError::Payments(format!(
-                    "error name: {}, message: {}",
-                    error.name, error.message
-                )));
+            struct TaxError {
+                message: String,
             }
 
-            if let Ok(error) = serde_json::from_value::<PayPalIdentityError>(value) {
-                return Err(ApiError::Payments(format!(
-                    "error name: {}, message: {}",
-                    error.error, error.error_description
-                )));
-            }
+            let err = serde_json::from_value::<TaxError>(value).map_err(|_| {
+                ApiError::Payments("could not retrieve tax form provider error body".to_string())
+            })?;
 
-            return Err(ApiError::Payments(
-                "could not retrieve PayPal error body".to_string(),
-            ));
+            return Err(ApiError::Payments(err.message));
         }
 
         Ok(serde_json::from_value(value)?)
     }
 
-    pub async fn make_tremendous_request<T: Serialize, X: DeserializeOwned>(
-        &self,
-        method: Method,
-        path: &str,
-        body: Option<T>,
-    ) -> Result<X, ApiError> {
-        let client = reqwest::Client::new();
-        let mut request = client
-            .request(
-                method,
-                format!("{}{path}", dotenvy::var("TREMENDOUS_API_URL")?),
-            )
-            .header(
-                "Authorization",
-                format!("Bearer {}", dotenvy::var("TREMENDOUS_API_KEY")?),
-            );
-
-        if let Some(body) = body {
-            request = request.json(&body);
+    /// Gets how much one USD is worth in `currency`, from the FX rate provider. Rates are cached
+    /// for 15 minutes, while quotes lock a rate in for `FX_QUOTE_LOCK_MINUTES`
+    pub async fn get_fx_rate(&self, currency: &str) -> Result<Decimal, ApiError> {
+        let currency = currency.to_uppercase();
+        if currency == BASE_CURRENCY {
+            return Ok(Decimal::ONE);
         }
 
-        let resp = request
-            .send()
-            .await
-            .map_err(|_| ApiError::Payments("could not communicate with Tremendous".to_string()))?;
+        if currency_exponent(&currency).is_none() {
+            return Err(ApiError::InvalidInput(format!(
+                "Unsupported currency: {currency}"
+            )));
+        }
 
-        let status = resp.status();
+        async fn refresh_fx_rates(queue: &PayoutsQueue) -> Result<FxRates, ApiError> {
+            let mut fx_rates = queue.fx_rates.write().await;
 
-        let value = resp.json::<Value>().await.map_err(|_| {
-            ApiError::Payments("could not retrieve Tremendous response body".to_string())
-        })?;
+            #[derive(Deserialize)]
+            struct RatesResponse {
+                rates: HashMap<String, PayoutDecimal>,
+            }
 
-        if !status.is_success() {
-            if let Some(obj) = value.as_object() {
-                if let Some(array) = obj.get("errors") {
-                    #[derive(Deserialize)]
-                    struct TremendousError {
-                        message: String,
-                    }
+            let response = reqwest::Client::new()
+                .get(format!(
+                    "{}latest?base={BASE_CURRENCY}",
+                    dotenvy::var("FX_API_URL")?
+                ))
+                .bearer_auth(dotenvy::var("FX_API_KEY")?)
+                .send()
+                .await
+                .and_then(|x| x.error_for_status())
+                .map_err(|_| {
+                    ApiError::Payments(
+                        "could not communicate with the FX rate provider".to_string(),
+                    )
+                })?
+                .json::<RatesResponse>()
+                .await
+                .map_err(|_| {
+                    ApiError::Payments("could not retrieve FX rate provider response".to_string())
+                })?;
+
+            let new_rates = FxRates {
+                rates: response
+                    .rates
+                    .into_iter()
+                    .map(|(currency, rate)| (currency.to_uppercase(), rate.0))
+                    .collect(),
+                expires: Utc::now() + Duration::minutes(15),
+            };
 
-                    let err =
-                        serde_json::from_value::<TremendousError>(array.clone()).map_err(|_| {
-                            ApiError::Payments(
-                                "could not retrieve Tremendous error json body".to_string(),
-                            )
-                        })?;
+            *fx_rates = Some(new_rates.clone());
 
-                    return Err(ApiError::Payments(err.message));
-                }
+            Ok(new_rates)
+        }
 
-                return Err(ApiError::Payments(
-                    "could not retrieve Tremendous error body".to_string(),
-                ));
+        let read = self.fx_rates.read().await;
+        let rates = if let Some(rates) = read.as_ref() {
+            if rates.expires < Utc::now() {
+                drop(read);
+                refresh_fx_rates(self).await?
+            } else {
+                rates.clone()
             }
-        }
+        } else {
+            drop(read);
+            refresh_fx_rates(self).await?
+        };
 
-        Ok(serde_json::from_value(value)?)
+        rates
+            .rates
+            .get(&currency)
+            .copied()
+            .filter(|x| *x > Decimal::ZERO)
+            .ok_or_else(|| ApiError::Payments(format!("No exchange rate available for {currency}")))
     }
 
     pub async fn get_payout_methods(&self) -> Result<Vec<PayoutMethod>, ApiError> {
@@ -263,224 +215,14 @@ impl PayoutsQueue {
             let mut options = queue.payout_options.write().await;
 
             let mut methods = Vec::new();
-
-            #[derive(Deserialize)]
-            pub struct Sku {
-                pub min: Decimal,
-                pub max: Decimal,
-            }
-
-            #[derive(Deserialize, Eq, PartialEq)]
-            #[serde(rename_all = "snake_case")]
-            pub enum ProductImageType {
-                Card,
-                Logo,
-            }
-
-            #[derive(Deserialize)]
-            pub struct ProductImage {
-                pub src: String,
-                #[serde(rename = "type")]
-                pub type_: ProductImageType,
-            }
-
-            #[derive(Deserialize)]
-            pub struct ProductCountry {
-                pub abbr: String,
-            }
-
-            #[derive(Deserialize)]
-            pub struct Product {
-                pub id: String,
-                pub category: String,
-                pub name: String,
-                pub description: String,
-                pub disclosure: String,
-                pub skus: Vec<Sku>,
-                pub currency_codes: Vec<String>,
-                pub countries: Vec<ProductCountry>,
-                pub images: Vec<ProductImage>,
-            }
-
-            #[derive(Deserialize)]
-            pub struct TremendousResponse {
-                pub products: Vec<Product>,
-            }
-
-            let response = queue
-                .make_tremendous_request::<(), TremendousResponse>(Method::GET, "products", None)
-                .await?;
-
-            for product in response.products {
-                const BLACKLISTED_IDS: &[&str] = &[
-                    // physical visa
-                    "A2J05SWPI2QG",
-                    // crypto
-                    "1UOOSHUUYTAM",
-                    "5EVJN47HPDFT",
-                    "NI9M4EVAVGFJ",
-                    "VLY29QHTMNGT",
-                    "7XU98H109Y3A",
-                    "0CGEDFP2UIKV",
-                    "PDYLQU0K073Y",
-                    "HCS5Z7O2NV5G",
-                    "IY1VMST1MOXS",
-                    "VRPZLJ7HCA8X",
-                    // bitcard (crypto)
-                    "GWQQS5RM8IZS",
-                    "896MYD4SGOGZ",
-                    "PWLEN1VZGMZA",
-                    "A2VRM96J5K5W",
-                    "HV9ICIM3JT7P",
-                    "K2KLSPVWC2Q4",
-                    "HRBRQLLTDF95",
-                    "UUBYLZVK7QAB",
-                    "BH8W3XEDEOJN",
-                    "7WGE043X1RYQ",
-                    "2B13MHUZZVTF",
-                    "JN6R44P86EYX",
-                    "DA8H43GU84SO",
-                    "QK2XAQHSDEH4",
-                    "J7K1IQFS76DK",
-                    "NL4JQ2G7UPRZ",
-                    "OEFTMSBA5ELH",
-                    "A3CQK6UHNV27",
-                ];
-                const SUPPORTED_METHODS: &[&str] =
-                    &["merchant_cards", "visa", "bank", "ach", "visa_card"];
-
-                if !SUPPORTED_METHODS.contains(&&*product.category)
-                    || BLACKLISTED_IDS.contains(&&*product.id)
-                {
-                    continue;
-                };
-
-                let method = PayoutMethod {
-                    id: product.id,
-                    type_: PayoutMethodType::Tremendous,
-                    name: product.name.clone(),
-                    supported_countries: product.countries.into_iter().map(|x| x.abbr).collect(),
-                    image_url: product
-                        .images
-                        .into_iter()
-                        .find(|x| x.type_ == ProductImageType::Card)
-                        .map(|x| x.src),
-                    interval: if product.skus.len() > 1 {
-                        let mut values = product
-                            .skus
-                            .into_iter()
-                            .map(|x| PayoutDecimal(x.min))
-                            .collect::<Vec<_>>();
-                        values.sort_by(|a, b| a.0.cmp(&b.0));
-
-                        PayoutInterval::Fixed { values }
-                    } else if let Some(first) = product.skus.first() {
-                        PayoutInterval::Standard {
-                            min: first.min,
-                            max: first.max,
-                        }
-                    } else {
-                        PayoutInterval::Standard {
-                            min: Decimal::ZERO,
-                            max: Decimal::from(5_000),
-                        }
-                    },
-                    fee: if product.category == "ach" {
-                        PayoutMethodFee {
-                            percentage: Decimal::from(4) / Decimal::from(100),
-                            min: Decimal::from(1) / Decimal::from(4),
-                            max: None,
-                        }
-                    } else {
-                        PayoutMethodFee {
-                            percentage: Default::default(),
-                            min: Default::default(),
-                            max: None,
-                        }
-                    },
-                };
-
-                // we do not support interval gift cards with non US based currencies since we cannot do currency conversions properly
-                if let PayoutInterval::Fixed { .. } = method.interval {
-                    if !product.currency_codes.contains(&"USD".to_string()) {
-                        continue;
-                    }
-                }
-
-                methods.push(method);
+            for provider in &queue.providers {
+                methods.extend(provider.payout_methods().await?.into_iter().filter(|x| {
+                    queue
+                        .provider(x.type_)
+                        .map_or(false, |p| p.name() == provider.name())
+                }));
             }
 
-            const UPRANK_IDS: &[&str] = &["ET0ZVETV5ILN", "Q24BD9EZ332JT", "UIL1ZYJU5MKN"];
-            const DOWNRANK_IDS: &[&str] = &["EIPF8Q00EMM1", "OU2MWXYWPNWQ"];
-
-            methods.sort_by(|a, b| {
-                let a_top = UPRANK_IDS.contains(&&*a.id);
-                let a_bottom = DOWNRANK_IDS.contains(&&*a.id);
-                let b_top = UPRANK_IDS.contains(&&*b.id);
-                let b_bottom = DOWNRANK_IDS.contains(&&*b.id);
-
-                match (a_top, a_bottom, b_top, b_bottom) {
-                    (true, _, true, _) => a.name.cmp(&b.name), // Both in top_priority: sort alphabetically
-                    (_, true, _, true) => a.name.cmp(&b.name), // Both in bottom_priority: sort alphabetically
-                    (true, _, _, _) => std::cmp::Ordering::Less, // a in top_priority: a comes first
-                    (_, _, true, _) => std::cmp::Ordering::Greater, // b in top_priority: b comes first
-                    (_, true, _, _) => std::cmp::Ordering::Greater, // a in bottom_priority: b comes first
-                    (_, _, _, true) => std::cmp::Ordering::Less, // b in bottom_priority: a comes first
-                    (_, _, _, _) => a.name.cmp(&b.name), // Neither in priority: sort alphabetically
-                }
-            });
-
-            {
-                let paypal_us = PayoutMethod {
-                    id: "paypal_us".to_string(),
-                    type_: PayoutMethodType::PayPal,
-                    name: "PayPal".to_string(),
-                    supported_countries: vec!["US".to_string()],
-                    image_url: None,
-                    interval: PayoutInterval::Standard {
-                        min: Decimal::from(1) / Decimal::from(4),
-                        max: Decimal::from(100_000),
-                    },
-                    fee: PayoutMethodFee {
-                        percentage: Decimal::from(2) / Decimal::from(100),
-                        min: Decimal::from(1) / Decimal::from(4),
-                        max: Some(Decimal::from(1)),
-                    },
-                };
-
-                let mut venmo = paypal_us.clone();
-                venmo.id = "venmo".to_string();
-                venmo.name = "Venmo".to_string();
-                venmo.type_ = PayoutMethodType::Venmo;
-
-                methods.insert(0, paypal_us);
-                methods.insert(1, venmo)
-            }
-
-            methods.insert(
-                2,
-                PayoutMethod {
-                    id: "paypal_in".to_string(),
-                    type_: PayoutMethodType::PayPal,
-                    name: "PayPal".to_string(),
-                    supported_countries: rust_iso3166::ALL
-                        .iter()
-                        .filter(|x| x.alpha2 != "US")
-                        .map(|x| x.alpha2.to_string())
-                        .collect(),
-                    image_url: None,
-                    interval: PayoutInterval::Standard {
-                        min: Decimal::from(1) / Decimal::from(4),
-                        max: Decimal::from(100_000),
-                    },
-                    fee: PayoutMethodFee {
-                        percentage: Decimal::from(2) / Decimal::from(100),
-                        min: Decimal::ZERO,
-                        max: Some(Decimal::from(20)),
-                    },
-                },
-            );
-
             let new_options = PayoutMethods {
                 options: methods,
                 expires: Utc::now() + Duration::hours(6),
@@ -513,6 +255,331 @@ impl PayoutsQueue {
             .or_insert_with(|| Arc::new(Mutex::new(())))
             .clone()
     }
+
+    /// Applies payout events reported by a provider to the payouts and balances they affect
+    pub async fn apply_events(
+        &self,
+        events: Vec<PayoutEvent>,
+        pool: &PgPool,
+        redis: &RedisPool,
+    ) -> Result<(), ApiError> {
+        for event in events {
+            match event {
+                PayoutEvent::Succeeded { platform_id } => {
+                    let completed = sqlx::query!(
+                        "
+                        UPDATE payouts
+                        SET status = $1
+                        WHERE platform_id = $2 AND status <> $1
+                        RETURNING user_id, amount, method, currency, currency_amount
+                        ",
+                        PayoutStatus::Success.as_str(),
+                        platform_id
+                    )
+                    .fetch_all(pool)
+                    .await?
+                    .into_iter()
+                    .map(|x| CompletedPayout {
+                        user_id: x.user_id,
+                        amount: x.amount,
+                        method: x.method,
+                        currency: x.currency,
+                        currency_amount: x.currency_amount,
+                    })
+                    .collect::<Vec<_>>();
+
+                    send_payout_receipts(completed, pool, redis).await?;
+                }
+                PayoutEvent::Failed {
+                    platform_id,
+                    status,
+                    reason,
+                } => {
+                    self.refund_payout(
+                        &platform_id,
+                        &[PayoutStatus::InTransit],
+                        status,
+                        reason,
+                        pool,
+                        redis,
+                    )
+                    .await?;
+                }
+                PayoutEvent::Reversed {
+                    platform_id,
+                    reason,
+                } => {
+                    self.refund_payout(
+                        &platform_id,
+                        &[PayoutStatus::InTransit, PayoutStatus::Success],
+                        PayoutStatus::Failed,
+                        Some(reason),
+                        pool,
+                        redis,
+                    )
+                    .await?;
+                }
+                PayoutEvent::RecipientSettled {
+                    method,
+                    recipient,
+                    before,
+                } => {
+                    let completed = sqlx::query!(
+                        "
+                        UPDATE payouts
+                        SET status = $1, failure_reason = NULL
+                        WHERE method = $2 AND method_address = $3 AND status = $4
+                        AND created <= $5
+                        RETURNING user_id, amount, method, currency, currency_amount
+                        ",
+                        PayoutStatus::Success.as_str(),
+                        method.as_str(),
+                        recipient,
+                        PayoutStatus::InTransit.as_str(),
+                        before
+                    )
+                    .fetch_all(pool)
+                    .await?
+                    .into_iter()
+                    .map(|x| CompletedPayout {
+                        user_id: x.user_id,
+                        amount: x.amount,
+                        method: x.method,
+                        currency: x.currency,
+                        currency_amount: x.currency_amount,
+                    })
+                    .collect::<Vec<_>>();
+
+                    send_payout_receipts(completed, pool, redis).await?;
+                }
+                PayoutEvent::RecipientSettlementFailed {
+                    method,
+                    recipient,
+                    before,
+                    reason,
+                } => {
+                    sqlx::query!(
+                        "
+                        UPDATE payouts
+                        SET failure_reason = $1
+                        WHERE method = $2 AND method_address = $3 AND status = $4
+                        AND created <= $5
+                        ",
+                        reason,
+                        method.as_str(),
+                        recipient,
+                        PayoutStatus::InTransit.as_str(),
+                        before
+                    )
+                    .execute(pool)
+                    .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a payout to the user's balance, if it is in one of `from` statuses. The status
+    /// change and the check are one statement, so concurrent events (such as a webhook and
+    /// a status poll) can only refund a payout once
+    async fn refund_payout(
+        &self,
+        platform_id: &str,
+        from: &[PayoutStatus],
+        status: PayoutStatus,
+        reason: Option<String>,
+        pool: &PgPool,
+        redis: &RedisPool,
+    ) -> Result<(), ApiError> {
+        let mut transaction = pool.begin().await?;
+
+        let refunded = sqlx::query!(
+            "
+            UPDATE payouts
+            SET status = $1, failure_reason = COALESCE($2, failure_reason)
+            WHERE platform_id = $3 AND status = ANY($4)
+            RETURNING user_id, amount, fee, withholding
+            ",
+            status.as_str(),
+            reason,
+            platform_id,
+            &from
+                .iter()
+                .map(|x| x.as_str().to_string())
+                .collect::<Vec<_>>()
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+
+        if refunded.is_empty() {
+            return Ok(());
+        }
+
+        let mut _guards = Vec::new();
+        for user_id in refunded.iter().map(|x| x.user_id).sorted().dedup() {
+            _guards.push(
+                self.lock_user_payouts(UserId(user_id as u64))
+                    .lock_owned()
+                    .await,
+            );
+        }
+
+        for result in &refunded {
+            sqlx::query!(
+                "
+                UPDATE users
+                SET balance = balance + $1
+                WHERE id = $2
+                ",
+                base_to_minor_units(
+                    result.amount
+                        + result.fee.unwrap_or(Decimal::ZERO)
+                        + result.withholding.unwrap_or(Decimal::ZERO)
+                ),
+                result.user_id
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        crate::database::models::user_item::User::clear_caches(
+            &refunded
+                .iter()
+                .map(|x| (crate::database::models::UserId(x.user_id), None))
+                .collect::<Vec<_>>(),
+            redis,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// A payout which has just reached its recipient
+struct CompletedPayout {
+    user_id: i64,
+    amount: Decimal,
+    method: Option<String>,
+    currency: Option<String>,
+    currency_amount: Option<i64>,
+}
+
+impl CompletedPayout {
+    /// The amount received, in the currency it was sent in
+    fn display_amount(&self) -> String {
+        match (&self.currency, self.currency_amount) {
+            (Some(currency), Some(amount)) => format!(
+                "{} {}",
+                Money {
+                    amount,
+                    currency: currency.clone(),
+                }
+                .to_decimal(),
+                currency
+            ),
+            _ => format!(
+                "{} {}",
+                self.amount
+                    .round_dp_with_strategy(2, RoundingStrategy::ToZero),
+                BASE_CURRENCY
+            ),
+        }
+    }
+}
+
+/// Emails users a receipt for each of their payouts which reached them
+async fn send_payout_receipts(
+    payouts: Vec<CompletedPayout>,
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<(), ApiError> {
+    if payouts.is_empty() {
+        return Ok(());
+    }
+
+    let users = crate::database::models::User::get_many_ids(
+        &payouts
+            .iter()
+            .map(|x| crate::database::models::UserId(x.user_id))
+            .unique()
+            .collect::<Vec<_>>(),
+        pool,
+        redis,
+    )
+    .await?;
+
+    for payout in payouts {
+        let Some(email) = users
+            .iter()
+            .find(|x| x.id.0 == payout.user_id)
+            .filter(|x| x.email_verified)
+            .and_then(|x| x.email.clone())
+        else {
+            continue;
+        };
+
+        send_email(
+            email,
+            EmailTemplate::PayoutReceipt,
+            DEFAULT_LOCALE,
+            HashMap::from([
+                ("amount".to_string(), payout.display_amount()),
+                (
+                    "method".to_string(),
+                    payout.method.clone().unwrap_or_default(),
+                ),
+                (
+                    "button_link".to_string(),
+                    format!("{}/dashboard/revenue", dotenvy::var("SITE_URL")?),
+                ),
+            ]),
+            pool,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Checks on payouts which have been in transit for a while, in case a provider's webhook was
+/// missed
+pub async fn sync_payout_statuses(
+    pool: &PgPool,
+    redis: &RedisPool,
+    payouts_queue: &PayoutsQueue,
+) -> Result<(), ApiError> {
+    let payouts = sqlx::query!(
+        "
+        SELECT method, platform_id FROM payouts
+        WHERE status = $1 AND platform_id IS NOT NULL AND created < $2
+        ORDER BY created ASC
+        LIMIT 500
+        ",
+        PayoutStatus::InTransit.as_str(),
+        Utc::now() - Duration::hours(1)
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut events = Vec::new();
+    for payout in payouts {
+        let (Some(method), Some(platform_id)) = (payout.method, payout.platform_id) else {
+            continue;
+        };
+        let Some(provider) = payouts_queue.provider(PayoutMethodType::from_string(&method)) else {
+            continue;
+        };
+
+        match provider.poll_status(&platform_id).await {
+            Ok(event) => events.extend(event),
+            Err(err) => warn!("Polling payout {platform_id} failed: {:?}", err),
+        }
+    }
+
+    payouts_queue.apply_events(events, pool, redis).await
 }
 
 pub async fn process_payout(
@@ -615,8 +682,8 @@ pub async fn process_payout(
     };
 
     struct Project {
-        // user_id, payouts_split
-        team_members: Vec<(i64, Decimal)>,
+        // user_id, payouts_split, enrolled in the monetization program
+        team_members: Vec<(i64, Decimal, bool)>,
     }
 
     let mut projects_map: HashMap<i64, Project> = HashMap::new();
@@ -625,9 +692,11 @@ pub async fn process_payout(
 
     sqlx::query!(
         "
-        SELECT m.id id, tm.user_id user_id, tm.payouts_split payouts_split
+        SELECT m.id id, tm.user_id user_id, tm.payouts_split payouts_split,
+            (me.user_id IS NOT NULL) \"enrolled!\"
         FROM mods m
         INNER JOIN team_members tm on m.team_id = tm.team_id AND tm.accepted = TRUE
+        LEFT JOIN monetization_enrollments me ON me.user_id = tm.user_id AND me.status = $3
         WHERE m.id = ANY($1) AND m.monetization_status = $2
         ",
         &multipliers
@@ -636,17 +705,20 @@ pub async fn process_payout(
             .map(|x| *x as i64)
             .collect::<Vec<i64>>(),
         MonetizationStatus::Monetized.as_str(),
+        EnrollmentStatus::Active.as_str(),
     )
     .fetch_many(&mut *transaction)
     .try_for_each(|e| {
         if let Some(row) = e.right() {
             if let Some(project) = projects_map.get_mut(&row.id) {
-                project.team_members.push((row.user_id, row.payouts_split));
+                project
+                    .team_members
+                    .push((row.user_id, row.payouts_split, row.enrolled));
             } else {
                 projects_map.insert(
                     row.id,
                     Project {
-                        team_members: vec![(row.user_id, row.payouts_split)],
+                        team_members: vec![(row.user_id, row.payouts_split, row.enrolled)],
                     },
                 );
             }
@@ -681,7 +753,9 @@ pub async fn process_payout(
             let sum_splits: Decimal = project.team_members.iter().map(|x| x.1).sum();
 
             if sum_splits > Decimal::ZERO {
-                for (user_id, split) in project.team_members {
+                // Members who are not enrolled do not earn their split, and it is not passed on
+                // to the rest of the team either
+                for (user_id, split, _) in project.team_members.into_iter().filter(|x| x.2) {
                     let payout: Decimal = payout * project_multiplier * (split / sum_splits);
 
                     if payout > Decimal::ZERO {
@@ -696,7 +770,7 @@ pub async fn process_payout(
                             SET balance = balance + $1
                             WHERE id = $2
                             ",
-                            payout,
+                            base_to_minor_units(payout),
                             user_id
                         )
                         .execute(&mut *transaction)
@@ -738,6 +812,139 @@ pub async fn process_payout(
     Ok(())
 }
 
+/// Queues a job withdrawing the balance of each user who opted into automatic payouts. Runs
+/// once a month per user, on or after the configured day of the month
+pub async fn process_automatic_payouts(pool: &PgPool) -> Result<(), ApiError> {
+    let now = Utc::now();
+    if now.day() < parse_var::<u32>("AUTOMATIC_PAYOUTS_DAY").unwrap_or(1) {
+        return Ok(());
+    }
+
+    let month_start: DateTime<Utc> = DateTime::from_naive_utc_and_offset(
+        now.date_naive()
+            .with_day(1)
+            .and_then(|x| x.and_hms_opt(0, 0, 0))
+            .unwrap_or_default(),
+        Utc,
+    );
+
+    let schedules = PayoutSchedule::get_due(month_start, pool).await?;
+
+    // Payouts already queued for a user are not queued twice
+    for schedule in schedules {
+        Job::enqueue(
+            JobPayload::AutomaticPayout {
+                user_id: schedule.user_id.into(),
+            },
+            now,
+            pool,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Runs a user's automatic payout and tells them how it went. Errors on our side are returned,
+/// so the job is retried
+pub async fn run_automatic_payout(
+    user_id: crate::database::models::UserId,
+    pool: &PgPool,
+    redis: &RedisPool,
+    payouts_queue: &PayoutsQueue,
+) -> Result<(), ApiError> {
+    let Some(schedule) = PayoutSchedule::get(user_id, pool).await? else {
+        return Ok(());
+    };
+
+    let methods = payouts_queue.get_payout_methods().await?;
+
+    let body = match automatic_payout(&schedule, &methods, pool, redis, payouts_queue).await {
+        Ok(Some(payout)) => Some(NotificationBody::PayoutSent {
+            payout_id: payout.id.into(),
+            amount: payout.amount,
+        }),
+        Ok(None) => None,
+        // These are caused by the user's settings or the provider, so the user is told
+        Err(ApiError::InvalidInput(reason)) | Err(ApiError::Payments(reason)) => {
+            Some(NotificationBody::PayoutFailed { reason })
+        }
+        Err(err) => return Err(err),
+    };
+
+    let mut transaction = pool.begin().await?;
+    PayoutSchedule::mark_attempted(schedule.user_id, &mut transaction).await?;
+    if let Some(body) = body {
+        NotificationBuilder { body }
+            .insert(schedule.user_id, &mut transaction, redis)
+            .await?;
+    }
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Makes a single automatic payout, returning `None` if the balance is below the threshold
+async fn automatic_payout(
+    schedule: &PayoutSchedule,
+    methods: &[PayoutMethod],
+    pool: &PgPool,
+    redis: &RedisPool,
+    payouts_queue: &PayoutsQueue,
+) -> Result<Option<crate::database::models::payout_item::Payout>, ApiError> {
+    let Some(user) = crate::database::models::User::get_id(schedule.user_id, pool, redis).await?
+    else {
+        return Ok(None);
+    };
+
+    let method = methods
+        .iter()
+        .find(|x| x.id == schedule.method_id && x.type_ == schedule.method)
+        .ok_or_else(|| {
+            ApiError::InvalidInput(
+                "The payment method for your automatic payouts is no longer available, please choose another one!"
+                    .to_string(),
+            )
+        })?;
+
+    // Fractions of a cent are left in the balance for the next payout
+    let balance = user
+        .balance_amount()
+        .round_dp_with_strategy(2, RoundingStrategy::ToZero);
+    let (min, amount) = match &method.interval {
+        PayoutInterval::Standard { min, max } => (*min, std::cmp::min(balance, *max)),
+        PayoutInterval::Fixed { values } => (
+            values.first().map(|x| x.0).unwrap_or_default(),
+            values
+                .iter()
+                .map(|x| x.0)
+                .filter(|x| *x <= balance)
+                .max()
+                .unwrap_or_default(),
+        ),
+    };
+
+    if amount < schedule.threshold || amount - method.fee.compute(amount) < min {
+        return Ok(None);
+    }
+
+    let payout = withdraw(
+        user,
+        &Withdrawal {
+            amount,
+            method: schedule.method,
+            method_id: schedule.method_id.clone(),
+            quote_id: None,
+        },
+        pool,
+        redis,
+        payouts_queue,
+    )
+    .await?;
+
+    Ok(Some(payout))
+}
+
 // Used for testing, should be the same as the above function
 pub async fn insert_payouts(
     insert_user_ids: Vec<i64>,
@@ -759,3 +966,62 @@ pub async fn insert_payouts(
     .execute(&mut **transaction)
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payouts::{MockProvider, Recipient, Transfer};
+
+    #[actix_rt::test]
+    async fn mock_provider_replaces_method_type() {
+        let mock = Arc::new(MockProvider::new(PayoutMethodType::PayPal));
+        let queue =
+            PayoutsQueue::with_providers(vec![Arc::new(PayPalProvider::new()), mock.clone()]);
+
+        assert_eq!(
+            queue.provider(PayoutMethodType::PayPal).map(|x| x.name()),
+            Some("mock")
+        );
+        assert_eq!(
+            queue.provider(PayoutMethodType::Venmo).map(|x| x.name()),
+            Some("paypal")
+        );
+        assert!(queue.provider(PayoutMethodType::Stripe).is_none());
+
+        let methods = queue
+            .get_payout_methods()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|x| x.id)
+            .collect::<Vec<_>>();
+        assert_eq!(methods, vec!["venmo", "mock_paypal"]);
+    }
+
+    #[actix_rt::test]
+    async fn mock_provider_records_transfers() {
+        let mock = MockProvider::new(PayoutMethodType::PayPal);
+        let method = mock.payout_methods().await.unwrap().remove(0);
+        let recipient = Recipient {
+            address: "creator".to_string(),
+            display_address: "creator".to_string(),
+        };
+
+        let platform_id = mock
+            .execute_transfer(&Transfer {
+                payout_id: crate::models::ids::PayoutId(1),
+                method: &method,
+                recipient: &recipient,
+                recipient_name: "creator",
+                amount: Decimal::new(1050, 2),
+                currency: BASE_CURRENCY,
+            })
+            .await
+            .unwrap();
+
+        let transfers = mock.transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(Some(transfers[0].platform_id.clone()), platform_id);
+        assert_eq!(transfers[0].amount, Decimal::new(1050, 2));
+    }
+}
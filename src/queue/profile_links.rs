@@ -0,0 +1,145 @@
+use crate::database::models::user_profile_item::UserProfile;
+use crate::database::models::{DatabaseError, User, UserId};
+use crate::database::redis::RedisPool;
+use crate::util::public_url::{check_public_url, public_client_builder};
+use chrono::Utc;
+use log::info;
+use reqwest::redirect::Policy;
+use sqlx::PgPool;
+use std::time::Duration;
+use thiserror::Error;
+use url::Url;
+
+/// The most of a linked page read while looking for the link back to the profile
+const MAX_PAGE_SIZE: usize = 1 << 20;
+/// The most redirects followed while fetching a linked page
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum ProfileLinkError {
+    #[error("Database Error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("Environment Error")]
+    Env(#[from] dotenvy::Error),
+}
+
+/// Whether a profile link may be fetched to verify it, which takes HTTPS on the default port
+/// and a domain name rather than an address
+pub fn is_verifiable(url: &Url) -> bool {
+    url.scheme() == "https"
+        && url.port().is_none()
+        && url.domain().map_or(false, |x| x.contains('.'))
+}
+
+/// Verifies the links on a user's profile which are not yet verified, by checking whether the
+/// pages they go to link back to the profile. Links which can't be fetched are left unverified
+pub async fn verify_profile_links(
+    user_id: UserId,
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<(), ProfileLinkError> {
+    let Some(user) = User::get_id(user_id, pool, redis).await? else {
+        return Ok(());
+    };
+    let profile = UserProfile::get(user_id, pool).await?;
+    let site_url = dotenvy::var("SITE_URL")?;
+
+    let profile_urls = [
+        format!("{}/user/{}", site_url, user.username),
+        format!(
+            "{}/user/{}",
+            site_url,
+            crate::models::ids::UserId::from(user.id)
+        ),
+    ]
+    .map(|x| x.to_lowercase());
+
+    for link in profile.links.iter().filter(|x| x.verified.is_none()) {
+        match fetch_page(&link.url).await {
+            Some(page) if profile_urls.iter().any(|x| links_to(&page, x)) => {
+                UserProfile::set_link_verified(user_id, &link.url, Utc::now(), pool).await?;
+            }
+            _ => info!(
+                "Profile link {} of user {} not verified",
+                link.url, user_id.0
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a page contains a URL as a whole link, rather than as the start of a longer one such
+/// as another user's profile with a name starting the same way
+fn links_to(page: &str, url: &str) -> bool {
+    page.match_indices(url).any(|(index, _)| {
+        page[index + url.len()..].chars().next().map_or(false, |x| {
+            matches!(x, '"' | '\'' | '/' | '?' | '#') || x.is_whitespace()
+        })
+    })
+}
+
+/// Fetches the start of a page, lowercased. Only public addresses are fetched, as the links are
+/// user-supplied
+async fn fetch_page(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok().filter(is_verifiable)?;
+    let url = check_public_url(url.as_str()).await.ok()?;
+    let client = public_client_builder()
+        .timeout(Duration::from_secs(10))
+        .redirect(Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if is_verifiable(attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.error("redirected to a URL which can't be verified")
+            }
+        }))
+        .build()
+        .ok()?;
+
+    let mut response = client.get(url).send().await.ok()?.error_for_status().ok()?;
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await.ok()? {
+        data.extend_from_slice(&chunk);
+        if data.len() >= MAX_PAGE_SIZE {
+            data.truncate(MAX_PAGE_SIZE);
+            break;
+        }
+    }
+
+    Some(String::from_utf8_lossy(&data).to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_links_must_match_whole_urls() {
+        let url = "https://modrinth.com/user/jay";
+
+        for page in [
+            r#"<a href="https://modrinth.com/user/jay">"#,
+            "<a href='https://modrinth.com/user/jay/'>",
+            "see https://modrinth.com/user/jay?tab=mods",
+            "https://modrinth.com/user/jay#about and more",
+            "https://modrinth.com/user/jay\n",
+        ] {
+            assert!(links_to(page, url), "{} should link to the profile", page);
+        }
+
+        for page in [
+            r#"<a href="https://modrinth.com/user/jayden">"#,
+            "https://modrinth.com/user/jay.evil",
+            "https://modrinth.com/user/jay",
+            "nothing here",
+        ] {
+            assert!(
+                !links_to(page, url),
+                "{} should not link to the profile",
+                page
+            );
+        }
+    }
+}
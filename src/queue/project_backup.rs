@@ -0,0 +1,174 @@
+use crate::database::models::job_item::Job;
+use crate::database::models::{self, DatabaseError};
+use crate::database::redis::RedisPool;
+use crate::file_hosting::{FileHost, FileHostingError};
+use crate::models::ids::{ProjectId, VersionId};
+use crate::models::projects::{Project, Version};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::io::Write;
+use thiserror::Error;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("Database Error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("Error while reading file: {0}")]
+    FileHosting(#[from] FileHostingError),
+    #[error("Error while writing archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Error while writing archive: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Serialization Error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Environment Error")]
+    Env(#[from] dotenvy::Error),
+}
+
+/// A file of a version, as listed in the manifest of a backup. Version files are too large to
+/// be copied into backups, so they are listed with what is needed to download and check them
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BackupVersionFile {
+    pub version_id: VersionId,
+    pub version_number: String,
+    pub filename: String,
+    pub url: String,
+    pub hashes: HashMap<String, String>,
+    pub size: u32,
+    pub primary: bool,
+}
+
+/// The `manifest.json` of a backup
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BackupManifest {
+    pub project_id: ProjectId,
+    pub exported: DateTime<Utc>,
+    /// The images copied into the archive, by the path they were copied to
+    pub images: HashMap<String, String>,
+    pub version_files: Vec<BackupVersionFile>,
+}
+
+/// Builds an archive of a project for its authors to keep, and returns its URL. The archive
+/// has the project as `project.json`, its versions as `versions.json`, the description as
+/// `description.md`, the icon and gallery images, and a `manifest.json` listing the files of
+/// every version. Returns `None` if the project no longer exists
+pub async fn export_project_backup(
+    job_id: i64,
+    project_id: ProjectId,
+    pool: &PgPool,
+    redis: &RedisPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<Option<String>, BackupError> {
+    let Some(project) = models::Project::get_id(project_id.into(), pool, redis).await? else {
+        return Ok(None);
+    };
+    let cdn_url = dotenvy::var("CDN_URL")?;
+
+    let mut versions = models::Version::get_many(&project.versions, pool, redis)
+        .await?
+        .into_iter()
+        .map(Version::from)
+        .collect::<Vec<_>>();
+    versions.sort_by_key(|x| x.date_published);
+
+    // Images are only copied from the file host. Any others are left at the URLs listed in
+    // `project.json`
+    let mut images = Vec::new();
+    if let Some(icon_url) = &project.inner.icon_url {
+        images.push((icon_url.clone(), "icon".to_string()));
+    }
+    for (i, item) in project.gallery_items.iter().enumerate() {
+        images.push((item.image_url.clone(), format!("gallery/{}", i + 1)));
+    }
+
+    let mut image_data = Vec::new();
+    let mut image_paths = HashMap::new();
+    for (i, (url, name)) in images.iter().enumerate() {
+        let Some(file_name) = url.strip_prefix(&format!("{cdn_url}/")) else {
+            continue;
+        };
+        let data = file_host.download_file(file_name).await?;
+        let path = match file_name.rsplit_once('.') {
+            Some((_, ext)) if !ext.contains('/') => format!("{name}.{ext}"),
+            _ => name.clone(),
+        };
+
+        image_paths.insert(path.clone(), url.clone());
+        image_data.push((path, data));
+        Job::set_progress(job_id, 0.8 * (i + 1) as f32 / images.len() as f32, pool).await?;
+    }
+
+    let manifest = BackupManifest {
+        project_id,
+        exported: Utc::now(),
+        images: image_paths,
+        version_files: versions
+            .iter()
+            .flat_map(|version| {
+                version.files.iter().map(move |file| BackupVersionFile {
+                    version_id: version.id,
+                    version_number: version.version_number.clone(),
+                    filename: file.filename.clone(),
+                    url: file.url.clone(),
+                    hashes: file.hashes.clone(),
+                    size: file.size,
+                    primary: file.primary,
+                })
+            })
+            .collect(),
+    };
+    let project = Project::from(project);
+    let description = project.description.clone();
+
+    let archive = actix_web::web::block(move || -> Result<Vec<u8>, BackupError> {
+        let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = FileOptions::default();
+
+        zip.start_file("manifest.json", options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+        zip.start_file("project.json", options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&project)?)?;
+        zip.start_file("versions.json", options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&versions)?)?;
+        zip.start_file("description.md", options)?;
+        zip.write_all(description.as_bytes())?;
+        for (path, data) in image_data {
+            zip.start_file(path, options)?;
+            zip.write_all(&data)?;
+        }
+
+        Ok(zip.finish()?.into_inner())
+    })
+    .await
+    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))??;
+
+    // The random part keeps backups from being found by guessing their URL
+    let key = ChaCha20Rng::from_entropy()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect::<String>();
+    let upload_data = file_host
+        .upload_file(
+            "application/zip",
+            &format!(
+                "data/exports/{}/{}-backup-{}.zip",
+                key,
+                project_id,
+                Utc::now().format("%Y-%m-%d")
+            ),
+            Bytes::from(archive),
+        )
+        .await?;
+
+    Ok(Some(format!("{}/{}", cdn_url, upload_data.file_name)))
+}
@@ -0,0 +1,609 @@
+use crate::database::models::github_link_item::GitHubLink;
+use crate::database::models::loader_fields::{Loader, LoaderField, LoaderFieldEnumValue};
+use crate::database::models::project_item::GalleryItem;
+use crate::database::models::version_item::VersionBuilder;
+use crate::database::models::{self, DatabaseError};
+use crate::database::redis::RedisPool;
+use crate::file_hosting::{FileHost, FileHostingError};
+use crate::models::ids::{ProjectId, UserId};
+use crate::models::moderation::ImageSource;
+use crate::models::projects::VersionStatus;
+use crate::routes::v3::project_creation::{undo_uploads, CreateError};
+use crate::routes::v3::version_creation::{
+    try_create_version_fields, upload_file_data, MAX_FILE_SIZE,
+};
+use crate::util::curseforge::{self, CurseForgeError, CurseForgeFile, CurseForgeProject};
+use crate::util::github::{self, GitHubError};
+use crate::util::upload_progress::UploadTracker;
+use crate::util::{ext, image_scan, img};
+use bytes::{Bytes, BytesMut};
+use chrono::Utc;
+use image::ImageError;
+use log::warn;
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// The largest gallery image which is copied over
+const MAX_GALLERY_IMAGE_SIZE: usize = 5 * (1 << 20);
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Error while importing from CurseForge: {0}")]
+    CurseForge(#[from] CurseForgeError),
+    #[error("Error while importing from GitHub: {0}")]
+    GitHub(#[from] GitHubError),
+    #[error("Database Error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("Database Error: {0}")]
+    Sqlx(#[from] sqlx::Error),
+    #[error("Error while uploading file: {0}")]
+    FileHosting(#[from] FileHostingError),
+    #[error("Error while creating version: {0}")]
+    Create(String),
+    #[error("Image Parsing Error: {0}")]
+    Image(#[from] ImageError),
+    #[error("Environment Error")]
+    Env(#[from] dotenvy::Error),
+}
+
+/// Copies the icon, gallery and optionally the files of a CurseForge project into the draft
+/// project it was imported as. Each part is skipped if the project already has it, so the
+/// import can be retried after failing part of the way through
+pub async fn import_curseforge_project(
+    project_id: ProjectId,
+    user_id: UserId,
+    curseforge_id: u32,
+    rehost_files: bool,
+    pool: &PgPool,
+    redis: &RedisPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<(), ImportError> {
+    // The project may have been deleted since it was imported
+    let Some(project) = models::Project::get_id(project_id.into(), pool, redis).await? else {
+        return Ok(());
+    };
+
+    let cdn_url = dotenvy::var("CDN_URL")?;
+    let curseforge_project = curseforge::get_project(curseforge_id).await?;
+
+    if project.inner.icon_url.is_none() {
+        import_icon(project_id, &curseforge_project, pool, file_host, &cdn_url).await?;
+    }
+    if project.gallery_items.is_empty() {
+        import_gallery(project_id, &curseforge_project, pool, file_host, &cdn_url).await?;
+    }
+    if rehost_files {
+        let mut files = curseforge::get_files(curseforge_id).await?;
+        files.sort_by_key(|x| x.file_date);
+        for file in files {
+            import_file(project_id, user_id, &file, pool, redis, file_host, &cdn_url).await?;
+        }
+    }
+
+    models::Project::clear_cache(project.inner.id, project.inner.slug, None, redis).await?;
+
+    Ok(())
+}
+
+async fn import_icon(
+    project_id: ProjectId,
+    curseforge_project: &CurseForgeProject,
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+    cdn_url: &str,
+) -> Result<(), ImportError> {
+    let Some(logo) = &curseforge_project.logo else {
+        return Ok(());
+    };
+    let Some((extension, content_type)) =
+        url_extension(&logo.url).and_then(|x| Some((x.clone(), ext::get_image_content_type(&x)?)))
+    else {
+        return Ok(());
+    };
+
+    let bytes = match curseforge::download(&logo.url, img::MAX_ANIMATED_ICON_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(CurseForgeError::TooLarge) => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    let animation = img::get_animation(&bytes)?;
+    if let Err(err) = img::check_icon(&bytes, animation.as_ref()) {
+        warn!(
+            "Skipping the icon of CurseForge project {}: {err}",
+            curseforge_project.id
+        );
+        return Ok(());
+    }
+    let color = img::get_color_from_img(&bytes)?;
+
+    let hash = sha1::Sha1::from(&bytes).hexdigest();
+    let path = format!("data/{project_id}/{hash}");
+    let upload_data = file_host
+        .upload_file(content_type, &format!("{path}.{extension}"), bytes.clone())
+        .await?;
+    let perceptual_hash = img::get_perceptual_hash(bytes.clone()).await;
+    let renditions =
+        img::upload_renditions(file_host, bytes, &path, img::MAX_ICON_DIMENSION).await?;
+
+    let icon_url = format!("{cdn_url}/{}", upload_data.file_name);
+
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        "
+        UPDATE mods
+        SET icon_url = $1, color = $2, icon_renditions = $3, icon_perceptual_hash = $4
+        WHERE id = $5
+        ",
+        icon_url,
+        color.map(|x| x as i32),
+        json!(img::rendition_urls(&renditions, cdn_url)),
+        perceptual_hash,
+        models::ProjectId::from(project_id) as models::ProjectId,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    image_scan::queue_scan(
+        &icon_url,
+        ImageSource::ProjectIcon { project_id },
+        &mut *transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+async fn import_gallery(
+    project_id: ProjectId,
+    curseforge_project: &CurseForgeProject,
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+    cdn_url: &str,
+) -> Result<(), ImportError> {
+    let mut gallery_items = Vec::new();
+    for screenshot in &curseforge_project.screenshots {
+        let Some((extension, content_type)) = url_extension(&screenshot.url)
+            .and_then(|x| Some((x.clone(), ext::get_image_content_type(&x)?)))
+        else {
+            continue;
+        };
+
+        let bytes = match curseforge::download(&screenshot.url, MAX_GALLERY_IMAGE_SIZE).await {
+            Ok(bytes) => bytes,
+            Err(CurseForgeError::TooLarge) => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        let hash = sha1::Sha1::from(&bytes).hexdigest();
+        let path = format!("data/{project_id}/images/{hash}");
+        let image_url = format!("{cdn_url}/{path}.{extension}");
+        if gallery_items
+            .iter()
+            .any(|x: &GalleryItem| x.image_url == image_url)
+        {
+            continue;
+        }
+
+        file_host
+            .upload_file(content_type, &format!("{path}.{extension}"), bytes.clone())
+            .await?;
+        let perceptual_hash = img::get_perceptual_hash(bytes.clone()).await;
+        let renditions =
+            img::upload_renditions(file_host, bytes, &path, img::MAX_GALLERY_DIMENSION).await?;
+
+        gallery_items.push(GalleryItem {
+            image_id: None,
+            image_url,
+            featured: gallery_items.is_empty(),
+            name: screenshot.title.clone().filter(|x| !x.is_empty()),
+            description: screenshot.description.clone().filter(|x| !x.is_empty()),
+            created: Utc::now(),
+            ordering: gallery_items.len() as i64,
+            renditions: img::rendition_urls(&renditions, cdn_url),
+            perceptual_hash,
+        });
+    }
+
+    let mut transaction = pool.begin().await?;
+    for gallery_item in &gallery_items {
+        image_scan::queue_scan(
+            &gallery_item.image_url,
+            ImageSource::GalleryImage { project_id },
+            &mut *transaction,
+        )
+        .await?;
+    }
+    GalleryItem::insert_many(gallery_items, project_id.into(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Copies a file into a new version. Files which were already copied, which CurseForge
+/// doesn't allow downloading, or whose loaders and game versions Modrinth doesn't know are
+/// skipped
+async fn import_file(
+    project_id: ProjectId,
+    user_id: UserId,
+    file: &CurseForgeFile,
+    pool: &PgPool,
+    redis: &RedisPool,
+    file_host: &(dyn FileHost + Send + Sync),
+    cdn_url: &str,
+) -> Result<(), ImportError> {
+    let Some(download_url) = &file.download_url else {
+        return Ok(());
+    };
+    if !accepts_file(&file.file_name) {
+        return Ok(());
+    }
+
+    let imported = sqlx::query!(
+        "
+        SELECT EXISTS(
+            SELECT 1 FROM files f
+            INNER JOIN versions v ON v.id = f.version_id
+            WHERE v.mod_id = $1 AND f.filename = $2
+        )
+        ",
+        models::ProjectId::from(project_id) as models::ProjectId,
+        file.file_name,
+    )
+    .fetch_one(pool)
+    .await?
+    .exists
+    .unwrap_or(false);
+    if imported {
+        return Ok(());
+    }
+
+    // CurseForge lists loaders and environments along with the game versions
+    let all_loaders = Loader::list(pool, redis).await?;
+    let loaders = all_loaders
+        .iter()
+        .filter(|x| {
+            file.game_versions
+                .iter()
+                .any(|y| y.eq_ignore_ascii_case(&x.loader))
+        })
+        .collect::<Vec<_>>();
+    if loaders.is_empty() {
+        return Ok(());
+    }
+    let loader_ids = loaders.iter().map(|x| x.id).collect::<Vec<_>>();
+
+    let loader_fields = LoaderField::get_fields(&loader_ids, pool, redis).await?;
+    let loader_field_enum_values =
+        LoaderFieldEnumValue::list_many_loader_fields(&loader_fields, pool, redis).await?;
+
+    let game_versions = loader_fields
+        .iter()
+        .find(|x| x.field == "game_versions")
+        .and_then(|x| loader_field_enum_values.get(&x.id))
+        .map(|values| {
+            file.game_versions
+                .iter()
+                .filter(|x| values.iter().any(|y| &y.value == *x))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if game_versions.is_empty() {
+        return Ok(());
+    }
+
+    let client = file.game_versions.iter().any(|x| x == "Client");
+    let server = file.game_versions.iter().any(|x| x == "Server");
+    let (client, server) = if client || server {
+        (client, server)
+    } else {
+        (true, true)
+    };
+    let fields = [
+        ("game_versions", json!(game_versions)),
+        ("client_only", json!(client)),
+        ("server_only", json!(server)),
+        ("singleplayer", json!(true)),
+        ("client_and_server", json!(true)),
+    ]
+    .iter()
+    .filter(|(name, _)| loader_fields.iter().any(|x| x.field == *name))
+    .map(|(name, value)| (name.to_string(), value.clone()))
+    .collect::<HashMap<_, _>>();
+
+    let data = match curseforge::download(download_url, MAX_FILE_SIZE).await {
+        Ok(data) => data,
+        Err(CurseForgeError::TooLarge) => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut transaction = pool.begin().await?;
+    insert_version(
+        ImportedVersion {
+            name: file.display_name.chars().take(64).collect(),
+            version_number: version_number(&file.display_name)
+                .unwrap_or_else(|| file.id.to_string()),
+            changelog: String::new(),
+            version_type: match file.release_type {
+                2 => "beta",
+                3 => "alpha",
+                _ => "release",
+            },
+            status: VersionStatus::Listed,
+            loaders: loaders.iter().map(|x| x.loader.clone()).collect(),
+            fields,
+            files: vec![(file.file_name.clone(), data)],
+        },
+        project_id,
+        user_id,
+        &mut transaction,
+        redis,
+        file_host,
+        cdn_url,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Imports the releases of the GitHub repository a project links as versions, skipping the
+/// releases which were already imported and those without any files Modrinth accepts
+pub async fn import_github_releases(
+    project_id: ProjectId,
+    release_id: Option<u64>,
+    pool: &PgPool,
+    redis: &RedisPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<(), ImportError> {
+    // The repository may have been unlinked since the import was queued
+    let Some(link) = GitHubLink::get(project_id.into(), pool).await? else {
+        return Ok(());
+    };
+
+    let cdn_url = dotenvy::var("CDN_URL")?;
+    let (releases, status) = match release_id {
+        Some(release_id) => (
+            vec![github::get_release(&link.repository, release_id).await?],
+            VersionStatus::Draft,
+        ),
+        None => {
+            let mut releases = github::get_releases(&link.repository).await?;
+            releases.reverse();
+            (releases, VersionStatus::Listed)
+        }
+    };
+
+    for release in releases {
+        if release.draft || GitHubLink::is_imported(project_id.into(), release.id, pool).await? {
+            continue;
+        }
+
+        let mut files = Vec::new();
+        for asset in &release.assets {
+            if !accepts_file(&asset.name) {
+                continue;
+            }
+
+            match github::download(asset, MAX_FILE_SIZE).await {
+                Ok(data) => files.push((asset.name.clone(), data)),
+                Err(GitHubError::TooLarge) => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let mut transaction = pool.begin().await?;
+        let version_id = if files.is_empty() {
+            None
+        } else {
+            insert_version(
+                ImportedVersion {
+                    name: release
+                        .name
+                        .clone()
+                        .filter(|x| !x.trim().is_empty())
+                        .unwrap_or_else(|| release.tag_name.clone())
+                        .chars()
+                        .take(64)
+                        .collect(),
+                    version_number: version_number(&release.tag_name)
+                        .unwrap_or_else(|| release.id.to_string()),
+                    changelog: release.body.clone().unwrap_or_default(),
+                    version_type: if release.prerelease {
+                        "beta"
+                    } else {
+                        "release"
+                    },
+                    status,
+                    loaders: link.loaders.clone(),
+                    fields: link.version_fields.clone(),
+                    files,
+                },
+                project_id,
+                link.user_id.into(),
+                &mut transaction,
+                redis,
+                file_host,
+                &cdn_url,
+            )
+            .await?
+        };
+        GitHubLink::mark_imported(project_id.into(), release.id, version_id, &mut transaction)
+            .await?;
+        transaction.commit().await?;
+    }
+
+    if let Some(project) = models::Project::get_id(project_id.into(), pool, redis).await? {
+        models::Project::clear_cache(project.inner.id, project.inner.slug, None, redis).await?;
+    }
+
+    Ok(())
+}
+
+/// A version copied over from another site
+struct ImportedVersion {
+    name: String,
+    version_number: String,
+    changelog: String,
+    version_type: &'static str,
+    status: VersionStatus,
+    loaders: Vec<String>,
+    fields: HashMap<String, serde_json::Value>,
+    /// The files, by their file names. The first file is the primary one
+    files: Vec<(String, Bytes)>,
+}
+
+/// Creates a version from files copied over from another site. Versions whose files or
+/// fields Modrinth doesn't accept are left out rather than failing the import, returning
+/// `None`
+async fn insert_version(
+    version: ImportedVersion,
+    project_id: ProjectId,
+    user_id: UserId,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    redis: &RedisPool,
+    file_host: &(dyn FileHost + Send + Sync),
+    cdn_url: &str,
+) -> Result<Option<models::VersionId>, ImportError> {
+    let loaders = Loader::list(&mut **transaction, redis)
+        .await?
+        .into_iter()
+        .filter(|x| version.loaders.contains(&x.loader))
+        .collect::<Vec<_>>();
+    if loaders.is_empty() {
+        return Ok(None);
+    }
+    let loader_ids = loaders.iter().map(|x| x.id).collect::<Vec<_>>();
+
+    let loader_fields = LoaderField::get_fields(&loader_ids, &mut **transaction, redis).await?;
+    let mut loader_field_enum_values =
+        LoaderFieldEnumValue::list_many_loader_fields(&loader_fields, &mut **transaction, redis)
+            .await?;
+
+    let version_id = models::generate_version_id(transaction).await?;
+    let version_fields = match try_create_version_fields(
+        version_id.into(),
+        &version.fields,
+        &loader_fields,
+        &mut loader_field_enum_values,
+    ) {
+        Ok(version_fields) => version_fields,
+        Err(err) => {
+            warn!(
+                "Skipping imported version {}: {err}",
+                version.version_number
+            );
+            return Ok(None);
+        }
+    };
+
+    let mut uploaded_files = Vec::new();
+    let mut files = Vec::new();
+    let mut dependencies = Vec::new();
+    let total_files_len = version.files.len();
+    for (file_name, data) in version.files {
+        let extension = file_name
+            .rsplit_once('.')
+            .map(|x| x.1.to_lowercase())
+            .unwrap_or_default();
+        // The error can't be held across the await below, as it isn't always `Send`
+        let error = match upload_file_data(
+            BytesMut::from(&data[..]),
+            &file_name,
+            &extension,
+            file_host,
+            total_files_len,
+            &mut uploaded_files,
+            &mut files,
+            &mut dependencies,
+            cdn_url,
+            project_id,
+            version_id.into(),
+            &version_fields,
+            loaders
+                .iter()
+                .map(|x| crate::models::projects::Loader(x.loader.clone()))
+                .collect(),
+            false,
+            false,
+            None,
+            transaction,
+            redis,
+            &UploadTracker::default(),
+        )
+        .await
+        {
+            Ok(()) => continue,
+            Err(
+                err @ (CreateError::InvalidInput(..)
+                | CreateError::InvalidFileType(..)
+                | CreateError::FileValidationError(..)),
+            ) => {
+                warn!(
+                    "Skipping imported version {}: {err}",
+                    version.version_number
+                );
+                None
+            }
+            Err(err) => Some(err.to_string()),
+        };
+
+        undo_uploads(file_host, &uploaded_files)
+            .await
+            .map_err(|err| ImportError::Create(err.to_string()))?;
+        return match error {
+            Some(err) => Err(ImportError::Create(err)),
+            None => Ok(None),
+        };
+    }
+
+    VersionBuilder {
+        version_id,
+        project_id: project_id.into(),
+        author_id: user_id.into(),
+        name: version.name,
+        version_number: version.version_number,
+        changelog: version.changelog,
+        files,
+        dependencies,
+        loaders: loader_ids,
+        version_fields,
+        version_type: version.version_type.to_string(),
+        featured: false,
+        status: version.status,
+        requested_status: None,
+        ordering: None,
+    }
+    .insert(transaction)
+    .await?;
+
+    Ok(Some(version_id))
+}
+
+/// Makes a version number out of a name, keeping only the characters version numbers may use
+fn version_number(name: &str) -> Option<String> {
+    let number = name
+        .replace(' ', "-")
+        .chars()
+        .filter(|x| x.is_ascii_alphanumeric() || matches!(x, '.' | '-' | '_' | '+'))
+        .take(32)
+        .collect::<String>();
+
+    Some(number).filter(|x| !x.is_empty())
+}
+
+/// Whether a file has an extension project files may have
+fn accepts_file(file_name: &str) -> bool {
+    file_name.rsplit_once('.').map_or(false, |x| {
+        ext::project_file_type(&x.1.to_lowercase()).is_some()
+    })
+}
+
+fn url_extension(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next()?;
+    let (_, extension) = path.rsplit_once('/')?.1.rsplit_once('.')?;
+
+    Some(extension.to_lowercase())
+}
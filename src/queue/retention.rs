@@ -0,0 +1,285 @@
+use crate::database::models::session_item::Session;
+use crate::database::models::{
+    DatabaseError, Image, ImageId, Project, ProjectId, SessionId, User, UserId,
+};
+use crate::database::redis::RedisPool;
+use crate::file_hosting::FileHost;
+use crate::models::images::ImageContext;
+use crate::models::jobs::JobStatus;
+use crate::routes::ApiError;
+use crate::util::env::parse_var;
+use log::{info, warn};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Unattached images removed per run, so a backlog is worked through over several runs
+const IMAGES_PER_RUN: i64 = 1000;
+
+/// How many days data is kept once it is no longer needed, by category. Auth flows and rate
+/// limit windows only live in Redis with an expiry, and raw analytics are dropped by the
+/// ClickHouse rollups after `ANALYTICS_RAW_RETENTION_DAYS`, so they are not purged here.
+pub struct RetentionPolicy {
+    /// Sessions past their refresh expiry
+    pub expired_sessions: i32,
+    /// OAuth access tokens past their expiry
+    pub expired_oauth_tokens: i32,
+    /// Uploaded images never attached to a project, version, thread message or report
+    pub unattached_images: i32,
+    /// Jobs which failed permanently
+    pub dead_jobs: i32,
+    /// Completed jobs started by a user, along with the files they produced
+    pub completed_jobs: i32,
+    /// Processed webhooks received from other services. Deliveries are only recognized as
+    /// replays while they are kept, so this should be longer than any sender retries for
+    pub processed_webhooks: i32,
+}
+
+impl RetentionPolicy {
+    pub fn from_env() -> Self {
+        RetentionPolicy {
+            expired_sessions: parse_var("RETENTION_EXPIRED_SESSIONS_DAYS").unwrap_or(0),
+            expired_oauth_tokens: parse_var("RETENTION_EXPIRED_OAUTH_TOKENS_DAYS").unwrap_or(7),
+            unattached_images: parse_var("RETENTION_UNATTACHED_IMAGES_DAYS").unwrap_or(1),
+            dead_jobs: parse_var("RETENTION_DEAD_JOBS_DAYS").unwrap_or(30),
+            completed_jobs: parse_var("RETENTION_COMPLETED_JOBS_DAYS").unwrap_or(7),
+            processed_webhooks: parse_var("RETENTION_PROCESSED_WEBHOOKS_DAYS").unwrap_or(30),
+        }
+    }
+}
+
+/// Purges everything kept past its retention
+pub async fn purge_expired_data(
+    pool: &PgPool,
+    redis: &RedisPool,
+    file_host: &Arc<dyn FileHost + Send + Sync>,
+) -> Result<(), ApiError> {
+    let policy = RetentionPolicy::from_env();
+
+    let sessions = purge_sessions(policy.expired_sessions, pool, redis).await?;
+    let oauth_tokens = purge_oauth_tokens(policy.expired_oauth_tokens, pool).await?;
+    let images = purge_images(policy.unattached_images, pool, redis, file_host).await?;
+    let jobs = purge_dead_jobs(policy.dead_jobs, pool).await?;
+    let completed_jobs = purge_completed_jobs(policy.completed_jobs, pool, file_host).await?;
+    let webhooks = purge_processed_webhooks(policy.processed_webhooks, pool).await?;
+    let projects = purge_deleted_projects(pool, redis).await?;
+    let users = purge_deleted_users(pool, redis).await?;
+
+    info!(
+        "Purged {} sessions, {} OAuth access tokens, {} images, {} dead jobs, {} completed jobs, {} webhooks, {} projects and {} users",
+        sessions, oauth_tokens, images, jobs, completed_jobs, webhooks, projects, users
+    );
+
+    Ok(())
+}
+
+async fn purge_sessions(days: i32, pool: &PgPool, redis: &RedisPool) -> Result<u64, DatabaseError> {
+    let removed = sqlx::query!(
+        "
+        DELETE FROM sessions
+        WHERE refresh_expires <= NOW() - make_interval(days => $1)
+        RETURNING id, session, user_id
+        ",
+        days,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let count = removed.len() as u64;
+    Session::clear_cache(
+        removed
+            .into_iter()
+            .map(|x| {
+                (
+                    Some(SessionId(x.id)),
+                    Some(x.session),
+                    Some(UserId(x.user_id)),
+                )
+            })
+            .collect(),
+        redis,
+    )
+    .await?;
+
+    Ok(count)
+}
+
+async fn purge_oauth_tokens(days: i32, pool: &PgPool) -> Result<u64, DatabaseError> {
+    let result = sqlx::query!(
+        "
+        DELETE FROM oauth_access_tokens
+        WHERE expires <= NOW() - make_interval(days => $1)
+        ",
+        days,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+async fn purge_images(
+    days: i32,
+    pool: &PgPool,
+    redis: &RedisPool,
+    file_host: &Arc<dyn FileHost + Send + Sync>,
+) -> Result<u64, ApiError> {
+    let images = sqlx::query!(
+        "
+        SELECT id, url
+        FROM uploaded_images
+        WHERE mod_id IS NULL AND version_id IS NULL AND thread_message_id IS NULL
+            AND report_id IS NULL AND created <= NOW() - make_interval(days => $1)
+        ORDER BY created
+        LIMIT $2
+        ",
+        days,
+        IMAGES_PER_RUN,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let cdn_url = dotenvy::var("CDN_URL")?;
+    let mut count = 0;
+    for image in images {
+        // Images whose file could not be deleted are kept, so it is tried again next run
+        if let Some(path) = image.url.split(&format!("{cdn_url}/")).nth(1) {
+            if let Err(e) = file_host.delete_file_version("", path).await {
+                warn!("Deleting unattached image {} failed: {}", image.id, e);
+                continue;
+            }
+        }
+
+        let mut transaction = pool.begin().await?;
+        crate::database::models::Image::remove(ImageId(image.id), &mut transaction, redis).await?;
+        transaction.commit().await?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+async fn purge_processed_webhooks(days: i32, pool: &PgPool) -> Result<u64, DatabaseError> {
+    let result = sqlx::query!(
+        "
+        DELETE FROM inbound_webhooks
+        WHERE processed <= NOW() - make_interval(days => $1)
+        ",
+        days,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+async fn purge_dead_jobs(days: i32, pool: &PgPool) -> Result<u64, DatabaseError> {
+    let result = sqlx::query!(
+        "
+        DELETE FROM jobs
+        WHERE status = $1 AND finished <= NOW() - make_interval(days => $2)
+        ",
+        JobStatus::Dead.as_str(),
+        days,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+async fn purge_completed_jobs(
+    days: i32,
+    pool: &PgPool,
+    file_host: &Arc<dyn FileHost + Send + Sync>,
+) -> Result<u64, ApiError> {
+    let jobs = sqlx::query!(
+        "
+        SELECT id, result_url
+        FROM jobs
+        WHERE status = $1 AND finished <= NOW() - make_interval(days => $2)
+        ",
+        JobStatus::Completed.as_str(),
+        days,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let cdn_url = dotenvy::var("CDN_URL")?;
+    let mut ids = Vec::new();
+    for job in jobs {
+        // Jobs whose result could not be deleted are kept, so it is tried again next run
+        if let Some(path) = job
+            .result_url
+            .as_deref()
+            .and_then(|x| x.split(&format!("{cdn_url}/")).nth(1))
+        {
+            if let Err(e) = file_host.delete_file_version("", path).await {
+                warn!("Deleting the result of job {} failed: {}", job.id, e);
+                continue;
+            }
+        }
+        ids.push(job.id);
+    }
+
+    let result = sqlx::query!(
+        "
+        DELETE FROM jobs
+        WHERE id = ANY($1)
+        ",
+        &ids,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Removes the projects deleted more than `DELETION_GRACE_DAYS` ago for good. The grace period
+/// is part of what users are promised when deleting, so it is not configurable like the others
+async fn purge_deleted_projects(pool: &PgPool, redis: &RedisPool) -> Result<u64, DatabaseError> {
+    let mut count = 0;
+    for id in Project::get_purgeable(pool).await? {
+        let mut transaction = pool.begin().await?;
+
+        let context = ImageContext::Project {
+            project_id: Some(id.into()),
+        };
+        for image in Image::get_many_contexted(context, &mut transaction).await? {
+            Image::remove(image.id, &mut transaction, redis).await?;
+        }
+
+        sqlx::query!(
+            "
+            DELETE FROM collections_mods
+            WHERE mod_id = $1
+            ",
+            id as ProjectId,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        if Project::remove(id, &mut transaction, redis)
+            .await?
+            .is_some()
+        {
+            count += 1;
+        }
+        transaction.commit().await?;
+    }
+
+    Ok(count)
+}
+
+/// Removes the accounts deleted more than `DELETION_GRACE_DAYS` ago for good
+async fn purge_deleted_users(pool: &PgPool, redis: &RedisPool) -> Result<u64, DatabaseError> {
+    let mut count = 0;
+    for id in User::get_purgeable(pool).await? {
+        let mut transaction = pool.begin().await?;
+        if User::remove(id, &mut transaction, redis).await?.is_some() {
+            count += 1;
+        }
+        transaction.commit().await?;
+    }
+
+    Ok(count)
+}
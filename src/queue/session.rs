@@ -1,18 +1,40 @@
 use crate::database::models::pat_item::PersonalAccessToken;
+use crate::database::models::pat_usage_item::{PatError, PatUsageHour};
 use crate::database::models::session_item::Session;
 use crate::database::models::{DatabaseError, OAuthAccessTokenId, PatId, SessionId, UserId};
 use crate::database::redis::RedisPool;
 use crate::routes::internal::session::SessionMetadata;
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use itertools::Itertools;
 use sqlx::PgPool;
 use std::collections::{HashMap, HashSet};
 use tokio::sync::Mutex;
 
+/// Failed requests kept for each personal access token
+const KEPT_PAT_ERRORS: i64 = 50;
+/// Failed requests sampled for each token between batches
+const SAMPLED_PAT_ERRORS: usize = 5;
+/// Days the usage of tokens is kept for
+pub const PAT_USAGE_DAYS: i64 = 30;
+/// Tokens whose usage is counted between batches. Usage of any others is dropped, so requests
+/// with made up tokens cannot fill the queue
+const MAX_QUEUED_PAT_USAGE: usize = 10000;
+
+/// The requests made with a token since the last batch
+#[derive(Default)]
+struct PatUsage {
+    requests: i64,
+    errors: i64,
+    rate_limited: i64,
+    sampled_errors: Vec<PatError>,
+}
+
 pub struct AuthQueue {
     session_queue: Mutex<HashMap<SessionId, SessionMetadata>>,
     pat_queue: Mutex<HashSet<PatId>>,
     oauth_access_token_queue: Mutex<HashSet<OAuthAccessTokenId>>,
+    // By token, as tokens are only looked up when batched
+    pat_usage_queue: Mutex<HashMap<String, PatUsage>>,
 }
 
 impl Default for AuthQueue {
@@ -28,6 +50,7 @@ impl AuthQueue {
             session_queue: Mutex::new(HashMap::with_capacity(1000)),
             pat_queue: Mutex::new(HashSet::with_capacity(1000)),
             oauth_access_token_queue: Mutex::new(HashSet::with_capacity(1000)),
+            pat_usage_queue: Mutex::new(HashMap::with_capacity(1000)),
         }
     }
     pub async fn add_session(&self, id: SessionId, metadata: SessionMetadata) {
@@ -42,6 +65,31 @@ impl AuthQueue {
         self.oauth_access_token_queue.lock().await.insert(id);
     }
 
+    /// Counts a request made with a personal access token, sampling it if it failed
+    pub async fn add_pat_usage(&self, token: &str, method: &str, path: &str, status: u16) {
+        let mut queue = self.pat_usage_queue.lock().await;
+        if !queue.contains_key(token) && queue.len() >= MAX_QUEUED_PAT_USAGE {
+            return;
+        }
+
+        let usage = queue.entry(token.to_string()).or_default();
+        usage.requests += 1;
+        if status >= 400 {
+            usage.errors += 1;
+            if usage.sampled_errors.len() < SAMPLED_PAT_ERRORS {
+                usage.sampled_errors.push(PatError {
+                    method: method.to_string(),
+                    path: path.to_string(),
+                    status,
+                    created: Utc::now(),
+                });
+            }
+        }
+        if status == 429 {
+            usage.rate_limited += 1;
+        }
+    }
+
     pub async fn take_sessions(&self) -> HashMap<SessionId, SessionMetadata> {
         let mut queue = self.session_queue.lock().await;
         let len = queue.len();
@@ -60,6 +108,12 @@ impl AuthQueue {
         let session_queue = self.take_sessions().await;
         let pat_queue = Self::take_hashset(&self.pat_queue).await;
         let oauth_access_token_queue = Self::take_hashset(&self.oauth_access_token_queue).await;
+        let pat_usage_queue = {
+            let mut queue = self.pat_usage_queue.lock().await;
+            let len = queue.len();
+
+            std::mem::replace(&mut *queue, HashMap::with_capacity(len))
+        };
 
         if !session_queue.is_empty()
             || !pat_queue.is_empty()
@@ -137,10 +191,57 @@ impl AuthQueue {
             PersonalAccessToken::clear_cache(clear_cache_pats, redis).await?;
         }
 
+        if !pat_usage_queue.is_empty() {
+            update_pat_usage(pat_usage_queue, pool, redis).await?;
+        }
+
         Ok(())
     }
 }
 
+async fn update_pat_usage(
+    pat_usage_queue: HashMap<String, PatUsage>,
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<(), DatabaseError> {
+    let tokens = pat_usage_queue.keys().collect_vec();
+    let pats = PersonalAccessToken::get_many(&tokens, pool, redis).await?;
+
+    let mut transaction = pool.begin().await?;
+    for pat in pats {
+        let Some(usage) = pat_usage_queue.get(&pat.access_token) else {
+            continue;
+        };
+
+        PatUsageHour::add(
+            pat.id,
+            usage.requests,
+            usage.errors,
+            usage.rate_limited,
+            &mut transaction,
+        )
+        .await?;
+
+        if !usage.sampled_errors.is_empty() {
+            PatError::insert_many(
+                pat.id,
+                &usage.sampled_errors,
+                KEPT_PAT_ERRORS,
+                &mut transaction,
+            )
+            .await?;
+        }
+    }
+    PatUsageHour::remove_before(
+        Utc::now() - Duration::days(PAT_USAGE_DAYS),
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
 async fn update_oauth_access_token_last_used(
     oauth_access_token_queue: HashSet<OAuthAccessTokenId>,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
@@ -0,0 +1,184 @@
+use crate::database::models::similar_project_item::SimilarProject;
+use crate::database::models::ProjectId;
+use crate::models::projects::ProjectStatus;
+use crate::routes::ApiError;
+use chrono::{Duration, Utc};
+use log::info;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// How many similar projects are kept for each project
+const SIMILAR_PER_PROJECT: usize = 20;
+/// How far back downloads count towards two projects being downloaded together
+const CO_DOWNLOAD_DAYS: i64 = 90;
+/// How many users have to download or follow both projects for it to count
+const MIN_SHARED_USERS: i64 = 2;
+
+/// How much each signal adds to a score, summing to 1
+const CATEGORY_WEIGHT: f32 = 0.4;
+const LOADER_WEIGHT: f32 = 0.2;
+const CO_DOWNLOAD_WEIGHT: f32 = 0.25;
+const CO_FOLLOW_WEIGHT: f32 = 0.15;
+
+struct ProjectSignals {
+    categories: HashSet<i32>,
+    loaders: HashSet<i32>,
+}
+
+fn jaccard(a: &HashSet<i32>, b: &HashSet<i32>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        0.0
+    } else {
+        a.intersection(b).count() as f32 / union as f32
+    }
+}
+
+/// Computes the projects similar to each public project, replacing the previous results.
+/// Projects are only similar if they share a loader, so mods are not suggested for resource
+/// packs. Shared users are scaled by the most any project shares with the project, so popular
+/// projects do not drown out the rest
+pub async fn compute_similar_projects(
+    pool: &PgPool,
+    clickhouse: Arc<clickhouse::Client>,
+) -> Result<(), ApiError> {
+    let projects = sqlx::query!(
+        "
+        SELECT m.id,
+            ARRAY(
+                SELECT mc.joining_category_id FROM mods_categories mc
+                WHERE mc.joining_mod_id = m.id
+            ) categories,
+            ARRAY(
+                SELECT DISTINCT lv.loader_id FROM versions v
+                INNER JOIN loaders_versions lv ON lv.version_id = v.id
+                WHERE v.mod_id = m.id
+            ) loaders
+        FROM mods m
+        WHERE m.status = ANY($1)
+        ",
+        &*ProjectStatus::iterator()
+            .filter(|x| x.is_searchable())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>(),
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| {
+        (
+            r.id,
+            ProjectSignals {
+                categories: r.categories.unwrap_or_default().into_iter().collect(),
+                loaders: r.loaders.unwrap_or_default().into_iter().collect(),
+            },
+        )
+    })
+    .collect::<HashMap<_, _>>();
+
+    // Shared users of each pair, both ways round
+    let mut co_downloads: HashMap<(i64, i64), f32> = HashMap::new();
+    for pair in crate::clickhouse::fetch_co_downloads(
+        Utc::now() - Duration::days(CO_DOWNLOAD_DAYS),
+        MIN_SHARED_USERS as u64,
+        clickhouse,
+    )
+    .await?
+    {
+        co_downloads.insert((pair.id as i64, pair.other_id as i64), pair.total as f32);
+    }
+
+    let mut co_follows: HashMap<(i64, i64), f32> = HashMap::new();
+    for pair in sqlx::query!(
+        "
+        SELECT a.mod_id id, b.mod_id other_id, COUNT(*) total
+        FROM mod_follows a
+        INNER JOIN mod_follows b ON b.follower_id = a.follower_id AND b.mod_id <> a.mod_id
+        GROUP BY a.mod_id, b.mod_id
+        HAVING COUNT(*) >= $1
+        ",
+        MIN_SHARED_USERS,
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        co_follows.insert((pair.id, pair.other_id), pair.total.unwrap_or(0) as f32);
+    }
+
+    let mut by_category: HashMap<i32, Vec<i64>> = HashMap::new();
+    for (id, signals) in &projects {
+        for category in &signals.categories {
+            by_category.entry(*category).or_default().push(*id);
+        }
+    }
+    let mut shared_users: HashMap<i64, Vec<i64>> = HashMap::new();
+    for (id, other_id) in co_downloads.keys().chain(co_follows.keys()) {
+        shared_users.entry(*id).or_default().push(*other_id);
+    }
+
+    let most_shared = |pairs: &HashMap<(i64, i64), f32>, id: i64, others: &HashSet<i64>| {
+        others
+            .iter()
+            .filter_map(|x| pairs.get(&(id, *x)))
+            .fold(1.0f32, |a, b| a.max(*b))
+    };
+
+    // Candidates are projects sharing a category, or downloaded or followed together. They
+    // are gathered one project at a time, as large categories would hold too many pairs
+    let mut similarities = Vec::new();
+    for (id, signals) in &projects {
+        let others = signals
+            .categories
+            .iter()
+            .filter_map(|x| by_category.get(x))
+            .flatten()
+            .chain(shared_users.get(id).into_iter().flatten())
+            .filter(|x| *x != id)
+            .copied()
+            .collect::<HashSet<_>>();
+        let most_downloaded = most_shared(&co_downloads, *id, &others);
+        let most_followed = most_shared(&co_follows, *id, &others);
+
+        let mut scored = others
+            .iter()
+            .filter_map(|other_id| {
+                let other = projects.get(other_id)?;
+                if signals.loaders.is_disjoint(&other.loaders) {
+                    return None;
+                }
+
+                let score = CATEGORY_WEIGHT * jaccard(&signals.categories, &other.categories)
+                    + LOADER_WEIGHT * jaccard(&signals.loaders, &other.loaders)
+                    + CO_DOWNLOAD_WEIGHT
+                        * co_downloads.get(&(*id, *other_id)).copied().unwrap_or(0.0)
+                        / most_downloaded
+                    + CO_FOLLOW_WEIGHT * co_follows.get(&(*id, *other_id)).copied().unwrap_or(0.0)
+                        / most_followed;
+
+                Some((*other_id, score))
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        similarities.extend(scored.into_iter().take(SIMILAR_PER_PROJECT).map(
+            |(other_id, score)| SimilarProject {
+                project_id: ProjectId(*id),
+                similar_id: ProjectId(other_id),
+                score,
+            },
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+    SimilarProject::replace_all(&similarities, &mut transaction).await?;
+    transaction.commit().await?;
+
+    info!(
+        "Computed {} similar projects for {} projects",
+        similarities.len(),
+        projects.len()
+    );
+
+    Ok(())
+}
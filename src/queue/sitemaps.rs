@@ -0,0 +1,251 @@
+use crate::file_hosting::FileHost;
+use crate::models::ids::base62_impl::to_base62;
+use crate::models::projects::ProjectStatus;
+use crate::routes::ApiError;
+use bytes::Bytes;
+use chrono::Utc;
+use log::info;
+use sqlx::PgPool;
+use std::sync::Arc;
+use yaserde_derive::YaSerialize;
+
+/// The most URLs in one sitemap page. Crawlers accept up to 50,000, but smaller pages are
+/// quicker to fetch
+pub const SITEMAP_PAGE_SIZE: i64 = 10_000;
+
+#[derive(Default, Debug, Clone, YaSerialize)]
+#[yaserde(
+    root = "urlset",
+    rename = "urlset",
+    namespace = "http://www.sitemaps.org/schemas/sitemap/0.9"
+)]
+struct UrlSet {
+    #[yaserde(rename = "url")]
+    urls: Vec<SitemapUrl>,
+}
+
+#[derive(Default, Debug, Clone, YaSerialize)]
+#[yaserde(rename = "url")]
+struct SitemapUrl {
+    loc: String,
+    lastmod: String,
+}
+
+#[derive(Default, Debug, Clone, YaSerialize)]
+#[yaserde(
+    root = "sitemapindex",
+    rename = "sitemapindex",
+    namespace = "http://www.sitemaps.org/schemas/sitemap/0.9"
+)]
+struct SitemapIndex {
+    #[yaserde(rename = "sitemap")]
+    sitemaps: Vec<SitemapUrl>,
+}
+
+/// The pages sitemaps are generated for
+#[derive(Clone, Copy, Debug)]
+enum SitemapKind {
+    Projects,
+    Users,
+    Organizations,
+}
+
+impl SitemapKind {
+    const ALL: [SitemapKind; 3] = [
+        SitemapKind::Projects,
+        SitemapKind::Users,
+        SitemapKind::Organizations,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SitemapKind::Projects => "projects",
+            SitemapKind::Users => "users",
+            SitemapKind::Organizations => "organizations",
+        }
+    }
+
+    /// Fetches the URLs of a page, keyed by an ID to continue from
+    async fn fetch_page(
+        &self,
+        after: i64,
+        site_url: &str,
+        pool: &PgPool,
+    ) -> Result<Vec<(i64, SitemapUrl)>, ApiError> {
+        let searchable = ProjectStatus::iterator()
+            .filter(|x| x.is_searchable())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+
+        let urls = match self {
+            SitemapKind::Projects => sqlx::query!(
+                "
+                SELECT m.id, m.slug, m.updated,
+                    (
+                        SELECT MIN(pt.name) FROM versions v
+                        INNER JOIN loaders_versions lv ON lv.version_id = v.id
+                        INNER JOIN loaders_project_types lpt ON lpt.joining_loader_id = lv.loader_id
+                        INNER JOIN project_types pt ON pt.id = lpt.joining_project_type_id
+                        WHERE v.mod_id = m.id
+                    ) project_type
+                FROM mods m
+                WHERE m.id > $1 AND m.status = ANY($2) AND m.deleted IS NULL
+                ORDER BY m.id
+                LIMIT $3
+                ",
+                after,
+                &*searchable,
+                SITEMAP_PAGE_SIZE,
+            )
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|r| {
+                let id = r.id;
+                (
+                    id,
+                    SitemapUrl {
+                        loc: format!(
+                            "{}/{}/{}",
+                            site_url,
+                            r.project_type.unwrap_or_else(|| "project".to_string()),
+                            r.slug.unwrap_or_else(|| to_base62(id as u64))
+                        ),
+                        lastmod: r.updated.format("%Y-%m-%d").to_string(),
+                    },
+                )
+            })
+            .collect(),
+            // Only users with public projects have profiles worth crawling, and they were
+            // last changed when their latest project was
+            SitemapKind::Users => sqlx::query!(
+                "
+                SELECT u.id, u.username, MAX(m.updated) updated
+                FROM users u
+                INNER JOIN team_members tm ON tm.user_id = u.id AND tm.accepted
+                INNER JOIN mods m ON m.team_id = tm.team_id AND m.status = ANY($2)
+                    AND m.deleted IS NULL
+                WHERE u.id > $1 AND u.deleted IS NULL
+                GROUP BY u.id
+                ORDER BY u.id
+                LIMIT $3
+                ",
+                after,
+                &*searchable,
+                SITEMAP_PAGE_SIZE,
+            )
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|r| {
+                (
+                    r.id,
+                    SitemapUrl {
+                        loc: format!("{}/user/{}", site_url, r.username),
+                        lastmod: r
+                            .updated
+                            .unwrap_or_else(Utc::now)
+                            .format("%Y-%m-%d")
+                            .to_string(),
+                    },
+                )
+            })
+            .collect(),
+            SitemapKind::Organizations => sqlx::query!(
+                "
+                SELECT o.id, o.slug, o.updated_at
+                FROM organizations o
+                WHERE o.id > $1
+                ORDER BY o.id
+                LIMIT $2
+                ",
+                after,
+                SITEMAP_PAGE_SIZE,
+            )
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|r| {
+                (
+                    r.id,
+                    SitemapUrl {
+                        loc: format!("{}/organization/{}", site_url, r.slug),
+                        lastmod: r.updated_at.format("%Y-%m-%d").to_string(),
+                    },
+                )
+            })
+            .collect(),
+        };
+
+        Ok(urls)
+    }
+}
+
+/// The path a sitemap file is stored at in the file host, which `routes::sitemaps` serves
+pub fn sitemap_path(file_name: &str) -> String {
+    format!("sitemaps/{file_name}")
+}
+
+async fn upload_xml<T: yaserde::YaSerialize>(
+    file_name: &str,
+    data: &T,
+    file_host: &Arc<dyn FileHost + Send + Sync>,
+) -> Result<(), ApiError> {
+    let xml = yaserde::ser::to_string(data).map_err(ApiError::Xml)?;
+    file_host
+        .upload_file(
+            "application/xml",
+            &sitemap_path(file_name),
+            Bytes::from(xml),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Generates the sitemaps of public projects, users and organizations, paged by ID, along
+/// with an index of every page
+pub async fn generate_sitemaps(
+    pool: &PgPool,
+    file_host: &Arc<dyn FileHost + Send + Sync>,
+) -> Result<(), ApiError> {
+    let site_url = dotenvy::var("SITE_URL")?;
+    let api_url = dotenvy::var("SELF_ADDR")?;
+    let today = Utc::now().format("%Y-%m-%d").to_string();
+
+    let mut index = SitemapIndex::default();
+    for kind in SitemapKind::ALL {
+        let mut after = 0;
+        for page in 1.. {
+            let urls = kind.fetch_page(after, &site_url, pool).await?;
+            let Some((last_id, _)) = urls.last() else {
+                break;
+            };
+            after = *last_id;
+            let done = (urls.len() as i64) < SITEMAP_PAGE_SIZE;
+
+            let file_name = format!("{}-{}.xml", kind.as_str(), page);
+            upload_xml(
+                &file_name,
+                &UrlSet {
+                    urls: urls.into_iter().map(|x| x.1).collect(),
+                },
+                file_host,
+            )
+            .await?;
+            index.sitemaps.push(SitemapUrl {
+                loc: format!("{api_url}/sitemaps/{file_name}"),
+                lastmod: today.clone(),
+            });
+
+            if done {
+                break;
+            }
+        }
+    }
+
+    upload_xml("index.xml", &index, file_host).await?;
+    info!("Generated {} sitemap pages", index.sitemaps.len());
+
+    Ok(())
+}
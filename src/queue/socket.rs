@@ -4,12 +4,15 @@ use dashmap::DashMap;
 
 pub struct ActiveSockets {
     pub auth_sockets: DashMap<String, Session>,
+    /// Sockets following the processing of uploads, by upload ID
+    pub upload_sockets: DashMap<String, Session>,
 }
 
 impl Default for ActiveSockets {
     fn default() -> Self {
         Self {
             auth_sockets: DashMap::new(),
+            upload_sockets: DashMap::new(),
         }
     }
 }
@@ -0,0 +1,226 @@
+use crate::database::models::job_item::Job;
+use crate::database::models::DatabaseError;
+use crate::models::jobs::JobPayload;
+use crate::util::env::parse_strings_from_var;
+use chrono::{DateTime, Datelike, Duration, Utc};
+use log::info;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use thiserror::Error;
+
+/// The colour of the Discord embed reports are posted as
+const EMBED_COLOR: u32 = 0x1bd96a;
+
+#[derive(Error, Debug)]
+pub enum StaffReportError {
+    #[error("Database Error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("Database Error: {0}")]
+    SqlxDatabase(#[from] sqlx::Error),
+    #[error("Serialization Error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Error while posting the report: {0}")]
+    Webhook(#[from] reqwest::Error),
+}
+
+/// What happened on the site over a week, from Monday to Monday in UTC
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StaffReport {
+    pub week_start: DateTime<Utc>,
+    pub week_end: DateTime<Utc>,
+    /// Projects submitted for review
+    pub submissions: i64,
+    /// Projects moderators approved or rejected out of the review queue
+    pub approvals: i64,
+    pub rejections: i64,
+    pub new_users: i64,
+    pub reports_filed: i64,
+    /// Payouts requested by creators, leaving out failed and cancelled ones
+    pub payouts: i64,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub payout_volume: Decimal,
+}
+
+impl StaffReport {
+    /// The rows of the report, as labels and values
+    fn rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Submissions", self.submissions.to_string()),
+            ("Approvals", self.approvals.to_string()),
+            ("Rejections", self.rejections.to_string()),
+            ("New users", self.new_users.to_string()),
+            ("Reports filed", self.reports_filed.to_string()),
+            (
+                "Payouts",
+                format!("{} (${})", self.payouts, self.payout_volume.round_dp(2)),
+            ),
+        ]
+    }
+
+    fn title(&self) -> String {
+        format!(
+            "Weekly report for {} to {}",
+            self.week_start.format("%Y-%m-%d"),
+            (self.week_end - Duration::days(1)).format("%Y-%m-%d")
+        )
+    }
+}
+
+/// Gets the stats of the week starting at `week_start`
+pub async fn get_staff_report<'a, E>(
+    week_start: DateTime<Utc>,
+    exec: E,
+) -> Result<StaffReport, sqlx::Error>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let week_end = week_start + Duration::days(7);
+
+    // Status changes are read from the messages they leave in project threads
+    let stats = sqlx::query!(
+        "
+        SELECT
+            (
+                SELECT COUNT(*) FROM threads_messages
+                WHERE created >= $1 AND created < $2
+                AND body->>'type' = 'status_change' AND body->>'new_status' = 'processing'
+            ) submissions,
+            (
+                SELECT COUNT(*) FROM threads_messages
+                WHERE created >= $1 AND created < $2
+                AND body->>'type' = 'status_change' AND body->>'old_status' = 'processing'
+                AND body->>'new_status' IN ('approved', 'unlisted', 'private', 'archived', 'scheduled')
+            ) approvals,
+            (
+                SELECT COUNT(*) FROM threads_messages
+                WHERE created >= $1 AND created < $2
+                AND body->>'type' = 'status_change' AND body->>'old_status' = 'processing'
+                AND body->>'new_status' IN ('rejected', 'withheld')
+            ) rejections,
+            (SELECT COUNT(*) FROM users WHERE created >= $1 AND created < $2) new_users,
+            (SELECT COUNT(*) FROM reports WHERE created >= $1 AND created < $2) reports_filed,
+            (
+                SELECT COUNT(*) FROM payouts
+                WHERE created >= $1 AND created < $2
+                AND status NOT IN ('failed', 'cancelled', 'cancelling')
+            ) payouts,
+            (
+                SELECT SUM(amount) FROM payouts
+                WHERE created >= $1 AND created < $2
+                AND status NOT IN ('failed', 'cancelled', 'cancelling')
+            ) payout_volume
+        ",
+        week_start,
+        week_end,
+    )
+    .fetch_one(exec)
+    .await?;
+
+    Ok(StaffReport {
+        week_start,
+        week_end,
+        submissions: stats.submissions.unwrap_or(0),
+        approvals: stats.approvals.unwrap_or(0),
+        rejections: stats.rejections.unwrap_or(0),
+        new_users: stats.new_users.unwrap_or(0),
+        reports_filed: stats.reports_filed.unwrap_or(0),
+        payouts: stats.payouts.unwrap_or(0),
+        payout_volume: stats.payout_volume.unwrap_or_default(),
+    })
+}
+
+/// Sends the report of the last full week to the staff webhook in `STAFF_REPORT_WEBHOOK` and
+/// the addresses in `STAFF_REPORT_EMAILS`, unless it was already sent. Nothing is sent, or
+/// recorded as sent, while neither is configured
+pub async fn send_staff_report(pool: &PgPool) -> Result<(), StaffReportError> {
+    let webhook_url = dotenvy::var("STAFF_REPORT_WEBHOOK")
+        .ok()
+        .filter(|x| !x.is_empty());
+    let emails = parse_strings_from_var("STAFF_REPORT_EMAILS").unwrap_or_default();
+    if webhook_url.is_none() && emails.is_empty() {
+        return Ok(());
+    }
+
+    let today = Utc::now().date_naive();
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let week_end = DateTime::<Utc>::from_naive_utc_and_offset(
+        monday.and_hms_opt(0, 0, 0).unwrap_or_default(),
+        Utc,
+    );
+    let week_start = week_end - Duration::days(7);
+
+    let mut transaction = pool.begin().await?;
+    let report = get_staff_report(week_start, &mut *transaction).await?;
+
+    let inserted = sqlx::query!(
+        "
+        INSERT INTO staff_reports (week_start, stats)
+        VALUES ($1, $2)
+        ON CONFLICT (week_start) DO NOTHING
+        ",
+        week_start,
+        serde_json::to_value(&report)?,
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected()
+        > 0;
+    if !inserted {
+        info!("Staff report for {} was already sent", week_start);
+        return Ok(());
+    }
+
+    // The emails and the record of the report are only committed once the webhook was posted
+    // to, so a failed post does not send the emails twice when the job is retried
+    let body = format!(
+        "<h2>{}</h2><table>{}</table>",
+        report.title(),
+        report
+            .rows()
+            .into_iter()
+            .map(|(label, value)| format!(
+                "<tr><th align=\"left\">{label}</th><td>{value}</td></tr>"
+            ))
+            .collect::<String>()
+    );
+    for to in emails {
+        Job::enqueue(
+            JobPayload::SendEmail {
+                to,
+                subject: report.title(),
+                body: body.clone(),
+            },
+            Utc::now(),
+            &mut *transaction,
+        )
+        .await?;
+    }
+
+    if let Some(webhook_url) = webhook_url {
+        let embed = json!({
+            "embeds": [{
+                "title": report.title(),
+                "color": EMBED_COLOR,
+                "timestamp": report.week_end,
+                "fields": report
+                    .rows()
+                    .into_iter()
+                    .map(|(name, value)| json!({ "name": name, "value": value, "inline": true }))
+                    .collect::<Vec<_>>(),
+            }],
+        });
+
+        reqwest::Client::new()
+            .post(&webhook_url)
+            .json(&embed)
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
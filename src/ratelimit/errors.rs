@@ -1,5 +1,6 @@
 //! Errors that can occur during middleware processing stage
 use crate::models::error::ApiError;
+use crate::routes::ErrorCode;
 use actix_web::ResponseError;
 use log::*;
 use thiserror::Error;
@@ -10,18 +11,14 @@ use thiserror::Error;
 ///
 #[derive(Debug, Error)]
 pub enum ARError {
-    /// Read/Write error on store
-    #[error("read/write operation failed: {0}")]
-    ReadWrite(String),
-
     /// Identifier error
     #[error("client identification failed")]
     Identification,
     /// Limited Error
     #[error("You are being rate-limited. Please wait {reset} seconds. {remaining}/{max_requests} remaining.")]
     Limited {
-        max_requests: usize,
-        remaining: usize,
+        max_requests: u64,
+        remaining: u64,
         reset: u64,
     },
 }
@@ -35,17 +32,23 @@ impl ResponseError for ARError {
                 reset,
             } => {
                 let mut response = actix_web::HttpResponse::TooManyRequests();
+                response.insert_header(("ratelimit-limit", max_requests.to_string()));
+                response.insert_header(("ratelimit-remaining", remaining.to_string()));
+                response.insert_header(("ratelimit-reset", reset.to_string()));
+                response.insert_header(("retry-after", reset.to_string()));
                 response.insert_header(("x-ratelimit-limit", max_requests.to_string()));
                 response.insert_header(("x-ratelimit-remaining", remaining.to_string()));
                 response.insert_header(("x-ratelimit-reset", reset.to_string()));
                 response.json(ApiError {
-                    error: "ratelimit_error",
+                    error: ErrorCode::RatelimitError.as_str(),
                     description: &self.to_string(),
+                    fields: Vec::new(),
                 })
             }
             _ => actix_web::HttpResponse::build(self.status_code()).json(ApiError {
-                error: "ratelimit_error",
+                error: ErrorCode::RatelimitError.as_str(),
                 description: &self.to_string(),
+                fields: Vec::new(),
             }),
         }
     }
@@ -1,13 +1,20 @@
+use crate::database::models::oauth_token_item::OAuthAccessToken;
+use crate::database::models::pat_item::PersonalAccessToken;
+use crate::database::models::session_item::Session;
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
+use crate::models::ids::OAuthClientId;
 use crate::ratelimit::errors::ARError;
-use crate::ratelimit::{ActorMessage, ActorResponse};
-use actix::dev::*;
+use crate::ratelimit::{is_approved_oauth_client, route_cost, RateLimitTier, RATE_LIMIT_WINDOW};
 use actix_web::{
     dev::{Service, ServiceRequest, ServiceResponse, Transform},
     error::Error as AWError,
-    http::header::{HeaderName, HeaderValue},
+    http::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION},
 };
+use chrono::Utc;
 use futures::future::{ok, Ready};
 use log::*;
+use sqlx::PgPool;
 use std::{
     cell::RefCell,
     future::Future,
@@ -15,63 +22,43 @@ use std::{
     pin::Pin,
     rc::Rc,
     task::{Context, Poll},
-    time::Duration,
 };
 
+const RATE_LIMIT_NAMESPACE: &str = "rate_limit";
+
 type RateLimiterIdentifier = Rc<Box<dyn Fn(&ServiceRequest) -> Result<String, ARError> + 'static>>;
 
-pub struct RateLimiter<T>
-where
-    T: Handler<ActorMessage> + Send + Sync + 'static,
-    T::Context: ToEnvelope<T, ActorMessage>,
-{
-    interval: Duration,
-    max_requests: usize,
-    store: Addr<T>,
+pub struct RateLimiter {
+    pool: PgPool,
+    redis: RedisPool,
     identifier: RateLimiterIdentifier,
     ignore_key: Option<String>,
 }
 
-impl<T> RateLimiter<T>
-where
-    T: Handler<ActorMessage> + Send + Sync + 'static,
-    <T as Actor>::Context: ToEnvelope<T, ActorMessage>,
-{
-    /// Creates a new instance of `RateLimiter` with the provided address of `StoreActor`.
-    pub fn new(store: Addr<T>) -> Self {
+impl RateLimiter {
+    /// Creates a new instance of `RateLimiter`, which identifies anonymous clients by their
+    /// peer address
+    pub fn new(pool: PgPool, redis: RedisPool) -> Self {
         let identifier = |req: &ServiceRequest| {
             let connection_info = req.connection_info();
             let ip = connection_info.peer_addr().ok_or(ARError::Identification)?;
             Ok(String::from(ip))
         };
         RateLimiter {
-            interval: Duration::from_secs(0),
-            max_requests: 0,
-            store,
+            pool,
+            redis,
             identifier: Rc::new(Box::new(identifier)),
             ignore_key: None,
         }
     }
 
-    /// Specify the interval. The counter for a client is reset after this interval
-    pub fn with_interval(mut self, interval: Duration) -> Self {
-        self.interval = interval;
-        self
-    }
-
-    /// Specify the maximum number of requests allowed in the given interval.
-    pub fn with_max_requests(mut self, max_requests: usize) -> Self {
-        self.max_requests = max_requests;
-        self
-    }
-
     /// Sets key which can be used to bypass rate-limiter
     pub fn with_ignore_key(mut self, ignore_key: Option<String>) -> Self {
         self.ignore_key = ignore_key;
         self
     }
 
-    /// Function to get the identifier for the client request
+    /// Function to get the IP address anonymous clients are identified by
     pub fn with_identifier<F: Fn(&ServiceRequest) -> Result<String, ARError> + 'static>(
         mut self,
         identifier: F,
@@ -81,26 +68,23 @@ where
     }
 }
 
-impl<T, S, B> Transform<S, ServiceRequest> for RateLimiter<T>
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
 where
-    T: Handler<ActorMessage> + Send + Sync + 'static,
-    T::Context: ToEnvelope<T, ActorMessage>,
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = AWError> + 'static,
     S::Future: 'static,
     B: 'static,
 {
     type Response = ServiceResponse<B>;
     type Error = S::Error;
-    type Transform = RateLimitMiddleware<S, T>;
+    type Transform = RateLimitMiddleware<S>;
     type InitError = ();
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
         ok(RateLimitMiddleware {
             service: Rc::new(RefCell::new(service)),
-            store: self.store.clone(),
-            max_requests: self.max_requests,
-            interval: self.interval.as_secs(),
+            pool: self.pool.clone(),
+            redis: self.redis.clone(),
             identifier: self.identifier.clone(),
             ignore_key: self.ignore_key.clone(),
         })
@@ -108,27 +92,22 @@ where
 }
 
 /// Service factory for RateLimiter
-pub struct RateLimitMiddleware<S, T>
+pub struct RateLimitMiddleware<S>
 where
     S: 'static,
-    T: Handler<ActorMessage> + 'static,
 {
     service: Rc<RefCell<S>>,
-    store: Addr<T>,
-    // Exists here for the sole purpose of knowing the max_requests and interval from RateLimiter
-    max_requests: usize,
-    interval: u64,
+    pool: PgPool,
+    redis: RedisPool,
     identifier: RateLimiterIdentifier,
     ignore_key: Option<String>,
 }
 
-impl<T, S, B> Service<ServiceRequest> for RateLimitMiddleware<S, T>
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
 where
-    T: Handler<ActorMessage> + 'static,
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = AWError> + 'static,
     S::Future: 'static,
     B: 'static,
-    T::Context: ToEnvelope<T, ActorMessage>,
 {
     type Response = ServiceResponse<B>;
     type Error = S::Error;
@@ -139,14 +118,13 @@ where
     }
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let store = self.store.clone();
         let srv = self.service.clone();
-        let max_requests = self.max_requests;
-        let interval = Duration::from_secs(self.interval);
+        let pool = self.pool.clone();
+        let redis = self.redis.clone();
         let identifier = self.identifier.clone();
         let ignore_key = self.ignore_key.clone();
         Box::pin(async move {
-            let identifier: String = (identifier)(&req)?;
+            let ip: String = (identifier)(&req)?;
 
             if let Some(ignore_key) = ignore_key {
                 if let Some(key) = req.headers().get("x-ratelimit-key") {
@@ -158,103 +136,210 @@ where
                 }
             }
 
-            let remaining: ActorResponse = store
-                .send(ActorMessage::Get(String::from(&identifier)))
-                .await
-                .map_err(|_| ARError::Identification)?;
-            match remaining {
-                ActorResponse::Get(opt) => {
-                    let opt = opt.await?;
-                    if let Some(c) = opt {
-                        // Existing entry in store
-                        let expiry = store
-                            .send(ActorMessage::Expire(String::from(&identifier)))
-                            .await
-                            .map_err(|_| ARError::ReadWrite("Setting timeout".to_string()))?;
-                        let reset: Duration = match expiry {
-                            ActorResponse::Expire(dur) => dur.await?,
-                            _ => unreachable!(),
-                        };
-                        if c == 0 {
-                            info!("Limit exceeded for client: {}", &identifier);
-                            Err(ARError::Limited {
-                                max_requests,
-                                remaining: c,
-                                reset: reset.as_secs(),
-                            }
-                            .into())
-                        } else {
-                            // Decrement value
-                            let res: ActorResponse = store
-                                .send(ActorMessage::Update {
-                                    key: identifier,
-                                    value: 1,
-                                })
-                                .await
-                                .map_err(|_| {
-                                    ARError::ReadWrite("Decrementing ratelimit".to_string())
-                                })?;
-                            let updated_value: usize = match res {
-                                ActorResponse::Update(c) => c.await?,
-                                _ => unreachable!(),
-                            };
-                            // Execute the request
-                            let fut = srv.call(req);
-                            let mut res = fut.await?;
-                            let headers = res.headers_mut();
-                            // Safe unwraps, since usize is always convertible to string
-                            headers.insert(
-                                HeaderName::from_static("x-ratelimit-limit"),
-                                HeaderValue::from_str(max_requests.to_string().as_str())?,
-                            );
-                            headers.insert(
-                                HeaderName::from_static("x-ratelimit-remaining"),
-                                HeaderValue::from_str(updated_value.to_string().as_str())?,
-                            );
-                            headers.insert(
-                                HeaderName::from_static("x-ratelimit-reset"),
-                                HeaderValue::from_str(reset.as_secs().to_string().as_str())?,
-                            );
-                            Ok(res)
-                        }
-                    } else {
-                        // New client, create entry in store
-                        let current_value = max_requests - 1;
-                        let res = store
-                            .send(ActorMessage::Set {
-                                key: String::from(&identifier),
-                                value: current_value,
-                                expiry: interval,
-                            })
-                            .await
-                            .map_err(|_| ARError::ReadWrite("Creating store entry".to_string()))?;
-                        match res {
-                            ActorResponse::Set(c) => c.await?,
-                            _ => unreachable!(),
-                        }
-                        let fut = srv.call(req);
-                        let mut res = fut.await?;
-                        let headers = res.headers_mut();
-                        // Safe unwraps, since usize is always convertible to string
-                        headers.insert(
-                            HeaderName::from_static("x-ratelimit-limit"),
-                            HeaderValue::from_str(max_requests.to_string().as_str()).unwrap(),
-                        );
-                        headers.insert(
-                            HeaderName::from_static("x-ratelimit-remaining"),
-                            HeaderValue::from_str(current_value.to_string().as_str()).unwrap(),
-                        );
-                        headers.insert(
-                            HeaderName::from_static("x-ratelimit-reset"),
-                            HeaderValue::from_str(interval.as_secs().to_string().as_str()).unwrap(),
-                        );
-                        Ok(res)
-                    }
+            let cost = route_cost(req.method(), req.path());
+            let status = match hit(req.headers(), &ip, cost, &pool, &redis).await {
+                Ok(status) => status,
+                // Requests are let through rather than failed while Redis is unavailable
+                Err(err) => {
+                    warn!("Rate limiting failed: {:?}", err);
+                    let fut = srv.call(req);
+                    let res = fut.await?;
+                    return Ok(res);
                 }
-                _ => {
-                    unreachable!();
+            };
+
+            if status.exceeded {
+                info!("Limit exceeded for client: {}", &status.client);
+                return Err(ARError::Limited {
+                    max_requests: status.limit,
+                    remaining: 0,
+                    reset: status.reset,
                 }
+                .into());
+            }
+
+            // Execute the request
+            let fut = srv.call(req);
+            let mut res = fut.await?;
+            let headers = res.headers_mut();
+            for (name, value) in [
+                ("ratelimit-limit", status.limit),
+                ("ratelimit-remaining", status.remaining),
+                ("ratelimit-reset", status.reset),
+                ("x-ratelimit-limit", status.limit),
+                ("x-ratelimit-remaining", status.remaining),
+                ("x-ratelimit-reset", status.reset),
+            ] {
+                headers.insert(
+                    HeaderName::from_static(name),
+                    HeaderValue::from_str(value.to_string().as_str())?,
+                );
             }
+            Ok(res)
         })
     }
 }
+
+struct WindowStatus {
+    client: String,
+    limit: u64,
+    remaining: u64,
+    /// Seconds until the current window ends
+    reset: u64,
+    exceeded: bool,
+}
+
+/// Counts a request against its client's quota. The count is estimated over a sliding window,
+/// by weighting the previous fixed window's count by how much of it overlaps the sliding one.
+/// Limited requests count too, so clients retrying in a tight loop stay limited.
+async fn hit(
+    headers: &HeaderMap,
+    ip: &str,
+    cost: i64,
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<WindowStatus, DatabaseError> {
+    let token = headers.get(AUTHORIZATION).and_then(|x| x.to_str().ok());
+    let (tier, client) = identify_client(token, ip, pool, redis)
+        .await?
+        .swap_remove(0);
+    let limit = tier.quota();
+
+    let now = Utc::now().timestamp();
+    let (current, previous) = redis
+        .connect()
+        .await?
+        .incr_window(RATE_LIMIT_NAMESPACE, &client, cost, RATE_LIMIT_WINDOW, now)
+        .await?;
+
+    let elapsed = now % RATE_LIMIT_WINDOW;
+    let used = sliding_window_count(current, previous, elapsed);
+
+    Ok(WindowStatus {
+        client,
+        limit: limit as u64,
+        remaining: (limit - used).max(0) as u64,
+        reset: (RATE_LIMIT_WINDOW - elapsed) as u64,
+        exceeded: used > limit,
+    })
+}
+
+fn sliding_window_count(current: i64, previous: i64, elapsed: i64) -> i64 {
+    (previous * (RATE_LIMIT_WINDOW - elapsed)) / RATE_LIMIT_WINDOW + current
+}
+
+/// The state of a quota requests are counted against
+pub struct BucketStatus {
+    pub tier: RateLimitTier,
+    pub limit: i64,
+    pub remaining: i64,
+    /// Seconds until the current window ends
+    pub reset: i64,
+    /// Whether requests made with the same token as this request are counted against it
+    pub current_token: bool,
+}
+
+/// Gets the quotas a client's requests are counted against, without counting a request.
+/// The first is the one requests made with the same token are counted against.
+pub async fn bucket_statuses(
+    token: Option<&str>,
+    ip: &str,
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<Vec<BucketStatus>, DatabaseError> {
+    let buckets = identify_client(token, ip, pool, redis).await?;
+
+    let now = Utc::now().timestamp();
+    let index = now / RATE_LIMIT_WINDOW;
+    let elapsed = now % RATE_LIMIT_WINDOW;
+
+    let mut redis = redis.connect().await?;
+    let mut statuses = Vec::with_capacity(buckets.len());
+    for (i, (tier, client)) in buckets.into_iter().enumerate() {
+        let counts = redis
+            .multi_get::<i64>(
+                RATE_LIMIT_NAMESPACE,
+                [
+                    format!("{}:{}", client, index),
+                    format!("{}:{}", client, index - 1),
+                ],
+            )
+            .await?;
+        let used = sliding_window_count(counts[0].unwrap_or(0), counts[1].unwrap_or(0), elapsed);
+
+        let limit = tier.quota();
+        statuses.push(BucketStatus {
+            tier,
+            limit,
+            remaining: (limit - used).max(0),
+            reset: RATE_LIMIT_WINDOW - elapsed,
+            current_token: i == 0,
+        });
+    }
+
+    Ok(statuses)
+}
+
+/// Finds the tiers of the client making a request, and the keys its requests are counted
+/// under. The first is the one counted against by this request, and the others are the ones of
+/// the same user's other tokens. Requests with an invalid token are counted by IP address, so
+/// made up tokens cannot be used to get a fresh quota.
+async fn identify_client(
+    token: Option<&str>,
+    ip: &str,
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<Vec<(RateLimitTier, String)>, DatabaseError> {
+    let anonymous = vec![(RateLimitTier::Anonymous, format!("ip:{}", ip))];
+
+    let Some(token) = token else {
+        return Ok(anonymous);
+    };
+
+    let client = match token.split_once('_') {
+        Some(("mrp", _)) => PersonalAccessToken::get(token, pool, redis)
+            .await?
+            .filter(|x| x.expires > Utc::now())
+            .map(|x| {
+                vec![(
+                    RateLimitTier::Authenticated,
+                    format!("user:{}", x.user_id.0),
+                )]
+            }),
+        Some(("mra", _)) => Session::get(token, pool, redis)
+            .await?
+            .filter(|x| x.expires > Utc::now())
+            .map(|x| {
+                vec![(
+                    RateLimitTier::Authenticated,
+                    format!("user:{}", x.user_id.0),
+                )]
+            }),
+        Some(("mro", _)) => {
+            OAuthAccessToken::get_cached(OAuthAccessToken::hash_token(token), pool, redis)
+                .await?
+                .filter(|x| x.expires > Utc::now())
+                .map(|x| {
+                    let client_id = OAuthClientId::from(x.client_id).to_string();
+                    let user = (
+                        RateLimitTier::Authenticated,
+                        format!("user:{}", x.user_id.0),
+                    );
+                    if x.client_verified || is_approved_oauth_client(&client_id) {
+                        vec![
+                            (
+                                RateLimitTier::ApprovedApp,
+                                format!("oauth:{}:{}", client_id, x.user_id.0),
+                            ),
+                            user,
+                        ]
+                    } else {
+                        vec![user]
+                    }
+                })
+        }
+        _ => None,
+    };
+
+    Ok(client.unwrap_or(anonymous))
+}
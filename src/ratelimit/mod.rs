@@ -1,64 +1,85 @@
-use std::future::Future;
-use std::marker::Send;
-use std::pin::Pin;
-use std::time::Duration;
-
-use crate::ratelimit::errors::ARError;
-use actix::dev::*;
+use crate::util::env::{parse_strings_from_var, parse_var};
+use actix_web::http::Method;
+use serde::Serialize;
 
 pub mod errors;
-pub mod memory;
-/// The code for this module was directly taken from https://github.com/TerminalWitchcraft/actix-ratelimit
-/// with some modifications including upgrading it to Actix 4!
+/// Rate limits requests with a sliding window shared by all instances through Redis
 pub mod middleware;
 
-/// Represents message that can be handled by a `StoreActor`
-pub enum ActorMessage {
-    /// Get the remaining count based on the provided identifier
-    Get(String),
-    /// Set the count of the client identified by `key` to `value` valid for `expiry`
-    Set {
-        key: String,
-        value: usize,
-        expiry: Duration,
-    },
-    /// Change the value of count for the client identified by `key` by `value`
-    Update { key: String, value: usize },
-    /// Get the expiration time for the client.
-    Expire(String),
-    /// Remove the client from the store
-    Remove(String),
+/// The length of a rate limit window, in seconds
+pub const RATE_LIMIT_WINDOW: i64 = 60;
+
+/// The kinds of clients rate limits are applied to, each with its own quota
+#[derive(Serialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitTier {
+    /// Limited by IP address
+    Anonymous,
+    /// Limited by user, for requests made with a session, PAT or OAuth token
+    Authenticated,
+    /// Limited by user and app, for requests made with a token of a verified OAuth app, or of
+    /// one approved for a higher quota
+    ApprovedApp,
+}
+
+impl RateLimitTier {
+    /// The cost of the requests allowed per window
+    pub fn quota(&self) -> i64 {
+        match self {
+            RateLimitTier::Anonymous => parse_var("RATE_LIMIT_ANONYMOUS").unwrap_or(300),
+            RateLimitTier::Authenticated => parse_var("RATE_LIMIT_AUTHENTICATED").unwrap_or(600),
+            RateLimitTier::ApprovedApp => parse_var("RATE_LIMIT_APPROVED_APP").unwrap_or(3000),
+        }
+    }
 }
 
-impl Message for ActorMessage {
-    type Result = ActorResponse;
+/// Whether tokens of an OAuth app are given the approved app quota without it being verified
+pub fn is_approved_oauth_client(client_id: &str) -> bool {
+    parse_strings_from_var("RATE_LIMIT_APPROVED_OAUTH_CLIENTS")
+        .unwrap_or_default()
+        .iter()
+        .any(|x| x == client_id)
 }
 
-/// Wrapper type for `Pin<Box<dyn Future>>` type
-pub type Output<T> = Pin<Box<dyn Future<Output = Result<T, ARError>> + Send>>;
+/// Routes costing more than one request, as a method, a path and the cost. Paths also match
+/// the routes nested under them
+pub const ROUTE_COSTS: &[(&str, &str, i64)] = &[
+    ("GET", "/v2/search", 2),
+    ("GET", "/v3/search", 2),
+    ("POST", "/v2/version_files", 3),
+    ("POST", "/v3/version_files", 3),
+    ("POST", "/v2/project", 5),
+    ("POST", "/v3/project", 5),
+    ("POST", "/v2/version", 5),
+    ("POST", "/v3/version", 5),
+];
 
-/// Represents data returned in response to `Messages` by a `StoreActor`
-pub enum ActorResponse {
-    /// Returned in response to [Messages::Get](enum.Messages.html)
-    Get(Output<Option<usize>>),
-    /// Returned in response to [Messages::Set](enum.Messages.html)
-    Set(Output<()>),
-    /// Returned in response to [Messages::Update](enum.Messages.html)
-    Update(Output<usize>),
-    /// Returned in response to [Messages::Expire](enum.Messages.html)
-    Expire(Output<Duration>),
-    /// Returned in response to [Messages::Remove](enum.Messages.html)
-    Remove(Output<usize>),
+/// How much of the quota a request uses
+pub fn route_cost(method: &Method, path: &str) -> i64 {
+    ROUTE_COSTS
+        .iter()
+        .find(|(route_method, route_path, _)| {
+            method.as_str() == *route_method
+                && path
+                    .strip_prefix(route_path)
+                    .map(|rest| rest.is_empty() || rest.starts_with('/'))
+                    .unwrap_or(false)
+        })
+        .map(|(_, _, cost)| *cost)
+        .unwrap_or(1)
 }
 
-impl<A, M> MessageResponse<A, M> for ActorResponse
-where
-    A: Actor,
-    M: actix::Message<Result = ActorResponse>,
-{
-    fn handle(self, _: &mut A::Context, tx: Option<OneshotSender<Self>>) {
-        if let Some(tx) = tx {
-            let _ = tx.send(self);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_costs_match_nested_routes_only() {
+        assert_eq!(route_cost(&Method::GET, "/v2/search"), 2);
+        assert_eq!(route_cost(&Method::POST, "/v2/version"), 5);
+        assert_eq!(route_cost(&Method::POST, "/v2/version/AABBCCDD/file"), 5);
+        assert_eq!(route_cost(&Method::POST, "/v2/version_file/abcdef"), 1);
+        assert_eq!(route_cost(&Method::POST, "/v2/version_files"), 3);
+        assert_eq!(route_cost(&Method::GET, "/v2/version/AABBCCDD"), 1);
     }
 }
@@ -1,6 +1,6 @@
 use crate::auth::get_user_from_headers;
 use crate::database::redis::RedisPool;
-use crate::models::analytics::{PageView, Playtime};
+use crate::models::analytics::{PageView, Playtime, PlaytimeSession};
 use crate::models::pats::Scopes;
 use crate::queue::analytics::AnalyticsQueue;
 use crate::queue::maxmind::MaxMindIndexer;
@@ -8,18 +8,25 @@ use crate::queue::session::AuthQueue;
 use crate::routes::ApiError;
 use crate::util::date::get_current_tenths_of_ms;
 use crate::util::env::parse_strings_from_var;
+use crate::util::traffic::traffic_source;
+use crate::util::user_agent::classify_user_agent;
 use actix_web::{post, web};
 use actix_web::{HttpRequest, HttpResponse};
-use serde::Deserialize;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
 use sqlx::PgPool;
 use std::collections::HashMap;
 use std::net::{AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr};
 use std::sync::Arc;
 use url::Url;
+use validator::Validate;
 
 pub const FILTERED_HEADERS: &[&str] = &[
     "authorization",
     "cookie",
+    // stored sanitized as the traffic source instead
+    "referer",
     "modrinth-admin",
     // we already retrieve/use these elsewhere- so they are unneeded
     "user-agent",
@@ -52,6 +59,8 @@ pub fn convert_to_ip_v6(src: &str) -> Result<Ipv6Addr, AddrParseError> {
 #[derive(Deserialize)]
 pub struct UrlInput {
     url: String,
+    /// The referrer of the page (`document.referrer`), if any
+    referrer: Option<String>,
 }
 
 //this route should be behind the cloudflare WAF to prevent non-browsers from calling it
@@ -105,15 +114,25 @@ pub async fn page_view_ingest(
     })
     .unwrap_or_else(|_| Ipv4Addr::new(127, 0, 0, 1).to_ipv6_mapped());
 
+    let location = maxmind.query(ip).await;
+    let user_agent = headers.get("user-agent").cloned().unwrap_or_default();
+    let (platform, os) = classify_user_agent(&user_agent);
+    let traffic = traffic_source(Some(&url), url_input.referrer.as_deref());
+
     let mut view = PageView {
         recorded: get_current_tenths_of_ms(),
         domain: domain.to_string(),
         site_path: url.path().to_string(),
         user_id: 0,
         project_id: 0,
-        ip,
-        country: maxmind.query(ip).await.unwrap_or_default(),
-        user_agent: headers.get("user-agent").cloned().unwrap_or_default(),
+        country: location.country,
+        continent: location.continent,
+        platform: platform.as_str().to_string(),
+        os: os.as_str().to_string(),
+        referrer: traffic.referrer,
+        source: traffic.source,
+        medium: traffic.medium,
+        campaign: traffic.campaign,
         headers: headers
             .into_iter()
             .filter(|x| !FILTERED_HEADERS.contains(&&*x.0))
@@ -215,3 +234,93 @@ pub async fn playtime_ingest(
 
     Ok(HttpResponse::NoContent().finish())
 }
+
+/// Hashes an identifier with the analytics salt and the current day, so the result can be
+/// counted within a day but not linked across days or reversed
+pub fn hash_analytics_identifier(value: &str) -> u64 {
+    let hash = sha2::Sha256::digest(
+        format!(
+            "{}:{}:{}",
+            dotenvy::var("ANALYTICS_SALT").unwrap_or_default(),
+            Utc::now().date_naive(),
+            value
+        )
+        .as_bytes(),
+    );
+
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+#[derive(Deserialize, Validate, Debug)]
+pub struct PlaytimeSessionsInput {
+    /// A random ID generated by the launcher for each installation. It is never stored as-is
+    #[validate(length(min = 8, max = 64))]
+    pub instance_id: String,
+    #[validate(length(max = 100))]
+    #[validate]
+    pub sessions: Vec<PlaytimeSessionInput>,
+}
+
+#[derive(Serialize, Deserialize, Validate, Debug)]
+pub struct PlaytimeSessionInput {
+    pub version_id: crate::models::ids::VersionId,
+    pub seconds: u32,
+    #[validate(length(max = 64))]
+    pub loader: String,
+    #[validate(length(max = 64))]
+    pub game_version: String,
+}
+
+/// The longest session accepted, in seconds. Anything longer is most likely an idle instance
+const MAX_SESSION_SECONDS: u32 = 24 * 60 * 60;
+
+/// Opt-in launcher telemetry: records how long a set of versions were played for in a session.
+/// Launchers should only call this once the user has opted in. No user or IP information is
+/// recorded, and the instance ID is hashed with a daily rotating salt
+pub async fn playtime_sessions_ingest(
+    analytics_queue: web::Data<Arc<AnalyticsQueue>>,
+    sessions_input: web::Json<PlaytimeSessionsInput>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    sessions_input.validate()?;
+
+    let sessions_input = sessions_input.into_inner();
+    let instance = hash_analytics_identifier(&sessions_input.instance_id);
+
+    let versions = crate::database::models::Version::get_many(
+        &sessions_input
+            .sessions
+            .iter()
+            .map(|x| x.version_id.into())
+            .collect::<Vec<_>>(),
+        &**pool,
+        &redis,
+    )
+    .await?;
+
+    for session in sessions_input.sessions {
+        if session.seconds == 0 || session.seconds > MAX_SESSION_SECONDS {
+            continue;
+        }
+
+        if let Some(version) = versions
+            .iter()
+            .find(|x| session.version_id == x.inner.id.into())
+        {
+            analytics_queue.add_playtime_session(PlaytimeSession {
+                recorded: get_current_tenths_of_ms(),
+                seconds: session.seconds as u64,
+                instance,
+                project_id: version.inner.project_id.0 as u64,
+                version_id: version.inner.id.0 as u64,
+                loader: session.loader,
+                game_version: session.game_version,
+            });
+        }
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,110 @@
+use crate::database::redis::RedisPool;
+use crate::file_hosting::FileHost;
+use crate::search::SearchConfig;
+use actix_web::{get, web, HttpResponse};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// How long each dependency is given to answer before it is reported as down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a readiness report is reused, so frequent probes do not add load to dependencies
+const REPORT_CACHE_TIME: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref CACHED_REPORT: Mutex<Option<(Instant, ReadinessReport)>> = Mutex::new(None);
+}
+
+#[derive(Serialize, Clone)]
+struct ReadinessReport {
+    ready: bool,
+    checks: BTreeMap<&'static str, DependencyStatus>,
+}
+
+#[derive(Serialize, Clone)]
+struct DependencyStatus {
+    healthy: bool,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Liveness check. Only shows the process is serving requests
+#[get("/healthz")]
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "healthy": true }))
+}
+
+/// Readiness check. Responds with a 503 if any dependency needed to serve requests is down,
+/// so the instance is taken out of rotation until it recovers
+#[get("/readyz")]
+pub async fn readyz(
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    config: web::Data<SearchConfig>,
+    file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
+) -> HttpResponse {
+    // Held while checking, so concurrent probes wait for one report instead of all checking
+    let mut cached = CACHED_REPORT.lock().await;
+    let report = match &*cached {
+        Some((checked_at, report)) if checked_at.elapsed() < REPORT_CACHE_TIME => report.clone(),
+        _ => {
+            let report = check_dependencies(&pool, &redis, &config, &***file_host).await;
+            *cached = Some((Instant::now(), report.clone()));
+            report
+        }
+    };
+    drop(cached);
+
+    if report.ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+async fn check_dependencies(
+    pool: &PgPool,
+    redis: &RedisPool,
+    config: &SearchConfig,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> ReadinessReport {
+    let (postgres, redis, meilisearch, file_host) = futures::join!(
+        check(async { sqlx::query("SELECT 1").execute(pool).await.map(|_| ()) }),
+        check(async { redis.connect().await?.ping().await }),
+        check(async { config.make_client().health().await.map(|_| ()) }),
+        check(file_host.health_check()),
+    );
+
+    let checks = BTreeMap::from([
+        ("postgres", postgres),
+        ("redis", redis),
+        ("meilisearch", meilisearch),
+        ("file_host", file_host),
+    ]);
+
+    ReadinessReport {
+        ready: checks.values().all(|x| x.healthy),
+        checks,
+    }
+}
+
+async fn check<E: std::fmt::Display>(fut: impl Future<Output = Result<(), E>>) -> DependencyStatus {
+    let start = Instant::now();
+    let error = match tokio::time::timeout(CHECK_TIMEOUT, fut).await {
+        Ok(Ok(())) => None,
+        Ok(Err(err)) => Some(err.to_string()),
+        Err(_) => Some("timed out".to_string()),
+    };
+
+    DependencyStatus {
+        healthy: error.is_none(),
+        latency_ms: start.elapsed().as_millis(),
+        error,
+    }
+}
@@ -1,27 +1,87 @@
+use crate::auth::email::send_email_raw;
+use crate::auth::email::template::{
+    get_template_content, render_template, EmailTemplate, EmailTemplateContent,
+};
+use crate::auth::get_user_from_headers;
 use crate::auth::validate::get_user_record_from_bearer_token;
+use crate::database::models::categories::{Category, ProjectType};
+use crate::database::models::email_template_item::EmailTemplateItem;
+use crate::database::models::feature_flag_item::FeatureFlag;
+use crate::database::models::featured_item::FeaturedRow;
+use crate::database::models::job_item::Job;
+use crate::database::models::legal_document_item::LegalDocument;
+use crate::database::models::loader_fields::{
+    Game, Loader, LoaderField, LoaderFieldEnum, LoaderFieldEnumValue, LoaderFieldType,
+};
+use crate::database::models::moderation_template_item::ModerationTemplate;
+use crate::database::models::User;
 use crate::database::redis::RedisPool;
+use crate::database::ReadOnlyPool;
 use crate::models::analytics::Download;
+use crate::models::featured::FeaturedKind;
 use crate::models::ids::ProjectId;
+use crate::models::jobs::{JobPayload, JobStatus};
+use crate::models::legal::LegalDocumentKind;
 use crate::models::pats::Scopes;
-use crate::queue::analytics::AnalyticsQueue;
+use crate::queue::analytics::{is_excluded_download_asn, AnalyticsQueue};
 use crate::queue::maxmind::MaxMindIndexer;
 use crate::queue::session::AuthQueue;
 use crate::routes::ApiError;
 use crate::search::SearchConfig;
 use crate::util::date::get_current_tenths_of_ms;
 use crate::util::guards::admin_key_guard;
-use actix_web::{patch, post, web, HttpRequest, HttpResponse};
-use serde::Deserialize;
+use crate::util::maintenance::{self, MaintenanceState};
+use crate::util::reload::{reload, ReloadError};
+use crate::util::traffic::traffic_source;
+use crate::util::user_agent::classify_user_agent;
+use crate::util::validate::RE_URL_SAFE;
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::collections::HashMap;
 use std::net::Ipv4Addr;
 use std::sync::Arc;
+use validator::Validate;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("admin")
             .service(count_download)
-            .service(force_reindex),
+            .service(force_reindex)
+            .service(email_templates_list)
+            .service(email_template_edit)
+            .service(email_template_delete)
+            .service(email_preview)
+            .service(email_test)
+            .service(jobs_list)
+            .service(job_retry)
+            .service(feature_flags_list)
+            .service(feature_flag_edit)
+            .service(feature_flag_delete)
+            .service(feature_flag_override_edit)
+            .service(feature_flag_override_delete)
+            .service(moderation_template_edit)
+            .service(moderation_template_delete)
+            .service(loader_edit)
+            .service(loader_delete)
+            .service(loader_field_edit)
+            .service(loader_field_delete)
+            .service(loader_field_enum_edit)
+            .service(loader_field_enum_value_edit)
+            .service(loader_field_enum_value_delete)
+            .service(category_edit)
+            .service(category_delete)
+            .service(featured_list)
+            .service(featured_create)
+            .service(featured_edit)
+            .service(featured_delete)
+            .service(legal_documents_list)
+            .service(legal_document_publish)
+            .service(maintenance_get)
+            .service(maintenance_enable)
+            .service(maintenance_disable)
+            .service(config_reload),
     );
 }
 
@@ -101,7 +161,33 @@ pub async fn count_download(
     let ip = crate::routes::analytics::convert_to_ip_v6(&download_body.ip)
         .unwrap_or_else(|_| Ipv4Addr::new(127, 0, 0, 1).to_ipv6_mapped());
 
-    analytics_queue.add_download(Download {
+    // Downloads from CI providers and other known bot networks are not counted
+    if maxmind
+        .query_asn(ip)
+        .await
+        .map(is_excluded_download_asn)
+        .unwrap_or(false)
+    {
+        return Ok(HttpResponse::NoContent().body(""));
+    }
+
+    let location = maxmind.query(ip).await;
+    let user_agent = download_body
+        .headers
+        .get("user-agent")
+        .cloned()
+        .unwrap_or_default();
+    let (platform, os) = classify_user_agent(&user_agent);
+    let traffic = traffic_source(
+        Some(&url),
+        download_body
+            .headers
+            .iter()
+            .find(|x| x.0.to_lowercase() == "referer")
+            .map(|x| &**x.1),
+    );
+
+    let download = Download {
         recorded: get_current_tenths_of_ms(),
         domain: url.host_str().unwrap_or_default().to_string(),
         site_path: url.path().to_string(),
@@ -116,20 +202,23 @@ pub async fn count_download(
             .unwrap_or(0),
         project_id: project_id as u64,
         version_id: version_id as u64,
-        ip,
-        country: maxmind.query(ip).await.unwrap_or_default(),
-        user_agent: download_body
-            .headers
-            .get("user-agent")
-            .cloned()
-            .unwrap_or_default(),
+        country: location.country,
+        continent: location.continent,
+        platform: platform.as_str().to_string(),
+        os: os.as_str().to_string(),
+        referrer: traffic.referrer,
+        source: traffic.source,
+        medium: traffic.medium,
+        campaign: traffic.campaign,
         headers: download_body
             .headers
             .clone()
             .into_iter()
             .filter(|x| !crate::routes::analytics::FILTERED_HEADERS.contains(&&*x.0.to_lowercase()))
             .collect(),
-    });
+    };
+
+    analytics_queue.add_download(download, ip, &user_agent);
 
     Ok(HttpResponse::NoContent().body(""))
 }
@@ -137,11 +226,1231 @@ pub async fn count_download(
 #[post("/_force_reindex", guard = "admin_key_guard")]
 pub async fn force_reindex(
     pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadOnlyPool>,
     redis: web::Data<RedisPool>,
     config: web::Data<SearchConfig>,
 ) -> Result<HttpResponse, ApiError> {
     use crate::search::indexing::index_projects;
     let redis = redis.get_ref();
-    index_projects(pool.as_ref().clone(), redis.clone(), &config).await?;
+    index_projects(
+        pool.as_ref().clone(),
+        read_pool.as_ref().clone(),
+        redis.clone(),
+        &config,
+    )
+    .await?;
     Ok(HttpResponse::NoContent().finish())
 }
+
+async fn get_admin_user(
+    req: &HttpRequest,
+    pool: &PgPool,
+    redis: &RedisPool,
+    session_queue: &AuthQueue,
+) -> Result<crate::models::users::User, ApiError> {
+    let user = get_user_from_headers(
+        req,
+        pool,
+        redis,
+        session_queue,
+        Some(&[Scopes::SESSION_ACCESS]),
+    )
+    .await?
+    .1;
+
+    if !user.role.is_admin() {
+        return Err(ApiError::CustomAuthentication(
+            "You do not have permission to access this route!".to_string(),
+        ));
+    }
+
+    Ok(user)
+}
+
+#[derive(Serialize)]
+pub struct EmailTemplateLocales {
+    pub template: EmailTemplate,
+    /// Locales with a template stored in the database. Every template is also bundled in the
+    /// default locale
+    pub locales: Vec<String>,
+}
+
+#[get("/email/templates")]
+pub async fn email_templates_list(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let stored = EmailTemplateItem::get_all(&**pool).await?;
+
+    let templates = EmailTemplate::iterator()
+        .map(|template| EmailTemplateLocales {
+            template,
+            locales: stored
+                .iter()
+                .filter(|x| x.template == template)
+                .map(|x| x.locale.clone())
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(templates))
+}
+
+#[put("/email/templates/{template}/{locale}")]
+pub async fn email_template_edit(
+    req: HttpRequest,
+    info: web::Path<(EmailTemplate, String)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    content: web::Json<EmailTemplateContent>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let (template, locale) = info.into_inner();
+    if locale.is_empty() || locale.len() > 16 {
+        return Err(ApiError::InvalidInput("Invalid locale!".to_string()));
+    }
+
+    let mut transaction = pool.begin().await?;
+    EmailTemplateItem {
+        template,
+        locale: locale.to_lowercase(),
+        content: content.into_inner(),
+    }
+    .upsert(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[delete("/email/templates/{template}/{locale}")]
+pub async fn email_template_delete(
+    req: HttpRequest,
+    info: web::Path<(EmailTemplate, String)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let (template, locale) = info.into_inner();
+
+    let mut transaction = pool.begin().await?;
+    let result =
+        EmailTemplateItem::remove(template, &locale.to_lowercase(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EmailPreview {
+    pub template: EmailTemplate,
+    pub locale: String,
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
+
+#[post("/email/preview")]
+pub async fn email_preview(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    preview: web::Json<EmailPreview>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let content = get_template_content(preview.template, &preview.locale, &**pool).await?;
+
+    Ok(HttpResponse::Ok().json(render_template(&content, &preview.variables)))
+}
+
+/// Sends a rendered template to the requesting admin's own email address
+#[post("/email/test")]
+pub async fn email_test(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    preview: web::Json<EmailPreview>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let email = user.email.ok_or_else(|| {
+        ApiError::InvalidInput("Your account does not have an email address!".to_string())
+    })?;
+
+    let content = get_template_content(preview.template, &preview.locale, &**pool).await?;
+    let rendered = render_template(&content, &preview.variables);
+    send_email_raw(email, rendered.subject, rendered.body)?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[derive(Deserialize)]
+pub struct JobsQuery {
+    pub status: Option<JobStatus>,
+}
+
+/// Lists background jobs with a status, dead jobs by default
+#[get("/jobs")]
+pub async fn jobs_list(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    query: web::Query<JobsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let jobs = Job::get_by_status(query.status.unwrap_or(JobStatus::Dead), 100, &**pool)
+        .await?
+        .into_iter()
+        .map(crate::models::jobs::Job::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+/// Queues a dead job to run again
+#[post("/jobs/{id}/retry")]
+pub async fn job_retry(
+    req: HttpRequest,
+    info: web::Path<(i64,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    if !Job::retry(info.into_inner().0, &**pool).await? {
+        return Err(ApiError::InvalidInput(
+            "This job is not dead, or an equivalent job is already queued!".to_string(),
+        ));
+    }
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[get("/feature_flags")]
+pub async fn feature_flags_list(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let flags = FeatureFlag::get_all(&**pool, &redis)
+        .await?
+        .into_iter()
+        .map(crate::models::feature_flags::FeatureFlag::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(flags))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EditFeatureFlag {
+    #[validate(length(max = 2048))]
+    #[serde(default)]
+    pub description: String,
+    pub enabled: bool,
+    #[validate(range(min = 0, max = 100))]
+    pub rollout_percentage: i16,
+}
+
+/// Creates a flag, or updates the flag with the same key
+#[put("/feature_flags/{key}")]
+pub async fn feature_flag_edit(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    edit: web::Json<EditFeatureFlag>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    edit.validate()?;
+
+    let key = info.into_inner().0;
+    if key.is_empty() || key.len() > 64 || !RE_URL_SAFE.is_match(&key) {
+        return Err(ApiError::InvalidInput(
+            "Invalid feature flag key!".to_string(),
+        ));
+    }
+
+    let edit = edit.into_inner();
+    let mut transaction = pool.begin().await?;
+    FeatureFlag {
+        key,
+        description: edit.description,
+        enabled: edit.enabled,
+        rollout_percentage: edit.rollout_percentage,
+        overrides: HashMap::new(),
+        created: Utc::now(),
+        updated: Utc::now(),
+    }
+    .upsert(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    FeatureFlag::clear_cache(&redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[delete("/feature_flags/{key}")]
+pub async fn feature_flag_delete(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let mut transaction = pool.begin().await?;
+    let result = FeatureFlag::remove(&info.into_inner().0, &mut transaction).await?;
+    transaction.commit().await?;
+
+    FeatureFlag::clear_cache(&redis).await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EditFeatureFlagOverride {
+    pub enabled: bool,
+}
+
+/// Turns a flag on or off for a user, regardless of its rollout
+#[put("/feature_flags/{key}/overrides/{user}")]
+pub async fn feature_flag_override_edit(
+    req: HttpRequest,
+    info: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    edit: web::Json<EditFeatureFlagOverride>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let (key, user) = info.into_inner();
+    if FeatureFlag::get(&key, &**pool, &redis).await?.is_none() {
+        return Err(ApiError::NotFound);
+    }
+    let user = User::get(&user, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let mut transaction = pool.begin().await?;
+    FeatureFlag::set_override(&key, user.id, edit.enabled, &mut transaction).await?;
+    transaction.commit().await?;
+
+    FeatureFlag::clear_cache(&redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[delete("/feature_flags/{key}/overrides/{user}")]
+pub async fn feature_flag_override_delete(
+    req: HttpRequest,
+    info: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let (key, user) = info.into_inner();
+    let user = User::get(&user, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let mut transaction = pool.begin().await?;
+    let result = FeatureFlag::remove_override(&key, user.id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    FeatureFlag::clear_cache(&redis).await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+/// Gets the current read-only mode, or `null` if it is off
+#[get("/maintenance")]
+pub async fn maintenance_get(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let state = maintenance::get_state(&redis).await?;
+
+    Ok(HttpResponse::Ok().json(state))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EditModerationTemplate {
+    #[validate(length(min = 1, max = 255))]
+    pub title: String,
+    #[validate(length(min = 1, max = 65536))]
+    pub body: String,
+    #[serde(default)]
+    pub private: bool,
+}
+
+/// Creates a moderation response template, or updates the template with the same name
+#[put("/moderation/templates/{name}")]
+pub async fn moderation_template_edit(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    edit: web::Json<EditModerationTemplate>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    edit.validate()?;
+
+    let name = info.into_inner().0;
+    if name.is_empty() || name.len() > 64 || !RE_URL_SAFE.is_match(&name) {
+        return Err(ApiError::InvalidInput("Invalid template name!".to_string()));
+    }
+
+    let edit = edit.into_inner();
+    let mut transaction = pool.begin().await?;
+    ModerationTemplate {
+        name,
+        title: edit.title,
+        body: edit.body,
+        private: edit.private,
+        updated: Utc::now(),
+    }
+    .upsert(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[delete("/moderation/templates/{name}")]
+pub async fn moderation_template_delete(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let mut transaction = pool.begin().await?;
+    let result = ModerationTemplate::remove(&info.into_inner().0, &mut transaction).await?;
+    transaction.commit().await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EditLoader {
+    /// The loader's icon, as an SVG
+    #[validate(length(min = 1, max = 20000))]
+    pub icon: String,
+    /// The names of the project types the loader supports
+    pub supported_project_types: Vec<String>,
+    /// The slugs of the games the loader supports its project types in
+    pub supported_games: Vec<String>,
+    #[serde(default)]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Creates a loader, or updates the loader with the same name
+#[put("/loaders/{name}")]
+pub async fn loader_edit(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    edit: web::Json<EditLoader>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    edit.validate()?;
+
+    let name = info.into_inner().0;
+    if name.is_empty() || name.len() > 64 || !RE_URL_SAFE.is_match(&name) {
+        return Err(ApiError::InvalidInput("Invalid loader name!".to_string()));
+    }
+
+    let edit = edit.into_inner();
+    let mut project_types = Vec::new();
+    for project_type in &edit.supported_project_types {
+        project_types.push(
+            ProjectType::get_id(project_type, &**pool)
+                .await?
+                .ok_or_else(|| {
+                    ApiError::InvalidInput(format!("Project type {project_type} does not exist!"))
+                })?,
+        );
+    }
+    let mut games = Vec::new();
+    for game in &edit.supported_games {
+        games.push(
+            Game::get_slug(game, &**pool, &redis)
+                .await?
+                .ok_or_else(|| ApiError::InvalidInput(format!("Game {game} does not exist!")))?
+                .id,
+        );
+    }
+
+    let mut transaction = pool.begin().await?;
+    let id = Loader::upsert(
+        &name,
+        &edit.icon,
+        &serde_json::Value::Object(edit.metadata),
+        &mut transaction,
+    )
+    .await?;
+    Loader::set_project_types(id, &project_types, &games, &mut transaction).await?;
+    transaction.commit().await?;
+
+    Loader::clear_cache(id, &name, &redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[delete("/loaders/{name}")]
+pub async fn loader_delete(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let name = info.into_inner().0;
+    let id = Loader::get_id(&name, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if Loader::is_used(id, &**pool).await? {
+        return Err(ApiError::InvalidInput(
+            "Loaders with versions cannot be deleted!".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+    let result = Loader::remove(id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    Loader::clear_cache(id, &name, &redis).await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EditLoaderField {
+    /// One of `integer`, `text`, `boolean` and `enum`, or their `array_` forms
+    pub field_type: String,
+    /// The name of the enum the values of `enum` and `array_enum` fields come from
+    pub enum_type: Option<String>,
+    pub optional: bool,
+    pub min_val: Option<i32>,
+    pub max_val: Option<i32>,
+    /// The names of the loaders the field is available for
+    pub loaders: Vec<String>,
+}
+
+/// Creates a loader field, or updates the field with the same name. The type of a field
+/// can't change once versions have values for it
+#[put("/loader_fields/{name}")]
+pub async fn loader_field_edit(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    edit: web::Json<EditLoaderField>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let name = info.into_inner().0;
+    if name.is_empty() || name.len() > 64 || !RE_URL_SAFE.is_match(&name) {
+        return Err(ApiError::InvalidInput(
+            "Invalid loader field name!".to_string(),
+        ));
+    }
+
+    let edit = edit.into_inner();
+    if let (Some(min_val), Some(max_val)) = (edit.min_val, edit.max_val) {
+        if min_val > max_val {
+            return Err(ApiError::InvalidInput(
+                "The minimum value of a field cannot be greater than its maximum!".to_string(),
+            ));
+        }
+    }
+
+    let enum_id = if let Some(enum_type) = &edit.enum_type {
+        Some(
+            LoaderFieldEnum::get(enum_type, &**pool, &redis)
+                .await?
+                .ok_or_else(|| ApiError::InvalidInput(format!("Enum {enum_type} does not exist!")))?
+                .id
+                .0,
+        )
+    } else {
+        None
+    };
+    let field_type = LoaderFieldType::build(&edit.field_type, enum_id)
+        .ok_or_else(|| ApiError::InvalidInput("Invalid field type!".to_string()))?;
+
+    let existing = LoaderField::get_fields_all(&**pool, &redis)
+        .await?
+        .into_iter()
+        .find(|x| x.field == name);
+    if let Some(existing) = existing {
+        if existing.field_type != field_type && LoaderField::is_used(existing.id, &**pool).await? {
+            return Err(ApiError::InvalidInput(
+                "The type of a field with values on versions cannot be changed!".to_string(),
+            ));
+        }
+    }
+
+    let mut loader_ids = Vec::new();
+    for loader in &edit.loaders {
+        loader_ids.push(
+            Loader::get_id(loader, &**pool, &redis)
+                .await?
+                .ok_or_else(|| {
+                    ApiError::InvalidInput(format!("Loader {loader} does not exist!"))
+                })?,
+        );
+    }
+
+    let mut transaction = pool.begin().await?;
+    let id = LoaderField::upsert(
+        &name,
+        &field_type,
+        edit.optional,
+        edit.min_val,
+        edit.max_val,
+        &mut transaction,
+    )
+    .await?;
+    LoaderField::set_loaders(id, &loader_ids, &mut transaction).await?;
+    transaction.commit().await?;
+
+    // The field may have been taken away from loaders too, so every loader's fields are cleared
+    let all_loader_ids = Loader::list(&**pool, &redis)
+        .await?
+        .into_iter()
+        .map(|x| x.id)
+        .collect::<Vec<_>>();
+    LoaderField::clear_cache(&all_loader_ids, &redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[delete("/loader_fields/{name}")]
+pub async fn loader_field_delete(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let name = info.into_inner().0;
+    let field = LoaderField::get_fields_all(&**pool, &redis)
+        .await?
+        .into_iter()
+        .find(|x| x.field == name)
+        .ok_or(ApiError::NotFound)?;
+    if LoaderField::is_used(field.id, &**pool).await? {
+        return Err(ApiError::InvalidInput(
+            "Fields with values on versions cannot be deleted!".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+    let result = LoaderField::remove(field.id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    let all_loader_ids = Loader::list(&**pool, &redis)
+        .await?
+        .into_iter()
+        .map(|x| x.id)
+        .collect::<Vec<_>>();
+    LoaderField::clear_cache(&all_loader_ids, &redis).await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EditLoaderFieldEnum {
+    pub ordering: Option<i32>,
+    /// Whether values of the enum can be hidden from pickers, like snapshot game versions
+    #[serde(default)]
+    pub hidable: bool,
+}
+
+/// Creates the enum loader fields take their values from, or updates the enum with the same
+/// name
+#[put("/loader_field_enums/{name}")]
+pub async fn loader_field_enum_edit(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    edit: web::Json<EditLoaderFieldEnum>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let name = info.into_inner().0;
+    if name.is_empty() || name.len() > 64 || !RE_URL_SAFE.is_match(&name) {
+        return Err(ApiError::InvalidInput("Invalid enum name!".to_string()));
+    }
+
+    let mut transaction = pool.begin().await?;
+    let id = LoaderFieldEnum::upsert(&name, edit.ordering, edit.hidable, &mut transaction).await?;
+    transaction.commit().await?;
+
+    LoaderFieldEnum::clear_cache(id, &name, &redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[derive(Deserialize)]
+pub struct EditLoaderFieldEnumValue {
+    pub ordering: Option<i32>,
+    #[serde(default)]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Creates a value of an enum, or updates the value with the same name
+#[put("/loader_field_enums/{name}/values/{value}")]
+pub async fn loader_field_enum_value_edit(
+    req: HttpRequest,
+    info: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    edit: web::Json<EditLoaderFieldEnumValue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let (name, value) = info.into_inner();
+    if value.is_empty() || value.len() > 64 {
+        return Err(ApiError::InvalidInput("Invalid enum value!".to_string()));
+    }
+    let loader_field_enum = LoaderFieldEnum::get(&name, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let edit = edit.into_inner();
+    let mut transaction = pool.begin().await?;
+    LoaderFieldEnumValue::upsert(
+        loader_field_enum.id,
+        &value,
+        edit.ordering,
+        &serde_json::Value::Object(edit.metadata),
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    LoaderFieldEnum::clear_cache(loader_field_enum.id, &name, &redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[delete("/loader_field_enums/{name}/values/{value}")]
+pub async fn loader_field_enum_value_delete(
+    req: HttpRequest,
+    info: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let (name, value) = info.into_inner();
+    let loader_field_enum = LoaderFieldEnum::get(&name, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if LoaderFieldEnumValue::is_used(loader_field_enum.id, &value, &**pool).await? {
+        return Err(ApiError::InvalidInput(
+            "Enum values set on versions cannot be deleted!".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+    let result =
+        LoaderFieldEnumValue::remove(loader_field_enum.id, &value, &mut transaction).await?;
+    transaction.commit().await?;
+
+    LoaderFieldEnum::clear_cache(loader_field_enum.id, &name, &redis).await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EditCategory {
+    /// The category's icon, as an SVG
+    #[validate(length(min = 1, max = 20000))]
+    pub icon: String,
+    #[validate(length(max = 64))]
+    pub header: String,
+    #[serde(default)]
+    pub ordering: i64,
+    /// The name of the category of the same project type to nest this one under
+    pub parent: Option<String>,
+}
+
+/// Creates a category, or updates the category with the same name and project type. Projects
+/// are reindexed when the category is moved, since search matches them by their categories'
+/// ancestors
+#[put("/categories/{project_type}/{name}")]
+pub async fn category_edit(
+    req: HttpRequest,
+    info: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    edit: web::Json<EditCategory>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    edit.validate()?;
+
+    let (project_type_name, name) = info.into_inner();
+    if name.is_empty() || name.len() > 64 || !RE_URL_SAFE.is_match(&name) {
+        return Err(ApiError::InvalidInput("Invalid category name!".to_string()));
+    }
+    let project_type = ProjectType::get_id(&project_type_name, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let edit = edit.into_inner();
+    let existing = Category::get_id_project(&name, project_type, &**pool).await?;
+    let parent = match &edit.parent {
+        Some(parent) => {
+            let parent_id = Category::get_id_project(parent, project_type, &**pool)
+                .await?
+                .ok_or_else(|| {
+                    ApiError::InvalidInput(format!("Category {parent} does not exist!"))
+                })?;
+            if let Some(existing) = existing {
+                if parent_id == existing
+                    || Category::get_ancestor_ids(parent_id, &**pool)
+                        .await?
+                        .contains(&existing)
+                {
+                    return Err(ApiError::InvalidInput(
+                        "Categories can't be nested under themselves!".to_string(),
+                    ));
+                }
+            }
+            Some(parent_id)
+        }
+        None => None,
+    };
+
+    let moved = Category::list(&**pool, &redis)
+        .await?
+        .into_iter()
+        .find(|x| x.category == name && x.project_type == project_type_name)
+        .map_or(parent.is_some(), |x| x.parent != edit.parent);
+
+    let mut transaction = pool.begin().await?;
+    Category::upsert(
+        &name,
+        project_type,
+        &edit.icon,
+        &edit.header,
+        edit.ordering,
+        parent,
+        &mut transaction,
+    )
+    .await?;
+    if moved {
+        Job::enqueue(JobPayload::IndexProjects, Utc::now(), &mut *transaction).await?;
+    }
+    transaction.commit().await?;
+
+    Category::clear_cache(&redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[delete("/categories/{project_type}/{name}")]
+pub async fn category_delete(
+    req: HttpRequest,
+    info: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let (project_type_name, name) = info.into_inner();
+    let project_type = ProjectType::get_id(&project_type_name, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let id = Category::get_id_project(&name, project_type, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if Category::is_used(id, &**pool).await? {
+        return Err(ApiError::InvalidInput(
+            "Categories with projects cannot be deleted!".to_string(),
+        ));
+    }
+
+    let has_children = Category::list(&**pool, &redis)
+        .await?
+        .iter()
+        .any(|x| x.project_type == project_type_name && x.parent.as_ref() == Some(&name));
+
+    let mut transaction = pool.begin().await?;
+    let result = Category::remove(id, &mut transaction).await?;
+    if has_children {
+        Job::enqueue(JobPayload::IndexProjects, Utc::now(), &mut *transaction).await?;
+    }
+    transaction.commit().await?;
+
+    Category::clear_cache(&redis).await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[get("/featured")]
+pub async fn featured_list(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let rows = FeaturedRow::get_all(&**pool, &redis)
+        .await?
+        .into_iter()
+        .map(crate::models::featured::FeaturedRow::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(rows))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EditFeaturedRow {
+    pub kind: FeaturedKind,
+    #[validate(length(min = 1, max = 255))]
+    pub title: String,
+    #[validate(length(max = 2048))]
+    #[serde(default)]
+    pub description: String,
+    #[validate(length(max = 64))]
+    pub project_ids: Vec<ProjectId>,
+    /// Defaults to now
+    pub starts: Option<DateTime<Utc>>,
+    pub ends: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub ordering: i32,
+}
+
+impl EditFeaturedRow {
+    async fn into_row(self, id: i64, pool: &PgPool) -> Result<FeaturedRow, ApiError> {
+        let starts = self.starts.unwrap_or_else(Utc::now);
+        if self.ends.map_or(false, |x| x <= starts) {
+            return Err(ApiError::InvalidInput(
+                "A featured row must end after it starts!".to_string(),
+            ));
+        }
+
+        let project_ids = self
+            .project_ids
+            .into_iter()
+            .map(crate::database::models::ProjectId::from)
+            .collect::<Vec<_>>();
+        let found = sqlx::query!(
+            "
+            SELECT COUNT(DISTINCT id) FROM mods WHERE id = ANY($1)
+            ",
+            &project_ids.iter().map(|x| x.0).collect::<Vec<_>>(),
+        )
+        .fetch_one(pool)
+        .await?
+        .count
+        .unwrap_or(0);
+        let mut unique = project_ids.clone();
+        unique.sort_by_key(|x| x.0);
+        unique.dedup();
+        if found != unique.len() as i64 {
+            return Err(ApiError::InvalidInput(
+                "One or more of the featured projects do not exist!".to_string(),
+            ));
+        }
+
+        Ok(FeaturedRow {
+            id,
+            kind: self.kind,
+            title: self.title,
+            description: self.description,
+            project_ids,
+            starts,
+            ends: self.ends,
+            ordering: self.ordering,
+            created: Utc::now(),
+        })
+    }
+}
+
+/// Schedules a featured row, which is shown from its start until its end
+#[post("/featured")]
+pub async fn featured_create(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    edit: web::Json<EditFeaturedRow>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    edit.validate()?;
+    let mut row = edit.into_inner().into_row(0, &pool).await?;
+
+    let mut transaction = pool.begin().await?;
+    row.id = row.insert(&mut transaction).await?;
+    transaction.commit().await?;
+
+    FeaturedRow::clear_cache(&redis).await?;
+
+    Ok(HttpResponse::Ok().json(crate::models::featured::FeaturedRow::from(row)))
+}
+
+#[put("/featured/{id}")]
+pub async fn featured_edit(
+    req: HttpRequest,
+    info: web::Path<(i64,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    edit: web::Json<EditFeaturedRow>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    edit.validate()?;
+    let row = edit
+        .into_inner()
+        .into_row(info.into_inner().0, &pool)
+        .await?;
+
+    let mut transaction = pool.begin().await?;
+    let result = row.update(&mut transaction).await?;
+    transaction.commit().await?;
+
+    FeaturedRow::clear_cache(&redis).await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[delete("/featured/{id}")]
+pub async fn featured_delete(
+    req: HttpRequest,
+    info: web::Path<(i64,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let mut transaction = pool.begin().await?;
+    let result = FeaturedRow::remove(info.into_inner().0, &mut transaction).await?;
+    transaction.commit().await?;
+
+    FeaturedRow::clear_cache(&redis).await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+/// Lists every published version of every legal document, newest first
+#[get("/legal")]
+pub async fn legal_documents_list(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let documents = LegalDocument::get_all(&**pool)
+        .await?
+        .into_iter()
+        .map(crate::models::legal::LegalDocument::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(documents))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct PublishLegalDocument {
+    pub kind: LegalDocumentKind,
+    #[validate(length(min = 1, max = 262144))]
+    pub content: String,
+    /// Whether users have to accept the new version before making changes
+    #[serde(default = "default_material")]
+    pub material: bool,
+}
+
+fn default_material() -> bool {
+    true
+}
+
+/// Publishes a new version of a legal document. Published versions can't be edited, so fixes are
+/// published as new versions which are not material
+#[post("/legal")]
+pub async fn legal_document_publish(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    publish: web::Json<PublishLegalDocument>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    publish.validate()?;
+
+    let mut transaction = pool.begin().await?;
+    let document = LegalDocument::insert(
+        publish.kind,
+        &publish.content,
+        publish.material,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    LegalDocument::clear_cache(&redis).await?;
+
+    Ok(HttpResponse::Ok().json(crate::models::legal::LegalDocument::from(document)))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EnableMaintenance {
+    #[validate(length(min = 1, max = 2048))]
+    pub message: String,
+    pub retry_after: Option<u64>,
+}
+
+/// Turns read-only mode on, refusing changes outside of admin routes
+#[put("/maintenance")]
+pub async fn maintenance_enable(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    body: web::Json<EnableMaintenance>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    body.validate()?;
+
+    let body = body.into_inner();
+    maintenance::set_state(
+        Some(&MaintenanceState {
+            message: body.message,
+            retry_after: body.retry_after.unwrap_or(maintenance::DEFAULT_RETRY_AFTER),
+            started: Utc::now(),
+        }),
+        &redis,
+    )
+    .await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[delete("/maintenance")]
+pub async fn maintenance_disable(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    maintenance::set_state(None, &redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+/// Reloads the configuration which can be changed without a restart, like sending SIGHUP
+#[post("/config/reload")]
+pub async fn config_reload(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    get_admin_user(&req, &pool, &redis, &session_queue).await?;
+
+    let changed = reload(&redis).await.map_err(|err| match err {
+        ReloadError::Database(err) => ApiError::Database(err),
+        err => ApiError::InvalidInput(err.to_string()),
+    })?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "changed": changed })))
+}
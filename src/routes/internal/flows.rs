@@ -1,21 +1,30 @@
 use crate::auth::email::send_email;
+use crate::auth::email::template::{request_locale, EmailTemplate};
 use crate::auth::validate::get_user_record_from_bearer_token;
 use crate::auth::{get_user_from_headers, AuthProvider, AuthenticationError};
+use crate::database::models::email_suppression_item::EmailSuppression;
 use crate::database::models::flow_item::Flow;
+use crate::database::models::spam_flag_item::SpamFlag;
 use crate::database::redis::RedisPool;
 use crate::file_hosting::FileHost;
 use crate::models::ids::base62_impl::{parse_base62, to_base62};
 use crate::models::ids::random_base62_rng;
+use crate::models::moderation::ImageSource;
 use crate::models::pats::Scopes;
 use crate::models::users::{Badges, Role};
+use crate::queue::maxmind::MaxMindIndexer;
 use crate::queue::session::AuthQueue;
 use crate::queue::socket::ActiveSockets;
 use crate::routes::internal::session::issue_session;
 use crate::routes::ApiError;
 use crate::util::captcha::check_turnstile_captcha;
+use crate::util::circuit_breaker::breaker;
 use crate::util::env::parse_strings_from_var;
 use crate::util::ext::{get_image_content_type, get_image_ext};
-use crate::util::validate::{validation_errors_to_string, RE_URL_SAFE};
+use crate::util::image_scan;
+use crate::util::money::BASE_CURRENCY;
+use crate::util::spam::{self, SpamVerdict};
+use crate::util::validate::RE_URL_SAFE;
 use actix_web::web::{scope, Data, Payload, Query, ServiceConfig};
 use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
 use actix_ws::Closed;
@@ -52,6 +61,7 @@ pub fn config(cfg: &mut ServiceConfig) {
             .service(resend_verify_email)
             .service(set_email)
             .service(verify_email)
+            .service(restore_account)
             .service(subscribe_newsletter),
     );
 }
@@ -217,21 +227,37 @@ impl TempUser {
                     None
                 },
                 venmo_handle: None,
+                stripe_account_id: None,
+                stripe_payouts_enabled: false,
                 totp_secret: None,
                 username,
                 name: self.name,
                 email: self.email,
                 email_verified: true,
-                avatar_url,
+                email_undeliverable: false,
+                avatar_url: avatar_url.clone(),
                 bio: self.bio,
                 created: Utc::now(),
                 role: Role::Developer.to_string(),
                 badges: Badges::default(),
                 balance: Decimal::ZERO,
+                balance_currency: BASE_CURRENCY.to_string(),
+                deleted: None,
             }
             .insert(transaction)
             .await?;
 
+            if let Some(avatar_url) = avatar_url {
+                image_scan::queue_scan(
+                    &avatar_url,
+                    ImageSource::UserAvatar {
+                        user_id: user_id.into(),
+                    },
+                    &mut **transaction,
+                )
+                .await?;
+            }
+
             Ok(user_id)
         } else {
             Err(AuthenticationError::InvalidCredentials)
@@ -316,6 +342,30 @@ impl AuthProvider {
         })
     }
 
+    /// Exchanges the callback's code for a token, and gets the user it belongs to. Calls to a
+    /// provider stop for a while after it fails several times in a row
+    pub async fn get_oauth_user(
+        &self,
+        query: HashMap<String, String>,
+    ) -> Result<TempUser, AuthenticationError> {
+        let breaker = breaker(&format!("oauth_{}", self.as_str()));
+        breaker.check()?;
+
+        let result = async {
+            let token = self.get_token(query).await?;
+            self.get_user(&token).await
+        }
+        .await;
+
+        match &result {
+            Ok(_) => breaker.record_success(),
+            Err(AuthenticationError::Reqwest(_)) => breaker.record_failure(),
+            Err(_) => {}
+        }
+
+        result
+    }
+
     pub async fn get_token(
         &self,
         query: HashMap<String, String>,
@@ -1142,20 +1192,18 @@ pub async fn auth_callback(
     let sockets = active_sockets.clone();
     let state = state_string.clone();
     let res: Result<HttpResponse, AuthenticationError> = async move {
-
         let flow = Flow::get(&state, &redis).await?;
 
         // Extract cookie header from request
         if let Some(Flow::OAuth {
-                        user_id,
-                        provider,
-                        url,
-                    }) = flow
+            user_id,
+            provider,
+            url,
+        }) = flow
         {
             Flow::remove(&state, &redis).await?;
 
-            let token = provider.get_token(query).await?;
-            let oauth_user = provider.get_user(&token).await?;
+            let oauth_user = provider.get_oauth_user(query).await?;
 
             let user_id_opt = provider.get_user_id(&oauth_user.id, &**client).await?;
 
@@ -1171,7 +1219,7 @@ pub async fn auth_callback(
 
                 let user = crate::database::models::User::get_id(id, &**client, &redis).await?;
 
-                if provider == AuthProvider::PayPal  {
+                if provider == AuthProvider::PayPal {
                     sqlx::query!(
                         "
                         UPDATE users
@@ -1183,16 +1231,17 @@ pub async fn auth_callback(
                         oauth_user.id,
                         id as crate::database::models::ids::UserId,
                     )
-                        .execute(&mut *transaction)
-                        .await?;
+                    .execute(&mut *transaction)
+                    .await?;
                 } else if let Some(email) = user.and_then(|x| x.email) {
                     send_email(
                         email,
-                        "Authentication method added",
-                        &format!("When logging into Modrinth, you can now log in using the {} authentication provider.", provider.as_str()),
-                        "If you did not make this change, please contact us immediately through our support channels on Discord or via email (support@modrinth.com).",
-                        None,
-                    )?;
+                        EmailTemplate::AuthMethodAdded,
+                        &request_locale(&req),
+                        HashMap::from([("provider".to_string(), provider.as_str().to_string())]),
+                        &**client,
+                    )
+                    .await?;
                 }
 
                 transaction.commit().await?;
@@ -1244,22 +1293,30 @@ pub async fn auth_callback(
                                     serde_json::json!({
                                         "error": "2fa_required",
                                         "flow": flow,
-                                    }).to_string()
+                                    })
+                                    .to_string(),
                                 )
-                                .await.map_err(|_| AuthenticationError::SocketError)?;
+                                .await
+                                .map_err(|_| AuthenticationError::SocketError)?;
 
                             let _ = ws_conn.close(None).await;
 
                             return Ok(crate::auth::templates::Success {
-                                icon: user.avatar_url.as_deref().unwrap_or("https://cdn-raw.modrinth.com/placeholder.svg"),
+                                icon: user
+                                    .avatar_url
+                                    .as_deref()
+                                    .unwrap_or("https://cdn-raw.modrinth.com/placeholder.svg"),
                                 name: &user.username,
-                            }.render());
+                            }
+                            .render());
                         }
                     }
 
                     user_id
                 } else {
-                    oauth_user.create_account(provider, &mut transaction, &client, &file_host, &redis).await?
+                    oauth_user
+                        .create_account(provider, &mut transaction, &client, &file_host, &redis)
+                        .await?
                 };
 
                 let session = issue_session(req, user_id, &mut transaction, &redis).await?;
@@ -1283,11 +1340,10 @@ pub async fn auth_callback(
                         .json(serde_json::json!({ "url": redirect_url })))
                 } else {
                     let user = crate::database::models::user_item::User::get_id(
-                        user_id,
-                        &**client,
-                        &redis,
+                        user_id, &**client, &redis,
                     )
-                        .await?.ok_or_else(|| AuthenticationError::InvalidCredentials)?;
+                    .await?
+                    .ok_or_else(|| AuthenticationError::InvalidCredentials)?;
 
                     let mut ws_conn = {
                         let db = sockets.read().await;
@@ -1303,22 +1359,29 @@ pub async fn auth_callback(
                     ws_conn
                         .text(
                             serde_json::json!({
-                                        "code": session.session,
-                                    }).to_string()
+                                "code": session.session,
+                            })
+                            .to_string(),
                         )
-                        .await.map_err(|_| AuthenticationError::SocketError)?;
+                        .await
+                        .map_err(|_| AuthenticationError::SocketError)?;
                     let _ = ws_conn.close(None).await;
 
                     return Ok(crate::auth::templates::Success {
-                        icon: user.avatar_url.as_deref().unwrap_or("https://cdn-raw.modrinth.com/placeholder.svg"),
+                        icon: user
+                            .avatar_url
+                            .as_deref()
+                            .unwrap_or("https://cdn-raw.modrinth.com/placeholder.svg"),
                         name: &user.username,
-                    }.render());
+                    }
+                    .render());
                 }
             }
         } else {
             Err::<HttpResponse, AuthenticationError>(AuthenticationError::InvalidCredentials)
         }
-    }.await;
+    }
+    .await;
 
     // Because this is callback route, if we have an error, we need to ensure we close the original socket if it exists
     if let Err(ref e) = res {
@@ -1387,11 +1450,15 @@ pub async fn delete_auth_provider(
         if let Some(email) = user.email {
             send_email(
                 email,
-                "Authentication method removed",
-                &format!("When logging into Modrinth, you can no longer log in using the {} authentication provider.", delete_provider.provider.as_str()),
-                "If you did not make this change, please contact us immediately through our support channels on Discord or via email (support@modrinth.com).",
-                None,
-            )?;
+                EmailTemplate::AuthMethodRemoved,
+                &request_locale(&req),
+                HashMap::from([(
+                    "provider".to_string(),
+                    delete_provider.provider.as_str().to_string(),
+                )]),
+                &**pool,
+            )
+            .await?;
         }
     }
 
@@ -1444,12 +1511,10 @@ pub async fn create_account_with_password(
     req: HttpRequest,
     pool: Data<PgPool>,
     redis: Data<RedisPool>,
+    maxmind: Data<Arc<MaxMindIndexer>>,
     new_account: web::Json<NewAccount>,
 ) -> Result<HttpResponse, ApiError> {
-    new_account
-        .0
-        .validate()
-        .map_err(|err| ApiError::InvalidInput(validation_errors_to_string(err, None)))?;
+    new_account.0.validate()?;
 
     if !check_turnstile_captcha(&req, &new_account.challenge).await? {
         return Err(ApiError::Turnstile);
@@ -1497,6 +1562,13 @@ pub async fn create_account_with_password(
         ));
     }
 
+    let asn = spam::request_asn(&req, &maxmind).await;
+    let spam_score = spam::score_signup(Some(&new_account.email), asn, &**pool).await?;
+    let spam_verdict = spam::verdict(&spam_score);
+    if spam_verdict == SpamVerdict::Throttle {
+        return Err(ApiError::Throttled);
+    }
+
     let flow = Flow::ConfirmEmail {
         user_id,
         confirm_email: new_account.email.clone(),
@@ -1507,8 +1579,12 @@ pub async fn create_account_with_password(
     send_email_verify(
         new_account.email.clone(),
         flow,
-        &format!("Welcome to Modrinth, {}!", new_account.username),
-    )?;
+        EmailTemplate::WelcomeVerifyEmail,
+        HashMap::from([("username".to_string(), new_account.username.clone())]),
+        &request_locale(&req),
+        &**pool,
+    )
+    .await?;
 
     crate::database::models::User {
         id: user_id,
@@ -1523,21 +1599,33 @@ pub async fn create_account_with_password(
         paypal_country: None,
         paypal_email: None,
         venmo_handle: None,
+        stripe_account_id: None,
+        stripe_payouts_enabled: false,
         totp_secret: None,
         username: new_account.username.clone(),
         name: Some(new_account.username),
         email: Some(new_account.email.clone()),
         email_verified: false,
+        email_undeliverable: false,
         avatar_url: None,
         bio: None,
         created: Utc::now(),
         role: Role::Developer.to_string(),
         badges: Badges::default(),
         balance: Decimal::ZERO,
+        balance_currency: BASE_CURRENCY.to_string(),
+        deleted: None,
     }
     .insert(&mut transaction)
     .await?;
 
+    if let Some(asn) = asn {
+        spam::record_signup(user_id, asn, &mut transaction).await?;
+    }
+    if spam_verdict == SpamVerdict::Review {
+        SpamFlag::insert(user_id, None, &spam_score, &mut *transaction).await?;
+    }
+
     let session = issue_session(req, user_id, &mut transaction, &redis).await?;
     let res = crate::models::sessions::Session::from(session, true, None);
 
@@ -1857,11 +1945,12 @@ pub async fn finish_2fa_flow(
         if let Some(email) = user.email {
             send_email(
                 email,
-                "Two-factor authentication enabled",
-                "When logging into Modrinth, you can now enter a code generated by your authenticator app in addition to entering your usual email address and password.",
-                "If you did not make this change, please contact us immediately through our support channels on Discord or via email (support@modrinth.com).",
-                None,
-            )?;
+                EmailTemplate::TwoFactorEnabled,
+                &request_locale(&req),
+                HashMap::new(),
+                &**pool,
+            )
+            .await?;
         }
 
         transaction.commit().await?;
@@ -1945,11 +2034,12 @@ pub async fn remove_2fa(
     if let Some(email) = user.email {
         send_email(
             email,
-            "Two-factor authentication removed",
-            "When logging into Modrinth, you no longer need two-factor authentication to gain access.",
-            "If you did not make this change, please contact us immediately through our support channels on Discord or via email (support@modrinth.com).",
-            None,
-        )?;
+            EmailTemplate::TwoFactorRemoved,
+            &request_locale(&req),
+            HashMap::new(),
+            &**pool,
+        )
+        .await?;
     }
 
     transaction.commit().await?;
@@ -1991,11 +2081,20 @@ pub async fn reset_password_begin(
         if let Some(email) = user.email {
             send_email(
                 email,
-                "Reset your password",
-                "Please visit the following link below to reset your password. If the button does not work, you can copy the link and paste it into your browser.",
-                "If you did not request for your password to be reset, you can safely ignore this email.",
-                Some(("Reset password", &format!("{}/{}?flow={}", dotenvy::var("SITE_URL")?,  dotenvy::var("SITE_RESET_PASSWORD_PATH")?, flow))),
-            )?;
+                EmailTemplate::ResetPassword,
+                &request_locale(&req),
+                HashMap::from([(
+                    "button_link".to_string(),
+                    format!(
+                        "{}/{}?flow={}",
+                        dotenvy::var("SITE_URL")?,
+                        dotenvy::var("SITE_RESET_PASSWORD_PATH")?,
+                        flow
+                    ),
+                )]),
+                &**pool,
+            )
+            .await?;
         }
     }
 
@@ -2123,19 +2222,20 @@ pub async fn change_password(
     }
 
     if let Some(email) = user.email {
-        let changed = if update_password.is_some() {
-            "changed"
+        let template = if update_password.is_some() {
+            EmailTemplate::PasswordChanged
         } else {
-            "removed"
+            EmailTemplate::PasswordRemoved
         };
 
         send_email(
             email,
-            &format!("Password {}", changed),
-            &format!("Your password has been {} on your account.", changed),
-            "If you did not make this change, please contact us immediately through our support channels on Discord or via email (support@modrinth.com).",
-            None,
-        )?;
+            template,
+            &request_locale(&req),
+            HashMap::new(),
+            &**pool,
+        )
+        .await?;
     }
 
     transaction.commit().await?;
@@ -2158,10 +2258,7 @@ pub async fn set_email(
     email: web::Json<SetEmail>,
     session_queue: Data<AuthQueue>,
 ) -> Result<HttpResponse, ApiError> {
-    email
-        .0
-        .validate()
-        .map_err(|err| ApiError::InvalidInput(validation_errors_to_string(err, None)))?;
+    email.0.validate()?;
 
     let user = get_user_from_headers(
         &req,
@@ -2178,7 +2275,7 @@ pub async fn set_email(
     sqlx::query!(
         "
         UPDATE users
-        SET email = $1, email_verified = FALSE
+        SET email = $1, email_verified = FALSE, email_undeliverable = FALSE
         WHERE (id = $2)
         ",
         email.email,
@@ -2186,15 +2283,18 @@ pub async fn set_email(
     )
     .execute(&mut *transaction)
     .await?;
+    // Setting an address is asking for email to be sent to it, even if it bounced before
+    EmailSuppression::remove(&email.email, &mut *transaction).await?;
 
     if let Some(user_email) = user.email {
         send_email(
             user_email,
-            "Email changed",
-            &format!("Your email has been updated to {} on your account.", email.email),
-            "If you did not make this change, please contact us immediately through our support channels on Discord or via email (support@modrinth.com).",
-            None,
-        )?;
+            EmailTemplate::EmailChanged,
+            &request_locale(&req),
+            HashMap::from([("email".to_string(), email.email.clone())]),
+            &**pool,
+        )
+        .await?;
     }
 
     let flow = Flow::ConfirmEmail {
@@ -2207,8 +2307,12 @@ pub async fn set_email(
     send_email_verify(
         email.email.clone(),
         flow,
-        "We need to verify your email address.",
-    )?;
+        EmailTemplate::VerifyEmail,
+        HashMap::new(),
+        &request_locale(&req),
+        &**pool,
+    )
+    .await?;
 
     transaction.commit().await?;
     crate::database::models::User::clear_caches(&[(user.id.into(), None)], &redis).await?;
@@ -2240,6 +2344,10 @@ pub async fn resend_verify_email(
             ));
         }
 
+        // Users whose address bounced get the verification email once they ask for it, in
+        // case the problem with their inbox was fixed
+        EmailSuppression::remove(&email, &**pool).await?;
+
         let flow = Flow::ConfirmEmail {
             user_id: user.id.into(),
             confirm_email: email.clone(),
@@ -2247,7 +2355,15 @@ pub async fn resend_verify_email(
         .insert(Duration::hours(24), &redis)
         .await?;
 
-        send_email_verify(email, flow, "We need to verify your email address.")?;
+        send_email_verify(
+            email,
+            flow,
+            EmailTemplate::VerifyEmail,
+            HashMap::new(),
+            &request_locale(&req),
+            &**pool,
+        )
+        .await?;
 
         Ok(HttpResponse::NoContent().finish())
     } else {
@@ -2291,7 +2407,7 @@ pub async fn verify_email(
         sqlx::query!(
             "
             UPDATE users
-            SET email_verified = TRUE
+            SET email_verified = TRUE, email_undeliverable = FALSE
             WHERE (id = $1)
             ",
             user.id as crate::database::models::ids::UserId,
@@ -2311,6 +2427,42 @@ pub async fn verify_email(
     }
 }
 
+#[derive(Deserialize)]
+pub struct RestoreAccount {
+    pub flow: String,
+}
+
+/// Restores a deleted account from the link emailed when it was deleted, which works until the
+/// account is purged. The user signs in again afterwards, as deleting it signed them out
+#[post("account/restore")]
+pub async fn restore_account(
+    pool: Data<PgPool>,
+    redis: Data<RedisPool>,
+    restore: web::Json<RestoreAccount>,
+) -> Result<HttpResponse, ApiError> {
+    let flow = Flow::get(&restore.flow, &redis).await?;
+
+    if let Some(Flow::RestoreAccount { user_id }) = flow {
+        let mut transaction = pool.begin().await?;
+        let result =
+            crate::database::models::User::restore(user_id, &mut transaction, &redis).await?;
+        transaction.commit().await?;
+
+        Flow::remove(&restore.flow, &redis).await?;
+
+        if result.is_some() {
+            Ok(HttpResponse::NoContent().finish())
+        } else {
+            Err(ApiError::NotFound)
+        }
+    } else {
+        Err(ApiError::InvalidInput(
+            "Flow does not exist. The account may have already been restored or removed."
+                .to_string(),
+        ))
+    }
+}
+
 #[post("email/subscribe")]
 pub async fn subscribe_newsletter(
     req: HttpRequest,
@@ -2339,16 +2491,26 @@ pub async fn subscribe_newsletter(
     }
 }
 
-fn send_email_verify(
+async fn send_email_verify<'a, E>(
     email: String,
     flow: String,
-    opener: &str,
-) -> Result<(), crate::auth::email::MailError> {
-    send_email(
-        email,
-        "Verify your email",
-        opener,
-        "Please visit the following link below to verify your email. If the button does not work, you can copy the link and paste it into your browser. This link expires in 24 hours.",
-        Some(("Verify email", &format!("{}/{}?flow={}", dotenvy::var("SITE_URL")?,  dotenvy::var("SITE_VERIFY_EMAIL_PATH")?, flow))),
-    )
+    template: EmailTemplate,
+    mut variables: HashMap<String, String>,
+    locale: &str,
+    exec: E,
+) -> Result<(), crate::auth::email::MailError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres> + Copy,
+{
+    variables.insert(
+        "button_link".to_string(),
+        format!(
+            "{}/{}?flow={}",
+            dotenvy::var("SITE_URL")?,
+            dotenvy::var("SITE_VERIFY_EMAIL_PATH")?,
+            flow
+        ),
+    );
+
+    send_email(email, template, locale, variables, exec).await
 }
@@ -15,7 +15,6 @@ use rand_chacha::ChaCha20Rng;
 
 use crate::models::pats::{PersonalAccessToken, Scopes};
 use crate::queue::session::AuthQueue;
-use crate::util::validate::validation_errors_to_string;
 use serde::Deserialize;
 use sqlx::postgres::PgPool;
 use validator::Validate;
@@ -77,9 +76,7 @@ pub async fn create_pat(
     redis: Data<RedisPool>,
     session_queue: Data<AuthQueue>,
 ) -> Result<HttpResponse, ApiError> {
-    info.0
-        .validate()
-        .map_err(|err| ApiError::InvalidInput(validation_errors_to_string(err, None)))?;
+    info.0.validate()?;
 
     if info.scopes.is_restricted() {
         return Err(ApiError::InvalidInput(
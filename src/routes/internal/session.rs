@@ -1,27 +1,39 @@
 use crate::auth::{get_user_from_headers, AuthenticationError};
+use crate::database::models::impersonation_item::ImpersonationSession;
 use crate::database::models::session_item::Session as DBSession;
 use crate::database::models::session_item::SessionBuilder;
-use crate::database::models::UserId;
+use crate::database::models::{User, UserId};
 use crate::database::redis::RedisPool;
 use crate::models::pats::Scopes;
-use crate::models::sessions::Session;
+use crate::models::sessions::{Impersonation, Session};
+use crate::models::users::Role;
 use crate::queue::session::AuthQueue;
 use crate::routes::ApiError;
-use crate::util::env::parse_var;
+use crate::util::env::{parse_strings_from_var, parse_var};
 use actix_web::http::header::AUTHORIZATION;
 use actix_web::web::{scope, Data, ServiceConfig};
 use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
-use chrono::Utc;
+use chrono::{Duration, Utc};
+use log::info;
 use rand::distributions::Alphanumeric;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha20Rng;
+use serde::Deserialize;
 use sqlx::PgPool;
+use std::collections::HashMap;
+use validator::Validate;
 use woothee::parser::Parser;
 
+/// How long impersonation sessions last
+const IMPERSONATION_MINUTES: i64 = 60;
+
 pub fn config(cfg: &mut ServiceConfig) {
     cfg.service(
         scope("session")
             .service(list)
+            .service(impersonations_list)
+            .service(impersonate)
+            .service(impersonation_revoke)
             .service(delete)
             .service(refresh),
     );
@@ -250,3 +262,171 @@ pub async fn refresh(
         ))
     }
 }
+
+/// Whether a user may open impersonation sessions, which takes being an admin listed in
+/// `IMPERSONATION_ADMINS`
+fn can_impersonate(user: &crate::models::users::User) -> bool {
+    user.role.is_admin()
+        && parse_strings_from_var("IMPERSONATION_ADMINS")
+            .unwrap_or_default()
+            .contains(&user.id.to_string())
+}
+
+#[derive(Deserialize, Validate)]
+pub struct Impersonate {
+    /// The ID or username of the user
+    pub user: String,
+    /// Why the user is impersonated, such as the issue being reproduced, which the user sees
+    #[validate(length(min = 1, max = 2048))]
+    pub reason: String,
+}
+
+/// Opens a read-only session as a user for support staff to reproduce an issue the user
+/// reported. Every request made with it is recorded, and the user can see them and revoke it
+#[post("impersonate")]
+pub async fn impersonate(
+    req: HttpRequest,
+    body: web::Json<Impersonate>,
+    pool: Data<PgPool>,
+    redis: Data<RedisPool>,
+    session_queue: Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    // Only sessions have `SESSION_ACCESS`, so leaked access tokens cannot open these
+    let current_user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::SESSION_ACCESS]),
+    )
+    .await?
+    .1;
+
+    if !can_impersonate(&current_user) {
+        return Err(ApiError::CustomAuthentication(
+            "You don't have permission to impersonate users.".to_string(),
+        ));
+    }
+
+    body.validate()?;
+
+    let user = User::get(&body.user, &**pool, &redis)
+        .await?
+        .ok_or_else(|| ApiError::InvalidInput("The specified user does not exist!".to_string()))?;
+    if user.id == UserId::from(current_user.id) || Role::from_string(&user.role).is_admin() {
+        return Err(ApiError::InvalidInput(
+            "Admins cannot be impersonated.".to_string(),
+        ));
+    }
+
+    let token = ChaCha20Rng::from_entropy()
+        .sample_iter(&Alphanumeric)
+        .take(60)
+        .map(char::from)
+        .collect::<String>();
+    let token = format!("mri_{token}");
+
+    let mut transaction = pool.begin().await?;
+    let id = ImpersonationSession::insert(
+        &ImpersonationSession::hash_token(&token),
+        current_user.id.into(),
+        user.id,
+        &body.reason,
+        Utc::now() + Duration::minutes(IMPERSONATION_MINUTES),
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    info!(
+        "{} opened impersonation session {} as {}: {}",
+        current_user.username, id, user.username, body.reason
+    );
+
+    let session =
+        ImpersonationSession::get_active(&ImpersonationSession::hash_token(&token), &**pool)
+            .await?
+            .ok_or_else(|| {
+                ApiError::InvalidInput("The session could not be opened!".to_string())
+            })?;
+
+    Ok(HttpResponse::Ok().json(Impersonation::from(session, Some(token), Vec::new())))
+}
+
+/// Ends an impersonation session before it expires. Admins who can impersonate users can end
+/// any of them, and users the ones opened as them
+#[delete("impersonate/{id}")]
+pub async fn impersonation_revoke(
+    info: web::Path<(i64,)>,
+    req: HttpRequest,
+    pool: Data<PgPool>,
+    redis: Data<RedisPool>,
+    session_queue: Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let current_user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::SESSION_DELETE]),
+    )
+    .await?
+    .1;
+    let id = info.into_inner().0;
+
+    if !can_impersonate(&current_user) {
+        let sessions = ImpersonationSession::get_many_user(current_user.id.into(), &**pool).await?;
+        if !sessions.iter().any(|x| x.id == id) {
+            return Err(ApiError::NotFound);
+        }
+    }
+
+    ImpersonationSession::revoke(id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Lists the impersonation sessions opened as the user, with every request made with them
+#[get("impersonations")]
+pub async fn impersonations_list(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+    redis: Data<RedisPool>,
+    session_queue: Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let current_user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::SESSION_READ]),
+    )
+    .await?
+    .1;
+
+    let sessions = ImpersonationSession::get_many_user(current_user.id.into(), &**pool).await?;
+    let mut requests = HashMap::<i64, Vec<_>>::new();
+    for request in ImpersonationSession::get_requests(
+        &sessions.iter().map(|x| x.id).collect::<Vec<_>>(),
+        &**pool,
+    )
+    .await?
+    {
+        requests
+            .entry(request.session_id)
+            .or_default()
+            .push(request);
+    }
+
+    let impersonations = sessions
+        .into_iter()
+        .map(|session| {
+            let session_requests = requests.remove(&session.id).unwrap_or_default();
+            Impersonation::from(session, None, session_requests)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(impersonations))
+}
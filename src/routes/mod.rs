@@ -2,11 +2,13 @@ use crate::file_hosting::FileHostingError;
 use crate::routes::analytics::{page_view_ingest, playtime_ingest};
 use crate::util::cors::default_cors;
 use crate::util::env::parse_strings_from_var;
+use crate::util::validate::{validation_errors_to_fields, validation_errors_to_string};
 use actix_cors::Cors;
 use actix_files::Files;
 use actix_web::http::StatusCode;
 use actix_web::{web, HttpResponse};
 use futures::FutureExt;
+use serde::{Deserialize, Serialize};
 
 pub mod internal;
 pub mod v2;
@@ -15,11 +17,15 @@ pub mod v3;
 pub mod v2_reroute;
 
 mod analytics;
+mod health;
 mod index;
 mod maven;
 mod not_found;
+mod shortlinks;
+mod sitemaps;
 mod updates;
 
+pub use self::analytics::convert_to_ip_v6;
 pub use self::not_found::not_found;
 
 pub fn root_config(cfg: &mut web::ServiceConfig) {
@@ -73,6 +79,10 @@ pub fn root_config(cfg: &mut web::ServiceConfig) {
         web::scope("")
             .wrap(default_cors())
             .service(index::index_get)
+            .service(health::healthz)
+            .service(health::readyz)
+            .configure(sitemaps::config)
+            .configure(shortlinks::config)
             .service(Files::new("/", "assets/")),
     );
 }
@@ -101,6 +111,8 @@ pub enum ApiError {
     InvalidInput(String),
     #[error("Error while validating input: {0}")]
     Validation(String),
+    #[error("Error while validating input: {}", validation_errors_to_string(.0.clone(), None))]
+    InvalidFields(#[from] validator::ValidationErrors),
     #[error("Search Error: {0}")]
     Search(#[from] meilisearch_sdk::errors::Error),
     #[error("Indexing Error: {0}")]
@@ -125,6 +137,178 @@ pub enum ApiError {
     Reroute(#[from] reqwest::Error),
     #[error("Resource not found")]
     NotFound,
+    #[error("The resource was changed since it was fetched. Fetch it again and retry")]
+    PreconditionFailed,
+    #[error("{0}")]
+    Unavailable(#[from] crate::util::circuit_breaker::CircuitOpen),
+    #[error("Too much activity from this account or network. Try again later")]
+    Throttled,
+    #[error("{0}")]
+    ContentMismatch(#[from] crate::util::magic::ContentMismatch),
+}
+
+/// The registry of machine readable codes returned in the `error` field of API errors.
+///
+/// Clients should branch on these rather than on the description, which is meant for humans and
+/// may change at any time. Codes are part of the API contract: once released, they are never
+/// renamed or reused for a different kind of error. OAuth errors are the exception and use the
+/// codes defined by RFC 6749 instead.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, utoipa::ToSchema)]
+pub enum ErrorCode {
+    #[serde(rename = "environment_error")]
+    EnvironmentError,
+    #[serde(rename = "database_error")]
+    DatabaseError,
+    #[serde(rename = "clickhouse_error")]
+    ClickhouseError,
+    #[serde(rename = "unauthorized")]
+    Unauthorized,
+    #[serde(rename = "invalid_credentials")]
+    InvalidCredentials,
+    #[serde(rename = "invalid_auth_method")]
+    InvalidAuthMethod,
+    #[serde(rename = "invalid_client_id")]
+    InvalidClientId,
+    #[serde(rename = "duplicate_user")]
+    DuplicateUser,
+    #[serde(rename = "network_error")]
+    NetworkError,
+    #[serde(rename = "url_error")]
+    UrlError,
+    #[serde(rename = "socket")]
+    SocketError,
+    #[serde(rename = "xml_error")]
+    XmlError,
+    #[serde(rename = "json_error")]
+    JsonError,
+    #[serde(rename = "search_error")]
+    SearchError,
+    #[serde(rename = "meilisearch_error")]
+    MeilisearchError,
+    #[serde(rename = "indexing_error")]
+    IndexingError,
+    #[serde(rename = "file_hosting_error")]
+    FileHostingError,
+    #[serde(rename = "file_hosting")]
+    FileHosting,
+    #[serde(rename = "invalid_input")]
+    InvalidInput,
+    #[serde(rename = "invalid_image")]
+    InvalidImage,
+    #[serde(rename = "decoding_error")]
+    DecodingError,
+    #[serde(rename = "payments_error")]
+    PaymentsError,
+    #[serde(rename = "discord_error")]
+    DiscordError,
+    #[serde(rename = "turnstile_error")]
+    TurnstileError,
+    #[serde(rename = "password_hashing_error")]
+    PasswordHashingError,
+    #[serde(rename = "strength_check_error")]
+    StrengthCheckError,
+    #[serde(rename = "mail_error")]
+    MailError,
+    #[serde(rename = "reroute_error")]
+    RerouteError,
+    #[serde(rename = "not_found")]
+    NotFound,
+    #[serde(rename = "precondition_failed")]
+    PreconditionFailed,
+    #[serde(rename = "ratelimit_error")]
+    RatelimitError,
+    #[serde(rename = "read_only")]
+    ReadOnly,
+    #[serde(rename = "service_unavailable")]
+    ServiceUnavailable,
+    #[serde(rename = "legal_acceptance_required")]
+    LegalAcceptanceRequired,
+    #[serde(rename = "throttled")]
+    Throttled,
+    #[serde(rename = "content_mismatch")]
+    ContentMismatch,
+    #[serde(rename = "payload_too_large")]
+    PayloadTooLarge,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::EnvironmentError => "environment_error",
+            ErrorCode::DatabaseError => "database_error",
+            ErrorCode::ClickhouseError => "clickhouse_error",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::InvalidCredentials => "invalid_credentials",
+            ErrorCode::InvalidAuthMethod => "invalid_auth_method",
+            ErrorCode::InvalidClientId => "invalid_client_id",
+            ErrorCode::DuplicateUser => "duplicate_user",
+            ErrorCode::NetworkError => "network_error",
+            ErrorCode::UrlError => "url_error",
+            ErrorCode::SocketError => "socket",
+            ErrorCode::XmlError => "xml_error",
+            ErrorCode::JsonError => "json_error",
+            ErrorCode::SearchError => "search_error",
+            ErrorCode::MeilisearchError => "meilisearch_error",
+            ErrorCode::IndexingError => "indexing_error",
+            ErrorCode::FileHostingError => "file_hosting_error",
+            ErrorCode::FileHosting => "file_hosting",
+            ErrorCode::InvalidInput => "invalid_input",
+            ErrorCode::InvalidImage => "invalid_image",
+            ErrorCode::DecodingError => "decoding_error",
+            ErrorCode::PaymentsError => "payments_error",
+            ErrorCode::DiscordError => "discord_error",
+            ErrorCode::TurnstileError => "turnstile_error",
+            ErrorCode::PasswordHashingError => "password_hashing_error",
+            ErrorCode::StrengthCheckError => "strength_check_error",
+            ErrorCode::MailError => "mail_error",
+            ErrorCode::RerouteError => "reroute_error",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::PreconditionFailed => "precondition_failed",
+            ErrorCode::RatelimitError => "ratelimit_error",
+            ErrorCode::ReadOnly => "read_only",
+            ErrorCode::ServiceUnavailable => "service_unavailable",
+            ErrorCode::LegalAcceptanceRequired => "legal_acceptance_required",
+            ErrorCode::Throttled => "throttled",
+            ErrorCode::ContentMismatch => "content_mismatch",
+            ErrorCode::PayloadTooLarge => "payload_too_large",
+        }
+    }
+}
+
+impl ApiError {
+    /// The stable code this error is reported with
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ApiError::Env(..) => ErrorCode::EnvironmentError,
+            ApiError::SqlxDatabase(..) => ErrorCode::DatabaseError,
+            ApiError::Database(..) => ErrorCode::DatabaseError,
+            ApiError::Authentication(..) => ErrorCode::Unauthorized,
+            ApiError::CustomAuthentication(..) => ErrorCode::Unauthorized,
+            ApiError::Xml(..) => ErrorCode::XmlError,
+            ApiError::Json(..) => ErrorCode::JsonError,
+            ApiError::Search(..) => ErrorCode::SearchError,
+            ApiError::Indexing(..) => ErrorCode::IndexingError,
+            ApiError::FileHosting(..) => ErrorCode::FileHostingError,
+            ApiError::InvalidInput(..) => ErrorCode::InvalidInput,
+            ApiError::Validation(..) => ErrorCode::InvalidInput,
+            ApiError::InvalidFields(..) => ErrorCode::InvalidInput,
+            ApiError::Payments(..) => ErrorCode::PaymentsError,
+            ApiError::Discord(..) => ErrorCode::DiscordError,
+            ApiError::Turnstile => ErrorCode::TurnstileError,
+            ApiError::Decoding(..) => ErrorCode::DecodingError,
+            ApiError::ImageParse(..) => ErrorCode::InvalidImage,
+            ApiError::PasswordHashing(..) => ErrorCode::PasswordHashingError,
+            ApiError::PasswordStrengthCheck(..) => ErrorCode::StrengthCheckError,
+            ApiError::Mail(..) => ErrorCode::MailError,
+            ApiError::Clickhouse(..) => ErrorCode::ClickhouseError,
+            ApiError::Reroute(..) => ErrorCode::RerouteError,
+            ApiError::NotFound => ErrorCode::NotFound,
+            ApiError::PreconditionFailed => ErrorCode::PreconditionFailed,
+            ApiError::Unavailable(..) => ErrorCode::ServiceUnavailable,
+            ApiError::Throttled => ErrorCode::Throttled,
+            ApiError::ContentMismatch(..) => ErrorCode::ContentMismatch,
+        }
+    }
 }
 
 impl actix_web::ResponseError for ApiError {
@@ -143,6 +327,7 @@ impl actix_web::ResponseError for ApiError {
             ApiError::FileHosting(..) => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::InvalidInput(..) => StatusCode::BAD_REQUEST,
             ApiError::Validation(..) => StatusCode::BAD_REQUEST,
+            ApiError::InvalidFields(..) => StatusCode::BAD_REQUEST,
             ApiError::Payments(..) => StatusCode::FAILED_DEPENDENCY,
             ApiError::Discord(..) => StatusCode::FAILED_DEPENDENCY,
             ApiError::Turnstile => StatusCode::BAD_REQUEST,
@@ -153,37 +338,21 @@ impl actix_web::ResponseError for ApiError {
             ApiError::Mail(..) => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::Reroute(..) => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ApiError::Unavailable(..) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Throttled => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::ContentMismatch(..) => StatusCode::BAD_REQUEST,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
         HttpResponse::build(self.status_code()).json(crate::models::error::ApiError {
-            error: match self {
-                ApiError::Env(..) => "environment_error",
-                ApiError::SqlxDatabase(..) => "database_error",
-                ApiError::Database(..) => "database_error",
-                ApiError::Authentication(..) => "unauthorized",
-                ApiError::CustomAuthentication(..) => "unauthorized",
-                ApiError::Xml(..) => "xml_error",
-                ApiError::Json(..) => "json_error",
-                ApiError::Search(..) => "search_error",
-                ApiError::Indexing(..) => "indexing_error",
-                ApiError::FileHosting(..) => "file_hosting_error",
-                ApiError::InvalidInput(..) => "invalid_input",
-                ApiError::Validation(..) => "invalid_input",
-                ApiError::Payments(..) => "payments_error",
-                ApiError::Discord(..) => "discord_error",
-                ApiError::Turnstile => "turnstile_error",
-                ApiError::Decoding(..) => "decoding_error",
-                ApiError::ImageParse(..) => "invalid_image",
-                ApiError::PasswordHashing(..) => "password_hashing_error",
-                ApiError::PasswordStrengthCheck(..) => "strength_check_error",
-                ApiError::Mail(..) => "mail_error",
-                ApiError::Clickhouse(..) => "clickhouse_error",
-                ApiError::Reroute(..) => "reroute_error",
-                ApiError::NotFound => "not_found",
-            },
+            error: self.code().as_str(),
             description: &self.to_string(),
+            fields: match self {
+                ApiError::InvalidFields(errors) => validation_errors_to_fields(errors),
+                _ => Vec::new(),
+            },
         })
     }
 }
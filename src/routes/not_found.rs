@@ -1,10 +1,12 @@
 use crate::models::error::ApiError;
+use crate::routes::ErrorCode;
 use actix_web::{HttpResponse, Responder};
 
 pub async fn not_found() -> impl Responder {
     let data = ApiError {
-        error: "not_found",
+        error: ErrorCode::NotFound.as_str(),
         description: "the requested route does not exist",
+        fields: Vec::new(),
     };
 
     HttpResponse::NotFound().json(data)
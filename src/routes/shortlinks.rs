@@ -0,0 +1,36 @@
+use super::ApiError;
+use crate::database::models::shortlink_item::Shortlink;
+use crate::models::ids::{ProjectId, VersionId};
+use actix_web::{get, web, HttpResponse};
+use sqlx::PgPool;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(shortlink_redirect);
+}
+
+/// Follows a short link, counting the click. The link's code is passed on as the UTM campaign,
+/// so the page view the site records for the visit is attributed to the link in analytics
+#[get("s/{code}")]
+pub async fn shortlink_redirect(
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let code = info.into_inner().0;
+    let (project_id, version_id) = Shortlink::click(&code, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let site_url = dotenvy::var("SITE_URL")?;
+    let mut location = format!("{}/project/{}", site_url, ProjectId::from(project_id));
+    if let Some(version_id) = version_id {
+        location.push_str(&format!("/version/{}", VersionId::from(version_id)));
+    }
+    location.push_str(&format!(
+        "?utm_source=shortlink&utm_medium=shortlink&utm_campaign={}",
+        code
+    ));
+
+    Ok(HttpResponse::Found()
+        .append_header(("location", location))
+        .body(""))
+}
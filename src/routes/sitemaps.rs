@@ -0,0 +1,42 @@
+use super::ApiError;
+use crate::queue::sitemaps::sitemap_path;
+use actix_web::{get, web, HttpResponse};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref RE_SITEMAP_PAGE: Regex = Regex::new(r"^[a-z]+-[0-9]+\.xml$").unwrap();
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(sitemap_index);
+    cfg.service(sitemap_page);
+}
+
+/// Redirects to a sitemap in the file host, so the URLs crawlers are given stay the same
+/// wherever sitemaps are stored
+fn redirect(file_name: &str) -> Result<HttpResponse, ApiError> {
+    let cdn_url = dotenvy::var("CDN_URL")?;
+
+    Ok(HttpResponse::TemporaryRedirect()
+        .append_header((
+            "location",
+            format!("{}/{}", cdn_url, sitemap_path(file_name)),
+        ))
+        .body(""))
+}
+
+#[get("sitemap.xml")]
+pub async fn sitemap_index() -> Result<HttpResponse, ApiError> {
+    redirect("index.xml")
+}
+
+#[get("sitemaps/{file}")]
+pub async fn sitemap_page(info: web::Path<(String,)>) -> Result<HttpResponse, ApiError> {
+    let file = info.into_inner().0;
+    if !RE_SITEMAP_PAGE.is_match(&file) {
+        return Err(ApiError::NotFound);
+    }
+
+    redirect(&file)
+}
@@ -14,11 +14,15 @@ mod versions;
 
 pub use super::ApiError;
 use crate::util::cors::default_cors;
+use crate::util::etag::conditional_get;
+use crate::util::legal::LegalAcceptanceGate;
 
 pub fn config(cfg: &mut actix_web::web::ServiceConfig) {
     cfg.service(
         actix_web::web::scope("v2")
+            .wrap(LegalAcceptanceGate)
             .wrap(default_cors())
+            .wrap_fn(conditional_get)
             .configure(super::internal::admin::config)
             // Todo: separate these- they need to also follow v2-v3 conversion
             .configure(super::internal::session::config)
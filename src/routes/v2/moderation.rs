@@ -1,5 +1,4 @@
 use super::ApiError;
-use crate::models::projects::Project;
 use crate::models::v2::projects::LegacyProject;
 use crate::queue::session::AuthQueue;
 use crate::routes::v3;
@@ -34,16 +33,19 @@ pub async fn get_projects(
         req,
         pool.clone(),
         redis.clone(),
-        web::Query(v3::moderation::ResultCount { count: count.count }),
+        web::Query(v3::moderation::ResultCount {
+            count: count.count,
+            cursor: None,
+        }),
         session_queue,
     )
     .await
     .or_else(v2_reroute::flatten_404_error)?;
 
     // Convert to V2 projects
-    match v2_reroute::extract_ok_json::<Vec<Project>>(response).await {
-        Ok(project) => {
-            let legacy_projects = LegacyProject::from_many(project, &**pool, &redis).await?;
+    match v2_reroute::extract_ok_json::<v3::moderation::ModerationQueuePage>(response).await {
+        Ok(page) => {
+            let legacy_projects = LegacyProject::from_many(page.projects, &**pool, &redis).await?;
             Ok(HttpResponse::Ok().json(legacy_projects))
         }
         Err(response) => Ok(response),
@@ -176,6 +176,7 @@ pub async fn project_create(
                     v3::version_creation::InitialVersionData {
                         project_id: v.project_id,
                         file_parts: v.file_parts,
+                        file_urls: HashMap::new(),
                         version_number: v.version_number,
                         version_title: v.version_title,
                         version_body: v.version_body,
@@ -11,6 +11,7 @@ use crate::queue::session::AuthQueue;
 use crate::routes::v3::projects::ProjectIds;
 use crate::routes::{v2_reroute, v3, ApiError};
 use crate::search::{search_for_project, SearchConfig, SearchError};
+use actix_web::http::header::ETAG;
 use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
@@ -52,6 +53,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
 pub async fn project_search(
     web::Query(info): web::Query<SearchRequest>,
     config: web::Data<SearchConfig>,
+    redis: web::Data<RedisPool>,
 ) -> Result<HttpResponse, SearchError> {
     // TODO: make this nicer
     // Search now uses loader_fields instead of explicit 'client_side' and 'server_side' fields
@@ -94,12 +96,17 @@ pub async fn project_search(
                                 .into_iter()
                                 .map(|facet| {
                                     if let Some((key, operator, val)) = parse_facet(&facet) {
-                                        format!("{}{}{}", match key.as_str() {
-                                            "versions" => "game_versions",
-                                            "project_type" => "project_types",
-                                            "title" => "name",
-                                            x => x,
-                                        }, operator, val)
+                                        format!(
+                                            "{}{}{}",
+                                            match key.as_str() {
+                                                "versions" => "game_versions",
+                                                "project_type" => "project_types",
+                                                "title" => "name",
+                                                x => x,
+                                            },
+                                            operator,
+                                            val
+                                        )
                                     } else {
                                         facet.to_string()
                                     }
@@ -119,7 +126,7 @@ pub async fn project_search(
         ..info
     };
 
-    let results = search_for_project(&info, &config).await?;
+    let results = search_for_project(&info, &config, &redis).await?;
 
     let results = LegacySearchResults::from(results);
 
@@ -232,6 +239,8 @@ pub async fn project_get(
     let response = v3::projects::project_get(req, info, pool.clone(), redis.clone(), session_queue)
         .await
         .or_else(v2_reroute::flatten_404_error)?;
+    // Kept so the revision can be sent back in `If-Match` when editing
+    let etag = response.headers().get(ETAG).cloned();
 
     // Convert response to V2 format
     match v2_reroute::extract_ok_json::<Project>(response).await {
@@ -241,7 +250,11 @@ pub async fn project_get(
                 None => None,
             };
             let project = LegacyProject::from(project, version_item);
-            Ok(HttpResponse::Ok().json(project))
+            let mut response = HttpResponse::Ok().json(project);
+            if let Some(etag) = etag {
+                response.headers_mut().insert(ETAG, etag);
+            }
+            Ok(response)
         }
         Err(response) => Ok(response),
     }
@@ -543,6 +556,7 @@ pub async fn project_edit(
                     fields,
                     ..Default::default()
                 },
+                None,
                 session_queue.clone(),
             )
             .await?;
@@ -82,6 +82,7 @@ pub async fn reports(
         web::Query(v3::reports::ReportsRequestOptions {
             count: count.count,
             all: count.all,
+            cursor: None,
         }),
         session_queue,
     )
@@ -180,6 +181,7 @@ pub async fn report_edit(
         web::Json(v3::reports::EditReport {
             body: edit_report.body,
             closed: edit_report.closed,
+            outcome: None,
         }),
     )
     .await
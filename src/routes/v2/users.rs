@@ -8,6 +8,7 @@ use crate::models::v2::projects::LegacyProject;
 use crate::models::v2::user::LegacyUser;
 use crate::queue::session::AuthQueue;
 use crate::routes::{v2_reroute, v3, ApiError};
+use crate::util::cursor::next_page_cursor;
 use actix_web::{delete, get, patch, web, HttpRequest, HttpResponse};
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -253,18 +254,40 @@ pub async fn user_notifications(
     redis: web::Data<RedisPool>,
     session_queue: web::Data<AuthQueue>,
 ) -> Result<HttpResponse, ApiError> {
-    let response = v3::users::user_notifications(req, info, pool, redis, session_queue)
+    // V2 returns every notification at once, so walk through all of the V3 pages
+    let id = info.into_inner().0;
+    let mut legacy_notifications: Vec<LegacyNotification> = Vec::new();
+    let mut cursor = None;
+    loop {
+        let response = v3::users::user_notifications(
+            req.clone(),
+            web::Path::from((id.clone(),)),
+            web::Query(v3::users::NotificationListQuery {
+                cursor,
+                limit: Some(100),
+                ..Default::default()
+            }),
+            pool.clone(),
+            redis.clone(),
+            session_queue.clone(),
+        )
         .await
         .or_else(v2_reroute::flatten_404_error)?;
-    // Convert response to V2 format
-    match v2_reroute::extract_ok_json::<Vec<Notification>>(response).await {
-        Ok(notifications) => {
-            let legacy_notifications: Vec<LegacyNotification> = notifications
-                .into_iter()
-                .map(LegacyNotification::from)
-                .collect();
-            Ok(HttpResponse::Ok().json(legacy_notifications))
+        let next_cursor = next_page_cursor(&response);
+        // Convert response to V2 format
+        match v2_reroute::extract_ok_json::<Vec<Notification>>(response).await {
+            Ok(notifications) => {
+                legacy_notifications
+                    .extend(notifications.into_iter().map(LegacyNotification::from));
+
+                cursor = next_cursor;
+                if cursor.is_none() {
+                    break;
+                }
+            }
+            Err(response) => return Ok(response),
         }
-        Err(response) => Ok(response),
     }
+
+    Ok(HttpResponse::Ok().json(legacy_notifications))
 }
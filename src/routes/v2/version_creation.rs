@@ -191,6 +191,7 @@ pub async fn version_create(
                 Ok(v3::version_creation::InitialVersionData {
                     project_id: legacy_create.project_id,
                     file_parts: legacy_create.file_parts,
+                    file_urls: HashMap::new(),
                     version_number: legacy_create.version_number,
                     version_title: legacy_create.version_title,
                     version_body: legacy_create.version_body,
@@ -9,6 +9,7 @@ use crate::models::v2::projects::LegacyVersion;
 use crate::queue::session::AuthQueue;
 use crate::routes::{v2_reroute, v3};
 use crate::search::SearchConfig;
+use actix_web::http::header::ETAG;
 use actix_web::{delete, get, patch, web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
@@ -92,12 +93,20 @@ pub async fn version_list(
         version_type: filters.version_type,
         limit: filters.limit,
         offset: filters.offset,
+        cursor: None,
     };
 
-    let response =
-        v3::versions::version_list(req, info, web::Query(filters), pool, redis, session_queue)
-            .await
-            .or_else(v2_reroute::flatten_404_error)?;
+    let response = v3::versions::version_list(
+        req,
+        info,
+        web::Query(filters),
+        web::Query(HashMap::new()),
+        pool,
+        redis,
+        session_queue,
+    )
+    .await
+    .or_else(v2_reroute::flatten_404_error)?;
 
     // Convert response to V2 format
     match v2_reroute::extract_ok_json::<Vec<Version>>(response).await {
@@ -178,11 +187,17 @@ pub async fn version_get(
     let response = v3::versions::version_get_helper(req, id, pool, redis, session_queue)
         .await
         .or_else(v2_reroute::flatten_404_error)?;
+    // Kept so the revision can be sent back in `If-Match` when editing
+    let etag = response.headers().get(ETAG).cloned();
     // Convert response to V2 format
     match v2_reroute::extract_ok_json::<Version>(response).await {
         Ok(version) => {
             let v2_version = LegacyVersion::from(version);
-            Ok(HttpResponse::Ok().json(v2_version))
+            let mut response = HttpResponse::Ok().json(v2_version);
+            if let Some(etag) = etag {
+                response.headers_mut().insert(ETAG, etag);
+            }
+            Ok(response)
         }
         Err(response) => Ok(response),
     }
@@ -1,6 +1,11 @@
 use super::ApiError;
 use crate::database;
+use crate::database::models::job_item::Job;
 use crate::database::redis::RedisPool;
+use crate::database::ReadOnlyPool;
+use crate::models::analytics::AnalyticsDimension;
+use crate::models::jobs::{JobPayload, UserJob};
+use crate::models::payouts::PayoutStatus;
 use crate::models::teams::ProjectPermissions;
 use crate::{
     auth::get_user_from_headers,
@@ -13,6 +18,7 @@ use crate::{
 };
 use actix_web::{web, HttpRequest, HttpResponse};
 use chrono::{DateTime, Duration, Utc};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::types::PgInterval;
 use sqlx::PgPool;
@@ -23,14 +29,28 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("analytics")
             .route("playtime", web::get().to(playtimes_get))
+            .route(
+                "playtime",
+                web::post().to(crate::routes::analytics::playtime_sessions_ingest),
+            )
+            .route("playtime/sessions", web::get().to(playtime_sessions_get))
             .route("views", web::get().to(views_get))
             .route("downloads", web::get().to(downloads_get))
             .route("revenue", web::get().to(revenue_get))
+            .route("revenue/summary", web::get().to(revenue_summary_get))
             .route(
                 "countries/downloads",
                 web::get().to(countries_downloads_get),
             )
-            .route("countries/views", web::get().to(countries_views_get)),
+            .route("countries/views", web::get().to(countries_views_get))
+            .route(
+                "breakdown/downloads",
+                web::get().to(downloads_breakdown_get),
+            )
+            .route("breakdown/views", web::get().to(views_breakdown_get))
+            .route("project/{id}/export", web::get().to(project_export_get))
+            .route("project/{id}/export", web::post().to(project_export_start))
+            .route("project/{id}/versions", web::get().to(project_versions_get)),
     );
 }
 
@@ -124,6 +144,76 @@ pub async fn playtimes_get(
     Ok(HttpResponse::Ok().json(hm))
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FetchedPlaytimeSessions {
+    pub active_instances: u64,
+    pub average_session_seconds: f64,
+}
+
+/// Get launcher session data for a set of projects
+/// Data is returned as a hashmap of project ids to a hashmap of days to the number of distinct
+/// instances that played the project that day and their average session length
+/// eg:
+/// {
+///     "4N1tEhnO": {
+///         "1692835200": {
+///             "active_instances": 12,
+///             "average_session_seconds": 1843.5
+///         }
+///    }
+///}
+/// Sessions are always bucketed by day, so resolution_minutes is ignored
+pub async fn playtime_sessions_get(
+    req: HttpRequest,
+    clickhouse: web::Data<clickhouse::Client>,
+    data: web::Query<GetData>,
+    session_queue: web::Data<AuthQueue>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::ANALYTICS]),
+    )
+    .await
+    .map(|x| x.1)?;
+
+    let project_ids = data
+        .project_ids
+        .as_ref()
+        .map(|ids| serde_json::from_str::<Vec<String>>(ids))
+        .transpose()?;
+
+    let start_date = data.start_date.unwrap_or(Utc::now() - Duration::weeks(2));
+    let end_date = data.end_date.unwrap_or(Utc::now());
+
+    let project_ids = filter_allowed_ids(project_ids, user, &pool, &redis, None).await?;
+
+    let sessions = crate::clickhouse::fetch_playtime_sessions(
+        project_ids.unwrap_or_default(),
+        start_date,
+        end_date,
+        clickhouse.into_inner(),
+    )
+    .await?;
+
+    let mut hm: HashMap<String, HashMap<u32, FetchedPlaytimeSessions>> = HashMap::new();
+    for session in sessions {
+        hm.entry(to_base62(session.id)).or_default().insert(
+            session.time,
+            FetchedPlaytimeSessions {
+                active_instances: session.active_instances,
+                average_session_seconds: session.average_seconds,
+            },
+        );
+    }
+
+    Ok(HttpResponse::Ok().json(hm))
+}
+
 /// Get view data for a set of projects or versions
 /// Data is returned as a hashmap of project/version ids to a hashmap of days to views
 /// eg:
@@ -270,6 +360,7 @@ pub async fn revenue_get(
     data: web::Query<GetData>,
     session_queue: web::Data<AuthQueue>,
     pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadOnlyPool>,
     redis: web::Data<RedisPool>,
 ) -> Result<HttpResponse, ApiError> {
     let user = get_user_from_headers(
@@ -332,7 +423,7 @@ pub async fn revenue_get(
             end_date,
             duration,
         )
-            .fetch_all(&**pool)
+            .fetch_all(&read_pool.0)
             .await?.into_iter().map(|x| PayoutValue {
             mod_id: x.mod_id,
             amount_sum: x.amount_sum,
@@ -351,7 +442,7 @@ pub async fn revenue_get(
             end_date,
             duration,
         )
-            .fetch_all(&**pool)
+            .fetch_all(&read_pool.0)
             .await?.into_iter().map(|x| PayoutValue {
             mod_id: x.mod_id,
             amount_sum: x.amount_sum,
@@ -382,6 +473,237 @@ pub async fn revenue_get(
     Ok(HttpResponse::Ok().json(hm))
 }
 
+/// The query to fetch the revenue summary of the current user, or of an organization's projects
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetRevenueSummaryData {
+    pub organization_id: Option<String>,
+
+    pub start_date: Option<DateTime<Utc>>, // defaults to 2 weeks ago
+    pub end_date: Option<DateTime<Utc>>,   // defaults to now
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RevenueSummary {
+    /// Revenue earned in the requested range
+    pub total: rust_decimal::Decimal,
+    /// Revenue earned per day, keyed by the unix timestamp of the start of the day
+    pub by_day: HashMap<i64, rust_decimal::Decimal>,
+    /// Revenue earned per project
+    pub by_project: HashMap<String, rust_decimal::Decimal>,
+    /// How the user's balance is made up. Organizations have no balance, so this is only
+    /// returned for users
+    pub ledger: Option<RevenueLedger>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RevenueLedger {
+    /// All revenue ever earned
+    pub earned: rust_decimal::Decimal,
+    /// The sum of all adjustments made to the balance, negative if more was clawed back
+    pub adjusted: rust_decimal::Decimal,
+    /// All withdrawals which have not been cancelled or failed, including fees
+    pub withdrawn: rust_decimal::Decimal,
+    pub balance: rust_decimal::Decimal,
+    /// `earned + adjusted - withdrawn - balance`. Anything but zero means the balance is out of sync
+    /// with the ledger
+    pub discrepancy: rust_decimal::Decimal,
+}
+
+/// Get the revenue earned by the current user, or by the projects of an organization the user
+/// is a member of, bucketed by day and by project.
+/// For users, the summary is reconciled with the payouts ledger to show how their balance is
+/// made up.
+pub async fn revenue_summary_get(
+    req: HttpRequest,
+    data: web::Query<GetRevenueSummaryData>,
+    session_queue: web::Data<AuthQueue>,
+    pool: web::Data<PgPool>,
+    read_pool: web::Data<ReadOnlyPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_READ]),
+    )
+    .await
+    .map(|x| x.1)?;
+
+    let start_date = data.start_date.unwrap_or(Utc::now() - Duration::weeks(2));
+    let end_date = data.end_date.unwrap_or(Utc::now());
+
+    struct RevenueValue {
+        mod_id: Option<i64>,
+        amount_sum: Option<rust_decimal::Decimal>,
+        day: Option<DateTime<Utc>>,
+    }
+
+    let (values, ledger) = if let Some(organization_id) = &data.organization_id {
+        let organization = database::models::Organization::get(organization_id, &**pool, &redis)
+            .await?
+            .ok_or_else(|| {
+                ApiError::InvalidInput("The specified organization does not exist!".to_string())
+            })?;
+
+        let team_member = database::models::TeamMember::get_from_user_id(
+            organization.team_id,
+            user.id.into(),
+            &**pool,
+        )
+        .await?;
+        if team_member.is_none() && !user.role.is_admin() {
+            return Err(ApiError::InvalidInput(
+                "The specified organization does not exist!".to_string(),
+            ));
+        }
+
+        // Members see the revenue of the organization's projects with their default project
+        // permissions, so need the same permission as for a project's revenue
+        let permissions =
+            ProjectPermissions::get_permissions_by_role(&user.role, &None, &team_member)
+                .unwrap_or_default();
+        if !permissions.contains(ProjectPermissions::VIEW_ANALYTICS) {
+            return Err(ApiError::CustomAuthentication(
+                "You do not have permission to view this organization's revenue!".to_string(),
+            ));
+        }
+
+        let project_ids = sqlx::query!(
+            "SELECT id FROM mods WHERE organization_id = $1",
+            organization.id as database::models::ids::OrganizationId
+        )
+        .fetch_all(&read_pool.0)
+        .await?
+        .into_iter()
+        .map(|x| ProjectId(x.id as u64).to_string())
+        .collect::<Vec<_>>();
+        let project_ids = filter_allowed_ids(Some(project_ids), user, &pool, &redis, Some(true))
+            .await?
+            .unwrap_or_default();
+
+        let values = sqlx::query!(
+            "
+            SELECT mod_id, SUM(amount) amount_sum, date_trunc('day', created) AS day
+            FROM payouts_values
+            WHERE mod_id = ANY($1) AND created BETWEEN $2 AND $3
+            GROUP BY mod_id, day
+            ",
+            &project_ids.iter().map(|x| x.0 as i64).collect::<Vec<_>>(),
+            start_date,
+            end_date,
+        )
+        .fetch_all(&read_pool.0)
+        .await?
+        .into_iter()
+        .map(|x| RevenueValue {
+            mod_id: x.mod_id,
+            amount_sum: x.amount_sum,
+            day: x.day,
+        })
+        .collect::<Vec<_>>();
+
+        (values, None)
+    } else {
+        let values = sqlx::query!(
+            "
+            SELECT mod_id, SUM(amount) amount_sum, date_trunc('day', created) AS day
+            FROM payouts_values
+            WHERE user_id = $1 AND created BETWEEN $2 AND $3
+            GROUP BY mod_id, day
+            ",
+            user.id.0 as i64,
+            start_date,
+            end_date,
+        )
+        .fetch_all(&read_pool.0)
+        .await?
+        .into_iter()
+        .map(|x| RevenueValue {
+            mod_id: x.mod_id,
+            amount_sum: x.amount_sum,
+            day: x.day,
+        })
+        .collect::<Vec<_>>();
+
+        let earned = sqlx::query!(
+            "SELECT SUM(amount) earned FROM payouts_values WHERE user_id = $1",
+            user.id.0 as i64,
+        )
+        .fetch_one(&read_pool.0)
+        .await?
+        .earned
+        .unwrap_or_default();
+
+        let adjusted = sqlx::query!(
+            "SELECT SUM(amount) adjusted FROM payouts_adjustments WHERE user_id = $1",
+            user.id.0 as i64,
+        )
+        .fetch_one(&read_pool.0)
+        .await?
+        .adjusted
+        .unwrap_or_default();
+
+        let withdrawn = sqlx::query!(
+            "
+            SELECT SUM(amount + COALESCE(fee, 0) + COALESCE(withholding, 0)) withdrawn
+            FROM payouts
+            WHERE user_id = $1 AND NOT status = ANY($2)
+            ",
+            user.id.0 as i64,
+            &[
+                PayoutStatus::Cancelled.as_str().to_string(),
+                PayoutStatus::Failed.as_str().to_string(),
+            ][..],
+        )
+        .fetch_one(&read_pool.0)
+        .await?
+        .withdrawn
+        .unwrap_or_default();
+
+        let balance = user_item::User::get_id(user.id.into(), &**pool, &redis)
+            .await?
+            .map(|x| x.balance_amount())
+            .unwrap_or_default();
+
+        (
+            values,
+            Some(RevenueLedger {
+                earned,
+                adjusted,
+                withdrawn,
+                balance,
+                discrepancy: earned + adjusted - withdrawn - balance,
+            }),
+        )
+    };
+
+    let mut summary = RevenueSummary {
+        total: rust_decimal::Decimal::ZERO,
+        by_day: HashMap::new(),
+        by_project: HashMap::new(),
+        ledger,
+    };
+    for value in values {
+        if let Some(amount) = value.amount_sum {
+            summary.total += amount;
+
+            if let Some(day) = value.day {
+                *summary.by_day.entry(day.timestamp()).or_default() += amount;
+            }
+            if let Some(mod_id) = value.mod_id {
+                *summary
+                    .by_project
+                    .entry(to_base62(mod_id as u64))
+                    .or_default() += amount;
+            }
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(summary))
+}
+
 /// Get country data for a set of projects or versions
 /// Data is returned as a hashmap of project/version ids to a hashmap of coutnry to downloads.
 /// Unknown countries are labeled "".
@@ -526,6 +848,395 @@ pub async fn countries_views_get(
     Ok(HttpResponse::Ok().json(hm))
 }
 
+/// The query to fetch a breakdown of analytics data by a dimension.
+/// Like with /countries, the dates are a range to aggregate over.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetBreakdownData {
+    pub project_ids: Option<String>,
+    pub dimension: AnalyticsDimension,
+
+    pub start_date: Option<DateTime<Utc>>, // defaults to 2 weeks ago
+    pub end_date: Option<DateTime<Utc>>,   // defaults to now
+}
+
+/// Get download data for a set of projects broken down by a dimension: country, continent,
+/// platform, os, or traffic source (referrer, source, medium, campaign)
+/// Data is returned as a hashmap of project ids to a hashmap of dimension values to downloads.
+/// Unknown values are labeled "" ("unknown" for platform and os, "direct" for source), and
+/// countries with few downloads are condensed into "XX" as with /countries
+/// eg: with dimension=platform
+/// {
+///     "4N1tEhnO": {
+///         "launcher": 312,
+///         "web": 58
+///    }
+///}
+pub async fn downloads_breakdown_get(
+    req: HttpRequest,
+    clickhouse: web::Data<clickhouse::Client>,
+    data: web::Query<GetBreakdownData>,
+    session_queue: web::Data<AuthQueue>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    breakdown_get(req, clickhouse, data, session_queue, pool, redis, true).await
+}
+
+/// Get view data for a set of projects broken down by a dimension
+/// Data is returned in the same format as /breakdown/downloads
+pub async fn views_breakdown_get(
+    req: HttpRequest,
+    clickhouse: web::Data<clickhouse::Client>,
+    data: web::Query<GetBreakdownData>,
+    session_queue: web::Data<AuthQueue>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    breakdown_get(req, clickhouse, data, session_queue, pool, redis, false).await
+}
+
+async fn breakdown_get(
+    req: HttpRequest,
+    clickhouse: web::Data<clickhouse::Client>,
+    data: web::Query<GetBreakdownData>,
+    session_queue: web::Data<AuthQueue>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    downloads: bool,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::ANALYTICS]),
+    )
+    .await
+    .map(|x| x.1)?;
+
+    let project_ids = data
+        .project_ids
+        .as_ref()
+        .map(|ids| serde_json::from_str::<Vec<String>>(ids))
+        .transpose()?;
+
+    let start_date = data.start_date.unwrap_or(Utc::now() - Duration::weeks(2));
+    let end_date = data.end_date.unwrap_or(Utc::now());
+
+    let project_ids = filter_allowed_ids(project_ids, user, &pool, &redis, None)
+        .await?
+        .unwrap_or_default();
+
+    let breakdown = if downloads {
+        crate::clickhouse::fetch_downloads_breakdown(
+            project_ids,
+            data.dimension,
+            start_date,
+            end_date,
+            clickhouse.into_inner(),
+        )
+        .await?
+    } else {
+        crate::clickhouse::fetch_views_breakdown(
+            project_ids,
+            data.dimension,
+            start_date,
+            end_date,
+            clickhouse.into_inner(),
+        )
+        .await?
+    };
+
+    let mut hm: HashMap<String, HashMap<String, u64>> = HashMap::new();
+    for value in breakdown {
+        hm.entry(to_base62(value.id))
+            .or_default()
+            .insert(value.value, value.total);
+    }
+
+    if data.dimension == AnalyticsDimension::Country {
+        hm = hm
+            .into_iter()
+            .map(|(key, value)| (key, condense_countries(value)))
+            .collect();
+    }
+
+    Ok(HttpResponse::Ok().json(hm))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExportQuery {
+    pub format: crate::clickhouse::ExportFormat,
+    /// How far back to export: a number of days such as `30d`, or `all`. Defaults to `30d`
+    pub range: Option<String>,
+}
+
+/// The longest range that can be exported by days, about ten years
+const MAX_EXPORT_DAYS: i64 = 3650;
+
+/// Parses an export range into the date to export from
+fn parse_export_range(range: Option<&str>) -> Result<DateTime<Utc>, ApiError> {
+    let days = match range.unwrap_or("30d") {
+        "all" => MAX_EXPORT_DAYS,
+        range => range
+            .strip_suffix('d')
+            .and_then(|x| x.parse::<i64>().ok())
+            .filter(|x| *x > 0 && *x <= MAX_EXPORT_DAYS)
+            .ok_or_else(|| {
+                ApiError::InvalidInput(
+                    "Invalid range! Expected a number of days (such as 30d) or all".to_string(),
+                )
+            })?,
+    };
+
+    Ok(Utc::now() - Duration::days(days))
+}
+
+/// Export the raw daily metrics of a project as CSV or Parquet.
+/// Each row holds the views, downloads and playtime (in seconds) of one version on one day.
+/// Views are not recorded per version, so they are in rows with a version_id of 0.
+pub async fn project_export_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    data: web::Query<ExportQuery>,
+    session_queue: web::Data<AuthQueue>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::ANALYTICS]),
+    )
+    .await
+    .map(|x| x.1)?;
+
+    let start_date = parse_export_range(data.range.as_deref())?;
+    let end_date = Utc::now();
+
+    let project_id = filter_allowed_ids(Some(vec![info.into_inner().0]), user, &pool, &redis, None)
+        .await?
+        .and_then(|x| x.into_iter().next())
+        .ok_or(ApiError::NotFound)?;
+
+    let stream =
+        crate::clickhouse::export_project_metrics(project_id, start_date, end_date, data.format)
+            .await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(data.format.content_type())
+        .insert_header((
+            actix_web::http::header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"{}-{}.{}\"",
+                project_id,
+                end_date.format("%Y-%m-%d"),
+                data.format.extension()
+            ),
+        ))
+        .streaming(stream))
+}
+
+/// Start exporting the raw daily metrics of a project in the background, for exports too
+/// large to download in one request. Returns the job, which is polled at `/v3/jobs/{id}` until
+/// it is completed and has the URL of the export.
+pub async fn project_export_start(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    data: web::Query<ExportQuery>,
+    session_queue: web::Data<AuthQueue>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::ANALYTICS]),
+    )
+    .await
+    .map(|x| x.1)?;
+    let user_id = user.id;
+
+    let start_date = parse_export_range(data.range.as_deref())?;
+    let end_date = Utc::now();
+
+    let project_id = filter_allowed_ids(Some(vec![info.into_inner().0]), user, &pool, &redis, None)
+        .await?
+        .and_then(|x| x.into_iter().next())
+        .ok_or(ApiError::NotFound)?;
+
+    let job_id = Job::enqueue_for_user(
+        JobPayload::ExportProjectAnalytics {
+            project_id,
+            format: data.format,
+            start_date,
+            end_date,
+        },
+        user_id.into(),
+        &**pool,
+    )
+    .await?;
+    let job = Job::get(job_id, &**pool).await?.ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Accepted()
+        .insert_header((
+            actix_web::http::header::LOCATION,
+            format!("/v3/jobs/{}", job_id),
+        ))
+        .json(UserJob::from(job)))
+}
+
+/// The query to fetch the version adoption of a project.
+/// start_date and end_date default to two weeks ago and now, and resolution_minutes to 1 day
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetVersionAdoptionData {
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub resolution_minutes: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct VersionShare {
+    pub downloads: u64,
+    /// The fraction of all of the project's downloads in the interval
+    pub share: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct VersionAdoption {
+    /// Intervals to the download share of each version
+    pub versions: HashMap<u32, HashMap<VersionId, VersionShare>>,
+    /// Intervals to the download share of each game version. Versions supporting several game
+    /// versions count towards all of them, so these shares may add up to more than 1
+    pub game_versions: HashMap<u32, HashMap<String, VersionShare>>,
+}
+
+/// Get the download share of each version of a project over time, to see how quickly users
+/// move to new releases and which game versions are still in use
+/// eg:
+/// {
+///     "versions": {
+///         "1692835200": {
+///             "IIJJKKLL": { "downloads": 30, "share": 0.75 },
+///             "AABBCCDD": { "downloads": 10, "share": 0.25 }
+///         }
+///     },
+///     "game_versions": {
+///         "1692835200": {
+///             "1.20.1": { "downloads": 30, "share": 0.75 }
+///         }
+///     }
+///}
+pub async fn project_versions_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    clickhouse: web::Data<clickhouse::Client>,
+    data: web::Query<GetVersionAdoptionData>,
+    session_queue: web::Data<AuthQueue>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::ANALYTICS]),
+    )
+    .await
+    .map(|x| x.1)?;
+
+    let start_date = data.start_date.unwrap_or(Utc::now() - Duration::weeks(2));
+    let end_date = data.end_date.unwrap_or(Utc::now());
+    let resolution_minutes = data.resolution_minutes.unwrap_or(60 * 24);
+
+    let project_id = filter_allowed_ids(Some(vec![info.into_inner().0]), user, &pool, &redis, None)
+        .await?
+        .and_then(|x| x.into_iter().next())
+        .ok_or(ApiError::NotFound)?;
+
+    let downloads = crate::clickhouse::fetch_version_downloads(
+        project_id,
+        start_date,
+        end_date,
+        resolution_minutes,
+        clickhouse.into_inner(),
+    )
+    .await?;
+
+    let version_ids = downloads
+        .iter()
+        .map(|x| database::models::VersionId(x.id as i64))
+        .unique()
+        .collect::<Vec<_>>();
+    let game_versions: HashMap<u64, Vec<String>> =
+        database::models::Version::get_many(&version_ids, &**pool, &redis)
+            .await?
+            .into_iter()
+            .map(|x| {
+                let version = crate::models::projects::Version::from(x);
+                let game_versions = version
+                    .fields
+                    .get("game_versions")
+                    .and_then(|x| x.as_array())
+                    .map(|x| {
+                        x.iter()
+                            .filter_map(|x| x.as_str().map(|x| x.to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                (version.id.0, game_versions)
+            })
+            .collect();
+
+    let mut totals: HashMap<u32, u64> = HashMap::new();
+    for value in &downloads {
+        *totals.entry(value.time).or_default() += value.total;
+    }
+
+    let share = |time: u32, downloads: u64| {
+        let total = totals.get(&time).copied().unwrap_or_default();
+        if total == 0 {
+            0.0
+        } else {
+            downloads as f64 / total as f64
+        }
+    };
+
+    let mut adoption = VersionAdoption::default();
+    for value in &downloads {
+        adoption.versions.entry(value.time).or_default().insert(
+            VersionId(value.id),
+            VersionShare {
+                downloads: value.total,
+                share: share(value.time, value.total),
+            },
+        );
+
+        for game_version in game_versions.get(&value.id).into_iter().flatten() {
+            adoption
+                .game_versions
+                .entry(value.time)
+                .or_default()
+                .entry(game_version.clone())
+                .or_default()
+                .downloads += value.total;
+        }
+    }
+    for (time, game_versions) in adoption.game_versions.iter_mut() {
+        for value in game_versions.values_mut() {
+            value.share = share(*time, value.downloads);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(adoption))
+}
+
 fn condense_countries(countries: HashMap<String, u64>) -> HashMap<String, u64> {
     // Every country under '15' (view or downloads) should be condensed into 'XX'
     let mut hm = HashMap::new();
@@ -546,14 +1257,14 @@ fn condense_countries(countries: HashMap<String, u64>) -> HashMap<String, u64> {
 async fn filter_allowed_ids(
     mut project_ids: Option<Vec<String>>,
     user: crate::models::users::User,
-    pool: &web::Data<PgPool>,
+    pool: &PgPool,
     redis: &RedisPool,
     remove_defaults: Option<bool>,
 ) -> Result<Option<Vec<ProjectId>>, ApiError> {
     // If no project_ids or version_ids are provided, we default to all projects the user has *public* access to
     if project_ids.is_none() && !remove_defaults.unwrap_or(false) {
         project_ids = Some(
-            user_item::User::get_projects(user.id.into(), &***pool, redis)
+            user_item::User::get_projects(user.id.into(), pool, redis)
                 .await?
                 .into_iter()
                 .map(|x| ProjectId::from(x).to_string())
@@ -565,23 +1276,21 @@ async fn filter_allowed_ids(
     // - Filter out unauthorized projects/versions
     let project_ids = if let Some(project_strings) = project_ids {
         let projects_data =
-            database::models::Project::get_many(&project_strings, &***pool, redis).await?;
+            database::models::Project::get_many(&project_strings, pool, redis).await?;
 
         let team_ids = projects_data
             .iter()
             .map(|x| x.inner.team_id)
             .collect::<Vec<database::models::TeamId>>();
         let team_members =
-            database::models::TeamMember::get_from_team_full_many(&team_ids, &***pool, redis)
-                .await?;
+            database::models::TeamMember::get_from_team_full_many(&team_ids, pool, redis).await?;
 
         let organization_ids = projects_data
             .iter()
             .filter_map(|x| x.inner.organization_id)
             .collect::<Vec<database::models::OrganizationId>>();
         let organizations =
-            database::models::Organization::get_many_ids(&organization_ids, &***pool, redis)
-                .await?;
+            database::models::Organization::get_many_ids(&organization_ids, pool, redis).await?;
 
         let organization_team_ids = organizations
             .iter()
@@ -589,7 +1298,7 @@ async fn filter_allowed_ids(
             .collect::<Vec<database::models::TeamId>>();
         let organization_team_members = database::models::TeamMember::get_from_team_full_many(
             &organization_team_ids,
-            &***pool,
+            pool,
             redis,
         )
         .await?;
@@ -1,30 +1,43 @@
 use crate::auth::checks::is_visible_collection;
 use crate::auth::{filter_visible_collections, get_user_from_headers};
+use crate::database;
+use crate::database::models::collection_snapshot_item::CollectionSnapshot as DBCollectionSnapshot;
+use crate::database::models::notification_item::NotificationBuilder;
 use crate::database::models::{collection_item, generate_collection_id, project_item};
 use crate::database::redis::RedisPool;
 use crate::file_hosting::FileHost;
-use crate::models::collections::{Collection, CollectionStatus};
+use crate::models::collections::{
+    Collection, CollectionEditor, CollectionItem, CollectionPermissions, CollectionSnapshot,
+    CollectionSnapshotId, CollectionStatus,
+};
 use crate::models::ids::base62_impl::parse_base62;
-use crate::models::ids::{CollectionId, ProjectId};
+use crate::models::ids::{CollectionId, ProjectId, VersionId};
+use crate::models::moderation::ImageSource;
+use crate::models::notifications::NotificationBody;
 use crate::models::pats::Scopes;
+use crate::models::users::UserId;
 use crate::queue::session::AuthQueue;
 use crate::routes::v3::project_creation::CreateError;
 use crate::routes::ApiError;
+use crate::search::collections::{search_for_collections, CollectionSearchRequest};
+use crate::search::indexing::collections::remove_collections;
+use crate::search::{SearchConfig, SearchError};
+use crate::util::image_scan;
 use crate::util::routes::read_from_payload;
-use crate::util::validate::validation_errors_to_string;
-use crate::{database, models};
 use actix_web::web::Data;
 use actix_web::{web, HttpRequest, HttpResponse};
 use chrono::Utc;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::collections::HashMap;
 use std::sync::Arc;
 use validator::Validate;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.route("collections", web::get().to(collections_get));
     cfg.route("collection", web::post().to(collection_create));
+    cfg.route("search/collections", web::get().to(collection_search));
 
     cfg.service(
         web::scope("collection")
@@ -32,7 +45,35 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route("{id}", web::delete().to(collection_delete))
             .route("{id}", web::patch().to(collection_edit))
             .route("{id}/icon", web::patch().to(collection_icon_edit))
-            .route("{id}/icon", web::delete().to(delete_collection_icon)),
+            .route("{id}/icon", web::delete().to(delete_collection_icon))
+            .route("{id}/projects/order", web::post().to(collection_reorder))
+            .route(
+                "{id}/projects/{project_id}",
+                web::patch().to(collection_item_edit),
+            )
+            .route("{id}/snapshots", web::get().to(collection_snapshots_get))
+            .route("{id}/snapshots", web::post().to(collection_snapshot_create))
+            .route(
+                "{id}/snapshots/{snapshot_id}",
+                web::get().to(collection_snapshot_get),
+            )
+            .route(
+                "{id}/snapshots/{snapshot_id}",
+                web::delete().to(collection_snapshot_delete),
+            )
+            .route("{id}/follow", web::post().to(collection_follow))
+            .route("{id}/follow", web::delete().to(collection_unfollow))
+            .route("{id}/editors", web::get().to(collection_editors_get))
+            .route("{id}/editors", web::post().to(collection_editor_add))
+            .route("{id}/editors/join", web::post().to(collection_editor_join))
+            .route(
+                "{id}/editors/{user_id}",
+                web::patch().to(collection_editor_edit),
+            )
+            .route(
+                "{id}/editors/{user_id}",
+                web::delete().to(collection_editor_remove),
+            ),
     );
 }
 
@@ -73,9 +114,7 @@ pub async fn collection_create(
     .await?
     .1;
 
-    collection_create_data
-        .validate()
-        .map_err(|err| CreateError::InvalidInput(validation_errors_to_string(err, None)))?;
+    collection_create_data.validate()?;
 
     let mut transaction = client.begin().await?;
 
@@ -118,13 +157,33 @@ pub async fn collection_create(
         icon_url: None,
         color: None,
         status: collection_builder.status,
+        items: initial_project_ids
+            .iter()
+            .map(|x| CollectionItem {
+                project_id: *x,
+                note: None,
+            })
+            .collect(),
         projects: initial_project_ids,
+        editors: Vec::new(),
+        followers: 0,
     };
     transaction.commit().await?;
 
     Ok(HttpResponse::Ok().json(response))
 }
 
+/// Searches listed collections. The index is rebuilt along with the project index, so new
+/// collections and changes show up after the next indexing run
+pub async fn collection_search(
+    web::Query(info): web::Query<CollectionSearchRequest>,
+    config: web::Data<SearchConfig>,
+) -> Result<HttpResponse, SearchError> {
+    let results = search_for_collections(&info, &config).await?;
+
+    Ok(HttpResponse::Ok().json(results))
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CollectionIds {
     pub ids: String,
@@ -209,6 +268,7 @@ pub struct EditCollection {
     pub new_projects: Option<Vec<String>>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn collection_edit(
     req: HttpRequest,
     info: web::Path<(String,)>,
@@ -216,6 +276,7 @@ pub async fn collection_edit(
     new_collection: web::Json<EditCollection>,
     redis: web::Data<RedisPool>,
     session_queue: web::Data<AuthQueue>,
+    search_config: web::Data<SearchConfig>,
 ) -> Result<HttpResponse, ApiError> {
     let user = get_user_from_headers(
         &req,
@@ -227,17 +288,29 @@ pub async fn collection_edit(
     .await?
     .1;
 
-    new_collection
-        .validate()
-        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+    new_collection.validate()?;
 
     let string = info.into_inner().0;
     let id = database::models::CollectionId(parse_base62(&string)? as i64);
     let result = database::models::Collection::get(id, &**pool, &redis).await?;
 
     if let Some(collection_item) = result {
-        if !can_modify_collection(&collection_item, &user) {
-            return Ok(HttpResponse::Unauthorized().body(""));
+        let permissions = CollectionPermissions::get_permissions(&user, &collection_item)
+            .ok_or(ApiError::NotFound)?;
+
+        if (new_collection.name.is_some() || new_collection.description.is_some())
+            && !permissions.contains(CollectionPermissions::EDIT_DETAILS)
+        {
+            return Err(ApiError::CustomAuthentication(
+                "You don't have permission to edit this collection's details!".to_string(),
+            ));
+        }
+        if new_collection.new_projects.is_some()
+            && !permissions.contains(CollectionPermissions::EDIT_PROJECTS)
+        {
+            return Err(ApiError::CustomAuthentication(
+                "You don't have permission to edit this collection's projects!".to_string(),
+            ));
         }
 
         let id = collection_item.id;
@@ -274,7 +347,9 @@ pub async fn collection_edit(
 
         if let Some(status) = &new_collection.status {
             if !(user.role.is_mod()
-                || collection_item.status.is_approved() && status.can_be_requested())
+                || collection_item.user_id == user.id.into()
+                    && collection_item.status.is_approved()
+                    && status.can_be_requested())
             {
                 return Err(ApiError::CustomAuthentication(
                     "You don't have permission to set this status!".to_string(),
@@ -292,24 +367,13 @@ pub async fn collection_edit(
             )
             .execute(&mut *transaction)
             .await?;
+
+            if collection_item.status.is_searchable() && !status.is_searchable() {
+                remove_collections(&[collection_item.id.into()], &search_config).await?;
+            }
         }
 
         if let Some(new_project_ids) = &new_collection.new_projects {
-            // Delete all existing projects
-            sqlx::query!(
-                "
-                DELETE FROM collections_mods
-                WHERE collection_id = $1
-                ",
-                collection_item.id as database::models::ids::CollectionId,
-            )
-            .execute(&mut *transaction)
-            .await?;
-
-            let collection_item_ids = new_project_ids
-                .iter()
-                .map(|_| collection_item.id.0)
-                .collect_vec();
             let mut validated_project_ids = Vec::new();
             for project_id in new_project_ids {
                 let project = database::models::Project::get(project_id, &**pool, &redis)
@@ -321,19 +385,58 @@ pub async fn collection_edit(
                     })?;
                 validated_project_ids.push(project.inner.id.0);
             }
-            // Insert- don't throw an error if it already exists
-            sqlx::query!(
-                "
-                        INSERT INTO collections_mods (collection_id, mod_id)
-                        SELECT * FROM UNNEST ($1::int8[], $2::int8[])
-                        ON CONFLICT DO NOTHING
-                        ",
-                &collection_item_ids[..],
-                &validated_project_ids[..],
+            // Projects are kept in the order given, along with the notes of those already
+            // in the collection
+            database::models::Collection::set_projects(
+                collection_item.id,
+                &validated_project_ids
+                    .iter()
+                    .map(|x| database::models::ProjectId(*x))
+                    .collect_vec(),
+                &mut transaction,
             )
-            .execute(&mut *transaction)
             .await?;
 
+            let added_projects = validated_project_ids
+                .iter()
+                .filter(|x| !collection_item.projects.iter().any(|y| y.0 == **x))
+                .map(|x| ProjectId::from(database::models::ProjectId(*x)))
+                .unique()
+                .collect_vec();
+            let removed_projects = collection_item
+                .projects
+                .iter()
+                .filter(|x| !validated_project_ids.contains(&x.0))
+                .map(|x| ProjectId::from(*x))
+                .collect_vec();
+
+            // Followers of a private collection can no longer see it, so they aren't notified
+            let hidden = new_collection
+                .status
+                .as_ref()
+                .unwrap_or(&collection_item.status)
+                .is_hidden();
+            if !hidden && !(added_projects.is_empty() && removed_projects.is_empty()) {
+                let followers = database::models::Collection::get_followers(
+                    collection_item.id,
+                    &mut transaction,
+                )
+                .await?
+                .into_iter()
+                .filter(|x| *x != user.id.into())
+                .collect();
+
+                NotificationBuilder {
+                    body: NotificationBody::CollectionUpdate {
+                        collection_id: collection_item.id.into(),
+                        added_projects,
+                        removed_projects,
+                    },
+                }
+                .insert_many(followers, &mut transaction, &redis)
+                .await?;
+            }
+
             sqlx::query!(
                 "
                 UPDATE collections
@@ -391,7 +494,10 @@ pub async fn collection_icon_edit(
                 ApiError::InvalidInput("The specified collection does not exist!".to_string())
             })?;
 
-        if !can_modify_collection(&collection_item, &user) {
+        if !CollectionPermissions::get_permissions(&user, &collection_item)
+            .unwrap_or_default()
+            .contains(CollectionPermissions::EDIT_DETAILS)
+        {
             return Ok(HttpResponse::Unauthorized().body(""));
         }
 
@@ -405,6 +511,7 @@ pub async fn collection_icon_edit(
 
         let bytes =
             read_from_payload(&mut payload, 262144, "Icons must be smaller than 256KiB").await?;
+        crate::util::magic::check_content(&bytes, &ext.ext, content_type)?;
 
         let color = crate::util::img::get_color_from_img(&bytes)?;
 
@@ -418,6 +525,8 @@ pub async fn collection_icon_edit(
             )
             .await?;
 
+        let icon_url = format!("{}/{}", cdn_url, upload_data.file_name);
+
         let mut transaction = pool.begin().await?;
 
         sqlx::query!(
@@ -426,12 +535,18 @@ pub async fn collection_icon_edit(
             SET icon_url = $1, color = $2
             WHERE (id = $3)
             ",
-            format!("{}/{}", cdn_url, upload_data.file_name),
+            icon_url,
             color.map(|x| x as i32),
             collection_item.id as database::models::ids::CollectionId,
         )
         .execute(&mut *transaction)
         .await?;
+        image_scan::queue_scan(
+            &icon_url,
+            ImageSource::CollectionIcon { collection_id },
+            &mut *transaction,
+        )
+        .await?;
 
         transaction.commit().await?;
         database::models::Collection::clear_cache(collection_item.id, &redis).await?;
@@ -470,7 +585,10 @@ pub async fn delete_collection_icon(
         .ok_or_else(|| {
             ApiError::InvalidInput("The specified collection does not exist!".to_string())
         })?;
-    if !can_modify_collection(&collection_item, &user) {
+    if !CollectionPermissions::get_permissions(&user, &collection_item)
+        .unwrap_or_default()
+        .contains(CollectionPermissions::EDIT_DETAILS)
+    {
         return Ok(HttpResponse::Unauthorized().body(""));
     }
 
@@ -508,6 +626,7 @@ pub async fn collection_delete(
     pool: web::Data<PgPool>,
     redis: web::Data<RedisPool>,
     session_queue: web::Data<AuthQueue>,
+    search_config: web::Data<SearchConfig>,
 ) -> Result<HttpResponse, ApiError> {
     let user = get_user_from_headers(
         &req,
@@ -526,7 +645,8 @@ pub async fn collection_delete(
         .ok_or_else(|| {
             ApiError::InvalidInput("The specified collection does not exist!".to_string())
         })?;
-    if !can_modify_collection(&collection, &user) {
+    // Editors can't delete a collection, only its owner can
+    if !(collection.user_id == user.id.into() || user.role.is_mod()) {
         return Ok(HttpResponse::Unauthorized().body(""));
     }
     let mut transaction = pool.begin().await?;
@@ -536,6 +656,7 @@ pub async fn collection_delete(
 
     transaction.commit().await?;
     database::models::Collection::clear_cache(collection.id, &redis).await?;
+    remove_collections(&[collection.id.into()], &search_config).await?;
 
     if result.is_some() {
         Ok(HttpResponse::NoContent().body(""))
@@ -544,9 +665,722 @@ pub async fn collection_delete(
     }
 }
 
-fn can_modify_collection(
-    collection: &database::models::Collection,
-    user: &models::users::User,
-) -> bool {
-    collection.user_id == user.id.into() || user.role.is_mod()
+#[derive(Deserialize, Validate)]
+pub struct EditCollectionItem {
+    #[validate(length(max = 2048))]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "::serde_with::rust::double_option"
+    )]
+    pub note: Option<Option<String>>,
+}
+
+pub async fn collection_item_edit(
+    req: HttpRequest,
+    info: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    edit_item: web::Json<EditCollectionItem>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_WRITE]),
+    )
+    .await?
+    .1;
+
+    edit_item.validate()?;
+
+    let (string, project_string) = info.into_inner();
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let permissions =
+        CollectionPermissions::get_permissions(&user, &collection).ok_or(ApiError::NotFound)?;
+    if !permissions.contains(CollectionPermissions::EDIT_PROJECTS) {
+        return Err(ApiError::CustomAuthentication(
+            "You don't have permission to edit this collection's projects!".to_string(),
+        ));
+    }
+
+    let project = database::models::Project::get(&project_string, &**pool, &redis)
+        .await?
+        .filter(|x| collection.projects.contains(&x.inner.id))
+        .ok_or_else(|| {
+            ApiError::InvalidInput("The specified project is not in this collection!".to_string())
+        })?;
+
+    if let Some(note) = &edit_item.note {
+        let mut transaction = pool.begin().await?;
+        database::models::Collection::edit_note(
+            collection.id,
+            project.inner.id,
+            note.as_deref().map(str::trim).filter(|x| !x.is_empty()),
+            &mut transaction,
+        )
+        .await?;
+        transaction.commit().await?;
+        database::models::Collection::clear_cache(collection.id, &redis).await?;
+    }
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[derive(Deserialize)]
+pub struct CollectionOrder {
+    /// Every project in the collection, in the new order
+    pub projects: Vec<ProjectId>,
+}
+
+pub async fn collection_reorder(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    order: web::Json<CollectionOrder>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let string = info.into_inner().0;
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let permissions =
+        CollectionPermissions::get_permissions(&user, &collection).ok_or(ApiError::NotFound)?;
+    if !permissions.contains(CollectionPermissions::EDIT_PROJECTS) {
+        return Err(ApiError::CustomAuthentication(
+            "You don't have permission to edit this collection's projects!".to_string(),
+        ));
+    }
+
+    let projects = order
+        .projects
+        .iter()
+        .map(|x| database::models::ProjectId::from(*x))
+        .collect_vec();
+    if projects.len() != collection.projects.len()
+        || projects.iter().unique().count() != projects.len()
+        || !projects.iter().all(|x| collection.projects.contains(x))
+    {
+        return Err(ApiError::InvalidInput(
+            "The new order must contain every project in the collection exactly once".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+    database::models::Collection::set_projects(collection.id, &projects, &mut transaction).await?;
+    transaction.commit().await?;
+    database::models::Collection::clear_cache(collection.id, &redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+pub async fn collection_snapshots_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let string = info.into_inner().0;
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let user_option = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_READ]),
+    )
+    .await
+    .map(|x| x.1)
+    .ok();
+
+    if !is_visible_collection(&collection, &user_option).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    let snapshots = DBCollectionSnapshot::get_collection(collection.id, &**pool)
+        .await?
+        .into_iter()
+        .map(CollectionSnapshot::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(snapshots))
+}
+
+pub async fn collection_snapshot_get(
+    req: HttpRequest,
+    info: web::Path<(String, CollectionSnapshotId)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let (string, snapshot_id) = info.into_inner();
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let user_option = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_READ]),
+    )
+    .await
+    .map(|x| x.1)
+    .ok();
+
+    if !is_visible_collection(&collection, &user_option).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    let snapshot = DBCollectionSnapshot::get(snapshot_id.into(), &**pool)
+        .await?
+        .filter(|x| x.collection_id.0 == collection.id.0)
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(CollectionSnapshot::from(snapshot)))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct CreateCollectionSnapshot {
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    /// Versions to pin projects to. Projects which aren't listed are pinned to their newest
+    /// listed version
+    #[serde(default)]
+    pub versions: HashMap<ProjectId, VersionId>,
+}
+
+pub async fn collection_snapshot_create(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    snapshot_data: web::Json<CreateCollectionSnapshot>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_WRITE]),
+    )
+    .await?
+    .1;
+
+    snapshot_data.validate()?;
+
+    let string = info.into_inner().0;
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let permissions =
+        CollectionPermissions::get_permissions(&user, &collection).ok_or(ApiError::NotFound)?;
+    if !permissions.contains(CollectionPermissions::EDIT_PROJECTS) {
+        return Err(ApiError::CustomAuthentication(
+            "You don't have permission to snapshot this collection!".to_string(),
+        ));
+    }
+
+    let pinned_versions = database::models::Version::get_many(
+        &snapshot_data
+            .versions
+            .values()
+            .map(|x| (*x).into())
+            .collect_vec(),
+        &**pool,
+        &redis,
+    )
+    .await?;
+    let mut versions =
+        DBCollectionSnapshot::get_latest_versions(&collection.projects, &**pool).await?;
+    for (project_id, version_id) in &snapshot_data.versions {
+        let project_id = database::models::ProjectId::from(*project_id);
+        if !collection.projects.contains(&project_id) {
+            return Err(ApiError::InvalidInput(format!(
+                "The project {} is not in this collection!",
+                ProjectId::from(project_id)
+            )));
+        }
+        let version = pinned_versions
+            .iter()
+            .find(|x| x.inner.id == (*version_id).into() && x.inner.project_id == project_id)
+            .ok_or_else(|| {
+                ApiError::InvalidInput(format!(
+                    "The version {} does not belong to the project {}!",
+                    version_id,
+                    ProjectId::from(project_id)
+                ))
+            })?;
+        versions.insert(project_id, version.inner.id);
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    let snapshot = DBCollectionSnapshot {
+        id: database::models::generate_collection_snapshot_id(&mut transaction).await?,
+        collection_id: collection.id,
+        name: snapshot_data.name.clone(),
+        created_by: Some(user.id.into()),
+        created: Utc::now(),
+        // Projects without any listed versions can't be pinned, so they're left out
+        versions: collection
+            .projects
+            .iter()
+            .filter_map(|x| versions.get(x).map(|v| (*x, *v)))
+            .collect(),
+    };
+    snapshot.insert(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(CollectionSnapshot::from(snapshot)))
+}
+
+pub async fn collection_snapshot_delete(
+    req: HttpRequest,
+    info: web::Path<(String, CollectionSnapshotId)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let (string, snapshot_id) = info.into_inner();
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let permissions =
+        CollectionPermissions::get_permissions(&user, &collection).ok_or(ApiError::NotFound)?;
+    if !permissions.contains(CollectionPermissions::EDIT_PROJECTS) {
+        return Err(ApiError::CustomAuthentication(
+            "You don't have permission to delete this collection's snapshots!".to_string(),
+        ));
+    }
+
+    let snapshot = DBCollectionSnapshot::get(snapshot_id.into(), &**pool)
+        .await?
+        .filter(|x| x.collection_id.0 == collection.id.0)
+        .ok_or(ApiError::NotFound)?;
+
+    let mut transaction = pool.begin().await?;
+    DBCollectionSnapshot::remove(snapshot.id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+pub async fn collection_follow(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::USER_WRITE]),
+    )
+    .await?
+    .1;
+
+    let string = info.into_inner().0;
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or_else(|| {
+            ApiError::InvalidInput("The specified collection does not exist!".to_string())
+        })?;
+
+    let user_id = user.id.into();
+    if !is_visible_collection(&collection, &Some(user)).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    let mut transaction = pool.begin().await?;
+    let followed =
+        database::models::Collection::follow(collection.id, user_id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    if followed {
+        database::models::Collection::clear_cache(collection.id, &redis).await?;
+
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::InvalidInput(
+            "You are already following this collection!".to_string(),
+        ))
+    }
+}
+
+pub async fn collection_unfollow(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::USER_WRITE]),
+    )
+    .await?
+    .1;
+
+    let string = info.into_inner().0;
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or_else(|| {
+            ApiError::InvalidInput("The specified collection does not exist!".to_string())
+        })?;
+
+    let mut transaction = pool.begin().await?;
+    let unfollowed =
+        database::models::Collection::unfollow(collection.id, user.id.into(), &mut transaction)
+            .await?;
+    transaction.commit().await?;
+
+    if unfollowed {
+        database::models::Collection::clear_cache(collection.id, &redis).await?;
+
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::InvalidInput(
+            "You are not following this collection!".to_string(),
+        ))
+    }
+}
+
+pub async fn collection_editors_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let string = info.into_inner().0;
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection_data = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let user_option = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_READ]),
+    )
+    .await
+    .map(|x| x.1)
+    .ok();
+
+    if !is_visible_collection(&collection_data, &user_option).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    // Pending invites are only shown to those who can manage editors
+    let can_manage = user_option
+        .as_ref()
+        .and_then(|user| CollectionPermissions::get_permissions(user, &collection_data))
+        .map(|x| x.contains(CollectionPermissions::MANAGE_EDITORS))
+        .unwrap_or(false);
+
+    let editors = collection_data
+        .editors
+        .into_iter()
+        .filter(|x| can_manage || x.accepted)
+        .map(CollectionEditor::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(editors))
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NewCollectionEditor {
+    pub user_id: UserId,
+    #[serde(default)]
+    pub permissions: CollectionPermissions,
+}
+
+pub async fn collection_editor_add(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    new_editor: web::Json<NewCollectionEditor>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let string = info.into_inner().0;
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let permissions =
+        CollectionPermissions::get_permissions(&user, &collection).ok_or(ApiError::NotFound)?;
+    if !permissions.contains(CollectionPermissions::MANAGE_EDITORS) {
+        return Err(ApiError::CustomAuthentication(
+            "You don't have permission to invite editors to this collection!".to_string(),
+        ));
+    }
+    if !permissions.contains(new_editor.permissions) {
+        return Err(ApiError::InvalidInput(
+            "The new editor has permissions that you don't have".to_string(),
+        ));
+    }
+
+    if collection.user_id == new_editor.user_id.into() {
+        return Err(ApiError::InvalidInput(
+            "The owner of a collection can't be invited to edit it".to_string(),
+        ));
+    }
+    if let Some(editor) = collection
+        .editors
+        .iter()
+        .find(|x| x.user_id == new_editor.user_id.into())
+    {
+        return Err(ApiError::InvalidInput(if editor.accepted {
+            "The user is already an editor of this collection".to_string()
+        } else {
+            "There is already a pending invite for this user".to_string()
+        }));
+    }
+
+    let new_user = database::models::User::get_id(new_editor.user_id.into(), &**pool, &redis)
+        .await?
+        .ok_or_else(|| ApiError::InvalidInput("An invalid User ID specified".to_string()))?;
+
+    let mut transaction = pool.begin().await?;
+
+    database::models::Collection::upsert_editor(
+        collection.id,
+        new_user.id,
+        new_editor.permissions,
+        user.id.into(),
+        &mut transaction,
+    )
+    .await?;
+
+    NotificationBuilder {
+        body: NotificationBody::CollectionInvite {
+            collection_id: collection.id.into(),
+            invited_by: user.id,
+        },
+    }
+    .insert(new_user.id, &mut transaction, &redis)
+    .await?;
+
+    transaction.commit().await?;
+    database::models::Collection::clear_cache(collection.id, &redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+pub async fn collection_editor_join(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let string = info.into_inner().0;
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let editor = collection
+        .editors
+        .iter()
+        .find(|x| x.user_id == user.id.into())
+        .ok_or_else(|| {
+            ApiError::InvalidInput("There is no pending invite for this collection".to_string())
+        })?;
+    if editor.accepted {
+        return Err(ApiError::InvalidInput(
+            "You are already an editor of this collection".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+    database::models::Collection::accept_editor(collection.id, user.id.into(), &mut transaction)
+        .await?;
+    transaction.commit().await?;
+    database::models::Collection::clear_cache(collection.id, &redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EditCollectionEditor {
+    pub permissions: CollectionPermissions,
+}
+
+pub async fn collection_editor_edit(
+    req: HttpRequest,
+    info: web::Path<(String, UserId)>,
+    pool: web::Data<PgPool>,
+    edit_editor: web::Json<EditCollectionEditor>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let (string, editor_id) = info.into_inner();
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let permissions =
+        CollectionPermissions::get_permissions(&user, &collection).ok_or(ApiError::NotFound)?;
+    if !permissions.contains(CollectionPermissions::MANAGE_EDITORS) {
+        return Err(ApiError::CustomAuthentication(
+            "You don't have permission to edit the editors of this collection!".to_string(),
+        ));
+    }
+    let editor = collection
+        .editors
+        .iter()
+        .find(|x| x.user_id == editor_id.into())
+        .ok_or(ApiError::NotFound)?;
+    if !permissions.contains(editor.permissions | edit_editor.permissions) {
+        return Err(ApiError::InvalidInput(
+            "You can't change the permissions of an editor who has permissions that you don't have, or give permissions that you don't have".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+    database::models::Collection::edit_editor_permissions(
+        collection.id,
+        editor.user_id,
+        edit_editor.permissions,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+    database::models::Collection::clear_cache(collection.id, &redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+/// Removes an editor or revokes an invite. Editors can also use this to leave a collection or
+/// decline their invite
+pub async fn collection_editor_remove(
+    req: HttpRequest,
+    info: web::Path<(String, UserId)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::COLLECTION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let (string, editor_id) = info.into_inner();
+    let id = database::models::CollectionId(parse_base62(&string)? as i64);
+    let collection = database::models::Collection::get(id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let editor = collection
+        .editors
+        .iter()
+        .find(|x| x.user_id == editor_id.into())
+        .ok_or(ApiError::NotFound)?;
+
+    if editor_id != user.id {
+        let permissions =
+            CollectionPermissions::get_permissions(&user, &collection).ok_or(ApiError::NotFound)?;
+        if !permissions.contains(CollectionPermissions::MANAGE_EDITORS | editor.permissions) {
+            return Err(ApiError::CustomAuthentication(
+                "You don't have permission to remove this editor!".to_string(),
+            ));
+        }
+    }
+
+    let mut transaction = pool.begin().await?;
+    database::models::Collection::remove_editor(collection.id, editor.user_id, &mut transaction)
+        .await?;
+    transaction.commit().await?;
+    database::models::Collection::clear_cache(collection.id, &redis).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
 }
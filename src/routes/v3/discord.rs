@@ -0,0 +1,368 @@
+use crate::auth::get_user_from_headers;
+use crate::database::models::discord_role_link_item::DiscordRoleLink;
+use crate::database::models::{self, OrganizationId, ProjectId};
+use crate::database::redis::RedisPool;
+use crate::models::discord::{self as discord_models, DiscordRelationship};
+use crate::models::pats::Scopes;
+use crate::models::teams::{OrganizationPermissions, ProjectPermissions};
+use crate::queue::session::AuthQueue;
+use crate::routes::ApiError;
+use crate::webhooks::{required_header, verify_hmac, TIMESTAMP_TOLERANCE_SECONDS};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+
+/// The most role links a project or organization may have
+const MAX_ROLE_LINKS: usize = 25;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("project/{id}/discord_roles")
+            .route(web::get().to(project_discord_roles_get))
+            .route(web::post().to(project_discord_role_create)),
+    );
+    cfg.route(
+        "project/{id}/discord_roles/{link_id}",
+        web::delete().to(project_discord_role_delete),
+    );
+    cfg.service(
+        web::resource("organization/{id}/discord_roles")
+            .route(web::get().to(organization_discord_roles_get))
+            .route(web::post().to(organization_discord_role_create)),
+    );
+    cfg.route(
+        "organization/{id}/discord_roles/{link_id}",
+        web::delete().to(organization_discord_role_delete),
+    );
+    cfg.route("discord/verify", web::post().to(discord_verify));
+}
+
+/// What role links belong to
+#[derive(Copy, Clone)]
+enum RoleLinkOwner {
+    Project(ProjectId),
+    Organization(OrganizationId),
+}
+
+impl RoleLinkOwner {
+    fn ids(&self) -> (Option<ProjectId>, Option<OrganizationId>) {
+        match self {
+            RoleLinkOwner::Project(id) => (Some(*id), None),
+            RoleLinkOwner::Organization(id) => (None, Some(*id)),
+        }
+    }
+}
+
+/// Gets the project whose role links the user may manage, which takes permission to edit
+/// its details
+async fn get_project_owner(
+    req: &HttpRequest,
+    id: &str,
+    pool: &PgPool,
+    redis: &RedisPool,
+    session_queue: &AuthQueue,
+) -> Result<RoleLinkOwner, ApiError> {
+    let user = get_user_from_headers(
+        req,
+        pool,
+        redis,
+        session_queue,
+        Some(&[Scopes::PROJECT_WRITE]),
+    )
+    .await?
+    .1;
+
+    let project = models::Project::get(id, pool, redis)
+        .await?
+        .ok_or_else(|| {
+            ApiError::InvalidInput("The specified project does not exist!".to_string())
+        })?;
+
+    if !user.role.is_mod() {
+        let (team_member, organization_team_member) =
+            models::TeamMember::get_for_project_permissions(&project.inner, user.id.into(), pool)
+                .await?;
+
+        // Hide the project
+        if team_member.is_none() && organization_team_member.is_none() {
+            return Err(ApiError::CustomAuthentication(
+                "The specified project does not exist!".to_string(),
+            ));
+        }
+
+        let permissions = ProjectPermissions::get_permissions_by_role(
+            &user.role,
+            &team_member,
+            &organization_team_member,
+        )
+        .unwrap_or_default();
+
+        if !permissions.contains(ProjectPermissions::EDIT_DETAILS) {
+            return Err(ApiError::CustomAuthentication(
+                "You don't have permission to manage this project's Discord roles.".to_string(),
+            ));
+        }
+    }
+
+    Ok(RoleLinkOwner::Project(project.inner.id))
+}
+
+/// Gets the organization whose role links the user may manage, which takes permission to
+/// edit its details
+async fn get_organization_owner(
+    req: &HttpRequest,
+    id: &str,
+    pool: &PgPool,
+    redis: &RedisPool,
+    session_queue: &AuthQueue,
+) -> Result<RoleLinkOwner, ApiError> {
+    let user = get_user_from_headers(
+        req,
+        pool,
+        redis,
+        session_queue,
+        Some(&[Scopes::ORGANIZATION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let organization = models::Organization::get(id, pool, redis)
+        .await?
+        .ok_or_else(|| {
+            ApiError::InvalidInput("The specified organization does not exist!".to_string())
+        })?;
+
+    let team_member =
+        models::TeamMember::get_from_user_id(organization.team_id, user.id.into(), pool).await?;
+    let permissions = OrganizationPermissions::get_permissions_by_role(&user.role, &team_member)
+        .unwrap_or_default();
+
+    if !permissions.contains(OrganizationPermissions::EDIT_DETAILS) {
+        return Err(ApiError::CustomAuthentication(
+            "You don't have permission to manage this organization's Discord roles.".to_string(),
+        ));
+    }
+
+    Ok(RoleLinkOwner::Organization(organization.id))
+}
+
+async fn discord_roles_get(owner: RoleLinkOwner, pool: &PgPool) -> Result<HttpResponse, ApiError> {
+    let (project_id, organization_id) = owner.ids();
+    let links = DiscordRoleLink::get_many(project_id, organization_id, pool)
+        .await?
+        .into_iter()
+        .map(discord_models::DiscordRoleLink::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(links))
+}
+
+#[derive(Deserialize)]
+pub struct DiscordRoleLinkData {
+    /// The IDs of the Discord server and the role in it, as strings
+    pub guild_id: String,
+    pub role_id: String,
+    pub relationship: DiscordRelationship,
+}
+
+/// Adds a role link, generating the secret its bot signs requests with, which is returned
+/// only once
+async fn discord_role_create(
+    owner: RoleLinkOwner,
+    link_data: DiscordRoleLinkData,
+    pool: &PgPool,
+) -> Result<HttpResponse, ApiError> {
+    let parse_id = |id: &str| {
+        id.parse::<u64>()
+            .map(|x| x as i64)
+            .map_err(|_| ApiError::InvalidInput(format!("{id} is not a valid Discord ID")))
+    };
+    let guild_id = parse_id(&link_data.guild_id)?;
+    let role_id = parse_id(&link_data.role_id)?;
+
+    let (project_id, organization_id) = owner.ids();
+    let existing = DiscordRoleLink::get_many(project_id, organization_id, pool).await?;
+    if existing.len() >= MAX_ROLE_LINKS {
+        return Err(ApiError::InvalidInput(format!(
+            "At most {MAX_ROLE_LINKS} Discord roles can be linked"
+        )));
+    }
+
+    let secret = ChaCha20Rng::from_entropy()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect::<String>();
+
+    let mut link = DiscordRoleLink {
+        id: 0,
+        project_id,
+        organization_id,
+        guild_id,
+        role_id,
+        relationship: link_data.relationship,
+        secret: secret.clone(),
+        created: Utc::now(),
+    };
+    let mut transaction = pool.begin().await?;
+    link.id = link.insert(&mut transaction).await?;
+    transaction.commit().await?;
+
+    let mut response = serde_json::to_value(discord_models::DiscordRoleLink::from(link))?;
+    response["secret"] = json!(secret);
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+async fn discord_role_delete(
+    owner: RoleLinkOwner,
+    link_id: i64,
+    pool: &PgPool,
+) -> Result<HttpResponse, ApiError> {
+    let link = DiscordRoleLink::get(link_id, pool)
+        .await?
+        .filter(|x| (x.project_id, x.organization_id) == owner.ids())
+        .ok_or(ApiError::NotFound)?;
+
+    DiscordRoleLink::remove(link.id, pool).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+pub async fn project_discord_roles_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let owner =
+        get_project_owner(&req, &info.into_inner().0, &pool, &redis, &session_queue).await?;
+
+    discord_roles_get(owner, &pool).await
+}
+
+pub async fn project_discord_role_create(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    web::Json(link_data): web::Json<DiscordRoleLinkData>,
+) -> Result<HttpResponse, ApiError> {
+    let owner =
+        get_project_owner(&req, &info.into_inner().0, &pool, &redis, &session_queue).await?;
+
+    discord_role_create(owner, link_data, &pool).await
+}
+
+pub async fn project_discord_role_delete(
+    req: HttpRequest,
+    info: web::Path<(String, i64)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, link_id) = info.into_inner();
+    let owner = get_project_owner(&req, &id, &pool, &redis, &session_queue).await?;
+
+    discord_role_delete(owner, link_id, &pool).await
+}
+
+pub async fn organization_discord_roles_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let owner =
+        get_organization_owner(&req, &info.into_inner().0, &pool, &redis, &session_queue).await?;
+
+    discord_roles_get(owner, &pool).await
+}
+
+pub async fn organization_discord_role_create(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    web::Json(link_data): web::Json<DiscordRoleLinkData>,
+) -> Result<HttpResponse, ApiError> {
+    let owner =
+        get_organization_owner(&req, &info.into_inner().0, &pool, &redis, &session_queue).await?;
+
+    discord_role_create(owner, link_data, &pool).await
+}
+
+pub async fn organization_discord_role_delete(
+    req: HttpRequest,
+    info: web::Path<(String, i64)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, link_id) = info.into_inner();
+    let owner = get_organization_owner(&req, &id, &pool, &redis, &session_queue).await?;
+
+    discord_role_delete(owner, link_id, &pool).await
+}
+
+#[derive(Deserialize)]
+pub struct DiscordVerifyData {
+    pub role_link_id: i64,
+    /// The ID of the Discord user, as a string
+    pub discord_user_id: String,
+}
+
+/// Tells a bot whether a Discord user is granted a linked role, going by the Modrinth account
+/// the Discord account is linked to. Requests are signed with the role link's secret: the
+/// `X-Modrinth-Signature` header is `sha256=` followed by the hex encoded HMAC-SHA256 of
+/// `{timestamp}.{body}`, where the timestamp is sent in `X-Modrinth-Timestamp`
+pub async fn discord_verify(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    body: String,
+) -> Result<HttpResponse, ApiError> {
+    let timestamp = required_header(req.headers(), "X-Modrinth-Timestamp")?
+        .parse::<i64>()
+        .map_err(|_| ApiError::InvalidInput("Invalid timestamp".to_string()))?;
+    if (Utc::now().timestamp() - timestamp).abs() > TIMESTAMP_TOLERANCE_SECONDS {
+        return Err(ApiError::InvalidInput(
+            "Timestamp is outside of the tolerance".to_string(),
+        ));
+    }
+    let signature = required_header(req.headers(), "X-Modrinth-Signature")?
+        .strip_prefix("sha256=")
+        .and_then(|x| hex::decode(x).ok())
+        .ok_or_else(|| ApiError::InvalidInput("Invalid signature".to_string()))?;
+
+    let verify_data: DiscordVerifyData = serde_json::from_str(&body)?;
+    let link = DiscordRoleLink::get(verify_data.role_link_id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    verify_hmac(
+        link.secret.as_bytes(),
+        &format!("{timestamp}.{body}"),
+        &signature,
+    )?;
+
+    let discord_id = verify_data
+        .discord_user_id
+        .parse::<u64>()
+        .map_err(|_| ApiError::InvalidInput("Invalid Discord user ID".to_string()))?;
+    let granted = link.is_granted(discord_id as i64, &**pool).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "guild_id": (link.guild_id as u64).to_string(),
+        "role_id": (link.role_id as u64).to_string(),
+        "granted": granted,
+    })))
+}
@@ -0,0 +1,36 @@
+use crate::auth::get_user_from_headers;
+use crate::database::models::feature_flag_item::FeatureFlag;
+use crate::database::models::UserId;
+use crate::database::redis::RedisPool;
+use crate::queue::session::AuthQueue;
+use crate::routes::ApiError;
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("feature_flags", web::get().to(feature_flags_get));
+}
+
+/// Lists the keys of the flags which are on for the requesting user, so clients can show the
+/// same features as the API
+pub async fn feature_flags_get(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id: Option<UserId> =
+        get_user_from_headers(&req, &**pool, &redis, &session_queue, None)
+            .await
+            .ok()
+            .map(|x| x.1.id.into());
+
+    let enabled = FeatureFlag::get_all(&**pool, &redis)
+        .await?
+        .into_iter()
+        .filter(|x| x.is_enabled_for(user_id))
+        .map(|x| x.key)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(enabled))
+}
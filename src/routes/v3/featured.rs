@@ -0,0 +1,60 @@
+use super::projects::use_preferred_images;
+use crate::auth::filter_visible_projects;
+use crate::database::models::featured_item::FeaturedRow;
+use crate::database::redis::RedisPool;
+use crate::models::featured::ActiveFeaturedRow;
+use crate::models::ids::ProjectId;
+use crate::routes::ApiError;
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("featured", web::get().to(featured_get));
+}
+
+/// Lists the staff-curated rows which are shown now, by kind and ordering. Projects which are
+/// not public are left out of their rows
+pub async fn featured_get(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    let rows = FeaturedRow::get_active(&**pool, &redis).await?;
+
+    let mut project_ids = rows
+        .iter()
+        .flat_map(|x| x.project_ids.iter().copied())
+        .collect::<Vec<_>>();
+    project_ids.sort_by_key(|x| x.0);
+    project_ids.dedup_by_key(|x| x.0);
+
+    let projects = crate::database::Project::get_many_ids(&project_ids, &**pool, &redis).await?;
+    let mut projects = filter_visible_projects(projects, &None, &pool)
+        .await?
+        .into_iter()
+        .filter(|x| x.status.is_searchable())
+        .map(|x| (x.id, x))
+        .collect::<HashMap<ProjectId, _>>();
+    for project in projects.values_mut() {
+        use_preferred_images(project, &req);
+    }
+
+    let rows = rows
+        .into_iter()
+        .map(|x| ActiveFeaturedRow {
+            id: x.id,
+            kind: x.kind,
+            title: x.title,
+            description: x.description,
+            projects: x
+                .project_ids
+                .into_iter()
+                .filter_map(|id| projects.get(&ProjectId::from(id)).cloned())
+                .collect(),
+            ends: x.ends,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(rows))
+}
@@ -0,0 +1,292 @@
+use super::projects::use_preferred_images;
+use crate::auth::{filter_visible_projects, get_user_from_headers};
+use crate::database;
+use crate::database::models::{ProjectId, UserId, VersionId};
+use crate::database::redis::RedisPool;
+use crate::models::feed::{FeedItem, FeedRanking, FeedSource};
+use crate::models::pats::Scopes;
+use crate::models::projects::{Project, ProjectStatus, VersionStatus};
+use crate::queue::session::AuthQueue;
+use crate::routes::ApiError;
+use crate::util::feed::{rank, ranker, FeedCandidate};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use validator::Validate;
+
+/// How far back new versions of followed projects and the user's downloads are looked at
+const HISTORY_DAYS: i64 = 30;
+/// How far back downloads count towards trending projects
+const TRENDING_DAYS: i64 = 7;
+/// How many candidates are gathered from each source before ranking
+const CANDIDATES_PER_SOURCE: u32 = 100;
+/// How many of the user's most downloaded projects similar projects are found for
+const DOWNLOADED_PROJECTS: u32 = 10;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("feed").route("home", web::get().to(home_feed_get)));
+}
+
+#[derive(Deserialize, Validate)]
+pub struct HomeFeedQuery {
+    /// JSON array of loaders similar and trending projects must support
+    pub loaders: Option<String>,
+    /// JSON array of game versions similar and trending projects must support
+    pub game_versions: Option<String>,
+    #[serde(default)]
+    pub ranking: FeedRanking,
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<u32>,
+}
+
+/// Blends updates to followed projects, projects similar to the ones the user downloaded
+/// recently and trending projects into one feed
+pub async fn home_feed_get(
+    req: HttpRequest,
+    web::Query(query): web::Query<HomeFeedQuery>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    clickhouse: web::Data<clickhouse::Client>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::USER_READ]),
+    )
+    .await?
+    .1;
+
+    query.validate()?;
+    let loaders = query
+        .loaders
+        .as_deref()
+        .map(serde_json::from_str::<Vec<String>>)
+        .transpose()?;
+    let game_versions = query
+        .game_versions
+        .as_deref()
+        .map(serde_json::from_str::<Vec<String>>)
+        .transpose()?;
+
+    let user_id: UserId = user.id.into();
+    let clickhouse = clickhouse.into_inner();
+    let mut candidates = followed_updates(user_id, &pool).await?;
+    candidates.extend(similar_projects(user_id, &pool, clickhouse.clone()).await?);
+    candidates.extend(trending_projects(&pool, clickhouse).await?);
+
+    let mut project_ids = candidates.iter().map(|x| x.project_id).collect::<Vec<_>>();
+    project_ids.sort_by_key(|x| x.0);
+    project_ids.dedup();
+    let projects = database::Project::get_many_ids(&project_ids, &**pool, &redis).await?;
+    let projects = filter_visible_projects(projects, &Some(user), &pool)
+        .await?
+        .into_iter()
+        .map(|x| (ProjectId::from(x.id), x))
+        .collect::<HashMap<_, _>>();
+
+    // Recommendations have to run on the user's setup, while followed projects are shown
+    // regardless
+    candidates.retain(|x| {
+        projects.get(&x.project_id).map_or(false, |project| {
+            x.source == FeedSource::FollowedUpdate
+                || supports(project, loaders.as_deref(), game_versions.as_deref())
+        })
+    });
+
+    let ranked = rank(
+        candidates,
+        &*ranker(query.ranking),
+        query.limit.unwrap_or(20) as usize,
+    );
+    let items = ranked
+        .into_iter()
+        .filter_map(|x| {
+            let mut project = projects.get(&x.project_id)?.clone();
+            use_preferred_images(&mut project, &req);
+
+            Some(FeedItem {
+                source: x.source,
+                project,
+                version_id: x.version_id.map(|x| x.into()),
+                date: x.date,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(items))
+}
+
+/// Whether a project supports any of the loaders and any of the game versions, where given
+fn supports(
+    project: &Project,
+    loaders: Option<&[String]>,
+    game_versions: Option<&[String]>,
+) -> bool {
+    let loaders_match = loaders.map_or(true, |loaders| {
+        project.loaders.iter().any(|x| loaders.contains(x))
+    });
+    let game_versions_match = game_versions.map_or(true, |game_versions| {
+        project.fields.get("game_versions").map_or(false, |values| {
+            values
+                .iter()
+                .filter_map(|x| x.as_str())
+                .any(|x| game_versions.iter().any(|y| y == x))
+        })
+    });
+
+    loaders_match && game_versions_match
+}
+
+fn searchable_statuses() -> Vec<String> {
+    ProjectStatus::iterator()
+        .filter(|x| x.is_searchable())
+        .map(|x| x.to_string())
+        .collect()
+}
+
+/// Recent listed versions of the projects the user follows
+async fn followed_updates(user_id: UserId, pool: &PgPool) -> Result<Vec<FeedCandidate>, ApiError> {
+    let candidates = sqlx::query!(
+        "
+        SELECT v.id, v.mod_id, v.date_published
+        FROM versions v
+        INNER JOIN mod_follows mf ON mf.mod_id = v.mod_id AND mf.follower_id = $1
+        WHERE v.date_published > $2 AND v.status = ANY($3)
+        ORDER BY v.date_published DESC
+        LIMIT $4
+        ",
+        user_id as UserId,
+        Utc::now() - Duration::days(HISTORY_DAYS),
+        &*VersionStatus::iterator()
+            .filter(|x| x.is_listed())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>(),
+        CANDIDATES_PER_SOURCE as i64,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| FeedCandidate {
+        source: FeedSource::FollowedUpdate,
+        project_id: ProjectId(r.mod_id),
+        version_id: Some(VersionId(r.id)),
+        signal: 1.0,
+        date: r.date_published,
+    })
+    .collect();
+
+    Ok(candidates)
+}
+
+/// Projects sharing the most categories with the projects the user downloaded most recently,
+/// other than those projects and the ones the user follows
+async fn similar_projects(
+    user_id: UserId,
+    pool: &PgPool,
+    clickhouse: Arc<clickhouse::Client>,
+) -> Result<Vec<FeedCandidate>, ApiError> {
+    let downloaded = crate::clickhouse::fetch_user_downloaded_projects(
+        user_id.0 as u64,
+        Utc::now() - Duration::days(HISTORY_DAYS),
+        DOWNLOADED_PROJECTS,
+        clickhouse,
+    )
+    .await?
+    .into_iter()
+    .map(|x| x.id as i64)
+    .collect::<Vec<_>>();
+    if downloaded.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let rows = sqlx::query!(
+        "
+        SELECT m.id, m.updated, COUNT(DISTINCT mc.joining_category_id) shared
+        FROM mods_categories mc
+        INNER JOIN mods m ON m.id = mc.joining_mod_id AND m.status = ANY($2)
+        WHERE mc.joining_category_id IN (
+            SELECT joining_category_id FROM mods_categories WHERE joining_mod_id = ANY($1)
+        )
+        AND m.id <> ALL($1)
+        AND NOT EXISTS(
+            SELECT 1 FROM mod_follows mf WHERE mf.mod_id = m.id AND mf.follower_id = $3
+        )
+        GROUP BY m.id
+        ORDER BY shared DESC, m.downloads DESC
+        LIMIT $4
+        ",
+        &downloaded,
+        &*searchable_statuses(),
+        user_id as UserId,
+        CANDIDATES_PER_SOURCE as i64,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let most_shared = rows
+        .iter()
+        .filter_map(|x| x.shared)
+        .max()
+        .unwrap_or(1)
+        .max(1);
+
+    Ok(rows
+        .into_iter()
+        .map(|r| FeedCandidate {
+            source: FeedSource::Similar,
+            project_id: ProjectId(r.id),
+            version_id: None,
+            signal: r.shared.unwrap_or(0) as f64 / most_shared as f64,
+            date: r.updated,
+        })
+        .collect())
+}
+
+/// The public projects downloaded most lately
+async fn trending_projects(
+    pool: &PgPool,
+    clickhouse: Arc<clickhouse::Client>,
+) -> Result<Vec<FeedCandidate>, ApiError> {
+    let totals = crate::clickhouse::fetch_top_downloaded_projects(
+        Utc::now() - Duration::days(TRENDING_DAYS),
+        CANDIDATES_PER_SOURCE,
+        clickhouse,
+    )
+    .await?
+    .into_iter()
+    .map(|x| (x.id as i64, x.total))
+    .collect::<HashMap<_, _>>();
+    if totals.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let most_downloaded = totals.values().copied().max().unwrap_or(1).max(1);
+    let candidates = sqlx::query!(
+        "
+        SELECT id, updated
+        FROM mods
+        WHERE id = ANY($1) AND status = ANY($2)
+        ",
+        &totals.keys().copied().collect::<Vec<_>>(),
+        &*searchable_statuses(),
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| FeedCandidate {
+        source: FeedSource::Trending,
+        project_id: ProjectId(r.id),
+        version_id: None,
+        signal: totals.get(&r.id).copied().unwrap_or(0) as f64 / most_downloaded as f64,
+        date: r.updated,
+    })
+    .collect();
+
+    Ok(candidates)
+}
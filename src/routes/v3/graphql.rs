@@ -0,0 +1,653 @@
+use crate::auth::checks::{filter_visible_projects, filter_visible_versions};
+use crate::auth::get_user_from_headers;
+use crate::database::models::project_item::QueryProject;
+use crate::database::models::version_item::QueryVersion;
+use crate::database::models::{
+    DatabaseError, Project as DBProject, ProjectId, TeamId, TeamMember as DBTeamMember,
+    User as DBUser, UserId, Version as DBVersion, VersionId,
+};
+use crate::database::redis::RedisPool;
+use crate::models::ids::base62_impl::parse_base62;
+use crate::models::pats::Scopes;
+use crate::models::projects::{Dependency, Project, SearchRequest, Version, VersionFile};
+use crate::models::teams::TeamMember;
+use crate::models::users::User;
+use crate::queue::session::AuthQueue;
+use crate::search::{search_for_project, SearchConfig};
+use actix_web::{web, HttpRequest};
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Json, Object, Result, Schema, SimpleObject,
+};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// How deeply queries may nest, so one request cannot walk the whole graph
+const MAX_DEPTH: usize = 12;
+/// How many fields a query may resolve in total
+const MAX_COMPLEXITY: usize = 1000;
+
+type LabrinthSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+lazy_static! {
+    static ref SCHEMA: LabrinthSchema = Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish();
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("graphql", web::post().to(graphql));
+    cfg.route("graphql", web::get().to(graphql));
+}
+
+/// Runs a GraphQL query. Objects are only returned if the requesting user could see them through
+/// the REST routes, and nested objects are loaded in batches through the same caches
+pub async fn graphql(
+    req: HttpRequest,
+    request: GraphQLRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    config: web::Data<SearchConfig>,
+    session_queue: web::Data<AuthQueue>,
+) -> GraphQLResponse {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PROJECT_READ, Scopes::VERSION_READ]),
+    )
+    .await
+    .map(|x| x.1)
+    .ok();
+
+    let loader = DataLoader::new(
+        DatabaseLoader {
+            pool: pool.clone(),
+            redis: redis.clone(),
+        },
+        tokio::spawn,
+    );
+
+    let request = request
+        .into_inner()
+        .data(Viewer(user))
+        .data(loader)
+        .data(pool)
+        .data(redis)
+        .data(config);
+
+    SCHEMA.execute(request).await.into()
+}
+
+/// The user making the request, if any
+struct Viewer(Option<User>);
+
+/// Loads projects, versions, users and team members in batches, through the Redis caches
+struct DatabaseLoader {
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+}
+
+#[async_trait::async_trait]
+impl Loader<ProjectId> for DatabaseLoader {
+    type Value = QueryProject;
+    type Error = Arc<DatabaseError>;
+
+    async fn load(
+        &self,
+        keys: &[ProjectId],
+    ) -> Result<HashMap<ProjectId, Self::Value>, Self::Error> {
+        Ok(DBProject::get_many_ids(keys, &**self.pool, &self.redis)
+            .await?
+            .into_iter()
+            .map(|x| (x.inner.id, x))
+            .collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<VersionId> for DatabaseLoader {
+    type Value = QueryVersion;
+    type Error = Arc<DatabaseError>;
+
+    async fn load(
+        &self,
+        keys: &[VersionId],
+    ) -> Result<HashMap<VersionId, Self::Value>, Self::Error> {
+        Ok(DBVersion::get_many(keys, &**self.pool, &self.redis)
+            .await?
+            .into_iter()
+            .map(|x| (x.inner.id, x))
+            .collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<UserId> for DatabaseLoader {
+    type Value = DBUser;
+    type Error = Arc<DatabaseError>;
+
+    async fn load(&self, keys: &[UserId]) -> Result<HashMap<UserId, Self::Value>, Self::Error> {
+        Ok(DBUser::get_many_ids(keys, &**self.pool, &self.redis)
+            .await?
+            .into_iter()
+            .map(|x| (x.id, x))
+            .collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Loader<TeamId> for DatabaseLoader {
+    type Value = Vec<DBTeamMember>;
+    type Error = Arc<DatabaseError>;
+
+    async fn load(&self, keys: &[TeamId]) -> Result<HashMap<TeamId, Self::Value>, Self::Error> {
+        let mut teams: HashMap<TeamId, Vec<DBTeamMember>> = HashMap::new();
+        for member in DBTeamMember::get_from_team_full_many(keys, &**self.pool, &self.redis).await?
+        {
+            teams.entry(member.team_id).or_default().push(member);
+        }
+
+        Ok(teams)
+    }
+}
+
+fn parse_id(id: &str) -> Option<i64> {
+    parse_base62(id).ok().map(|x| x as i64)
+}
+
+async fn visible_projects(
+    ctx: &Context<'_>,
+    projects: Vec<QueryProject>,
+) -> Result<Vec<GqlProject>> {
+    let pool = ctx.data_unchecked::<web::Data<PgPool>>();
+    let viewer = ctx.data_unchecked::<Viewer>();
+
+    Ok(filter_visible_projects(projects, &viewer.0, pool)
+        .await?
+        .into_iter()
+        .map(GqlProject)
+        .collect())
+}
+
+async fn visible_versions(
+    ctx: &Context<'_>,
+    versions: Vec<QueryVersion>,
+) -> Result<Vec<GqlVersion>> {
+    let pool = ctx.data_unchecked::<web::Data<PgPool>>();
+    let redis = ctx.data_unchecked::<web::Data<RedisPool>>();
+    let viewer = ctx.data_unchecked::<Viewer>();
+
+    Ok(filter_visible_versions(versions, &viewer.0, pool, redis)
+        .await?
+        .into_iter()
+        .map(GqlVersion)
+        .collect())
+}
+
+async fn load_projects(ctx: &Context<'_>, ids: Vec<ProjectId>) -> Result<Vec<GqlProject>> {
+    let loader = ctx.data_unchecked::<DataLoader<DatabaseLoader>>();
+    let projects = loader.load_many(ids).await?.into_values().collect();
+
+    visible_projects(ctx, projects).await
+}
+
+async fn load_versions(ctx: &Context<'_>, ids: Vec<VersionId>) -> Result<Vec<GqlVersion>> {
+    let loader = ctx.data_unchecked::<DataLoader<DatabaseLoader>>();
+    let versions = loader.load_many(ids).await?.into_values().collect();
+
+    let mut versions = visible_versions(ctx, versions).await?;
+    versions.sort_by(|a, b| b.0.date_published.cmp(&a.0.date_published));
+    Ok(versions)
+}
+
+async fn load_user(ctx: &Context<'_>, id: UserId) -> Result<Option<GqlUser>> {
+    let loader = ctx.data_unchecked::<DataLoader<DatabaseLoader>>();
+
+    Ok(loader.load_one(id).await?.map(|x| GqlUser(x.into())))
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// A project by its ID or slug
+    async fn project(&self, ctx: &Context<'_>, id: String) -> Result<Option<GqlProject>> {
+        let pool = ctx.data_unchecked::<web::Data<PgPool>>();
+        let redis = ctx.data_unchecked::<web::Data<RedisPool>>();
+
+        let projects = DBProject::get(&id, &***pool, redis).await?;
+        Ok(visible_projects(ctx, projects.into_iter().collect())
+            .await?
+            .pop())
+    }
+
+    /// Projects by their IDs or slugs. Projects which do not exist are left out
+    async fn projects(&self, ctx: &Context<'_>, ids: Vec<String>) -> Result<Vec<GqlProject>> {
+        let pool = ctx.data_unchecked::<web::Data<PgPool>>();
+        let redis = ctx.data_unchecked::<web::Data<RedisPool>>();
+
+        let projects = DBProject::get_many(&ids, &***pool, redis).await?;
+        visible_projects(ctx, projects).await
+    }
+
+    /// A version by its ID
+    async fn version(&self, ctx: &Context<'_>, id: String) -> Result<Option<GqlVersion>> {
+        let Some(id) = parse_id(&id) else {
+            return Ok(None);
+        };
+
+        Ok(load_versions(ctx, vec![VersionId(id)]).await?.pop())
+    }
+
+    /// Versions by their IDs. Versions which do not exist are left out
+    async fn versions(&self, ctx: &Context<'_>, ids: Vec<String>) -> Result<Vec<GqlVersion>> {
+        load_versions(
+            ctx,
+            ids.iter()
+                .filter_map(|x| parse_id(x))
+                .map(VersionId)
+                .collect(),
+        )
+        .await
+    }
+
+    /// A user by their ID or username
+    async fn user(&self, ctx: &Context<'_>, id: String) -> Result<Option<GqlUser>> {
+        let pool = ctx.data_unchecked::<web::Data<PgPool>>();
+        let redis = ctx.data_unchecked::<web::Data<RedisPool>>();
+
+        Ok(DBUser::get(&id, &***pool, redis)
+            .await?
+            .map(|x| GqlUser(x.into())))
+    }
+
+    /// Users by their IDs or usernames. Users which do not exist are left out
+    async fn users(&self, ctx: &Context<'_>, ids: Vec<String>) -> Result<Vec<GqlUser>> {
+        let pool = ctx.data_unchecked::<web::Data<PgPool>>();
+        let redis = ctx.data_unchecked::<web::Data<RedisPool>>();
+
+        Ok(DBUser::get_many(&ids, &***pool, redis)
+            .await?
+            .into_iter()
+            .map(|x| GqlUser(x.into()))
+            .collect())
+    }
+
+    /// Searches for projects, taking the same parameters as the REST search route
+    async fn search(
+        &self,
+        ctx: &Context<'_>,
+        query: Option<String>,
+        facets: Option<String>,
+        index: Option<String>,
+        offset: Option<u32>,
+        limit: Option<u32>,
+    ) -> Result<GqlSearchResults> {
+        let config = ctx.data_unchecked::<web::Data<SearchConfig>>();
+        let redis = ctx.data_unchecked::<web::Data<RedisPool>>();
+
+        let request = SearchRequest {
+            query,
+            offset: offset.map(|x| x.to_string()),
+            index,
+            limit: limit.map(|x| x.to_string()),
+            new_filters: None,
+            facets,
+            filters: None,
+            version: None,
+        };
+        let results = search_for_project(&request, config, redis).await?;
+
+        Ok(GqlSearchResults {
+            hits: results
+                .hits
+                .into_iter()
+                .filter_map(Project::from_search)
+                .map(GqlProject)
+                .collect(),
+            page: results.page,
+            hits_per_page: results.hits_per_page,
+            total_hits: results.total_hits,
+        })
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "SearchResults")]
+pub struct GqlSearchResults {
+    hits: Vec<GqlProject>,
+    page: usize,
+    hits_per_page: usize,
+    total_hits: usize,
+}
+
+pub struct GqlProject(Project);
+
+#[Object(name = "Project")]
+impl GqlProject {
+    async fn id(&self) -> String {
+        self.0.id.to_string()
+    }
+
+    async fn slug(&self) -> Option<&str> {
+        self.0.slug.as_deref()
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn summary(&self) -> &str {
+        &self.0.summary
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    async fn project_types(&self) -> &[String] {
+        &self.0.project_types
+    }
+
+    async fn games(&self) -> &[String] {
+        &self.0.games
+    }
+
+    async fn categories(&self) -> &[String] {
+        &self.0.categories
+    }
+
+    async fn additional_categories(&self) -> &[String] {
+        &self.0.additional_categories
+    }
+
+    async fn loaders(&self) -> &[String] {
+        &self.0.loaders
+    }
+
+    async fn status(&self) -> &str {
+        self.0.status.as_str()
+    }
+
+    async fn license(&self) -> &str {
+        &self.0.license.id
+    }
+
+    async fn downloads(&self) -> u32 {
+        self.0.downloads
+    }
+
+    async fn followers(&self) -> u32 {
+        self.0.followers
+    }
+
+    async fn icon_url(&self) -> Option<&str> {
+        self.0.icon_url.as_deref()
+    }
+
+    async fn color(&self) -> Option<u32> {
+        self.0.color
+    }
+
+    async fn published(&self) -> DateTime<Utc> {
+        self.0.published
+    }
+
+    async fn updated(&self) -> DateTime<Utc> {
+        self.0.updated
+    }
+
+    async fn organization_id(&self) -> Option<String> {
+        self.0.organization.map(|x| x.to_string())
+    }
+
+    /// Loader fields aggregated across the project's versions
+    async fn fields(&self) -> Json<HashMap<String, Vec<serde_json::Value>>> {
+        Json(self.0.fields.clone())
+    }
+
+    /// The versions of the project the requesting user can see, newest first
+    async fn versions(&self, ctx: &Context<'_>) -> Result<Vec<GqlVersion>> {
+        load_versions(
+            ctx,
+            self.0
+                .versions
+                .iter()
+                .map(|x| VersionId::from(*x))
+                .collect(),
+        )
+        .await
+    }
+
+    /// The accepted members of the project's team. Invites and permissions are only shown
+    /// through the REST team routes
+    async fn members(&self, ctx: &Context<'_>) -> Result<Vec<GqlTeamMember>> {
+        let loader = ctx.data_unchecked::<DataLoader<DatabaseLoader>>();
+
+        let members = loader
+            .load_one(TeamId::from(self.0.team_id))
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|x| x.accepted)
+            .collect::<Vec<_>>();
+        let users = loader.load_many(members.iter().map(|x| x.user_id)).await?;
+
+        let mut members = members
+            .into_iter()
+            .filter_map(|member| {
+                users
+                    .get(&member.user_id)
+                    .map(|user| GqlTeamMember(TeamMember::from(member, user.clone(), true)))
+            })
+            .collect::<Vec<_>>();
+        members.sort_by_key(|x| x.0.ordering);
+        Ok(members)
+    }
+}
+
+pub struct GqlVersion(Version);
+
+#[Object(name = "Version")]
+impl GqlVersion {
+    async fn id(&self) -> String {
+        self.0.id.to_string()
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn version_number(&self) -> &str {
+        &self.0.version_number
+    }
+
+    async fn changelog(&self) -> &str {
+        &self.0.changelog
+    }
+
+    async fn featured(&self) -> bool {
+        self.0.featured
+    }
+
+    async fn version_type(&self) -> &str {
+        self.0.version_type.as_str()
+    }
+
+    async fn status(&self) -> &str {
+        self.0.status.as_str()
+    }
+
+    async fn date_published(&self) -> DateTime<Utc> {
+        self.0.date_published
+    }
+
+    async fn downloads(&self) -> u32 {
+        self.0.downloads
+    }
+
+    async fn loaders(&self) -> Vec<&str> {
+        self.0.loaders.iter().map(|x| x.0.as_str()).collect()
+    }
+
+    async fn project_types(&self) -> &[String] {
+        &self.0.project_types
+    }
+
+    async fn games(&self) -> &[String] {
+        &self.0.games
+    }
+
+    /// Loader specific fields, such as the game versions the version supports
+    async fn fields(&self) -> Json<HashMap<String, serde_json::Value>> {
+        Json(self.0.fields.clone())
+    }
+
+    async fn files(&self) -> Vec<GqlVersionFile> {
+        self.0.files.iter().cloned().map(GqlVersionFile).collect()
+    }
+
+    async fn dependencies(&self) -> Vec<GqlDependency> {
+        self.0
+            .dependencies
+            .iter()
+            .cloned()
+            .map(GqlDependency)
+            .collect()
+    }
+
+    async fn project(&self, ctx: &Context<'_>) -> Result<Option<GqlProject>> {
+        Ok(load_projects(ctx, vec![self.0.project_id.into()])
+            .await?
+            .pop())
+    }
+
+    async fn author(&self, ctx: &Context<'_>) -> Result<Option<GqlUser>> {
+        load_user(ctx, self.0.author_id.into()).await
+    }
+}
+
+pub struct GqlVersionFile(VersionFile);
+
+#[Object(name = "VersionFile")]
+impl GqlVersionFile {
+    async fn url(&self) -> &str {
+        &self.0.url
+    }
+
+    async fn filename(&self) -> &str {
+        &self.0.filename
+    }
+
+    async fn primary(&self) -> bool {
+        self.0.primary
+    }
+
+    async fn size(&self) -> u32 {
+        self.0.size
+    }
+
+    async fn file_type(&self) -> Option<&str> {
+        self.0.file_type.as_ref().map(|x| x.as_str())
+    }
+
+    /// The hashes of the file, keyed by algorithm
+    async fn hashes(&self) -> Json<HashMap<String, String>> {
+        Json(self.0.hashes.clone())
+    }
+}
+
+pub struct GqlDependency(Dependency);
+
+#[Object(name = "Dependency")]
+impl GqlDependency {
+    async fn dependency_type(&self) -> &str {
+        self.0.dependency_type.as_str()
+    }
+
+    async fn file_name(&self) -> Option<&str> {
+        self.0.file_name.as_deref()
+    }
+
+    async fn project(&self, ctx: &Context<'_>) -> Result<Option<GqlProject>> {
+        let Some(project_id) = self.0.project_id else {
+            return Ok(None);
+        };
+
+        Ok(load_projects(ctx, vec![project_id.into()]).await?.pop())
+    }
+
+    async fn version(&self, ctx: &Context<'_>) -> Result<Option<GqlVersion>> {
+        let Some(version_id) = self.0.version_id else {
+            return Ok(None);
+        };
+
+        Ok(load_versions(ctx, vec![version_id.into()]).await?.pop())
+    }
+}
+
+pub struct GqlUser(User);
+
+#[Object(name = "User")]
+impl GqlUser {
+    async fn id(&self) -> String {
+        self.0.id.to_string()
+    }
+
+    async fn username(&self) -> &str {
+        &self.0.username
+    }
+
+    async fn name(&self) -> Option<&str> {
+        self.0.name.as_deref()
+    }
+
+    async fn avatar_url(&self) -> Option<&str> {
+        self.0.avatar_url.as_deref()
+    }
+
+    async fn bio(&self) -> Option<&str> {
+        self.0.bio.as_deref()
+    }
+
+    async fn created(&self) -> DateTime<Utc> {
+        self.0.created
+    }
+
+    async fn role(&self) -> &str {
+        self.0.role.as_str()
+    }
+
+    /// The projects the user is a member of which the requesting user can see
+    async fn projects(&self, ctx: &Context<'_>) -> Result<Vec<GqlProject>> {
+        let pool = ctx.data_unchecked::<web::Data<PgPool>>();
+        let redis = ctx.data_unchecked::<web::Data<RedisPool>>();
+
+        let project_ids = DBUser::get_projects(self.0.id.into(), &***pool, redis).await?;
+        load_projects(ctx, project_ids).await
+    }
+}
+
+pub struct GqlTeamMember(TeamMember);
+
+#[Object(name = "TeamMember")]
+impl GqlTeamMember {
+    async fn role(&self) -> &str {
+        &self.0.role
+    }
+
+    async fn is_owner(&self) -> bool {
+        self.0.is_owner
+    }
+
+    async fn user(&self) -> GqlUser {
+        GqlUser(self.0.user.clone())
+    }
+}
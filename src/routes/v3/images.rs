@@ -7,12 +7,15 @@ use crate::database::models::{project_item, report_item, thread_item, version_it
 use crate::database::redis::RedisPool;
 use crate::file_hosting::FileHost;
 use crate::models::ids::{ThreadMessageId, VersionId};
-use crate::models::images::{Image, ImageContext};
+use crate::models::images::{Image, ImageContext, ImageId};
 use crate::models::reports::ReportId;
 use crate::queue::session::AuthQueue;
 use crate::routes::ApiError;
+use crate::util::img;
 use crate::util::routes::read_from_payload;
 use actix_web::{web, HttpRequest, HttpResponse};
+use itertools::Itertools;
+use log::warn;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
@@ -20,6 +23,7 @@ use super::threads::is_authorized_thread;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.route("image", web::post().to(images_add));
+    cfg.route("image/{id}/thumb", web::get().to(image_thumbnail));
 }
 
 #[derive(Serialize, Deserialize)]
@@ -46,9 +50,19 @@ pub async fn images_add(
     redis: web::Data<RedisPool>,
     session_queue: web::Data<AuthQueue>,
 ) -> Result<HttpResponse, ApiError> {
-    if let Some(content_type) = crate::util::ext::get_image_content_type(&data.ext) {
-        let mut context = ImageContext::from_str(&data.context, None);
+    let mut context = ImageContext::from_str(&data.context, None);
 
+    // Thread messages may also carry log files, as moderation discussions often need crash logs
+    let attachment_type =
+        if let Some(content_type) = crate::util::ext::get_image_content_type(&data.ext) {
+            Some((content_type, false))
+        } else if let ImageContext::ThreadMessage { .. } = context {
+            crate::util::ext::get_log_content_type(&data.ext).map(|x| (x, true))
+        } else {
+            None
+        };
+
+    if let Some((content_type, is_log)) = attachment_type {
         let scopes = vec![context.relevant_scope()];
 
         let cdn_url = dotenvy::var("CDN_URL")?;
@@ -165,8 +179,35 @@ pub async fn images_add(
         }
 
         // Upload the image to the file host
-        let bytes =
-            read_from_payload(&mut payload, 1_048_576, "Icons must be smaller than 1MiB").await?;
+        let bytes = if is_log {
+            let bytes = read_from_payload(
+                &mut payload,
+                5_242_880,
+                "Log files must be smaller than 5MiB",
+            )
+            .await?;
+
+            if std::str::from_utf8(&bytes).is_err() {
+                return Err(ApiError::InvalidInput(
+                    "Log files must be plain UTF-8 text!".to_string(),
+                ));
+            }
+
+            bytes
+        } else {
+            let bytes =
+                read_from_payload(&mut payload, 1_048_576, "Icons must be smaller than 1MiB")
+                    .await?;
+
+            if image::guess_format(&bytes).is_err() {
+                return Err(ApiError::InvalidInput(
+                    "The specified file is not an image!".to_string(),
+                ));
+            }
+
+            bytes
+        };
+        crate::util::magic::check_content(&bytes, &data.ext, content_type)?;
 
         let hash = sha1::Sha1::from(&bytes).hexdigest();
         let upload_data = file_host
@@ -237,7 +278,86 @@ pub async fn images_add(
         Ok(HttpResponse::Ok().json(image))
     } else {
         Err(ApiError::InvalidInput(
-            "The specified file is not an image!".to_string(),
+            "The specified file is not an image! Thread messages also accept .log and .txt files"
+                .to_string(),
         ))
     }
 }
+
+#[derive(Deserialize)]
+pub struct ThumbnailQuery {
+    /// The width of the thumbnail, which must be one of the supported sizes
+    pub size: u32,
+}
+
+/// Redirects to a thumbnail of an uploaded or gallery image, generating it the first time it
+/// is requested. Like the image's own URL, the id is only known to those who can see the image
+pub async fn image_thumbnail(
+    info: web::Path<(ImageId,)>,
+    web::Query(query): web::Query<ThumbnailQuery>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
+) -> Result<HttpResponse, ApiError> {
+    if !img::THUMBNAIL_SIZES.contains(&query.size) {
+        return Err(ApiError::InvalidInput(format!(
+            "Thumbnails can only be {} pixels wide",
+            img::THUMBNAIL_SIZES.iter().join(", ")
+        )));
+    }
+
+    let id: database::models::ImageId = info.into_inner().0.into();
+    let size = query.size;
+
+    if let Some(url) = database::models::Image::get_thumbnail_url(id, size, &redis).await? {
+        return Ok(redirect(&url));
+    }
+
+    let original_url =
+        if let Some(image) = database::models::Image::get(id, &**pool, &redis).await? {
+            image.url
+        } else if let Some(url) = project_item::GalleryItem::get_url(id, &**pool).await? {
+            url
+        } else {
+            return Err(ApiError::NotFound);
+        };
+
+    let cdn_url = dotenvy::var("CDN_URL")?;
+    let original_path = original_url
+        .split(&format!("{cdn_url}/"))
+        .nth(1)
+        .ok_or(ApiError::NotFound)?;
+    let data = file_host.download_file(original_path).await?;
+
+    // Thumbnails are an optimization, so files which can't be scaled down (such as thread
+    // message logs) are served as they are
+    let thumbnail = web::block(move || img::create_thumbnail(&data, size))
+        .await
+        .map_err(|err| err.to_string())
+        .and_then(|x| x.map_err(|err| err.to_string()));
+    let thumbnail = match thumbnail {
+        Ok(thumbnail) => thumbnail,
+        Err(err) => {
+            warn!("Failed to generate thumbnail for {original_path}: {err}");
+            return Ok(redirect(&original_url));
+        }
+    };
+
+    let upload_data = file_host
+        .upload_file(
+            "image/webp",
+            &img::thumbnail_path(original_path, size),
+            bytes::Bytes::from(thumbnail),
+        )
+        .await?;
+    let url = format!("{}/{}", cdn_url, upload_data.file_name);
+    database::models::Image::set_thumbnail_url(id, size, &url, &redis).await?;
+
+    Ok(redirect(&url))
+}
+
+fn redirect(url: &str) -> HttpResponse {
+    HttpResponse::TemporaryRedirect()
+        .append_header(("location", url))
+        .body("")
+}
@@ -0,0 +1,37 @@
+use crate::auth::get_user_from_headers;
+use crate::database::models::job_item::Job;
+use crate::database::models::UserId;
+use crate::database::redis::RedisPool;
+use crate::models::jobs::UserJob;
+use crate::queue::session::AuthQueue;
+use crate::routes::ApiError;
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("jobs/{id}", web::get().to(job_get));
+}
+
+/// Gets a job started by a long operation, such as an export, which is polled until it is
+/// completed or dead. Jobs can only be seen by the user who started them and admins
+pub async fn job_get(
+    req: HttpRequest,
+    info: web::Path<(i64,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &redis, &session_queue, None)
+        .await?
+        .1;
+
+    let job = Job::get(info.into_inner().0, &**pool)
+        .await?
+        .filter(|x| {
+            x.user_id.is_some()
+                && (x.user_id == Some(UserId::from(user.id)) || user.role.is_admin())
+        })
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(UserJob::from(job)))
+}
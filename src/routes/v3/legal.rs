@@ -0,0 +1,119 @@
+use crate::auth::get_user_from_headers;
+use crate::database::models::legal_document_item::LegalDocument;
+use crate::database::redis::RedisPool;
+use crate::models::legal::LegalAcceptance;
+use crate::models::pats::Scopes;
+use crate::queue::session::AuthQueue;
+use crate::routes::ApiError;
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("legal")
+            .route("", web::get().to(legal_get))
+            .route("pending", web::get().to(legal_pending))
+            .route("acceptances", web::get().to(legal_acceptances))
+            .route("{id}/accept", web::post().to(legal_accept)),
+    );
+}
+
+/// Gets the latest version of the terms, privacy policy and rules
+pub async fn legal_get(
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    let documents = LegalDocument::get_latest(&**pool, &redis)
+        .await?
+        .into_iter()
+        .map(crate::models::legal::LegalDocument::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(documents))
+}
+
+/// Gets the documents the user has to accept before they can make changes again
+pub async fn legal_pending(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::USER_READ]),
+    )
+    .await?
+    .1;
+
+    let documents = LegalDocument::get_pending(user.id.into(), user.created, &**pool, &redis)
+        .await?
+        .into_iter()
+        .map(crate::models::legal::LegalDocument::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(documents))
+}
+
+/// Gets the versions of documents the user accepted, and when
+pub async fn legal_acceptances(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::USER_READ]),
+    )
+    .await?
+    .1;
+
+    let acceptances = LegalDocument::get_acceptances(user.id.into(), &**pool)
+        .await?
+        .into_iter()
+        .map(LegalAcceptance::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(acceptances))
+}
+
+/// Accepts the latest version of a document. Older versions can't be accepted once a newer one
+/// is published
+pub async fn legal_accept(
+    req: HttpRequest,
+    info: web::Path<(i64,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::USER_WRITE]),
+    )
+    .await?
+    .1;
+
+    let document = LegalDocument::get(info.into_inner().0, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let latest = LegalDocument::get_latest(&**pool, &redis).await?;
+    if !latest.iter().any(|x| x.id == document.id) {
+        return Err(ApiError::InvalidInput(
+            "A newer version of this document has been published!".to_string(),
+        ));
+    }
+
+    LegalDocument::accept(user.id.into(), document.id, &**pool).await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
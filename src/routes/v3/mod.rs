@@ -1,50 +1,89 @@
 pub use super::ApiError;
 use crate::util::cors::default_cors;
+use crate::util::etag::conditional_get;
+use crate::util::fields::sparse_fieldsets;
+use crate::util::legal::LegalAcceptanceGate;
 use actix_web::{web, HttpResponse};
 use serde_json::json;
 
 pub mod analytics_get;
 pub mod collections;
+pub mod discord;
+pub mod feature_flags;
+pub mod featured;
+pub mod feed;
+pub mod graphql;
 pub mod images;
+pub mod jobs;
+pub mod legal;
 pub mod moderation;
 pub mod notifications;
+pub mod openapi;
 pub mod organizations;
+pub mod pats;
 pub mod payouts;
+pub mod permissions;
 pub mod project_creation;
+pub mod project_import;
 pub mod projects;
+pub mod rate_limit;
 pub mod reports;
+pub mod shortlinks;
 pub mod statistics;
 pub mod tags;
 pub mod teams;
 pub mod threads;
+pub mod uploads;
 pub mod users;
 pub mod version_creation;
 pub mod version_file;
 pub mod versions;
+pub mod webhooks;
 
 pub mod oauth_clients;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("v3")
+            // Inside `default_cors`, so refused changes can still be read by browsers
+            .wrap(LegalAcceptanceGate)
             .wrap(default_cors())
+            // Inside `conditional_get`, so ETags are of the pruned responses
+            .wrap_fn(sparse_fieldsets)
+            .wrap_fn(conditional_get)
             .configure(analytics_get::config)
             .configure(collections::config)
+            .configure(discord::config)
+            .configure(feature_flags::config)
+            .configure(featured::config)
+            .configure(feed::config)
+            .configure(graphql::config)
             .configure(images::config)
+            .configure(jobs::config)
+            .configure(legal::config)
             .configure(moderation::config)
             .configure(notifications::config)
+            .configure(openapi::config)
             .configure(organizations::config)
+            .configure(pats::config)
+            .configure(permissions::config)
             .configure(project_creation::config)
+            .configure(project_import::config)
+            // Before `projects`, whose scope would otherwise take the project routes
+            .configure(shortlinks::config)
             .configure(projects::config)
+            .configure(rate_limit::config)
             .configure(reports::config)
             .configure(statistics::config)
             .configure(tags::config)
             .configure(teams::config)
             .configure(threads::config)
+            .configure(uploads::config)
             .configure(users::config)
             .configure(version_file::config)
             .configure(payouts::config)
-            .configure(versions::config),
+            .configure(versions::config)
+            .configure(webhooks::config),
     );
 }
 
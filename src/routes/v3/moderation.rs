@@ -1,27 +1,104 @@
 use super::ApiError;
 use crate::database;
+use crate::database::models::image_flag_item::ImageFlag as DBImageFlag;
+use crate::database::models::moderation_claim_item::ModerationClaim;
+use crate::database::models::moderation_template_item;
+use crate::database::models::project_item::SimilarImage as DBSimilarImage;
+use crate::database::models::spam_flag_item::SpamFlag as DBSpamFlag;
 use crate::database::redis::RedisPool;
-use crate::models::projects::ProjectStatus;
+use crate::models::ids::ProjectId;
+use crate::models::moderation::{
+    ImageFlag, ModerationEvent, ModerationTemplate, SimilarImage, SpamFlag, SpamScore,
+};
+use crate::models::projects::{Project, ProjectStatus};
+use crate::queue::moderation::ModerationEvents;
 use crate::queue::session::AuthQueue;
+use crate::util::cursor::Cursor;
+use crate::util::img::SIMILAR_IMAGE_DISTANCE;
 use crate::{auth::check_is_moderator_from_headers, models::pats::Scopes};
+use actix_web::http::header::{CacheControl, CacheDirective, ContentEncoding};
 use actix_web::{web, HttpRequest, HttpResponse};
-use serde::Deserialize;
+use bytes::Bytes;
+use chrono::{Duration, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::collections::HashMap;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_stream::wrappers::IntervalStream;
+
+/// How long a moderator holds a project they claimed, unless they claim it again
+const CLAIM_MINUTES: i64 = 30;
+/// The most matches looked up for each image of a project
+const MAX_SIMILAR_IMAGES: i64 = 10;
+/// How often a comment is sent on idle event streams, so proxies do not close them
+const KEEP_ALIVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.route("moderation/projects", web::get().to(get_projects));
+    cfg.route("moderation/events", web::get().to(moderation_events));
+    cfg.route(
+        "moderation/project/{id}/claim",
+        web::post().to(project_claim),
+    );
+    cfg.route(
+        "moderation/project/{id}/claim",
+        web::delete().to(project_unclaim),
+    );
+    cfg.route(
+        "moderation/project/{id}/similar-images",
+        web::get().to(project_similar_images),
+    );
+    cfg.route("moderation/templates", web::get().to(templates_list));
+    cfg.route("moderation/images", web::get().to(image_flags_list));
+    cfg.route(
+        "moderation/image/{id}",
+        web::delete().to(image_flag_resolve),
+    );
+    cfg.route("moderation/spam", web::get().to(spam_flags_list));
+    cfg.route("moderation/spam/{id}", web::delete().to(spam_flag_resolve));
 }
 
 #[derive(Deserialize)]
 pub struct ResultCount {
     #[serde(default = "default_count")]
     pub count: i16,
+    /// The `next_cursor` of the previous page
+    pub cursor: Option<Cursor>,
 }
 
 fn default_count() -> i16 {
     100
 }
 
+/// A page of the moderation queue, longest queued first
+#[derive(Serialize, Deserialize)]
+pub struct ModerationQueuePage {
+    pub projects: Vec<Project>,
+    /// The spam scores of the projects on this page which were flagged as likely spam when
+    /// they were created, by project
+    #[serde(default)]
+    pub spam_scores: HashMap<ProjectId, SpamScore>,
+    /// Pass as `cursor` to get the next page. Absent on the last page
+    pub next_cursor: Option<Cursor>,
+}
+
+/// A page of the image moderation queue, longest flagged first
+#[derive(Serialize, Deserialize)]
+pub struct ImageFlagPage {
+    pub flags: Vec<ImageFlag>,
+    /// Pass as `cursor` to get the next page. Absent on the last page
+    pub next_cursor: Option<Cursor>,
+}
+
+/// A page of the spam moderation queue, longest flagged first
+#[derive(Serialize, Deserialize)]
+pub struct SpamFlagPage {
+    pub flags: Vec<SpamFlag>,
+    /// Pass as `cursor` to get the next page. Absent on the last page
+    pub next_cursor: Option<Cursor>,
+}
+
 pub async fn get_projects(
     req: HttpRequest,
     pool: web::Data<PgPool>,
@@ -40,26 +117,388 @@ pub async fn get_projects(
 
     use futures::stream::TryStreamExt;
 
-    let project_ids = sqlx::query!(
+    let limit = count.count.max(0) as usize;
+    let mut keys = sqlx::query!(
         "
-        SELECT id FROM mods
-        WHERE status = $1
-        ORDER BY queued ASC
-        LIMIT $2;
+        SELECT id, queued FROM mods
+        WHERE status = $1 AND queued IS NOT NULL
+        AND ($2::timestamptz IS NULL OR (queued, id) > ($2, $3))
+        ORDER BY queued ASC, id ASC
+        LIMIT $4;
         ",
         ProjectStatus::Processing.as_str(),
-        count.count as i64
+        count.cursor.map(|x| x.timestamp),
+        count.cursor.map(|x| x.id),
+        // Fetch one extra row to know whether there is a next page
+        limit as i64 + 1,
     )
     .fetch_many(&**pool)
-    .try_filter_map(|e| async { Ok(e.right().map(|m| database::models::ProjectId(m.id))) })
-    .try_collect::<Vec<database::models::ProjectId>>()
+    .try_filter_map(|e| async {
+        Ok(e.right()
+            .and_then(|m| m.queued.map(|queued| Cursor::new(queued, m.id))))
+    })
+    .try_collect::<Vec<Cursor>>()
+    .await?;
+
+    let next_cursor = if keys.len() > limit {
+        keys.truncate(limit);
+        keys.last().copied()
+    } else {
+        None
+    };
+
+    let mut projects = database::Project::get_many_ids(
+        &keys
+            .iter()
+            .map(|x| database::models::ProjectId(x.id))
+            .collect::<Vec<_>>(),
+        &**pool,
+        &redis,
+    )
+    .await?;
+    projects.sort_by_key(|x| (x.inner.queued, x.inner.id.0));
+
+    let spam_scores = DBSpamFlag::get_many_projects(
+        &projects.iter().map(|x| x.inner.id).collect::<Vec<_>>(),
+        &**pool,
+    )
+    .await?
+    .into_iter()
+    .map(|(id, score)| (id.into(), score))
+    .collect();
+
+    Ok(HttpResponse::Ok().json(ModerationQueuePage {
+        projects: projects.into_iter().map(Project::from).collect(),
+        spam_scores,
+        next_cursor,
+    }))
+}
+
+/// Streams changes to the moderation queue as server-sent events, starting with the projects
+/// which are claimed right now. Each event's data is a JSON `ModerationEvent`
+pub async fn moderation_events(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    moderation_events: web::Data<ModerationEvents>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PROJECT_READ]),
+    )
     .await?;
 
-    let projects: Vec<_> = database::Project::get_many_ids(&project_ids, &**pool, &redis)
+    // Subscribed before reading the claims, so no claim made in between is missed
+    let receiver = moderation_events.subscribe();
+    let claims = ModerationClaim::get_active(&**pool)
         .await?
         .into_iter()
-        .map(crate::models::projects::Project::from)
-        .collect();
+        .map(|x| ModerationEvent::Claimed {
+            project_id: x.project_id.into(),
+            user_id: x.user_id.into(),
+            expires: x.expires,
+        })
+        .collect::<Vec<_>>();
+
+    let events = futures::stream::iter(claims).chain(futures::stream::unfold(
+        receiver,
+        |mut receiver| async move {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => ModerationEvent::Lagged,
+                Err(RecvError::Closed) => return None,
+            };
+            Some((event, receiver))
+        },
+    ));
+    let events = events.map(|event| {
+        let data = serde_json::to_string(&event).unwrap_or_default();
+        Bytes::from(format!("data: {data}\n\n"))
+    });
+    let keep_alive = IntervalStream::new(actix_rt::time::interval_at(
+        actix_rt::time::Instant::now() + KEEP_ALIVE_INTERVAL,
+        KEEP_ALIVE_INTERVAL,
+    ))
+    .map(|_| Bytes::from_static(b": keep-alive\n\n"));
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        // Compressed events would be held back until enough of them were written
+        .insert_header(ContentEncoding::Identity)
+        .streaming(futures::stream::select(events, keep_alive).map(Ok::<_, actix_web::Error>)))
+}
+
+/// Claims a project in the queue for the requesting moderator to review, so other moderators
+/// know to leave it. Claims last for half an hour, and claiming a project again extends it
+pub async fn project_claim(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PROJECT_WRITE]),
+    )
+    .await?;
+
+    let project = database::Project::get(&info.into_inner().0, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if project.inner.status != ProjectStatus::Processing {
+        return Err(ApiError::InvalidInput(
+            "Only projects under review can be claimed".to_string(),
+        ));
+    }
+
+    let claimed = ModerationClaim::claim(
+        project.inner.id,
+        user.id.into(),
+        Utc::now() + Duration::minutes(CLAIM_MINUTES),
+        &**pool,
+    )
+    .await?;
+    if !claimed {
+        return Err(ApiError::InvalidInput(
+            "This project is claimed by another moderator".to_string(),
+        ));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Releases the claim on a project, whoever holds it
+pub async fn project_unclaim(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PROJECT_WRITE]),
+    )
+    .await?;
+
+    let project = database::Project::get(&info.into_inner().0, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    match ModerationClaim::remove(project.inner.id, &**pool).await? {
+        Some(()) => Ok(HttpResponse::NoContent().finish()),
+        None => Err(ApiError::NotFound),
+    }
+}
+
+/// Finds the images of other projects which look like the icon or gallery images of a project,
+/// closest first, to spot projects reposting someone else's work
+pub async fn project_similar_images(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PROJECT_READ]),
+    )
+    .await?;
+
+    let project = database::Project::get(&info.into_inner().0, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let images = project
+        .inner
+        .icon_url
+        .iter()
+        .zip(project.inner.icon_perceptual_hash)
+        .chain(
+            project
+                .gallery_items
+                .iter()
+                .filter_map(|x| Some((&x.image_url, x.perceptual_hash?))),
+        );
+
+    let mut similar_images = Vec::new();
+    for (image_url, hash) in images {
+        let matches = DBSimilarImage::get_many(
+            hash,
+            SIMILAR_IMAGE_DISTANCE,
+            project.inner.id,
+            MAX_SIMILAR_IMAGES,
+            &**pool,
+        )
+        .await?;
+
+        similar_images.extend(matches.into_iter().map(|x| SimilarImage {
+            image_url: image_url.clone(),
+            matched_project_id: x.project_id.into(),
+            matched_image_url: x.image_url,
+            matched_icon: x.icon,
+            distance: x.distance,
+        }));
+    }
+    similar_images.sort_by_key(|x| x.distance);
+
+    Ok(HttpResponse::Ok().json(similar_images))
+}
+
+/// Lists the canned responses moderators can post into threads
+pub async fn templates_list(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::THREAD_READ]),
+    )
+    .await?;
+
+    let templates = moderation_template_item::ModerationTemplate::get_all(&**pool)
+        .await?
+        .into_iter()
+        .map(ModerationTemplate::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(templates))
+}
+
+/// Lists the uploaded images the content scanner flagged, longest flagged first
+pub async fn image_flags_list(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    count: web::Query<ResultCount>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::REPORT_READ]),
+    )
+    .await?;
+
+    let limit = count.count.max(0) as usize;
+    // Fetch one extra flag to know whether there is a next page
+    let mut flags = DBImageFlag::get_page(count.cursor, limit as i64 + 1, &**pool).await?;
+
+    let next_cursor = if flags.len() > limit {
+        flags.truncate(limit);
+        flags.last().map(|x| Cursor::new(x.created, x.id))
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(ImageFlagPage {
+        flags: flags.into_iter().map(ImageFlag::from).collect(),
+        next_cursor,
+    }))
+}
+
+/// Takes a flagged image out of the queue once a moderator reviewed it. Removing the image
+/// itself is done through the endpoints of whatever it belongs to
+pub async fn image_flag_resolve(
+    req: HttpRequest,
+    info: web::Path<(i64,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::REPORT_WRITE]),
+    )
+    .await?;
+
+    match DBImageFlag::remove(info.into_inner().0, &**pool).await? {
+        Some(()) => Ok(HttpResponse::NoContent().finish()),
+        None => Err(ApiError::NotFound),
+    }
+}
+
+/// Lists the accounts and projects which scored as likely spam, longest flagged first, with the
+/// signals which made up each score
+pub async fn spam_flags_list(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    count: web::Query<ResultCount>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::REPORT_READ]),
+    )
+    .await?;
+
+    let limit = count.count.max(0) as usize;
+    // Fetch one extra flag to know whether there is a next page
+    let mut flags = DBSpamFlag::get_page(count.cursor, limit as i64 + 1, &**pool).await?;
+
+    let next_cursor = if flags.len() > limit {
+        flags.truncate(limit);
+        flags.last().map(|x| Cursor::new(x.created, x.id))
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(SpamFlagPage {
+        flags: flags.into_iter().map(SpamFlag::from).collect(),
+        next_cursor,
+    }))
+}
+
+/// Takes an account or project out of the spam queue once a moderator reviewed it. Banning the
+/// account or rejecting the project is done through their own endpoints
+pub async fn spam_flag_resolve(
+    req: HttpRequest,
+    info: web::Path<(i64,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::REPORT_WRITE]),
+    )
+    .await?;
 
-    Ok(HttpResponse::Ok().json(projects))
+    match DBSpamFlag::remove(info.into_inner().0, &**pool).await? {
+        Some(()) => Ok(HttpResponse::NoContent().finish()),
+        None => Err(ApiError::NotFound),
+    }
 }
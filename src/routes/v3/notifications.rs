@@ -1,19 +1,83 @@
 use crate::auth::get_user_from_headers;
 use crate::database;
+use crate::database::models::notification_preference_item::NotificationPreference as DBNotificationPreference;
+use crate::database::models::notification_webhook_item::NotificationWebhook as DBNotificationWebhook;
+use crate::database::models::push_subscription_item::PushSubscription as DBPushSubscription;
+use crate::database::models::simple_push_item::SimplePushTarget as DBSimplePushTarget;
 use crate::database::redis::RedisPool;
 use crate::models::ids::NotificationId;
-use crate::models::notifications::Notification;
+use crate::models::notifications::{
+    Notification, NotificationChannel, NotificationPreference, NotificationWebhook,
+    PushSubscription, SimplePushProvider, SimplePushTarget,
+};
 use crate::models::pats::Scopes;
 use crate::queue::session::AuthQueue;
 use crate::routes::ApiError;
+use crate::util::public_url::check_public_url;
+use crate::util::webpush::is_push_service;
 use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::PgPool;
+use validator::Validate;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.route("notifications", web::get().to(notifications_get));
     cfg.route("notifications", web::patch().to(notifications_read));
     cfg.route("notifications", web::delete().to(notifications_delete));
+    cfg.route(
+        "notifications/preferences",
+        web::get().to(notification_preferences_get),
+    );
+    cfg.route(
+        "notifications/preferences",
+        web::patch().to(notification_preferences_edit),
+    );
+    cfg.route(
+        "notifications/push/vapid_key",
+        web::get().to(push_vapid_key_get),
+    );
+    cfg.route(
+        "notifications/push/subscriptions",
+        web::get().to(push_subscriptions_get),
+    );
+    cfg.route(
+        "notifications/push/subscriptions",
+        web::post().to(push_subscription_create),
+    );
+    cfg.route(
+        "notifications/push/subscriptions",
+        web::delete().to(push_subscription_delete),
+    );
+    cfg.route(
+        "notifications/webhook",
+        web::get().to(notification_webhook_get),
+    );
+    cfg.route(
+        "notifications/webhook",
+        web::put().to(notification_webhook_edit),
+    );
+    cfg.route(
+        "notifications/webhook",
+        web::delete().to(notification_webhook_delete),
+    );
+    cfg.route(
+        "notifications/simple_push",
+        web::get().to(simple_push_target_get),
+    );
+    cfg.route(
+        "notifications/simple_push",
+        web::put().to(simple_push_target_edit),
+    );
+    cfg.route(
+        "notifications/simple_push",
+        web::delete().to(simple_push_target_delete),
+    );
 
     cfg.service(
         web::scope("notification")
@@ -287,3 +351,423 @@ pub async fn notifications_delete(
 
     Ok(HttpResponse::NoContent().body(""))
 }
+
+pub async fn notification_preferences_get(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_READ]),
+    )
+    .await?
+    .1;
+
+    let preferences = DBNotificationPreference::get_many_user(&[user.id.into()], &**pool).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "channels": NotificationChannel::iterator().collect::<Vec<_>>(),
+        "preferences": preferences
+            .into_iter()
+            .map(NotificationPreference::from)
+            .collect::<Vec<_>>(),
+    })))
+}
+
+pub async fn notification_preferences_edit(
+    req: HttpRequest,
+    web::Json(preferences): web::Json<Vec<NotificationPreference>>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let mut transaction = pool.begin().await?;
+
+    for preference in preferences {
+        DBNotificationPreference {
+            user_id: user.id.into(),
+            channel: preference.channel,
+            notification_type: preference.notification_type,
+            enabled: preference.enabled,
+        }
+        .upsert(&mut transaction)
+        .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+pub async fn push_vapid_key_get() -> Result<HttpResponse, ApiError> {
+    let public_key = crate::util::webpush::vapid_public_key().ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "public_key": public_key,
+    })))
+}
+
+pub async fn push_subscriptions_get(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_READ]),
+    )
+    .await?
+    .1;
+
+    let subscriptions = DBPushSubscription::get_many_user(&[user.id.into()], &**pool).await?;
+
+    Ok(HttpResponse::Ok().json(
+        subscriptions
+            .into_iter()
+            .map(PushSubscription::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct PushSubscriptionKeys {
+    #[validate(length(min = 1, max = 255))]
+    pub p256dh: String,
+    #[validate(length(min = 1, max = 255))]
+    pub auth: String,
+}
+
+/// The JSON serialization of a browser `PushSubscription`
+#[derive(Deserialize, Validate)]
+pub struct NewPushSubscription {
+    #[validate(url, length(max = 2048))]
+    pub endpoint: String,
+    #[validate]
+    pub keys: PushSubscriptionKeys,
+}
+
+pub async fn push_subscription_create(
+    req: HttpRequest,
+    web::Json(subscription): web::Json<NewPushSubscription>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_WRITE]),
+    )
+    .await?
+    .1;
+
+    subscription.validate()?;
+
+    let endpoint = url::Url::parse(&subscription.endpoint)
+        .map_err(|_| ApiError::InvalidInput("Invalid push subscription endpoint!".to_string()))?;
+    if !is_push_service(&endpoint) {
+        return Err(ApiError::InvalidInput(
+            "Push subscription endpoints must use HTTPS and a known push service!".to_string(),
+        ));
+    }
+    check_public_url(endpoint.as_str()).await.map_err(|err| {
+        ApiError::InvalidInput(format!("Invalid push subscription endpoint: {err}"))
+    })?;
+
+    let mut transaction = pool.begin().await?;
+
+    let inserted = DBPushSubscription {
+        endpoint: subscription.endpoint,
+        user_id: user.id.into(),
+        p256dh: subscription.keys.p256dh,
+        auth: subscription.keys.auth,
+        created: Utc::now(),
+        last_used: None,
+    }
+    .insert(&mut transaction)
+    .await?;
+
+    if !inserted {
+        return Err(ApiError::InvalidInput(
+            "This push subscription is registered to another user!".to_string(),
+        ));
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[derive(Deserialize)]
+pub struct PushSubscriptionEndpoint {
+    pub endpoint: String,
+}
+
+pub async fn push_subscription_delete(
+    req: HttpRequest,
+    web::Json(subscription): web::Json<PushSubscriptionEndpoint>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let mut transaction = pool.begin().await?;
+
+    DBPushSubscription::remove_many(
+        &[subscription.endpoint],
+        Some(user.id.into()),
+        &mut transaction,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+pub async fn notification_webhook_get(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_READ]),
+    )
+    .await?
+    .1;
+
+    let webhook = DBNotificationWebhook::get_many_user(&[user.id.into()], &**pool)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(NotificationWebhook::from(webhook)))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EditNotificationWebhook {
+    #[validate(url, length(max = 2048))]
+    pub url: String,
+}
+
+/// Sets the user's webhook, generating a new signing secret which is returned only once
+pub async fn notification_webhook_edit(
+    req: HttpRequest,
+    web::Json(edit): web::Json<EditNotificationWebhook>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_WRITE]),
+    )
+    .await?
+    .1;
+
+    edit.validate()?;
+
+    check_public_url(&edit.url)
+        .await
+        .map_err(|err| ApiError::InvalidInput(format!("Invalid webhook URL: {err}")))?;
+
+    let secret = ChaCha20Rng::from_entropy()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect::<String>();
+
+    let mut transaction = pool.begin().await?;
+
+    DBNotificationWebhook {
+        user_id: user.id.into(),
+        url: edit.url.clone(),
+        secret: secret.clone(),
+        created: Utc::now(),
+        last_used: None,
+    }
+    .upsert(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "url": edit.url,
+        "secret": secret,
+    })))
+}
+
+pub async fn notification_webhook_delete(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let mut transaction = pool.begin().await?;
+    let result = DBNotificationWebhook::remove(user.id.into(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+pub async fn simple_push_target_get(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_READ]),
+    )
+    .await?
+    .1;
+
+    let target = DBSimplePushTarget::get_many_user(&[user.id.into()], &**pool)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(SimplePushTarget::from(target)))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EditSimplePushTarget {
+    pub provider: SimplePushProvider,
+    #[validate(url, length(max = 2048))]
+    pub url: String,
+    #[validate(length(min = 1, max = 255))]
+    pub token: Option<String>,
+}
+
+pub async fn simple_push_target_edit(
+    req: HttpRequest,
+    web::Json(edit): web::Json<EditSimplePushTarget>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_WRITE]),
+    )
+    .await?
+    .1;
+
+    edit.validate()?;
+
+    check_public_url(&edit.url)
+        .await
+        .map_err(|err| ApiError::InvalidInput(format!("Invalid push target URL: {err}")))?;
+
+    // Generic pushes are signed with the token, so receivers can tell they came from us
+    if edit.provider == SimplePushProvider::Generic && edit.token.is_none() {
+        return Err(ApiError::InvalidInput(
+            "Generic push targets need a token to sign messages with!".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    DBSimplePushTarget {
+        user_id: user.id.into(),
+        provider: edit.provider,
+        url: edit.url,
+        token: edit.token,
+        created: Utc::now(),
+        last_used: None,
+    }
+    .upsert(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+pub async fn simple_push_target_delete(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::NOTIFICATION_WRITE]),
+    )
+    .await?
+    .1;
+
+    let mut transaction = pool.begin().await?;
+    let result = DBSimplePushTarget::remove(user.id.into(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
@@ -27,18 +27,20 @@ use crate::{
             generate_oauth_client_id, generate_oauth_redirect_id,
             oauth_client_authorization_item::OAuthClientAuthorization,
             oauth_client_item::{OAuthClient, OAuthRedirectUri},
+            oauth_client_verification_item::OAuthClientVerification,
             DatabaseError, OAuthClientId, User,
         },
         redis::RedisPool,
     },
     models::{
         self,
-        oauth_clients::{GetOAuthClientsRequest, OAuthClientCreationResult},
+        oauth_clients::{
+            GetOAuthClientsRequest, OAuthClientCreationResult, OAuthClientVerificationStatus,
+        },
         pats::Scopes,
     },
     queue::session::AuthQueue,
     routes::v3::project_creation::CreateError,
-    util::validate::validation_errors_to_string,
 };
 
 use crate::database::models::oauth_client_item::OAuthClient as DBOAuthClient;
@@ -56,7 +58,11 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(oauth_client_icon_delete)
             .service(get_client)
             .service(get_clients)
-            .service(get_user_oauth_authorizations),
+            .service(get_user_oauth_authorizations)
+            .service(get_client_verification)
+            .service(apply_client_verification)
+            .service(list_client_verifications)
+            .service(review_client_verification),
     );
 }
 
@@ -178,9 +184,7 @@ pub async fn oauth_client_create<'a>(
     .await?
     .1;
 
-    new_oauth_app
-        .validate()
-        .map_err(|e| CreateError::ValidationError(validation_errors_to_string(e, None)))?;
+    new_oauth_app.validate()?;
 
     let mut transaction = pool.begin().await?;
 
@@ -203,6 +207,7 @@ pub async fn oauth_client_create<'a>(
         url: new_oauth_app.url.clone(),
         description: new_oauth_app.description.clone(),
         secret_hash: client_secret_hash,
+        verified: false,
     };
     client.clone().insert(&mut transaction).await?;
 
@@ -293,9 +298,7 @@ pub async fn oauth_client_edit(
     .await?
     .1;
 
-    client_updates
-        .validate()
-        .map_err(|e| ApiError::Validation(validation_errors_to_string(e, None)))?;
+    client_updates.validate()?;
 
     if client_updates.icon_url.is_none()
         && client_updates.name.is_none()
@@ -400,6 +403,7 @@ pub async fn oauth_client_icon_edit(
 
         let bytes =
             read_from_payload(&mut payload, 262144, "Icons must be smaller than 256KiB").await?;
+        crate::util::magic::check_content(&bytes, &ext.ext, content_type)?;
         let hash = sha1::Sha1::from(&bytes).hexdigest();
         let upload_data = file_host
             .upload_file(
@@ -581,6 +585,198 @@ async fn edit_redirects(
     Ok(())
 }
 
+/// Gets an app's verification, for its developer or staff
+#[get("app/{id}/verification")]
+pub async fn get_client_verification(
+    req: HttpRequest,
+    client_id: web::Path<ApiOAuthClientId>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let current_user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::SESSION_ACCESS]),
+    )
+    .await?
+    .1;
+
+    let client = OAuthClient::get(client_id.into_inner().into(), &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    client.validate_authorized(Some(&current_user))?;
+
+    let verification = OAuthClientVerification::get(client.id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(
+        HttpResponse::Ok().json(models::oauth_clients::OAuthClientVerification::from(
+            verification,
+        )),
+    )
+}
+
+#[derive(Deserialize, Validate)]
+pub struct OAuthClientVerificationApplication {
+    /// What the app is for and why it needs a higher rate limit or sensitive scopes
+    #[validate(length(min = 1, max = 2048))]
+    pub reason: String,
+}
+
+/// Applies for an app to be verified. The application is then reviewed by staff
+#[post("app/{id}/verification")]
+pub async fn apply_client_verification(
+    req: HttpRequest,
+    client_id: web::Path<ApiOAuthClientId>,
+    body: web::Json<OAuthClientVerificationApplication>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let current_user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::SESSION_ACCESS]),
+    )
+    .await?
+    .1;
+
+    body.validate()?;
+
+    let client = OAuthClient::get(client_id.into_inner().into(), &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    client.validate_authorized(Some(&current_user))?;
+
+    if let Some(verification) = OAuthClientVerification::get(client.id, &**pool).await? {
+        if !verification.status.can_apply() {
+            return Err(ApiError::InvalidInput(format!(
+                "You cannot apply while the application's verification is {}!",
+                verification.status
+            )));
+        }
+    }
+
+    let mut transaction = pool.begin().await?;
+    OAuthClientVerification::apply(client.id, &body.reason, &mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize)]
+pub struct OAuthClientVerificationFilter {
+    pub status: Option<OAuthClientVerificationStatus>,
+}
+
+/// Lists verifications with a status, pending applications by default. Moderator only
+#[get("verifications")]
+pub async fn list_client_verifications(
+    req: HttpRequest,
+    filter: web::Query<OAuthClientVerificationFilter>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let current_user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::SESSION_ACCESS]),
+    )
+    .await?
+    .1;
+
+    if !current_user.role.is_mod() {
+        return Err(ApiError::CustomAuthentication(
+            "You do not have the permissions to review OAuth applications!".to_string(),
+        ));
+    }
+
+    let verifications = OAuthClientVerification::get_by_status(
+        filter
+            .status
+            .unwrap_or(OAuthClientVerificationStatus::Pending),
+        &**pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(
+        verifications
+            .into_iter()
+            .map(models::oauth_clients::OAuthClientVerification::from)
+            .collect_vec(),
+    ))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct OAuthClientVerificationReview {
+    pub status: OAuthClientVerificationStatus,
+    #[validate(length(max = 2048))]
+    pub notes: Option<String>,
+}
+
+/// Verifies or rejects an application, or revokes or reinstates a verification. Moderator only
+#[post("app/{id}/verification/review")]
+pub async fn review_client_verification(
+    req: HttpRequest,
+    client_id: web::Path<ApiOAuthClientId>,
+    body: web::Json<OAuthClientVerificationReview>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let current_user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::SESSION_ACCESS]),
+    )
+    .await?
+    .1;
+
+    if !current_user.role.is_mod() {
+        return Err(ApiError::CustomAuthentication(
+            "You do not have the permissions to review OAuth applications!".to_string(),
+        ));
+    }
+
+    body.validate()?;
+
+    let client_id = client_id.into_inner().into();
+    let verification = OAuthClientVerification::get(client_id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if !verification.status.can_review_to(body.status) {
+        return Err(ApiError::InvalidInput(format!(
+            "A verification cannot go from {} to {}!",
+            verification.status, body.status
+        )));
+    }
+
+    let mut transaction = pool.begin().await?;
+    OAuthClientVerification::review(
+        client_id,
+        body.status,
+        current_user.id.into(),
+        body.notes.as_deref(),
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
 pub async fn get_clients_inner(
     ids: &[ApiOAuthClientId],
     req: HttpRequest,
@@ -0,0 +1,70 @@
+use actix_web::{web, HttpResponse};
+use lazy_static::lazy_static;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("openapi.json", web::get().to(openapi_json));
+}
+
+/// The specification of the v3 API, generated from the annotated routes and the models they
+/// return. Routes are added here when they are annotated with `#[utoipa::path]`
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Labrinth", description = "The Modrinth API"),
+    paths(
+        super::projects::project_search,
+        super::projects::projects_get,
+        super::projects::random_projects_get,
+        super::projects::project_get,
+        super::versions::versions_get,
+        super::versions::version_get,
+        super::versions::version_list,
+        super::users::users_get,
+        super::users::user_get,
+        super::users::projects_list,
+        super::teams::team_members_get_project,
+        super::teams::team_members_get,
+    ),
+    components(schemas(
+        crate::models::error::ApiError,
+        crate::models::error::FieldError,
+        crate::routes::ErrorCode
+    )),
+    modifiers(&TokenAuth),
+    tags(
+        (name = "projects", description = "Projects and searching for them"),
+        (name = "versions", description = "Versions of projects and their files"),
+        (name = "users", description = "Users and what they own"),
+        (name = "teams", description = "The members of project and organization teams"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Documents authentication with a session token, personal access token or OAuth access token
+/// in the `Authorization` header
+struct TokenAuth;
+
+impl Modify for TokenAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "token",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("Authorization"))),
+            );
+        }
+    }
+}
+
+lazy_static! {
+    static ref SPEC: String = ApiDoc::openapi()
+        .to_json()
+        .expect("OpenAPI specification should serialize");
+}
+
+/// Serves the OpenAPI specification, so clients can be generated from it
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(SPEC.as_str())
+}
@@ -3,22 +3,32 @@ use std::sync::Arc;
 
 use super::ApiError;
 use crate::auth::{filter_visible_projects, get_user_from_headers};
+use crate::database::models::ids::{generate_pat_id, generate_team_member_id, generate_user_id};
+use crate::database::models::organization_token_item::OrganizationToken as OrganizationTokenItem;
 use crate::database::models::team_item::TeamMember;
 use crate::database::models::{generate_organization_id, team_item, Organization};
 use crate::database::redis::RedisPool;
 use crate::file_hosting::FileHost;
-use crate::models::ids::base62_impl::parse_base62;
+use crate::models::ids::base62_impl::{parse_base62, to_base62};
 use crate::models::ids::UserId;
+use crate::models::moderation::ImageSource;
 use crate::models::organizations::OrganizationId;
 use crate::models::pats::Scopes;
 use crate::models::teams::{OrganizationPermissions, ProjectPermissions};
+use crate::models::users::{Badges, Role};
 use crate::queue::session::AuthQueue;
 use crate::routes::v3::project_creation::CreateError;
+use crate::util::image_scan;
+use crate::util::money::BASE_CURRENCY;
 use crate::util::routes::read_from_payload;
-use crate::util::validate::validation_errors_to_string;
 use crate::{database, models};
 use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
 use futures::TryStreamExt;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
@@ -43,6 +53,12 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route(
                 "{id}/members",
                 web::get().to(super::teams::team_members_get_organization),
+            )
+            .route("{id}/tokens", web::get().to(organization_tokens_get))
+            .route("{id}/tokens", web::post().to(organization_token_create))
+            .route(
+                "{id}/tokens/{token_id}",
+                web::delete().to(organization_token_delete),
             ),
     );
 }
@@ -120,9 +136,7 @@ pub async fn organization_create(
     .await?
     .1;
 
-    new_organization
-        .validate()
-        .map_err(|err| CreateError::ValidationError(validation_errors_to_string(err, None)))?;
+    new_organization.validate()?;
 
     let mut transaction = pool.begin().await?;
 
@@ -362,9 +376,7 @@ pub async fn organizations_edit(
     .await?
     .1;
 
-    new_organization
-        .validate()
-        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+    new_organization.validate()?;
 
     let string = info.into_inner().0;
     let result = database::models::Organization::get(&string, &**pool, &redis).await?;
@@ -560,8 +572,24 @@ pub async fn organization_delete(
     .user_id;
     let owner_id = database::models::ids::UserId(owner_id);
 
+    // Tokens owned by the organization stop working with it
+    let token_pats = database::models::pat_item::PersonalAccessToken::get_many_ids(
+        &OrganizationTokenItem::get_many_organization(organization.id, &**pool)
+            .await?
+            .into_iter()
+            .map(|x| x.pat_id)
+            .collect::<Vec<_>>(),
+        &**pool,
+        &redis,
+    )
+    .await?;
+
     let mut transaction = pool.begin().await?;
 
+    for pat in &token_pats {
+        database::models::pat_item::PersonalAccessToken::remove(pat.id, &mut transaction).await?;
+    }
+
     // Handle projects- every project that is in this organization needs to have its owner changed the organization owner
     // Now, no project should have an owner if it is in an organization, and also
     // the owner of an organization should not be a team member in any project
@@ -609,6 +637,15 @@ pub async fn organization_delete(
         database::models::TeamMember::clear_cache(team_id, &redis).await?;
     }
 
+    database::models::pat_item::PersonalAccessToken::clear_cache(
+        token_pats
+            .into_iter()
+            .map(|x| (Some(x.id), Some(x.access_token), Some(x.user_id)))
+            .collect(),
+        &redis,
+    )
+    .await?;
+
     if result.is_some() {
         Ok(HttpResponse::NoContent().body(""))
     } else {
@@ -975,6 +1012,7 @@ pub async fn organization_icon_edit(
 
         let bytes =
             read_from_payload(&mut payload, 262144, "Icons must be smaller than 256KiB").await?;
+        crate::util::magic::check_content(&bytes, &ext.ext, content_type)?;
 
         let color = crate::util::img::get_color_from_img(&bytes)?;
 
@@ -988,6 +1026,8 @@ pub async fn organization_icon_edit(
             )
             .await?;
 
+        let icon_url = format!("{}/{}", cdn_url, upload_data.file_name);
+
         let mut transaction = pool.begin().await?;
 
         sqlx::query!(
@@ -996,12 +1036,18 @@ pub async fn organization_icon_edit(
             SET icon_url = $1, color = $2
             WHERE (id = $3)
             ",
-            format!("{}/{}", cdn_url, upload_data.file_name),
+            icon_url,
             color.map(|x| x as i32),
             organization_item.id as database::models::ids::OrganizationId,
         )
         .execute(&mut *transaction)
         .await?;
+        image_scan::queue_scan(
+            &icon_url,
+            ImageSource::OrganizationIcon { organization_id },
+            &mut *transaction,
+        )
+        .await?;
 
         transaction.commit().await?;
         database::models::Organization::clear_cache(
@@ -1098,3 +1144,282 @@ pub async fn delete_organization_icon(
 
     Ok(HttpResponse::NoContent().body(""))
 }
+
+/// The role of the accounts organization tokens act as in the organization's team
+pub const ORGANIZATION_TOKEN_ROLE: &str = "API Token";
+
+/// Gets an organization whose tokens the user can manage. Managing tokens takes the same
+/// permissions as inviting members and giving them project permissions
+async fn get_token_organization(
+    string: &str,
+    user: &models::users::User,
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<(Organization, ProjectPermissions), ApiError> {
+    let organization = database::models::Organization::get(string, pool, redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if user.role.is_admin() {
+        return Ok((organization, ProjectPermissions::all()));
+    }
+
+    let team_member =
+        database::models::TeamMember::get_from_user_id(organization.team_id, user.id.into(), pool)
+            .await?;
+    let permissions = OrganizationPermissions::get_permissions_by_role(&user.role, &team_member)
+        .unwrap_or_default();
+    let team_member = team_member
+        .filter(|_| {
+            permissions.contains(
+                OrganizationPermissions::MANAGE_INVITES
+                    | OrganizationPermissions::EDIT_MEMBER_DEFAULT_PERMISSIONS,
+            )
+        })
+        .ok_or_else(|| {
+            ApiError::CustomAuthentication(
+                "You don't have permission to manage this organization's tokens!".to_string(),
+            )
+        })?;
+
+    Ok((organization, team_member.permissions))
+}
+
+pub async fn organization_tokens_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAT_READ]),
+    )
+    .await?
+    .1;
+
+    let (organization, _) =
+        get_token_organization(&info.into_inner().0, &user, &pool, &redis).await?;
+
+    let tokens = OrganizationTokenItem::get_many_organization(organization.id, &**pool).await?;
+    let pats = database::models::pat_item::PersonalAccessToken::get_many_ids(
+        &tokens.iter().map(|x| x.pat_id).collect::<Vec<_>>(),
+        &**pool,
+        &redis,
+    )
+    .await?;
+    let members = TeamMember::get_from_team_full(organization.team_id, &**pool, &redis).await?;
+
+    let tokens = pats
+        .into_iter()
+        .filter_map(|pat| {
+            let token = tokens.iter().find(|x| x.pat_id == pat.id)?.clone();
+            let permissions = members
+                .iter()
+                .find(|x| x.user_id == pat.user_id && x.accepted)
+                .map(|x| x.permissions)
+                .unwrap_or_default();
+
+            Some(models::organizations::OrganizationToken::from(
+                pat,
+                token,
+                permissions,
+                false,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct NewOrganizationToken {
+    #[validate(length(min = 3, max = 255))]
+    pub name: String,
+    pub scopes: Scopes,
+    /// The permissions the token has in every project of the organization
+    pub permissions: ProjectPermissions,
+    pub expires: DateTime<Utc>,
+}
+
+/// Creates a token owned by the organization. The token acts as an account of its own, added
+/// to the organization's team with the token's permissions
+pub async fn organization_token_create(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    new_token: web::Json<NewOrganizationToken>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    new_token.validate()?;
+
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAT_CREATE]),
+    )
+    .await?
+    .1;
+
+    if !Scopes::organization_token().contains(new_token.scopes) {
+        return Err(ApiError::InvalidInput(
+            "Organization tokens can only be given project, version and organization read scopes!"
+                .to_string(),
+        ));
+    }
+    if new_token.expires < Utc::now() {
+        return Err(ApiError::InvalidInput(
+            "Expire date must be in the future!".to_string(),
+        ));
+    }
+
+    let (organization, permissions) =
+        get_token_organization(&info.into_inner().0, &user, &pool, &redis).await?;
+    if !permissions.contains(new_token.permissions) {
+        return Err(ApiError::InvalidInput(
+            "The new token has permissions that you don't have".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    let pat_id = generate_pat_id(&mut transaction).await?;
+    let user_id = generate_user_id(&mut transaction).await?;
+
+    database::models::User {
+        id: user_id,
+        github_id: None,
+        discord_id: None,
+        gitlab_id: None,
+        google_id: None,
+        steam_id: None,
+        microsoft_id: None,
+        password: None,
+        paypal_id: None,
+        paypal_country: None,
+        paypal_email: None,
+        venmo_handle: None,
+        stripe_account_id: None,
+        stripe_payouts_enabled: false,
+        totp_secret: None,
+        username: format!("{}-token-{}", organization.slug, to_base62(pat_id.0 as u64)),
+        name: Some(new_token.name.clone()),
+        email: None,
+        email_verified: false,
+        email_undeliverable: false,
+        avatar_url: None,
+        bio: None,
+        created: Utc::now(),
+        role: Role::Developer.to_string(),
+        badges: Badges::default(),
+        balance: Decimal::ZERO,
+        balance_currency: BASE_CURRENCY.to_string(),
+        deleted: None,
+    }
+    .insert(&mut transaction)
+    .await?;
+
+    TeamMember {
+        id: generate_team_member_id(&mut transaction).await?,
+        team_id: organization.team_id,
+        user_id,
+        role: ORGANIZATION_TOKEN_ROLE.to_string(),
+        is_owner: false,
+        permissions: new_token.permissions,
+        organization_permissions: Some(OrganizationPermissions::NONE),
+        accepted: true,
+        payouts_split: Decimal::ZERO,
+        ordering: 0,
+    }
+    .insert(&mut transaction)
+    .await?;
+
+    let access_token = ChaCha20Rng::from_entropy()
+        .sample_iter(&Alphanumeric)
+        .take(60)
+        .map(char::from)
+        .collect::<String>();
+    let pat = database::models::pat_item::PersonalAccessToken {
+        id: pat_id,
+        name: new_token.name.clone(),
+        access_token: format!("mrp_{}", access_token),
+        scopes: new_token.scopes,
+        user_id,
+        created: Utc::now(),
+        expires: new_token.expires,
+        last_used: None,
+    };
+    pat.insert(&mut transaction).await?;
+
+    let token = OrganizationTokenItem {
+        pat_id,
+        organization_id: organization.id,
+        created_by: Some(user.id.into()),
+    };
+    token.insert(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    TeamMember::clear_cache(organization.team_id, &redis).await?;
+
+    Ok(
+        HttpResponse::Ok().json(models::organizations::OrganizationToken::from(
+            pat,
+            token,
+            new_token.permissions,
+            true,
+        )),
+    )
+}
+
+/// Revokes an organization token, removing its account from the organization's team. The
+/// account itself is kept, as it is still the author of what was uploaded with the token
+pub async fn organization_token_delete(
+    req: HttpRequest,
+    info: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAT_DELETE]),
+    )
+    .await?
+    .1;
+    let (string, token_id) = info.into_inner();
+
+    let (organization, _) = get_token_organization(&string, &user, &pool, &redis).await?;
+
+    let pat = database::models::pat_item::PersonalAccessToken::get(&token_id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    OrganizationTokenItem::get(pat.id, &**pool)
+        .await?
+        .filter(|x| x.organization_id == organization.id)
+        .ok_or(ApiError::NotFound)?;
+
+    let mut transaction = pool.begin().await?;
+    TeamMember::delete(organization.team_id, pat.user_id, &mut transaction).await?;
+    database::models::pat_item::PersonalAccessToken::remove(pat.id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    TeamMember::clear_cache(organization.team_id, &redis).await?;
+    database::models::pat_item::PersonalAccessToken::clear_cache(
+        vec![(Some(pat.id), Some(pat.access_token), Some(pat.user_id))],
+        &redis,
+    )
+    .await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
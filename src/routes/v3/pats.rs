@@ -0,0 +1,67 @@
+use crate::auth::get_user_from_headers;
+use crate::database::models::pat_item::PersonalAccessToken;
+use crate::database::models::pat_usage_item::{PatError, PatUsageHour};
+use crate::database::redis::RedisPool;
+use crate::models::pats::{PatUsage, Scopes};
+use crate::queue::session::{AuthQueue, PAT_USAGE_DAYS};
+use crate::routes::ApiError;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("pat/{id}/usage", web::get().to(pat_usage));
+}
+
+#[derive(Deserialize)]
+pub struct PatUsageQuery {
+    /// How many days of requests to count, 7 unless given
+    pub days: Option<i64>,
+}
+
+/// Gets the requests made with one of the user's personal access tokens by hour, and the
+/// latest of those which failed
+pub async fn pat_usage(
+    req: HttpRequest,
+    id: web::Path<(String,)>,
+    query: web::Query<PatUsageQuery>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let days = query.days.unwrap_or(7);
+    if !(1..=PAT_USAGE_DAYS).contains(&days) {
+        return Err(ApiError::InvalidInput(format!(
+            "Usage is only kept for {} days!",
+            PAT_USAGE_DAYS
+        )));
+    }
+
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAT_READ]),
+    )
+    .await?
+    .1;
+
+    let pat = PersonalAccessToken::get(&id.into_inner().0, &**pool, &redis)
+        .await?
+        .filter(|x| x.user_id == user.id.into())
+        .ok_or(ApiError::NotFound)?;
+
+    let since = Utc::now() - Duration::days(days);
+    let hours = PatUsageHour::get_many(pat.id, since, &**pool).await?;
+    let recent_errors = PatError::get_many(pat.id, &**pool).await?;
+
+    Ok(HttpResponse::Ok().json(PatUsage {
+        requests: hours.iter().map(|x| x.requests).sum(),
+        errors: hours.iter().map(|x| x.errors).sum(),
+        rate_limited: hours.iter().map(|x| x.rate_limited).sum(),
+        hours: hours.into_iter().map(Into::into).collect(),
+        recent_errors: recent_errors.into_iter().map(Into::into).collect(),
+    }))
+}
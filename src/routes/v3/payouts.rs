@@ -1,315 +1,297 @@
 use crate::auth::validate::get_user_record_from_bearer_token;
 use crate::auth::{get_user_from_headers, AuthenticationError};
 use crate::database::models::generate_payout_id;
+use crate::database::models::monetization_enrollment_item::MonetizationEnrollment;
+use crate::database::models::notification_item::NotificationBuilder;
+use crate::database::models::payout_adjustment_item::PayoutAdjustment;
+use crate::database::models::payout_quote_item::PayoutQuote;
+use crate::database::models::payout_schedule_item::PayoutSchedule;
+use crate::database::models::tax_form_item::TaxForm;
 use crate::database::redis::RedisPool;
-use crate::models::ids::PayoutId;
+use crate::models::ids::{PayoutId, ProjectId};
+use crate::models::notifications::NotificationBody;
 use crate::models::pats::Scopes;
-use crate::models::payouts::{PayoutMethodType, PayoutStatus};
+use crate::models::payouts::{
+    AdjustmentReason, EnrollmentInfo, EnrollmentStatus, MonetizationEligibility, PayoutBalance,
+    PayoutInterval, PayoutMethod, PayoutMethodType, PayoutStatus, TaxFormStatus, TaxYearSummary,
+};
+use crate::payouts::{PayoutProvider, Transfer};
 use crate::queue::payouts::PayoutsQueue;
 use crate::queue::session::AuthQueue;
 use crate::routes::ApiError;
-use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
-use chrono::Utc;
-use hex::ToHex;
-use hmac::{Hmac, Mac, NewMac};
+use crate::util::env::parse_var;
+use crate::util::money::{base_to_minor_units, Money, BASE_CURRENCY};
+use crate::util::statement::{Statement, StatementFormat, StatementLine, StatementLineKind};
+use crate::webhooks::{self, WebhookSource};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Months, NaiveDate, NaiveTime, TimeZone, Utc};
 use hyper::Method;
 use rust_decimal::Decimal;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use sha2::Sha256;
 use sqlx::PgPool;
+use validator::Validate;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("payout")
-            .service(paypal_webhook)
-            .service(tremendous_webhook)
+            .service(stripe_onboard)
+            .service(tax_webhook)
+            // Registered after the tax webhook, which it would otherwise match
+            .service(payout_webhook)
+            .service(get_tax_form)
+            .service(request_tax_form)
+            .service(tax_year_summary)
             .service(user_payouts)
             .service(create_payout)
+            .service(get_balance)
+            .service(quote_payout)
+            .service(create_adjustment)
+            .service(user_adjustments)
+            .service(get_enrollment)
+            .service(apply_enrollment)
+            .service(list_enrollments)
+            .service(review_enrollment)
+            .service(get_payout_schedule)
+            .service(edit_payout_schedule)
+            .service(delete_payout_schedule)
+            .service(monthly_statement)
+            .service(payout_statement)
             .service(cancel_payout)
             .service(payment_methods),
     );
 }
 
-#[post("_paypal")]
-pub async fn paypal_webhook(
+/// Receives webhooks from a payout provider, such as `/payout/_paypal`
+#[post("_{provider}")]
+pub async fn payout_webhook(
+    info: web::Path<(String,)>,
     req: HttpRequest,
     pool: web::Data<PgPool>,
-    redis: web::Data<RedisPool>,
     payouts: web::Data<PayoutsQueue>,
     body: String,
 ) -> Result<HttpResponse, ApiError> {
-    let auth_algo = req
-        .headers()
-        .get("PAYPAL-AUTH-ALGO")
-        .and_then(|x| x.to_str().ok())
-        .ok_or_else(|| ApiError::InvalidInput("missing auth algo".to_string()))?;
-    let cert_url = req
-        .headers()
-        .get("PAYPAL-CERT-URL")
-        .and_then(|x| x.to_str().ok())
-        .ok_or_else(|| ApiError::InvalidInput("missing cert url".to_string()))?;
-    let transmission_id = req
-        .headers()
-        .get("PAYPAL-TRANSMISSION-ID")
-        .and_then(|x| x.to_str().ok())
-        .ok_or_else(|| ApiError::InvalidInput("missing transmission ID".to_string()))?;
-    let transmission_sig = req
-        .headers()
-        .get("PAYPAL-TRANSMISSION-SIG")
-        .and_then(|x| x.to_str().ok())
-        .ok_or_else(|| ApiError::InvalidInput("missing transmission sig".to_string()))?;
-    let transmission_time = req
-        .headers()
-        .get("PAYPAL-TRANSMISSION-TIME")
-        .and_then(|x| x.to_str().ok())
-        .ok_or_else(|| ApiError::InvalidInput("missing transmission time".to_string()))?;
+    webhooks::receive(
+        WebhookSource::Payout(info.into_inner().0),
+        req.headers(),
+        &body,
+        &pool,
+        &payouts,
+    )
+    .await?;
 
-    #[derive(Deserialize)]
-    struct WebHookResponse {
-        verification_status: String,
-    }
+    Ok(HttpResponse::NoContent().finish())
+}
 
-    let webhook_res = payouts
-        .make_paypal_request::<(), WebHookResponse>(
-            Method::POST,
-            "notifications/verify-webhook-signature",
-            None,
-            // This is needed as serde re-orders fields, which causes the validation to fail for PayPal.
-            Some(format!(
-                "{{
-                    \"auth_algo\": \"{auth_algo}\",
-                    \"cert_url\": \"{cert_url}\",
-                    \"transmission_id\": \"{transmission_id}\",
-                    \"transmission_sig\": \"{transmission_sig}\",
-                    \"transmission_time\": \"{transmission_time}\",
-                    \"webhook_id\": \"{}\",
-                    \"webhook_event\": {body}
-                }}",
-                dotenvy::var("PAYPAL_WEBHOOK_ID")?
-            )),
-            None,
-        )
-        .await?;
+#[derive(Deserialize)]
+pub struct StripeOnboardingRequest {
+    /// ISO 3166-1 alpha-2 code of the country the account is set up in. Ignored if the user
+    /// already has an account
+    pub country: String,
+}
 
-    if &webhook_res.verification_status != "SUCCESS" {
-        return Err(ApiError::InvalidInput(
-            "Invalid webhook signature".to_string(),
-        ));
-    }
+#[derive(Serialize, Deserialize)]
+pub struct StripeOnboarding {
+    pub url: String,
+}
 
-    #[derive(Deserialize)]
-    struct PayPalResource {
-        pub payout_item_id: String,
-    }
+/// Creates a Stripe Connect Express account for the user if they do not have one yet, and
+/// returns a link to Stripe's hosted onboarding for it
+#[post("stripe/onboard")]
+pub async fn stripe_onboard(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    body: web::Json<StripeOnboardingRequest>,
+    session_queue: web::Data<AuthQueue>,
+    payouts_queue: web::Data<PayoutsQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let (scopes, user) =
+        get_user_record_from_bearer_token(&req, None, &**pool, &redis, &session_queue)
+            .await?
+            .ok_or_else(|| ApiError::Authentication(AuthenticationError::InvalidCredentials))?;
 
-    #[derive(Deserialize)]
-    struct PayPalWebhook {
-        pub event_type: String,
-        pub resource: PayPalResource,
+    if !scopes.contains(Scopes::PAYOUTS_WRITE) {
+        return Err(ApiError::Authentication(
+            AuthenticationError::InvalidCredentials,
+        ));
     }
 
-    let webhook = serde_json::from_str::<PayPalWebhook>(&body)?;
-
-    match &*webhook.event_type {
-        "PAYMENT.PAYOUTS-ITEM.BLOCKED"
-        | "PAYMENT.PAYOUTS-ITEM.DENIED"
-        | "PAYMENT.PAYOUTS-ITEM.REFUNDED"
-        | "PAYMENT.PAYOUTS-ITEM.RETURNED"
-        | "PAYMENT.PAYOUTS-ITEM.CANCELED" => {
-            let mut transaction = pool.begin().await?;
-
-            let result = sqlx::query!(
-                "SELECT user_id, amount, fee FROM payouts WHERE platform_id = $1 AND status = $2",
-                webhook.resource.payout_item_id,
-                PayoutStatus::InTransit.as_str()
-            )
-            .fetch_optional(&mut *transaction)
-            .await?;
+    let provider = payouts_queue
+        .provider(PayoutMethodType::Stripe)
+        .ok_or_else(|| ApiError::InvalidInput("Stripe payouts are not available!".to_string()))?;
+    let url = provider
+        .create_recipient(&user, &body.country, &pool, &redis)
+        .await?;
 
-            if let Some(result) = result {
-                let mtx =
-                    payouts.lock_user_payouts(crate::models::ids::UserId(result.user_id as u64));
-                let _guard = mtx.lock().await;
+    Ok(HttpResponse::Ok().json(StripeOnboarding { url }))
+}
 
-                sqlx::query!(
-                    "
-                    UPDATE users
-                    SET balance = balance + $1
-                    WHERE id = $2
-                    ",
-                    result.amount + result.fee.unwrap_or(Decimal::ZERO),
-                    result.user_id
-                )
-                .execute(&mut *transaction)
-                .await?;
+/// Receives the result of a user filling in their tax form at the tax form provider
+#[post("_tax")]
+pub async fn tax_webhook(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    payouts: web::Data<PayoutsQueue>,
+    body: String,
+) -> Result<HttpResponse, ApiError> {
+    webhooks::receive(WebhookSource::Tax, req.headers(), &body, &pool, &payouts).await?;
 
-                sqlx::query!(
-                    "
-                    UPDATE payouts
-                    SET status = $1
-                    WHERE platform_id = $2
-                    ",
-                    if &*webhook.event_type == "PAYMENT.PAYOUTS-ITEM.CANCELED" {
-                        PayoutStatus::Cancelled
-                    } else {
-                        PayoutStatus::Failed
-                    }
-                    .as_str(),
-                    webhook.resource.payout_item_id
-                )
-                .execute(&mut *transaction)
-                .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
 
-                transaction.commit().await?;
+#[get("tax")]
+pub async fn get_tax_form(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_READ]),
+    )
+    .await?
+    .1;
 
-                crate::database::models::user_item::User::clear_caches(
-                    &[(crate::database::models::UserId(result.user_id), None)],
-                    &redis,
-                )
-                .await?;
-            }
-        }
-        "PAYMENT.PAYOUTS-ITEM.SUCCEEDED" => {
-            let mut transaction = pool.begin().await?;
-            sqlx::query!(
-                "
-                UPDATE payouts
-                SET status = $1
-                WHERE platform_id = $2
-                ",
-                PayoutStatus::Success.as_str(),
-                webhook.resource.payout_item_id
-            )
-            .execute(&mut *transaction)
-            .await?;
-            transaction.commit().await?;
-        }
-        _ => {}
+    let form = TaxForm::get(user.id.into(), &**pool).await?;
+
+    if let Some(form) = form {
+        Ok(HttpResponse::Ok().json(crate::models::payouts::TaxForm::from(form)))
+    } else {
+        Ok(HttpResponse::NotFound().finish())
     }
+}
 
-    Ok(HttpResponse::NoContent().finish())
+#[derive(Serialize, Deserialize)]
+pub struct TaxFormLink {
+    pub url: String,
 }
 
-#[post("_tremendous")]
-pub async fn tremendous_webhook(
+/// Requests a new tax form for the user to fill in at the tax form provider, returning a link
+/// to it. Replaces the user's existing form, so payouts are paused until the new one is complete
+#[post("tax")]
+pub async fn request_tax_form(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     redis: web::Data<RedisPool>,
-    payouts: web::Data<PayoutsQueue>,
-    body: String,
+    session_queue: web::Data<AuthQueue>,
+    payouts_queue: web::Data<PayoutsQueue>,
 ) -> Result<HttpResponse, ApiError> {
-    let signature = req
-        .headers()
-        .get("Tremendous-Webhook-Signature")
-        .and_then(|x| x.to_str().ok())
-        .and_then(|x| x.split('=').next_back())
-        .ok_or_else(|| ApiError::InvalidInput("missing webhook signature".to_string()))?;
-
-    let mut mac: Hmac<Sha256> =
-        Hmac::new_from_slice(dotenvy::var("TREMENDOUS_PRIVATE_KEY")?.as_bytes())
-            .map_err(|_| ApiError::Payments("error initializing HMAC".to_string()))?;
-    mac.update(body.as_bytes());
-    let request_signature = mac.finalize().into_bytes().encode_hex::<String>();
-
-    if &*request_signature != signature {
-        return Err(ApiError::InvalidInput(
-            "Invalid webhook signature".to_string(),
+    let (scopes, user) =
+        get_user_record_from_bearer_token(&req, None, &**pool, &redis, &session_queue)
+            .await?
+            .ok_or_else(|| ApiError::Authentication(AuthenticationError::InvalidCredentials))?;
+
+    if !scopes.contains(Scopes::PAYOUTS_WRITE) {
+        return Err(ApiError::Authentication(
+            AuthenticationError::InvalidCredentials,
         ));
     }
 
+    let email_verified = user.email_verified;
+    let email = if let Some(email) = user.email.filter(|_| email_verified) {
+        email
+    } else {
+        return Err(ApiError::InvalidInput(
+            "You must verify your account email to proceed!".to_string(),
+        ));
+    };
+
     #[derive(Deserialize)]
-    pub struct TremendousResource {
+    struct TaxFormRequest {
         pub id: String,
+        pub url: String,
     }
 
-    #[derive(Deserialize)]
-    struct TremendousPayload {
-        pub resource: TremendousResource,
-    }
+    let res: TaxFormRequest = payouts_queue
+        .make_tax_request(
+            Method::POST,
+            "forms",
+            Some(json!({
+                "reference_id": crate::models::ids::UserId::from(user.id),
+                "email": email,
+                "name": user.name.unwrap_or(user.username),
+                "return_url": format!(
+                    "{}/{}",
+                    dotenvy::var("SITE_URL")?,
+                    dotenvy::var("SITE_TAX_FORM_PATH")?
+                ),
+            })),
+        )
+        .await?;
 
-    #[derive(Deserialize)]
-    struct TremendousWebhook {
-        pub event: String,
-        pub payload: TremendousPayload,
+    let mut transaction = pool.begin().await?;
+    TaxForm {
+        user_id: user.id,
+        provider_id: res.id,
+        form_type: None,
+        status: TaxFormStatus::Pending,
+        withholding_rate: Decimal::ZERO,
+        created: Utc::now(),
+        completed: None,
     }
+    .upsert(&mut transaction)
+    .await?;
+    transaction.commit().await?;
 
-    let webhook = serde_json::from_str::<TremendousWebhook>(&body)?;
-
-    match &*webhook.event {
-        "REWARDS.CANCELED" | "REWARDS.DELIVERY.FAILED" => {
-            let mut transaction = pool.begin().await?;
-
-            let result = sqlx::query!(
-                "SELECT user_id, amount, fee FROM payouts WHERE platform_id = $1 AND status = $2",
-                webhook.payload.resource.id,
-                PayoutStatus::InTransit.as_str()
-            )
-            .fetch_optional(&mut *transaction)
-            .await?;
-
-            if let Some(result) = result {
-                let mtx =
-                    payouts.lock_user_payouts(crate::models::ids::UserId(result.user_id as u64));
-                let _guard = mtx.lock().await;
-
-                sqlx::query!(
-                    "
-                    UPDATE users
-                    SET balance = balance + $1
-                    WHERE id = $2
-                    ",
-                    result.amount + result.fee.unwrap_or(Decimal::ZERO),
-                    result.user_id
-                )
-                .execute(&mut *transaction)
-                .await?;
+    Ok(HttpResponse::Ok().json(TaxFormLink { url: res.url }))
+}
 
-                sqlx::query!(
-                    "
-                    UPDATE payouts
-                    SET status = $1
-                    WHERE platform_id = $2
-                    ",
-                    if &*webhook.event == "REWARDS.CANCELED" {
-                        PayoutStatus::Cancelled
-                    } else {
-                        PayoutStatus::Failed
-                    }
-                    .as_str(),
-                    webhook.payload.resource.id
-                )
-                .execute(&mut *transaction)
-                .await?;
+#[derive(Deserialize)]
+pub struct TaxYearQuery {
+    pub year: i32,
+}
 
-                transaction.commit().await?;
+/// Totals the user's payouts over a calendar year, for year-end tax reporting
+#[get("tax/summary")]
+pub async fn tax_year_summary(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    query: web::Query<TaxYearQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_READ]),
+    )
+    .await?
+    .1;
 
-                crate::database::models::user_item::User::clear_caches(
-                    &[(crate::database::models::UserId(result.user_id), None)],
-                    &redis,
-                )
-                .await?;
-            }
-        }
-        "REWARDS.DELIVERY.SUCCEEDED" => {
-            let mut transaction = pool.begin().await?;
-            sqlx::query!(
-                "
-                UPDATE payouts
-                SET status = $1
-                WHERE platform_id = $2
-                ",
-                PayoutStatus::Success.as_str(),
-                webhook.payload.resource.id
-            )
-            .execute(&mut *transaction)
-            .await?;
-            transaction.commit().await?;
-        }
-        _ => {}
-    }
+    let result = sqlx::query!(
+        "
+        SELECT
+            COUNT(1) payouts,
+            SUM(amount + COALESCE(fee, 0) + COALESCE(withholding, 0)) gross,
+            SUM(COALESCE(fee, 0)) fees,
+            SUM(COALESCE(withholding, 0)) withheld
+        FROM payouts
+        WHERE user_id = $1 AND EXTRACT(YEAR FROM created) = $2 AND NOT status = ANY($3)
+        ",
+        user.id.0 as i64,
+        query.year as f64,
+        &[
+            PayoutStatus::Cancelled.as_str().to_string(),
+            PayoutStatus::Failed.as_str().to_string(),
+        ][..],
+    )
+    .fetch_one(&**pool)
+    .await?;
 
-    Ok(HttpResponse::NoContent().finish())
+    Ok(HttpResponse::Ok().json(TaxYearSummary {
+        year: query.year,
+        payouts: result.payouts.unwrap_or_default(),
+        gross: result.gross.unwrap_or_default(),
+        fees: result.fees.unwrap_or_default(),
+        withheld: result.withheld.unwrap_or_default(),
+    }))
 }
 
 #[get("")]
@@ -346,9 +328,12 @@ pub async fn user_payouts(
 #[derive(Deserialize)]
 pub struct Withdrawal {
     #[serde(with = "rust_decimal::serde::float")]
-    amount: Decimal,
-    method: PayoutMethodType,
-    method_id: String,
+    pub amount: Decimal,
+    pub method: PayoutMethodType,
+    pub method_id: String,
+    /// A quote to send the payout in another currency at, from `POST /payout/quote`
+    #[serde(default)]
+    pub quote_id: Option<String>,
 }
 
 #[post("")]
@@ -371,238 +356,129 @@ pub async fn create_payout(
         ));
     }
 
+    withdraw(user, &body, &pool, &redis, &payouts_queue).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Withdraws from the user's balance through a payout method. Used both for withdrawals made
+/// by the user and for automatic payouts
+pub async fn withdraw(
+    user: crate::database::models::User,
+    body: &Withdrawal,
+    pool: &PgPool,
+    redis: &RedisPool,
+    payouts_queue: &PayoutsQueue,
+) -> Result<crate::database::models::payout_item::Payout, ApiError> {
     let mtx = payouts_queue.lock_user_payouts(user.id.into());
     let _guard = mtx.lock().await;
 
-    if user.balance < body.amount || body.amount < Decimal::ZERO {
+    if user.balance_amount() < body.amount || body.amount < Decimal::ZERO {
         return Err(ApiError::InvalidInput(
             "You do not have enough funds to make this payout!".to_string(),
         ));
     }
 
+    let provider = payouts_queue
+        .provider(body.method)
+        .ok_or_else(|| ApiError::Payments("Invalid payment method specified!".to_string()))?;
     let payout_method = payouts_queue
         .get_payout_methods()
         .await?
         .into_iter()
-        .find(|x| x.id == body.method_id)
+        .find(|x| x.id == body.method_id && x.type_ == body.method)
         .ok_or_else(|| ApiError::InvalidInput("Invalid payment method specified!".to_string()))?;
 
-    let fee = std::cmp::min(
-        std::cmp::max(
-            payout_method.fee.min,
-            payout_method.fee.percentage * body.amount,
-        ),
-        payout_method.fee.max.unwrap_or(Decimal::MAX),
-    );
-
-    let transfer = (body.amount - fee).round_dp(2);
-    if transfer <= Decimal::ZERO {
-        return Err(ApiError::InvalidInput(
-            "You need to withdraw more to cover the fee!".to_string(),
-        ));
-    }
-
-    let mut transaction = pool.begin().await?;
-    let payout_id = generate_payout_id(&mut transaction).await?;
+    let (fee, withholding, transfer) =
+        withdrawal_amounts(user.id, body.amount, &*provider, &payout_method, pool).await?;
+    let recipient = provider.recipient(&user, &payout_method).await?;
 
-    let payout_item = match body.method {
-        PayoutMethodType::Venmo | PayoutMethodType::PayPal => {
-            let (wallet, wallet_type, address, display_address) =
-                if body.method == PayoutMethodType::Venmo {
-                    if let Some(venmo) = user.venmo_handle {
-                        ("Venmo", "user_handle", venmo.clone(), venmo)
-                    } else {
-                        return Err(ApiError::InvalidInput(
-                            "Venmo address has not been set for account!".to_string(),
-                        ));
-                    }
-                } else if let Some(paypal_id) = user.paypal_id {
-                    if let Some(paypal_country) = user.paypal_country {
-                        if &*paypal_country == "US" && &*body.method_id != "paypal_us" {
-                            return Err(ApiError::InvalidInput(
-                                "Please use the US PayPal transfer option!".to_string(),
-                            ));
-                        } else if &*paypal_country != "US" && &*body.method_id == "paypal_us" {
-                            return Err(ApiError::InvalidInput(
-                                "Please use the International PayPal transfer option!".to_string(),
-                            ));
-                        }
-
-                        (
-                            "PayPal",
-                            "paypal_id",
-                            paypal_id.clone(),
-                            user.paypal_email.unwrap_or(paypal_id),
-                        )
-                    } else {
-                        return Err(ApiError::InvalidInput(
-                            "Please re-link your PayPal account!".to_string(),
-                        ));
-                    }
-                } else {
-                    return Err(ApiError::InvalidInput(
-                        "You have not linked a PayPal account!".to_string(),
-                    ));
-                };
+    // Quoted withdrawals are sent in the quote's currency, at its locked rate
+    let quote = if let Some(quote_id) = &body.quote_id {
+        let quote = PayoutQuote::get(quote_id, redis)
+            .await?
+            .filter(|x| x.user_id == user.id && x.expires > Utc::now())
+            .ok_or_else(|| {
+                ApiError::InvalidInput("The quote has expired or does not exist!".to_string())
+            })?;
+
+        if quote.method != body.method
+            || quote.method_id != body.method_id
+            || quote.amount != body.amount
+            || quote.transfer != transfer
+        {
+            return Err(ApiError::InvalidInput(
+                "The withdrawal does not match the quote!".to_string(),
+            ));
+        }
 
-            #[derive(Deserialize)]
-            struct PayPalLink {
-                href: String,
-            }
+        Some(quote)
+    } else {
+        None
+    };
 
-            #[derive(Deserialize)]
-            struct PayoutsResponse {
-                pub links: Vec<PayPalLink>,
+    let (currency, send_amount) = match &quote {
+        Some(quote) => (
+            quote.currency.clone(),
+            Money {
+                amount: quote.destination_amount,
+                currency: quote.currency.clone(),
             }
+            .to_decimal(),
+        ),
+        None => (BASE_CURRENCY.to_string(), transfer),
+    };
+    let currency_amount = quote.as_ref().map(|x| x.destination_amount);
+    let fx_rate = quote.as_ref().map(|x| x.fx_rate);
+    let quoted_currency = quote.map(|x| x.currency);
 
-            let mut payout_item = crate::database::models::payout_item::Payout {
-                id: payout_id,
-                user_id: user.id,
-                created: Utc::now(),
-                status: PayoutStatus::InTransit,
-                amount: transfer,
-                fee: Some(fee),
-                method: Some(body.method),
-                method_address: Some(display_address),
-                platform_id: None,
-            };
-
-            let res: PayoutsResponse = payouts_queue.make_paypal_request(
-                Method::POST,
-                "payments/payouts",
-                Some(
-                    json! ({
-                        "sender_batch_header": {
-                            "sender_batch_id": format!("{}-payouts", Utc::now().to_rfc3339()),
-                            "email_subject": "You have received a payment from Modrinth!",
-                            "email_message": "Thank you for creating projects on Modrinth. Please claim this payment within 30 days.",
-                        },
-                        "items": [{
-                            "amount": {
-                                "currency": "USD",
-                                "value": transfer.to_string()
-                            },
-                            "receiver": address,
-                            "note": "Payment from Modrinth creator monetization program",
-                            "recipient_type": wallet_type,
-                            "recipient_wallet": wallet,
-                            "sender_item_id": crate::models::ids::PayoutId::from(payout_id),
-                        }]
-                    })
-                ),
-                None,
-                None
-            ).await?;
-
-            if let Some(link) = res.links.first() {
-                #[derive(Deserialize)]
-                struct PayoutItem {
-                    pub payout_item_id: String,
-                }
+    let mut transaction = pool.begin().await?;
+    let payout_id = generate_payout_id(&mut transaction).await?;
 
-                #[derive(Deserialize)]
-                struct PayoutData {
-                    pub items: Vec<PayoutItem>,
-                }
+    let mut payout_item = crate::database::models::payout_item::Payout {
+        id: payout_id,
+        user_id: user.id,
+        created: Utc::now(),
+        status: PayoutStatus::InTransit,
+        amount: transfer,
+        fee: Some(fee),
+        withholding: Some(withholding),
+        method: Some(body.method),
+        method_address: Some(recipient.display_address.clone()),
+        platform_id: None,
+        failure_reason: None,
+        currency: quoted_currency,
+        currency_amount,
+        fx_rate,
+    };
 
-                if let Ok(res) = payouts_queue
-                    .make_paypal_request::<(), PayoutData>(
-                        Method::GET,
-                        &link.href,
-                        None,
-                        None,
-                        Some(true),
-                    )
-                    .await
-                {
-                    if let Some(data) = res.items.first() {
-                        payout_item.platform_id = Some(data.payout_item_id.clone());
-                    }
-                }
-            }
+    let result = provider
+        .execute_transfer(&Transfer {
+            payout_id: payout_id.into(),
+            method: &payout_method,
+            recipient: &recipient,
+            recipient_name: &user.username,
+            amount: send_amount,
+            currency: &currency,
+        })
+        .await;
+
+    match result {
+        Ok(platform_id) => payout_item.platform_id = platform_id,
+        Err(err) => {
+            // Recorded without touching the balance, so the failure shows up in the user's
+            // payout history
+            payout_item.status = PayoutStatus::Failed;
+            payout_item.failure_reason = Some(match &err {
+                ApiError::Payments(reason) => reason.clone(),
+                err => err.to_string(),
+            });
+            payout_item.insert(&mut transaction).await?;
+            transaction.commit().await?;
 
-            payout_item
-        }
-        PayoutMethodType::Tremendous => {
-            if let Some(email) = user.email {
-                if user.email_verified {
-                    let mut payout_item = crate::database::models::payout_item::Payout {
-                        id: payout_id,
-                        user_id: user.id,
-                        created: Utc::now(),
-                        status: PayoutStatus::InTransit,
-                        amount: transfer,
-                        fee: Some(fee),
-                        method: Some(PayoutMethodType::Tremendous),
-                        method_address: Some(email.clone()),
-                        platform_id: None,
-                    };
-
-                    #[derive(Deserialize)]
-                    struct Reward {
-                        pub id: String,
-                    }
-
-                    #[derive(Deserialize)]
-                    struct Order {
-                        pub rewards: Vec<Reward>,
-                    }
-
-                    #[derive(Deserialize)]
-                    struct TremendousResponse {
-                        pub order: Order,
-                    }
-
-                    let res: TremendousResponse = payouts_queue
-                        .make_tremendous_request(
-                            Method::POST,
-                            "orders",
-                            Some(json! ({
-                                "payment": {
-                                    "funding_source_id": "BALANCE",
-                                },
-                                "rewards": [{
-                                    "value": {
-                                        "denomination": transfer
-                                    },
-                                    "delivery": {
-                                        "method": "EMAIL"
-                                    },
-                                    "recipient": {
-                                        "name": user.username,
-                                        "email": email
-                                    },
-                                    "products": [
-                                        &body.method_id,
-                                    ],
-                                    "campaign_id": dotenvy::var("TREMENDOUS_CAMPAIGN_ID")?,
-                                }]
-                            })),
-                        )
-                        .await?;
-
-                    if let Some(reward) = res.order.rewards.first() {
-                        payout_item.platform_id = Some(reward.id.clone())
-                    }
-
-                    payout_item
-                } else {
-                    return Err(ApiError::InvalidInput(
-                        "You must verify your account email to proceed!".to_string(),
-                    ));
-                }
-            } else {
-                return Err(ApiError::InvalidInput(
-                    "You must add an email to your account to proceed!".to_string(),
-                ));
-            }
-        }
-        PayoutMethodType::Unknown => {
-            return Err(ApiError::Payments(
-                "Invalid payment method specified!".to_string(),
-            ))
+            return Err(err);
         }
-    };
+    }
 
     sqlx::query!(
         "
@@ -610,7 +486,7 @@ pub async fn create_payout(
         SET balance = balance - $1
         WHERE id = $2
         ",
-        body.amount,
+        base_to_minor_units(body.amount),
         user.id as crate::database::models::ids::UserId
     )
     .execute(&mut *transaction)
@@ -618,77 +494,705 @@ pub async fn create_payout(
     payout_item.insert(&mut transaction).await?;
 
     transaction.commit().await?;
-    crate::database::models::User::clear_caches(&[(user.id, None)], &redis).await?;
 
-    Ok(HttpResponse::NoContent().finish())
+    // The quote is only used up once the transfer went through, so a failed withdrawal can be
+    // retried at the same rate
+    if let Some(quote_id) = &body.quote_id {
+        PayoutQuote::remove(quote_id, redis).await?;
+    }
+    crate::database::models::User::clear_caches(&[(user.id, None)], redis).await?;
+
+    Ok(payout_item)
 }
 
-#[delete("{id}")]
-pub async fn cancel_payout(
-    info: web::Path<(PayoutId,)>,
+/// Works out the fee and tax withholding of withdrawing `amount` through a payout method, and
+/// the transfer left to send
+async fn withdrawal_amounts(
+    user_id: crate::database::models::UserId,
+    amount: Decimal,
+    provider: &dyn PayoutProvider,
+    payout_method: &PayoutMethod,
+    pool: &PgPool,
+) -> Result<(Decimal, Decimal, Decimal), ApiError> {
+    let tax_form = TaxForm::get(user_id, pool).await?;
+    let withholding_rate = match tax_form {
+        Some(form) if form.status == TaxFormStatus::Complete => form.withholding_rate,
+        _ => {
+            return Err(ApiError::InvalidInput(
+                "You must complete your tax form before withdrawing!".to_string(),
+            ))
+        }
+    };
+
+    let fee = provider.quote_fee(payout_method, amount);
+    let withholding = (amount * withholding_rate).round_dp(2);
+
+    let transfer = (amount - fee - withholding).round_dp(2);
+    if transfer <= Decimal::ZERO {
+        return Err(ApiError::InvalidInput(
+            "You need to withdraw more to cover the fee!".to_string(),
+        ));
+    }
+
+    Ok((fee, withholding, transfer))
+}
+
+#[derive(Deserialize)]
+pub struct BalanceQuery {
+    pub currency: Option<String>,
+}
+
+/// Gets the user's balance in minor units, optionally converted into another currency at the
+/// current exchange rate
+#[get("balance")]
+pub async fn get_balance(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     redis: web::Data<RedisPool>,
-    payouts: web::Data<PayoutsQueue>,
     session_queue: web::Data<AuthQueue>,
+    payouts_queue: web::Data<PayoutsQueue>,
+    query: web::Query<BalanceQuery>,
 ) -> Result<HttpResponse, ApiError> {
     let user = get_user_from_headers(
         &req,
         &**pool,
         &redis,
         &session_queue,
-        Some(&[Scopes::PAYOUTS_WRITE]),
+        Some(&[Scopes::PAYOUTS_READ]),
     )
     .await?
     .1;
 
-    let id = info.into_inner().0;
-    let payout = crate::database::models::payout_item::Payout::get(id.into(), &**pool).await?;
+    let user = crate::database::models::User::get_id(user.id.into(), &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
 
-    if let Some(payout) = payout {
-        if payout.user_id != user.id.into() && !user.role.is_admin() {
-            return Ok(HttpResponse::NotFound().finish());
-        }
+    let available = user.balance_money();
+    let balance = user.balance_amount();
 
-        if let Some(platform_id) = payout.platform_id {
-            if let Some(method) = payout.method {
-                if payout.status != PayoutStatus::InTransit {
-                    return Err(ApiError::InvalidInput(
-                        "Payout cannot be cancelled!".to_string(),
-                    ));
-                }
+    let (converted, fx_rate) = if let Some(currency) = &query.currency {
+        let fx_rate = payouts_queue.get_fx_rate(currency).await?;
+        let converted = Money::convert(balance, currency, fx_rate)
+            .ok_or_else(|| ApiError::InvalidInput("Balance is out of range!".to_string()))?;
 
-                match method {
-                    PayoutMethodType::Venmo | PayoutMethodType::PayPal => {
-                        payouts
-                            .make_paypal_request::<(), ()>(
-                                Method::POST,
-                                &format!("payments/payouts-item/{}/cancel", platform_id),
-                                None,
-                                None,
-                                None,
-                            )
-                            .await?;
-                    }
-                    PayoutMethodType::Tremendous => {
-                        payouts
-                            .make_tremendous_request::<(), ()>(
-                                Method::POST,
-                                &format!("rewards/{}/cancel", platform_id),
-                                None,
-                            )
-                            .await?;
-                    }
-                    PayoutMethodType::Unknown => {
-                        return Err(ApiError::InvalidInput(
-                            "Payout cannot be cancelled!".to_string(),
-                        ))
-                    }
-                }
+        (Some(converted), Some(fx_rate))
+    } else {
+        (None, None)
+    };
 
-                let mut transaction = pool.begin().await?;
-                sqlx::query!(
-                    "
+    Ok(HttpResponse::Ok().json(PayoutBalance {
+        available,
+        converted,
+        fx_rate,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct QuoteRequest {
+    #[serde(with = "rust_decimal::serde::float")]
+    pub amount: Decimal,
+    pub method: PayoutMethodType,
+    pub method_id: String,
+    pub currency: String,
+}
+
+/// Quotes a withdrawal in another currency. The exchange rate is locked for
+/// `FX_QUOTE_LOCK_MINUTES`, and the quote is used by passing its ID when withdrawing.
+#[post("quote")]
+pub async fn quote_payout(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    body: web::Json<QuoteRequest>,
+    session_queue: web::Data<AuthQueue>,
+    payouts_queue: web::Data<PayoutsQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_WRITE]),
+    )
+    .await?
+    .1;
+
+    let user = crate::database::models::User::get_id(user.id.into(), &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if user.balance_amount() < body.amount || body.amount < Decimal::ZERO {
+        return Err(ApiError::InvalidInput(
+            "You do not have enough funds to make this payout!".to_string(),
+        ));
+    }
+
+    let currency = body.currency.to_uppercase();
+    let payout_method = payouts_queue
+        .get_payout_methods()
+        .await?
+        .into_iter()
+        .find(|x| x.id == body.method_id && x.type_ == body.method)
+        .ok_or_else(|| ApiError::InvalidInput("Invalid payment method specified!".to_string()))?;
+
+    if !payout_method.supported_currencies.contains(&currency) {
+        return Err(ApiError::InvalidInput(format!(
+            "This payment method cannot pay out in {currency}!"
+        )));
+    }
+
+    let provider = payouts_queue
+        .provider(body.method)
+        .ok_or_else(|| ApiError::Payments("Invalid payment method specified!".to_string()))?;
+    let (fee, withholding, transfer) =
+        withdrawal_amounts(user.id, body.amount, &*provider, &payout_method, &pool).await?;
+
+    let fx_rate = payouts_queue.get_fx_rate(&currency).await?;
+    let destination = Money::convert(transfer, &currency, fx_rate)
+        .ok_or_else(|| ApiError::InvalidInput("Invalid amount specified!".to_string()))?;
+
+    let quote = PayoutQuote {
+        user_id: user.id,
+        method: body.method,
+        method_id: body.method_id.clone(),
+        amount: body.amount,
+        fee,
+        withholding,
+        transfer,
+        currency,
+        destination_amount: destination.amount,
+        fx_rate,
+        expires: Utc::now()
+            + Duration::minutes(parse_var::<i64>("FX_QUOTE_LOCK_MINUTES").unwrap_or(10)),
+    };
+    let id = quote.insert(&redis).await?;
+
+    Ok(HttpResponse::Ok().json(crate::models::payouts::PayoutQuote::from(id, quote)))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct NewAdjustment {
+    pub user_id: crate::models::ids::UserId,
+    pub project_id: Option<ProjectId>,
+    /// Negative amounts are taken from the balance
+    #[serde(with = "rust_decimal::serde::float")]
+    pub amount: Decimal,
+    pub reason: AdjustmentReason,
+    #[validate(length(max = 2048))]
+    pub notes: Option<String>,
+    /// Whether the adjustment may leave the user with a negative balance
+    #[serde(default)]
+    pub allow_negative: bool,
+}
+
+/// Records an adjustment to a user's balance, such as a clawback of ad revenue or a reversal
+/// of fraudulent revenue, and notifies the user. Adjustments which would make the balance
+/// negative are rejected unless `allow_negative` is set. Admin only.
+#[post("adjustments")]
+pub async fn create_adjustment(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    body: web::Json<NewAdjustment>,
+    session_queue: web::Data<AuthQueue>,
+    payouts_queue: web::Data<PayoutsQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_WRITE]),
+    )
+    .await?
+    .1;
+
+    if !user.role.is_admin() {
+        return Err(ApiError::CustomAuthentication(
+            "You do not have the permissions to adjust balances!".to_string(),
+        ));
+    }
+
+    body.validate()?;
+
+    if body.amount.is_zero() || body.reason == AdjustmentReason::Unknown {
+        return Err(ApiError::InvalidInput(
+            "Adjustments must have an amount and a reason!".to_string(),
+        ));
+    }
+
+    let target = crate::database::models::User::get_id(body.user_id.into(), &**pool, &redis)
+        .await?
+        .ok_or_else(|| ApiError::InvalidInput("The specified user does not exist!".to_string()))?;
+
+    if let Some(project_id) = body.project_id {
+        let exists = sqlx::query!(
+            "SELECT EXISTS(SELECT 1 FROM mods WHERE id = $1)",
+            crate::database::models::ids::ProjectId::from(project_id).0,
+        )
+        .fetch_one(&**pool)
+        .await?
+        .exists
+        .unwrap_or(false);
+
+        if !exists {
+            return Err(ApiError::InvalidInput(
+                "The specified project does not exist!".to_string(),
+            ));
+        }
+    }
+
+    // Adjustments must not race with withdrawals checking the balance
+    let mtx = payouts_queue.lock_user_payouts(target.id.into());
+    let _guard = mtx.lock().await;
+
+    let mut transaction = pool.begin().await?;
+
+    let mut adjustment = PayoutAdjustment {
+        id: 0,
+        user_id: target.id,
+        project_id: body.project_id.map(|x| x.into()),
+        amount: body.amount,
+        reason: body.reason,
+        notes: body.notes.clone(),
+        created_by: Some(user.id.into()),
+        created: Utc::now(),
+    };
+    let balance = adjustment.insert(&mut transaction).await?;
+
+    if balance < Decimal::ZERO {
+        if !body.allow_negative {
+            return Err(ApiError::InvalidInput(format!(
+                "This adjustment would leave the user with a balance of {}. Set allow_negative to apply it anyway.",
+                balance.round_dp(2)
+            )));
+        }
+
+        log::warn!(
+            "Adjustment {} left user {} with a negative balance of {}",
+            adjustment.id,
+            target.id.0,
+            balance
+        );
+    }
+
+    NotificationBuilder {
+        body: NotificationBody::PayoutAdjusted {
+            amount: adjustment.amount,
+            reason: adjustment.reason,
+            balance,
+        },
+    }
+    .insert(target.id, &mut transaction, &redis)
+    .await?;
+
+    transaction.commit().await?;
+    crate::database::models::User::clear_caches(&[(target.id, None)], &redis).await?;
+
+    Ok(HttpResponse::Ok().json(crate::models::payouts::PayoutAdjustment::from(adjustment)))
+}
+
+/// Lists the adjustments made to the user's balance
+#[get("adjustments")]
+pub async fn user_adjustments(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_READ]),
+    )
+    .await?
+    .1;
+
+    let adjustments = PayoutAdjustment::get_all_for_user(user.id.into(), &**pool).await?;
+
+    Ok(HttpResponse::Ok().json(
+        adjustments
+            .into_iter()
+            .map(crate::models::payouts::PayoutAdjustment::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Checks which of the requirements to join the monetization program a user meets
+async fn monetization_eligibility(
+    user: &crate::database::models::User,
+    pool: &PgPool,
+) -> Result<MonetizationEligibility, ApiError> {
+    let min_age = parse_var::<i64>("MONETIZATION_MIN_ACCOUNT_AGE_DAYS").unwrap_or(30);
+    let tax_form = TaxForm::get(user.id, pool).await?;
+
+    Ok(MonetizationEligibility {
+        account_age: user.created <= Utc::now() - Duration::days(min_age),
+        two_factor: user.totp_secret.is_some(),
+        tax_form: tax_form
+            .map(|x| x.status == TaxFormStatus::Complete)
+            .unwrap_or(false),
+    })
+}
+
+/// Gets the user's enrollment in the monetization program, and which of its requirements they
+/// meet
+#[get("enrollment")]
+pub async fn get_enrollment(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_READ]),
+    )
+    .await?
+    .1;
+
+    let user = crate::database::models::User::get_id(user.id.into(), &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let enrollment = MonetizationEnrollment::get(user.id, &**pool).await?;
+    let eligibility = monetization_eligibility(&user, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(EnrollmentInfo {
+        enrollment: enrollment.map(crate::models::payouts::MonetizationEnrollment::from),
+        eligibility,
+    }))
+}
+
+/// Applies to the monetization program. The user must meet all of its requirements, and the
+/// application is then reviewed by an admin
+#[post("enrollment")]
+pub async fn apply_enrollment(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_WRITE]),
+    )
+    .await?
+    .1;
+
+    let user = crate::database::models::User::get_id(user.id.into(), &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if let Some(enrollment) = MonetizationEnrollment::get(user.id, &**pool).await? {
+        if !enrollment.status.can_apply() {
+            return Err(ApiError::InvalidInput(format!(
+                "You cannot apply while your enrollment is {}!",
+                enrollment.status
+            )));
+        }
+    }
+
+    let eligibility = monetization_eligibility(&user, &pool).await?;
+    if !eligibility.account_age {
+        return Err(ApiError::InvalidInput(
+            "Your account is too new to join the monetization program!".to_string(),
+        ));
+    }
+    if !eligibility.two_factor {
+        return Err(ApiError::InvalidInput(
+            "You must enable two-factor authentication to join the monetization program!"
+                .to_string(),
+        ));
+    }
+    if !eligibility.tax_form {
+        return Err(ApiError::InvalidInput(
+            "You must complete your tax form to join the monetization program!".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+    MonetizationEnrollment::apply(user.id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize)]
+pub struct EnrollmentFilter {
+    pub status: Option<EnrollmentStatus>,
+}
+
+/// Lists enrollments with a status, pending applications by default. Admin only
+#[get("enrollments")]
+pub async fn list_enrollments(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    filter: web::Query<EnrollmentFilter>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_READ]),
+    )
+    .await?
+    .1;
+
+    if !user.role.is_admin() {
+        return Err(ApiError::CustomAuthentication(
+            "You do not have the permissions to review enrollments!".to_string(),
+        ));
+    }
+
+    let enrollments = MonetizationEnrollment::get_by_status(
+        filter.status.unwrap_or(EnrollmentStatus::Pending),
+        &**pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(
+        enrollments
+            .into_iter()
+            .map(crate::models::payouts::MonetizationEnrollment::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EnrollmentReview {
+    pub status: EnrollmentStatus,
+    #[validate(length(max = 2048))]
+    pub notes: Option<String>,
+}
+
+/// Approves or rejects an application, or suspends or reinstates an enrolled user. Admin only
+#[post("enrollment/{id}/review")]
+pub async fn review_enrollment(
+    info: web::Path<(crate::models::ids::UserId,)>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    body: web::Json<EnrollmentReview>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_WRITE]),
+    )
+    .await?
+    .1;
+
+    if !user.role.is_admin() {
+        return Err(ApiError::CustomAuthentication(
+            "You do not have the permissions to review enrollments!".to_string(),
+        ));
+    }
+
+    body.validate()?;
+
+    let user_id = info.into_inner().0.into();
+    let enrollment = MonetizationEnrollment::get(user_id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if !enrollment.status.can_review_to(body.status) {
+        return Err(ApiError::InvalidInput(format!(
+            "An enrollment cannot go from {} to {}!",
+            enrollment.status, body.status
+        )));
+    }
+
+    let mut transaction = pool.begin().await?;
+    MonetizationEnrollment::review(
+        user_id,
+        body.status,
+        user.id.into(),
+        body.notes.as_deref(),
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("schedule")]
+pub async fn get_payout_schedule(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_READ]),
+    )
+    .await?
+    .1;
+
+    let schedule = PayoutSchedule::get(user.id.into(), &**pool).await?;
+
+    if let Some(schedule) = schedule {
+        Ok(HttpResponse::Ok().json(crate::models::payouts::PayoutSchedule::from(schedule)))
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct EditPayoutSchedule {
+    pub method: PayoutMethodType,
+    #[validate(length(min = 1, max = 255))]
+    pub method_id: String,
+    /// The balance is only withdrawn once it reaches this amount
+    #[serde(with = "rust_decimal::serde::float")]
+    pub threshold: Decimal,
+}
+
+/// Opts into automatic monthly payouts, or changes their settings
+#[put("schedule")]
+pub async fn edit_payout_schedule(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    body: web::Json<EditPayoutSchedule>,
+    session_queue: web::Data<AuthQueue>,
+    payouts_queue: web::Data<PayoutsQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_WRITE]),
+    )
+    .await?
+    .1;
+
+    body.validate()?;
+
+    let payout_method = payouts_queue
+        .get_payout_methods()
+        .await?
+        .into_iter()
+        .find(|x| x.id == body.method_id && x.type_ == body.method)
+        .ok_or_else(|| ApiError::InvalidInput("Invalid payment method specified!".to_string()))?;
+
+    let min = match &payout_method.interval {
+        PayoutInterval::Standard { min, .. } => *min,
+        PayoutInterval::Fixed { values } => values.first().map(|x| x.0).unwrap_or_default(),
+    };
+    if body.threshold - payout_method.fee.compute(body.threshold) < min {
+        return Err(ApiError::InvalidInput(
+            "The threshold must cover the minimum and fee of the payment method!".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+    PayoutSchedule {
+        user_id: user.id.into(),
+        method: body.method,
+        method_id: body.method_id.clone(),
+        threshold: body.threshold,
+        created: Utc::now(),
+        last_attempt: None,
+    }
+    .upsert(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("schedule")]
+pub async fn delete_payout_schedule(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_WRITE]),
+    )
+    .await?
+    .1;
+
+    let mut transaction = pool.begin().await?;
+    let result = PayoutSchedule::remove(user.id.into(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[delete("{id}")]
+pub async fn cancel_payout(
+    info: web::Path<(PayoutId,)>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    payouts: web::Data<PayoutsQueue>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_WRITE]),
+    )
+    .await?
+    .1;
+
+    let id = info.into_inner().0;
+    let payout = crate::database::models::payout_item::Payout::get(id.into(), &**pool).await?;
+
+    if let Some(payout) = payout {
+        if payout.user_id != user.id.into() && !user.role.is_admin() {
+            return Ok(HttpResponse::NotFound().finish());
+        }
+
+        if let Some(platform_id) = payout.platform_id {
+            if let Some(method) = payout.method {
+                if payout.status != PayoutStatus::InTransit {
+                    return Err(ApiError::InvalidInput(
+                        "Payout cannot be cancelled!".to_string(),
+                    ));
+                }
+
+                let provider = payouts.provider(method).ok_or_else(|| {
+                    ApiError::InvalidInput("Payout cannot be cancelled!".to_string())
+                })?;
+                provider.cancel_transfer(&platform_id).await?;
+
+                let mut transaction = pool.begin().await?;
+                sqlx::query!(
+                    "
                     UPDATE payouts
                     SET status = $1
                     WHERE platform_id = $2
@@ -716,6 +1220,298 @@ pub async fn cancel_payout(
     }
 }
 
+#[derive(Deserialize)]
+pub struct StatementQuery {
+    #[serde(default)]
+    pub format: StatementFormat,
+}
+
+/// Builds a statement of the revenue earned, payouts made and adjustments applied to a user's
+/// balance between `start` (exclusive) and `end` (inclusive). Failed and cancelled payouts are
+/// listed along with an adjustment refunding them, so the statement nets out to the change in
+/// balance over the period.
+async fn build_statement(
+    title: String,
+    user: &crate::database::models::User,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    payouts: Vec<crate::database::models::payout_item::Payout>,
+    pool: &PgPool,
+) -> Result<Statement, ApiError> {
+    let revenue = sqlx::query!(
+        "
+        SELECT pv.mod_id, m.name \"name?\", SUM(pv.amount) amount
+        FROM payouts_values pv
+        LEFT JOIN mods m ON m.id = pv.mod_id
+        WHERE pv.user_id = $1 AND pv.created > $2 AND pv.created <= $3
+        GROUP BY pv.mod_id, m.name
+        ORDER BY amount DESC
+        ",
+        user.id.0,
+        start,
+        end,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut lines = revenue
+        .into_iter()
+        .map(|x| StatementLine {
+            kind: StatementLineKind::Revenue,
+            date: None,
+            reference: x
+                .mod_id
+                .map(|x| ProjectId(x as u64).to_string())
+                .unwrap_or_default(),
+            description: x.name.unwrap_or_else(|| "Other revenue".to_string()),
+            amount: x.amount.unwrap_or_default(),
+        })
+        .collect::<Vec<_>>();
+
+    let adjustments = sqlx::query!(
+        "
+        SELECT id, mod_id, amount, reason, created
+        FROM payouts_adjustments
+        WHERE user_id = $1 AND created > $2 AND created <= $3
+        ORDER BY created ASC
+        ",
+        user.id.0,
+        start,
+        end,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    lines.extend(adjustments.into_iter().map(|x| {
+        StatementLine {
+            kind: StatementLineKind::Adjustment,
+            date: Some(x.created),
+            reference: x
+                .mod_id
+                .map(|x| ProjectId(x as u64).to_string())
+                .unwrap_or_else(|| x.id.to_string()),
+            description: AdjustmentReason::from_string(&x.reason)
+                .description()
+                .to_string(),
+            amount: x.amount,
+        }
+    }));
+
+    for payout in payouts {
+        let id = PayoutId::from(payout.id).to_string();
+        let method = payout
+            .method
+            .map(|x| x.as_str().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let fee = payout.fee.unwrap_or_default();
+        let withholding = payout.withholding.unwrap_or_default();
+
+        lines.push(StatementLine {
+            kind: StatementLineKind::Transfer,
+            date: Some(payout.created),
+            reference: id.clone(),
+            description: format!("Transfer via {method}"),
+            amount: -payout.amount,
+        });
+        if !fee.is_zero() {
+            lines.push(StatementLine {
+                kind: StatementLineKind::Fee,
+                date: Some(payout.created),
+                reference: id.clone(),
+                description: format!("{method} fee"),
+                amount: -fee,
+            });
+        }
+        if !withholding.is_zero() {
+            lines.push(StatementLine {
+                kind: StatementLineKind::Withholding,
+                date: Some(payout.created),
+                reference: id.clone(),
+                description: "Tax withholding".to_string(),
+                amount: -withholding,
+            });
+        }
+
+        if matches!(
+            payout.status,
+            PayoutStatus::Failed | PayoutStatus::Cancelled
+        ) {
+            lines.push(StatementLine {
+                kind: StatementLineKind::Adjustment,
+                date: Some(payout.created),
+                reference: id,
+                description: format!("Refund of {} payout", payout.status.as_str()),
+                amount: payout.amount + fee + withholding,
+            });
+        }
+    }
+
+    Ok(Statement {
+        title,
+        username: user.username.clone(),
+        period_start: start,
+        period_end: end,
+        lines,
+    })
+}
+
+fn statement_response(
+    statement: Statement,
+    format: StatementFormat,
+    filename: String,
+) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            actix_web::http::header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"{}.{}\"",
+                filename,
+                format.extension()
+            ),
+        ))
+        .body(statement.render(format))
+}
+
+/// Gets a statement for a single payout, itemizing the revenue earned since the previous
+/// payout and the fees and withholding taken from this one
+#[get("{id}/statement")]
+pub async fn payout_statement(
+    info: web::Path<(PayoutId,)>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    query: web::Query<StatementQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_READ]),
+    )
+    .await?
+    .1;
+
+    let id = info.into_inner().0;
+    let payout = crate::database::models::payout_item::Payout::get(id.into(), &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if payout.user_id != user.id.into() && !user.role.is_admin() {
+        return Err(ApiError::NotFound);
+    }
+
+    let owner = crate::database::models::User::get_id(payout.user_id, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let previous = sqlx::query!(
+        "
+        SELECT MAX(created) created
+        FROM payouts
+        WHERE user_id = $1 AND created < $2 AND NOT status = ANY($3)
+        ",
+        owner.id.0,
+        payout.created,
+        &[
+            PayoutStatus::Cancelled.as_str().to_string(),
+            PayoutStatus::Failed.as_str().to_string(),
+        ][..],
+    )
+    .fetch_one(&**pool)
+    .await?
+    .created
+    .unwrap_or(owner.created);
+
+    let end = payout.created;
+    let statement = build_statement(
+        format!("Payout statement {}", id),
+        &owner,
+        previous,
+        end,
+        vec![payout],
+        &pool,
+    )
+    .await?;
+
+    Ok(statement_response(
+        statement,
+        query.format,
+        format!("payout-{}", id),
+    ))
+}
+
+/// Gets a statement of all revenue and payouts of a calendar month
+#[get("statement/{year}/{month}")]
+pub async fn monthly_statement(
+    info: web::Path<(i32, u32)>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    query: web::Query<StatementQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PAYOUTS_READ]),
+    )
+    .await?
+    .1;
+
+    let (year, month) = info.into_inner();
+    let start = NaiveDate::from_ymd_opt(year, month, 1)
+        .ok_or_else(|| ApiError::InvalidInput("Invalid statement month!".to_string()))?;
+    let end = start
+        .checked_add_months(Months::new(1))
+        .ok_or_else(|| ApiError::InvalidInput("Invalid statement month!".to_string()))?;
+    let start = Utc.from_utc_datetime(&start.and_time(NaiveTime::MIN));
+    let end = Utc.from_utc_datetime(&end.and_time(NaiveTime::MIN));
+
+    let user = crate::database::models::User::get_id(user.id.into(), &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let payout_ids = sqlx::query!(
+        "
+        SELECT id
+        FROM payouts
+        WHERE user_id = $1 AND created > $2 AND created <= $3
+        ",
+        user.id.0,
+        start,
+        end,
+    )
+    .fetch_all(&**pool)
+    .await?
+    .into_iter()
+    .map(|x| crate::database::models::ids::PayoutId(x.id))
+    .collect::<Vec<_>>();
+    let mut payouts =
+        crate::database::models::payout_item::Payout::get_many(&payout_ids, &**pool).await?;
+    payouts.sort_by_key(|x| x.created);
+
+    let statement = build_statement(
+        format!("Monthly statement {year}-{month:02}"),
+        &user,
+        start,
+        end,
+        payouts,
+        &pool,
+    )
+    .await?;
+
+    Ok(statement_response(
+        statement,
+        query.format,
+        format!("statement-{year}-{month:02}"),
+    ))
+}
+
 #[derive(Deserialize)]
 pub struct MethodFilter {
     pub country: Option<String>,
@@ -0,0 +1,177 @@
+use crate::auth::checks::filter_visible_project_ids;
+use crate::auth::get_user_from_headers;
+use crate::database::models::{self, TeamMember};
+use crate::database::redis::RedisPool;
+use crate::models::ids::{OrganizationId, ProjectId, UserId};
+use crate::models::pats::Scopes;
+use crate::models::teams::{OrganizationPermissions, ProjectPermissions};
+use crate::models::users::Role;
+use crate::queue::session::AuthQueue;
+use crate::routes::ApiError;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use validator::Validate;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("permissions/check", web::post().to(permissions_check));
+}
+
+#[derive(Deserialize, Validate)]
+pub struct PermissionsCheck {
+    /// The user whose permissions are checked, the user making the request unless given.
+    /// Only moderators may check other users
+    pub user_id: Option<UserId>,
+    #[serde(default)]
+    #[validate(length(max = 100))]
+    pub projects: Vec<String>,
+    #[serde(default)]
+    #[validate(length(max = 100))]
+    pub organizations: Vec<String>,
+    /// The permissions checked for each project
+    #[serde(default)]
+    pub project_permissions: ProjectPermissions,
+    /// The permissions checked for each organization
+    #[serde(default)]
+    pub organization_permissions: OrganizationPermissions,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PermissionCheckResult<T> {
+    /// All the permissions the user has
+    pub permissions: T,
+    /// Whether the user has every permission which was checked
+    pub allowed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PermissionsCheckResults {
+    pub projects: HashMap<ProjectId, PermissionCheckResult<ProjectPermissions>>,
+    pub organizations: HashMap<OrganizationId, PermissionCheckResult<OrganizationPermissions>>,
+}
+
+/// Checks a user's permissions for many projects and organizations at once. Projects the user
+/// making the request cannot see, and projects and organizations which do not exist, are left
+/// out of the results
+pub async fn permissions_check(
+    req: HttpRequest,
+    check: web::Json<PermissionsCheck>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    check.validate()?;
+
+    let mut scopes = Vec::new();
+    if !check.projects.is_empty() {
+        scopes.push(Scopes::PROJECT_READ);
+    }
+    if !check.organizations.is_empty() {
+        scopes.push(Scopes::ORGANIZATION_READ);
+    }
+    let current_user = get_user_from_headers(&req, &**pool, &redis, &session_queue, Some(&scopes))
+        .await?
+        .1;
+
+    let (user_id, role) = match check.user_id {
+        Some(user_id) if user_id != current_user.id => {
+            if !current_user.role.is_mod() {
+                return Err(ApiError::CustomAuthentication(
+                    "You don't have permission to check other users' permissions.".to_string(),
+                ));
+            }
+
+            let user = models::User::get_id(user_id.into(), &**pool, &redis)
+                .await?
+                .ok_or_else(|| {
+                    ApiError::InvalidInput("The specified user does not exist!".to_string())
+                })?;
+            (user.id, Role::from_string(&user.role))
+        }
+        _ => (current_user.id.into(), current_user.role.clone()),
+    };
+
+    let projects = models::Project::get_many(&check.projects, &**pool, &redis).await?;
+    let visible = filter_visible_project_ids(
+        projects.iter().map(|x| &x.inner).collect(),
+        &Some(current_user),
+        &pool,
+    )
+    .await?;
+    let projects = projects
+        .into_iter()
+        .filter(|x| visible.contains(&x.inner.id))
+        .collect::<Vec<_>>();
+
+    let mut organization_ids = projects
+        .iter()
+        .filter_map(|x| x.inner.organization_id)
+        .collect::<Vec<_>>();
+    let organizations =
+        models::Organization::get_many(&check.organizations, &**pool, &redis).await?;
+    organization_ids.retain(|x| !organizations.iter().any(|y| y.id == *x));
+    let project_organizations =
+        models::Organization::get_many_ids(&organization_ids, &**pool, &redis).await?;
+
+    let team_ids = projects
+        .iter()
+        .map(|x| x.inner.team_id)
+        .chain(organizations.iter().map(|x| x.team_id))
+        .chain(project_organizations.iter().map(|x| x.team_id))
+        .collect::<Vec<_>>();
+    let members = TeamMember::get_from_user_id_many(&team_ids, user_id, &**pool).await?;
+    let member = |team_id: models::TeamId| members.iter().find(|x| x.team_id == team_id).cloned();
+
+    let results = PermissionsCheckResults {
+        projects: projects
+            .iter()
+            .map(|project| {
+                let organization_member = project
+                    .inner
+                    .organization_id
+                    .and_then(|id| {
+                        organizations
+                            .iter()
+                            .chain(project_organizations.iter())
+                            .find(|x| x.id == id)
+                    })
+                    .and_then(|x| member(x.team_id));
+                let permissions = ProjectPermissions::get_permissions_by_role(
+                    &role,
+                    &member(project.inner.team_id),
+                    &organization_member,
+                )
+                .unwrap_or_default();
+
+                (
+                    project.inner.id.into(),
+                    PermissionCheckResult {
+                        permissions,
+                        allowed: permissions.contains(check.project_permissions),
+                    },
+                )
+            })
+            .collect(),
+        organizations: organizations
+            .iter()
+            .map(|organization| {
+                let permissions = OrganizationPermissions::get_permissions_by_role(
+                    &role,
+                    &member(organization.team_id),
+                )
+                .unwrap_or_default();
+
+                (
+                    organization.id.into(),
+                    PermissionCheckResult {
+                        permissions,
+                        allowed: permissions.contains(check.organization_permissions),
+                    },
+                )
+            })
+            .collect(),
+    };
+
+    Ok(HttpResponse::Ok().json(results))
+}
@@ -1,6 +1,7 @@
 use super::version_creation::{try_create_version_fields, InitialVersionData};
 use crate::auth::{get_user_from_headers, AuthenticationError};
 use crate::database::models::loader_fields::{Loader, LoaderField, LoaderFieldEnumValue};
+use crate::database::models::spam_flag_item::SpamFlag;
 use crate::database::models::thread_item::ThreadBuilder;
 use crate::database::models::{self, image_item, User};
 use crate::database::redis::RedisPool;
@@ -8,6 +9,7 @@ use crate::file_hosting::{FileHost, FileHostingError};
 use crate::models::error::ApiError;
 use crate::models::ids::{ImageId, OrganizationId};
 use crate::models::images::{Image, ImageContext};
+use crate::models::moderation::ImageSource;
 use crate::models::pats::Scopes;
 use crate::models::projects::{
     License, Link, MonetizationStatus, ProjectId, ProjectStatus, VersionId, VersionStatus,
@@ -16,13 +18,19 @@ use crate::models::teams::ProjectPermissions;
 use crate::models::threads::ThreadType;
 use crate::models::users::UserId;
 use crate::queue::session::AuthQueue;
+use crate::routes::ErrorCode;
 use crate::search::indexing::IndexingError;
+use crate::util::curseforge::CurseForgeError;
+use crate::util::image_scan;
 use crate::util::routes::read_from_field;
-use crate::util::validate::validation_errors_to_string;
+use crate::util::spam::{self, SpamVerdict};
+use crate::util::upload_progress::UploadTracker;
+use crate::util::validate::{validation_errors_to_fields, validation_errors_to_string};
 use actix_multipart::{Field, Multipart};
 use actix_web::http::StatusCode;
 use actix_web::web::{self, Data};
 use actix_web::{HttpRequest, HttpResponse};
+use bytes::BytesMut;
 use chrono::Utc;
 use futures::stream::StreamExt;
 use image::ImageError;
@@ -55,6 +63,8 @@ pub enum CreateError {
     SerDeError(#[from] serde_json::Error),
     #[error("Error while validating input: {0}")]
     ValidationError(String),
+    #[error("Error while validating input: {}", validation_errors_to_string(.0.clone(), None))]
+    InvalidFields(#[from] validator::ValidationErrors),
     #[error("Error while uploading file: {0}")]
     FileHostingError(#[from] FileHostingError),
     #[error("Error while validating uploaded file: {0}")]
@@ -83,6 +93,12 @@ pub enum CreateError {
     ImageError(#[from] ImageError),
     #[error("Reroute Error: {0}")]
     RerouteError(#[from] reqwest::Error),
+    #[error("Import Error: {0}")]
+    ImportError(#[from] CurseForgeError),
+    #[error("Too much activity from this account or network. Try again later")]
+    Throttled,
+    #[error("{0}")]
+    ContentMismatch(#[from] crate::util::magic::ContentMismatch),
 }
 
 impl actix_web::ResponseError for CreateError {
@@ -106,42 +122,61 @@ impl actix_web::ResponseError for CreateError {
             CreateError::CustomAuthenticationError(..) => StatusCode::UNAUTHORIZED,
             CreateError::SlugCollision => StatusCode::BAD_REQUEST,
             CreateError::ValidationError(..) => StatusCode::BAD_REQUEST,
+            CreateError::InvalidFields(..) => StatusCode::BAD_REQUEST,
             CreateError::FileValidationError(..) => StatusCode::BAD_REQUEST,
             CreateError::ImageError(..) => StatusCode::BAD_REQUEST,
             CreateError::RerouteError(..) => StatusCode::INTERNAL_SERVER_ERROR,
+            CreateError::ImportError(..) => StatusCode::INTERNAL_SERVER_ERROR,
+            CreateError::Throttled => StatusCode::TOO_MANY_REQUESTS,
+            CreateError::ContentMismatch(..) => StatusCode::BAD_REQUEST,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
         HttpResponse::build(self.status_code()).json(ApiError {
-            error: match self {
-                CreateError::EnvError(..) => "environment_error",
-                CreateError::SqlxDatabaseError(..) => "database_error",
-                CreateError::DatabaseError(..) => "database_error",
-                CreateError::IndexingError(..) => "indexing_error",
-                CreateError::FileHostingError(..) => "file_hosting_error",
-                CreateError::SerDeError(..) => "invalid_input",
-                CreateError::MultipartError(..) => "invalid_input",
-                CreateError::MissingValueError(..) => "invalid_input",
-                CreateError::InvalidIconFormat(..) => "invalid_input",
-                CreateError::InvalidInput(..) => "invalid_input",
-                CreateError::InvalidGameVersion(..) => "invalid_input",
-                CreateError::InvalidLoader(..) => "invalid_input",
-                CreateError::InvalidCategory(..) => "invalid_input",
-                CreateError::InvalidFileType(..) => "invalid_input",
-                CreateError::Unauthorized(..) => "unauthorized",
-                CreateError::CustomAuthenticationError(..) => "unauthorized",
-                CreateError::SlugCollision => "invalid_input",
-                CreateError::ValidationError(..) => "invalid_input",
-                CreateError::FileValidationError(..) => "invalid_input",
-                CreateError::ImageError(..) => "invalid_image",
-                CreateError::RerouteError(..) => "reroute_error",
-            },
+            error: self.code().as_str(),
             description: &self.to_string(),
+            fields: match self {
+                CreateError::InvalidFields(errors) => validation_errors_to_fields(errors),
+                _ => Vec::new(),
+            },
         })
     }
 }
 
+impl CreateError {
+    /// The stable code this error is reported with
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CreateError::EnvError(..) => ErrorCode::EnvironmentError,
+            CreateError::SqlxDatabaseError(..) => ErrorCode::DatabaseError,
+            CreateError::DatabaseError(..) => ErrorCode::DatabaseError,
+            CreateError::IndexingError(..) => ErrorCode::IndexingError,
+            CreateError::FileHostingError(..) => ErrorCode::FileHostingError,
+            CreateError::SerDeError(..) => ErrorCode::InvalidInput,
+            CreateError::MultipartError(..) => ErrorCode::InvalidInput,
+            CreateError::MissingValueError(..) => ErrorCode::InvalidInput,
+            CreateError::InvalidIconFormat(..) => ErrorCode::InvalidInput,
+            CreateError::InvalidInput(..) => ErrorCode::InvalidInput,
+            CreateError::InvalidGameVersion(..) => ErrorCode::InvalidInput,
+            CreateError::InvalidLoader(..) => ErrorCode::InvalidInput,
+            CreateError::InvalidCategory(..) => ErrorCode::InvalidInput,
+            CreateError::InvalidFileType(..) => ErrorCode::InvalidInput,
+            CreateError::Unauthorized(..) => ErrorCode::Unauthorized,
+            CreateError::CustomAuthenticationError(..) => ErrorCode::Unauthorized,
+            CreateError::SlugCollision => ErrorCode::InvalidInput,
+            CreateError::ValidationError(..) => ErrorCode::InvalidInput,
+            CreateError::InvalidFields(..) => ErrorCode::InvalidInput,
+            CreateError::FileValidationError(..) => ErrorCode::InvalidInput,
+            CreateError::ImageError(..) => ErrorCode::InvalidImage,
+            CreateError::RerouteError(..) => ErrorCode::RerouteError,
+            CreateError::ImportError(..) => ErrorCode::NetworkError,
+            CreateError::Throttled => ErrorCode::Throttled,
+            CreateError::ContentMismatch(..) => ErrorCode::ContentMismatch,
+        }
+    }
+}
+
 pub fn default_project_type() -> String {
     "mod".to_string()
 }
@@ -238,7 +273,7 @@ pub struct UploadedFile {
 }
 
 pub async fn undo_uploads(
-    file_host: &dyn FileHost,
+    file_host: &(dyn FileHost + Send + Sync),
     uploaded_files: &[UploadedFile],
 ) -> Result<(), CreateError> {
     for file in uploaded_files {
@@ -259,6 +294,7 @@ pub async fn project_create(
 ) -> Result<HttpResponse, CreateError> {
     let mut transaction = client.begin().await?;
     let mut uploaded_files = Vec::new();
+    let progress = UploadTracker::from_request(&req);
 
     let result = project_create_inner(
         req,
@@ -269,10 +305,13 @@ pub async fn project_create(
         &client,
         &redis,
         &session_queue,
+        &progress,
     )
     .await;
 
-    if result.is_err() {
+    if let Err(err) = &result {
+        progress.failed(err).await;
+
         let undo_result = undo_uploads(&***file_host, &uploaded_files).await;
         let rollback_result = transaction.rollback().await;
 
@@ -282,6 +321,7 @@ pub async fn project_create(
         }
     } else {
         transaction.commit().await?;
+        progress.published().await;
     }
 
     result
@@ -321,11 +361,12 @@ async fn project_create_inner(
     req: HttpRequest,
     payload: &mut Multipart,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-    file_host: &dyn FileHost,
+    file_host: &(dyn FileHost + Send + Sync),
     uploaded_files: &mut Vec<UploadedFile>,
     pool: &PgPool,
     redis: &RedisPool,
     session_queue: &AuthQueue,
+    progress: &UploadTracker,
 ) -> Result<HttpResponse, CreateError> {
     // The base URL for files uploaded to backblaze
     let cdn_url = dotenvy::var("CDN_URL")?;
@@ -348,6 +389,9 @@ async fn project_create_inner(
     let mut versions;
     let mut versions_map = std::collections::HashMap::new();
     let mut gallery_urls = Vec::new();
+    // The perceptual hash of each gallery image, by URL
+    let mut gallery_hashes = HashMap::new();
+    let spam_score;
     {
         // The first multipart field must be named "data" and contain a
         // JSON `ProjectCreateData` object.
@@ -379,9 +423,19 @@ async fn project_create_inner(
         }
         let create_data: ProjectCreateData = serde_json::from_slice(&data)?;
 
-        create_data
-            .validate()
-            .map_err(|err| CreateError::InvalidInput(validation_errors_to_string(err, None)))?;
+        create_data.validate()?;
+
+        spam_score = spam::score_submission(
+            current_user.id.into(),
+            current_user.email.as_deref(),
+            current_user.created,
+            &[&create_data.summary, &create_data.description],
+            pool,
+        )
+        .await?;
+        if spam::verdict(&spam_score) == SpamVerdict::Throttle {
+            return Err(CreateError::Throttled);
+        }
 
         let slug_project_id_option: Option<ProjectId> =
             serde_json::from_str(&format!("\"{}\"", create_data.slug)).ok();
@@ -422,6 +476,12 @@ async fn project_create_inner(
         // Create VersionBuilders for the versions specified in `initial_versions`
         versions = Vec::with_capacity(create_data.initial_versions.len());
         for (i, data) in create_data.initial_versions.iter().enumerate() {
+            if data.file_parts.is_empty() || !data.file_urls.is_empty() {
+                return Err(CreateError::InvalidInput(String::from(
+                    "Initial versions must have their files uploaded",
+                )));
+            }
+
             // Create a map of multipart field names to version indices
             for name in &data.file_parts {
                 if versions_map.insert(name.to_owned(), i).is_some() {
@@ -507,21 +567,40 @@ async fn project_create_inner(
                         .ok_or_else(|| {
                             CreateError::InvalidIconFormat(file_extension.to_string())
                         })?;
-                    let url = format!("data/{project_id}/images/{hash}.{file_extension}");
+                    crate::util::magic::check_content(&data, file_extension, content_type)?;
+                    let path = format!("data/{project_id}/images/{hash}");
+                    let url = format!("{path}.{file_extension}");
+                    let data = data.freeze();
                     let upload_data = file_host
-                        .upload_file(content_type, &url, data.freeze())
+                        .upload_file(content_type, &url, data.clone())
                         .await?;
                     uploaded_files.push(UploadedFile {
                         file_id: upload_data.file_id,
                         file_name: upload_data.file_name,
                     });
+                    if let Some(hash) = crate::util::img::get_perceptual_hash(data.clone()).await {
+                        gallery_hashes.insert(format!("{cdn_url}/{url}"), hash);
+                    }
+                    let renditions = crate::util::img::upload_renditions(
+                        file_host,
+                        data,
+                        &path,
+                        crate::util::img::MAX_GALLERY_DIMENSION,
+                    )
+                    .await?;
+                    uploaded_files.extend(renditions.iter().map(|x| UploadedFile {
+                        file_id: x.file_id.clone(),
+                        file_name: x.file_name.clone(),
+                    }));
                     gallery_urls.push(crate::models::projects::GalleryItem {
+                        image_id: Some(models::generate_image_id(transaction).await?.into()),
                         url: format!("{cdn_url}/{url}"),
                         featured: item.featured,
                         name: item.name.clone(),
                         description: item.description.clone(),
                         created: Utc::now(),
                         ordering: item.ordering,
+                        renditions: crate::util::img::rendition_urls(&renditions, &cdn_url),
                     });
                     return Ok(());
                 }
@@ -557,6 +636,7 @@ async fn project_create_inner(
                 None,
                 transaction,
                 redis,
+                progress,
             )
             .await?;
 
@@ -687,6 +767,8 @@ async fn project_create_inner(
             summary: project_create_data.summary,
             description: project_create_data.description,
             icon_url: icon_data.clone().map(|x| x.0),
+            icon_renditions: icon_data.clone().map(|x| x.2).unwrap_or_default(),
+            icon_perceptual_hash: icon_data.clone().and_then(|x| x.3),
 
             license_url: project_create_data.license_url,
             categories,
@@ -700,12 +782,15 @@ async fn project_create_inner(
             gallery_items: gallery_urls
                 .iter()
                 .map(|x| models::project_item::GalleryItem {
+                    image_id: x.image_id.map(|x| x.into()),
                     image_url: x.url.clone(),
                     featured: x.featured,
                     name: x.name.clone(),
                     description: x.description.clone(),
                     created: x.created,
                     ordering: x.ordering,
+                    renditions: x.renditions.clone(),
+                    perceptual_hash: gallery_hashes.get(&x.url).copied(),
                 })
                 .collect(),
             color: icon_data.and_then(|x| x.1),
@@ -718,6 +803,33 @@ async fn project_create_inner(
         let id = project_builder_actual.insert(&mut *transaction).await?;
         User::clear_project_cache(&[current_user.id.into()], redis).await?;
 
+        if spam::verdict(&spam_score) == SpamVerdict::Review {
+            SpamFlag::insert(
+                current_user.id.into(),
+                Some(id),
+                &spam_score,
+                &mut **transaction,
+            )
+            .await?;
+        }
+
+        if let Some(icon_url) = &project_builder.icon_url {
+            image_scan::queue_scan(
+                icon_url,
+                ImageSource::ProjectIcon { project_id },
+                &mut **transaction,
+            )
+            .await?;
+        }
+        for gallery_item in &project_builder.gallery_items {
+            image_scan::queue_scan(
+                &gallery_item.image_url,
+                ImageSource::GalleryImage { project_id },
+                &mut **transaction,
+            )
+            .await?;
+        }
+
         for image_id in project_create_data.uploaded_images {
             if let Some(db_image) =
                 image_item::Image::get(image_id.into(), &mut **transaction, redis).await?
@@ -815,6 +927,7 @@ async fn project_create_inner(
                 .map(|v| v.version_id.into())
                 .collect::<Vec<_>>(),
             icon_url: project_builder.icon_url.clone(),
+            icon_renditions: project_builder.icon_renditions.clone(),
             link_urls: project_builder
                 .link_urls
                 .clone()
@@ -846,9 +959,7 @@ async fn create_initial_version(
         )));
     }
 
-    version_data
-        .validate()
-        .map_err(|err| CreateError::ValidationError(validation_errors_to_string(err, None)))?;
+    version_data.validate()?;
 
     // Randomly generate a new id to be used for the version
     let version_id: VersionId = models::generate_version_id(transaction).await?.into();
@@ -913,21 +1024,38 @@ async fn process_icon_upload(
     uploaded_files: &mut Vec<UploadedFile>,
     id: u64,
     file_extension: &str,
-    file_host: &dyn FileHost,
+    file_host: &(dyn FileHost + Send + Sync),
     mut field: Field,
     cdn_url: &str,
-) -> Result<(String, Option<u32>), CreateError> {
-    if let Some(content_type) = crate::util::ext::get_image_content_type(file_extension) {
-        let data = read_from_field(&mut field, 262144, "Icons must be smaller than 256KiB").await?;
+) -> Result<(String, Option<u32>, HashMap<String, String>, Option<i64>), CreateError> {
+    if let Some(content_type) = crate::util::ext::get_icon_content_type(file_extension) {
+        let data = read_from_field(
+            &mut field,
+            crate::util::img::MAX_ANIMATED_ICON_SIZE,
+            "Icons must be smaller than 1MiB",
+        )
+        .await?;
+        crate::util::magic::check_content(&data, file_extension, content_type)?;
+        let data = if content_type == "image/svg+xml" {
+            let svg = crate::util::svg::sanitize_svg(&data).map_err(CreateError::InvalidInput)?;
+            BytesMut::from(svg.as_bytes())
+        } else {
+            data
+        };
+        let animation = crate::util::img::get_animation(&data)?;
+        crate::util::img::check_icon(&data, animation.as_ref())
+            .map_err(CreateError::InvalidInput)?;
 
         let color = crate::util::img::get_color_from_img(&data)?;
 
         let hash = sha1::Sha1::from(&data).hexdigest();
+        let path = format!("data/{id}/{hash}");
+        let data = data.freeze();
         let upload_data = file_host
             .upload_file(
                 content_type,
-                &format!("data/{id}/{hash}.{file_extension}"),
-                data.freeze(),
+                &format!("{path}.{file_extension}"),
+                data.clone(),
             )
             .await?;
 
@@ -936,7 +1064,25 @@ async fn process_icon_upload(
             file_name: upload_data.file_name.clone(),
         });
 
-        Ok((format!("{}/{}", cdn_url, upload_data.file_name), color))
+        let perceptual_hash = crate::util::img::get_perceptual_hash(data.clone()).await;
+        let renditions = crate::util::img::upload_renditions(
+            file_host,
+            data,
+            &path,
+            crate::util::img::MAX_ICON_DIMENSION,
+        )
+        .await?;
+        uploaded_files.extend(renditions.iter().map(|x| UploadedFile {
+            file_id: x.file_id.clone(),
+            file_name: x.file_name.clone(),
+        }));
+
+        Ok((
+            format!("{}/{}", cdn_url, upload_data.file_name),
+            color,
+            crate::util::img::rendition_urls(&renditions, cdn_url),
+            perceptual_hash,
+        ))
     } else {
         Err(CreateError::InvalidIconFormat(file_extension.to_string()))
     }
@@ -0,0 +1,449 @@
+use super::project_creation::CreateError;
+use crate::auth::get_user_from_headers;
+use crate::database::models::categories::{Category, LinkPlatform};
+use crate::database::models::github_link_item::GitHubLink;
+use crate::database::models::job_item::Job;
+use crate::database::models::loader_fields::{LoaderField, LoaderFieldEnumValue};
+use crate::database::models::thread_item::ThreadBuilder;
+use crate::database::models::{self, User};
+use crate::database::redis::RedisPool;
+use crate::models::jobs::JobPayload;
+use crate::models::pats::Scopes;
+use crate::models::projects::{Loader, MonetizationStatus, Project, ProjectStatus, VersionId};
+use crate::models::teams::ProjectPermissions;
+use crate::models::threads::ThreadType;
+use crate::queue::session::AuthQueue;
+use crate::routes::v3::version_creation::try_create_version_fields;
+use crate::routes::ApiError;
+use crate::util::curseforge::{self, CurseForgeError};
+use crate::util::github::{self, GitHubError};
+use crate::util::validate::RE_URL_SAFE;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use validator::Validate;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route(
+        "project/import/curseforge",
+        web::post().to(project_import_curseforge),
+    );
+    cfg.service(
+        web::resource("project/{id}/github")
+            .route(web::get().to(github_link_get))
+            .route(web::put().to(github_link_edit))
+            .route(web::delete().to(github_link_delete)),
+    );
+}
+
+#[derive(Deserialize, Validate)]
+pub struct CurseForgeImportData {
+    /// The ID of the project on CurseForge
+    pub curseforge_id: u32,
+    #[validate(
+        length(min = 3, max = 64),
+        regex = "crate::util::validate::RE_URL_SAFE"
+    )]
+    /// The slug of the new project. Defaults to the project's slug on CurseForge
+    pub slug: Option<String>,
+    /// Whether the project's files are copied into versions of the new project
+    #[serde(default)]
+    pub rehost_files: bool,
+}
+
+/// Creates a draft project from a CurseForge project. To prove they own the project, users put
+/// the verification code they are given into its description on CurseForge first. The icon,
+/// gallery and files are copied over in the background
+pub async fn project_import_curseforge(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    web::Json(import_data): web::Json<CurseForgeImportData>,
+) -> Result<HttpResponse, CreateError> {
+    let current_user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PROJECT_CREATE]),
+    )
+    .await?
+    .1;
+
+    import_data.validate()?;
+
+    if !curseforge::is_enabled() {
+        return Err(CreateError::InvalidInput(
+            "Importing from CurseForge is not available".to_string(),
+        ));
+    }
+
+    let curseforge_id = import_data.curseforge_id;
+    let curseforge_project = curseforge::get_project(curseforge_id)
+        .await
+        .map_err(not_found)?;
+    let description = curseforge::get_description(curseforge_id)
+        .await
+        .map_err(not_found)?;
+
+    let code = curseforge::verification_code(current_user.id, curseforge_id);
+    if !description.contains(&code) {
+        return Err(CreateError::InvalidInput(format!(
+            "Add {code} to the project's description on CurseForge to prove you own it, then try again"
+        )));
+    }
+
+    let slug = import_data
+        .slug
+        .unwrap_or(curseforge_project.slug)
+        .to_lowercase();
+    if slug.len() < 3 || slug.len() > 64 || !RE_URL_SAFE.is_match(&slug) {
+        return Err(CreateError::InvalidInput(
+            "The project's slug on CurseForge can't be used, so a slug must be given".to_string(),
+        ));
+    }
+    let slug_taken = sqlx::query!(
+        "
+        SELECT EXISTS(SELECT 1 FROM mods WHERE slug = LOWER($1))
+        ",
+        slug
+    )
+    .fetch_one(&**pool)
+    .await?
+    .exists
+    .unwrap_or(false);
+    if slug_taken {
+        return Err(CreateError::SlugCollision);
+    }
+
+    let mut transaction = pool.begin().await?;
+    let project_id = models::generate_project_id(&mut transaction).await?;
+
+    // Categories are matched by name, and the ones Modrinth doesn't have are left out
+    let mut categories = Vec::new();
+    for category in &curseforge_project.categories {
+        categories.extend(
+            Category::get_ids(&category.slug, &mut *transaction)
+                .await?
+                .into_values(),
+        );
+    }
+    categories.sort_by_key(|x| x.0);
+    categories.dedup();
+
+    let link_platforms = LinkPlatform::list(&mut *transaction, &redis).await?;
+    let links = &curseforge_project.links;
+    let link_urls = [
+        ("source", &links.source_url),
+        ("issues", &links.issues_url),
+        ("wiki", &links.wiki_url),
+    ]
+    .iter()
+    .filter_map(|(platform, url)| {
+        let link_platform = link_platforms.iter().find(|x| x.name == *platform)?;
+        Some(models::project_item::LinkUrl {
+            platform_id: link_platform.id,
+            platform_name: link_platform.name.clone(),
+            url: (*url).clone().filter(|x| !x.is_empty())?,
+            donation: link_platform.donation,
+        })
+    })
+    .collect();
+
+    let team_id = models::team_item::TeamBuilder {
+        members: vec![models::team_item::TeamMemberBuilder {
+            user_id: current_user.id.into(),
+            role: crate::models::teams::OWNER_ROLE.to_owned(),
+            is_owner: true,
+            permissions: ProjectPermissions::all(),
+            organization_permissions: None,
+            accepted: true,
+            payouts_split: Decimal::ONE_HUNDRED,
+            ordering: 0,
+        }],
+    }
+    .insert(&mut transaction)
+    .await?;
+
+    models::project_item::ProjectBuilder {
+        project_id,
+        team_id,
+        organization_id: None,
+        name: truncate(&curseforge_project.name, 64),
+        summary: truncate(&curseforge_project.summary, 255),
+        description: truncate(description.replace(&code, "").trim(), 65536),
+        icon_url: None,
+        icon_renditions: Default::default(),
+        icon_perceptual_hash: None,
+        license_url: None,
+        categories,
+        additional_categories: Vec::new(),
+        initial_versions: Vec::new(),
+        status: ProjectStatus::Draft,
+        requested_status: Some(ProjectStatus::Approved),
+        // CurseForge's API doesn't say which license a project uses
+        license: "LicenseRef-All-Rights-Reserved".to_string(),
+        slug: Some(slug),
+        link_urls,
+        gallery_items: Vec::new(),
+        color: None,
+        monetization_status: MonetizationStatus::Monetized,
+    }
+    .insert(&mut transaction)
+    .await?;
+
+    ThreadBuilder {
+        type_: ThreadType::Project,
+        members: vec![],
+        project_id: Some(project_id),
+        report_id: None,
+    }
+    .insert(&mut transaction)
+    .await?;
+
+    Job::enqueue(
+        JobPayload::ImportCurseForgeProject {
+            project_id: project_id.into(),
+            user_id: current_user.id,
+            curseforge_id,
+            rehost_files: import_data.rehost_files,
+        },
+        Utc::now(),
+        &mut *transaction,
+    )
+    .await?;
+
+    transaction.commit().await?;
+    User::clear_project_cache(&[current_user.id.into()], &redis).await?;
+
+    let project = models::Project::get_id(project_id, &**pool, &redis)
+        .await?
+        .ok_or_else(|| CreateError::InvalidInput("The project could not be found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(Project::from(project)))
+}
+
+fn not_found(err: CurseForgeError) -> CreateError {
+    match err {
+        CurseForgeError::Request(err) if err.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+            CreateError::InvalidInput("The CurseForge project does not exist".to_string())
+        }
+        err => err.into(),
+    }
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+#[derive(Deserialize, Validate)]
+pub struct GitHubLinkData {
+    /// The repository, as `owner/name`
+    #[validate(length(min = 3, max = 255))]
+    pub repository: String,
+    /// The loaders and version fields imported versions get, which releases don't say
+    pub loaders: Vec<Loader>,
+    #[serde(default)]
+    pub fields: HashMap<String, serde_json::Value>,
+    /// Whether new releases are imported as draft versions when they are published
+    #[serde(default)]
+    pub sync: bool,
+    /// Whether the releases the repository already has are imported as well
+    #[serde(default)]
+    pub import_existing: bool,
+}
+
+pub async fn github_link_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let (_, project) =
+        get_github_project(&req, &info.into_inner().0, &pool, &redis, &session_queue).await?;
+
+    let link = GitHubLink::get(project.inner.id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(crate::models::projects::GitHubLink::from(link)))
+}
+
+/// Links a GitHub repository to a project, replacing the one linked before. Releases are
+/// only synced once GitHub sends them to the GitHub webhook, which must be set up on the
+/// repository
+pub async fn github_link_edit(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    web::Json(link_data): web::Json<GitHubLinkData>,
+) -> Result<HttpResponse, ApiError> {
+    let (user, project) =
+        get_github_project(&req, &info.into_inner().0, &pool, &redis, &session_queue).await?;
+
+    link_data.validate()?;
+
+    let repository = link_data.repository.trim_end_matches(".git").to_lowercase();
+    let valid_repository = repository.split_once('/').map_or(false, |(owner, name)| {
+        [owner, name].iter().all(|x| {
+            !x.is_empty()
+                && x.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        })
+    });
+    if !valid_repository {
+        return Err(ApiError::InvalidInput(
+            "Repositories must be given as owner/name".to_string(),
+        ));
+    }
+
+    // The loaders and fields are checked now, as imports are skipped if they aren't valid
+    let all_loaders = models::loader_fields::Loader::list(&**pool, &redis).await?;
+    let mut loader_ids = Vec::new();
+    for loader in &link_data.loaders {
+        let loader = all_loaders
+            .iter()
+            .find(|x| x.loader == loader.0)
+            .ok_or_else(|| ApiError::InvalidInput(format!("Loader {} does not exist", loader.0)))?;
+        loader_ids.push(loader.id);
+    }
+    if loader_ids.is_empty() {
+        return Err(ApiError::InvalidInput(
+            "At least one loader must be given".to_string(),
+        ));
+    }
+    let loader_fields = LoaderField::get_fields(&loader_ids, &**pool, &redis).await?;
+    let mut loader_field_enum_values =
+        LoaderFieldEnumValue::list_many_loader_fields(&loader_fields, &**pool, &redis).await?;
+    try_create_version_fields(
+        VersionId(0),
+        &link_data.fields,
+        &loader_fields,
+        &mut loader_field_enum_values,
+    )
+    .map_err(|err| ApiError::InvalidInput(err.to_string()))?;
+
+    github::check_repository(&repository)
+        .await
+        .map_err(|err| match err {
+            GitHubError::Request(err) if err.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                ApiError::InvalidInput("The GitHub repository does not exist".to_string())
+            }
+            GitHubError::Request(err) => ApiError::Reroute(err),
+            GitHubError::CircuitOpen(err) => ApiError::Unavailable(err),
+            GitHubError::TooLarge | GitHubError::RemoteFile(_) => {
+                ApiError::InvalidInput(err.to_string())
+            }
+        })?;
+
+    let mut transaction = pool.begin().await?;
+    GitHubLink {
+        project_id: project.inner.id,
+        repository,
+        user_id: user.id.into(),
+        loaders: link_data.loaders.into_iter().map(|x| x.0).collect(),
+        version_fields: link_data.fields,
+        sync: link_data.sync,
+        created: Utc::now(),
+    }
+    .upsert(&mut transaction)
+    .await?;
+    if link_data.import_existing {
+        Job::enqueue(
+            JobPayload::ImportGitHubReleases {
+                project_id: project.inner.id.into(),
+                release_id: None,
+            },
+            Utc::now(),
+            &mut *transaction,
+        )
+        .await?;
+    }
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+pub async fn github_link_delete(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let (_, project) =
+        get_github_project(&req, &info.into_inner().0, &pool, &redis, &session_queue).await?;
+
+    GitHubLink::remove(project.inner.id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+/// Gets the user along with a project whose GitHub link they may manage, which takes
+/// permission to upload versions
+async fn get_github_project(
+    req: &HttpRequest,
+    id: &str,
+    pool: &PgPool,
+    redis: &RedisPool,
+    session_queue: &AuthQueue,
+) -> Result<
+    (
+        crate::models::users::User,
+        models::project_item::QueryProject,
+    ),
+    ApiError,
+> {
+    let user = get_user_from_headers(
+        req,
+        pool,
+        redis,
+        session_queue,
+        Some(&[Scopes::PROJECT_WRITE]),
+    )
+    .await?
+    .1;
+
+    let project = models::Project::get(id, pool, redis)
+        .await?
+        .ok_or_else(|| {
+            ApiError::InvalidInput("The specified project does not exist!".to_string())
+        })?;
+
+    if !user.role.is_mod() {
+        let (team_member, organization_team_member) =
+            models::TeamMember::get_for_project_permissions(&project.inner, user.id.into(), pool)
+                .await?;
+
+        // Hide the project
+        if team_member.is_none() && organization_team_member.is_none() {
+            return Err(ApiError::CustomAuthentication(
+                "The specified project does not exist!".to_string(),
+            ));
+        }
+
+        let permissions = ProjectPermissions::get_permissions_by_role(
+            &user.role,
+            &team_member,
+            &organization_team_member,
+        )
+        .unwrap_or_default();
+
+        if !permissions.contains(ProjectPermissions::UPLOAD_VERSION) {
+            return Err(ApiError::CustomAuthentication(
+                "You don't have permission to manage this project's GitHub repository.".to_string(),
+            ));
+        }
+    }
+
+    Ok((user, project))
+}
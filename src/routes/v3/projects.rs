@@ -2,17 +2,23 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::auth::checks::is_visible_project;
+use crate::auth::email::send_email;
+use crate::auth::email::template::{EmailTemplate, DEFAULT_LOCALE};
 use crate::auth::{filter_visible_projects, get_user_from_headers};
+use crate::database::models::job_item::Job;
 use crate::database::models::notification_item::NotificationBuilder;
 use crate::database::models::project_item::{GalleryItem, ModCategory};
+use crate::database::models::similar_project_item::SimilarProject;
 use crate::database::models::thread_item::ThreadMessageBuilder;
-use crate::database::models::{ids as db_ids, image_item, TeamMember};
+use crate::database::models::{ids as db_ids, TeamMember};
 use crate::database::redis::RedisPool;
 use crate::database::{self, models as db_models};
 use crate::file_hosting::FileHost;
 use crate::models;
 use crate::models::ids::base62_impl::parse_base62;
 use crate::models::images::ImageContext;
+use crate::models::jobs::{DiscordChannel, JobPayload, UserJob};
+use crate::models::moderation::ImageSource;
 use crate::models::notifications::NotificationBody;
 use crate::models::pats::Scopes;
 use crate::models::projects::{
@@ -24,10 +30,12 @@ use crate::queue::session::AuthQueue;
 use crate::routes::ApiError;
 use crate::search::indexing::remove_documents;
 use crate::search::{search_for_project, SearchConfig, SearchError};
-use crate::util::img;
+use crate::util::etag::{if_match_revisions, revision_etag};
 use crate::util::routes::read_from_payload;
-use crate::util::validate::validation_errors_to_string;
+use crate::util::{image_scan, img, svg};
+use actix_web::http::header::{ETag, VARY};
 use actix_web::{web, HttpRequest, HttpResponse};
+use bytes::BytesMut;
 use chrono::Utc;
 use futures::TryStreamExt;
 use itertools::Itertools;
@@ -47,6 +55,8 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route("{id}", web::get().to(project_get))
             .route("{id}/check", web::get().to(project_get_check))
             .route("{id}", web::delete().to(project_delete))
+            .route("{id}/restore", web::post().to(project_restore))
+            .route("{id}/export", web::post().to(project_backup_start))
             .route("{id}", web::patch().to(project_edit))
             .route("{id}/icon", web::patch().to(project_icon_edit))
             .route("{id}/icon", web::delete().to(delete_project_icon))
@@ -56,6 +66,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route("{id}/follow", web::post().to(project_follow))
             .route("{id}/follow", web::delete().to(project_unfollow))
             .route("{id}/organization", web::get().to(project_get_organization))
+            .route("{id}/similar", web::get().to(project_similar_get))
             .service(
                 web::scope("{project_id}")
                     .route(
@@ -78,14 +89,19 @@ pub struct RandomProjects {
     pub count: u32,
 }
 
+#[utoipa::path(
+    get,
+    path = "/v3/projects_random",
+    tag = "projects",
+    params(("count" = u32, Query, description = "How many projects to return, from 1 to 100")),
+    responses((status = 200, body = Vec<crate::models::projects::Project>))
+)]
 pub async fn random_projects_get(
     web::Query(count): web::Query<RandomProjects>,
     pool: web::Data<PgPool>,
     redis: web::Data<RedisPool>,
 ) -> Result<HttpResponse, ApiError> {
-    count
-        .validate()
-        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+    count.validate()?;
 
     let project_ids = sqlx::query!(
         "
@@ -116,6 +132,13 @@ pub struct ProjectIds {
     pub ids: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/v3/projects",
+    tag = "projects",
+    params(("ids" = String, Query, description = "A JSON array of project IDs or slugs")),
+    responses((status = 200, body = Vec<crate::models::projects::Project>))
+)]
 pub async fn projects_get(
     req: HttpRequest,
     web::Query(ids): web::Query<ProjectIds>,
@@ -137,11 +160,26 @@ pub async fn projects_get(
     .map(|x| x.1)
     .ok();
 
-    let projects = filter_visible_projects(projects_data, &user_option, &pool).await?;
+    let mut projects = filter_visible_projects(projects_data, &user_option, &pool).await?;
+    for project in &mut projects {
+        use_preferred_images(project, &req);
+    }
 
-    Ok(HttpResponse::Ok().json(projects))
+    Ok(HttpResponse::Ok()
+        .insert_header((VARY, "Accept"))
+        .json(projects))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v3/project/{id}",
+    tag = "projects",
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 200, body = crate::models::projects::Project),
+        (status = 404, description = "Not found", body = crate::models::error::ApiError),
+    )
+)]
 pub async fn project_get(
     req: HttpRequest,
     info: web::Path<(String,)>,
@@ -165,12 +203,96 @@ pub async fn project_get(
 
     if let Some(data) = project_data {
         if is_visible_project(&data.inner, &user_option, &pool).await? {
-            return Ok(HttpResponse::Ok().json(Project::from(data)));
+            let etag = revision_etag(data.inner.revision);
+            let mut project = Project::from(data);
+            use_preferred_images(&mut project, &req);
+
+            return Ok(HttpResponse::Ok()
+                .insert_header(ETag(etag))
+                .insert_header((VARY, "Accept"))
+                .json(project));
         }
     }
     Err(ApiError::NotFound)
 }
 
+#[derive(Deserialize, Validate)]
+pub struct SimilarProjects {
+    #[validate(range(min = 1, max = 20))]
+    pub limit: Option<u32>,
+}
+
+/// Lists the projects most similar to a project, most similar first. Similar projects are
+/// computed periodically, so new projects have none at first
+pub async fn project_similar_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    web::Query(query): web::Query<SimilarProjects>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    query.validate()?;
+
+    let string = info.into_inner().0;
+    let project_data = db_models::Project::get(&string, &**pool, &redis).await?;
+    let user_option = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PROJECT_READ]),
+    )
+    .await
+    .map(|x| x.1)
+    .ok();
+
+    let Some(data) = project_data else {
+        return Err(ApiError::NotFound);
+    };
+    if !is_visible_project(&data.inner, &user_option, &pool).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    let similar_ids =
+        SimilarProject::get_many(data.inner.id, query.limit.unwrap_or(10) as i64, &**pool)
+            .await?
+            .into_iter()
+            .map(|x| x.similar_id)
+            .collect::<Vec<_>>();
+    let projects = db_models::Project::get_many_ids(&similar_ids, &**pool, &redis).await?;
+    let mut projects = filter_visible_projects(projects, &user_option, &pool)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect::<HashMap<_, _>>();
+
+    let similar = similar_ids
+        .into_iter()
+        .filter_map(|id| {
+            let mut project = projects.remove(&models::ids::ProjectId::from(id))?;
+            use_preferred_images(&mut project, &req);
+            Some(project)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(similar))
+}
+
+/// Points a project's icon and gallery at the best rendition of each image the client accepts
+pub(super) fn use_preferred_images(project: &mut Project, req: &HttpRequest) {
+    if let Some(icon_url) = &project.icon_url {
+        project.icon_url = Some(img::preferred_image_url(
+            icon_url,
+            &project.icon_renditions,
+            req,
+        ));
+    }
+    for item in &mut project.gallery {
+        item.url = img::preferred_image_url(&item.url, &item.renditions, req);
+    }
+}
+
 #[derive(Serialize, Deserialize, Validate)]
 pub struct EditProject {
     #[validate(
@@ -248,9 +370,7 @@ pub async fn project_edit(
     .await?
     .1;
 
-    new_project
-        .validate()
-        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+    new_project.validate()?;
 
     let string = info.into_inner().0;
     let result = db_models::Project::get(&string, &**pool, &redis).await?;
@@ -274,6 +394,14 @@ pub async fn project_edit(
         if let Some(perms) = permissions {
             let mut transaction = pool.begin().await?;
 
+            db_models::Project::bump_revision(
+                id,
+                if_match_revisions(&req).as_deref(),
+                &mut transaction,
+            )
+            .await?
+            .ok_or(ApiError::PreconditionFailed)?;
+
             if let Some(name) = &new_project.name {
                 if !perms.contains(ProjectPermissions::EDIT_DETAILS) {
                     return Err(ApiError::CustomAuthentication(
@@ -377,16 +505,17 @@ pub async fn project_edit(
                     .await?;
                 }
                 if status.is_searchable() && !project_item.inner.webhook_sent {
-                    if let Ok(webhook_url) = dotenvy::var("PUBLIC_DISCORD_WEBHOOK") {
-                        crate::util::webhook::send_discord_webhook(
-                            project_item.inner.id.into(),
-                            &pool,
-                            &redis,
-                            webhook_url,
-                            None,
+                    if dotenvy::var("PUBLIC_DISCORD_WEBHOOK").is_ok() {
+                        Job::enqueue(
+                            JobPayload::DiscordWebhook {
+                                project_id: project_item.inner.id.into(),
+                                channel: DiscordChannel::Public,
+                                message: None,
+                            },
+                            Utc::now(),
+                            &mut *transaction,
                         )
-                        .await
-                        .ok();
+                        .await?;
 
                         sqlx::query!(
                             "
@@ -402,26 +531,24 @@ pub async fn project_edit(
                 }
 
                 if user.role.is_mod() {
-                    if let Ok(webhook_url) = dotenvy::var("MODERATION_DISCORD_WEBHOOK") {
-                        crate::util::webhook::send_discord_webhook(
-                            project_item.inner.id.into(),
-                            &pool,
-                            &redis,
-                            webhook_url,
-                            Some(
-                                format!(
+                    if dotenvy::var("MODERATION_DISCORD_WEBHOOK").is_ok() {
+                        Job::enqueue(
+                            JobPayload::DiscordWebhook {
+                                project_id: project_item.inner.id.into(),
+                                channel: DiscordChannel::Moderation,
+                                message: Some(format!(
                                     "**[{}]({}/user/{})** changed project status from **{}** to **{}**",
                                     user.username,
                                     dotenvy::var("SITE_URL")?,
                                     user.username,
                                     &project_item.inner.status.as_friendly_str(),
                                     status.as_friendly_str(),
-                                )
-                                .to_string(),
-                            ),
+                                )),
+                            },
+                            Utc::now(),
+                            &mut *transaction,
                         )
-                        .await
-                        .ok();
+                        .await?;
                     }
                 }
 
@@ -446,8 +573,40 @@ pub async fn project_edit(
                             new_status: *status,
                         },
                     }
-                    .insert_many(notified_members, &mut transaction, &redis)
+                    .insert_many(notified_members.clone(), &mut transaction, &redis)
                     .await?;
+
+                    if user.role.is_mod() {
+                        let project_link = format!(
+                            "{}/project/{}",
+                            dotenvy::var("SITE_URL")?,
+                            project_item.inner.slug.clone().unwrap_or_else(|| {
+                                models::ids::ProjectId::from(project_item.inner.id).to_string()
+                            })
+                        );
+
+                        let members =
+                            db_models::User::get_many_ids(&notified_members, &**pool, &redis)
+                                .await?;
+                        for email in members
+                            .into_iter()
+                            .filter(|x| x.email_verified)
+                            .filter_map(|x| x.email)
+                        {
+                            send_email(
+                                email,
+                                EmailTemplate::ModerationDecision,
+                                DEFAULT_LOCALE,
+                                HashMap::from([
+                                    ("project".to_string(), project_item.inner.name.clone()),
+                                    ("status".to_string(), status.as_friendly_str().to_string()),
+                                    ("button_link".to_string(), project_link.clone()),
+                                ]),
+                                &**pool,
+                            )
+                            .await?;
+                        }
+                    }
                 }
 
                 ThreadMessageBuilder {
@@ -893,7 +1052,7 @@ pub async fn edit_project_categories(
     Ok(())
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, utoipa::ToSchema)]
 pub struct ReturnSearchResults {
     pub hits: Vec<Project>,
     pub page: usize,
@@ -901,11 +1060,28 @@ pub struct ReturnSearchResults {
     pub total_hits: usize,
 }
 
+#[utoipa::path(
+    get,
+    path = "/v3/search",
+    tag = "projects",
+    params(
+        ("query" = Option<String>, Query, description = "The text to search for"),
+        ("facets" = Option<String>, Query, description = "A JSON array of arrays of facets to filter by. `environment` is one of client, server, client_required or server_required"),
+        ("index" = Option<String>, Query, description = "How to sort results: relevance, downloads, follows, updated or newest"),
+        ("offset" = Option<String>, Query, description = "How many results to skip"),
+        ("limit" = Option<String>, Query, description = "How many results to return, at most 100"),
+    ),
+    responses(
+        (status = 200, body = ReturnSearchResults),
+        (status = 400, description = "Invalid search parameters", body = crate::models::error::ApiError),
+    )
+)]
 pub async fn project_search(
     web::Query(info): web::Query<SearchRequest>,
     config: web::Data<SearchConfig>,
+    redis: web::Data<RedisPool>,
 ) -> Result<HttpResponse, SearchError> {
-    let results = search_for_project(&info, &config).await?;
+    let results = search_for_project(&info, &config, &redis).await?;
 
     let results = ReturnSearchResults {
         hits: results
@@ -1066,9 +1242,7 @@ pub async fn projects_edit(
     .await?
     .1;
 
-    bulk_edit_project
-        .validate()
-        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+    bulk_edit_project.validate()?;
 
     let project_ids: Vec<db_ids::ProjectId> = serde_json::from_str::<Vec<ProjectId>>(&ids.ids)?
         .into_iter()
@@ -1322,7 +1496,7 @@ pub async fn project_icon_edit(
     mut payload: web::Payload,
     session_queue: web::Data<AuthQueue>,
 ) -> Result<HttpResponse, ApiError> {
-    if let Some(content_type) = crate::util::ext::get_image_content_type(&ext.ext) {
+    if let Some(content_type) = crate::util::ext::get_icon_content_type(&ext.ext) {
         let cdn_url = dotenvy::var("CDN_URL")?;
         let user = get_user_from_headers(
             &req,
@@ -1378,36 +1552,65 @@ pub async fn project_icon_edit(
                 file_host.delete_file_version("", icon_path).await?;
             }
         }
+        img::delete_renditions(&***file_host, &project_item.inner.icon_renditions).await?;
 
-        let bytes =
-            read_from_payload(&mut payload, 262144, "Icons must be smaller than 256KiB").await?;
+        let bytes = read_from_payload(
+            &mut payload,
+            img::MAX_ANIMATED_ICON_SIZE,
+            "Icons must be smaller than 1MiB",
+        )
+        .await?;
+        crate::util::magic::check_content(&bytes, &ext.ext, content_type)?;
+        let bytes = if content_type == "image/svg+xml" {
+            let svg = svg::sanitize_svg(&bytes).map_err(ApiError::InvalidInput)?;
+            BytesMut::from(svg.as_bytes())
+        } else {
+            bytes
+        };
+        let animation = img::get_animation(&bytes)?;
+        img::check_icon(&bytes, animation.as_ref()).map_err(ApiError::InvalidInput)?;
 
         let color = crate::util::img::get_color_from_img(&bytes)?;
 
         let hash = sha1::Sha1::from(&bytes).hexdigest();
         let project_id: ProjectId = project_item.inner.id.into();
+        let path = format!("data/{}/{}", project_id, hash);
+        let bytes = bytes.freeze();
         let upload_data = file_host
             .upload_file(
                 content_type,
-                &format!("data/{}/{}.{}", project_id, hash, ext.ext),
-                bytes.freeze(),
+                &format!("{}.{}", path, ext.ext),
+                bytes.clone(),
             )
             .await?;
+        let perceptual_hash = img::get_perceptual_hash(bytes.clone()).await;
+        let renditions =
+            img::upload_renditions(&***file_host, bytes, &path, img::MAX_ICON_DIMENSION).await?;
+
+        let icon_url = format!("{}/{}", cdn_url, upload_data.file_name);
 
         let mut transaction = pool.begin().await?;
 
         sqlx::query!(
             "
             UPDATE mods
-            SET icon_url = $1, color = $2
-            WHERE (id = $3)
+            SET icon_url = $1, color = $2, icon_renditions = $3, icon_perceptual_hash = $4
+            WHERE (id = $5)
             ",
-            format!("{}/{}", cdn_url, upload_data.file_name),
+            icon_url,
             color.map(|x| x as i32),
+            json!(img::rendition_urls(&renditions, &cdn_url)),
+            perceptual_hash,
             project_item.inner.id as db_ids::ProjectId,
         )
         .execute(&mut *transaction)
         .await?;
+        image_scan::queue_scan(
+            &icon_url,
+            ImageSource::ProjectIcon { project_id },
+            &mut *transaction,
+        )
+        .await?;
 
         transaction.commit().await?;
         db_models::Project::clear_cache(
@@ -1489,13 +1692,15 @@ pub async fn delete_project_icon(
             file_host.delete_file_version("", icon_path).await?;
         }
     }
+    img::delete_renditions(&***file_host, &project_item.inner.icon_renditions).await?;
 
     let mut transaction = pool.begin().await?;
 
     sqlx::query!(
         "
         UPDATE mods
-        SET icon_url = NULL, color = NULL
+        SET icon_url = NULL, color = NULL, icon_renditions = '{}'::jsonb,
+            icon_perceptual_hash = NULL
         WHERE (id = $1)
         ",
         project_item.inner.id as db_ids::ProjectId,
@@ -1533,8 +1738,7 @@ pub async fn add_gallery_item(
     session_queue: web::Data<AuthQueue>,
 ) -> Result<HttpResponse, ApiError> {
     if let Some(content_type) = crate::util::ext::get_image_content_type(&ext.ext) {
-        item.validate()
-            .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+        item.validate()?;
 
         let cdn_url = dotenvy::var("CDN_URL")?;
         let user = get_user_from_headers(
@@ -1596,10 +1800,12 @@ pub async fn add_gallery_item(
             "Gallery image exceeds the maximum of 5MiB.",
         )
         .await?;
+        crate::util::magic::check_content(&bytes, &ext.ext, content_type)?;
         let hash = sha1::Sha1::from(&bytes).hexdigest();
 
         let id: ProjectId = project_item.inner.id.into();
-        let url = format!("data/{}/images/{}.{}", id, hash, &*ext.ext);
+        let path = format!("data/{}/images/{}", id, hash);
+        let url = format!("{}.{}", path, &*ext.ext);
 
         let file_url = format!("{cdn_url}/{url}");
         if project_item
@@ -1612,9 +1818,26 @@ pub async fn add_gallery_item(
             ));
         }
 
+        // Images which only differ by their encoding or size would take up storage twice
+        let bytes = bytes.freeze();
+        let perceptual_hash = img::get_perceptual_hash(bytes.clone()).await;
+        if let Some(hash) = perceptual_hash {
+            if project_item
+                .gallery_items
+                .iter()
+                .any(|x| x.perceptual_hash == Some(hash))
+            {
+                return Err(ApiError::InvalidInput(
+                    "You may not upload duplicate gallery images!".to_string(),
+                ));
+            }
+        }
+
         file_host
-            .upload_file(content_type, &url, bytes.freeze())
+            .upload_file(content_type, &url, bytes.clone())
             .await?;
+        let renditions =
+            img::upload_renditions(&***file_host, bytes, &path, img::MAX_GALLERY_DIMENSION).await?;
 
         let mut transaction = pool.begin().await?;
 
@@ -1632,13 +1855,23 @@ pub async fn add_gallery_item(
             .await?;
         }
 
+        image_scan::queue_scan(
+            &file_url,
+            ImageSource::GalleryImage { project_id: id },
+            &mut *transaction,
+        )
+        .await?;
+
         let gallery_item = vec![db_models::project_item::GalleryItem {
+            image_id: None,
             image_url: file_url,
             featured: item.featured,
             name: item.name,
             description: item.description,
             created: Utc::now(),
             ordering: item.ordering.unwrap_or(0),
+            renditions: img::rendition_urls(&renditions, &cdn_url),
+            perceptual_hash,
         }];
         GalleryItem::insert_many(gallery_item, project_item.inner.id, &mut transaction).await?;
 
@@ -1701,8 +1934,7 @@ pub async fn edit_gallery_item(
     .1;
     let string = info.into_inner().0;
 
-    item.validate()
-        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+    item.validate()?;
 
     let project_item = db_models::Project::get(&string, &**pool, &redis)
         .await?
@@ -1740,10 +1972,12 @@ pub async fn edit_gallery_item(
     }
     let mut transaction = pool.begin().await?;
 
+    // Items may be referred to by any of their renditions, as those are the URLs clients get
     let id = sqlx::query!(
         "
         SELECT id FROM mods_gallery
         WHERE image_url = $1
+        OR EXISTS (SELECT 1 FROM jsonb_each_text(renditions) r WHERE r.value = $1)
         ",
         item.url
     )
@@ -1896,10 +2130,12 @@ pub async fn delete_gallery_item(
     }
     let mut transaction = pool.begin().await?;
 
-    let id = sqlx::query!(
+    // Items may be referred to by any of their renditions, as those are the URLs clients get
+    let gallery_item = sqlx::query!(
         "
-        SELECT id FROM mods_gallery
+        SELECT id, image_id, image_url, renditions FROM mods_gallery
         WHERE image_url = $1
+        OR EXISTS (SELECT 1 FROM jsonb_each_text(renditions) r WHERE r.value = $1)
         ",
         item.url
     )
@@ -1910,15 +2146,23 @@ pub async fn delete_gallery_item(
             "Gallery item at URL {} is not part of the project's gallery.",
             item.url
         ))
-    })?
-    .id;
+    })?;
+    let id = gallery_item.id;
 
     let cdn_url = dotenvy::var("CDN_URL")?;
-    let name = item.url.split(&format!("{cdn_url}/")).nth(1);
+    let name = gallery_item.image_url.split(&format!("{cdn_url}/")).nth(1);
 
     if let Some(icon_path) = name {
         file_host.delete_file_version("", icon_path).await?;
+        img::delete_thumbnails(&***file_host, icon_path).await?;
     }
+    let renditions = serde_json::from_value(gallery_item.renditions).unwrap_or_default();
+    img::delete_renditions(&***file_host, &renditions).await?;
+    db_models::Image::clear_thumbnail_cache(
+        db_ids::ImageId(gallery_item.image_id),
+        &mut redis.connect().await?,
+    )
+    .await?;
 
     let mut transaction = pool.begin().await?;
 
@@ -1940,6 +2184,8 @@ pub async fn delete_gallery_item(
     Ok(HttpResponse::NoContent().body(""))
 }
 
+/// Deletes a project. It is hidden everywhere, and can be restored by its members until it is
+/// purged `DELETION_GRACE_DAYS` later
 pub async fn project_delete(
     req: HttpRequest,
     info: web::Path<(String,)>,
@@ -1996,26 +2242,8 @@ pub async fn project_delete(
     }
 
     let mut transaction = pool.begin().await?;
-    let context = ImageContext::Project {
-        project_id: Some(project.inner.id.into()),
-    };
-    let uploaded_images = db_models::Image::get_many_contexted(context, &mut transaction).await?;
-    for image in uploaded_images {
-        image_item::Image::remove(image.id, &mut transaction, &redis).await?;
-    }
-
-    sqlx::query!(
-        "
-        DELETE FROM collections_mods
-        WHERE mod_id = $1
-        ",
-        project.inner.id as db_ids::ProjectId,
-    )
-    .execute(&mut *transaction)
-    .await?;
-
-    let result = db_models::Project::remove(project.inner.id, &mut transaction, &redis).await?;
-
+    let result =
+        db_models::Project::soft_remove(project.inner.id, &mut transaction, &redis).await?;
     transaction.commit().await?;
 
     remove_documents(
@@ -2035,6 +2263,127 @@ pub async fn project_delete(
     }
 }
 
+/// Restores a deleted project which has not been purged yet. Members who could delete the
+/// project can restore it, and it is added back to search on the next index
+pub async fn project_restore(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PROJECT_DELETE]),
+    )
+    .await?
+    .1;
+    let string = info.into_inner().0;
+
+    let project = db_models::Project::get_many_including_deleted(&[&string], &**pool, &redis)
+        .await?
+        .into_iter()
+        .next()
+        .filter(|x| x.inner.deleted.is_some())
+        .ok_or(ApiError::NotFound)?;
+
+    if !user.role.is_admin() {
+        let (team_member, organization_team_member) =
+            db_models::TeamMember::get_for_project_permissions(
+                &project.inner,
+                user.id.into(),
+                &**pool,
+            )
+            .await?;
+
+        let permissions = ProjectPermissions::get_permissions_by_role(
+            &user.role,
+            &team_member,
+            &organization_team_member,
+        )
+        .unwrap_or_default();
+
+        // Hide the project from users who couldn't have deleted it
+        if !permissions.contains(ProjectPermissions::DELETE_PROJECT) {
+            return Err(ApiError::NotFound);
+        }
+    }
+
+    let mut transaction = pool.begin().await?;
+    let result = db_models::Project::restore(project.inner.id, &mut transaction, &redis).await?;
+    transaction.commit().await?;
+
+    if result.is_some() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+/// Starts building an archive of a project's metadata, versions, description and images, for
+/// its members to keep as a backup. Returns the job, which is polled at `/v3/jobs/{id}` until
+/// it is completed and has the URL of the archive
+pub async fn project_backup_start(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::PROJECT_READ, Scopes::VERSION_READ]),
+    )
+    .await?
+    .1;
+    let string = info.into_inner().0;
+
+    let project = db_models::Project::get(&string, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !is_visible_project(&project.inner, &Some(user.clone()), &pool).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    let (team_member, organization_team_member) =
+        db_models::TeamMember::get_for_project_permissions(&project.inner, user.id.into(), &**pool)
+            .await?;
+    if ProjectPermissions::get_permissions_by_role(
+        &user.role,
+        &team_member,
+        &organization_team_member,
+    )
+    .is_none()
+    {
+        return Err(ApiError::CustomAuthentication(
+            "You must be a member of this project to export it!".to_string(),
+        ));
+    }
+
+    let job_id = Job::enqueue_for_user(
+        JobPayload::ExportProjectBackup {
+            project_id: project.inner.id.into(),
+        },
+        user.id.into(),
+        &**pool,
+    )
+    .await?;
+    let job = Job::get(job_id, &**pool).await?.ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Accepted()
+        .insert_header((
+            actix_web::http::header::LOCATION,
+            format!("/v3/jobs/{}", job_id),
+        ))
+        .json(UserJob::from(job)))
+}
+
 pub async fn project_follow(
     req: HttpRequest,
     info: web::Path<(String,)>,
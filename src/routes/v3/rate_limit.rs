@@ -0,0 +1,106 @@
+use crate::database::redis::RedisPool;
+use crate::ratelimit::middleware::bucket_statuses;
+use crate::ratelimit::{RateLimitTier, RATE_LIMIT_WINDOW, ROUTE_COSTS};
+use crate::routes::ApiError;
+use crate::util::env::parse_var;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use sqlx::PgPool;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("rate_limit", web::get().to(rate_limit_get));
+}
+
+#[derive(Serialize)]
+pub struct RateLimitStatus {
+    /// The length of a rate limit window, in seconds
+    pub window: i64,
+    /// Whether the request was made with the key exempting it from rate limits
+    pub exempt: bool,
+    /// The quotas the caller's requests are counted against. The first is the one of the token
+    /// the request was made with
+    pub buckets: Vec<RateLimitBucket>,
+    /// The routes costing more than one request
+    pub costs: Vec<RouteCost>,
+}
+
+#[derive(Serialize)]
+pub struct RateLimitBucket {
+    pub tier: RateLimitTier,
+    /// The cost of the requests allowed per window
+    pub limit: i64,
+    pub remaining: i64,
+    /// Seconds until the current window ends
+    pub reset: i64,
+    /// Whether requests made with the token of this request are counted against this quota
+    pub current_token: bool,
+}
+
+#[derive(Serialize)]
+pub struct RouteCost {
+    pub method: &'static str,
+    /// Routes nested under this path cost the same
+    pub path: &'static str,
+    pub cost: i64,
+}
+
+/// Gets the caller's rate limits and how much of them is left, so clients can slow down before
+/// being limited. This request is counted like any other
+pub async fn rate_limit_get(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    let conn_info = req.connection_info().clone();
+    let ip = if parse_var("CLOUDFLARE_INTEGRATION").unwrap_or(false) {
+        if let Some(header) = req.headers().get("CF-Connecting-IP") {
+            header.to_str().ok()
+        } else {
+            conn_info.peer_addr()
+        }
+    } else {
+        conn_info.peer_addr()
+    }
+    .ok_or_else(|| ApiError::InvalidInput("Could not identify the client".to_string()))?;
+
+    let exempt = dotenvy::var("RATE_LIMIT_IGNORE_KEY")
+        .ok()
+        .zip(
+            req.headers()
+                .get("x-ratelimit-key")
+                .and_then(|x| x.to_str().ok()),
+        )
+        .map(|(key, header)| key == header)
+        .unwrap_or(false);
+
+    let token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|x| x.to_str().ok());
+    let buckets = bucket_statuses(token, ip, &pool, &redis)
+        .await?
+        .into_iter()
+        .map(|x| RateLimitBucket {
+            tier: x.tier,
+            limit: x.limit,
+            remaining: x.remaining,
+            reset: x.reset,
+            current_token: x.current_token,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(RateLimitStatus {
+        window: RATE_LIMIT_WINDOW,
+        exempt,
+        buckets,
+        costs: ROUTE_COSTS
+            .iter()
+            .map(|(method, path, cost)| RouteCost {
+                method,
+                path,
+                cost: *cost,
+            })
+            .collect(),
+    }))
+}
@@ -1,18 +1,21 @@
 use crate::auth::{check_is_moderator_from_headers, get_user_from_headers};
 use crate::database;
 use crate::database::models::image_item;
+use crate::database::models::notification_item::NotificationBuilder;
 use crate::database::models::thread_item::{ThreadBuilder, ThreadMessageBuilder};
 use crate::database::redis::RedisPool;
 use crate::models::ids::ImageId;
 use crate::models::ids::{base62_impl::parse_base62, ProjectId, UserId, VersionId};
 use crate::models::images::{Image, ImageContext};
+use crate::models::notifications::NotificationBody;
 use crate::models::pats::Scopes;
-use crate::models::reports::{ItemType, Report};
+use crate::models::reports::{ItemType, Report, ReportOutcome};
 use crate::models::threads::{MessageBody, ThreadType};
 use crate::queue::session::AuthQueue;
 use crate::routes::ApiError;
+use crate::util::cursor::{next_page_link, Cursor};
 use crate::util::img;
-use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
 use chrono::Utc;
 use futures::StreamExt;
 use serde::Deserialize;
@@ -26,6 +29,10 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.route("report/{id}", web::get().to(report_get));
     cfg.route("report/{id}", web::patch().to(report_edit));
     cfg.route("report/{id}", web::delete().to(report_delete));
+    cfg.route(
+        "report/{id}/request-info",
+        web::post().to(report_request_info),
+    );
 }
 
 #[derive(Deserialize, Validate)]
@@ -213,6 +220,8 @@ pub async fn report_create(
         created: Utc::now(),
         closed: false,
         thread_id: thread_id.into(),
+        outcome: None,
+        awaiting_reporter: false,
     }))
 }
 
@@ -222,6 +231,8 @@ pub struct ReportsRequestOptions {
     pub count: i16,
     #[serde(default = "default_all")]
     pub all: bool,
+    /// The cursor from the `Link` header of the previous page
+    pub cursor: Option<Cursor>,
 }
 
 fn default_count() -> i16 {
@@ -231,6 +242,8 @@ fn default_all() -> bool {
     true
 }
 
+/// Lists open reports, oldest first. When the list is cut short by `count`, the next page is
+/// linked in the `Link` header
 pub async fn reports(
     req: HttpRequest,
     pool: web::Data<PgPool>,
@@ -250,53 +263,62 @@ pub async fn reports(
 
     use futures::stream::TryStreamExt;
 
-    let report_ids = if user.role.is_mod() && count.all {
-        sqlx::query!(
-            "
-            SELECT id FROM reports
-            WHERE closed = FALSE
-            ORDER BY created ASC
-            LIMIT $1;
-            ",
-            count.count as i64
-        )
-        .fetch_many(&**pool)
-        .try_filter_map(|e| async {
-            Ok(e.right()
-                .map(|m| crate::database::models::ids::ReportId(m.id)))
-        })
-        .try_collect::<Vec<crate::database::models::ids::ReportId>>()
-        .await?
+    // Moderators see every open report, everyone else only the ones they made
+    let reporter = if user.role.is_mod() && count.all {
+        None
     } else {
-        sqlx::query!(
-            "
-            SELECT id FROM reports
-            WHERE closed = FALSE AND reporter = $1
-            ORDER BY created ASC
-            LIMIT $2;
-            ",
-            user.id.0 as i64,
-            count.count as i64
-        )
-        .fetch_many(&**pool)
-        .try_filter_map(|e| async {
-            Ok(e.right()
-                .map(|m| crate::database::models::ids::ReportId(m.id)))
-        })
-        .try_collect::<Vec<crate::database::models::ids::ReportId>>()
-        .await?
+        Some(user.id.0 as i64)
     };
+    let limit = count.count.max(0) as usize;
+
+    let mut keys = sqlx::query!(
+        "
+        SELECT id, created FROM reports
+        WHERE closed = FALSE
+        AND ($1::bigint IS NULL OR reporter = $1)
+        AND ($2::timestamptz IS NULL OR (created, id) > ($2, $3))
+        ORDER BY created ASC, id ASC
+        LIMIT $4;
+        ",
+        reporter,
+        count.cursor.map(|x| x.timestamp),
+        count.cursor.map(|x| x.id),
+        // Fetch one extra row to know whether there is a next page
+        limit as i64 + 1,
+    )
+    .fetch_many(&**pool)
+    .try_filter_map(|e| async { Ok(e.right().map(|m| Cursor::new(m.created, m.id))) })
+    .try_collect::<Vec<Cursor>>()
+    .await?;
 
-    let query_reports =
-        crate::database::models::report_item::Report::get_many(&report_ids, &**pool).await?;
+    let next_cursor = if keys.len() > limit {
+        keys.truncate(limit);
+        keys.last().copied()
+    } else {
+        None
+    };
 
-    let mut reports: Vec<Report> = Vec::new();
+    let mut query_reports = crate::database::models::report_item::Report::get_many(
+        &keys
+            .iter()
+            .map(|x| crate::database::models::ids::ReportId(x.id))
+            .collect::<Vec<_>>(),
+        &**pool,
+    )
+    .await?;
+    query_reports.sort_by(|a, b| (a.created, a.id.0).cmp(&(b.created, b.id.0)));
 
-    for x in query_reports {
-        reports.push(x.into());
+    let mut builder = HttpResponse::Ok();
+    if let Some(cursor) = next_cursor {
+        builder.insert_header((header::LINK, next_page_link(&req, cursor)));
     }
 
-    Ok(HttpResponse::Ok().json(reports))
+    Ok(builder.json(
+        query_reports
+            .into_iter()
+            .map(Report::from)
+            .collect::<Vec<_>>(),
+    ))
 }
 
 #[derive(Deserialize)]
@@ -376,6 +398,8 @@ pub struct EditReport {
     #[validate(length(max = 65536))]
     pub body: Option<String>,
     pub closed: Option<bool>,
+    /// How the report was resolved. Only moderators may set it, and it is shown to the reporter
+    pub outcome: Option<ReportOutcome>,
 }
 
 pub async fn report_edit(
@@ -420,6 +444,26 @@ pub async fn report_edit(
             .await?;
         }
 
+        if let Some(outcome) = edit_report.outcome {
+            if !user.role.is_mod() {
+                return Err(ApiError::InvalidInput(
+                    "You cannot set the outcome of a report!".to_string(),
+                ));
+            }
+
+            sqlx::query!(
+                "
+                UPDATE reports
+                SET outcome = $1
+                WHERE (id = $2)
+                ",
+                outcome.as_str(),
+                id as crate::database::models::ids::ReportId,
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
         if let Some(edit_closed) = edit_report.closed {
             if !user.role.is_mod() {
                 return Err(ApiError::InvalidInput(
@@ -427,6 +471,43 @@ pub async fn report_edit(
                 ));
             }
 
+            if edit_closed && !report.closed {
+                // Nothing is left to answer once a report is closed
+                sqlx::query!(
+                    "
+                    UPDATE reports
+                    SET awaiting_reporter = FALSE
+                    WHERE (id = $1)
+                    ",
+                    id as crate::database::models::ids::ReportId,
+                )
+                .execute(&mut *transaction)
+                .await?;
+
+                if report.reporter != user.id.into() {
+                    NotificationBuilder {
+                        body: NotificationBody::ReportClosed {
+                            report_id: report.id.into(),
+                            thread_id: report.thread_id.into(),
+                            outcome: edit_report.outcome.or(report.outcome),
+                        },
+                    }
+                    .insert(report.reporter, &mut transaction, &redis)
+                    .await?;
+                }
+            } else if !edit_closed && report.closed && edit_report.outcome.is_none() {
+                sqlx::query!(
+                    "
+                    UPDATE reports
+                    SET outcome = NULL
+                    WHERE (id = $1)
+                    ",
+                    id as crate::database::models::ids::ReportId,
+                )
+                .execute(&mut *transaction)
+                .await?;
+            }
+
             ThreadMessageBuilder {
                 author_id: Some(user.id.into()),
                 body: if !edit_closed && report.closed {
@@ -522,3 +603,69 @@ pub async fn report_delete(
         Err(ApiError::NotFound)
     }
 }
+
+#[derive(Deserialize, Validate)]
+pub struct ReportInfoRequest {
+    #[validate(length(min = 1, max = 65536))]
+    pub body: String,
+}
+
+/// Asks the reporter for more information, such as further evidence, through the report's
+/// thread. The report stays out of the moderation inbox until the reporter replies
+pub async fn report_request_info(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    info: web::Path<(crate::models::reports::ReportId,)>,
+    session_queue: web::Data<AuthQueue>,
+    request: web::Json<ReportInfoRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user = check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::REPORT_WRITE]),
+    )
+    .await?;
+
+    request.validate()?;
+
+    let id = info.into_inner().0.into();
+    let report = crate::database::models::report_item::Report::get(id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if report.closed {
+        return Err(ApiError::InvalidInput(
+            "Reopen the report before asking its reporter for more information".to_string(),
+        ));
+    }
+
+    // Moderators' identities stay hidden from the reporter, as with any other message
+    super::threads::send_message(
+        &user,
+        report.thread_id,
+        MessageBody::Text {
+            body: request.into_inner().body,
+            private: false,
+            replying_to: None,
+            associated_images: Vec::new(),
+        },
+        &pool,
+        &redis,
+    )
+    .await?;
+
+    sqlx::query!(
+        "
+        UPDATE reports
+        SET awaiting_reporter = TRUE
+        WHERE (id = $1)
+        ",
+        id as crate::database::models::ids::ReportId,
+    )
+    .execute(&**pool)
+    .await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
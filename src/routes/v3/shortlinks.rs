@@ -0,0 +1,182 @@
+use crate::auth::get_user_from_headers;
+use crate::database::models::shortlink_item::Shortlink;
+use crate::database::models::{self, ProjectId, UserId};
+use crate::database::redis::RedisPool;
+use crate::models::ids::VersionId;
+use crate::models::pats::Scopes;
+use crate::models::shortlinks as shortlink_models;
+use crate::models::teams::ProjectPermissions;
+use crate::queue::session::AuthQueue;
+use crate::routes::ApiError;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+/// The most short links a project may have
+const MAX_SHORTLINKS: usize = 100;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("project/{id}/shortlinks")
+            .route(web::get().to(shortlinks_get))
+            .route(web::post().to(shortlink_create)),
+    );
+    cfg.route(
+        "project/{id}/shortlinks/{code}",
+        web::delete().to(shortlink_delete),
+    );
+}
+
+/// Gets the project whose short links the user may manage, which takes permission to edit its
+/// details, along with the user
+async fn get_project(
+    req: &HttpRequest,
+    id: &str,
+    scope: Scopes,
+    pool: &PgPool,
+    redis: &RedisPool,
+    session_queue: &AuthQueue,
+) -> Result<(ProjectId, UserId), ApiError> {
+    let user = get_user_from_headers(req, pool, redis, session_queue, Some(&[scope]))
+        .await?
+        .1;
+
+    let project = models::Project::get(id, pool, redis)
+        .await?
+        .ok_or_else(|| {
+            ApiError::InvalidInput("The specified project does not exist!".to_string())
+        })?;
+
+    if !user.role.is_mod() {
+        let (team_member, organization_team_member) =
+            models::TeamMember::get_for_project_permissions(&project.inner, user.id.into(), pool)
+                .await?;
+
+        // Hide the project
+        if team_member.is_none() && organization_team_member.is_none() {
+            return Err(ApiError::CustomAuthentication(
+                "The specified project does not exist!".to_string(),
+            ));
+        }
+
+        let permissions = ProjectPermissions::get_permissions_by_role(
+            &user.role,
+            &team_member,
+            &organization_team_member,
+        )
+        .unwrap_or_default();
+
+        if !permissions.contains(ProjectPermissions::EDIT_DETAILS) {
+            return Err(ApiError::CustomAuthentication(
+                "You don't have permission to manage this project's short links.".to_string(),
+            ));
+        }
+    }
+
+    Ok((project.inner.id, user.id.into()))
+}
+
+pub async fn shortlinks_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let (project_id, _) = get_project(
+        &req,
+        &info.into_inner().0,
+        Scopes::PROJECT_READ,
+        &pool,
+        &redis,
+        &session_queue,
+    )
+    .await?;
+
+    let links = Shortlink::get_many_project(project_id, &**pool)
+        .await?
+        .into_iter()
+        .map(shortlink_models::Shortlink::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(links))
+}
+
+#[derive(Deserialize)]
+pub struct ShortlinkData {
+    /// The version to link to. The link goes to the project itself if this is not given
+    #[serde(default)]
+    pub version_id: Option<VersionId>,
+}
+
+pub async fn shortlink_create(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    web::Json(link_data): web::Json<ShortlinkData>,
+) -> Result<HttpResponse, ApiError> {
+    let (project_id, user_id) = get_project(
+        &req,
+        &info.into_inner().0,
+        Scopes::PROJECT_WRITE,
+        &pool,
+        &redis,
+        &session_queue,
+    )
+    .await?;
+
+    let version_id = match link_data.version_id {
+        Some(version_id) => {
+            let version = models::Version::get(version_id.into(), &**pool, &redis)
+                .await?
+                .filter(|x| x.inner.project_id == project_id)
+                .ok_or_else(|| {
+                    ApiError::InvalidInput(
+                        "The specified version does not belong to this project!".to_string(),
+                    )
+                })?;
+            Some(version.inner.id)
+        }
+        None => None,
+    };
+
+    let existing = Shortlink::get_many_project(project_id, &**pool).await?;
+    if existing.len() >= MAX_SHORTLINKS {
+        return Err(ApiError::InvalidInput(format!(
+            "A project can have at most {MAX_SHORTLINKS} short links"
+        )));
+    }
+
+    let mut transaction = pool.begin().await?;
+    let link = Shortlink::insert(project_id, version_id, user_id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(shortlink_models::Shortlink::from(link)))
+}
+
+pub async fn shortlink_delete(
+    req: HttpRequest,
+    info: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, code) = info.into_inner();
+    let (project_id, _) = get_project(
+        &req,
+        &id,
+        Scopes::PROJECT_WRITE,
+        &pool,
+        &redis,
+        &session_queue,
+    )
+    .await?;
+
+    Shortlink::remove(&code, project_id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
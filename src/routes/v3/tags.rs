@@ -59,6 +59,8 @@ pub struct CategoryData {
     pub name: String,
     pub project_type: String,
     pub header: String,
+    /// The name of the category this one is nested under
+    pub parent: Option<String>,
 }
 
 pub async fn category_list(
@@ -73,6 +75,7 @@ pub async fn category_list(
             name: x.category,
             project_type: x.project_type,
             header: x.header,
+            parent: x.parent,
         })
         .collect::<Vec<_>>();
 
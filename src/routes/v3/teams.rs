@@ -5,7 +5,7 @@ use crate::database::models::team_item::TeamAssociationId;
 use crate::database::models::{Organization, Team, TeamMember, User};
 use crate::database::redis::RedisPool;
 use crate::database::Project;
-use crate::models::notifications::NotificationBody;
+use crate::models::notifications::{MembershipChange, NotificationBody};
 use crate::models::pats::Scopes;
 use crate::models::teams::{OrganizationPermissions, ProjectPermissions, TeamId};
 use crate::models::users::UserId;
@@ -38,6 +38,16 @@ pub fn config(cfg: &mut web::ServiceConfig) {
 // also the members of the organization's team if the project is associated with an organization
 // (Unlike team_members_get_project, which only returns the members of the project's team)
 // They can be differentiated by the "organization_permissions" field being null or not
+#[utoipa::path(
+    get,
+    path = "/v3/project/{id}/members",
+    tag = "teams",
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 200, body = Vec<crate::models::teams::TeamMember>),
+        (status = 404, description = "Not found", body = crate::models::error::ApiError),
+    )
+)]
 pub async fn team_members_get_project(
     req: HttpRequest,
     info: web::Path<(String,)>,
@@ -169,6 +179,16 @@ pub async fn team_members_get_organization(
 }
 
 // Returns all members of a team, but not necessarily those of a project-team's organization (unlike team_members_get_project)
+#[utoipa::path(
+    get,
+    path = "/v3/team/{id}/members",
+    tag = "teams",
+    params(("id" = String, Path, description = "The ID of the team")),
+    responses(
+        (status = 200, body = Vec<crate::models::teams::TeamMember>),
+        (status = 404, description = "Not found", body = crate::models::error::ApiError),
+    )
+)]
 pub async fn team_members_get(
     req: HttpRequest,
     info: web::Path<(TeamId,)>,
@@ -338,6 +358,20 @@ pub async fn join_team(
         )
         .await?;
 
+        if let Some(team_association) = Team::get_association(team_id, &**pool).await? {
+            notify_membership_change(
+                team_id,
+                team_association,
+                current_user.id.into(),
+                current_user.id.into(),
+                MembershipChange::Joined,
+                &pool,
+                &mut transaction,
+                &redis,
+            )
+            .await?;
+        }
+
         transaction.commit().await?;
 
         User::clear_project_cache(&[current_user.id.into()], &redis).await?;
@@ -351,6 +385,67 @@ pub async fn join_team(
     Ok(HttpResponse::NoContent().body(""))
 }
 
+/// Notifies a member about a change to their membership, along with every other member of
+/// the team who can manage members. The user who made the change is not notified.
+async fn notify_membership_change(
+    team_id: crate::database::models::TeamId,
+    team_association: TeamAssociationId,
+    member_id: crate::database::models::UserId,
+    changed_by: crate::database::models::UserId,
+    change: MembershipChange,
+    pool: &PgPool,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    redis: &RedisPool,
+) -> Result<(), ApiError> {
+    let members = TeamMember::get_from_team_full(team_id, pool, redis).await?;
+
+    let mut recipients = members
+        .into_iter()
+        .filter(|x| {
+            x.accepted
+                && (x.is_owner
+                    || match team_association {
+                        TeamAssociationId::Project(_) => {
+                            x.permissions.contains(ProjectPermissions::EDIT_MEMBER)
+                        }
+                        TeamAssociationId::Organization(_) => x
+                            .organization_permissions
+                            .unwrap_or_default()
+                            .contains(OrganizationPermissions::EDIT_MEMBER),
+                    })
+        })
+        .map(|x| x.user_id)
+        .collect::<Vec<_>>();
+    recipients.push(member_id);
+    recipients.sort_by_key(|x| x.0);
+    recipients.dedup();
+    recipients.retain(|x| *x != changed_by);
+
+    if recipients.is_empty() {
+        return Ok(());
+    }
+
+    let (project_id, organization_id) = match team_association {
+        TeamAssociationId::Project(pid) => (Some(pid.into()), None),
+        TeamAssociationId::Organization(oid) => (None, Some(oid.into())),
+    };
+
+    NotificationBuilder {
+        body: NotificationBody::TeamMembershipChange {
+            team_id: team_id.into(),
+            project_id,
+            organization_id,
+            member_id: member_id.into(),
+            changed_by: changed_by.into(),
+            change,
+        },
+    }
+    .insert_many(recipients, transaction, redis)
+    .await?;
+
+    Ok(())
+}
+
 fn default_role() -> String {
     "Member".to_string()
 }
@@ -563,6 +658,18 @@ pub async fn add_team_member(
                 .await?;
             }
         }
+    } else {
+        notify_membership_change(
+            team_id,
+            team_association,
+            new_member.user_id.into(),
+            current_user.id.into(),
+            MembershipChange::Added,
+            &pool,
+            &mut transaction,
+            &redis,
+        )
+        .await?;
     }
 
     transaction.commit().await?;
@@ -715,6 +822,25 @@ pub async fn edit_team_member(
     )
     .await?;
 
+    // Only accepted members are told about changes that affect what they can do
+    if edit_member_db.accepted
+        && (edit_member.permissions.is_some()
+            || edit_member.organization_permissions.is_some()
+            || edit_member.role.is_some())
+    {
+        notify_membership_change(
+            id,
+            team_association,
+            user_id,
+            current_user.id.into(),
+            MembershipChange::PermissionsChanged,
+            &pool,
+            &mut transaction,
+            &redis,
+        )
+        .await?;
+    }
+
     transaction.commit().await?;
     TeamMember::clear_cache(id, &redis).await?;
 
@@ -861,6 +987,20 @@ pub async fn transfer_ownership(
             vec![]
         };
 
+    if let Some(team_association) = team_association_id {
+        notify_membership_change(
+            id.into(),
+            team_association,
+            new_owner.user_id.into(),
+            current_user.id.into(),
+            MembershipChange::OwnershipTransferred,
+            &pool,
+            &mut transaction,
+            &redis,
+        )
+        .await?;
+    }
+
     transaction.commit().await?;
     TeamMember::clear_cache(id.into(), &redis).await?;
     for team_id in project_teams_edited {
@@ -991,6 +1131,25 @@ pub async fn remove_team_member(
             }
         }
 
+        // Declined and cancelled invites are not membership changes
+        if delete_member.accepted {
+            notify_membership_change(
+                id,
+                team_association,
+                delete_member.user_id,
+                current_user.id.into(),
+                if delete_member.user_id == current_user.id.into() {
+                    MembershipChange::Left
+                } else {
+                    MembershipChange::Removed
+                },
+                &pool,
+                &mut transaction,
+                &redis,
+            )
+            .await?;
+        }
+
         transaction.commit().await?;
 
         TeamMember::clear_cache(id, &redis).await?;
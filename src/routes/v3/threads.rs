@@ -1,8 +1,11 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::auth::email::template::substitute;
 use crate::auth::{check_is_moderator_from_headers, get_user_from_headers};
 use crate::database;
 use crate::database::models::image_item;
+use crate::database::models::moderation_template_item::ModerationTemplate;
 use crate::database::models::notification_item::NotificationBuilder;
 use crate::database::models::thread_item::ThreadMessageBuilder;
 use crate::database::redis::RedisPool;
@@ -12,12 +15,14 @@ use crate::models::images::{Image, ImageContext};
 use crate::models::notifications::NotificationBody;
 use crate::models::pats::Scopes;
 use crate::models::projects::ProjectStatus;
-use crate::models::threads::{MessageBody, Thread, ThreadId, ThreadType};
+use crate::models::threads::{MessageBody, Thread, ThreadId, ThreadMessageRevision, ThreadType};
 use crate::models::users::User;
 use crate::queue::session::AuthQueue;
 use crate::routes::ApiError;
 use actix_web::{web, HttpRequest, HttpResponse};
 use futures::TryStreamExt;
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::Deserialize;
 use sqlx::PgPool;
 
@@ -27,9 +32,22 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route("inbox", web::get().to(moderation_inbox))
             .route("{id}", web::get().to(thread_get))
             .route("{id}", web::post().to(thread_send_message))
-            .route("{id}/read", web::post().to(thread_read)),
+            .route("{id}/read", web::post().to(thread_read))
+            .route("{id}/template", web::post().to(thread_send_template))
+            .route("{id}/subscribe", web::post().to(thread_subscribe))
+            .route(
+                "{id}/subscribe",
+                web::delete().to(thread_subscription_clear),
+            )
+            .route("{id}/mute", web::post().to(thread_mute))
+            .route("{id}/mute", web::delete().to(thread_subscription_clear)),
+    );
+    cfg.service(
+        web::scope("message")
+            .route("{id}", web::patch().to(message_edit))
+            .route("{id}", web::delete().to(message_delete))
+            .route("{id}/history", web::get().to(message_history)),
     );
-    cfg.service(web::scope("message").route("{id}", web::delete().to(message_delete)));
     cfg.route("threads", web::get().to(threads_get));
 }
 
@@ -324,14 +342,32 @@ pub async fn thread_send_message(
     .await?
     .1;
 
-    let string: database::models::ThreadId = info.into_inner().0.into();
+    send_message(
+        &user,
+        info.into_inner().0.into(),
+        new_message.into_inner().body,
+        &pool,
+        &redis,
+    )
+    .await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
 
+/// Posts a message to a thread as the user, notifying everyone concerned
+pub async fn send_message(
+    user: &User,
+    string: database::models::ThreadId,
+    body: MessageBody,
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<(), ApiError> {
     if let MessageBody::Text {
         body,
         replying_to,
         private,
         ..
-    } = &new_message.body
+    } = &body
     {
         if body.len() > 65536 {
             return Err(ApiError::InvalidInput(
@@ -347,7 +383,7 @@ pub async fn thread_send_message(
 
         if let Some(replying_to) = replying_to {
             let thread_message =
-                database::models::ThreadMessage::get((*replying_to).into(), &**pool).await?;
+                database::models::ThreadMessage::get((*replying_to).into(), pool).await?;
 
             if let Some(thread_message) = thread_message {
                 if thread_message.thread_id != string {
@@ -367,10 +403,10 @@ pub async fn thread_send_message(
         ));
     }
 
-    let result = database::models::Thread::get(string, &**pool).await?;
+    let result = database::models::Thread::get(string, pool).await?;
 
     if let Some(thread) = result {
-        if !is_authorized_thread(&thread, &user, &pool).await? {
+        if !is_authorized_thread(&thread, user, pool).await? {
             return Err(ApiError::NotFound);
         }
 
@@ -378,23 +414,31 @@ pub async fn thread_send_message(
 
         let id = ThreadMessageBuilder {
             author_id: Some(user.id.into()),
-            body: new_message.body.clone(),
+            body: body.clone(),
             thread_id: thread.id,
         }
         .insert(&mut transaction)
         .await?;
 
+        // Users who were already notified of this message, so subscribers aren't notified twice
+        let mut notified: Vec<database::models::UserId> = Vec::new();
+
         let mod_notif = if let Some(project_id) = thread.project_id {
-            let project = database::models::Project::get_id(project_id, &**pool, &redis).await?;
+            let project = database::models::Project::get_id(project_id, pool, redis).await?;
 
             if let Some(project) = project {
                 if project.inner.status != ProjectStatus::Processing && user.role.is_mod() {
                     let members = database::models::TeamMember::get_from_team_full(
                         project.inner.team_id,
-                        &**pool,
-                        &redis,
+                        pool,
+                        redis,
                     )
                     .await?;
+                    let members = members
+                        .into_iter()
+                        .map(|x| x.user_id)
+                        .filter(|x| !thread.muted.contains(x))
+                        .collect::<Vec<_>>();
 
                     NotificationBuilder {
                         body: NotificationBody::ModeratorMessage {
@@ -404,18 +448,15 @@ pub async fn thread_send_message(
                             report_id: None,
                         },
                     }
-                    .insert_many(
-                        members.into_iter().map(|x| x.user_id).collect(),
-                        &mut transaction,
-                        &redis,
-                    )
+                    .insert_many(members.clone(), &mut transaction, redis)
                     .await?;
+                    notified.extend(members);
                 }
             }
 
             !user.role.is_mod()
         } else if let Some(report_id) = thread.report_id {
-            let report = database::models::report_item::Report::get(report_id, &**pool).await?;
+            let report = database::models::report_item::Report::get(report_id, pool).await?;
 
             if let Some(report) = report {
                 if report.closed && !user.role.is_mod() {
@@ -424,7 +465,20 @@ pub async fn thread_send_message(
                     ));
                 }
 
-                if user.id != report.reporter.into() {
+                if report.awaiting_reporter && user.id == report.reporter.into() {
+                    sqlx::query!(
+                        "
+                        UPDATE reports
+                        SET awaiting_reporter = FALSE
+                        WHERE id = $1
+                        ",
+                        report.id as database::models::ids::ReportId,
+                    )
+                    .execute(&mut *transaction)
+                    .await?;
+                }
+
+                if user.id != report.reporter.into() && !thread.muted.contains(&report.reporter) {
                     NotificationBuilder {
                         body: NotificationBody::ModeratorMessage {
                             thread_id: thread.id.into(),
@@ -433,8 +487,9 @@ pub async fn thread_send_message(
                             report_id: Some(report.id.into()),
                         },
                     }
-                    .insert(report.reporter, &mut transaction, &redis)
+                    .insert(report.reporter, &mut transaction, redis)
                     .await?;
+                    notified.push(report.reporter);
                 }
             }
 
@@ -443,6 +498,40 @@ pub async fn thread_send_message(
             false
         };
 
+        let user_id: database::models::UserId = user.id.into();
+        let subscribers = thread
+            .get_subscribers()
+            .into_iter()
+            .filter(|x| *x != user_id && !notified.contains(x))
+            .collect::<Vec<_>>();
+        if !subscribers.is_empty() {
+            let private = matches!(body, MessageBody::Text { private: true, .. });
+
+            // Subscribers may have lost access to the thread since, such as by leaving the team
+            let mut recipients = Vec::new();
+            for subscriber in
+                database::models::User::get_many_ids(&subscribers, pool, redis).await?
+            {
+                let subscriber: User = subscriber.into();
+                if (!private || subscriber.role.is_mod())
+                    && is_authorized_thread(&thread, &subscriber, pool).await?
+                {
+                    recipients.push(subscriber.id.into());
+                }
+            }
+
+            NotificationBuilder {
+                body: NotificationBody::ThreadMessage {
+                    thread_id: thread.id.into(),
+                    message_id: id.into(),
+                    project_id: thread.project_id.map(|x| x.into()),
+                    report_id: thread.report_id.map(|x| x.into()),
+                },
+            }
+            .insert_many(recipients, &mut transaction, redis)
+            .await?;
+        }
+
         sqlx::query!(
             "
             UPDATE threads
@@ -457,11 +546,11 @@ pub async fn thread_send_message(
 
         if let MessageBody::Text {
             associated_images, ..
-        } = &new_message.body
+        } = &body
         {
             for image_id in associated_images {
                 if let Some(db_image) =
-                    image_item::Image::get((*image_id).into(), &mut *transaction, &redis).await?
+                    image_item::Image::get((*image_id).into(), &mut *transaction, redis).await?
                 {
                     let image: Image = db_image.into();
                     if !matches!(image.context, ImageContext::ThreadMessage { .. })
@@ -473,13 +562,20 @@ pub async fn thread_send_message(
                         )));
                     }
 
+                    if image.owner_id != user.id {
+                        return Err(ApiError::InvalidInput(format!(
+                            "Image {} was not uploaded by you",
+                            image_id
+                        )));
+                    }
+
                     sqlx::query!(
                         "
                         UPDATE uploaded_images
                         SET thread_message_id = $1
                         WHERE id = $2
                         ",
-                        thread.id.0,
+                        id.0,
                         image_id.0 as i64
                     )
                     .execute(&mut *transaction)
@@ -497,12 +593,98 @@ pub async fn thread_send_message(
 
         transaction.commit().await?;
 
-        Ok(HttpResponse::NoContent().body(""))
+        Ok(())
     } else {
         Err(ApiError::NotFound)
     }
 }
 
+lazy_static! {
+    static ref RE_TEMPLATE_VARIABLE: Regex = Regex::new(r"\{\{ *([a-zA-Z0-9_]+) *\}\}").unwrap();
+}
+
+#[derive(Deserialize)]
+pub struct TemplateThreadMessage {
+    /// The name of the moderation template to post
+    pub template: String,
+    /// Values for the template's variables, such as the `reason` a project was rejected for
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
+
+/// Posts a moderation template into a thread, filling in its variables
+pub async fn thread_send_template(
+    req: HttpRequest,
+    info: web::Path<(ThreadId,)>,
+    pool: web::Data<PgPool>,
+    message: web::Json<TemplateThreadMessage>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = check_is_moderator_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::THREAD_WRITE]),
+    )
+    .await?;
+
+    let thread_id: database::models::ThreadId = info.into_inner().0.into();
+    let message = message.into_inner();
+
+    let template = ModerationTemplate::get(&message.template, &**pool)
+        .await?
+        .ok_or_else(|| {
+            ApiError::InvalidInput(format!("Template {} does not exist", message.template))
+        })?;
+    let thread = database::models::Thread::get(thread_id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    // Details of the thread's project always take precedence over what the moderator passed
+    let mut variables = message.variables;
+    if let Some(project_id) = thread.project_id {
+        if let Some(project) =
+            database::models::Project::get_id(project_id, &**pool, &redis).await?
+        {
+            variables.insert("project_name".to_string(), project.inner.name);
+            variables.insert(
+                "project_slug".to_string(),
+                project.inner.slug.unwrap_or_default(),
+            );
+            variables.insert(
+                "project_id".to_string(),
+                crate::models::ids::ProjectId::from(project.inner.id).to_string(),
+            );
+        }
+    }
+
+    let body = substitute(&template.body, &variables, false);
+    if let Some(missing) = RE_TEMPLATE_VARIABLE.captures(&body) {
+        return Err(ApiError::InvalidInput(format!(
+            "No value given for the template variable {}",
+            &missing[1]
+        )));
+    }
+
+    send_message(
+        &user,
+        thread_id,
+        MessageBody::Text {
+            body,
+            private: template.private,
+            replying_to: None,
+            associated_images: Vec::new(),
+        },
+        &pool,
+        &redis,
+    )
+    .await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
 pub async fn moderation_inbox(
     req: HttpRequest,
     pool: web::Data<PgPool>,
@@ -569,6 +751,75 @@ pub async fn thread_read(
     Ok(HttpResponse::NoContent().body(""))
 }
 
+pub async fn thread_subscribe(
+    req: HttpRequest,
+    info: web::Path<(ThreadId,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    set_thread_subscription(req, info, pool, redis, session_queue, Some(true)).await
+}
+
+pub async fn thread_mute(
+    req: HttpRequest,
+    info: web::Path<(ThreadId,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    set_thread_subscription(req, info, pool, redis, session_queue, Some(false)).await
+}
+
+/// Undoes a subscription or mute, so the user is notified according to the automatic rules again
+pub async fn thread_subscription_clear(
+    req: HttpRequest,
+    info: web::Path<(ThreadId,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    set_thread_subscription(req, info, pool, redis, session_queue, None).await
+}
+
+async fn set_thread_subscription(
+    req: HttpRequest,
+    info: web::Path<(ThreadId,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+    subscribed: Option<bool>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::THREAD_WRITE]),
+    )
+    .await?
+    .1;
+
+    let thread = database::models::Thread::get(info.into_inner().0.into(), &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !is_authorized_thread(&thread, &user, &pool).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    let mut transaction = pool.begin().await?;
+    database::models::Thread::set_subscription(
+        thread.id,
+        user.id.into(),
+        subscribed,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
 pub async fn message_delete(
     req: HttpRequest,
     info: web::Path<(ThreadMessageId,)>,
@@ -627,3 +878,131 @@ pub async fn message_delete(
         Err(ApiError::NotFound)
     }
 }
+
+#[derive(Deserialize)]
+pub struct EditThreadMessage {
+    pub body: String,
+}
+
+pub async fn message_edit(
+    req: HttpRequest,
+    info: web::Path<(ThreadMessageId,)>,
+    pool: web::Data<PgPool>,
+    edit_message: web::Json<EditThreadMessage>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::THREAD_WRITE]),
+    )
+    .await?
+    .1;
+
+    if edit_message.body.len() > 65536 {
+        return Err(ApiError::InvalidInput(
+            "Input body is too long!".to_string(),
+        ));
+    }
+
+    let message = database::models::ThreadMessage::get(info.into_inner().0.into(), &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    // Unlike deletion, moderators may not put words in someone else's mouth
+    if message.author_id != Some(user.id.into()) {
+        return Err(ApiError::CustomAuthentication(
+            "You cannot edit this message!".to_string(),
+        ));
+    }
+
+    let new_body = if let MessageBody::Text {
+        body,
+        private,
+        replying_to,
+        associated_images,
+    } = &message.body
+    {
+        if *body == edit_message.body {
+            return Ok(HttpResponse::NoContent().body(""));
+        }
+
+        MessageBody::Text {
+            body: edit_message.body.clone(),
+            private: *private,
+            replying_to: *replying_to,
+            associated_images: associated_images.clone(),
+        }
+    } else {
+        return Err(ApiError::InvalidInput(
+            "Only text messages can be edited!".to_string(),
+        ));
+    };
+
+    if let Some(thread) = database::models::Thread::get(message.thread_id, &**pool).await? {
+        if let Some(report_id) = thread.report_id {
+            let report = database::models::report_item::Report::get(report_id, &**pool).await?;
+
+            if report.map(|x| x.closed).unwrap_or(false) && !user.role.is_mod() {
+                return Err(ApiError::InvalidInput(
+                    "You may not edit messages in a closed report".to_string(),
+                ));
+            }
+        }
+    }
+
+    let mut transaction = pool.begin().await?;
+    message.edit(new_body, &mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+pub async fn message_history(
+    req: HttpRequest,
+    info: web::Path<(ThreadMessageId,)>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::THREAD_READ]),
+    )
+    .await?
+    .1;
+
+    let message = database::models::ThreadMessage::get(info.into_inner().0.into(), &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let private = match message.body {
+        MessageBody::Text { private, .. } | MessageBody::Deleted { private } => private,
+        _ => false,
+    };
+    if private && !user.role.is_mod() {
+        return Err(ApiError::NotFound);
+    }
+
+    let thread = database::models::Thread::get(message.thread_id, &**pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !is_authorized_thread(&thread, &user, &pool).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    let revisions: Vec<ThreadMessageRevision> =
+        database::models::ThreadMessage::get_revisions(message.id, &**pool)
+            .await?
+            .into_iter()
+            .map(From::from)
+            .collect();
+
+    Ok(HttpResponse::Ok().json(revisions))
+}
@@ -0,0 +1,100 @@
+use crate::auth::get_user_from_headers;
+use crate::database::redis::RedisPool;
+use crate::models::pats::Scopes;
+use crate::queue::session::AuthQueue;
+use crate::queue::socket::ActiveSockets;
+use crate::routes::ApiError;
+use crate::util::upload_progress::get_progress;
+use actix_web::web::{Data, Payload};
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_ws::Message;
+use futures::StreamExt;
+use rand::distributions::Alphanumeric;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// How long an upload socket is kept open for before it is closed, in case it is never used
+const UPLOAD_SOCKET_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    // Before `upload/{id}`, which would otherwise take the socket route
+    cfg.route("upload/ws", web::get().to(upload_ws));
+    cfg.route("upload/{id}", web::get().to(upload_progress));
+}
+
+/// Opens a socket following the processing of an upload. The first message sent is the ID
+/// to send the upload with, in the `x-upload-id` header, after which every event of the
+/// upload is sent until it is published or fails. The socket is forgotten when the client
+/// closes it or after an hour
+pub async fn upload_ws(
+    req: HttpRequest,
+    body: Payload,
+    db: Data<RwLock<ActiveSockets>>,
+    pool: Data<PgPool>,
+    redis: Data<RedisPool>,
+    session_queue: Data<AuthQueue>,
+) -> Result<HttpResponse, actix_web::Error> {
+    get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::VERSION_CREATE]),
+    )
+    .await?;
+
+    let (res, mut session, mut msg_stream) = actix_ws::handle(&req, body)?;
+
+    let upload_id = ChaCha20Rng::from_entropy()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect::<String>();
+
+    if session
+        .text(serde_json::json!({ "upload_id": upload_id }).to_string())
+        .await
+        .is_err()
+    {
+        return Ok(res);
+    }
+
+    db.write()
+        .await
+        .upload_sockets
+        .insert(upload_id.clone(), session);
+
+    actix_rt::spawn(async move {
+        let _ = tokio::time::timeout(UPLOAD_SOCKET_TIMEOUT, async {
+            while let Some(Ok(msg)) = msg_stream.next().await {
+                if let Message::Close(_) = msg {
+                    break;
+                }
+            }
+        })
+        .await;
+
+        let session = db.read().await.upload_sockets.remove(&upload_id);
+        if let Some((_, session)) = session {
+            let _ = session.close(None).await;
+        }
+    });
+
+    Ok(res)
+}
+
+/// Gets the events of an upload so far, for when its socket was closed or connected to
+/// another instance
+pub async fn upload_progress(
+    id: web::Path<(String,)>,
+    redis: Data<RedisPool>,
+) -> Result<HttpResponse, ApiError> {
+    let events = get_progress(&id.into_inner().0, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(events))
+}
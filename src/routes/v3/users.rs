@@ -1,26 +1,50 @@
 use std::{collections::HashMap, sync::Arc};
 
-use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use chrono::Utc;
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use url::Url;
 use validator::Validate;
 
 use crate::{
-    auth::{filter_visible_projects, get_user_from_headers},
-    database::{models::User, redis::RedisPool},
+    auth::{
+        email::{
+            send_email,
+            template::{request_locale, EmailTemplate},
+        },
+        filter_visible_projects, get_user_from_headers,
+    },
+    database::{
+        models::{
+            flow_item::Flow,
+            job_item::Job,
+            notification_item::{Notification as DBNotification, NotificationFilter},
+            user_profile_item::UserProfile as DBUserProfile,
+            User, DELETION_GRACE_DAYS,
+        },
+        redis::RedisPool,
+    },
     file_hosting::FileHost,
     models::{
-        collections::{Collection, CollectionStatus},
-        ids::UserId,
-        notifications::Notification,
+        collections::{Collection, CollectionId, CollectionStatus},
+        ids::{ProjectId, UserId},
+        jobs::JobPayload,
+        moderation::ImageSource,
+        notifications::{Notification, NotificationType},
         pats::Scopes,
         projects::Project,
-        users::{Badges, Role},
+        users::{Badges, ProfileLink, Role, UserProfile},
+    },
+    queue::{profile_links::is_verifiable, session::AuthQueue},
+    util::{
+        cursor::{next_page_link, Cursor},
+        image_scan,
+        markdown::sanitize_markdown,
+        routes::read_from_payload,
     },
-    queue::session::AuthQueue,
-    util::{routes::read_from_payload, validate::validation_errors_to_string},
 };
 
 use super::{oauth_clients::get_user_clients, ApiError};
@@ -36,14 +60,33 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route("{user_id}/collections", web::get().to(collections_list))
             .route("{user_id}/organizations", web::get().to(orgs_list))
             .route("{id}", web::patch().to(user_edit))
+            .route("{id}/profile", web::patch().to(user_profile_edit))
             .route("{id}/icon", web::patch().to(user_icon_edit))
             .route("{id}", web::delete().to(user_delete))
             .route("{id}/follows", web::get().to(user_follows))
             .route("{id}/notifications", web::get().to(user_notifications))
+            .route(
+                "{id}/notifications",
+                web::patch().to(user_notifications_read),
+            )
+            .route(
+                "{id}/notifications",
+                web::delete().to(user_notifications_delete),
+            )
             .route("{id}/oauth_apps", web::get().to(get_user_clients)),
     );
 }
 
+#[utoipa::path(
+    get,
+    path = "/v3/user/{id}/projects",
+    tag = "users",
+    params(("id" = String, Path, description = "The ID or username of the user")),
+    responses(
+        (status = 200, body = Vec<crate::models::projects::Project>),
+        (status = 404, description = "Not found", body = crate::models::error::ApiError),
+    )
+)]
 pub async fn projects_list(
     req: HttpRequest,
     info: web::Path<(String,)>,
@@ -107,6 +150,13 @@ pub struct UserIds {
     pub ids: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/v3/users",
+    tag = "users",
+    params(("ids" = String, Query, description = "A JSON array of user IDs or usernames")),
+    responses((status = 200, body = Vec<crate::models::users::User>))
+)]
 pub async fn users_get(
     web::Query(ids): web::Query<UserIds>,
     pool: web::Data<PgPool>,
@@ -121,6 +171,16 @@ pub async fn users_get(
     Ok(HttpResponse::Ok().json(users))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v3/user/{id}",
+    tag = "users",
+    params(("id" = String, Path, description = "The ID or username of the user")),
+    responses(
+        (status = 200, body = crate::models::users::User),
+        (status = 404, description = "Not found", body = crate::models::error::ApiError),
+    )
+)]
 pub async fn user_get(
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
@@ -129,13 +189,54 @@ pub async fn user_get(
     let user_data = User::get(&info.into_inner().0, &**pool, &redis).await?;
 
     if let Some(data) = user_data {
-        let response: crate::models::users::User = data.into();
+        let profile = get_public_profile(data.id, &pool, &redis).await?;
+        let mut response: crate::models::users::User = data.into();
+        response.profile = Some(profile);
         Ok(HttpResponse::Ok().json(response))
     } else {
         Err(ApiError::NotFound)
     }
 }
 
+/// Gets a user's profile as it is shown to everyone. Pinned projects and the featured
+/// collection are left out once they are no longer public
+async fn get_public_profile(
+    user_id: crate::database::models::UserId,
+    pool: &PgPool,
+    redis: &RedisPool,
+) -> Result<UserProfile, ApiError> {
+    let profile = DBUserProfile::get(user_id, pool).await?;
+
+    let projects =
+        crate::database::models::Project::get_many_ids(&profile.pinned_projects, pool, redis)
+            .await?;
+    let pinned_projects = profile
+        .pinned_projects
+        .iter()
+        .filter(|id| {
+            projects
+                .iter()
+                .any(|x| x.inner.id == **id && x.inner.status.is_searchable())
+        })
+        .map(|x| (*x).into())
+        .collect();
+
+    let featured_collection = match profile.featured_collection {
+        Some(id) => crate::database::models::Collection::get(id, pool, redis)
+            .await?
+            .filter(|x| x.user_id == user_id && x.status == CollectionStatus::Listed)
+            .map(|x| x.id.into()),
+        None => None,
+    };
+
+    Ok(UserProfile {
+        about: profile.about,
+        pinned_projects,
+        featured_collection,
+        links: profile.links,
+    })
+}
+
 pub async fn collections_list(
     req: HttpRequest,
     info: web::Path<(String,)>,
@@ -306,9 +407,7 @@ pub async fn user_edit(
     )
     .await?;
 
-    new_user
-        .validate()
-        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+    new_user.validate()?;
 
     let id_option = User::get(&info.into_inner().0, &**pool, &redis).await?;
 
@@ -451,6 +550,159 @@ pub async fn user_edit(
     }
 }
 
+#[derive(Serialize, Deserialize, Validate)]
+pub struct EditProfileLink {
+    #[validate(length(min = 1, max = 64))]
+    pub label: String,
+    #[validate(url, length(max = 2048))]
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct EditUserProfile {
+    /// Markdown, which is sanitized before it is saved
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "::serde_with::rust::double_option"
+    )]
+    #[validate(length(max = 65536))]
+    pub about: Option<Option<String>>,
+    /// Projects the user is a member of, in the order they are shown
+    #[validate(length(max = 6))]
+    pub pinned_projects: Option<Vec<ProjectId>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "::serde_with::rust::double_option"
+    )]
+    pub featured_collection: Option<Option<CollectionId>>,
+    #[validate(length(max = 10))]
+    #[validate]
+    pub links: Option<Vec<EditProfileLink>>,
+}
+
+/// Edits the sections of a user's profile. New links are verified in the background, and links
+/// keep their verification as long as their URL is unchanged
+pub async fn user_profile_edit(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    new_profile: web::Json<EditUserProfile>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(
+        &req,
+        &**pool,
+        &redis,
+        &session_queue,
+        Some(&[Scopes::USER_WRITE]),
+    )
+    .await?
+    .1;
+
+    new_profile.validate()?;
+
+    let actual_user = User::get(&info.into_inner().0, &**pool, &redis)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if UserId::from(actual_user.id) != user.id && !user.role.is_mod() {
+        return Err(ApiError::CustomAuthentication(
+            "You do not have permission to edit this user!".to_string(),
+        ));
+    }
+
+    let mut profile = DBUserProfile::get(actual_user.id, &**pool).await?;
+
+    if let Some(about) = &new_profile.about {
+        profile.about = about
+            .as_deref()
+            .map(sanitize_markdown)
+            .filter(|x| !x.trim().is_empty());
+    }
+
+    if let Some(pinned_projects) = &new_profile.pinned_projects {
+        let projects = User::get_projects(actual_user.id, &**pool, &redis).await?;
+
+        profile.pinned_projects.clear();
+        for project_id in pinned_projects {
+            let id = (*project_id).into();
+            if !projects.contains(&id) {
+                return Err(ApiError::InvalidInput(format!(
+                    "Project {project_id} is not one of this user's projects"
+                )));
+            }
+            if !profile.pinned_projects.contains(&id) {
+                profile.pinned_projects.push(id);
+            }
+        }
+    }
+
+    if let Some(featured_collection) = &new_profile.featured_collection {
+        profile.featured_collection = match featured_collection {
+            Some(collection_id) => {
+                let collection = crate::database::models::Collection::get(
+                    (*collection_id).into(),
+                    &**pool,
+                    &redis,
+                )
+                .await?
+                .filter(|x| x.user_id == actual_user.id)
+                .ok_or_else(|| {
+                    ApiError::InvalidInput(format!(
+                        "Collection {collection_id} is not one of this user's collections"
+                    ))
+                })?;
+                Some(collection.id)
+            }
+            None => None,
+        };
+    }
+
+    let mut verify_links = false;
+    if let Some(links) = &new_profile.links {
+        let mut new_links = Vec::new();
+        for link in links {
+            if !Url::parse(&link.url).map_or(false, |x| is_verifiable(&x)) {
+                return Err(ApiError::InvalidInput(format!(
+                    "{} is not an HTTPS link to a website",
+                    link.url
+                )));
+            }
+
+            let verified = profile
+                .links
+                .iter()
+                .find(|x| x.url == link.url)
+                .and_then(|x| x.verified);
+            verify_links |= verified.is_none();
+            new_links.push(ProfileLink {
+                label: link.label.clone(),
+                url: link.url.clone(),
+                verified,
+            });
+        }
+        profile.links = new_links;
+    }
+
+    let mut transaction = pool.begin().await?;
+    profile.upsert(&mut transaction).await?;
+    if verify_links {
+        Job::enqueue(
+            JobPayload::VerifyProfileLinks {
+                user_id: actual_user.id.into(),
+            },
+            Utc::now(),
+            &mut *transaction,
+        )
+        .await?;
+    }
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Extension {
     pub ext: String,
@@ -500,6 +752,7 @@ pub async fn user_icon_edit(
 
             let bytes =
                 read_from_payload(&mut payload, 2097152, "Icons must be smaller than 2MiB").await?;
+            crate::util::magic::check_content(&bytes, &ext.ext, content_type)?;
 
             let hash = sha1::Sha1::from(&bytes).hexdigest();
             let upload_data = file_host
@@ -510,17 +763,20 @@ pub async fn user_icon_edit(
                 )
                 .await?;
 
+            let avatar_url = format!("{}/{}", cdn_url, upload_data.file_name);
             sqlx::query!(
                 "
                 UPDATE users
                 SET avatar_url = $1
                 WHERE (id = $2)
                 ",
-                format!("{}/{}", cdn_url, upload_data.file_name),
+                avatar_url,
                 actual_user.id as crate::database::models::ids::UserId,
             )
             .execute(&**pool)
             .await?;
+            image_scan::queue_scan(&avatar_url, ImageSource::UserAvatar { user_id }, &**pool)
+                .await?;
             User::clear_caches(&[(actual_user.id, None)], &redis).await?;
 
             Ok(HttpResponse::NoContent().body(""))
@@ -535,6 +791,8 @@ pub async fn user_icon_edit(
     }
 }
 
+/// Deletes an account. It is hidden and signed out everywhere, and can be restored until it is
+/// purged `DELETION_GRACE_DAYS` later
 pub async fn user_delete(
     req: HttpRequest,
     info: web::Path<(String,)>,
@@ -553,7 +811,8 @@ pub async fn user_delete(
     .1;
     let id_option = User::get(&info.into_inner().0, &**pool, &redis).await?;
 
-    if let Some(id) = id_option.map(|x| x.id) {
+    if let Some(deleted_user) = id_option {
+        let id = deleted_user.id;
         if !user.role.is_admin() && user.id != id.into() {
             return Err(ApiError::CustomAuthentication(
                 "You do not have permission to delete this user!".to_string(),
@@ -562,10 +821,41 @@ pub async fn user_delete(
 
         let mut transaction = pool.begin().await?;
 
-        let result = User::remove(id, &mut transaction, &redis).await?;
+        let result = User::soft_remove(id, &mut transaction, &redis).await?;
 
         transaction.commit().await?;
 
+        // Users who deleted their own account are emailed a link to restore it, which accounts
+        // deleted by staff don't get
+        if let Some(email) = deleted_user
+            .email
+            .filter(|_| result.is_some() && user.id == id.into())
+        {
+            let flow = Flow::RestoreAccount { user_id: id }
+                .insert(chrono::Duration::days(DELETION_GRACE_DAYS as i64), &redis)
+                .await?;
+
+            send_email(
+                email,
+                EmailTemplate::AccountDeleted,
+                &request_locale(&req),
+                HashMap::from([
+                    ("days".to_string(), DELETION_GRACE_DAYS.to_string()),
+                    (
+                        "button_link".to_string(),
+                        format!(
+                            "{}/{}?flow={}",
+                            dotenvy::var("SITE_URL")?,
+                            dotenvy::var("SITE_RESTORE_ACCOUNT_PATH")?,
+                            flow
+                        ),
+                    ),
+                ]),
+                &**pool,
+            )
+            .await?;
+        }
+
         if result.is_some() {
             Ok(HttpResponse::NoContent().body(""))
         } else {
@@ -631,43 +921,168 @@ pub async fn user_follows(
     }
 }
 
+/// The default and maximum number of notifications returned per page
+const DEFAULT_NOTIFICATIONS_LIMIT: u32 = 50;
+const MAX_NOTIFICATIONS_LIMIT: u32 = 100;
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct NotificationListQuery {
+    /// The cursor from the `Link` header of the previous page
+    pub cursor: Option<Cursor>,
+    pub limit: Option<u32>,
+    /// JSON array of notification types to include
+    pub types: Option<String>,
+    pub read: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NotificationFilterQuery {
+    /// JSON array of notification types to include
+    pub types: Option<String>,
+    pub read: Option<bool>,
+    pub before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+fn parse_notification_types(
+    types: Option<&str>,
+) -> Result<Option<Vec<NotificationType>>, ApiError> {
+    types
+        .map(|x| serde_json::from_str::<Vec<NotificationType>>(x))
+        .transpose()
+        .map_err(|_| ApiError::InvalidInput("Invalid notification types!".to_string()))
+}
+
+/// Gets the user whose notifications are being accessed, checking that the current user may
+/// access them
+async fn get_notifications_user(
+    req: &HttpRequest,
+    user_id: &str,
+    scopes: &[Scopes],
+    pool: &PgPool,
+    redis: &RedisPool,
+    session_queue: &AuthQueue,
+) -> Result<crate::database::models::UserId, ApiError> {
+    let user = get_user_from_headers(req, pool, redis, session_queue, Some(scopes))
+        .await?
+        .1;
+    let id = User::get(user_id, pool, redis)
+        .await?
+        .map(|x| x.id)
+        .ok_or(ApiError::NotFound)?;
+
+    if !user.role.is_admin() && user.id != id.into() {
+        return Err(ApiError::CustomAuthentication(
+            "You do not have permission to see the notifications of this user!".to_string(),
+        ));
+    }
+
+    Ok(id)
+}
+
+/// Lists a user's notifications, newest first. When the list is cut short by `limit`, the next
+/// page is linked in the `Link` header
 pub async fn user_notifications(
     req: HttpRequest,
     info: web::Path<(String,)>,
+    web::Query(query): web::Query<NotificationListQuery>,
     pool: web::Data<PgPool>,
     redis: web::Data<RedisPool>,
     session_queue: web::Data<AuthQueue>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(
+    let id = get_notifications_user(
         &req,
-        &**pool,
+        &info.into_inner().0,
+        &[Scopes::NOTIFICATION_READ],
+        &pool,
         &redis,
         &session_queue,
-        Some(&[Scopes::NOTIFICATION_READ]),
     )
-    .await?
-    .1;
-    let id_option = User::get(&info.into_inner().0, &**pool, &redis).await?;
+    .await?;
 
-    if let Some(id) = id_option.map(|x| x.id) {
-        if !user.role.is_admin() && user.id != id.into() {
-            return Err(ApiError::CustomAuthentication(
-                "You do not have permission to see the notifications of this user!".to_string(),
-            ));
-        }
+    let filter = NotificationFilter {
+        types: parse_notification_types(query.types.as_deref())?,
+        read: query.read,
+        before: None,
+    };
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_NOTIFICATIONS_LIMIT)
+        .clamp(1, MAX_NOTIFICATIONS_LIMIT);
+
+    let (notifications, next_cursor) =
+        DBNotification::get_page_user(id, &filter, query.cursor, limit as i64, &**pool).await?;
+
+    let mut builder = HttpResponse::Ok();
+    if let Some(cursor) = next_cursor {
+        builder.insert_header((header::LINK, next_page_link(&req, cursor)));
+    }
 
-        let mut notifications: Vec<Notification> =
-            crate::database::models::notification_item::Notification::get_many_user(
-                id, &**pool, &redis,
-            )
-            .await?
+    Ok(builder.json(
+        notifications
             .into_iter()
-            .map(Into::into)
-            .collect();
+            .map(Notification::from)
+            .collect::<Vec<_>>(),
+    ))
+}
 
-        notifications.sort_by(|a, b| b.created.cmp(&a.created));
-        Ok(HttpResponse::Ok().json(notifications))
-    } else {
-        Err(ApiError::NotFound)
-    }
+pub async fn user_notifications_read(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    web::Query(query): web::Query<NotificationFilterQuery>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let id = get_notifications_user(
+        &req,
+        &info.into_inner().0,
+        &[Scopes::NOTIFICATION_WRITE],
+        &pool,
+        &redis,
+        &session_queue,
+    )
+    .await?;
+
+    let filter = NotificationFilter {
+        types: parse_notification_types(query.types.as_deref())?,
+        read: None,
+        before: query.before,
+    };
+
+    let mut transaction = pool.begin().await?;
+    DBNotification::read_many_user(id, &filter, &mut transaction, &redis).await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+pub async fn user_notifications_delete(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    web::Query(query): web::Query<NotificationFilterQuery>,
+    pool: web::Data<PgPool>,
+    redis: web::Data<RedisPool>,
+    session_queue: web::Data<AuthQueue>,
+) -> Result<HttpResponse, ApiError> {
+    let id = get_notifications_user(
+        &req,
+        &info.into_inner().0,
+        &[Scopes::NOTIFICATION_WRITE],
+        &pool,
+        &redis,
+        &session_queue,
+    )
+    .await?;
+
+    let filter = NotificationFilter {
+        types: parse_notification_types(query.types.as_deref())?,
+        read: query.read,
+        before: query.before,
+    };
+
+    let mut transaction = pool.begin().await?;
+    DBNotification::remove_many_user(id, &filter, &mut transaction, &redis).await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
 }
@@ -1,5 +1,6 @@
 use super::project_creation::{CreateError, UploadedFile};
 use crate::auth::get_user_from_headers;
+use crate::database::models::job_item::Job;
 use crate::database::models::loader_fields::{LoaderField, LoaderFieldEnumValue, VersionField};
 use crate::database::models::notification_item::NotificationBuilder;
 use crate::database::models::version_item::{
@@ -9,7 +10,8 @@ use crate::database::models::{self, image_item, Organization};
 use crate::database::redis::RedisPool;
 use crate::file_hosting::FileHost;
 use crate::models::images::{Image, ImageContext, ImageId};
-use crate::models::notifications::NotificationBody;
+use crate::models::jobs::JobPayload;
+use crate::models::notifications::{DependencyChange, NotificationBody};
 use crate::models::pack::PackFileHash;
 use crate::models::pats::Scopes;
 use crate::models::projects::{skip_nulls, DependencyType};
@@ -18,13 +20,16 @@ use crate::models::projects::{
     VersionType,
 };
 use crate::models::teams::ProjectPermissions;
+use crate::models::uploads::UploadStatus;
 use crate::queue::session::AuthQueue;
 use crate::util::routes::read_from_field;
-use crate::util::validate::validation_errors_to_string;
+use crate::util::upload_progress::UploadTracker;
+use crate::util::{body_limit, remote_file};
 use crate::validate::{validate_file, ValidationResult};
 use actix_multipart::{Field, Multipart};
 use actix_web::web::Data;
 use actix_web::{web, HttpRequest, HttpResponse};
+use bytes::BytesMut;
 use chrono::Utc;
 use futures::stream::StreamExt;
 use itertools::Itertools;
@@ -34,6 +39,9 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use validator::Validate;
 
+/// The largest project file which can be uploaded, unless the project may upload larger files
+pub const MAX_FILE_SIZE: usize = 500 * (1 << 20);
+
 fn default_requested_status() -> VersionStatus {
     VersionStatus::Listed
 }
@@ -42,8 +50,14 @@ fn default_requested_status() -> VersionStatus {
 pub struct InitialVersionData {
     #[serde(alias = "mod_id")]
     pub project_id: Option<ProjectId>,
-    #[validate(length(min = 1, max = 256))]
+    #[validate(length(max = 256))]
     pub file_parts: Vec<String>,
+    /// Files fetched by the server rather than uploaded, as URLs by file name. Only HTTPS URLs
+    /// on the domains in `REMOTE_FILE_DOMAINS` are accepted. Not supported when creating
+    /// projects
+    #[validate(length(max = 16))]
+    #[serde(default)]
+    pub file_urls: HashMap<String, String>,
     #[validate(
         length(min = 1, max = 32),
         regex = "crate::util::validate::RE_URL_SAFE"
@@ -105,6 +119,7 @@ pub async fn version_create(
 ) -> Result<HttpResponse, CreateError> {
     let mut transaction = client.begin().await?;
     let mut uploaded_files = Vec::new();
+    let progress = UploadTracker::from_request(&req);
 
     let result = version_create_inner(
         req,
@@ -115,10 +130,13 @@ pub async fn version_create(
         &mut uploaded_files,
         &client,
         &session_queue,
+        &progress,
     )
     .await;
 
-    if result.is_err() {
+    if let Err(err) = &result {
+        progress.failed(err).await;
+
         let undo_result =
             super::project_creation::undo_uploads(&***file_host, &uploaded_files).await;
         let rollback_result = transaction.rollback().await;
@@ -129,6 +147,7 @@ pub async fn version_create(
         }
     } else {
         transaction.commit().await?;
+        progress.published().await;
     }
 
     result
@@ -140,10 +159,11 @@ async fn version_create_inner(
     payload: &mut Multipart,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     redis: &RedisPool,
-    file_host: &dyn FileHost,
+    file_host: &(dyn FileHost + Send + Sync),
     uploaded_files: &mut Vec<UploadedFile>,
     pool: &PgPool,
     session_queue: &AuthQueue,
+    progress: &UploadTracker,
 ) -> Result<HttpResponse, CreateError> {
     let cdn_url = dotenvy::var("CDN_URL")?;
 
@@ -190,9 +210,17 @@ async fn version_create_inner(
                     ));
                 }
 
-                version_create_data.validate().map_err(|err| {
-                    CreateError::ValidationError(validation_errors_to_string(err, None))
-                })?;
+                version_create_data.validate()?;
+
+                if let Some(name) = version_create_data
+                    .file_urls
+                    .keys()
+                    .find(|x| version_create_data.file_parts.contains(x))
+                {
+                    return Err(CreateError::InvalidInput(format!(
+                        "{name} is both uploaded and fetched from a URL"
+                    )));
+                }
 
                 if !version_create_data.status.can_be_requested() {
                     return Err(CreateError::InvalidInput(
@@ -336,7 +364,7 @@ async fn version_create_inner(
             upload_file(
                 &mut field,
                 file_host,
-                version_data.file_parts.len(),
+                version_data.file_parts.len() + version_data.file_urls.len(),
                 uploaded_files,
                 &mut version.files,
                 &mut version.dependencies,
@@ -351,6 +379,7 @@ async fn version_create_inner(
                 version_data.file_types.get(name).copied().flatten(),
                 transaction,
                 redis,
+                progress,
             )
             .await?;
 
@@ -369,9 +398,52 @@ async fn version_create_inner(
 
     let version_data = initial_version_data
         .ok_or_else(|| CreateError::InvalidInput("`data` field is required".to_string()))?;
-    let builder = version_builder
+    let mut builder = version_builder
         .ok_or_else(|| CreateError::InvalidInput("`data` field is required".to_string()))?;
 
+    let loaders = selected_loaders
+        .iter()
+        .flatten()
+        .map(|x| Loader(x.loader.clone()))
+        .collect::<Vec<_>>();
+    for (file_name, url) in &version_data.file_urls {
+        let (_, file_extension) = file_name.rsplit_once('.').ok_or_else(|| {
+            CreateError::MissingValueError("Missing content file extension".to_string())
+        })?;
+        if crate::util::ext::project_file_type(file_extension).is_none() {
+            return Err(CreateError::InvalidFileType(file_extension.to_string()));
+        }
+
+        let data = remote_file::fetch(url, body_limit::max_file_size(builder.project_id.into()))
+            .await
+            .map_err(|err| {
+                CreateError::InvalidInput(format!("Could not fetch {file_name}: {err}"))
+            })?;
+
+        upload_file_data(
+            data,
+            file_name,
+            file_extension,
+            file_host,
+            version_data.file_parts.len() + version_data.file_urls.len(),
+            uploaded_files,
+            &mut builder.files,
+            &mut builder.dependencies,
+            &cdn_url,
+            builder.project_id.into(),
+            builder.version_id.into(),
+            &builder.version_fields,
+            loaders.clone(),
+            version_data.primary_file.is_some(),
+            version_data.primary_file.as_ref() == Some(file_name),
+            version_data.file_types.get(file_name).copied().flatten(),
+            transaction,
+            redis,
+            progress,
+        )
+        .await?;
+    }
+
     if builder.files.is_empty() {
         return Err(CreateError::InvalidInput(
             "Versions must have at least one file uploaded to them".to_string(),
@@ -404,6 +476,19 @@ async fn version_create_inner(
     .insert_many(users, &mut *transaction, redis)
     .await?;
 
+    if !builder.status.is_hidden() {
+        Job::enqueue(
+            JobPayload::NotifyDependents {
+                project_id,
+                version_id,
+                change: DependencyChange::Released,
+            },
+            Utc::now(),
+            &mut **transaction,
+        )
+        .await?;
+    }
+
     let loader_structs = selected_loaders.unwrap_or_default();
     let (all_project_types, all_games): (Vec<String>, Vec<String>) =
         loader_structs.iter().fold((vec![], vec![]), |mut acc, x| {
@@ -514,6 +599,7 @@ pub async fn upload_file_to_version(
     let mut uploaded_files = Vec::new();
 
     let version_id = models::VersionId::from(url_data.into_inner().0);
+    let progress = UploadTracker::from_request(&req);
 
     let result = upload_file_to_version_inner(
         req,
@@ -525,10 +611,13 @@ pub async fn upload_file_to_version(
         &mut uploaded_files,
         version_id,
         &session_queue,
+        &progress,
     )
     .await;
 
-    if result.is_err() {
+    if let Err(err) = &result {
+        progress.failed(err).await;
+
         let undo_result =
             super::project_creation::undo_uploads(&***file_host, &uploaded_files).await;
         let rollback_result = transaction.rollback().await;
@@ -539,6 +628,7 @@ pub async fn upload_file_to_version(
         }
     } else {
         transaction.commit().await?;
+        progress.published().await;
     }
 
     result
@@ -551,10 +641,11 @@ async fn upload_file_to_version_inner(
     client: Data<PgPool>,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     redis: Data<RedisPool>,
-    file_host: &dyn FileHost,
+    file_host: &(dyn FileHost + Send + Sync),
     uploaded_files: &mut Vec<UploadedFile>,
     version_id: models::VersionId,
     session_queue: &AuthQueue,
+    progress: &UploadTracker,
 ) -> Result<HttpResponse, CreateError> {
     let cdn_url = dotenvy::var("CDN_URL")?;
 
@@ -710,6 +801,7 @@ async fn upload_file_to_version_inner(
                 file_data.file_types.get(name).copied().flatten(),
                 transaction,
                 &redis,
+                progress,
             )
             .await?;
 
@@ -747,7 +839,7 @@ async fn upload_file_to_version_inner(
 #[allow(clippy::too_many_arguments)]
 pub async fn upload_file(
     field: &mut Field,
-    file_host: &dyn FileHost,
+    file_host: &(dyn FileHost + Send + Sync),
     total_files_len: usize,
     uploaded_files: &mut Vec<UploadedFile>,
     version_files: &mut Vec<VersionFileBuilder>,
@@ -763,22 +855,84 @@ pub async fn upload_file(
     file_type: Option<FileType>,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     redis: &RedisPool,
+    progress: &UploadTracker,
 ) -> Result<(), CreateError> {
     let (file_name, file_extension) = get_name_ext(content_disposition)?;
 
+    if crate::util::ext::project_file_type(file_extension).is_none() {
+        return Err(CreateError::InvalidFileType(file_extension.to_string()));
+    }
+
+    let max_file_size = body_limit::max_file_size(project_id);
+    let data = read_from_field(
+        field,
+        max_file_size,
+        &format!(
+            "Project file exceeds the maximum of {}MiB. Contact a moderator or admin to request permission to upload larger files.",
+            max_file_size >> 20
+        ),
+    )
+    .await?;
+
+    upload_file_data(
+        data,
+        file_name,
+        file_extension,
+        file_host,
+        total_files_len,
+        uploaded_files,
+        version_files,
+        dependencies,
+        cdn_url,
+        project_id,
+        version_id,
+        version_fields,
+        loaders,
+        ignore_primary,
+        force_primary,
+        file_type,
+        transaction,
+        redis,
+        progress,
+    )
+    .await
+}
+
+/// Validates and uploads a file's data, adding it to `version_files`. Used for files which
+/// don't come from a multipart upload, such as imported ones
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_file_data(
+    data: BytesMut,
+    file_name: &str,
+    file_extension: &str,
+    file_host: &(dyn FileHost + Send + Sync),
+    total_files_len: usize,
+    uploaded_files: &mut Vec<UploadedFile>,
+    version_files: &mut Vec<VersionFileBuilder>,
+    dependencies: &mut Vec<DependencyBuilder>,
+    cdn_url: &str,
+    project_id: ProjectId,
+    version_id: VersionId,
+    version_fields: &[VersionField],
+    loaders: Vec<Loader>,
+    ignore_primary: bool,
+    force_primary: bool,
+    file_type: Option<FileType>,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    redis: &RedisPool,
+    progress: &UploadTracker,
+) -> Result<(), CreateError> {
     if file_name.contains('/') {
         return Err(CreateError::InvalidInput(
             "File names must not contain slashes!".to_string(),
         ));
     }
 
+    progress.file(file_name, UploadStatus::Received).await;
+
     let content_type = crate::util::ext::project_file_type(file_extension)
         .ok_or_else(|| CreateError::InvalidFileType(file_extension.to_string()))?;
-
-    let data = read_from_field(
-        field, 500 * (1 << 20),
-        "Project file exceeds the maximum of 500MiB. Contact a moderator or admin to request permission to upload larger files."
-    ).await?;
+    crate::util::magic::check_content(&data, file_extension, content_type)?;
 
     let hash = sha1::Sha1::from(&data).hexdigest();
     let exists = sqlx::query!(
@@ -803,6 +957,8 @@ pub async fn upload_file(
         ));
     }
 
+    progress.file(file_name, UploadStatus::Scanned).await;
+
     let validation_result = validate_file(
         data.clone().into(),
         file_extension.to_string(),
@@ -814,6 +970,8 @@ pub async fn upload_file(
     )
     .await?;
 
+    progress.file(file_name, UploadStatus::Validated).await;
+
     if let ValidationResult::PassWithPackDataAndFiles {
         ref format,
         ref files,
@@ -4,16 +4,19 @@ use super::ApiError;
 use crate::auth::checks::{filter_visible_versions, is_visible_project, is_visible_version};
 use crate::auth::get_user_from_headers;
 use crate::database;
+use crate::database::models::job_item::Job;
 use crate::database::models::loader_fields::{
     self, LoaderField, LoaderFieldEnumValue, VersionField,
 };
-use crate::database::models::version_item::{DependencyBuilder, LoaderVersion};
+use crate::database::models::version_item::{DependencyBuilder, LoaderVersion, VersionFieldFilter};
 use crate::database::models::{image_item, Organization};
 use crate::database::redis::RedisPool;
 use crate::models;
 use crate::models::ids::base62_impl::parse_base62;
 use crate::models::ids::VersionId;
 use crate::models::images::ImageContext;
+use crate::models::jobs::JobPayload;
+use crate::models::notifications::DependencyChange;
 use crate::models::pats::Scopes;
 use crate::models::projects::{skip_nulls, Loader};
 use crate::models::projects::{Dependency, FileType, VersionStatus, VersionType};
@@ -21,9 +24,12 @@ use crate::models::teams::ProjectPermissions;
 use crate::queue::session::AuthQueue;
 use crate::search::indexing::remove_documents;
 use crate::search::SearchConfig;
+use crate::util::cursor::{next_page_link, Cursor};
+use crate::util::etag::{if_match_revisions, revision_etag};
 use crate::util::img;
-use crate::util::validate::validation_errors_to_string;
+use actix_web::http::header::{self, ETag};
 use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
@@ -107,6 +113,13 @@ pub struct VersionIds {
     pub ids: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/v3/versions",
+    tag = "versions",
+    params(("ids" = String, Query, description = "A JSON array of version IDs")),
+    responses((status = 200, body = Vec<crate::models::projects::Version>))
+)]
 pub async fn versions_get(
     req: HttpRequest,
     web::Query(ids): web::Query<VersionIds>,
@@ -136,6 +149,16 @@ pub async fn versions_get(
     Ok(HttpResponse::Ok().json(versions))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v3/version/{id}",
+    tag = "versions",
+    params(("id" = String, Path, description = "The ID of the version")),
+    responses(
+        (status = 200, body = crate::models::projects::Version),
+        (status = 404, description = "Not found", body = crate::models::error::ApiError),
+    )
+)]
 pub async fn version_get(
     req: HttpRequest,
     info: web::Path<(models::ids::VersionId,)>,
@@ -169,7 +192,9 @@ pub async fn version_get_helper(
 
     if let Some(data) = version_data {
         if is_visible_version(&data.inner, &user_option, &pool, &redis).await? {
-            return Ok(HttpResponse::Ok().json(models::projects::Version::from(data)));
+            return Ok(HttpResponse::Ok()
+                .insert_header(ETag(revision_etag(data.inner.revision)))
+                .json(models::projects::Version::from(data)));
         }
     }
 
@@ -231,22 +256,28 @@ pub async fn version_edit(
     session_queue: web::Data<AuthQueue>,
 ) -> Result<HttpResponse, ApiError> {
     let new_version: EditVersion = serde_json::from_value(new_version.into_inner())?;
+    let if_match = if_match_revisions(&req);
     version_edit_helper(
         req,
         info.into_inner(),
         pool,
         redis,
         new_version,
+        if_match,
         session_queue,
     )
     .await
 }
+
+/// Edits a version. `if_match` holds the revisions the edit may be made to, as it is only
+/// read from `If-Match` when the version itself is being edited
 pub async fn version_edit_helper(
     req: HttpRequest,
     info: (VersionId,),
     pool: web::Data<PgPool>,
     redis: web::Data<RedisPool>,
     new_version: EditVersion,
+    if_match: Option<Vec<i32>>,
     session_queue: web::Data<AuthQueue>,
 ) -> Result<HttpResponse, ApiError> {
     let user = get_user_from_headers(
@@ -259,9 +290,7 @@ pub async fn version_edit_helper(
     .await?
     .1;
 
-    new_version
-        .validate()
-        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+    new_version.validate()?;
 
     let version_id = info.0;
     let id = version_id.into();
@@ -309,6 +338,10 @@ pub async fn version_edit_helper(
 
             let mut transaction = pool.begin().await?;
 
+            database::models::Version::bump_revision(id, if_match.as_deref(), &mut transaction)
+                .await?
+                .ok_or(ApiError::PreconditionFailed)?;
+
             if let Some(name) = &new_version.name {
                 sqlx::query!(
                     "
@@ -601,6 +634,24 @@ pub async fn version_edit_helper(
                 )
                 .execute(&mut *transaction)
                 .await?;
+
+                let change = match (version_item.inner.status.is_hidden(), status.is_hidden()) {
+                    (true, false) => Some(DependencyChange::Released),
+                    (false, true) => Some(DependencyChange::Yanked),
+                    _ => None,
+                };
+                if let Some(change) = change {
+                    Job::enqueue(
+                        JobPayload::NotifyDependents {
+                            project_id: version_item.inner.project_id.into(),
+                            version_id: id.into(),
+                            change,
+                        },
+                        Utc::now(),
+                        &mut *transaction,
+                    )
+                    .await?;
+                }
             }
 
             if let Some(file_types) = &new_version.file_types {
@@ -689,6 +740,8 @@ pub struct VersionListFilters {
     pub version_type: Option<VersionType>,
     pub limit: Option<usize>,
     pub offset: Option<usize>,
+    /// The cursor from the `Link` header of the previous page
+    pub cursor: Option<Cursor>,
     /*
         Loader fields to filter with:
         "game_versions": ["1.16.5", "1.17"]
@@ -698,10 +751,47 @@ pub struct VersionListFilters {
     pub loader_fields: Option<String>,
 }
 
+/// The parameters of `version_list` which aren't loader fields
+const VERSION_LIST_PARAMS: &[&str] = &[
+    "loaders",
+    "featured",
+    "version_type",
+    "limit",
+    "offset",
+    "cursor",
+    "loader_fields",
+];
+
+#[utoipa::path(
+    get,
+    path = "/v3/project/{id}/version",
+    tag = "versions",
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        ("loaders" = Option<String>, Query, description = "A JSON array of loaders to filter by"),
+        ("featured" = Option<bool>, Query, description = "Only return featured or non-featured versions"),
+        ("version_type" = Option<VersionType>, Query, description = "Only return versions of this type"),
+        ("limit" = Option<usize>, Query, description = "How many versions to return"),
+        ("offset" = Option<usize>, Query, description = "How many versions to skip"),
+        ("cursor" = Option<String>, Query, description = "The cursor from the `Link` header of the previous page"),
+    ),
+    responses(
+        (status = 200, body = Vec<crate::models::projects::Version>, description = "The versions, newest first. When `limit` cuts the list short, the next page is linked in the `Link` header"),
+        (status = 404, description = "Not found", body = crate::models::error::ApiError),
+    )
+)]
+/// Lists a project's versions. Any loader field can also be passed to only get versions with
+/// one of its comma-separated values, such as `game_versions=1.20.1,1.21.x`, where `.x` matches
+/// a version and everything under it.
+///
+/// Pages can be fetched with `limit` and `offset`, or by following the `rel="next"` link, which
+/// continues from the last version of the page even if versions are published in between
+#[allow(clippy::too_many_arguments)]
 pub async fn version_list(
     req: HttpRequest,
     info: web::Path<(String,)>,
     web::Query(filters): web::Query<VersionListFilters>,
+    web::Query(fields): web::Query<HashMap<String, String>>,
     pool: web::Data<PgPool>,
     redis: web::Data<RedisPool>,
     session_queue: web::Data<AuthQueue>,
@@ -733,11 +823,49 @@ pub async fn version_list(
             .loaders
             .as_ref()
             .map(|x| serde_json::from_str::<Vec<String>>(x).unwrap_or_default());
-        let mut versions = database::models::Version::get_many(&project.versions, &**pool, &redis)
+
+        // Parameters which aren't loader fields are left alone, so unknown ones are ignored
+        let all_loader_fields = LoaderField::get_fields_all(&**pool, &redis).await?;
+        let field_filters = fields
+            .into_iter()
+            .filter(|(key, _)| !VERSION_LIST_PARAMS.contains(&&**key))
+            .filter_map(|(key, values)| {
+                Some(VersionFieldFilter {
+                    field_id: all_loader_fields.iter().find(|x| x.field == key)?.id,
+                    values: values
+                        .split(',')
+                        .map(|x| x.trim().to_string())
+                        .filter(|x| !x.is_empty())
+                        .collect(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let version_ids = if field_filters.is_empty() {
+            project.versions.clone()
+        } else {
+            database::models::Version::get_ids_matching_fields(
+                project.inner.id,
+                &field_filters,
+                &**pool,
+            )
+            .await?
+        };
+        // Only versions after the cursor in the list are loaded
+        let version_ids = if let Some(cursor) = filters.cursor {
+            database::models::Version::get_ids_before(
+                &version_ids,
+                cursor.timestamp,
+                cursor.id,
+                &**pool,
+            )
+            .await?
+        } else {
+            version_ids
+        };
+
+        let mut versions = database::models::Version::get_many(&version_ids, &**pool, &redis)
             .await?
             .into_iter()
-            .skip(filters.offset.unwrap_or(0))
-            .take(filters.limit.unwrap_or(usize::MAX))
             .filter(|x| {
                 let mut bool = true;
 
@@ -823,12 +951,33 @@ pub async fn version_list(
             }
         }
 
-        response.sort_by(|a, b| b.inner.date_published.cmp(&a.inner.date_published));
+        // Ordered by (date published, ID), so versions published at the same time keep their
+        // order between pages
+        response.sort_by(|a, b| {
+            (b.inner.date_published, b.inner.id.0).cmp(&(a.inner.date_published, a.inner.id.0))
+        });
         response.dedup_by(|a, b| a.inner.id == b.inner.id);
 
-        let response = filter_visible_versions(response, &user_option, &pool, &redis).await?;
+        let mut response = filter_visible_versions(response, &user_option, &pool, &redis)
+            .await?
+            .into_iter()
+            .skip(filters.offset.unwrap_or(0))
+            .collect::<Vec<_>>();
+
+        let mut builder = HttpResponse::Ok();
+        if let Some(limit) = filters.limit {
+            if response.len() > limit {
+                response.truncate(limit);
+                if let Some(last) = response.last() {
+                    builder.insert_header((
+                        header::LINK,
+                        next_page_link(&req, Cursor::new(last.date_published, last.id.0 as i64)),
+                    ));
+                }
+            }
+        }
 
-        Ok(HttpResponse::Ok().json(response))
+        Ok(builder.json(response))
     } else {
         Err(ApiError::NotFound)
     }
@@ -907,6 +1056,19 @@ pub async fn version_delete(
         image_item::Image::remove(image.id, &mut transaction, &redis).await?;
     }
 
+    if !version.inner.status.is_hidden() {
+        Job::enqueue(
+            JobPayload::NotifyDependents {
+                project_id: version.inner.project_id.into(),
+                version_id: version.inner.id.into(),
+                change: DependencyChange::Yanked,
+            },
+            Utc::now(),
+            &mut *transaction,
+        )
+        .await?;
+    }
+
     let result =
         database::models::Version::remove_full(version.inner.id, &redis, &mut transaction).await?;
     transaction.commit().await?;
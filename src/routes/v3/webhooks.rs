@@ -0,0 +1,37 @@
+use crate::queue::payouts::PayoutsQueue;
+use crate::routes::ApiError;
+use crate::webhooks::{self, WebhookSource};
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("webhook")
+            .route("github", web::post().to(github_webhook))
+            .route("email", web::post().to(email_webhook)),
+    );
+}
+
+/// Receives release events of repositories linked as the source of projects
+pub async fn github_webhook(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    payouts: web::Data<PayoutsQueue>,
+    body: String,
+) -> Result<HttpResponse, ApiError> {
+    webhooks::receive(WebhookSource::GitHub, req.headers(), &body, &pool, &payouts).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Receives bounces and complaints about emails sent to users
+pub async fn email_webhook(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    payouts: web::Data<PayoutsQueue>,
+    body: String,
+) -> Result<HttpResponse, ApiError> {
+    webhooks::receive(WebhookSource::Email, req.headers(), &body, &pool, &payouts).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
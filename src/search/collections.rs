@@ -0,0 +1,119 @@
+use super::{SearchConfig, SearchError};
+use chrono::{DateTime, Utc};
+use meilisearch_sdk::client::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{info_span, Instrument};
+
+/// A collection document used for uploading collections to MeiliSearch's collection index
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadSearchCollection {
+    pub collection_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// The username of the collection's owner
+    pub author: String,
+    pub icon_url: Option<String>,
+    pub color: Option<u32>,
+    /// The number of searchable projects in the collection
+    pub project_count: i64,
+    /// Every category of the searchable projects in the collection
+    pub categories: Vec<String>,
+    pub follows: i32,
+    /// RFC 3339 formatted creation date of the collection
+    pub date_created: DateTime<Utc>,
+    /// Unix timestamp of the creation date of the collection
+    pub created_timestamp: i64,
+    /// RFC 3339 formatted date/time the collection was last updated
+    pub date_modified: DateTime<Utc>,
+    /// Unix timestamp of the last update
+    pub modified_timestamp: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResultSearchCollection {
+    pub collection_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub author: String,
+    pub icon_url: Option<String>,
+    pub color: Option<u32>,
+    pub project_count: i64,
+    pub categories: Vec<String>,
+    pub follows: i32,
+    /// RFC 3339 formatted creation date of the collection
+    pub date_created: String,
+    /// RFC 3339 formatted date the collection was last updated
+    pub date_modified: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CollectionSearchResults {
+    pub hits: Vec<ResultSearchCollection>,
+    pub page: usize,
+    pub hits_per_page: usize,
+    pub total_hits: usize,
+}
+
+// These fields must always succeed parsing; deserialize errors aren't
+// processed correctly (don't return JSON errors)
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CollectionSearchRequest {
+    pub query: Option<String>,
+    pub offset: Option<String>,
+    pub index: Option<String>,
+    pub limit: Option<String>,
+
+    /// A Meilisearch filter, such as `categories = "magic" AND project_count > 10`
+    pub filters: Option<String>,
+}
+
+fn get_sort(index: &str) -> Result<[&'static str; 1], SearchError> {
+    Ok(match index {
+        "relevance" | "follows" => ["follows:desc"],
+        "projects" => ["project_count:desc"],
+        "updated" => ["date_modified:desc"],
+        "newest" => ["date_created:desc"],
+        i => return Err(SearchError::InvalidIndex(i.to_string())),
+    })
+}
+
+#[tracing::instrument(skip_all, fields(index = info.index.as_deref().unwrap_or("relevance")))]
+pub async fn search_for_collections(
+    info: &CollectionSearchRequest,
+    config: &SearchConfig,
+) -> Result<CollectionSearchResults, SearchError> {
+    let client = Client::new(&*config.address, Some(&*config.key));
+
+    let offset: usize = info.offset.as_deref().unwrap_or("0").parse()?;
+    let limit: usize = info.limit.as_deref().unwrap_or("10").parse()?;
+    let sort = get_sort(info.index.as_deref().unwrap_or("relevance"))?;
+
+    let meilisearch_index = client
+        .get_index(config.get_index_name("collections"))
+        .await?;
+
+    let results = {
+        let mut query = meilisearch_index.search();
+        query
+            .with_page(offset / limit + 1)
+            .with_hits_per_page(limit)
+            .with_query(info.query.as_deref().unwrap_or_default())
+            .with_sort(&sort);
+
+        if let Some(filters) = info.filters.as_deref() {
+            query.with_filter(filters);
+        }
+
+        query
+            .execute::<ResultSearchCollection>()
+            .instrument(info_span!("meilisearch.search", db.system = "meilisearch"))
+            .await?
+    };
+
+    Ok(CollectionSearchResults {
+        hits: results.hits.into_iter().map(|r| r.result).collect(),
+        page: results.page.unwrap_or_default(),
+        hits_per_page: results.hits_per_page.unwrap_or_default(),
+        total_hits: results.total_hits.unwrap_or_default(),
+    })
+}
@@ -0,0 +1,151 @@
+use log::info;
+use meilisearch_sdk::indexes::Index;
+use meilisearch_sdk::settings::{PaginationSetting, Settings};
+use sqlx::postgres::PgPool;
+
+use super::{create_or_update_index, IndexingError, MEILISEARCH_CHUNK_SIZE, TIMEOUT};
+use crate::models::collections::CollectionStatus;
+use crate::models::ids::base62_impl::to_base62;
+use crate::search::collections::UploadSearchCollection;
+use crate::search::SearchConfig;
+
+pub async fn get_collections_index(
+    config: &SearchConfig,
+) -> Result<Index, meilisearch_sdk::errors::Error> {
+    let client = config.make_client();
+    let name = config.get_index_name("collections");
+
+    create_or_update_index(&client, &name, "collection_id", collection_settings()).await
+}
+
+/// Replaces the collections index with every listed collection. Collections are few and
+/// small compared to projects, so they are indexed in one go
+#[tracing::instrument(skip_all)]
+pub async fn index_collections(pool: &PgPool, config: &SearchConfig) -> Result<(), IndexingError> {
+    info!("Indexing collections.");
+
+    let index = get_collections_index(config).await?;
+
+    let searchable_statuses = crate::models::projects::ProjectStatus::iterator()
+        .filter(|x| x.is_searchable())
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+
+    // Only searchable projects count towards a collection's size and categories, so the
+    // index doesn't reveal anything about private projects
+    let collections = sqlx::query!(
+        "
+        SELECT c.id, c.name, c.description, c.icon_url, c.color, c.created, c.updated, c.follows,
+        u.username,
+        COUNT(DISTINCT m.id) project_count,
+        ARRAY_AGG(DISTINCT cat.category) filter (where cat.category is not null) categories
+        FROM collections c
+        INNER JOIN users u ON u.id = c.user_id
+        LEFT JOIN collections_mods cm ON cm.collection_id = c.id
+        LEFT JOIN mods m ON m.id = cm.mod_id AND m.status = ANY($1)
+        LEFT JOIN mods_categories mc ON mc.joining_mod_id = m.id
+        LEFT JOIN categories cat ON cat.id = mc.joining_category_id
+        WHERE c.status = $2
+        GROUP BY c.id, u.username
+        ",
+        &searchable_statuses,
+        CollectionStatus::Listed.to_string(),
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|c| UploadSearchCollection {
+        collection_id: to_base62(c.id as u64),
+        name: c.name,
+        description: c.description,
+        author: c.username,
+        icon_url: c.icon_url,
+        color: c.color.map(|x| x as u32),
+        project_count: c.project_count.unwrap_or(0),
+        categories: c.categories.unwrap_or_default(),
+        follows: c.follows,
+        date_created: c.created,
+        created_timestamp: c.created.timestamp(),
+        date_modified: c.updated,
+        modified_timestamp: c.updated.timestamp(),
+    })
+    .collect::<Vec<_>>();
+
+    let client = config.make_client();
+    index
+        .delete_all_documents()
+        .await?
+        .wait_for_completion(&client, None, Some(TIMEOUT))
+        .await?;
+
+    for chunk in collections.chunks(MEILISEARCH_CHUNK_SIZE) {
+        index
+            .add_or_replace(chunk, Some("collection_id"))
+            .await?
+            .wait_for_completion(&client, None, Some(TIMEOUT))
+            .await?;
+    }
+
+    info!("Indexed {} collections.", collections.len());
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all, fields(count = ids.len()))]
+pub async fn remove_collections(
+    ids: &[crate::models::ids::CollectionId],
+    config: &SearchConfig,
+) -> Result<(), meilisearch_sdk::errors::Error> {
+    let index = get_collections_index(config).await?;
+
+    index
+        .delete_documents(&ids.iter().map(|x| to_base62(x.0)).collect::<Vec<_>>())
+        .await?;
+
+    Ok(())
+}
+
+fn collection_settings() -> Settings {
+    // Sorted like the settings Meilisearch returns, so unchanged settings aren't set again
+    let mut sorted_display = DISPLAYED_ATTRIBUTES.to_vec();
+    sorted_display.sort();
+    let mut sorted_sortable = SORTABLE_ATTRIBUTES.to_vec();
+    sorted_sortable.sort();
+    let mut sorted_attrs = ATTRIBUTES_FOR_FACETING.to_vec();
+    sorted_attrs.sort();
+    Settings::new()
+        .with_displayed_attributes(sorted_display)
+        .with_searchable_attributes(SEARCHABLE_ATTRIBUTES)
+        .with_sortable_attributes(sorted_sortable)
+        .with_filterable_attributes(sorted_attrs)
+        .with_pagination(PaginationSetting {
+            max_total_hits: 2147483647,
+        })
+}
+
+const DISPLAYED_ATTRIBUTES: &[&str] = &[
+    "collection_id",
+    "name",
+    "description",
+    "author",
+    "icon_url",
+    "color",
+    "project_count",
+    "categories",
+    "follows",
+    "date_created",
+    "date_modified",
+];
+
+const SEARCHABLE_ATTRIBUTES: &[&str] = &["name", "description", "author"];
+
+const ATTRIBUTES_FOR_FACETING: &[&str] = &[
+    "author",
+    "categories",
+    "project_count",
+    "follows",
+    "created_timestamp",
+    "modified_timestamp",
+];
+
+const SORTABLE_ATTRIBUTES: &[&str] = &["follows", "project_count", "date_created", "date_modified"];
@@ -1,62 +1,114 @@
 use futures::TryStreamExt;
+use itertools::Itertools;
 use log::info;
 use std::collections::HashMap;
 
 use super::IndexingError;
+use crate::database::models::categories::Category;
 use crate::database::models::{project_item, version_item, ProjectId, VersionId};
 use crate::database::redis::RedisPool;
 use crate::models;
-use crate::models::v2::projects::LegacyProject;
+use crate::models::v2::projects::{LegacyProject, LegacySideType};
 use crate::routes::v2_reroute;
 use crate::search::UploadSearchProject;
 use sqlx::postgres::PgPool;
 
-pub async fn get_all_ids(
-    pool: PgPool,
-) -> Result<Vec<(VersionId, ProjectId, String)>, IndexingError> {
+fn searchable_statuses() -> Vec<String> {
+    crate::models::projects::ProjectStatus::iterator()
+        .filter(|x| x.is_searchable())
+        .map(|x| x.to_string())
+        .collect()
+}
+
+pub async fn count_searchable_projects(pool: &PgPool) -> Result<i64, IndexingError> {
+    let count = sqlx::query!(
+        "
+        SELECT COUNT(*) count
+        FROM mods
+        WHERE status = ANY($1) AND deleted IS NULL
+        ",
+        &*searchable_statuses(),
+    )
+    .fetch_one(pool)
+    .await?
+    .count;
+
+    Ok(count.unwrap_or(0))
+}
+
+/// Gets the IDs of up to `limit` searchable projects, newest first. Projects after the first
+/// chunk are fetched by passing the last ID of the previous chunk as `before`
+pub async fn get_project_ids_chunk(
+    pool: &PgPool,
+    before: Option<ProjectId>,
+    limit: i64,
+) -> Result<Vec<ProjectId>, IndexingError> {
+    let ids = sqlx::query!(
+        "
+        SELECT id
+        FROM mods
+        WHERE status = ANY($1) AND deleted IS NULL AND ($2::bigint IS NULL OR id < $2)
+        ORDER BY id DESC
+        LIMIT $3
+        ",
+        &*searchable_statuses(),
+        before.map(|x| x.0),
+        limit,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|x| ProjectId(x.id))
+    .collect();
+
+    Ok(ids)
+}
+
+/// Gets the visible versions of projects, along with the project and owner of each
+pub async fn get_visible_ids(
+    pool: &PgPool,
+    project_ids: &[ProjectId],
+) -> Result<HashMap<VersionId, (ProjectId, String)>, IndexingError> {
     // TODO: Currently org owner is set to be considered owner. It may be worth considering
     // adding a new facetable 'organization' field to the search index, and using that instead,
     // and making owner to be optional.
-    let all_visible_ids: Vec<(VersionId, ProjectId, String)> = sqlx::query!(
+    let visible_ids = sqlx::query!(
         "
         SELECT v.id id, m.id mod_id, COALESCE(u.username, ou.username) owner_username
         FROM versions v
-        INNER JOIN mods m ON v.mod_id = m.id AND m.status = ANY($2)
+        INNER JOIN mods m ON v.mod_id = m.id
         LEFT JOIN team_members tm ON tm.team_id = m.team_id AND tm.is_owner = TRUE AND tm.accepted = TRUE
         LEFT JOIN users u ON tm.user_id = u.id
         LEFT JOIN organizations o ON o.id = m.organization_id
         LEFT JOIN team_members otm ON otm.team_id = o.team_id AND otm.is_owner = TRUE AND otm.accepted = TRUE
         LEFT JOIN users ou ON otm.user_id = ou.id
-        WHERE v.status != ANY($1)
+        WHERE m.id = ANY($2) AND v.status != ANY($1)
         GROUP BY v.id, m.id, u.username, ou.username
-        ORDER BY m.id DESC;
         ",
         &*crate::models::projects::VersionStatus::iterator()
             .filter(|x| x.is_hidden())
             .map(|x| x.to_string())
             .collect::<Vec<String>>(),
-        &*crate::models::projects::ProjectStatus::iterator()
-            .filter(|x| x.is_searchable())
-            .map(|x| x.to_string())
-            .collect::<Vec<String>>(),
+        &project_ids.iter().map(|x| x.0).collect::<Vec<_>>(),
     )
-    .fetch_many(&pool)
-    .try_filter_map(|e| async move {
-        Ok(e.right().map(|m| {
-            let project_id: ProjectId = ProjectId(m.mod_id);
-            let version_id: VersionId = VersionId(m.id);
-            let owner_username = m.owner_username.unwrap_or_default();
-            (version_id, project_id, owner_username)
-        }))
+    .fetch(pool)
+    .map_ok(|m| {
+        (
+            VersionId(m.id),
+            (ProjectId(m.mod_id), m.owner_username.unwrap_or_default()),
+        )
     })
-    .try_collect::<Vec<_>>()
+    .try_collect()
     .await?;
 
-    Ok(all_visible_ids)
+    Ok(visible_ids)
 }
 
+/// Builds the search documents of versions. Projects and versions are read from `read_pool`
+/// without going through the cache, while categories are read from the primary's cache
 pub async fn index_local(
     pool: &PgPool,
+    read_pool: &PgPool,
     redis: &RedisPool,
     visible_ids: HashMap<VersionId, (ProjectId, String)>,
 ) -> Result<Vec<UploadSearchProject>, IndexingError> {
@@ -66,16 +118,17 @@ pub async fn index_local(
         .map(|(project_id, _)| project_id)
         .cloned()
         .collect::<Vec<_>>();
-    let projects: HashMap<_, _> = project_item::Project::get_many_ids(&project_ids, pool, redis)
-        .await?
-        .into_iter()
-        .map(|p| (p.inner.id, p))
-        .collect();
+    let projects: HashMap<_, _> =
+        project_item::Project::get_many_ids_uncached(&project_ids, read_pool)
+            .await?
+            .into_iter()
+            .map(|p| (p.inner.id, p))
+            .collect();
 
     info!("Fetched local projects!");
 
     let version_ids = visible_ids.keys().cloned().collect::<Vec<_>>();
-    let versions: HashMap<_, _> = version_item::Version::get_many(&version_ids, pool, redis)
+    let versions: HashMap<_, _> = version_item::Version::get_many_uncached(&version_ids, read_pool)
         .await?
         .into_iter()
         .map(|v| (v.inner.id, v))
@@ -83,6 +136,8 @@ pub async fn index_local(
 
     info!("Fetched local versions!");
 
+    let category_ancestors = Category::get_ancestors(&Category::list(pool, redis).await?);
+
     let mut uploads = Vec::new();
     // TODO: could possibly clone less here?
     for (version_id, (project_id, owner_username)) in visible_ids {
@@ -121,6 +176,22 @@ pub async fn index_local(
         let display_categories = categories.clone();
         categories.append(&mut additional_categories);
 
+        // Projects are also found by the categories their own categories are nested under.
+        // These are kept apart so they aren't shown as the project's categories
+        let mut ancestor_categories = categories
+            .iter()
+            .cartesian_product(&m.project_types)
+            .filter_map(|(category, project_type)| {
+                category_ancestors.get(&(project_type.clone(), category.clone()))
+            })
+            .flatten()
+            .filter(|x| !categories.contains(x))
+            .cloned()
+            .collect::<Vec<_>>();
+        ancestor_categories.sort();
+        ancestor_categories.dedup();
+        categories.extend(ancestor_categories.iter().cloned());
+
         let version_fields = v.version_fields.clone();
         let unvectorized_loader_fields = v
             .version_fields
@@ -181,6 +252,7 @@ pub async fn index_local(
             &unvectorized_loader_fields,
             Some(&v2_og_project_type),
         );
+        let environment = get_environment(client_side, server_side);
 
         if let Ok(client_side) = serde_json::to_value(client_side) {
             loader_fields.insert("client_side".to_string(), vec![client_side]);
@@ -225,6 +297,7 @@ pub async fn index_local(
             display_categories,
             open_source,
             color: m.inner.color,
+            environment,
             loader_fields,
             license_url: m.inner.license_url.clone(),
             monetization_status: Some(m.inner.monetization_status),
@@ -239,6 +312,7 @@ pub async fn index_local(
             games: m.games.clone(),
             links: m.urls.clone(),
             gallery_items: m.gallery_items.clone(),
+            ancestor_categories,
             loaders,
         };
 
@@ -247,3 +321,22 @@ pub async fn index_local(
 
     Ok(uploads)
 }
+
+/// Gets the values of the `environment` facet from the sides a version supports. These are
+/// worked out the same way for every loader and project type, so the side loader fields of a
+/// plugin, a resource pack and a mod can all be searched for the same way
+fn get_environment(client_side: LegacySideType, server_side: LegacySideType) -> Vec<String> {
+    let mut environment = Vec::new();
+    for (side, side_type) in [("client", client_side), ("server", server_side)] {
+        match side_type {
+            LegacySideType::Required => {
+                environment.push(side.to_string());
+                environment.push(format!("{side}_required"));
+            }
+            LegacySideType::Optional => environment.push(side.to_string()),
+            LegacySideType::Unsupported | LegacySideType::Unknown => {}
+        }
+    }
+
+    environment
+}
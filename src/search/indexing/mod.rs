@@ -1,21 +1,26 @@
 /// This module is used for the indexing from any source.
+pub mod collections;
 pub mod local_import;
 
-use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Instant;
 
 use crate::database::redis::RedisPool;
+use crate::database::ReadOnlyPool;
 use crate::models::ids::base62_impl::to_base62;
 use crate::search::{SearchConfig, UploadSearchProject};
+use crate::util::env::parse_var;
+use crate::util::metrics::{result_label, INDEXING_DURATION};
 use local_import::index_local;
-use log::info;
+use log::{info, warn};
 use meilisearch_sdk::client::Client;
+use meilisearch_sdk::errors::ErrorCode;
 use meilisearch_sdk::indexes::Index;
 use meilisearch_sdk::settings::{PaginationSetting, Settings};
 use sqlx::postgres::PgPool;
 use thiserror::Error;
 
-use self::local_import::get_all_ids;
+use self::local_import::{count_searchable_projects, get_project_ids_chunk, get_visible_ids};
 
 #[derive(Error, Debug)]
 pub enum IndexingError {
@@ -36,11 +41,14 @@ pub enum IndexingError {
 // The chunk size for adding projects to the indexing database. If the request size
 // is too large (>10MiB) then the request fails with an error.  This chunk size
 // assumes a max average size of 4KiB per project to avoid this cap.
-const MEILISEARCH_CHUNK_SIZE: usize = 2500; // Should be less than FETCH_PROJECT_SIZE
-const FETCH_PROJECT_SIZE: usize = 5000;
+const MEILISEARCH_CHUNK_SIZE: usize = 2500;
+/// How many projects are loaded, transformed and uploaded at a time by default. Only one
+/// chunk is held in memory at once, so this bounds the memory indexing uses
+const DEFAULT_INDEXING_CHUNK_SIZE: i64 = 1000;
 
 const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
 
+#[tracing::instrument(skip_all, fields(count = ids.len()))]
 pub async fn remove_documents(
     ids: &[crate::models::ids::VersionId],
     config: &SearchConfig,
@@ -56,8 +64,27 @@ pub async fn remove_documents(
     Ok(())
 }
 
+#[tracing::instrument(skip_all)]
+/// Indexes every searchable project. Projects are read from the read replica, while the cached
+/// lookups indexing needs are made on the primary, so nothing read from the replica is cached
 pub async fn index_projects(
     pool: PgPool,
+    read_pool: ReadOnlyPool,
+    redis: RedisPool,
+    config: &SearchConfig,
+) -> Result<(), IndexingError> {
+    let started = Instant::now();
+    let result = index_all_projects(pool, read_pool.0, redis, config).await;
+    INDEXING_DURATION
+        .with_label_values(&[result_label(&result)])
+        .observe(started.elapsed().as_secs_f64());
+
+    result
+}
+
+async fn index_all_projects(
+    pool: PgPool,
+    read_pool: PgPool,
     redis: RedisPool,
     config: &SearchConfig,
 ) -> Result<(), IndexingError> {
@@ -72,55 +99,68 @@ pub async fn index_projects(
             .map(|x| x.field)
             .collect::<Vec<_>>();
 
-    let all_ids = get_all_ids(pool.clone()).await?;
-    let all_ids_len = all_ids.len();
-    info!("Got all ids, indexing {} projects", all_ids_len);
-
-    let mut so_far = 0;
-    let as_chunks: Vec<_> = all_ids
-        .into_iter()
-        .chunks(FETCH_PROJECT_SIZE)
-        .into_iter()
-        .map(|x| x.collect::<Vec<_>>())
-        .collect();
-
-    for id_chunk in as_chunks {
+    let chunk_size = parse_var("INDEXING_CHUNK_SIZE").unwrap_or(DEFAULT_INDEXING_CHUNK_SIZE);
+    let total = count_searchable_projects(&read_pool).await?;
+    info!("Indexing {} projects in chunks of {}", total, chunk_size);
+
+    let mut cursor = None;
+    let mut indexed_projects = 0;
+    let mut indexed_versions = 0;
+    let mut failed = Vec::new();
+    loop {
+        let project_ids = get_project_ids_chunk(&read_pool, cursor, chunk_size).await?;
+        let Some(last) = project_ids.last() else {
+            break;
+        };
+        cursor = Some(*last);
+
+        let visible_ids = get_visible_ids(&read_pool, &project_ids).await?;
+        let uploads = index_local(&pool, &read_pool, &redis, visible_ids).await?;
+        indexed_versions += uploads.len();
+        failed.extend(add_projects(&indices, uploads, all_loader_fields.clone(), config).await?);
+
+        indexed_projects += project_ids.len();
         info!(
-            "Fetching chunk {}-{}/{}, size: {}",
-            so_far,
-            so_far + FETCH_PROJECT_SIZE,
-            all_ids_len,
-            id_chunk.len()
+            "Indexed {}/{} projects ({} versions)",
+            indexed_projects, total, indexed_versions
         );
-        so_far += FETCH_PROJECT_SIZE;
+    }
 
-        let id_chunk = id_chunk
-            .into_iter()
-            .map(|(version_id, project_id, owner_username)| {
-                (version_id, (project_id, owner_username))
-            })
-            .collect::<HashMap<_, _>>();
-        let uploads = index_local(&pool, &redis, id_chunk).await?;
-
-        info!("Got chunk, adding to docs_to_add");
-        add_projects(&indices, uploads, all_loader_fields.clone(), config).await?;
+    collections::index_collections(&read_pool, config).await?;
+
+    if failed.is_empty() {
+        info!("Done adding projects.");
+    } else {
+        warn!(
+            "Done adding projects, {} projects in {} batches could not be added:",
+            failed.iter().map(|x| x.count).sum::<usize>(),
+            failed.len()
+        );
+        for batch in &failed {
+            warn!(
+                "{}: {} projects starting with version id {}: {}",
+                batch.index, batch.count, batch.first_version_id, batch.error
+            );
+        }
     }
 
-    info!("Done adding projects.");
     Ok(())
 }
 
+#[tracing::instrument(skip_all)]
 pub async fn get_indexes(
     config: &SearchConfig,
 ) -> Result<Vec<Index>, meilisearch_sdk::errors::Error> {
     let client = config.make_client();
     let project_name = config.get_index_name("projects");
     let project_filtered_name = config.get_index_name("projects_filtered");
-    let projects_index = create_or_update_index(&client, &project_name, None).await?;
+    let projects_index =
+        create_or_update_index(&client, &project_name, "version_id", default_settings()).await?;
     let projects_filtered_index = create_or_update_index(
         &client,
         &project_filtered_name,
-        Some(&[
+        "version_id",
+        default_settings().with_ranking_rules([
             "sort",
             "words",
             "typo",
@@ -137,7 +177,8 @@ pub async fn get_indexes(
 async fn create_or_update_index(
     client: &Client,
     name: &str,
-    custom_rules: Option<&'static [&'static str]>,
+    primary_key: &str,
+    settings: Settings,
 ) -> Result<Index, meilisearch_sdk::errors::Error> {
     info!("Updating/creating index.");
 
@@ -147,12 +188,6 @@ async fn create_or_update_index(
 
             let old_settings = index.get_settings().await?;
 
-            let mut settings = default_settings();
-
-            if let Some(custom_rules) = custom_rules {
-                settings = settings.with_ranking_rules(custom_rules);
-            }
-
             let old_settings = Settings {
                 synonyms: None, // We don't use synonyms right now
                 stop_words: if settings.stop_words.is_none() {
@@ -243,7 +278,7 @@ async fn create_or_update_index(
             info!("Creating index.");
 
             // Only create index and set settings if the index doesn't already exist
-            let task = client.create_index(name, Some("version_id")).await?;
+            let task = client.create_index(name, Some(primary_key)).await?;
             let task = task
                 .wait_for_completion(client, None, Some(TIMEOUT))
                 .await?;
@@ -251,12 +286,6 @@ async fn create_or_update_index(
                 .try_make_index(client)
                 .map_err(|x| x.unwrap_failure())?;
 
-            let mut settings = default_settings();
-
-            if let Some(custom_rules) = custom_rules {
-                settings = settings.with_ranking_rules(custom_rules);
-            }
-
             index
                 .set_settings(&settings)
                 .await?
@@ -268,25 +297,143 @@ async fn create_or_update_index(
     }
 }
 
+/// A batch of documents which could not be added to an index
+#[derive(Debug)]
+pub struct FailedBatch {
+    pub index: String,
+    pub first_version_id: String,
+    pub count: usize,
+    pub error: String,
+}
+
+/// Batches are sent to Meilisearch before the ones sent earlier are processed, up to this
+/// many, so its task queue does not grow faster than it is worked through
+const MAX_PENDING_BATCHES: usize = 4;
+/// How many times a batch is sent again after Meilisearch rate limits it
+const MAX_RATE_LIMIT_RETRIES: u32 = 5;
+
+struct Batch<'a> {
+    projects: &'a [UploadSearchProject],
+    rate_limit_retries: u32,
+}
+
+impl<'a> Batch<'a> {
+    fn new(projects: &'a [UploadSearchProject]) -> Self {
+        Batch {
+            projects,
+            rate_limit_retries: 0,
+        }
+    }
+}
+
+fn is_too_large(err: &meilisearch_sdk::errors::Error) -> bool {
+    match err {
+        meilisearch_sdk::errors::Error::Meilisearch(err) => {
+            matches!(err.error_code, ErrorCode::PayloadTooLarge)
+        }
+        meilisearch_sdk::errors::Error::MeilisearchCommunication(err) => err.status_code == 413,
+        _ => false,
+    }
+}
+
+fn is_rate_limited(err: &meilisearch_sdk::errors::Error) -> bool {
+    matches!(
+        err,
+        meilisearch_sdk::errors::Error::MeilisearchCommunication(err) if err.status_code == 429
+    )
+}
+
+/// Adds projects to an index. Batches which are too large are split, and batches which
+/// Meilisearch fails to process are split until the documents at fault are found, so one bad
+/// document only fails itself. Returns the batches which could not be added.
+#[tracing::instrument(skip_all, fields(index = index.uid.as_str(), count = mods.len()))]
 async fn add_to_index(
     client: &Client,
     index: &Index,
     mods: &[UploadSearchProject],
-) -> Result<(), IndexingError> {
-    for chunk in mods.chunks(MEILISEARCH_CHUNK_SIZE) {
-        info!(
-            "Adding chunk starting with version id {}",
-            chunk[0].version_id
+) -> Result<Vec<FailedBatch>, IndexingError> {
+    let mut queued = mods
+        .chunks(MEILISEARCH_CHUNK_SIZE)
+        .map(Batch::new)
+        .collect::<VecDeque<_>>();
+    let mut pending = VecDeque::new();
+    let mut failed = Vec::new();
+
+    let fail = |failed: &mut Vec<FailedBatch>, batch: &Batch, error: String| {
+        warn!(
+            "Failed to add {} projects starting with version id {}: {}",
+            batch.projects.len(),
+            batch.projects[0].version_id,
+            error
         );
-        index
-            .add_or_replace(chunk, Some("version_id"))
-            .await?
+        failed.push(FailedBatch {
+            index: index.uid.clone(),
+            first_version_id: batch.projects[0].version_id.clone(),
+            count: batch.projects.len(),
+            error,
+        });
+    };
+
+    loop {
+        while pending.len() < MAX_PENDING_BATCHES {
+            let Some(mut batch) = queued.pop_front() else {
+                break;
+            };
+
+            match index
+                .add_or_replace(batch.projects, Some("version_id"))
+                .await
+            {
+                Ok(task) => pending.push_back((batch, task)),
+                Err(err) if is_too_large(&err) && batch.projects.len() > 1 => {
+                    let (first, second) = batch.projects.split_at(batch.projects.len() / 2);
+                    queued.push_front(Batch::new(second));
+                    queued.push_front(Batch::new(first));
+                }
+                Err(err)
+                    if is_rate_limited(&err)
+                        && batch.rate_limit_retries < MAX_RATE_LIMIT_RETRIES =>
+                {
+                    batch.rate_limit_retries += 1;
+                    tokio::time::sleep(std::time::Duration::from_secs(
+                        2u64.pow(batch.rate_limit_retries),
+                    ))
+                    .await;
+                    queued.push_front(batch);
+                }
+                Err(err) if is_too_large(&err) || is_rate_limited(&err) => {
+                    fail(&mut failed, &batch, err.to_string())
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let Some((batch, task)) = pending.pop_front() else {
+            break;
+        };
+
+        let task = task
             .wait_for_completion(client, None, Some(std::time::Duration::from_secs(3600)))
             .await?;
-        info!("Added chunk of {} projects to index", chunk.len());
+        if task.is_failure() {
+            let error = task.unwrap_failure();
+            if batch.projects.len() > 1 {
+                let (first, second) = batch.projects.split_at(batch.projects.len() / 2);
+                queued.push_front(Batch::new(second));
+                queued.push_front(Batch::new(first));
+            } else {
+                fail(&mut failed, &batch, error.error_message);
+            }
+        } else {
+            info!(
+                "Added chunk of {} projects starting with version id {} to index",
+                batch.projects.len(),
+                batch.projects[0].version_id
+            );
+        }
     }
 
-    Ok(())
+    Ok(failed)
 }
 
 async fn update_and_add_to_index(
@@ -294,7 +441,7 @@ async fn update_and_add_to_index(
     index: &Index,
     projects: &[UploadSearchProject],
     _additional_fields: &[String],
-) -> Result<(), IndexingError> {
+) -> Result<Vec<FailedBatch>, IndexingError> {
     // TODO: Uncomment this- hardcoding loader_fields is a band-aid fix, and will be fixed soon
     // let mut new_filterable_attributes: Vec<String> = index.get_filterable_attributes().await?;
     // let mut new_displayed_attributes = index.get_displayed_attributes().await?;
@@ -317,9 +464,7 @@ async fn update_and_add_to_index(
 
     info!("Adding to index.");
 
-    add_to_index(client, index, projects).await?;
-
-    Ok(())
+    add_to_index(client, index, projects).await
 }
 
 pub async fn add_projects(
@@ -327,13 +472,15 @@ pub async fn add_projects(
     projects: Vec<UploadSearchProject>,
     additional_fields: Vec<String>,
     config: &SearchConfig,
-) -> Result<(), IndexingError> {
+) -> Result<Vec<FailedBatch>, IndexingError> {
     let client = config.make_client();
+    let mut failed = Vec::new();
     for index in indices {
-        update_and_add_to_index(&client, index, &projects, &additional_fields).await?;
+        failed
+            .extend(update_and_add_to_index(&client, index, &projects, &additional_fields).await?);
     }
 
-    Ok(())
+    Ok(failed)
 }
 
 fn default_settings() -> Settings {
@@ -399,6 +546,7 @@ const DEFAULT_DISPLAYED_ATTRIBUTES: &[&str] = &[
     "organization_id",
     "links",
     "gallery_items",
+    "ancestor_categories",
     "loaders", // search uses loaders as categories- this is purely for the Project model.
 ];
 
@@ -419,6 +567,7 @@ const DEFAULT_ATTRIBUTES_FOR_FACETING: &[&str] = &[
     "project_id",
     "open_source",
     "color",
+    "environment",
     // Note: loader fields are not here, but are added on as they are needed (so they can be dynamically added depending on which exist).
     // TODO: remove these- as they should be automatically populated. This is a band-aid fix.
     "server_only",
@@ -1,10 +1,15 @@
 use crate::database::models::project_item::{GalleryItem, LinkUrl};
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
 use crate::models::error::ApiError;
 use crate::models::projects::{MonetizationStatus, ProjectStatus, SearchRequest};
+use crate::routes::ErrorCode;
+use crate::util::circuit_breaker::{breaker, CircuitOpen};
 use actix_web::http::StatusCode;
 use actix_web::HttpResponse;
 use chrono::{DateTime, Utc};
 use itertools::Itertools;
+use log::warn;
 use meilisearch_sdk::client::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -12,9 +17,15 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Write;
 use thiserror::Error;
+use tracing::{info_span, Instrument};
 
+pub mod collections;
 pub mod indexing;
 
+const SEARCH_RESULTS_NAMESPACE: &str = "search_results";
+/// How long search results are kept to serve while Meilisearch is down
+const SEARCH_RESULTS_EXPIRY: i64 = 60 * 60;
+
 #[derive(Error, Debug)]
 pub enum SearchError {
     #[error("MeiliSearch Error: {0}")]
@@ -29,6 +40,10 @@ pub enum SearchError {
     Env(#[from] dotenvy::Error),
     #[error("Invalid index to sort by: {0}")]
     InvalidIndex(String),
+    #[error("Database Error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("{0}")]
+    Unavailable(#[from] CircuitOpen),
 }
 
 impl actix_web::ResponseError for SearchError {
@@ -40,24 +55,35 @@ impl actix_web::ResponseError for SearchError {
             SearchError::IntParsing(..) => StatusCode::BAD_REQUEST,
             SearchError::InvalidIndex(..) => StatusCode::BAD_REQUEST,
             SearchError::FormatError(..) => StatusCode::BAD_REQUEST,
+            SearchError::Database(..) => StatusCode::INTERNAL_SERVER_ERROR,
+            SearchError::Unavailable(..) => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
         HttpResponse::build(self.status_code()).json(ApiError {
-            error: match self {
-                SearchError::Env(..) => "environment_error",
-                SearchError::MeiliSearch(..) => "meilisearch_error",
-                SearchError::Serde(..) => "invalid_input",
-                SearchError::IntParsing(..) => "invalid_input",
-                SearchError::InvalidIndex(..) => "invalid_input",
-                SearchError::FormatError(..) => "invalid_input",
-            },
+            error: self.code().as_str(),
             description: &self.to_string(),
+            fields: Vec::new(),
         })
     }
 }
 
+impl SearchError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            SearchError::Env(..) => ErrorCode::EnvironmentError,
+            SearchError::MeiliSearch(..) => ErrorCode::MeilisearchError,
+            SearchError::Serde(..) => ErrorCode::InvalidInput,
+            SearchError::IntParsing(..) => ErrorCode::InvalidInput,
+            SearchError::InvalidIndex(..) => ErrorCode::InvalidInput,
+            SearchError::FormatError(..) => ErrorCode::InvalidInput,
+            SearchError::Database(..) => ErrorCode::DatabaseError,
+            SearchError::Unavailable(..) => ErrorCode::ServiceUnavailable,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SearchConfig {
     pub address: String,
@@ -117,6 +143,9 @@ pub struct UploadSearchProject {
     pub modified_timestamp: i64,
     pub open_source: bool,
     pub color: Option<u32>,
+    /// Where the version can be installed: `client` and `server` when it works installed on
+    /// that side, plus `client_required` and `server_required` when it does not work without it
+    pub environment: Vec<String>,
 
     // Hidden fields to get the Project model out of the search results.
     pub license_url: Option<String>,
@@ -133,6 +162,9 @@ pub struct UploadSearchProject {
     pub gallery_items: Vec<GalleryItem>, // Gallery *only* urls are stored in gallery, but the gallery items are stored here- required for the Project model.
     pub games: Vec<String>,              // Todo: in future, could be a searchable field.
     pub organization_id: Option<String>, // Todo: in future, could be a searchable field.
+    /// Categories the project's categories are nested under, which are searchable as
+    /// `categories` but aren't the project's own
+    pub ancestor_categories: Vec<String>,
 
     #[serde(flatten)]
     pub loader_fields: HashMap<String, Vec<serde_json::Value>>,
@@ -184,6 +216,8 @@ pub struct ResultSearchProject {
     pub gallery_items: Vec<GalleryItem>, // Gallery *only* urls are stored in gallery, but the gallery items are stored here- required for the Project model.
     pub games: Vec<String>,              // Todo: in future, could be a searchable field.
     pub organization_id: Option<String>, // Todo: in future, could be a searchable field.
+    #[serde(default)]
+    pub ancestor_categories: Vec<String>,
 
     #[serde(flatten)]
     pub loader_fields: HashMap<String, Vec<serde_json::Value>>,
@@ -205,9 +239,66 @@ pub fn get_sort_index(
     })
 }
 
+/// Searches for projects. Results are kept for a while, and served if Meilisearch is down
+#[tracing::instrument(skip_all, fields(index = info.index.as_deref().unwrap_or("relevance")))]
 pub async fn search_for_project(
     info: &SearchRequest,
     config: &SearchConfig,
+    redis: &RedisPool,
+) -> Result<SearchResults, SearchError> {
+    let breaker = breaker("meilisearch");
+    let cache_key = sha1::Sha1::from(serde_json::to_string(info)?).hexdigest();
+
+    let unavailable = match breaker.check() {
+        Ok(()) => match query_meilisearch(info, config).await {
+            Ok(results) => {
+                breaker.record_success();
+                if let Err(err) = cache_results(&cache_key, &results, redis).await {
+                    warn!("Caching search results failed: {}", err);
+                }
+                return Ok(results);
+            }
+            // Errors returned by Meilisearch itself are caused by the query
+            Err(SearchError::MeiliSearch(err))
+                if !matches!(err, meilisearch_sdk::errors::Error::Meilisearch(_)) =>
+            {
+                breaker.record_failure();
+                SearchError::MeiliSearch(err)
+            }
+            Err(err) => return Err(err),
+        },
+        Err(err) => SearchError::Unavailable(err),
+    };
+
+    let mut redis = redis.connect().await?;
+    match redis
+        .get_deserialized_from_json(SEARCH_RESULTS_NAMESPACE, &cache_key)
+        .await?
+    {
+        Some(results) => Ok(results),
+        None => Err(unavailable),
+    }
+}
+
+async fn cache_results(
+    cache_key: &str,
+    results: &SearchResults,
+    redis: &RedisPool,
+) -> Result<(), DatabaseError> {
+    let mut redis = redis.connect().await?;
+    redis
+        .set_serialized_to_json(
+            SEARCH_RESULTS_NAMESPACE,
+            cache_key,
+            results,
+            Some(SEARCH_RESULTS_EXPIRY),
+        )
+        .await
+}
+
+async fn query_meilisearch(
+    info: &SearchRequest,
+    config: &SearchConfig,
 ) -> Result<SearchResults, SearchError> {
     let client = Client::new(&*config.address, Some(&*config.key));
 
@@ -309,7 +400,10 @@ pub async fn search_for_project(
             }
         }
 
-        query.execute::<ResultSearchProject>().await?
+        query
+            .execute::<ResultSearchProject>()
+            .instrument(info_span!("meilisearch.search", db.system = "meilisearch"))
+            .await?
     };
 
     Ok(SearchResults {
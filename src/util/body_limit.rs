@@ -0,0 +1,248 @@
+use crate::models::ids::ProjectId;
+use crate::routes::v3::version_creation::MAX_FILE_SIZE;
+use crate::util::env::{parse_strings_from_var, parse_var};
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::{JsonPayloadError, PayloadError};
+use actix_web::http::header::CONTENT_LENGTH;
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use futures::{FutureExt, StreamExt};
+use std::rc::Rc;
+
+const KIB: usize = 1 << 10;
+const MIB: usize = 1 << 20;
+
+/// The largest body of a request to a route not in `ROUTE_LIMITS`
+const DEFAULT_LIMIT: usize = MIB;
+/// The largest body of a `PATCH` to a route not in `ROUTE_LIMITS`, which only change a few
+/// fields
+const PATCH_LIMIT: usize = 64 * KIB;
+/// The largest body of a request carrying Markdown, which can be up to 65536 characters
+const TEXT_LIMIT: usize = 512 * KIB;
+/// The largest project file projects in `LARGE_UPLOAD_PROJECTS` can upload, unless
+/// `LARGE_FILE_SIZE` is set
+const DEFAULT_LARGE_FILE_SIZE: usize = 1 << 30;
+/// The largest JSON body of any route. JSON bodies are limited to this even where
+/// `BodyLimits` isn't used, so no app can read an unbounded body into memory
+pub const MAX_JSON_LIMIT: usize = 2 * MIB;
+/// The largest body of a request creating a project or version, which can carry several
+/// files, unless `MAX_UPLOAD_SIZE` is set
+const DEFAULT_UPLOAD_LIMIT: usize = 2 << 30;
+
+#[derive(Copy, Clone, Debug)]
+enum Limit {
+    Bytes(usize),
+    /// Project and version files, whose limit is configured
+    Upload,
+}
+
+/// The routes which accept other bodies than `DEFAULT_LIMIT` or `PATCH_LIMIT`, by method and
+/// path. Paths are matched without their `/v2` or `/v3` prefix, `*` matches any one segment
+/// and the first route which matches is used
+const ROUTE_LIMITS: &[(&str, &str, Limit)] = &[
+    ("POST", "project", Limit::Upload),
+    ("POST", "version", Limit::Upload),
+    ("POST", "version/*/file", Limit::Upload),
+    ("POST", "project/*/gallery", Limit::Bytes(5 * MIB)),
+    ("POST", "image", Limit::Bytes(5 * MIB)),
+    ("PATCH", "project/*/icon", Limit::Bytes(MIB)),
+    ("PATCH", "user/*/icon", Limit::Bytes(2 * MIB)),
+    ("PATCH", "organization/*/icon", Limit::Bytes(256 * KIB)),
+    ("PATCH", "collection/*/icon", Limit::Bytes(256 * KIB)),
+    ("PATCH", "oauth/app/*/icon", Limit::Bytes(256 * KIB)),
+    ("PATCH", "project/*", Limit::Bytes(TEXT_LIMIT)),
+    ("PATCH", "version/*", Limit::Bytes(TEXT_LIMIT)),
+    ("PATCH", "user/*/profile", Limit::Bytes(TEXT_LIMIT)),
+    ("PATCH", "report/*", Limit::Bytes(TEXT_LIMIT)),
+    ("PATCH", "message/*", Limit::Bytes(TEXT_LIMIT)),
+    // Launchers look up every file of an instance at once
+    ("POST", "version_files", Limit::Bytes(2 * MIB)),
+    ("POST", "version_files/update", Limit::Bytes(2 * MIB)),
+    (
+        "POST",
+        "version_files/update_individual",
+        Limit::Bytes(2 * MIB),
+    ),
+];
+
+/// The largest project file which can be uploaded to a project. Projects in
+/// `LARGE_UPLOAD_PROJECTS`, such as large modpacks, may upload larger files
+pub fn max_file_size(project_id: ProjectId) -> usize {
+    let large = parse_strings_from_var("LARGE_UPLOAD_PROJECTS").unwrap_or_default();
+
+    if large.contains(&project_id.to_string()) {
+        parse_var("LARGE_FILE_SIZE").unwrap_or(DEFAULT_LARGE_FILE_SIZE)
+    } else {
+        MAX_FILE_SIZE
+    }
+}
+
+fn matches(pattern: &str, path: &str) -> bool {
+    let mut pattern = pattern.split('/');
+    let mut path = path.split('/');
+
+    loop {
+        match (pattern.next(), path.next()) {
+            (None, None) => return true,
+            (Some(x), Some(y)) if x == "*" || x == y => continue,
+            _ => return false,
+        }
+    }
+}
+
+/// The largest body a request to a route may have
+pub fn route_limit(method: &str, path: &str) -> usize {
+    let path = path.trim_matches('/');
+    let path = path
+        .strip_prefix("v2/")
+        .or_else(|| path.strip_prefix("v3/"))
+        .unwrap_or(path);
+
+    let limit = ROUTE_LIMITS
+        .iter()
+        .find(|(x, pattern, _)| *x == method && matches(pattern, path))
+        .map(|(_, _, limit)| *limit);
+
+    match limit {
+        Some(Limit::Bytes(limit)) => limit,
+        Some(Limit::Upload) => parse_var("MAX_UPLOAD_SIZE").unwrap_or(DEFAULT_UPLOAD_LIMIT),
+        None if method == "PATCH" => PATCH_LIMIT,
+        None => DEFAULT_LIMIT,
+    }
+}
+
+/// Refuses request bodies larger than their route allows with a 413. Bodies sent without a
+/// length are cut off once they reach it
+pub struct BodyLimits;
+
+impl<S, B> Transform<S, ServiceRequest> for BodyLimits
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = BodyLimitsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(BodyLimitsMiddleware {
+            service: Rc::new(service),
+        })
+    }
+}
+
+pub struct BodyLimitsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for BodyLimitsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let srv = self.service.clone();
+        let limit = route_limit(req.method().as_str(), req.path());
+
+        let length = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.parse::<usize>().ok());
+        if length.map(|x| x > limit).unwrap_or(false) {
+            return async move { Err(PayloadTooLargeError(limit).into()) }.boxed_local();
+        }
+
+        let mut read = 0;
+        let payload = req.take_payload().map(move |chunk| {
+            let chunk = chunk?;
+            read += chunk.len();
+            if read > limit {
+                Err(PayloadError::Overflow)
+            } else {
+                Ok(chunk)
+            }
+        });
+        req.set_payload(Payload::Stream {
+            payload: Box::pin(payload),
+        });
+
+        async move { srv.call(req).await }.boxed_local()
+    }
+}
+
+/// The 413 for a JSON body over its route's limit, or over `MAX_JSON_LIMIT`. Returns `None` for
+/// other errors reading JSON
+pub fn json_overflow_error(err: &JsonPayloadError, req: &HttpRequest) -> Option<Error> {
+    match err {
+        JsonPayloadError::Overflow { .. }
+        | JsonPayloadError::OverflowKnownLength { .. }
+        | JsonPayloadError::Payload(PayloadError::Overflow) => Some(
+            PayloadTooLargeError(std::cmp::min(
+                route_limit(req.method().as_str(), req.path()),
+                MAX_JSON_LIMIT,
+            ))
+            .into(),
+        ),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+struct PayloadTooLargeError(usize);
+
+impl std::fmt::Display for PayloadTooLargeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 >= MIB {
+            write!(f, "Request bodies must be at most {}MiB", self.0 / MIB)
+        } else {
+            write!(f, "Request bodies must be at most {}KiB", self.0 / KIB)
+        }
+    }
+}
+
+impl actix_web::ResponseError for PayloadTooLargeError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        actix_web::http::StatusCode::PAYLOAD_TOO_LARGE
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::PayloadTooLarge().json(crate::models::error::ApiError {
+            error: crate::routes::ErrorCode::PayloadTooLarge.as_str(),
+            description: &self.to_string(),
+            fields: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_get_their_limits() {
+        assert_eq!(route_limit("PATCH", "/v3/project/AABBCCDD/icon"), MIB);
+        assert_eq!(route_limit("PATCH", "/v2/project/AABBCCDD"), TEXT_LIMIT);
+        assert_eq!(route_limit("PATCH", "/v3/organization/test"), PATCH_LIMIT);
+        assert_eq!(route_limit("POST", "/v2/version_files/update"), 2 * MIB);
+        assert_eq!(route_limit("POST", "/v3/thread/AABBCCDD"), DEFAULT_LIMIT);
+        assert!(route_limit("POST", "/v3/version/AABBCCDD/file") > MAX_FILE_SIZE);
+    }
+
+    #[test]
+    fn wildcards_match_one_segment() {
+        assert!(matches("project/*/icon", "project/test/icon"));
+        assert!(!matches("project/*", "project/test/icon"));
+        assert!(!matches("project/*/icon", "project/test"));
+    }
+}
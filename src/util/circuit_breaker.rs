@@ -0,0 +1,98 @@
+use crate::util::env::parse_var;
+use chrono::Utc;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use std::sync::atomic::{AtomicI64, AtomicU32, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+lazy_static! {
+    static ref BREAKERS: DashMap<String, Arc<CircuitBreaker>> = DashMap::new();
+}
+
+#[derive(Error, Debug)]
+#[error("{0} is temporarily unavailable, please try again later")]
+pub struct CircuitOpen(pub String);
+
+/// Stops calls to an external service after it fails several times in a row, so an outage
+/// fails requests right away instead of tying them up until they time out. Once open, the
+/// breaker lets a single call through every cooldown to check whether the service is back.
+pub struct CircuitBreaker {
+    name: String,
+    threshold: u32,
+    cooldown_ms: i64,
+    /// Failures since the last successful call
+    failures: AtomicU32,
+    /// When the breaker lets the next call through, in milliseconds since the epoch. Zero if
+    /// it is closed
+    open_until: AtomicI64,
+}
+
+/// Gets the breaker of a service, shared by all of its callers
+pub fn breaker(name: &str) -> Arc<CircuitBreaker> {
+    BREAKERS
+        .entry(name.to_string())
+        .or_insert_with(|| Arc::new(CircuitBreaker::new(name)))
+        .clone()
+}
+
+impl CircuitBreaker {
+    fn new(name: &str) -> Self {
+        CircuitBreaker {
+            name: name.to_string(),
+            threshold: parse_var("CIRCUIT_BREAKER_THRESHOLD").unwrap_or(5),
+            cooldown_ms: parse_var::<i64>("CIRCUIT_BREAKER_COOLDOWN").unwrap_or(30) * 1000,
+            failures: AtomicU32::new(0),
+            open_until: AtomicI64::new(0),
+        }
+    }
+
+    /// Checks whether the service may be called. Calls which are let through must report how
+    /// they went with `record_success` or `record_failure`
+    pub fn check(&self) -> Result<(), CircuitOpen> {
+        let open_until = self.open_until.load(Ordering::Acquire);
+        if open_until == 0 {
+            return Ok(());
+        }
+
+        let now = Utc::now().timestamp_millis();
+        if now < open_until {
+            return Err(CircuitOpen(self.name.clone()));
+        }
+
+        // The cooldown is over. Whoever moves it forward first gets to try the service, while
+        // everyone else waits for the outcome
+        self.open_until
+            .compare_exchange(
+                open_until,
+                now + self.cooldown_ms,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .map(|_| ())
+            .map_err(|_| CircuitOpen(self.name.clone()))
+    }
+
+    pub fn record_success(&self) {
+        self.failures.store(0, Ordering::Release);
+        if self.open_until.swap(0, Ordering::AcqRel) != 0 {
+            info!("Circuit breaker for {} closed", self.name);
+        }
+    }
+
+    /// Records a failure caused by the service being unavailable. Errors caused by the
+    /// request, such as invalid input, should not be recorded
+    pub fn record_failure(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::AcqRel) + 1;
+        if failures >= self.threshold {
+            let open_until = Utc::now().timestamp_millis() + self.cooldown_ms;
+            if self.open_until.swap(open_until, Ordering::AcqRel) == 0 {
+                warn!(
+                    "Circuit breaker for {} opened after {} failures",
+                    self.name, failures
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::models::ids::UserId;
+use crate::util::circuit_breaker::{breaker, CircuitOpen};
+use crate::util::env::var;
+use crate::util::remote_file::{self, RemoteFileError};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use thiserror::Error;
+
+const CURSEFORGE_API_URL: &str = "https://api.curseforge.com/v1";
+
+/// How many files are fetched from the CurseForge API at once
+const FILES_PAGE_SIZE: usize = 50;
+
+#[derive(Error, Debug)]
+pub enum CurseForgeError {
+    #[error("Importing from CurseForge is not configured")]
+    NotConfigured,
+    #[error("Error while communicating with the CurseForge API: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("{0}")]
+    CircuitOpen(#[from] CircuitOpen),
+    #[error("The file is too large to import")]
+    TooLarge,
+    #[error("{0}")]
+    RemoteFile(RemoteFileError),
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: T,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CurseForgeProject {
+    pub id: u32,
+    pub name: String,
+    pub slug: String,
+    pub summary: String,
+    pub links: CurseForgeLinks,
+    #[serde(default)]
+    pub categories: Vec<CurseForgeCategory>,
+    pub logo: Option<CurseForgeImage>,
+    #[serde(default)]
+    pub screenshots: Vec<CurseForgeImage>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CurseForgeLinks {
+    pub website_url: Option<String>,
+    pub wiki_url: Option<String>,
+    pub issues_url: Option<String>,
+    pub source_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CurseForgeCategory {
+    pub name: String,
+    pub slug: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CurseForgeImage {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub url: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CurseForgeFile {
+    pub id: u32,
+    pub display_name: String,
+    pub file_name: String,
+    /// 1 for releases, 2 for betas and 3 for alphas
+    pub release_type: u8,
+    pub file_date: DateTime<Utc>,
+    /// Missing when the author does not allow the file to be downloaded through the API
+    pub download_url: Option<String>,
+    /// The game versions, loaders and environments the file is for, such as `1.20.1`,
+    /// `Fabric` and `Client`
+    #[serde(default)]
+    pub game_versions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Pagination {
+    total_count: usize,
+}
+
+#[derive(Deserialize)]
+struct FilesResponse {
+    data: Vec<CurseForgeFile>,
+    pagination: Pagination,
+}
+
+/// Whether projects can be imported from CurseForge, which they can once an API key is set
+pub fn is_enabled() -> bool {
+    var("CURSEFORGE_API_KEY").map_or(false, |x| !x.is_empty())
+}
+
+/// The code a user puts in a CurseForge project's description to prove they own it. It is
+/// different for every user, so it can't be used to import the project into anyone else's
+/// account
+pub fn verification_code(user_id: UserId, curseforge_id: u32) -> String {
+    let hash = sha1::Sha1::from(format!("{user_id}:{curseforge_id}")).hexdigest();
+
+    format!("modrinth-import-{}", &hash[..16])
+}
+
+async fn get<T: DeserializeOwned>(path: &str) -> Result<T, CurseForgeError> {
+    let key = var("CURSEFORGE_API_KEY")
+        .ok()
+        .filter(|x| !x.is_empty())
+        .ok_or(CurseForgeError::NotConfigured)?;
+
+    let breaker = breaker("curseforge");
+    breaker.check()?;
+
+    let result = reqwest::Client::new()
+        .get(format!("{CURSEFORGE_API_URL}{path}"))
+        .header("x-api-key", key)
+        .send()
+        .await
+        .and_then(|x| x.error_for_status());
+    let response = match result {
+        // Missing projects are the caller's problem rather than CurseForge's
+        Err(err) if err.status().map_or(false, |x| x.is_client_error()) => return Err(err.into()),
+        Err(err) => {
+            breaker.record_failure();
+            return Err(err.into());
+        }
+        Ok(response) => response,
+    };
+    breaker.record_success();
+
+    Ok(response.json().await?)
+}
+
+pub async fn get_project(id: u32) -> Result<CurseForgeProject, CurseForgeError> {
+    Ok(get::<Response<_>>(&format!("/mods/{id}")).await?.data)
+}
+
+/// Gets a project's description, as HTML
+pub async fn get_description(id: u32) -> Result<String, CurseForgeError> {
+    Ok(get::<Response<_>>(&format!("/mods/{id}/description"))
+        .await?
+        .data)
+}
+
+/// Gets every file of a project, newest first
+pub async fn get_files(id: u32) -> Result<Vec<CurseForgeFile>, CurseForgeError> {
+    let mut files = Vec::new();
+    loop {
+        let page: FilesResponse = get(&format!(
+            "/mods/{id}/files?index={}&pageSize={FILES_PAGE_SIZE}",
+            files.len()
+        ))
+        .await?;
+        let done = page.data.is_empty();
+        files.extend(page.data);
+
+        if done || files.len() >= page.pagination.total_count {
+            break;
+        }
+    }
+
+    Ok(files)
+}
+
+/// Downloads an image or file CurseForge hosts, up to `max_size` bytes
+pub async fn download(url: &str, max_size: usize) -> Result<bytes::Bytes, CurseForgeError> {
+    match remote_file::fetch(url, max_size).await {
+        Ok(data) => Ok(data.freeze()),
+        Err(RemoteFileError::TooLarge(_)) => Err(CurseForgeError::TooLarge),
+        Err(err) => Err(CurseForgeError::RemoteFile(err)),
+    }
+}
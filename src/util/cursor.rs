@@ -0,0 +1,77 @@
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse};
+use base64::Engine;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// An opaque keyset pagination cursor, pointing at the last item of a page ordered by
+/// `(timestamp, id)`. Serialized as an URL-safe string clients should pass back unchanged.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub timestamp: DateTime<Utc>,
+    pub id: i64,
+}
+
+impl Cursor {
+    pub fn new(timestamp: DateTime<Utc>, id: i64) -> Self {
+        Self { timestamp, id }
+    }
+
+    pub fn encode(&self) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.timestamp.timestamp_micros(),
+            self.id
+        ))
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(value)
+            .ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (timestamp, id) = decoded.split_once(':')?;
+
+        Some(Self {
+            timestamp: Utc.timestamp_micros(timestamp.parse().ok()?).single()?,
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+/// A `Link` header value pointing at the next page of a list: the same request, continuing
+/// from `cursor` rather than from an offset
+pub fn next_page_link(req: &HttpRequest, cursor: Cursor) -> String {
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(
+            url::form_urlencoded::parse(req.query_string().as_bytes())
+                .filter(|(key, _)| key != "cursor" && key != "offset"),
+        )
+        .append_pair("cursor", &cursor.encode())
+        .finish();
+
+    format!("<{}?{}>; rel=\"next\"", req.path(), query)
+}
+
+/// Reads the cursor back out of the `Link` header of a response, if it links a next page
+pub fn next_page_cursor(response: &HttpResponse) -> Option<Cursor> {
+    let link = response.headers().get(header::LINK)?.to_str().ok()?;
+    let (_, query) = link.strip_prefix('<')?.split_once('>')?.0.split_once('?')?;
+
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "cursor")
+        .and_then(|(_, value)| Cursor::decode(&value))
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.encode())
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Cursor::decode(&value).ok_or_else(|| serde::de::Error::custom("invalid cursor"))
+    }
+}
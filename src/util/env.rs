@@ -1,10 +1,32 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::RwLock;
+
+lazy_static! {
+    /// Values of variables reloaded since startup, see `util::reload`
+    static ref RELOADED: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+}
+
+/// Gets a variable, preferring the value it was last reloaded with
+pub fn var(var: &str) -> Result<String, dotenvy::Error> {
+    if let Some(value) = RELOADED.read().unwrap().get(var) {
+        return Ok(value.clone());
+    }
+
+    dotenvy::var(var)
+}
+
+/// Replaces the reloaded values of variables
+pub(crate) fn set_reloaded(values: HashMap<String, String>) {
+    *RELOADED.write().unwrap() = values;
+}
 
 pub fn parse_var<T: FromStr>(var: &'static str) -> Option<T> {
-    dotenvy::var(var).ok().and_then(|i| i.parse().ok())
+    self::var(var).ok().and_then(|i| i.parse().ok())
 }
 pub fn parse_strings_from_var(var: &'static str) -> Option<Vec<String>> {
-    dotenvy::var(var)
+    self::var(var)
         .ok()
         .and_then(|s| serde_json::from_str::<Vec<String>>(&s).ok())
 }
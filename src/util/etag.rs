@@ -0,0 +1,137 @@
+use actix_web::body::{to_bytes, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{EntityTag, Header, HeaderValue, IfMatch, IfNoneMatch, ETAG};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{Error, HttpRequest, HttpResponse};
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+/// Routes whose GET responses are given ETags. Paths also match the routes nested under them
+const ETAG_ROUTES: &[&str] = &[
+    "/v2/project",
+    "/v2/projects",
+    "/v2/version",
+    "/v2/versions",
+    "/v2/version_file",
+    "/v2/user",
+    "/v2/users",
+    "/v2/search",
+    "/v3/project",
+    "/v3/projects",
+    "/v3/version",
+    "/v3/versions",
+    "/v3/version_file",
+    "/v3/user",
+    "/v3/users",
+    "/v3/search",
+];
+
+fn has_etag(path: &str) -> bool {
+    ETAG_ROUTES.iter().any(|route| {
+        path.strip_prefix(route)
+            .map(|rest| rest.is_empty() || rest.starts_with('/'))
+            .unwrap_or(false)
+    })
+}
+
+/// The entity tag of a revision of a project or version, set by their GET routes. The hash of
+/// the body is appended to it by `conditional_get`
+pub fn revision_etag(revision: i32) -> EntityTag {
+    EntityTag::new_strong(format!("r{revision}"))
+}
+
+/// The revision an entity tag from `revision_etag` was made from
+fn revision_of(tag: &EntityTag) -> Option<i32> {
+    tag.tag().strip_prefix('r')?.split('.').next()?.parse().ok()
+}
+
+/// The revisions an edit may be made to, from its `If-Match` header. `None` if the edit may be
+/// made to any revision, as the header is absent or `*`
+pub fn if_match_revisions(req: &HttpRequest) -> Option<Vec<i32>> {
+    match IfMatch::parse(req).ok()? {
+        IfMatch::Any => None,
+        IfMatch::Items(tags) => Some(tags.iter().filter_map(revision_of).collect()),
+    }
+}
+
+/// Gives successful GET responses of public routes a weak ETag hashed from their body, and
+/// answers requests whose `If-None-Match` matches it with an empty 304. Clients polling for
+/// updates, such as launchers, then only download responses which changed.
+///
+/// Responses which already have an ETag, such as the revision of a project, keep it with the
+/// hash appended, so it can still be sent back in `If-Match` when editing.
+pub fn conditional_get<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> LocalBoxFuture<'static, Result<ServiceResponse, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    let tagged = req.method() == Method::GET && has_etag(req.path());
+    let if_none_match = IfNoneMatch::parse(&req).ok();
+
+    let fut = srv.call(req);
+    async move {
+        let res = fut.await?;
+        if !tagged || res.status() != StatusCode::OK {
+            return Ok(res.map_into_boxed_body());
+        }
+
+        let (req, res) = res.into_parts();
+        let revision = res
+            .headers()
+            .get(ETAG)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.parse::<EntityTag>().ok());
+        let (res, body) = res.into_parts();
+        let body = to_bytes(body).await.map_err(|err| {
+            let err: Box<dyn std::error::Error> = err.into();
+            actix_web::error::ErrorInternalServerError(err.to_string())
+        })?;
+
+        let hash = sha1::Sha1::from(&body).hexdigest();
+        let etag = match revision {
+            Some(revision) => EntityTag::new(revision.weak, format!("{}.{}", revision.tag(), hash)),
+            None => EntityTag::new_weak(hash),
+        };
+        let not_modified = match if_none_match {
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(tags)) => tags.iter().any(|x| x.weak_eq(&etag)),
+            None => false,
+        };
+
+        let mut res = if not_modified {
+            HttpResponse::NotModified().finish()
+        } else {
+            res.set_body(body).map_into_boxed_body()
+        };
+        res.headers_mut().insert(
+            ETAG,
+            HeaderValue::from_str(&etag.to_string())
+                .map_err(actix_web::error::ErrorInternalServerError)?,
+        );
+
+        Ok(ServiceResponse::new(req, res))
+    }
+    .boxed_local()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_revisions_from_etags() {
+        assert_eq!(revision_of(&revision_etag(12)), Some(12));
+        assert_eq!(
+            revision_of(&EntityTag::new_strong("r7.0a1b2c".to_string())),
+            Some(7)
+        );
+        assert_eq!(
+            revision_of(&EntityTag::new_weak("0a1b2c".to_string())),
+            None
+        );
+    }
+}
@@ -9,6 +9,21 @@ pub fn get_image_content_type(extension: &str) -> Option<&'static str> {
     }
 }
 
+/// Project icons may also be SVGs, which are sanitized before they are stored
+pub fn get_icon_content_type(extension: &str) -> Option<&'static str> {
+    match extension {
+        "svg" => Some("image/svg+xml"),
+        _ => get_image_content_type(extension),
+    }
+}
+
+pub fn get_log_content_type(extension: &str) -> Option<&'static str> {
+    match extension {
+        "log" | "txt" => Some("text/plain"),
+        _ => None,
+    }
+}
+
 pub fn get_image_ext(content_type: &str) -> Option<&'static str> {
     match content_type {
         "image/bmp" => Some("bmp"),
@@ -0,0 +1,132 @@
+use crate::database::models::{ProjectId, VersionId};
+use crate::models::feed::{FeedRanking, FeedSource};
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+
+/// How many days it takes for an item's blended score to halve
+const HALF_LIFE_DAYS: f64 = 7.0;
+
+/// Something which could be shown in a user's home feed
+#[derive(Clone, Debug)]
+pub struct FeedCandidate {
+    pub source: FeedSource,
+    pub project_id: ProjectId,
+    pub version_id: Option<VersionId>,
+    /// How strong the reason to show the item is compared to others from the same source,
+    /// from 0 to 1
+    pub signal: f64,
+    pub date: DateTime<Utc>,
+}
+
+/// Orders the candidates of a home feed. Strategies only score candidates, so each can be
+/// swapped in without changing how candidates are gathered
+pub trait FeedRanker {
+    /// Scores a candidate, with higher scores shown first
+    fn score(&self, candidate: &FeedCandidate, now: DateTime<Utc>) -> f64;
+}
+
+pub struct BlendedRanker;
+
+impl BlendedRanker {
+    fn weight(source: FeedSource) -> f64 {
+        match source {
+            FeedSource::FollowedUpdate => 1.0,
+            FeedSource::Similar => 0.6,
+            FeedSource::Trending => 0.4,
+        }
+    }
+}
+
+impl FeedRanker for BlendedRanker {
+    fn score(&self, candidate: &FeedCandidate, now: DateTime<Utc>) -> f64 {
+        let age_days = (now - candidate.date).num_minutes().max(0) as f64 / (24.0 * 60.0);
+
+        Self::weight(candidate.source)
+            * candidate.signal.clamp(0.0, 1.0)
+            * 0.5f64.powf(age_days / HALF_LIFE_DAYS)
+    }
+}
+
+pub struct ChronologicalRanker;
+
+impl FeedRanker for ChronologicalRanker {
+    fn score(&self, candidate: &FeedCandidate, _now: DateTime<Utc>) -> f64 {
+        candidate.date.timestamp() as f64
+    }
+}
+
+pub fn ranker(ranking: FeedRanking) -> Box<dyn FeedRanker> {
+    match ranking {
+        FeedRanking::Blended => Box::new(BlendedRanker),
+        FeedRanking::Chronological => Box::new(ChronologicalRanker),
+    }
+}
+
+/// Orders candidates by a ranker, keeping only the best scored item of each project
+pub fn rank(
+    candidates: Vec<FeedCandidate>,
+    ranker: &dyn FeedRanker,
+    limit: usize,
+) -> Vec<FeedCandidate> {
+    let now = Utc::now();
+    let mut scored = candidates
+        .into_iter()
+        .map(|x| (ranker.score(&x, now), x))
+        .collect::<Vec<_>>();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut seen = HashSet::new();
+    scored
+        .into_iter()
+        .map(|x| x.1)
+        .filter(|x| seen.insert(x.project_id))
+        .take(limit)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(source: FeedSource, project_id: i64, signal: f64, days_ago: i64) -> FeedCandidate {
+        FeedCandidate {
+            source,
+            project_id: ProjectId(project_id),
+            version_id: None,
+            signal,
+            date: Utc::now() - chrono::Duration::days(days_ago),
+        }
+    }
+
+    #[test]
+    fn blended_ranking_favors_followed_and_recent_items() {
+        let ranked = rank(
+            vec![
+                candidate(FeedSource::Trending, 1, 1.0, 0),
+                candidate(FeedSource::FollowedUpdate, 2, 1.0, 0),
+                candidate(FeedSource::FollowedUpdate, 3, 1.0, 30),
+            ],
+            &BlendedRanker,
+            10,
+        );
+
+        let ids = ranked.iter().map(|x| x.project_id.0).collect::<Vec<_>>();
+        assert_eq!(ids, vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn projects_are_shown_once() {
+        let ranked = rank(
+            vec![
+                candidate(FeedSource::Trending, 1, 1.0, 0),
+                candidate(FeedSource::FollowedUpdate, 1, 1.0, 0),
+                candidate(FeedSource::Similar, 2, 0.5, 0),
+            ],
+            &ChronologicalRanker,
+            1,
+        );
+
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].project_id.0, 1);
+    }
+}
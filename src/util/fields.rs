@@ -0,0 +1,175 @@
+use actix_web::body::{to_bytes, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, Error};
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Routes whose GET responses can be pruned with `fields`, as path segments. `*` matches any
+/// one segment
+const FIELDSET_ROUTES: &[&[&str]] = &[
+    &["v3", "project", "*"],
+    &["v3", "projects"],
+    &["v3", "version", "*"],
+    &["v3", "versions"],
+    &["v3", "user", "*"],
+    &["v3", "users"],
+];
+
+fn has_fieldsets(path: &str) -> bool {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    FIELDSET_ROUTES.iter().any(|route| {
+        route.len() == segments.len()
+            && route
+                .iter()
+                .zip(&segments)
+                .all(|(route, segment)| *route == "*" || route == segment)
+    })
+}
+
+#[derive(Deserialize)]
+struct FieldsQuery {
+    fields: Option<String>,
+}
+
+/// The fields to keep, by name. Fields without nested fields are kept whole
+#[derive(Default)]
+struct FieldSet(HashMap<String, Option<FieldSet>>);
+
+impl FieldSet {
+    /// Parses a comma separated list of fields, with nested fields separated by dots, such as
+    /// `id,name,files.hashes`
+    fn parse(value: &str) -> Self {
+        let mut set = FieldSet::default();
+        for field in value.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            set.insert(&field.split('.').collect::<Vec<_>>());
+        }
+        set
+    }
+
+    fn insert(&mut self, path: &[&str]) {
+        let Some((first, rest)) = path.split_first() else {
+            return;
+        };
+
+        if rest.is_empty() {
+            self.0.insert(first.to_string(), None);
+        } else if let Some(nested) = self
+            .0
+            .entry(first.to_string())
+            .or_insert_with(|| Some(FieldSet::default()))
+        {
+            nested.insert(rest);
+        }
+    }
+
+    /// Removes every field not in the set. Arrays are pruned element by element
+    fn prune(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                map.retain(|key, _| self.0.contains_key(key));
+                for (key, value) in map.iter_mut() {
+                    if let Some(Some(nested)) = self.0.get(key) {
+                        nested.prune(value);
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|x| self.prune(x)),
+            _ => {}
+        }
+    }
+}
+
+/// Prunes successful GET responses of large resources to the fields listed in the `fields`
+/// query parameter, so clients which only need a few fields, such as launchers, are not sent
+/// whole descriptions and galleries. Responses are returned whole without the parameter.
+pub fn sparse_fieldsets<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> LocalBoxFuture<'static, Result<ServiceResponse, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    let fields = if req.method() == Method::GET && has_fieldsets(req.path()) {
+        web::Query::<FieldsQuery>::from_query(req.query_string())
+            .ok()
+            .and_then(|x| x.into_inner().fields)
+            .map(|x| FieldSet::parse(&x))
+    } else {
+        None
+    };
+
+    let fut = srv.call(req);
+    async move {
+        let res = fut.await?;
+        let Some(fields) = fields.filter(|_| res.status() == StatusCode::OK) else {
+            return Ok(res.map_into_boxed_body());
+        };
+
+        let (req, res) = res.into_parts();
+        let (mut res, body) = res.into_parts();
+        let body = to_bytes(body).await.map_err(|err| {
+            let err: Box<dyn std::error::Error> = err.into();
+            actix_web::error::ErrorInternalServerError(err.to_string())
+        })?;
+
+        let body = match serde_json::from_slice::<Value>(&body) {
+            Ok(mut value) => {
+                fields.prune(&mut value);
+                serde_json::to_vec(&value).map_err(actix_web::error::ErrorInternalServerError)?
+            }
+            // Not JSON, so nothing to prune
+            Err(_) => body.to_vec(),
+        };
+
+        res.headers_mut().remove(CONTENT_LENGTH);
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(ServiceResponse::new(
+            req,
+            res.set_body(body).map_into_boxed_body(),
+        ))
+    }
+    .boxed_local()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn prunes_nested_fields() {
+        let mut value = json!([{
+            "id": "AABBCCDD",
+            "name": "Example",
+            "description": "A long description",
+            "files": [{ "url": "https://cdn.example.com/a.jar", "hashes": { "sha1": "abc" } }],
+        }]);
+
+        FieldSet::parse("id, name,files.hashes,files.url,missing").prune(&mut value);
+
+        assert_eq!(
+            value,
+            json!([{
+                "id": "AABBCCDD",
+                "name": "Example",
+                "files": [{ "url": "https://cdn.example.com/a.jar", "hashes": { "sha1": "abc" } }],
+            }])
+        );
+    }
+
+    #[test]
+    fn matches_only_resource_routes() {
+        assert!(has_fieldsets("/v3/project/example"));
+        assert!(has_fieldsets("/v3/versions"));
+        assert!(!has_fieldsets("/v3/project/example/members"));
+        assert!(!has_fieldsets("/v2/project/example"));
+    }
+}
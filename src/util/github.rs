@@ -0,0 +1,116 @@
+use crate::util::circuit_breaker::{breaker, CircuitOpen};
+use crate::util::env::var;
+use crate::util::remote_file::{self, RemoteFileError};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use thiserror::Error;
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// How many releases are fetched from the GitHub API at once
+const RELEASES_PAGE_SIZE: usize = 100;
+
+#[derive(Error, Debug)]
+pub enum GitHubError {
+    #[error("Error while communicating with the GitHub API: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("{0}")]
+    CircuitOpen(#[from] CircuitOpen),
+    #[error("The file is too large to import")]
+    TooLarge,
+    #[error("{0}")]
+    RemoteFile(RemoteFileError),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitHubRelease {
+    pub id: u64,
+    pub tag_name: String,
+    pub name: Option<String>,
+    /// The release notes, as Markdown
+    pub body: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub assets: Vec<GitHubAsset>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GitHubAsset {
+    pub name: String,
+    pub size: u64,
+    pub browser_download_url: String,
+}
+
+async fn get<T: DeserializeOwned>(path: &str) -> Result<T, GitHubError> {
+    let breaker = breaker("github");
+    breaker.check()?;
+
+    let mut request = reqwest::Client::new()
+        .get(format!("{GITHUB_API_URL}{path}"))
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .header(reqwest::header::USER_AGENT, "modrinth/labrinth");
+    // Requests are made without a token unless one is set, which GitHub limits far more
+    if let Some(token) = var("GITHUB_API_TOKEN").ok().filter(|x| !x.is_empty()) {
+        request = request.bearer_auth(token);
+    }
+
+    let result = request.send().await.and_then(|x| x.error_for_status());
+    let response = match result {
+        // Missing repositories are the caller's problem rather than GitHub's
+        Err(err) if err.status().map_or(false, |x| x.is_client_error()) => return Err(err.into()),
+        Err(err) => {
+            breaker.record_failure();
+            return Err(err.into());
+        }
+        Ok(response) => response,
+    };
+    breaker.record_success();
+
+    Ok(response.json().await?)
+}
+
+/// Checks a repository exists, given as `owner/name`
+pub async fn check_repository(repository: &str) -> Result<(), GitHubError> {
+    get::<serde_json::Value>(&format!("/repos/{repository}")).await?;
+
+    Ok(())
+}
+
+/// Gets every published release of a repository, newest first
+pub async fn get_releases(repository: &str) -> Result<Vec<GitHubRelease>, GitHubError> {
+    let mut releases = Vec::new();
+    for page in 1.. {
+        let page: Vec<GitHubRelease> = get(&format!(
+            "/repos/{repository}/releases?per_page={RELEASES_PAGE_SIZE}&page={page}"
+        ))
+        .await?;
+        let done = page.len() < RELEASES_PAGE_SIZE;
+        releases.extend(page.into_iter().filter(|x| !x.draft));
+
+        if done {
+            break;
+        }
+    }
+
+    Ok(releases)
+}
+
+pub async fn get_release(repository: &str, id: u64) -> Result<GitHubRelease, GitHubError> {
+    get(&format!("/repos/{repository}/releases/{id}")).await
+}
+
+/// Downloads a release asset, up to `max_size` bytes
+pub async fn download(asset: &GitHubAsset, max_size: usize) -> Result<bytes::Bytes, GitHubError> {
+    if asset.size > max_size as u64 {
+        return Err(GitHubError::TooLarge);
+    }
+
+    match remote_file::fetch(&asset.browser_download_url, max_size).await {
+        Ok(data) => Ok(data.freeze()),
+        Err(RemoteFileError::TooLarge(_)) => Err(GitHubError::TooLarge),
+        Err(err) => Err(GitHubError::RemoteFile(err)),
+    }
+}
@@ -0,0 +1,93 @@
+use crate::database::models::job_item::Job;
+use crate::database::models::DatabaseError;
+use crate::models::jobs::JobPayload;
+use crate::models::moderation::ImageSource;
+use crate::util::circuit_breaker::{breaker, CircuitOpen};
+use crate::util::env::{parse_var, var};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// The confidence a scan needs in a label to flag an image, unless `IMAGE_SCAN_THRESHOLD` is set
+const DEFAULT_THRESHOLD: f32 = 0.9;
+
+#[derive(Error, Debug)]
+pub enum ImageScanError {
+    #[error("Image scanning is not configured")]
+    NotConfigured,
+    #[error("Error while communicating with the image scanning API: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("{0}")]
+    CircuitOpen(#[from] CircuitOpen),
+}
+
+/// Whether uploaded images are scanned, which they are once a scanning API is configured
+pub fn is_enabled() -> bool {
+    var("IMAGE_SCAN_API_URL").map_or(false, |x| !x.is_empty())
+}
+
+/// Queues an uploaded image to be scanned in the background, if scanning is enabled
+pub async fn queue_scan<'a, E>(
+    image_url: &str,
+    source: ImageSource,
+    exec: E,
+) -> Result<(), DatabaseError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    if is_enabled() {
+        Job::enqueue(
+            JobPayload::ScanImage {
+                image_url: image_url.to_string(),
+                source,
+            },
+            Utc::now(),
+            exec,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Asks the scanning API how confident it is that an image shows each kind of unwanted
+/// content it looks for. The API is sent `{ "url": "<image url>" }`, and answers with the
+/// confidence of each label from 0 to 1, as `{ "labels": { "nsfw": 0.98, "gore": 0.01 } }`
+pub async fn scan_image(image_url: &str) -> Result<HashMap<String, f32>, ImageScanError> {
+    #[derive(Deserialize)]
+    struct ScanResponse {
+        labels: HashMap<String, f32>,
+    }
+
+    let url = var("IMAGE_SCAN_API_URL").map_err(|_| ImageScanError::NotConfigured)?;
+
+    let breaker = breaker("image_scan");
+    breaker.check()?;
+
+    let result = reqwest::Client::new()
+        .post(url)
+        .bearer_auth(var("IMAGE_SCAN_API_KEY").unwrap_or_default())
+        .json(&json!({ "url": image_url }))
+        .send()
+        .await
+        .and_then(|x| x.error_for_status());
+    let response = match result {
+        Ok(response) => response,
+        Err(err) => {
+            breaker.record_failure();
+            return Err(err.into());
+        }
+    };
+    breaker.record_success();
+
+    Ok(response.json::<ScanResponse>().await?.labels)
+}
+
+/// Whether a scan is confident enough in any label to put the image in front of moderators
+pub fn should_flag(labels: &HashMap<String, f32>) -> bool {
+    let threshold = parse_var("IMAGE_SCAN_THRESHOLD").unwrap_or(DEFAULT_THRESHOLD);
+
+    labels.values().any(|x| *x >= threshold)
+}
@@ -1,14 +1,56 @@
 use crate::database;
 use crate::database::models::image_item;
 use crate::database::redis::RedisPool;
+use crate::file_hosting::{FileHost, FileHostingError, UploadFileData};
 use crate::models::images::ImageContext;
 use crate::routes::ApiError;
+use crate::util::svg;
+use actix_web::HttpRequest;
+use bytes::Bytes;
 use color_thief::ColorFormat;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::{PngDecoder, PngEncoder};
+use image::codecs::webp::{WebPDecoder, WebPEncoder, WebPQuality};
+use image::error::{EncodingError, ImageFormatHint};
 use image::imageops::FilterType;
-use image::{EncodableLayout, ImageError};
+use image::{
+    AnimationDecoder, ColorType, DynamicImage, EncodableLayout, Frames, ImageDecoder, ImageEncoder,
+    ImageError, ImageFormat, RgbaImage,
+};
+use log::warn;
+use ravif::{Encoder, Img, RGBA8};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// The largest width or height of an icon's renditions
+pub const MAX_ICON_DIMENSION: u32 = 512;
+/// The largest width or height of a gallery image's renditions
+pub const MAX_GALLERY_DIMENSION: u32 = 2048;
+
+/// The formats renditions are generated in, from most to least preferred, as
+/// (content type, file extension) pairs
+pub const RENDITION_FORMATS: &[(&str, &str)] = &[("image/avif", "avif"), ("image/webp", "webp")];
+/// The content type of the still rendition animated images get instead of optimized ones
+pub const STILL_RENDITION: &str = "image/png";
+
+/// The largest size of a still icon, in bytes
+pub const MAX_ICON_SIZE: usize = 262144;
+/// The largest size of an animated icon, in bytes
+pub const MAX_ANIMATED_ICON_SIZE: usize = 1 << 20;
+/// The largest width or height of an animated icon
+pub const MAX_ANIMATED_ICON_DIMENSION: u32 = 256;
+/// The most frames an animated icon may have
+pub const MAX_ANIMATED_ICON_FRAMES: usize = 256;
+
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    /// The number of frames, counted up to one more than the most an animated icon may have
+    pub frames: usize,
+}
 
 pub fn get_color_from_img(data: &[u8]) -> Result<Option<u32>, ImageError> {
-    let image = image::load_from_memory(data)?
+    let image = load_image(data, 256)?
         .resize(256, 256, FilterType::Nearest)
         .crop_imm(128, 128, 64, 64);
     let color = color_thief::get_palette(image.to_rgb8().as_bytes(), ColorFormat::Rgb, 10, 2)
@@ -19,6 +61,382 @@ pub fn get_color_from_img(data: &[u8]) -> Result<Option<u32>, ImageError> {
     Ok(color)
 }
 
+/// The frames of an animated GIF, APNG or WebP image along with its dimensions. Returns `None`
+/// for still images
+fn get_frames(data: &[u8]) -> Result<Option<((u32, u32), Frames<'_>)>, ImageError> {
+    let cursor = Cursor::new(data);
+
+    // SVGs aren't a format the image crate knows, and are never animated
+    let Ok(format) = image::guess_format(data) else {
+        return Ok(None);
+    };
+
+    Ok(match format {
+        ImageFormat::Gif => {
+            let decoder = GifDecoder::new(cursor)?;
+            Some((decoder.dimensions(), decoder.into_frames()))
+        }
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(cursor)?;
+            if decoder.is_apng() {
+                Some((decoder.dimensions(), decoder.apng().into_frames()))
+            } else {
+                None
+            }
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(cursor)?;
+            if decoder.has_animation() {
+                Some((decoder.dimensions(), decoder.into_frames()))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Reads the animation of an image. Images with a single frame count as still
+pub fn get_animation(data: &[u8]) -> Result<Option<Animation>, ImageError> {
+    let Some(((width, height), frames)) = get_frames(data)? else {
+        return Ok(None);
+    };
+
+    // Frames are decoded one at a time and dropped, so huge animations aren't held in memory
+    let mut count = 0;
+    for frame in frames.take(MAX_ANIMATED_ICON_FRAMES + 1) {
+        frame?;
+        count += 1;
+    }
+
+    Ok((count > 1).then_some(Animation {
+        width,
+        height,
+        frames: count,
+    }))
+}
+
+/// Checks an uploaded icon against the limits of still or animated icons
+pub fn check_icon(data: &[u8], animation: Option<&Animation>) -> Result<(), String> {
+    match animation {
+        None if data.len() > MAX_ICON_SIZE => {
+            Err("Icons must be smaller than 256KiB".to_string())
+        }
+        Some(_) if data.len() > MAX_ANIMATED_ICON_SIZE => {
+            Err("Animated icons must be smaller than 1MiB".to_string())
+        }
+        Some(animation)
+            if animation.width > MAX_ANIMATED_ICON_DIMENSION
+                || animation.height > MAX_ANIMATED_ICON_DIMENSION =>
+        {
+            Err(format!(
+                "Animated icons must be at most {MAX_ANIMATED_ICON_DIMENSION}x{MAX_ANIMATED_ICON_DIMENSION} pixels"
+            ))
+        }
+        Some(animation) if animation.frames > MAX_ANIMATED_ICON_FRAMES => Err(format!(
+            "Animated icons must have at most {MAX_ANIMATED_ICON_FRAMES} frames"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Scales an image down to fit within `max_dimension` and re-encodes it in every rendition
+/// format, returned along with their content type and extension. Only the pixels are carried
+/// over, so EXIF and other metadata are stripped.
+///
+/// The optimized formats would only keep the first frame of an animated image, so animated
+/// images get that frame as a still rendition instead, for places which can't show animations
+pub fn optimize_image(
+    data: &[u8],
+    max_dimension: u32,
+) -> Result<Vec<(&'static str, &'static str, Vec<u8>)>, ImageError> {
+    if let Some((_, mut frames)) = get_frames(data)? {
+        if let Some(frame) = frames.next() {
+            let still = encode_png(&frame?.into_buffer())?;
+            return Ok(vec![(STILL_RENDITION, "still.png", still)]);
+        }
+    }
+
+    let mut image = load_image(data, max_dimension)?;
+    if image.width() > max_dimension || image.height() > max_dimension {
+        image = image.resize(max_dimension, max_dimension, FilterType::Lanczos3);
+    }
+    let image = image.to_rgba8();
+
+    let mut renditions = Vec::with_capacity(RENDITION_FORMATS.len());
+    for (content_type, ext) in RENDITION_FORMATS {
+        let buffer = match *content_type {
+            "image/avif" => encode_avif(&image)?,
+            _ => {
+                let mut buffer = Vec::new();
+                WebPEncoder::new_with_quality(&mut buffer, WebPQuality::lossy(80)).write_image(
+                    image.as_raw(),
+                    image.width(),
+                    image.height(),
+                    ColorType::Rgba8,
+                )?;
+                buffer
+            }
+        };
+        renditions.push((*content_type, *ext, buffer));
+    }
+
+    // Not every client can show SVGs, so vector images also get a still rendition
+    if svg::is_svg(data) {
+        renditions.push((STILL_RENDITION, "still.png", encode_png(&image)?));
+    }
+
+    Ok(renditions)
+}
+
+/// Decodes an image, rendering SVGs to fit within `svg_dimension`
+fn load_image(data: &[u8], svg_dimension: u32) -> Result<DynamicImage, ImageError> {
+    if svg::is_svg(data) {
+        svg::rasterize_svg(data, svg_dimension)
+    } else {
+        image::load_from_memory(data)
+    }
+}
+
+fn encode_avif(image: &RgbaImage) -> Result<Vec<u8>, ImageError> {
+    let pixels = image
+        .pixels()
+        .map(|x| RGBA8::new(x[0], x[1], x[2], x[3]))
+        .collect::<Vec<_>>();
+
+    let encoded = Encoder::new()
+        .with_quality(80.0)
+        .with_alpha_quality(80.0)
+        .with_speed(8)
+        .encode_rgba(Img::new(
+            &pixels[..],
+            image.width() as usize,
+            image.height() as usize,
+        ))
+        .map_err(|err| {
+            ImageError::Encoding(EncodingError::new(
+                ImageFormatHint::Exact(ImageFormat::Avif),
+                err,
+            ))
+        })?;
+
+    Ok(encoded.avif_file)
+}
+
+fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, ImageError> {
+    let mut buffer = Vec::new();
+    PngEncoder::new(&mut buffer).write_image(
+        image.as_raw(),
+        image.width(),
+        image.height(),
+        ColorType::Rgba8,
+    )?;
+
+    Ok(buffer)
+}
+
+/// Generates and uploads the renditions of an uploaded image next to the original, which is
+/// stored at `{path}.{original extension}`.
+///
+/// Renditions are an optimization, so an image that can't be converted is only logged and
+/// keeps being served as it was uploaded
+pub async fn upload_renditions(
+    file_host: &(dyn FileHost + Send + Sync),
+    data: Bytes,
+    path: &str,
+    max_dimension: u32,
+) -> Result<Vec<UploadFileData>, FileHostingError> {
+    let result = actix_web::web::block(move || optimize_image(&data, max_dimension))
+        .await
+        .map_err(|err| err.to_string())
+        .and_then(|x| x.map_err(|err| err.to_string()));
+    let renditions = match result {
+        Ok(renditions) => renditions,
+        Err(err) => {
+            warn!("Failed to generate renditions for {path}: {err}");
+            return Ok(Vec::new());
+        }
+    };
+
+    let mut uploads = Vec::with_capacity(renditions.len());
+    for (content_type, ext, bytes) in renditions {
+        uploads.push(
+            file_host
+                .upload_file(content_type, &format!("{path}.{ext}"), Bytes::from(bytes))
+                .await?,
+        );
+    }
+
+    Ok(uploads)
+}
+
+/// The URL of each uploaded rendition by content type, as stored in the database
+pub fn rendition_urls(uploads: &[UploadFileData], cdn_url: &str) -> HashMap<String, String> {
+    uploads
+        .iter()
+        .map(|x| {
+            (
+                x.content_type.clone(),
+                format!("{}/{}", cdn_url, x.file_name),
+            )
+        })
+        .collect()
+}
+
+/// Deletes the renditions of an image from the file host
+pub async fn delete_renditions(
+    file_host: &dyn FileHost,
+    renditions: &HashMap<String, String>,
+) -> Result<(), ApiError> {
+    let cdn_url = dotenvy::var("CDN_URL")?;
+
+    for url in renditions.values() {
+        if let Some(path) = url.split(&format!("{cdn_url}/")).nth(1) {
+            file_host.delete_file_version("", path).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The widths thumbnails can be requested in. Other widths are refused so each image only
+/// ever has a few thumbnails in storage
+pub const THUMBNAIL_SIZES: &[u32] = &[96, 256, 512, 1024];
+
+/// The path the thumbnail of an image with a given width is stored at, next to the original
+pub fn thumbnail_path(original_path: &str, size: u32) -> String {
+    let stem = original_path
+        .rsplit_once('.')
+        .map_or(original_path, |(stem, _)| stem);
+
+    format!("{stem}_{size}w.webp")
+}
+
+/// Scales an image down to `size` pixels wide and encodes it as WebP. Images which are already
+/// narrower keep their size
+pub fn create_thumbnail(data: &[u8], size: u32) -> Result<Vec<u8>, ImageError> {
+    let mut image = load_image(data, size)?;
+    if image.width() > size {
+        image = image.resize(size, u32::MAX, FilterType::Lanczos3);
+    }
+    let image = image.to_rgba8();
+
+    let mut buffer = Vec::new();
+    WebPEncoder::new_with_quality(&mut buffer, WebPQuality::lossy(80)).write_image(
+        image.as_raw(),
+        image.width(),
+        image.height(),
+        ColorType::Rgba8,
+    )?;
+
+    Ok(buffer)
+}
+
+/// Deletes every thumbnail of an image from the file host
+pub async fn delete_thumbnails(
+    file_host: &dyn FileHost,
+    original_path: &str,
+) -> Result<(), ApiError> {
+    for size in THUMBNAIL_SIZES {
+        file_host
+            .delete_file_version("", &thumbnail_path(original_path, *size))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Perceptual hashes at most this many bits apart are treated as the same picture
+pub const SIMILAR_IMAGE_DISTANCE: i64 = 6;
+
+/// A difference hash of an image: whether each pixel of a 9x8 grayscale copy is darker than
+/// its right neighbour. Unlike a checksum, it barely changes when an image is resized,
+/// re-encoded or lightly edited, so copies of an image can be found by comparing hashes
+pub fn perceptual_hash(data: &[u8]) -> Result<i64, ImageError> {
+    let image = load_image(data, 64)?
+        .resize_exact(9, 8, FilterType::Triangle)
+        .to_rgba8();
+    // Transparent pixels are laid over white, so whatever color they hide doesn't matter
+    let luma = |x: u32, y: u32| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let gray = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+        (gray * a as u32 + 255 * (255 - a as u32)) / 255
+    };
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash = hash << 1 | (luma(x, y) < luma(x + 1, y)) as u64;
+        }
+    }
+
+    Ok(hash as i64)
+}
+
+/// Hashes an uploaded image off of the request's thread. Hashes only feed duplicate
+/// detection, so an image that can't be hashed is logged and stored without one
+pub async fn get_perceptual_hash(data: Bytes) -> Option<i64> {
+    let result = actix_web::web::block(move || perceptual_hash(&data))
+        .await
+        .map_err(|err| err.to_string())
+        .and_then(|x| x.map_err(|err| err.to_string()));
+
+    match result {
+        Ok(hash) => Some(hash),
+        Err(err) => {
+            warn!("Failed to compute the perceptual hash of an image: {err}");
+            None
+        }
+    }
+}
+
+/// Picks the URL of the most preferred rendition the client accepts, falling back to the
+/// original when it accepts none of them. Clients that don't send an `Accept` header are
+/// assumed to only support the original
+pub fn preferred_image_url(
+    original: &str,
+    renditions: &HashMap<String, String>,
+    req: &HttpRequest,
+) -> String {
+    let accept = req
+        .headers()
+        .get(actix_web::http::header::ACCEPT)
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default();
+
+    // Vector images are the best format for clients which can show them, and others get the
+    // still rendition rather than an image they may not be able to show at all
+    let svg = original.ends_with(".svg");
+    if svg && accepts(accept, "image/svg+xml") {
+        return original.to_string();
+    }
+
+    RENDITION_FORMATS
+        .iter()
+        .filter(|(content_type, _)| accepts(accept, content_type))
+        .find_map(|(content_type, _)| renditions.get(*content_type))
+        .or_else(|| renditions.get(STILL_RENDITION).filter(|_| svg))
+        .cloned()
+        .unwrap_or_else(|| original.to_string())
+}
+
+/// Whether an `Accept` header explicitly lists a content type. Wildcards don't count, as
+/// browsers send `*/*` for API requests whatever image formats they support
+fn accepts(accept: &str, content_type: &str) -> bool {
+    accept.split(',').any(|range| {
+        let mut parts = range.split(';');
+        let media_type = parts.next().unwrap_or_default().trim();
+        let rejected = parts.any(|x| {
+            x.trim()
+                .strip_prefix("q=")
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .map_or(false, |q| q <= 0.0)
+        });
+
+        media_type.eq_ignore_ascii_case(content_type) && !rejected
+    })
+}
+
 // check changes to associated images
 // if they no longer exist in the String list, delete them
 // Eg: if description is modified and no longer contains a link to an iamge
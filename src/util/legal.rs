@@ -0,0 +1,152 @@
+use crate::auth::validate::get_user_record_from_bearer_token;
+use crate::database::models::legal_document_item::LegalDocument;
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
+use crate::models::legal::LegalAcceptanceRequired;
+use crate::queue::session::AuthQueue;
+use crate::routes::ErrorCode;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::Method;
+use actix_web::{web, Error, HttpResponse};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use futures::FutureExt;
+use log::warn;
+use reqwest::header::AUTHORIZATION;
+use sqlx::PgPool;
+use std::rc::Rc;
+
+/// Routes which still accept changes from users who have documents to accept, so they can
+/// accept them, sign out or act as staff. Paths also match the routes nested under them
+const ALLOWED_ROUTES: &[&str] = &["/v3/legal", "/v2/admin", "/v2/auth", "/v2/session"];
+
+fn is_allowed(req: &ServiceRequest) -> bool {
+    matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        || !req.headers().contains_key(AUTHORIZATION)
+        || ALLOWED_ROUTES.iter().any(|route| {
+            req.path()
+                .strip_prefix(route)
+                .map(|rest| rest.is_empty() || rest.starts_with('/'))
+                .unwrap_or(false)
+        })
+}
+
+/// Gets the documents the user making a request still has to accept
+async fn pending_documents(req: &ServiceRequest) -> Result<Vec<LegalDocument>, DatabaseError> {
+    let (Some(pool), Some(redis), Some(session_queue)) = (
+        req.app_data::<web::Data<PgPool>>(),
+        req.app_data::<web::Data<RedisPool>>(),
+        req.app_data::<web::Data<AuthQueue>>(),
+    ) else {
+        return Ok(Vec::new());
+    };
+    let pool: &PgPool = pool;
+
+    // Users are only looked up once a material version has been published
+    if LegalDocument::get_required(pool, redis).await?.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Invalid credentials are left for the route to refuse
+    let user = get_user_record_from_bearer_token(req.request(), None, pool, redis, session_queue)
+        .await
+        .ok()
+        .flatten();
+
+    match user {
+        Some((_, user)) => LegalDocument::get_pending(user.id, user.created, pool, redis).await,
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Refuses changes with a 403 from users who have not accepted the latest material version of
+/// the terms, privacy policy or rules, naming the documents they have to accept. Reads keep
+/// working, so the documents can still be shown to them
+pub struct LegalAcceptanceGate;
+
+impl<S, B> Transform<S, ServiceRequest> for LegalAcceptanceGate
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = LegalAcceptanceGateMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(LegalAcceptanceGateMiddleware {
+            service: Rc::new(service),
+        })
+    }
+}
+
+pub struct LegalAcceptanceGateMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for LegalAcceptanceGateMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let srv = self.service.clone();
+
+        async move {
+            if !is_allowed(&req) {
+                // Changes are let through if the documents cannot be read, like in read-only
+                // mode, so a Redis outage does not turn into a write outage too
+                let pending = pending_documents(&req).await.unwrap_or_else(|err| {
+                    warn!("Checking legal document acceptance failed: {}", err);
+                    Vec::new()
+                });
+                if !pending.is_empty() {
+                    return Err(AcceptanceRequiredError(pending).into());
+                }
+            }
+
+            srv.call(req).await
+        }
+        .boxed_local()
+    }
+}
+
+#[derive(Debug)]
+struct AcceptanceRequiredError(Vec<LegalDocument>);
+
+impl std::fmt::Display for AcceptanceRequiredError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Updated legal documents must be accepted before making changes: {}",
+            self.0
+                .iter()
+                .map(|x| x.kind.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+impl actix_web::ResponseError for AcceptanceRequiredError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        actix_web::http::StatusCode::FORBIDDEN
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::Forbidden().json(LegalAcceptanceRequired {
+            error: ErrorCode::LegalAcceptanceRequired.as_str().to_string(),
+            description: self.to_string(),
+            documents: self.0.iter().cloned().map(Into::into).collect(),
+        })
+    }
+}
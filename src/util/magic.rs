@@ -0,0 +1,134 @@
+use thiserror::Error;
+
+/// The kinds of files told apart by their first bytes
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FileSignature {
+    /// Zip archives, which jars, mods, resource packs and modpacks all are
+    Zip,
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    Webp,
+    /// Windows, Linux and macOS programs
+    Executable,
+    Unknown,
+}
+
+impl FileSignature {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileSignature::Zip => "zip archive",
+            FileSignature::Png => "PNG image",
+            FileSignature::Jpeg => "JPEG image",
+            FileSignature::Gif => "GIF image",
+            FileSignature::Bmp => "BMP image",
+            FileSignature::Webp => "WebP image",
+            FileSignature::Executable => "executable program",
+            FileSignature::Unknown => "file of an unknown type",
+        }
+    }
+
+    /// The signature of files stored with a content type, if it has one
+    pub fn from_content_type(content_type: &str) -> Option<FileSignature> {
+        match content_type {
+            "application/java-archive"
+            | "application/zip"
+            | "application/x-modrinth-modpack+zip" => Some(FileSignature::Zip),
+            "image/png" => Some(FileSignature::Png),
+            "image/jpeg" => Some(FileSignature::Jpeg),
+            "image/gif" => Some(FileSignature::Gif),
+            "image/bmp" => Some(FileSignature::Bmp),
+            "image/webp" => Some(FileSignature::Webp),
+            _ => None,
+        }
+    }
+}
+
+/// Tells what kind of file data is from its magic bytes
+pub fn sniff(data: &[u8]) -> FileSignature {
+    if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+        FileSignature::Zip
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        FileSignature::Png
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        FileSignature::Jpeg
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        FileSignature::Gif
+    } else if data.starts_with(b"BM") {
+        FileSignature::Bmp
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        FileSignature::Webp
+    } else if data.starts_with(b"MZ")
+        || data.starts_with(b"\x7fELF")
+        || [
+            b"\xfe\xed\xfa\xce",
+            b"\xfe\xed\xfa\xcf",
+            b"\xce\xfa\xed\xfe",
+            b"\xcf\xfa\xed\xfe",
+        ]
+        .iter()
+        .any(|x| data.starts_with(*x))
+    {
+        FileSignature::Executable
+    } else {
+        FileSignature::Unknown
+    }
+}
+
+/// The contents of an uploaded file are not what its extension says they are
+#[derive(Error, Debug)]
+#[error(
+    "The contents of the file ({}) do not match its .{extension} extension",
+    .detected.as_str()
+)]
+pub struct ContentMismatch {
+    pub extension: String,
+    pub detected: FileSignature,
+}
+
+/// Checks that a file uploaded with `extension`, stored as `content_type`, has the magic bytes
+/// of that type. Types without a signature, such as SVGs and logs, only have executables refused
+pub fn check_content(
+    data: &[u8],
+    extension: &str,
+    content_type: &str,
+) -> Result<(), ContentMismatch> {
+    let detected = sniff(data);
+    let matches = match FileSignature::from_content_type(content_type) {
+        Some(expected) => detected == expected,
+        None => detected != FileSignature::Executable,
+    };
+
+    if matches {
+        Ok(())
+    } else {
+        Err(ContentMismatch {
+            extension: extension.to_string(),
+            detected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniff_recognizes_signatures() {
+        assert_eq!(sniff(b"PK\x03\x04rest"), FileSignature::Zip);
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n...."), FileSignature::Png);
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 "), FileSignature::Webp);
+        assert_eq!(sniff(b"MZ\x90\0"), FileSignature::Executable);
+        assert_eq!(sniff(b"hello"), FileSignature::Unknown);
+    }
+
+    #[test]
+    fn renamed_executables_are_refused() {
+        let err = check_content(b"MZ\x90\0", "jar", "application/java-archive").unwrap_err();
+        assert_eq!(err.detected, FileSignature::Executable);
+        assert!(check_content(b"MZ\x90\0", "txt", "text/plain").is_err());
+        assert!(check_content(b"PK\x03\x04", "jar", "application/java-archive").is_ok());
+        assert!(check_content(b"<svg/>", "svg", "image/svg+xml").is_ok());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
+use crate::util::env::parse_var;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::http::Method;
+use actix_web::{Error, HttpResponse};
+use chrono::{DateTime, Utc};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use futures::FutureExt;
+use lazy_static::lazy_static;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+const MAINTENANCE_NAMESPACE: &str = "maintenance";
+/// How long instances use the mode they last read before reading it from Redis again
+const STATE_CACHE_TIME: Duration = Duration::from_secs(5);
+pub const DEFAULT_RETRY_AFTER: u64 = 300;
+/// Read-only mode is kept far longer than cache entries, so it lasts until it is turned off
+const MAINTENANCE_EXPIRY: i64 = 60 * 60 * 24 * 30;
+
+/// Routes which still accept changes in read-only mode. Paths also match the routes nested
+/// under them
+const ALLOWED_ROUTES: &[&str] = &["/_internal/admin", "/v2/admin"];
+
+lazy_static! {
+    static ref CACHED_STATE: RwLock<Option<(Instant, Option<MaintenanceState>)>> =
+        RwLock::new(None);
+}
+
+/// Read-only mode, in which requests that would change anything are refused while reads keep
+/// working. Turned on by admins during migrations or storage incidents
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MaintenanceState {
+    pub message: String,
+    /// Seconds clients are told to wait before trying again
+    pub retry_after: u64,
+    pub started: DateTime<Utc>,
+}
+
+/// Gets the current mode. `MAINTENANCE_MODE` turns read-only mode on for deploys where Redis
+/// itself may be unavailable
+pub async fn get_state(redis: &RedisPool) -> Result<Option<MaintenanceState>, DatabaseError> {
+    if parse_var("MAINTENANCE_MODE").unwrap_or(false) {
+        return Ok(Some(MaintenanceState {
+            message: "Modrinth is undergoing maintenance".to_string(),
+            retry_after: DEFAULT_RETRY_AFTER,
+            started: Utc::now(),
+        }));
+    }
+
+    let mut redis = redis.connect().await?;
+    redis
+        .get_deserialized_from_json(MAINTENANCE_NAMESPACE, "state")
+        .await
+}
+
+pub async fn set_state(
+    state: Option<&MaintenanceState>,
+    redis: &RedisPool,
+) -> Result<(), DatabaseError> {
+    let mut redis = redis.connect().await?;
+    match state {
+        Some(state) => {
+            redis
+                .set_serialized_to_json(
+                    MAINTENANCE_NAMESPACE,
+                    "state",
+                    state,
+                    Some(MAINTENANCE_EXPIRY),
+                )
+                .await?
+        }
+        None => redis.delete(MAINTENANCE_NAMESPACE, "state").await?,
+    }
+
+    *CACHED_STATE.write().await = None;
+
+    Ok(())
+}
+
+async fn cached_state(redis: &RedisPool) -> Option<MaintenanceState> {
+    if let Some((read_at, state)) = &*CACHED_STATE.read().await {
+        if read_at.elapsed() < STATE_CACHE_TIME {
+            return state.clone();
+        }
+    }
+
+    // Changes are let through if the mode cannot be read, so a Redis outage does not turn
+    // into a write outage too
+    let state = get_state(redis).await.unwrap_or_else(|err| {
+        warn!("Reading maintenance mode failed: {}", err);
+        None
+    });
+    *CACHED_STATE.write().await = Some((Instant::now(), state.clone()));
+
+    state
+}
+
+fn is_allowed(req: &ServiceRequest) -> bool {
+    matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        || ALLOWED_ROUTES.iter().any(|route| {
+            req.path()
+                .strip_prefix(route)
+                .map(|rest| rest.is_empty() || rest.starts_with('/'))
+                .unwrap_or(false)
+        })
+}
+
+/// Refuses requests which could change anything with a 503 while read-only mode is on
+pub struct ReadOnlyMode {
+    redis: RedisPool,
+}
+
+impl ReadOnlyMode {
+    pub fn new(redis: RedisPool) -> Self {
+        ReadOnlyMode { redis }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ReadOnlyMode
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ReadOnlyModeMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(ReadOnlyModeMiddleware {
+            service: Rc::new(service),
+            redis: self.redis.clone(),
+        })
+    }
+}
+
+pub struct ReadOnlyModeMiddleware<S> {
+    service: Rc<S>,
+    redis: RedisPool,
+}
+
+impl<S, B> Service<ServiceRequest> for ReadOnlyModeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let srv = self.service.clone();
+        let redis = self.redis.clone();
+
+        async move {
+            if !is_allowed(&req) {
+                if let Some(state) = cached_state(&redis).await {
+                    return Err(MaintenanceError(state).into());
+                }
+            }
+
+            srv.call(req).await
+        }
+        .boxed_local()
+    }
+}
+
+#[derive(Debug)]
+struct MaintenanceError(MaintenanceState);
+
+impl std::fmt::Display for MaintenanceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.message)
+    }
+}
+
+impl actix_web::ResponseError for MaintenanceError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        actix_web::http::StatusCode::SERVICE_UNAVAILABLE
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::ServiceUnavailable()
+            .insert_header((RETRY_AFTER, self.0.retry_after.to_string()))
+            .json(crate::models::error::ApiError {
+                error: crate::routes::ErrorCode::ReadOnly.as_str(),
+                description: &self.0.message,
+                fields: Vec::new(),
+            })
+    }
+}
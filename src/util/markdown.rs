@@ -0,0 +1,84 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+lazy_static! {
+    static ref RE_HTML_COMMENT: Regex = Regex::new(r"(?s)<!--.*?(?:-->|$)").unwrap();
+    static ref RE_HTML_TAG: Regex =
+        Regex::new(r"</?[A-Za-z][A-Za-z0-9-]*(?:\s[^<>]*)?/?>").unwrap();
+    static ref RE_AUTOLINK: Regex = Regex::new(r"<([A-Za-z][A-Za-z0-9+.-]*:[^<>\s]*)>").unwrap();
+    static ref RE_INLINE_LINK: Regex =
+        Regex::new(r"(\]\(\s*<?)((?:[^()\s>]|\([^()\s]*\))*)").unwrap();
+    static ref RE_REFERENCE_LINK: Regex =
+        Regex::new(r"(?m)^( {0,3}\[[^\]]+\]:\s*<?)(\S*)").unwrap();
+}
+
+/// Whether a link destination can only go to a web page, an email address or somewhere on the
+/// same site. Renderers decode entities in destinations, so `&` is only let through in
+/// destinations which already have a safe scheme
+fn is_safe_destination(destination: &str) -> bool {
+    let lowercase = destination.to_lowercase();
+
+    ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|x| lowercase.starts_with(x))
+        || !(lowercase.contains(':') || lowercase.contains('&'))
+}
+
+/// Strips raw HTML from Markdown, and replaces the destinations of links and images which
+/// could run scripts, such as `javascript:` URLs, with `#`. Text is otherwise left as written
+pub fn sanitize_markdown(text: &str) -> String {
+    let text = RE_HTML_COMMENT.replace_all(text, "");
+    let text = RE_AUTOLINK.replace_all(&text, |caps: &Captures| {
+        if is_safe_destination(&caps[1]) {
+            caps[0].to_string()
+        } else {
+            String::new()
+        }
+    });
+    let text = RE_HTML_TAG.replace_all(&text, "");
+
+    let replace_destination = |caps: &Captures| {
+        if is_safe_destination(&caps[2]) {
+            caps[0].to_string()
+        } else {
+            format!("{}#", &caps[1])
+        }
+    };
+    let text = RE_INLINE_LINK.replace_all(&text, replace_destination);
+    let text = RE_REFERENCE_LINK.replace_all(&text, replace_destination);
+
+    text.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_keeps_plain_markdown() {
+        let text = "# About\n\nI make **mods**. See [my site](https://example.com) or <https://example.com>.";
+        assert_eq!(sanitize_markdown(text), text);
+    }
+
+    #[test]
+    fn sanitize_strips_html() {
+        assert_eq!(
+            sanitize_markdown("Hi<script>alert(1)</script> <img src=x onerror=alert(1)><!-- x -->"),
+            "Hialert(1) "
+        );
+    }
+
+    #[test]
+    fn sanitize_replaces_script_links() {
+        assert_eq!(
+            sanitize_markdown(
+                "[a](javascript:alert(1)) ![b]( JaVaScRiPt:x) [c](&#106;avascript:x)"
+            ),
+            "[a](#) ![b]( #) [c](#)"
+        );
+        assert_eq!(
+            sanitize_markdown("[a]: data:text/html,x\n<javascript:alert(1)>"),
+            "[a]: #\n"
+        );
+    }
+}
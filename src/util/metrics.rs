@@ -0,0 +1,124 @@
+use crate::database::models::job_item::Job;
+use crate::database::models::DatabaseError;
+use lazy_static::lazy_static;
+use prometheus::core::Collector;
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry};
+use sqlx::PgPool;
+
+lazy_static! {
+    static ref REGISTRY: Registry = Registry::new();
+
+    /// Connections of the database pools, by pool (`primary` or `replica`) and state (`idle` or
+    /// `active`)
+    pub static ref DATABASE_CONNECTIONS: IntGaugeVec = register(
+        IntGaugeVec::new(
+            Opts::new("labrinth_database_connections", "Connections held by the database pools"),
+            &["pool", "state"],
+        )
+        .unwrap()
+    );
+    /// Cache reads by namespace and result (`hit` or `miss`)
+    pub static ref CACHE_REQUESTS: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new("labrinth_cache_requests_total", "Entries read from the Redis cache"),
+            &["namespace", "result"],
+        )
+        .unwrap()
+    );
+    /// How long search indexing runs take, by result (`success` or `failure`)
+    pub static ref INDEXING_DURATION: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "labrinth_search_indexing_duration_seconds",
+                "Time taken to index all projects for search",
+            )
+            .buckets(vec![30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0]),
+            &["result"],
+        )
+        .unwrap()
+    );
+    /// Jobs in the job queue, by status
+    pub static ref JOBS_QUEUED: IntGaugeVec = register(
+        IntGaugeVec::new(
+            Opts::new("labrinth_jobs", "Jobs in the background job queue"),
+            &["status"],
+        )
+        .unwrap()
+    );
+    /// Job runs by kind and result (`success`, `retry` or `dead`)
+    pub static ref JOBS_PROCESSED: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new("labrinth_jobs_processed_total", "Background job runs"),
+            &["kind", "result"],
+        )
+        .unwrap()
+    );
+    /// Webhook deliveries by kind (`discord` or `notification`) and result (`success` or
+    /// `failure`)
+    pub static ref WEBHOOK_DELIVERIES: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new("labrinth_webhook_deliveries_total", "Webhooks sent"),
+            &["kind", "result"],
+        )
+        .unwrap()
+    );
+    /// Scheduled payout runs by task and result (`success` or `failure`)
+    pub static ref PAYOUT_RUNS: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new("labrinth_payout_runs_total", "Scheduled payout task runs"),
+            &["task", "result"],
+        )
+        .unwrap()
+    );
+}
+
+fn register<T: Collector + Clone + 'static>(metric: T) -> T {
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("Metric registered twice");
+    metric
+}
+
+/// The registry served at `/metrics`, which request metrics are also added to
+pub fn registry() -> Registry {
+    // Metrics are registered on first use, so they are touched here to be exported from the
+    // start
+    lazy_static::initialize(&DATABASE_CONNECTIONS);
+    lazy_static::initialize(&CACHE_REQUESTS);
+    lazy_static::initialize(&INDEXING_DURATION);
+    lazy_static::initialize(&JOBS_QUEUED);
+    lazy_static::initialize(&JOBS_PROCESSED);
+    lazy_static::initialize(&WEBHOOK_DELIVERIES);
+    lazy_static::initialize(&PAYOUT_RUNS);
+
+    REGISTRY.clone()
+}
+
+/// The label for the result of an operation
+pub fn result_label<T, E>(result: &Result<T, E>) -> &'static str {
+    if result.is_ok() {
+        "success"
+    } else {
+        "failure"
+    }
+}
+
+/// Updates the metrics which are sampled rather than counted as things happen
+pub async fn sample(pool: &PgPool, read_pool: &PgPool) -> Result<(), DatabaseError> {
+    for (name, pool) in [("primary", pool), ("replica", read_pool)] {
+        let idle = pool.num_idle() as i64;
+        DATABASE_CONNECTIONS
+            .with_label_values(&[name, "idle"])
+            .set(idle);
+        DATABASE_CONNECTIONS
+            .with_label_values(&[name, "active"])
+            .set(pool.size() as i64 - idle);
+    }
+
+    JOBS_QUEUED.reset();
+    for (status, count) in Job::count_by_status(pool).await? {
+        JOBS_QUEUED.with_label_values(&[status.as_str()]).set(count);
+    }
+
+    Ok(())
+}
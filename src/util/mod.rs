@@ -1,13 +1,41 @@
 pub mod actix;
 pub mod bitflag;
+pub mod body_limit;
 pub mod captcha;
+pub mod circuit_breaker;
 pub mod cors;
+pub mod curseforge;
+pub mod cursor;
 pub mod date;
 pub mod env;
+pub mod etag;
 pub mod ext;
+pub mod feed;
+pub mod fields;
+pub mod github;
 pub mod guards;
+pub mod image_scan;
 pub mod img;
+pub mod legal;
+pub mod magic;
+pub mod maintenance;
+pub mod markdown;
+pub mod metrics;
+pub mod money;
+pub mod pat_usage;
+pub mod public_url;
 pub mod redis;
+pub mod reload;
+pub mod remote_file;
 pub mod routes;
+pub mod simple_push;
+pub mod spam;
+pub mod statement;
+pub mod svg;
+pub mod telemetry;
+pub mod traffic;
+pub mod upload_progress;
+pub mod user_agent;
 pub mod validate;
 pub mod webhook;
+pub mod webpush;
@@ -0,0 +1,150 @@
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The currency revenue is earned in, and which new balances are kept in. Balances are stored in
+/// the minor unit of their currency, but as decimals rather than [`Money`], as revenue accrues in
+/// fractions of the minor unit
+pub const BASE_CURRENCY: &str = "USD";
+
+/// Currencies payouts can be quoted in, with the number of decimal places of their minor unit
+/// (ISO 4217)
+pub const CURRENCIES: &[(&str, u32)] = &[
+    ("USD", 2),
+    ("EUR", 2),
+    ("GBP", 2),
+    ("CAD", 2),
+    ("AUD", 2),
+    ("NZD", 2),
+    ("CHF", 2),
+    ("SEK", 2),
+    ("NOK", 2),
+    ("DKK", 2),
+    ("PLN", 2),
+    ("CZK", 2),
+    ("HUF", 2),
+    ("BRL", 2),
+    ("MXN", 2),
+    ("INR", 2),
+    ("PHP", 2),
+    ("SGD", 2),
+    ("HKD", 2),
+    ("JPY", 0),
+    ("KRW", 0),
+];
+
+/// The number of decimal places of a currency's minor unit, `None` if it is not supported
+pub fn currency_exponent(currency: &str) -> Option<u32> {
+    CURRENCIES
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(currency))
+        .map(|(_, exponent)| *exponent)
+}
+
+/// Converts a decimal amount into minor units, keeping fractions of the minor unit
+pub fn to_minor_units(amount: Decimal, currency: &str) -> Option<Decimal> {
+    amount.checked_mul(Decimal::from(10i64.pow(currency_exponent(currency)?)))
+}
+
+/// Converts an amount in minor units, such as a balance, back into a decimal amount
+pub fn from_minor_units(amount: Decimal, currency: &str) -> Option<Decimal> {
+    amount.checked_div(Decimal::from(10i64.pow(currency_exponent(currency)?)))
+}
+
+/// Converts an amount in the base currency, which the payouts ledger is kept in, into the minor
+/// units balances are kept in
+pub fn base_to_minor_units(amount: Decimal) -> Decimal {
+    to_minor_units(amount, BASE_CURRENCY).unwrap_or(amount)
+}
+
+/// An amount of money in the minor unit of its currency, such as cents for USD
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, ToSchema)]
+pub struct Money {
+    pub amount: i64,
+    pub currency: String,
+}
+
+impl Money {
+    /// Converts a decimal amount into minor units. Fractions of the minor unit are truncated,
+    /// so the amount never exceeds what is available
+    pub fn from_decimal(amount: Decimal, currency: &str) -> Option<Money> {
+        let exponent = currency_exponent(currency)?;
+
+        let minor = amount.round_dp_with_strategy(exponent, RoundingStrategy::ToZero)
+            * Decimal::from(10i64.pow(exponent));
+
+        Some(Money {
+            amount: minor.to_i64()?,
+            currency: currency.to_uppercase(),
+        })
+    }
+
+    /// Converts a balance in minor units, truncating fractions of the minor unit
+    pub fn from_minor_units(amount: Decimal, currency: &str) -> Option<Money> {
+        currency_exponent(currency)?;
+
+        Some(Money {
+            amount: amount.trunc().to_i64()?,
+            currency: currency.to_uppercase(),
+        })
+    }
+
+    pub fn to_decimal(&self) -> Decimal {
+        Decimal::new(self.amount, currency_exponent(&self.currency).unwrap_or(0))
+    }
+
+    /// Converts an amount in the base currency, given how much one unit of it is worth in
+    /// `currency`
+    pub fn convert(amount: Decimal, currency: &str, rate: Decimal) -> Option<Money> {
+        Money::from_decimal(amount.checked_mul(rate)?, currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_are_truncated_to_minor_units() {
+        assert_eq!(
+            Money::from_decimal(Decimal::new(123456, 4), "usd"),
+            Some(Money {
+                amount: 1234,
+                currency: "USD".to_string(),
+            })
+        );
+        assert_eq!(
+            Money::from_decimal(Decimal::new(15999, 2), "JPY").map(|x| x.amount),
+            Some(159)
+        );
+        assert_eq!(Money::from_decimal(Decimal::ONE, "XXX"), None);
+    }
+
+    #[test]
+    fn minor_units_round_trip() {
+        let money = Money::from_decimal(Decimal::new(1050, 2), "EUR").unwrap();
+        assert_eq!(money.to_decimal(), Decimal::new(1050, 2));
+    }
+
+    #[test]
+    fn balances_keep_fractions_of_minor_units() {
+        let minor = to_minor_units(Decimal::new(123456, 4), "USD").unwrap();
+        assert_eq!(minor, Decimal::new(123456, 2));
+        assert_eq!(
+            from_minor_units(minor, "USD"),
+            Some(Decimal::new(123456, 4))
+        );
+        assert_eq!(Money::from_minor_units(minor, "USD").unwrap().amount, 1234);
+        assert_eq!(to_minor_units(Decimal::ONE, "XXX"), None);
+    }
+
+    #[test]
+    fn conversion_uses_rate() {
+        let money = Money::convert(Decimal::from(10), "EUR", Decimal::new(9215, 4)).unwrap();
+        assert_eq!(money.amount, 921);
+
+        let money = Money::convert(Decimal::from(10), "JPY", Decimal::new(14832, 2)).unwrap();
+        assert_eq!(money.amount, 1483);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::queue::session::AuthQueue;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{web, Error};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use futures::FutureExt;
+use std::rc::Rc;
+
+/// Counts the requests made with each personal access token, and samples those which failed,
+/// so developers can see which of their tokens is using up their rate limit. Wrapped outside
+/// the rate limiter, so the requests it refuses are counted too
+pub struct PatUsageTracker;
+
+impl<S, B> Transform<S, ServiceRequest> for PatUsageTracker
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = PatUsageTrackerMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(PatUsageTrackerMiddleware {
+            service: Rc::new(service),
+        })
+    }
+}
+
+pub struct PatUsageTrackerMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for PatUsageTrackerMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let srv = self.service.clone();
+
+        // Tokens are only checked when the usage is batched, so made up ones are dropped there
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|x| x.to_str().ok())
+            .filter(|x| x.starts_with("mrp_"))
+            .map(|x| x.to_string());
+        let Some((token, session_queue)) =
+            token.zip(req.app_data::<web::Data<AuthQueue>>().cloned())
+        else {
+            return async move { srv.call(req).await }.boxed_local();
+        };
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+
+        async move {
+            let res = srv.call(req).await;
+
+            let status = match &res {
+                Ok(res) => res.status(),
+                Err(err) => err.as_response_error().status_code(),
+            };
+            session_queue
+                .add_pat_usage(&token, &method, &path, status.as_u16())
+                .await;
+
+            res
+        }
+        .boxed_local()
+    }
+}
@@ -0,0 +1,228 @@
+//! Guards for requests to user-supplied URLs, which must only ever reach the public internet
+//! and never internal services such as the database or cloud metadata endpoints
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use thiserror::Error;
+use url::{Host, Url};
+
+/// The most redirects followed when requesting a user-supplied URL
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum PublicUrlError {
+    #[error("Invalid URL")]
+    Invalid,
+    #[error("URLs must use HTTPS")]
+    NotHttps,
+    #[error("Could not resolve {0}")]
+    Unresolved(String),
+    #[error("{0} does not resolve to a public address")]
+    NotPublic(String),
+}
+
+impl PublicUrlError {
+    /// Whether checking the URL again later could succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, PublicUrlError::Unresolved(_))
+    }
+}
+
+fn is_public_ipv4(ip: &Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8, "this network"
+        || a == 0
+        // 100.64.0.0/10, carrier-grade NAT
+        || (a == 100 && (b & 0xc0) == 64)
+        // 192.0.0.0/24, IETF protocol assignments
+        || (a == 192 && b == 0 && c == 0)
+        // 198.18.0.0/15, benchmarking
+        || (a == 198 && (b & 0xfe) == 18)
+        // 240.0.0.0/4, reserved
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: &Ipv6Addr) -> bool {
+    // IPv4-mapped (::ffff:0:0/96) and NAT64 (64:ff9b::/96) addresses reach the embedded
+    // IPv4 address
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(&ipv4);
+    }
+    let segments = ip.segments();
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let octets = ip.octets();
+        return is_public_ipv4(&Ipv4Addr::new(
+            octets[12], octets[13], octets[14], octets[15],
+        ));
+    }
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // fc00::/7, unique local
+        || (segments[0] & 0xfe00) == 0xfc00
+        // fe80::/10, link local
+        || (segments[0] & 0xffc0) == 0xfe80
+        // 2001:db8::/32, documentation
+        || (segments[0] == 0x2001 && segments[1] == 0xdb8))
+}
+
+/// Whether an address is routable on the public internet
+pub fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+async fn resolve_public(host: &str, port: u16) -> Result<Vec<SocketAddr>, PublicUrlError> {
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| PublicUrlError::Unresolved(host.to_string()))?
+        .collect::<Vec<_>>();
+
+    if addrs.is_empty() {
+        return Err(PublicUrlError::Unresolved(host.to_string()));
+    }
+    // A host with any private address is refused outright, so which address the connection
+    // ends up using doesn't matter
+    if !addrs.iter().all(|x| is_public_ip(&x.ip())) {
+        return Err(PublicUrlError::NotPublic(host.to_string()));
+    }
+
+    Ok(addrs)
+}
+
+/// Checks that a URL uses HTTPS and that its host only resolves to public addresses
+pub async fn check_public_url(url: &str) -> Result<Url, PublicUrlError> {
+    let url = Url::parse(url).map_err(|_| PublicUrlError::Invalid)?;
+    if url.scheme() != "https" {
+        return Err(PublicUrlError::NotHttps);
+    }
+
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            resolve_public(domain, url.port_or_known_default().unwrap_or(443)).await?;
+        }
+        Some(Host::Ipv4(ip)) => {
+            if !is_public_ipv4(&ip) {
+                return Err(PublicUrlError::NotPublic(ip.to_string()));
+            }
+        }
+        Some(Host::Ipv6(ip)) => {
+            if !is_public_ipv6(&ip) {
+                return Err(PublicUrlError::NotPublic(ip.to_string()));
+            }
+        }
+        None => return Err(PublicUrlError::Invalid),
+    }
+
+    Ok(url)
+}
+
+/// Resolves hostnames like the system resolver, but refuses hosts with non-public addresses.
+/// This is checked on every connection, so a host can't pass the check when a URL is saved
+/// and resolve to an internal address later.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = resolve_public(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether a redirect target may be followed. Hostnames are checked by the resolver, but
+/// IP literals never reach it.
+fn is_allowed_redirect(url: &Url) -> bool {
+    url.scheme() == "https"
+        && match url.host() {
+            Some(Host::Domain(_)) => true,
+            Some(Host::Ipv4(ip)) => is_public_ipv4(&ip),
+            Some(Host::Ipv6(ip)) => is_public_ipv6(&ip),
+            None => false,
+        }
+}
+
+/// A client for requests to user-supplied URLs, which can only connect to public addresses.
+/// URLs should still be checked with [`check_public_url`] before sending, to refuse IP literals.
+pub fn public_client() -> Result<reqwest::Client, reqwest::Error> {
+    public_client_builder().build()
+}
+
+/// Builds a client like [`public_client`]. Redirect policies set on the builder replace the
+/// default one, and must refuse IP literals themselves
+pub fn public_client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if is_allowed_redirect(attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.error("redirected to a non-public address")
+            }
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(!is_public_ip(&ip.parse().unwrap()), "{} is public", ip);
+        }
+
+        for ip in ["1.1.1.1", "140.82.112.3", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(&ip.parse().unwrap()), "{} is not public", ip);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn ip_literal_urls_are_checked() {
+        assert!(matches!(
+            check_public_url("https://127.0.0.1/hook").await,
+            Err(PublicUrlError::NotPublic(_))
+        ));
+        assert!(matches!(
+            check_public_url("https://[::1]:8000/hook").await,
+            Err(PublicUrlError::NotPublic(_))
+        ));
+        assert!(matches!(
+            check_public_url("http://1.1.1.1/hook").await,
+            Err(PublicUrlError::NotHttps)
+        ));
+        assert!(check_public_url("https://1.1.1.1/hook").await.is_ok());
+    }
+}
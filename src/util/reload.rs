@@ -0,0 +1,96 @@
+use crate::database::models::feature_flag_item::FeatureFlag;
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
+use crate::util::env::set_reloaded;
+use std::collections::HashMap;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Variables which can be changed without a restart, with how their values are validated.
+/// Others are only read at startup, or are not safe to change while running
+const RELOADABLE_VARS: &[(&str, fn(&str) -> bool)] = &[
+    ("RATE_LIMIT_ANONYMOUS", parses::<i64>),
+    ("RATE_LIMIT_AUTHENTICATED", parses::<i64>),
+    ("RATE_LIMIT_APPROVED_APP", parses::<i64>),
+    (
+        "RATE_LIMIT_APPROVED_OAUTH_CLIENTS",
+        parses_json::<Vec<String>>,
+    ),
+    ("WHITELISTED_MODPACK_DOMAINS", parses_json::<Vec<String>>),
+    ("REMOTE_FILE_DOMAINS", parses_json::<Vec<String>>),
+    ("DOWNLOAD_EXCLUDED_ASNS", parses_json::<Vec<u32>>),
+    ("DOWNLOAD_DEDUP_TTL", parses::<u64>),
+    ("NOTIFICATION_GROUPING_WINDOW", parses::<i64>),
+    ("MONETIZATION_MIN_ACCOUNT_AGE_DAYS", parses::<i64>),
+    ("SPAM_REVIEW_THRESHOLD", parses::<i32>),
+    ("SPAM_THROTTLE_THRESHOLD", parses::<i32>),
+    ("SPAM_SIGNUP_BURST_LIMIT", parses::<i64>),
+    ("SPAM_DISPOSABLE_EMAIL_DOMAINS", parses_json::<Vec<String>>),
+    ("STAFF_REPORT_EMAILS", parses_json::<Vec<String>>),
+    ("LARGE_UPLOAD_PROJECTS", parses_json::<Vec<String>>),
+    ("IMPERSONATION_ADMINS", parses_json::<Vec<String>>),
+    ("MAINTENANCE_MODE", parses::<bool>),
+];
+
+#[derive(Error, Debug)]
+pub enum ReloadError {
+    #[error("Error while reading configuration file: {0}")]
+    File(#[from] dotenvy::Error),
+    #[error("Invalid configuration, nothing was reloaded: {0}")]
+    Invalid(String),
+    #[error("Database Error: {0}")]
+    Database(#[from] DatabaseError),
+}
+
+fn parses<T: FromStr>(value: &str) -> bool {
+    value.parse::<T>().is_ok()
+}
+
+fn parses_json<T: serde::de::DeserializeOwned>(value: &str) -> bool {
+    serde_json::from_str::<T>(value).is_ok()
+}
+
+/// Reloads the reloadable variables from the file at `CONFIG_RELOAD_PATH` (`.env` by default),
+/// falling back to the environment for variables the file does not set. Every value is
+/// validated before any is applied, so a bad edit leaves the running configuration as it was.
+/// Feature flags are also read again from the database. Returns the variables which changed.
+pub async fn reload(redis: &RedisPool) -> Result<Vec<&'static str>, ReloadError> {
+    let path = dotenvy::var("CONFIG_RELOAD_PATH").unwrap_or_else(|_| ".env".to_string());
+    let file = dotenvy::from_path_iter(path)?.collect::<Result<HashMap<_, _>, _>>()?;
+
+    let mut values = HashMap::new();
+    let mut invalid = Vec::new();
+    for (name, is_valid) in RELOADABLE_VARS {
+        let Some(value) = file
+            .get(*name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+        else {
+            continue;
+        };
+
+        if is_valid(&value) {
+            values.insert(name.to_string(), value);
+        } else {
+            invalid.push(*name);
+        }
+    }
+
+    if !invalid.is_empty() {
+        return Err(ReloadError::Invalid(format!(
+            "invalid values for {}",
+            invalid.join(", ")
+        )));
+    }
+
+    let changed = RELOADABLE_VARS
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| crate::util::env::var(name).ok().as_ref() != values.get(*name))
+        .collect();
+    set_reloaded(values);
+
+    FeatureFlag::clear_cache(redis).await?;
+
+    Ok(changed)
+}
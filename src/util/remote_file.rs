@@ -0,0 +1,74 @@
+use crate::util::env::parse_strings_from_var;
+use crate::util::public_url::public_client_builder;
+use bytes::BytesMut;
+use reqwest::redirect::Policy;
+use thiserror::Error;
+use url::Url;
+
+/// The most redirects followed while fetching a file
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum RemoteFileError {
+    #[error("Files can only be fetched over HTTPS from {}", allowed_domains().join(", "))]
+    NotAllowed,
+    #[error("The file exceeds the maximum of {0} bytes")]
+    TooLarge(usize),
+    #[error("Error while fetching the file: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+fn allowed_domains() -> Vec<String> {
+    parse_strings_from_var("REMOTE_FILE_DOMAINS").unwrap_or_default()
+}
+
+/// Whether files may be fetched from a URL, which takes HTTPS and a domain in
+/// `REMOTE_FILE_DOMAINS`
+pub fn is_allowed(url: &Url) -> bool {
+    url.scheme() == "https"
+        && url.port().is_none()
+        && url
+            .domain()
+            .map_or(false, |x| allowed_domains().iter().any(|y| y == x))
+}
+
+/// Fetches a file from an allowed URL, up to `max_size` bytes. Redirects are only followed to
+/// allowed URLs, as file hosts such as GitHub redirect downloads to their CDNs. Files are fetched
+/// through this whether users link them or they are imported from another platform
+pub async fn fetch(url: &str, max_size: usize) -> Result<BytesMut, RemoteFileError> {
+    let url = Url::parse(url).map_err(|_| RemoteFileError::NotAllowed)?;
+    if !is_allowed(&url) {
+        return Err(RemoteFileError::NotAllowed);
+    }
+
+    let client = public_client_builder()
+        .user_agent("modrinth/labrinth")
+        .redirect(Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if is_allowed(attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.error("redirected to a URL files can't be fetched from")
+            }
+        }))
+        .build()?;
+
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    if response
+        .content_length()
+        .map_or(false, |x| x > max_size as u64)
+    {
+        return Err(RemoteFileError::TooLarge(max_size));
+    }
+
+    let mut data = BytesMut::new();
+    while let Some(chunk) = response.chunk().await? {
+        data.extend_from_slice(&chunk);
+        if data.len() > max_size {
+            return Err(RemoteFileError::TooLarge(max_size));
+        }
+    }
+
+    Ok(data)
+}
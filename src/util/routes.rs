@@ -26,7 +26,7 @@ pub async fn read_from_payload(
 pub async fn read_from_field(
     field: &mut Field,
     cap: usize,
-    err_msg: &'static str,
+    err_msg: &str,
 ) -> Result<BytesMut, CreateError> {
     let mut bytes = BytesMut::new();
     while let Some(chunk) = field.next().await {
@@ -0,0 +1,98 @@
+use crate::database::models::simple_push_item::SimplePushTarget;
+use crate::models::notifications::{Notification, SimplePushProvider};
+use crate::util::public_url::{check_public_url, PublicUrlError};
+use crate::util::webhook::sign_payload;
+use chrono::Utc;
+use reqwest::StatusCode;
+use serde_json::json;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SimplePushError {
+    #[error("Error while sending push message: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("Invalid push target URL: {0}")]
+    Url(#[from] PublicUrlError),
+    #[error("Error while signing push message: {0}")]
+    Signing(String),
+    #[error("Push target rejected the message with status {0}")]
+    Rejected(StatusCode),
+}
+
+impl SimplePushError {
+    /// Whether sending again later could succeed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SimplePushError::Network(_) => true,
+            SimplePushError::Url(err) => err.is_retryable(),
+            SimplePushError::Signing(_) => false,
+            SimplePushError::Rejected(status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+        }
+    }
+}
+
+/// Sends a notification as a short alert to a user's ntfy topic or generic push endpoint.
+///
+/// Generic pushes are signed like notification webhooks, with the target's token as the
+/// secret, in the `X-Modrinth-Timestamp` and `X-Modrinth-Signature` headers.
+pub async fn send_simple_push(
+    client: &reqwest::Client,
+    target: &SimplePushTarget,
+    notification: &Notification,
+) -> Result<(), SimplePushError> {
+    let url = check_public_url(&target.url).await?;
+    let link = format!(
+        "{}{}",
+        dotenvy::var("SITE_URL").unwrap_or_default(),
+        notification.link
+    );
+
+    let request = match target.provider {
+        // https://docs.ntfy.sh/publish/
+        SimplePushProvider::Ntfy => {
+            let request = client
+                .post(url)
+                .header("Title", &notification.name)
+                .header("Click", &link)
+                .header("Tags", "modrinth")
+                .body(notification.text.clone());
+
+            if let Some(token) = &target.token {
+                request.bearer_auth(token)
+            } else {
+                request
+            }
+        }
+        SimplePushProvider::Generic => {
+            let token = target.token.as_deref().ok_or_else(|| {
+                SimplePushError::Signing("generic push targets need a token".to_string())
+            })?;
+            let body = json!({
+                "title": notification.name,
+                "body": notification.text,
+                "url": link,
+            })
+            .to_string();
+            let timestamp = Utc::now().timestamp();
+            let signature =
+                sign_payload(token, timestamp, &body).map_err(SimplePushError::Signing)?;
+
+            client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header("X-Modrinth-Timestamp", timestamp.to_string())
+                .header("X-Modrinth-Signature", format!("sha256={signature}"))
+                .body(body)
+        }
+    };
+
+    let response = request.send().await?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(SimplePushError::Rejected(response.status()))
+    }
+}
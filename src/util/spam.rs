@@ -0,0 +1,250 @@
+use crate::database::models::ids::UserId;
+use crate::database::models::DatabaseError;
+use crate::models::moderation::{SpamScore, SpamSignalKind};
+use crate::queue::maxmind::MaxMindIndexer;
+use crate::routes::convert_to_ip_v6;
+use crate::util::env::{parse_var, var};
+use actix_web::HttpRequest;
+use chrono::{DateTime, Duration, Utc};
+
+/// Points each signal adds to a score
+const DISPOSABLE_EMAIL_POINTS: i32 = 40;
+const SIGNUP_BURST_POINTS: i32 = 30;
+const MASS_SUBMISSIONS_POINTS: i32 = 30;
+const LINK_DENSITY_POINTS: i32 = 30;
+
+/// The score which puts an account or project in the spam moderation queue, unless
+/// `SPAM_REVIEW_THRESHOLD` is set
+const DEFAULT_REVIEW_THRESHOLD: i32 = 30;
+/// The score at which sign ups and project creation are refused, unless
+/// `SPAM_THROTTLE_THRESHOLD` is set
+const DEFAULT_THROTTLE_THRESHOLD: i32 = 70;
+/// Sign ups from one autonomous system within `SIGNUP_BURST_MINUTES` which count as a burst,
+/// unless `SPAM_SIGNUP_BURST_LIMIT` is set
+const DEFAULT_SIGNUP_BURST_LIMIT: i64 = 10;
+const SIGNUP_BURST_MINUTES: i32 = 60;
+/// Projects created by one account within `MASS_SUBMISSIONS_HOURS` which count as mass
+/// submissions
+const MASS_SUBMISSIONS_LIMIT: i64 = 3;
+const MASS_SUBMISSIONS_HOURS: i64 = 24;
+/// Text with at least this many links, and more than `MAX_LINKS_PER_100_WORDS`, is link spam
+const MIN_DENSE_LINKS: usize = 3;
+const MAX_LINKS_PER_100_WORDS: f64 = 10.0;
+/// Accounts older than this are trusted, and what they submit is not scored
+const NEW_ACCOUNT_DAYS: i64 = 7;
+
+/// What is done with activity given its score
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SpamVerdict {
+    Allow,
+    /// Let through, and put in the spam moderation queue
+    Review,
+    /// Refused
+    Throttle,
+}
+
+pub fn verdict(score: &SpamScore) -> SpamVerdict {
+    let review = parse_var("SPAM_REVIEW_THRESHOLD").unwrap_or(DEFAULT_REVIEW_THRESHOLD);
+    let throttle = parse_var("SPAM_THROTTLE_THRESHOLD").unwrap_or(DEFAULT_THROTTLE_THRESHOLD);
+
+    if score.score >= throttle {
+        SpamVerdict::Throttle
+    } else if score.score >= review {
+        SpamVerdict::Review
+    } else {
+        SpamVerdict::Allow
+    }
+}
+
+/// Gets the autonomous system a request was made from, if the MaxMind database knows it
+pub async fn request_asn(req: &HttpRequest, maxmind: &MaxMindIndexer) -> Option<u32> {
+    let conn_info = req.connection_info().clone();
+    let ip = if parse_var("CLOUDFLARE_INTEGRATION").unwrap_or(false) {
+        req.headers()
+            .get("CF-Connecting-IP")
+            .and_then(|x| x.to_str().ok())
+            .or_else(|| conn_info.peer_addr())
+    } else {
+        conn_info.peer_addr()
+    }?;
+
+    maxmind.query_asn(convert_to_ip_v6(ip).ok()?).await
+}
+
+/// Records the autonomous system an account signed up from, so bursts of sign ups can be seen
+pub async fn record_signup(
+    user_id: UserId,
+    asn: u32,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), DatabaseError> {
+    sqlx::query!(
+        "
+        INSERT INTO account_signups (user_id, asn)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO NOTHING
+        ",
+        user_id as UserId,
+        asn as i64,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Scores an account signing up from `asn` with `email`
+pub async fn score_signup<'a, E>(
+    email: Option<&str>,
+    asn: Option<u32>,
+    exec: E,
+) -> Result<SpamScore, DatabaseError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let mut score = SpamScore::default();
+    score_email(&mut score, email);
+
+    if let Some(asn) = asn {
+        let signups = sqlx::query!(
+            "
+            SELECT COUNT(*) FROM account_signups
+            WHERE asn = $1 AND created > $2
+            ",
+            asn as i64,
+            Utc::now() - Duration::minutes(SIGNUP_BURST_MINUTES as i64),
+        )
+        .fetch_one(exec)
+        .await?
+        .count
+        .unwrap_or(0);
+        score_signup_burst(&mut score, asn, signups);
+    }
+
+    Ok(score)
+}
+
+/// Scores a project a user is creating from its summary and description. The account's own
+/// signals are counted again while it is new, and what older accounts submit is not scored
+pub async fn score_submission<'a, E>(
+    user_id: UserId,
+    email: Option<&str>,
+    user_created: DateTime<Utc>,
+    texts: &[&str],
+    exec: E,
+) -> Result<SpamScore, DatabaseError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres> + Copy,
+{
+    let mut score = SpamScore::default();
+    if Utc::now() - user_created > Duration::days(NEW_ACCOUNT_DAYS) {
+        return Ok(score);
+    }
+
+    score_email(&mut score, email);
+
+    // Counted around the time the account signed up, rather than now
+    let signup = sqlx::query!(
+        "
+        SELECT s.asn, (
+            SELECT COUNT(*) FROM account_signups o
+            WHERE o.asn = s.asn AND o.user_id != s.user_id
+            AND o.created BETWEEN s.created - make_interval(mins => $2) AND s.created
+        ) signups
+        FROM account_signups s
+        WHERE s.user_id = $1
+        ",
+        user_id as UserId,
+        SIGNUP_BURST_MINUTES,
+    )
+    .fetch_optional(exec)
+    .await?;
+    if let Some(signup) = signup {
+        score_signup_burst(&mut score, signup.asn as u32, signup.signups.unwrap_or(0));
+    }
+
+    let projects = sqlx::query!(
+        "
+        SELECT COUNT(DISTINCT m.id) FROM mods m
+        INNER JOIN team_members tm ON tm.team_id = m.team_id AND tm.accepted = TRUE
+        WHERE tm.user_id = $1 AND m.published > $2
+        ",
+        user_id as UserId,
+        Utc::now() - Duration::hours(MASS_SUBMISSIONS_HOURS),
+    )
+    .fetch_one(exec)
+    .await?
+    .count
+    .unwrap_or(0);
+    if projects >= MASS_SUBMISSIONS_LIMIT {
+        score.add(
+            SpamSignalKind::MassSubmissions,
+            MASS_SUBMISSIONS_POINTS,
+            format!(
+                "{} projects created in the last {} hours",
+                projects, MASS_SUBMISSIONS_HOURS
+            ),
+        );
+    }
+
+    let (links, words) = texts.iter().fold((0, 0), |(links, words), text| {
+        let (x, y) = count_links(text);
+        (links + x, words + y)
+    });
+    let density = links as f64 * 100.0 / words.max(1) as f64;
+    if links >= MIN_DENSE_LINKS && density > MAX_LINKS_PER_100_WORDS {
+        score.add(
+            SpamSignalKind::LinkDensity,
+            LINK_DENSITY_POINTS,
+            format!("{} links in {} words", links, words),
+        );
+    }
+
+    Ok(score)
+}
+
+fn score_email(score: &mut SpamScore, email: Option<&str>) {
+    let Some(domain) = email
+        .and_then(|x| x.rsplit_once('@'))
+        .map(|x| x.1.to_lowercase())
+    else {
+        return;
+    };
+
+    let disposable = var("SPAM_DISPOSABLE_EMAIL_DOMAINS")
+        .ok()
+        .and_then(|x| serde_json::from_str::<Vec<String>>(&x).ok())
+        .unwrap_or_default();
+    if disposable
+        .iter()
+        .any(|x| domain == *x || domain.ends_with(&format!(".{}", x)))
+    {
+        score.add(
+            SpamSignalKind::DisposableEmail,
+            DISPOSABLE_EMAIL_POINTS,
+            format!("Email address is from {}", domain),
+        );
+    }
+}
+
+fn score_signup_burst(score: &mut SpamScore, asn: u32, signups: i64) {
+    let limit = parse_var("SPAM_SIGNUP_BURST_LIMIT").unwrap_or(DEFAULT_SIGNUP_BURST_LIMIT);
+
+    if signups >= limit {
+        score.add(
+            SpamSignalKind::SignupBurst,
+            SIGNUP_BURST_POINTS,
+            format!(
+                "{} other accounts signed up from AS{} within {} minutes",
+                signups, asn, SIGNUP_BURST_MINUTES
+            ),
+        );
+    }
+}
+
+/// Counts the links and the words in Markdown text
+fn count_links(text: &str) -> (usize, usize) {
+    let words = text.split_whitespace().count();
+    let links = text.matches("http://").count() + text.matches("https://").count();
+
+    (links, words)
+}
@@ -0,0 +1,355 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Lines per page of a PDF statement
+const PDF_LINES_PER_PAGE: usize = 58;
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementFormat {
+    #[default]
+    Pdf,
+    Csv,
+}
+
+impl StatementFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            StatementFormat::Pdf => "application/pdf",
+            StatementFormat::Csv => "text/csv",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StatementFormat::Pdf => "pdf",
+            StatementFormat::Csv => "csv",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StatementLineKind {
+    /// Revenue earned by a project
+    Revenue,
+    /// A correction to the balance, such as a refunded payout
+    Adjustment,
+    /// Money sent to the user
+    Transfer,
+    Fee,
+    Withholding,
+}
+
+impl StatementLineKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatementLineKind::Revenue => "revenue",
+            StatementLineKind::Adjustment => "adjustment",
+            StatementLineKind::Transfer => "transfer",
+            StatementLineKind::Fee => "fee",
+            StatementLineKind::Withholding => "withholding",
+        }
+    }
+}
+
+/// A single line of a statement. Amounts are signed: positive amounts are credited to the
+/// balance, negative ones taken from it
+#[derive(Clone, Debug)]
+pub struct StatementLine {
+    pub kind: StatementLineKind,
+    pub date: Option<DateTime<Utc>>,
+    /// The project or payout the line refers to
+    pub reference: String,
+    pub description: String,
+    pub amount: Decimal,
+}
+
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub title: String,
+    pub username: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub lines: Vec<StatementLine>,
+}
+
+impl Statement {
+    pub fn total(&self, kind: StatementLineKind) -> Decimal {
+        self.lines
+            .iter()
+            .filter(|x| x.kind == kind)
+            .map(|x| x.amount)
+            .sum()
+    }
+
+    pub fn net(&self) -> Decimal {
+        self.lines.iter().map(|x| x.amount).sum()
+    }
+
+    pub fn render(&self, format: StatementFormat) -> Vec<u8> {
+        match format {
+            StatementFormat::Pdf => render_pdf(&self.text_lines()),
+            StatementFormat::Csv => self.render_csv().into_bytes(),
+        }
+    }
+
+    fn render_csv(&self) -> String {
+        let mut csv = String::from("type,date,reference,description,amount\n");
+        for line in &self.lines {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                line.kind.as_str(),
+                line.date.map(|x| x.to_rfc3339()).unwrap_or_default(),
+                csv_escape(&line.reference),
+                csv_escape(&line.description),
+                line.amount.round_dp(2),
+            ));
+        }
+
+        csv
+    }
+
+    /// Lays the statement out as lines of monospaced text
+    fn text_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            self.title.clone(),
+            String::new(),
+            format!("User: {}", self.username),
+            format!(
+                "Period: {} - {}",
+                self.period_start.format("%Y-%m-%d"),
+                self.period_end.format("%Y-%m-%d")
+            ),
+            String::new(),
+        ];
+
+        let sections = [
+            ("Revenue", &[StatementLineKind::Revenue][..]),
+            ("Adjustments", &[StatementLineKind::Adjustment][..]),
+            (
+                "Payouts",
+                &[
+                    StatementLineKind::Transfer,
+                    StatementLineKind::Fee,
+                    StatementLineKind::Withholding,
+                ][..],
+            ),
+        ];
+
+        for (name, kinds) in sections {
+            let section = self
+                .lines
+                .iter()
+                .filter(|x| kinds.contains(&x.kind))
+                .collect::<Vec<_>>();
+            if section.is_empty() {
+                continue;
+            }
+
+            lines.push(name.to_string());
+            for line in section {
+                let description = format!(
+                    "{} {}",
+                    line.date
+                        .map(|x| x.format("%Y-%m-%d").to_string())
+                        .unwrap_or_default(),
+                    line.description
+                );
+                lines.push(format!(
+                    "  {:<60} {:>14}",
+                    description.trim().chars().take(60).collect::<String>(),
+                    format!("${}", line.amount.round_dp(2))
+                ));
+            }
+            lines.push(String::new());
+        }
+
+        for (name, amount) in [
+            ("Total revenue", self.total(StatementLineKind::Revenue)),
+            (
+                "Total adjustments",
+                self.total(StatementLineKind::Adjustment),
+            ),
+            ("Total fees", self.total(StatementLineKind::Fee)),
+            (
+                "Total withholding",
+                self.total(StatementLineKind::Withholding),
+            ),
+            ("Total transferred", self.total(StatementLineKind::Transfer)),
+            ("Net change in balance", self.net()),
+        ] {
+            lines.push(format!(
+                "{:<62} {:>14}",
+                name,
+                format!("${}", amount.round_dp(2))
+            ));
+        }
+
+        lines
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Escapes text for a PDF string literal. The standard fonts only cover Latin-1 reliably, so
+/// anything outside of ASCII is replaced
+fn pdf_escape(value: &str) -> String {
+    value
+        .chars()
+        .map(|x| match x {
+            '\\' | '(' | ')' => format!("\\{x}"),
+            x if x.is_ascii() && !x.is_ascii_control() => x.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+/// Renders lines of text into a minimal PDF, in a monospaced font so columns line up
+pub fn render_pdf(lines: &[String]) -> Vec<u8> {
+    let pages = lines.chunks(PDF_LINES_PER_PAGE).collect::<Vec<_>>();
+    let pages = if pages.is_empty() {
+        vec![&[][..]]
+    } else {
+        pages
+    };
+
+    // Objects 1-3 are the catalog, page tree and font, then a page and its content per page
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|x| format!("{} 0 R", 4 + x * 2))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_string(),
+    ];
+
+    for (index, page) in pages.iter().enumerate() {
+        let mut content = String::from("BT /F1 9 Tf 12 TL 40 752 Td\n");
+        for line in page.iter() {
+            content.push_str(&format!("({}) Tj T*\n", pdf_escape(line)));
+        }
+        content.push_str("ET");
+
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            5 + index * 2
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            content.len(),
+            content
+        ));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", index + 1, object));
+    }
+
+    let xref = pdf.len();
+    pdf.push_str(&format!(
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    ));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    ));
+
+    pdf.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statement() -> Statement {
+        Statement {
+            title: "Monthly statement".to_string(),
+            username: "creator".to_string(),
+            period_start: DateTime::from_timestamp(1704067200, 0).unwrap(),
+            period_end: DateTime::from_timestamp(1706745600, 0).unwrap(),
+            lines: vec![
+                StatementLine {
+                    kind: StatementLineKind::Revenue,
+                    date: None,
+                    reference: "AANobbMI".to_string(),
+                    description: "Sodium, the \"fast\" one".to_string(),
+                    amount: Decimal::new(1250, 2),
+                },
+                StatementLine {
+                    kind: StatementLineKind::Transfer,
+                    date: None,
+                    reference: "abcdef".to_string(),
+                    description: "Transfer via paypal".to_string(),
+                    amount: Decimal::new(-1000, 2),
+                },
+                StatementLine {
+                    kind: StatementLineKind::Fee,
+                    date: None,
+                    reference: "abcdef".to_string(),
+                    description: "Fee".to_string(),
+                    amount: Decimal::new(-25, 2),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn csv_statement_escapes_fields() {
+        let csv = String::from_utf8(statement().render(StatementFormat::Csv)).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "type,date,reference,description,amount");
+        assert_eq!(
+            lines[1],
+            "revenue,,AANobbMI,\"Sodium, the \"\"fast\"\" one\",12.50"
+        );
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn statement_totals() {
+        let statement = statement();
+        assert_eq!(
+            statement.total(StatementLineKind::Revenue),
+            Decimal::new(1250, 2)
+        );
+        assert_eq!(statement.net(), Decimal::new(225, 2));
+    }
+
+    #[test]
+    fn pdf_has_valid_structure() {
+        let lines = (0..100).map(|x| format!("Line ({x})")).collect::<Vec<_>>();
+        let pdf = String::from_utf8(render_pdf(&lines)).unwrap();
+
+        assert!(pdf.starts_with("%PDF-1.4"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("/Count 2"));
+        assert!(pdf.contains("(Line \\(99\\)) Tj"));
+
+        // The xref table must point at the start of each object
+        let xref = pdf.rfind("xref\n").unwrap();
+        for (index, entry) in pdf[xref..].lines().skip(3).take(7).enumerate() {
+            let offset = entry[..10].parse::<usize>().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj", index + 1)));
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, ImageError};
+use resvg::tiny_skia;
+use resvg::usvg::{self, TreeParsing};
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+
+/// The most elements a sanitized SVG may have, which also bounds the work of rasterizing it
+pub const MAX_SVG_ELEMENTS: usize = 2048;
+
+/// Elements kept by the sanitizer. Anything that can run scripts, load external resources or
+/// embed other documents (`script`, `image`, `foreignObject`, `style`, ...) is left out
+const ALLOWED_ELEMENTS: &[&str] = &[
+    "svg",
+    "g",
+    "defs",
+    "title",
+    "desc",
+    "symbol",
+    "use",
+    "path",
+    "rect",
+    "circle",
+    "ellipse",
+    "line",
+    "polyline",
+    "polygon",
+    "linearGradient",
+    "radialGradient",
+    "stop",
+    "clipPath",
+    "mask",
+    "pattern",
+];
+
+/// Attributes kept by the sanitizer. Event handlers are left out, and `href` is handled
+/// separately so it can only point inside the document
+const ALLOWED_ATTRIBUTES: &[&str] = &[
+    "id",
+    "viewBox",
+    "preserveAspectRatio",
+    "width",
+    "height",
+    "x",
+    "y",
+    "x1",
+    "y1",
+    "x2",
+    "y2",
+    "cx",
+    "cy",
+    "r",
+    "rx",
+    "ry",
+    "fx",
+    "fy",
+    "fr",
+    "d",
+    "points",
+    "pathLength",
+    "transform",
+    "style",
+    "color",
+    "display",
+    "visibility",
+    "opacity",
+    "fill",
+    "fill-opacity",
+    "fill-rule",
+    "stroke",
+    "stroke-width",
+    "stroke-linecap",
+    "stroke-linejoin",
+    "stroke-miterlimit",
+    "stroke-dasharray",
+    "stroke-dashoffset",
+    "stroke-opacity",
+    "clip-path",
+    "clip-rule",
+    "clipPathUnits",
+    "mask",
+    "maskUnits",
+    "maskContentUnits",
+    "offset",
+    "stop-color",
+    "stop-opacity",
+    "gradientUnits",
+    "gradientTransform",
+    "spreadMethod",
+    "patternUnits",
+    "patternContentUnits",
+    "patternTransform",
+];
+
+/// Rebuilds an SVG from an allowlist of elements and attributes. Scripts, event handlers,
+/// `foreignObject`, stylesheets and references to anything outside the document are dropped,
+/// along with DTDs, which the parser refuses
+pub fn sanitize_svg(data: &[u8]) -> Result<String, String> {
+    let text = std::str::from_utf8(data).map_err(|_| "SVGs must be valid UTF-8".to_string())?;
+    let document = roxmltree::Document::parse(text).map_err(|err| format!("Invalid SVG: {err}"))?;
+
+    let root = document.root_element();
+    if root.tag_name().name() != "svg" || root.tag_name().namespace() != Some(SVG_NAMESPACE) {
+        return Err("Invalid SVG: the root element must be an <svg> element".to_string());
+    }
+    if root.descendants().filter(|x| x.is_element()).count() > MAX_SVG_ELEMENTS {
+        return Err(format!(
+            "SVGs must have at most {MAX_SVG_ELEMENTS} elements"
+        ));
+    }
+
+    let mut output = String::with_capacity(text.len());
+    write_element(root, &mut output);
+
+    Ok(output)
+}
+
+fn write_element(node: roxmltree::Node<'_, '_>, output: &mut String) {
+    let name = node.tag_name().name();
+    if node.tag_name().namespace() != Some(SVG_NAMESPACE) || !ALLOWED_ELEMENTS.contains(&name) {
+        return;
+    }
+
+    output.push('<');
+    output.push_str(name);
+    if node.parent_element().is_none() {
+        output.push_str(&format!(" xmlns=\"{SVG_NAMESPACE}\""));
+    }
+
+    for attribute in node.attributes() {
+        let value = attribute.value();
+        let allowed = match (attribute.namespace(), attribute.name()) {
+            (None | Some(XLINK_NAMESPACE), "href") => value.starts_with('#'),
+            (None, name) => ALLOWED_ATTRIBUTES.contains(&name) && is_safe_value(value),
+            _ => false,
+        };
+
+        if allowed {
+            output.push_str(&format!(" {}=\"{}\"", attribute.name(), escape(value)));
+        }
+    }
+    output.push('>');
+
+    for child in node.children() {
+        if child.is_element() {
+            write_element(child, output);
+        } else if child.is_text() {
+            output.push_str(&escape(child.text().unwrap_or_default()));
+        }
+    }
+
+    output.push_str(&format!("</{name}>"));
+}
+
+/// Whether an attribute value only references things inside the document. `url()` is how
+/// styles and paint servers load external resources, so it may only point at fragments
+fn is_safe_value(value: &str) -> bool {
+    let lowercase = value.to_lowercase();
+    if lowercase.contains("javascript:")
+        || lowercase.contains("expression(")
+        || lowercase.contains("@import")
+    {
+        return false;
+    }
+
+    lowercase.split("url(").skip(1).all(|x| {
+        x.trim_start()
+            .trim_start_matches(|c| c == '"' || c == '\'')
+            .starts_with('#')
+    })
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders a sanitized SVG to fit within `max_dimension`
+pub fn rasterize_svg(data: &[u8], max_dimension: u32) -> Result<DynamicImage, ImageError> {
+    let decoding_error = |err: String| {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Name("SVG".to_string()),
+            err,
+        ))
+    };
+
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default())
+        .map_err(|err| decoding_error(err.to_string()))?;
+    let tree = resvg::Tree::from_usvg(&tree);
+
+    let (width, height) = (tree.size.width() as f32, tree.size.height() as f32);
+    let scale = max_dimension as f32 / width.max(height);
+    let mut pixmap = tiny_skia::Pixmap::new(
+        ((width * scale).round() as u32).max(1),
+        ((height * scale).round() as u32).max(1),
+    )
+    .ok_or_else(|| decoding_error("The SVG has no size".to_string()))?;
+    tree.render(
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    let png = pixmap
+        .encode_png()
+        .map_err(|err| decoding_error(err.to_string()))?;
+
+    image::load_from_memory(&png)
+}
+
+/// Whether a stored image is an SVG. Stored SVGs are always sanitized, so they start with the
+/// root element
+pub fn is_svg(data: &[u8]) -> bool {
+    data.starts_with(b"<svg")
+}
@@ -0,0 +1,168 @@
+use crate::util::env::parse_var;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::{Error, HttpMessage, HttpRequest};
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use opentelemetry::global;
+use opentelemetry::trace::TraceError;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, Tracer};
+use opentelemetry_sdk::Resource;
+use std::collections::HashMap;
+use tracing::Span;
+use tracing_actix_web::{DefaultRootSpanBuilder, RequestId, RootSpan, RootSpanBuilder};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// The key of the request ID in the contexts from `current_context`
+const REQUEST_ID_KEY: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Sets up logging, filtered by `RUST_LOG`. Lines are written as JSON if `LOG_FORMAT` is
+/// `json`, with the fields of the spans they were logged in, such as the request ID, user and
+/// route. If `OTEL_EXPORTER_OTLP_ENDPOINT` is set, spans are also exported to it over OTLP.
+/// Existing `log` records are forwarded as span events. Returns whether spans are exported.
+pub fn init() -> bool {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let tracer = match dotenvy::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(endpoint) => match otlp_tracer(endpoint) {
+            Ok(tracer) => Some(tracer),
+            Err(err) => {
+                eprintln!("Failed to set up trace exporting: {}", err);
+                None
+            }
+        },
+        Err(_) => None,
+    };
+
+    let json = dotenvy::var("LOG_FORMAT")
+        .map(|x| x.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+
+    let enabled = tracer.is_some();
+    tracing_subscriber::registry()
+        .with(filter)
+        .with((!json).then(tracing_subscriber::fmt::layer))
+        .with(json.then(|| {
+            tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_span_list(true)
+        }))
+        .with(tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer)))
+        .init();
+
+    enabled
+}
+
+/// Flushes the spans which have not been exported yet
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+fn otlp_tracer(endpoint: String) -> Result<Tracer, TraceError> {
+    let sample_ratio = parse_var("OTEL_TRACES_SAMPLE_RATIO").unwrap_or(0.1);
+    let service_name = dotenvy::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "labrinth".to_string());
+
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            opentelemetry_sdk::trace::config()
+                // Requests from services which are already tracing are always kept if they were
+                // sampled upstream
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                    sample_ratio,
+                ))))
+                .with_resource(Resource::new(vec![KeyValue::new(
+                    "service.name",
+                    service_name,
+                )])),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)
+}
+
+/// The trace context of the current span, as W3C trace context headers, along with the ID of
+/// the request being handled. Stored with work which is picked up later, such as jobs, so its
+/// spans join the trace and request which queued it
+pub fn current_context() -> HashMap<String, String> {
+    let mut carrier = HashMap::new();
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut carrier));
+
+    if let Ok(request_id) = REQUEST_ID.try_with(|x| x.clone()) {
+        carrier.insert(REQUEST_ID_KEY.to_string(), request_id);
+    }
+
+    carrier
+}
+
+/// Makes a span part of the trace a context from `current_context` belongs to. The span must
+/// declare a `request_id` field for the ID of the request to be recorded on it
+pub fn set_parent(span: &Span, carrier: &HashMap<String, String>) {
+    if carrier.is_empty() {
+        return;
+    }
+
+    if let Some(request_id) = carrier.get(REQUEST_ID_KEY) {
+        span.record("request_id", request_id.as_str());
+    }
+
+    let context = global::get_text_map_propagator(|propagator| propagator.extract(carrier));
+    span.set_parent(context);
+}
+
+/// Creates the span of each request, which every line logged while handling it is part of
+pub struct RequestRootSpan;
+
+impl RootSpanBuilder for RequestRootSpan {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        tracing_actix_web::root_span!(request, user_id = tracing::field::Empty)
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, Error>) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
+/// Records the user a request was authenticated as on its span
+pub fn record_user(req: &HttpRequest, user_id: impl std::fmt::Display) {
+    if let Some(span) = req.extensions().get::<RootSpan>() {
+        span.record("user_id", tracing::field::display(user_id));
+    }
+}
+
+/// Makes the ID of the request being handled available to `current_context`. Must be inside
+/// the `TracingLogger` middleware, which assigns the ID
+pub fn with_request_id<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> LocalBoxFuture<'static, Result<ServiceResponse<B>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    let request_id = req.extensions().get::<RequestId>().map(|x| x.to_string());
+
+    let fut = srv.call(req);
+    match request_id {
+        Some(request_id) => REQUEST_ID.scope(request_id, fut).boxed_local(),
+        None => fut.boxed_local(),
+    }
+}
@@ -0,0 +1,141 @@
+use url::Url;
+
+/// Longest tag value kept, anything longer is truncated
+const MAX_TAG_LENGTH: usize = 64;
+
+/// Well known referrer domains, grouped under a single source name
+const KNOWN_SOURCES: &[(&str, &[&str])] = &[
+    ("youtube", &["youtube.com", "youtu.be"]),
+    ("reddit", &["reddit.com", "redd.it"]),
+    ("discord", &["discord.com", "discord.gg", "discordapp.com"]),
+    ("twitter", &["twitter.com", "x.com", "t.co"]),
+    ("github", &["github.com"]),
+    ("curseforge", &["curseforge.com"]),
+    ("planetminecraft", &["planetminecraft.com"]),
+    ("google", &["google.com"]),
+    ("bing", &["bing.com"]),
+    ("duckduckgo", &["duckduckgo.com"]),
+];
+
+/// Where a page view or download came from. All values are sanitized: the referrer is reduced
+/// to its host and tags to short lowercase identifiers, so no personal data from URLs is kept
+#[derive(Default, Clone, Debug, Eq, PartialEq)]
+pub struct TrafficSource {
+    /// Host of the referring page, empty for direct or internal traffic
+    pub referrer: String,
+    /// `utm_source` if given, otherwise derived from the referrer. `direct` if neither is known
+    pub source: String,
+    pub medium: String,
+    pub campaign: String,
+}
+
+/// Reduces a tag to lowercase alphanumerics, `-`, `_` and `.`
+fn sanitize_tag(value: &str) -> String {
+    value
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|x| x.is_ascii_alphanumeric() || matches!(x, '-' | '_' | '.'))
+        .take(MAX_TAG_LENGTH)
+        .collect()
+}
+
+/// Gets the host of a referrer URL, ignoring internal referrers from Modrinth itself
+fn sanitize_referrer(referrer: &str) -> Option<String> {
+    let url = Url::parse(referrer).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+
+    let host = url.host_str()?.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+
+    if host == "modrinth.com" || host.ends_with(".modrinth.com") {
+        return None;
+    }
+
+    Some(host.chars().take(MAX_TAG_LENGTH).collect())
+}
+
+fn known_source(host: &str) -> Option<&'static str> {
+    KNOWN_SOURCES
+        .iter()
+        .find(|(_, domains)| {
+            domains
+                .iter()
+                .any(|x| host == *x || host.ends_with(&format!(".{x}")))
+        })
+        .map(|(name, _)| *name)
+}
+
+/// Works out the traffic source of a request from the campaign tags (`utm_source`,
+/// `utm_medium`, `utm_campaign`) of the requested URL and its referrer
+pub fn traffic_source(url: Option<&Url>, referrer: Option<&str>) -> TrafficSource {
+    let mut source = TrafficSource::default();
+
+    if let Some(url) = url {
+        for (key, value) in url.query_pairs() {
+            match &*key {
+                "utm_source" => source.source = sanitize_tag(&value),
+                "utm_medium" => source.medium = sanitize_tag(&value),
+                "utm_campaign" => source.campaign = sanitize_tag(&value),
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(referrer) = referrer.and_then(sanitize_referrer) {
+        if source.source.is_empty() {
+            source.source = known_source(&referrer)
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| referrer.clone());
+        }
+        source.referrer = referrer;
+    }
+
+    if source.source.is_empty() {
+        source.source = "direct".to_string();
+    }
+
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn campaign_tags_take_priority_over_referrer() {
+        let url = Url::parse(
+            "https://modrinth.com/mod/sodium?utm_source=YouTube&utm_medium=video&utm_campaign=Showcase%202024!",
+        )
+        .unwrap();
+
+        let source = traffic_source(Some(&url), Some("https://www.reddit.com/r/feedthebeast/"));
+        assert_eq!(
+            source,
+            TrafficSource {
+                referrer: "reddit.com".to_string(),
+                source: "youtube".to_string(),
+                medium: "video".to_string(),
+                campaign: "showcase2024".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn referrer_is_reduced_to_known_source() {
+        let source = traffic_source(None, Some("https://m.youtube.com/watch?v=abcdef"));
+        assert_eq!(source.referrer, "m.youtube.com");
+        assert_eq!(source.source, "youtube");
+    }
+
+    #[test]
+    fn internal_and_missing_referrers_are_direct() {
+        let source = traffic_source(None, Some("https://modrinth.com/mods"));
+        assert_eq!(source.referrer, "");
+        assert_eq!(source.source, "direct");
+
+        assert_eq!(traffic_source(None, None).source, "direct");
+    }
+}
@@ -0,0 +1,125 @@
+use crate::database::models::DatabaseError;
+use crate::database::redis::RedisPool;
+use crate::models::uploads::{UploadProgress, UploadStatus};
+use crate::queue::socket::ActiveSockets;
+use actix_web::{web, HttpRequest};
+use chrono::Utc;
+use log::warn;
+use tokio::sync::RwLock;
+
+/// The header uploads are given their ID in, as sent on the upload's socket
+pub const UPLOAD_ID_HEADER: &str = "x-upload-id";
+
+const UPLOAD_PROGRESS_NAMESPACE: &str = "upload_progress";
+/// How long the events of an upload are kept for, in seconds
+const UPLOAD_PROGRESS_EXPIRY: i64 = 60 * 60;
+
+/// Reports the processing of an upload, to the socket following it and to Redis, so it can
+/// still be read when the socket is connected to another instance. Uploads sent without an
+/// ID are not reported
+#[derive(Default)]
+pub struct UploadTracker {
+    upload_id: Option<String>,
+    redis: Option<web::Data<RedisPool>>,
+    sockets: Option<web::Data<RwLock<ActiveSockets>>>,
+}
+
+impl UploadTracker {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let upload_id = req
+            .headers()
+            .get(UPLOAD_ID_HEADER)
+            .and_then(|x| x.to_str().ok())
+            .filter(|x| !x.is_empty() && x.len() <= 64)
+            .map(|x| x.to_string());
+
+        UploadTracker {
+            upload_id,
+            redis: req.app_data::<web::Data<RedisPool>>().cloned(),
+            sockets: req.app_data::<web::Data<RwLock<ActiveSockets>>>().cloned(),
+        }
+    }
+
+    pub async fn file(&self, file_name: &str, status: UploadStatus) {
+        self.update(Some(file_name), status, None).await
+    }
+
+    pub async fn published(&self) {
+        self.update(None, UploadStatus::Published, None).await
+    }
+
+    pub async fn failed(&self, error: &impl std::fmt::Display) {
+        self.update(None, UploadStatus::Failed, Some(error.to_string()))
+            .await
+    }
+
+    /// Reports an event. Failing to report one never fails the upload
+    async fn update(&self, file_name: Option<&str>, status: UploadStatus, error: Option<String>) {
+        let Some(upload_id) = &self.upload_id else {
+            return;
+        };
+        let event = UploadProgress {
+            upload_id: upload_id.clone(),
+            file_name: file_name.map(|x| x.to_string()),
+            status,
+            error,
+            created: Utc::now(),
+        };
+
+        if let Some(redis) = &self.redis {
+            if let Err(err) = record_progress(&event, redis).await {
+                warn!("Recording upload progress failed: {}", err);
+            }
+        }
+
+        if let Some(sockets) = &self.sockets {
+            let sockets = sockets.read().await;
+            let session = sockets
+                .upload_sockets
+                .get(upload_id)
+                .map(|x| x.value().clone());
+            if let Some(mut session) = session {
+                let sent = match serde_json::to_string(&event) {
+                    Ok(text) => session.text(text).await.is_ok(),
+                    Err(_) => false,
+                };
+                if !sent || status.is_final() {
+                    sockets.upload_sockets.remove(upload_id);
+                    let _ = session.close(None).await;
+                }
+            }
+        }
+    }
+}
+
+async fn record_progress(event: &UploadProgress, redis: &RedisPool) -> Result<(), DatabaseError> {
+    let mut redis = redis.connect().await?;
+    let mut events = redis
+        .get_deserialized_from_json::<Vec<UploadProgress>>(
+            UPLOAD_PROGRESS_NAMESPACE,
+            &event.upload_id,
+        )
+        .await?
+        .unwrap_or_default();
+    events.push(event.clone());
+
+    redis
+        .set_serialized_to_json(
+            UPLOAD_PROGRESS_NAMESPACE,
+            &event.upload_id,
+            &events,
+            Some(UPLOAD_PROGRESS_EXPIRY),
+        )
+        .await
+}
+
+/// Gets the events of an upload so far, oldest first
+pub async fn get_progress(
+    upload_id: &str,
+    redis: &RedisPool,
+) -> Result<Option<Vec<UploadProgress>>, DatabaseError> {
+    let mut redis = redis.connect().await?;
+    redis
+        .get_deserialized_from_json(UPLOAD_PROGRESS_NAMESPACE, upload_id)
+        .await
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+/// User agent prefixes of known launchers, lowercase
+const LAUNCHER_AGENTS: &[&str] = &[
+    "modrinth/theseus",
+    "modrinth/app",
+    "prismlauncher",
+    "polymc",
+    "multimc",
+    "atlauncher",
+    "gdlauncher",
+    "feedthebeast",
+    "ftb-app",
+    "technic",
+    "packwiz",
+    "ferium",
+    "mrpack-install",
+];
+
+/// What kind of client a download or page view came from
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Platform {
+    Launcher,
+    Web,
+    Unknown,
+}
+
+impl Platform {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Platform::Launcher => "launcher",
+            Platform::Web => "web",
+            Platform::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OperatingSystem {
+    Windows,
+    MacOs,
+    Linux,
+    Android,
+    Ios,
+    Unknown,
+}
+
+impl OperatingSystem {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperatingSystem::Windows => "windows",
+            OperatingSystem::MacOs => "macos",
+            OperatingSystem::Linux => "linux",
+            OperatingSystem::Android => "android",
+            OperatingSystem::Ios => "ios",
+            OperatingSystem::Unknown => "unknown",
+        }
+    }
+}
+
+/// Classifies a user agent into a coarse platform and operating system. Only these are stored
+/// for analytics breakdowns, not the full user agent
+pub fn classify_user_agent(user_agent: &str) -> (Platform, OperatingSystem) {
+    let user_agent = user_agent.to_lowercase();
+
+    let platform = if LAUNCHER_AGENTS.iter().any(|x| user_agent.starts_with(x)) {
+        Platform::Launcher
+    } else if user_agent.starts_with("mozilla/") {
+        Platform::Web
+    } else {
+        Platform::Unknown
+    };
+
+    // Order matters: Android user agents contain "linux", and iOS ones "mac os x"
+    let os = if user_agent.contains("android") {
+        OperatingSystem::Android
+    } else if user_agent.contains("iphone") || user_agent.contains("ipad") {
+        OperatingSystem::Ios
+    } else if user_agent.contains("windows") {
+        OperatingSystem::Windows
+    } else if user_agent.contains("mac os") || user_agent.contains("macos") {
+        OperatingSystem::MacOs
+    } else if user_agent.contains("linux") {
+        OperatingSystem::Linux
+    } else {
+        OperatingSystem::Unknown
+    };
+
+    (platform, os)
+}
+
+/// A coarse class of a user agent: its product name, platform and operating system, without
+/// any version numbers. Used to tell apart different clients behind the same IP
+pub fn user_agent_class(user_agent: &str) -> String {
+    let (platform, os) = classify_user_agent(user_agent);
+    let product = user_agent
+        .split(['/', ' '])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    format!("{}:{}:{}", product, platform.as_str(), os.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_launcher_user_agent() {
+        assert_eq!(
+            classify_user_agent("PrismLauncher/7.2 (Windows NT 10.0)"),
+            (Platform::Launcher, OperatingSystem::Windows)
+        );
+    }
+
+    #[test]
+    fn classify_browser_user_agents() {
+        assert_eq!(
+            classify_user_agent(
+                "Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36"
+            ),
+            (Platform::Web, OperatingSystem::Android)
+        );
+        assert_eq!(
+            classify_user_agent(
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.1 Safari/605.1.15"
+            ),
+            (Platform::Web, OperatingSystem::MacOs)
+        );
+    }
+
+    #[test]
+    fn classify_unknown_user_agent() {
+        assert_eq!(
+            classify_user_agent("curl/8.4.0"),
+            (Platform::Unknown, OperatingSystem::Unknown)
+        );
+    }
+
+    #[test]
+    fn user_agent_class_ignores_versions() {
+        assert_eq!(
+            user_agent_class("PrismLauncher/7.2 (Windows NT 10.0)"),
+            user_agent_class("PrismLauncher/8.0 (Windows NT 10.0)")
+        );
+        assert_eq!(
+            user_agent_class("PrismLauncher/7.2 (Windows NT 10.0)"),
+            "prismlauncher:launcher:windows"
+        );
+    }
+}
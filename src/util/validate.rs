@@ -3,6 +3,7 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use validator::{ValidationErrors, ValidationErrorsKind};
 
+use crate::models::error::FieldError;
 use crate::models::pats::Scopes;
 
 lazy_static! {
@@ -57,6 +58,38 @@ pub fn validation_errors_to_string(errors: ValidationErrors, adder: Option<Strin
     String::new()
 }
 
+/// Flattens validation errors into one entry per failing rule, with nested fields named by path
+pub fn validation_errors_to_fields(errors: &ValidationErrors) -> Vec<FieldError> {
+    fn collect(errors: &ValidationErrors, prefix: &str, output: &mut Vec<FieldError>) {
+        for (field, kind) in errors.errors().iter().sorted_by_key(|(field, _)| **field) {
+            let path = if prefix.is_empty() {
+                field.to_string()
+            } else {
+                format!("{prefix}.{field}")
+            };
+
+            match kind {
+                ValidationErrorsKind::Struct(errors) => collect(errors, &path, output),
+                ValidationErrorsKind::List(list) => {
+                    for (index, errors) in list {
+                        collect(errors, &format!("{path}[{index}]"), output);
+                    }
+                }
+                ValidationErrorsKind::Field(errors) => {
+                    output.extend(errors.iter().map(|error| FieldError {
+                        field: path.clone(),
+                        code: error.code.to_string(),
+                    }));
+                }
+            }
+        }
+    }
+
+    let mut output = Vec::new();
+    collect(errors, "", &mut output);
+    output
+}
+
 pub fn validate_deps(
     values: &[crate::models::projects::Dependency],
 ) -> Result<(), validator::ValidationError> {
@@ -148,4 +181,44 @@ mod tests {
         let result = validate_name("  ");
         assert!(result.is_err());
     }
+    #[test]
+    fn validation_errors_to_fields_names_nested_paths() {
+        use validator::Validate;
+
+        #[derive(Validate)]
+        struct Item {
+            #[validate(length(min = 3))]
+            title: String,
+        }
+
+        #[derive(Validate)]
+        struct Body {
+            #[validate(length(max = 2))]
+            name: String,
+            #[validate]
+            items: Vec<Item>,
+        }
+
+        let body = Body {
+            name: "abc".to_string(),
+            items: vec![
+                Item {
+                    title: "valid".to_string(),
+                },
+                Item {
+                    title: "x".to_string(),
+                },
+            ],
+        };
+
+        let fields = validation_errors_to_fields(&body.validate().unwrap_err());
+        let fields = fields
+            .iter()
+            .map(|x| (x.field.as_str(), x.code.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![("items[1].title", "length"), ("name", "length")]
+        );
+    }
 }
@@ -1,11 +1,20 @@
 use crate::database::models::legacy_loader_fields::MinecraftGameVersion;
+use crate::database::models::notification_webhook_item::NotificationWebhook;
 use crate::database::redis::RedisPool;
+use crate::models::notifications::Notification;
 use crate::models::projects::ProjectId;
 use crate::routes::ApiError;
+use crate::util::public_url::{check_public_url, public_client, PublicUrlError};
 use chrono::{DateTime, Utc};
+use hex::ToHex;
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::StatusCode;
 use serde::Serialize;
+use serde_json::json;
+use sha2::Sha256;
 use sqlx::PgPool;
 use std::usize;
+use thiserror::Error;
 
 #[derive(Serialize)]
 struct DiscordEmbed {
@@ -267,7 +276,12 @@ pub async fn send_discord_webhook(
             }),
         };
 
-        let client = reqwest::Client::new();
+        let client = public_client().map_err(|_| {
+            ApiError::Discord("Error while building projects webhook client".to_string())
+        })?;
+        check_public_url(&webhook_url)
+            .await
+            .map_err(|err| ApiError::Discord(format!("Invalid projects webhook URL: {err}")))?;
 
         client
             .post(&webhook_url)
@@ -285,6 +299,131 @@ pub async fn send_discord_webhook(
     Ok(())
 }
 
+/// Posts a plain message to a Discord webhook, for announcements to staff channels
+pub async fn send_discord_message(webhook_url: &str, message: String) -> Result<(), ApiError> {
+    let client = public_client().map_err(|_| {
+        ApiError::Discord("Error while building message webhook client".to_string())
+    })?;
+    check_public_url(webhook_url)
+        .await
+        .map_err(|err| ApiError::Discord(format!("Invalid message webhook URL: {err}")))?;
+
+    client
+        .post(webhook_url)
+        .json(&DiscordWebhook {
+            avatar_url: Some("https://cdn.modrinth.com/Modrinth_Dark_Logo.png".to_string()),
+            username: Some("Modrinth".to_string()),
+            embeds: Vec::new(),
+            content: Some(message),
+        })
+        .send()
+        .await
+        .and_then(|x| x.error_for_status())
+        .map_err(|_| ApiError::Discord("Error while sending message webhook".to_string()))?;
+
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum NotificationWebhookError {
+    #[error("Error while sending webhook: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("Error while signing webhook: {0}")]
+    Signing(String),
+    #[error("Webhook rejected the delivery with status {0}")]
+    Rejected(StatusCode),
+    #[error("Invalid webhook URL: {0}")]
+    Url(#[from] PublicUrlError),
+}
+
+impl NotificationWebhookError {
+    /// Whether delivering again later could succeed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            NotificationWebhookError::Network(_) => true,
+            NotificationWebhookError::Signing(_) => false,
+            NotificationWebhookError::Rejected(status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            NotificationWebhookError::Url(err) => err.is_retryable(),
+        }
+    }
+}
+
+/// Builds the webhook payload for a notification. Discord and Slack webhooks get a chat
+/// message, any other URL gets the notification itself as JSON
+fn notification_webhook_payload(url: &str, notification: &Notification) -> serde_json::Value {
+    let link = format!(
+        "{}{}",
+        dotenvy::var("SITE_URL").unwrap_or_default(),
+        notification.link
+    );
+
+    if url.starts_with("https://discord.com/api/webhooks/")
+        || url.starts_with("https://discordapp.com/api/webhooks/")
+    {
+        json!(DiscordWebhook {
+            avatar_url: Some("https://cdn.modrinth.com/Modrinth_Dark_Logo.png".to_string()),
+            username: Some("Modrinth".to_string()),
+            embeds: vec![],
+            content: Some(format!(
+                "**{}**\n{}\n{}",
+                notification.name, notification.text, link
+            )),
+        })
+    } else if url.starts_with("https://hooks.slack.com/") {
+        json!({
+            "text": format!("*{}*\n{}\n<{}>", notification.name, notification.text, link),
+        })
+    } else {
+        json!({
+            "type": notification.body.notification_type(),
+            "notification": notification,
+        })
+    }
+}
+
+/// The hex HMAC-SHA256 of `{timestamp}.{body}`, keyed with a webhook's secret
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> Result<String, String> {
+    let mut mac: Hmac<Sha256> =
+        Hmac::new_from_slice(secret.as_bytes()).map_err(|err| err.to_string())?;
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    Ok(mac.finalize().into_bytes().encode_hex::<String>())
+}
+
+/// Sends a notification to a user's webhook, signed with the webhook's secret.
+///
+/// The `X-Modrinth-Signature` header holds `sha256=` followed by the hex HMAC-SHA256 of
+/// `{timestamp}.{body}`, where the timestamp is sent in `X-Modrinth-Timestamp`.
+pub async fn send_notification_webhook(
+    client: &reqwest::Client,
+    webhook: &NotificationWebhook,
+    notification: &Notification,
+) -> Result<(), NotificationWebhookError> {
+    let body = serde_json::to_string(&notification_webhook_payload(&webhook.url, notification))
+        .unwrap_or_default();
+    let timestamp = Utc::now().timestamp();
+    let signature = sign_payload(&webhook.secret, timestamp, &body)
+        .map_err(NotificationWebhookError::Signing)?;
+
+    let url = check_public_url(&webhook.url).await?;
+
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Modrinth-Timestamp", timestamp.to_string())
+        .header("X-Modrinth-Signature", format!("sha256={signature}"))
+        .body(body)
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(NotificationWebhookError::Rejected(response.status()))
+    }
+}
+
 fn get_gv_range(
     mut game_versions: Vec<MinecraftGameVersion>,
     mut all_game_versions: Vec<MinecraftGameVersion>,
@@ -0,0 +1,149 @@
+use crate::database::models::push_subscription_item::PushSubscription;
+use crate::util::public_url::{check_public_url, PublicUrlError};
+use base64::Engine;
+use chrono::Utc;
+use reqwest::StatusCode;
+use serde::Serialize;
+use thiserror::Error;
+
+/// How long push services should hold on to a message for an offline client, in seconds
+const PUSH_TTL: i64 = 60 * 60 * 24;
+
+/// Hosts of the push services browsers subscribe through: FCM (Chrome, Edge, Opera), Mozilla
+/// autopush (Firefox), Apple (Safari) and WNS (legacy Edge). A leading `.` matches subdomains.
+const PUSH_SERVICE_HOSTS: &[&str] = &[
+    "fcm.googleapis.com",
+    "updates.push.services.mozilla.com",
+    ".push.apple.com",
+    ".notify.windows.com",
+];
+
+#[derive(Error, Debug)]
+pub enum WebPushError {
+    #[error("Environment Error")]
+    Env(#[from] dotenvy::Error),
+    #[error("Error while encrypting push message: {0}")]
+    Encryption(#[from] ece::Error),
+    #[error("Error while signing VAPID token: {0}")]
+    Signing(#[from] jsonwebtoken::errors::Error),
+    #[error("Error while decoding key: {0}")]
+    Decoding(#[from] base64::DecodeError),
+    #[error("Invalid push endpoint: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("Error while sending push message: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("Push endpoint is not a known push service")]
+    UnknownService,
+    #[error("Invalid push endpoint: {0}")]
+    Endpoint(#[from] PublicUrlError),
+    #[error("Push subscription has expired or been revoked")]
+    Expired,
+    #[error("Push service rejected the message with status {0}")]
+    Rejected(StatusCode),
+}
+
+#[derive(Serialize)]
+pub struct WebPushMessage {
+    pub notification_id: String,
+    pub title: String,
+    pub body: String,
+    pub url: String,
+}
+
+#[derive(Serialize)]
+struct VapidClaims {
+    aud: String,
+    exp: i64,
+    sub: String,
+}
+
+/// The server's VAPID public key (an uncompressed P-256 point, base64url encoded), used as the
+/// `applicationServerKey` when browsers subscribe
+pub fn vapid_public_key() -> Option<String> {
+    dotenvy::var("VAPID_PUBLIC_KEY")
+        .ok()
+        .filter(|x| !x.is_empty() && x != "none")
+}
+
+/// Whether an endpoint belongs to a known push service, which are the only hosts messages are
+/// sent to
+pub fn is_push_service(endpoint: &url::Url) -> bool {
+    endpoint.scheme() == "https"
+        && endpoint.port().is_none()
+        && endpoint.domain().map_or(false, |domain| {
+            PUSH_SERVICE_HOSTS.iter().any(|host| {
+                if host.starts_with('.') {
+                    domain.ends_with(host)
+                } else {
+                    domain == *host
+                }
+            })
+        })
+}
+
+fn decode_base64_url(value: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(value.trim_end_matches('='))
+}
+
+fn vapid_authorization(endpoint: &url::Url) -> Result<String, WebPushError> {
+    let public_key = dotenvy::var("VAPID_PUBLIC_KEY")?;
+    // PKCS#8 DER private key, base64 encoded
+    let private_key =
+        base64::engine::general_purpose::STANDARD.decode(dotenvy::var("VAPID_PRIVATE_KEY")?)?;
+
+    let claims = VapidClaims {
+        aud: endpoint.origin().ascii_serialization(),
+        exp: (Utc::now() + chrono::Duration::hours(12)).timestamp(),
+        sub: dotenvy::var("VAPID_SUBJECT")?,
+    };
+
+    let token = jsonwebtoken::encode(
+        &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::ES256),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_ec_der(&private_key),
+    )?;
+
+    Ok(format!("vapid t={token}, k={public_key}"))
+}
+
+/// Encrypts and sends a message to a single push subscription.
+///
+/// Returns [`WebPushError::Expired`] if the push service reports that the subscription no
+/// longer exists, and [`WebPushError::UnknownService`] if the endpoint isn't a known push
+/// service, in which case it should be removed.
+pub async fn send_web_push(
+    client: &reqwest::Client,
+    subscription: &PushSubscription,
+    message: &WebPushMessage,
+) -> Result<(), WebPushError> {
+    let endpoint = url::Url::parse(&subscription.endpoint)?;
+    if !is_push_service(&endpoint) {
+        return Err(WebPushError::UnknownService);
+    }
+    check_public_url(endpoint.as_str()).await?;
+
+    let body = ece::encrypt(
+        &decode_base64_url(&subscription.p256dh)?,
+        &decode_base64_url(&subscription.auth)?,
+        &serde_json::to_vec(message).unwrap_or_default(),
+    )?;
+
+    let response = client
+        .post(endpoint.clone())
+        .header("TTL", PUSH_TTL.to_string())
+        .header("Content-Encoding", "aes128gcm")
+        .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+        .header(
+            reqwest::header::AUTHORIZATION,
+            vapid_authorization(&endpoint)?,
+        )
+        .body(body)
+        .send()
+        .await?;
+
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        StatusCode::NOT_FOUND | StatusCode::GONE => Err(WebPushError::Expired),
+        status => Err(WebPushError::Rejected(status)),
+    }
+}
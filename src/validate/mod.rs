@@ -1,5 +1,7 @@
 use crate::database::models::legacy_loader_fields::MinecraftGameVersion;
-use crate::database::models::loader_fields::VersionField;
+use crate::database::models::loader_fields::{
+    self, LoaderFieldEnumValue, VersionField, VersionFieldValue,
+};
 use crate::database::models::DatabaseError;
 use crate::database::redis::RedisPool;
 use crate::models::pack::PackFormat;
@@ -14,6 +16,7 @@ use crate::validate::quilt::QuiltValidator;
 use crate::validate::resourcepack::{PackValidator, TexturePackValidator};
 use crate::validate::shader::{CanvasShaderValidator, CoreShaderValidator, ShaderValidator};
 use chrono::{DateTime, Utc};
+use itertools::Itertools;
 use std::io::Cursor;
 use thiserror::Error;
 use zip::ZipArchive;
@@ -72,7 +75,7 @@ pub enum SupportedGameVersions {
     PastDate(DateTime<Utc>),
     Range(DateTime<Utc>, DateTime<Utc>),
     #[allow(dead_code)]
-    Custom(Vec<MinecraftGameVersion>),
+    Custom(Vec<LoaderFieldEnumValue>),
 }
 
 pub trait Validator: Sync {
@@ -88,121 +91,144 @@ pub trait Validator: Sync {
 
 static ALWAYS_ALLOWED_EXT: &[&str] = &["zip", "txt"];
 
-static VALIDATORS: &[&dyn Validator] = &[
-    &ModpackValidator,
-    &FabricValidator,
-    &ForgeValidator,
-    &LegacyForgeValidator,
-    &QuiltValidator,
-    &LiteLoaderValidator,
-    &PackValidator,
-    &TexturePackValidator,
-    &PluginYmlValidator,
-    &BungeeCordValidator,
-    &VelocityValidator,
-    &SpongeValidator,
-    &CanvasShaderValidator,
-    &ShaderValidator,
-    &CoreShaderValidator,
-    &DataPackValidator,
-];
+/// The validators for the files of a game. Supporting a new game means registering its
+/// validators here, along with the loader field its versions are stored in
+pub struct GameValidators {
+    /// The slug of the game
+    pub game: &'static str,
+    /// The loader field holding the game versions a version supports, which are checked
+    /// against the game versions each validator supports
+    pub version_field: &'static str,
+    pub validators: &'static [&'static dyn Validator],
+}
+
+static REGISTRY: &[GameValidators] = &[GameValidators {
+    game: "minecraft-java",
+    version_field: MinecraftGameVersion::FIELD_NAME,
+    validators: &[
+        &ModpackValidator,
+        &FabricValidator,
+        &ForgeValidator,
+        &LegacyForgeValidator,
+        &QuiltValidator,
+        &LiteLoaderValidator,
+        &PackValidator,
+        &TexturePackValidator,
+        &PluginYmlValidator,
+        &BungeeCordValidator,
+        &VelocityValidator,
+        &SpongeValidator,
+        &CanvasShaderValidator,
+        &ShaderValidator,
+        &CoreShaderValidator,
+        &DataPackValidator,
+    ],
+}];
+
+/// Finds the validators which apply to a file of a game and project type, for the loaders and
+/// game versions it is uploaded for
+fn get_validators(
+    game: &str,
+    project_type: &str,
+    loaders: &[Loader],
+    version_fields: &[VersionField],
+) -> Vec<&'static dyn Validator> {
+    let Some(registered) = REGISTRY.iter().find(|x| x.game == game) else {
+        return Vec::new();
+    };
+
+    let game_versions = version_fields
+        .iter()
+        .filter(|x| x.field_name == registered.version_field)
+        .flat_map(|x| match &x.value {
+            VersionFieldValue::Enum(_, value) => vec![value.clone()],
+            VersionFieldValue::ArrayEnum(_, values) => values.clone(),
+            _ => Vec::new(),
+        })
+        .collect::<Vec<_>>();
+
+    registered
+        .validators
+        .iter()
+        .copied()
+        .filter(|validator| {
+            validator.get_project_types().contains(&project_type)
+                && loaders
+                    .iter()
+                    .any(|x| validator.get_supported_loaders().contains(&&*x.0))
+                && game_version_supported(&game_versions, validator.get_supported_game_versions())
+        })
+        .collect()
+}
 
 /// The return value is whether this file should be marked as primary or not, based on the analysis of the file
 #[allow(clippy::too_many_arguments)]
 pub async fn validate_file(
     data: bytes::Bytes,
     file_extension: String,
-    loaders: Vec<Loader>,
+    mut loaders: Vec<Loader>,
     file_type: Option<FileType>,
     version_fields: Vec<VersionField>,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     redis: &RedisPool,
 ) -> Result<ValidationResult, ValidationError> {
-    // TODO: This needs to be revisited or removed with v3.
-    // Currently, it checks if the loader is the modpack loader, and extracts the pack data from it.
-    // This (and the funnction that calls this) should be refactored such that
-    // - validators are removed (or altogether reworked)
-    // - if a mrpack is uploaded, the pack data is extracted and usable to extract dependencies automatically
-
-    // TODO: A test needs to be written for this.
-    match loaders {
-        loaders if loaders == vec![Loader("mrpack".to_string())] => {
-            let game_versions = version_fields
-                .into_iter()
-                .find_map(|v| MinecraftGameVersion::try_from_version_field(&v).ok())
-                .unwrap_or_default();
-            let all_game_versions =
-                MinecraftGameVersion::list_transaction(&mut *transaction, redis).await?;
-            validate_minecraft_file(
-                data,
-                file_extension,
-                "modpack".to_string(),
-                loaders,
-                game_versions,
-                all_game_versions,
-                file_type,
-            )
-            .await
+    // The games and project types of a file are those its loaders support
+    let all_loaders = loader_fields::Loader::list(&mut **transaction, redis).await?;
+    let supported = all_loaders
+        .iter()
+        .filter(|x| loaders.iter().any(|y| y.0 == x.loader))
+        .collect::<Vec<_>>();
+    let games = supported
+        .iter()
+        .flat_map(|x| x.supported_games.iter())
+        .unique()
+        .collect::<Vec<_>>();
+    let mut project_types = supported
+        .iter()
+        .flat_map(|x| x.supported_project_types.iter().map(|x| &**x))
+        .unique()
+        .collect::<Vec<_>>();
+
+    if let Some(file_type) = file_type {
+        match file_type {
+            FileType::RequiredResourcePack | FileType::OptionalResourcePack => {
+                project_types = vec!["resourcepack"];
+                loaders = vec![Loader("minecraft".to_string())];
+            }
+            FileType::Unknown => {}
         }
-        _ => Ok(ValidationResult::Pass),
     }
-}
 
-async fn validate_minecraft_file(
-    data: bytes::Bytes,
-    file_extension: String,
-    mut project_type: String,
-    mut loaders: Vec<Loader>,
-    game_versions: Vec<MinecraftGameVersion>,
-    all_game_versions: Vec<MinecraftGameVersion>,
-    file_type: Option<FileType>,
-) -> Result<ValidationResult, ValidationError> {
+    let validators = games
+        .into_iter()
+        .cartesian_product(project_types)
+        .flat_map(|(game, project_type)| {
+            get_validators(game, project_type, &loaders, &version_fields)
+        })
+        .collect::<Vec<_>>();
+
+    if validators.is_empty() {
+        return Ok(ValidationResult::Pass);
+    }
+
     actix_web::web::block(move || {
         let reader = Cursor::new(data);
         let mut zip = ZipArchive::new(reader)?;
 
-        if let Some(file_type) = file_type {
-            match file_type {
-                FileType::RequiredResourcePack | FileType::OptionalResourcePack => {
-                    project_type = "resourcepack".to_string();
-                    loaders = vec![Loader("minecraft".to_string())];
-                }
-                FileType::Unknown => {}
+        for validator in &validators {
+            if validator.get_file_extensions().contains(&&*file_extension) {
+                return validator.validate(&mut zip);
             }
         }
 
-        let mut visited = false;
-        for validator in VALIDATORS {
-            if validator.get_project_types().contains(&&*project_type)
-                && loaders
-                    .iter()
-                    .any(|x| validator.get_supported_loaders().contains(&&*x.0))
-                && game_version_supported(
-                    &game_versions,
-                    &all_game_versions,
-                    validator.get_supported_game_versions(),
-                )
-            {
-                if validator.get_file_extensions().contains(&&*file_extension) {
-                    return validator.validate(&mut zip);
-                } else {
-                    visited = true;
-                }
-            }
-        }
-
-        if visited {
-            if ALWAYS_ALLOWED_EXT.contains(&&*file_extension) {
-                Ok(ValidationResult::Warning(
-                    "File extension is invalid for input file",
-                ))
-            } else {
-                Err(ValidationError::InvalidInput(
-                    format!("File extension {file_extension} is invalid for input file").into(),
-                ))
-            }
+        if ALWAYS_ALLOWED_EXT.contains(&&*file_extension) {
+            Ok(ValidationResult::Warning(
+                "File extension is invalid for input file",
+            ))
         } else {
-            Ok(ValidationResult::Pass)
+            Err(ValidationError::InvalidInput(
+                format!("File extension {file_extension} is invalid for input file").into(),
+            ))
         }
     })
     .await?
@@ -210,30 +236,18 @@ async fn validate_minecraft_file(
 
 // Write tests for this
 fn game_version_supported(
-    game_versions: &[MinecraftGameVersion],
-    all_game_versions: &[MinecraftGameVersion],
+    game_versions: &[LoaderFieldEnumValue],
     supported_game_versions: SupportedGameVersions,
 ) -> bool {
     match supported_game_versions {
         SupportedGameVersions::All => true,
-        SupportedGameVersions::PastDate(date) => game_versions.iter().any(|x| {
-            all_game_versions
-                .iter()
-                .find(|y| y.version == x.version)
-                .map(|x| x.created > date)
-                .unwrap_or(false)
-        }),
-        SupportedGameVersions::Range(before, after) => game_versions.iter().any(|x| {
-            all_game_versions
-                .iter()
-                .find(|y| y.version == x.version)
-                .map(|x| x.created > before && x.created < after)
-                .unwrap_or(false)
-        }),
+        SupportedGameVersions::PastDate(date) => game_versions.iter().any(|x| x.created > date),
+        SupportedGameVersions::Range(before, after) => game_versions
+            .iter()
+            .any(|x| x.created > before && x.created < after),
         SupportedGameVersions::Custom(versions) => {
             let version_ids = versions.iter().map(|gv| gv.id).collect::<Vec<_>>();
-            let game_version_ids: Vec<_> = game_versions.iter().map(|gv| gv.id).collect::<Vec<_>>();
-            version_ids.iter().any(|x| game_version_ids.contains(x))
+            game_versions.iter().any(|x| version_ids.contains(&x.id))
         }
     }
 }
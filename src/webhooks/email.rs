@@ -0,0 +1,118 @@
+use super::{required_header, verify_hmac, TIMESTAMP_TOLERANCE_SECONDS};
+use crate::database::models::email_suppression_item::EmailSuppression;
+use crate::database::models::{User, UserId};
+use crate::database::redis::RedisPool;
+use crate::routes::ApiError;
+use actix_web::http::header::HeaderMap;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+/// Checks a delivery signed following the Standard Webhooks specification, which the email
+/// provider uses: an HMAC of `{id}.{timestamp}.{body}` under a `whsec_` prefixed secret
+pub fn verify(headers: &HeaderMap, body: &str) -> Result<String, ApiError> {
+    let id = required_header(headers, "webhook-id")?;
+    let timestamp = required_header(headers, "webhook-timestamp")?
+        .parse::<i64>()
+        .map_err(|_| ApiError::InvalidInput("Invalid webhook timestamp".to_string()))?;
+    if (Utc::now().timestamp() - timestamp).abs() > TIMESTAMP_TOLERANCE_SECONDS {
+        return Err(ApiError::InvalidInput(
+            "Webhook timestamp is outside of the tolerance".to_string(),
+        ));
+    }
+
+    let secret = dotenvy::var("EMAIL_WEBHOOK_SECRET")?;
+    let secret = STANDARD
+        .decode(secret.strip_prefix("whsec_").unwrap_or(&secret))
+        .map_err(|_| ApiError::InvalidInput("Invalid webhook secret".to_string()))?;
+    let message = format!("{id}.{timestamp}.{body}");
+
+    // Several signatures are sent while the secret is being rotated
+    let verified = required_header(headers, "webhook-signature")?
+        .split(' ')
+        .filter_map(|x| x.strip_prefix("v1,"))
+        .filter_map(|x| STANDARD.decode(x).ok())
+        .any(|signature| verify_hmac(&secret, &message, &signature).is_ok());
+    if !verified {
+        return Err(ApiError::InvalidInput(
+            "Invalid webhook signature".to_string(),
+        ));
+    }
+
+    Ok(id.to_string())
+}
+
+/// Stops sending email to addresses which bounced permanently or marked an email as spam.
+/// Users with these addresses are marked unverified and undeliverable, so they are asked to
+/// change their email, until they verify an address again
+pub async fn process(body: &str, pool: &PgPool, redis: &RedisPool) -> Result<(), ApiError> {
+    #[derive(Deserialize)]
+    struct EmailBounce {
+        #[serde(rename = "type")]
+        pub type_: String,
+    }
+
+    #[derive(Deserialize)]
+    struct EmailEventData {
+        pub to: Vec<String>,
+        pub bounce: Option<EmailBounce>,
+    }
+
+    #[derive(Deserialize)]
+    struct EmailEvent {
+        #[serde(rename = "type")]
+        pub type_: String,
+        pub data: EmailEventData,
+    }
+
+    let event = serde_json::from_str::<EmailEvent>(body)?;
+
+    let reason = match &*event.type_ {
+        // Transient bounces, such as a full inbox, are retried by the provider
+        "email.bounced"
+            if event
+                .data
+                .bounce
+                .as_ref()
+                .map_or(true, |x| x.type_.eq_ignore_ascii_case("permanent")) =>
+        {
+            "bounce"
+        }
+        "email.complained" => "complaint",
+        _ => return Ok(()),
+    };
+    let emails = event
+        .data
+        .to
+        .iter()
+        .map(|x| x.to_lowercase())
+        .collect::<Vec<_>>();
+
+    let mut transaction = pool.begin().await?;
+    EmailSuppression::insert_many(&emails, reason, &mut transaction).await?;
+    let users = sqlx::query!(
+        "
+        UPDATE users
+        SET email_verified = FALSE, email_undeliverable = TRUE
+        WHERE lower(email) = ANY($1) AND NOT email_undeliverable
+        RETURNING id, username
+        ",
+        &emails,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    User::clear_caches(
+        &users
+            .into_iter()
+            .map(|x| (UserId(x.id), Some(x.username)))
+            .collect::<Vec<_>>(),
+        redis,
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use super::{required_header, verify_hmac};
+use crate::database::models::github_link_item::GitHubLink;
+use crate::database::models::job_item::Job;
+use crate::database::models::notification_item::NotificationBuilder;
+use crate::database::models::{ProjectId, UserId};
+use crate::database::redis::RedisPool;
+use crate::models::jobs::JobPayload;
+use crate::models::notifications::NotificationBody;
+use crate::routes::ApiError;
+use actix_web::http::header::HeaderMap;
+use chrono::Utc;
+use futures::TryStreamExt;
+use itertools::Itertools;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+/// Checks the `X-Hub-Signature-256` GitHub signs deliveries with, using the secret set on the
+/// webhook in GitHub
+pub fn verify(headers: &HeaderMap, body: &str) -> Result<String, ApiError> {
+    let delivery_id = required_header(headers, "X-GitHub-Delivery")?;
+    let signature = required_header(headers, "X-Hub-Signature-256")?;
+    let signature = signature
+        .strip_prefix("sha256=")
+        .and_then(|x| hex::decode(x).ok())
+        .ok_or_else(|| ApiError::InvalidInput("Invalid webhook signature".to_string()))?;
+
+    verify_hmac(
+        dotenvy::var("GITHUB_WEBHOOK_SECRET")?.as_bytes(),
+        body,
+        &signature,
+    )?;
+
+    Ok(delivery_id.to_string())
+}
+
+/// Notifies the members of projects linking a repository as their source when a release of
+/// it is published, and imports the release as a draft version into projects syncing the
+/// repository's releases. Other events, such as the ping sent when the webhook is created,
+/// are ignored
+pub async fn process(body: &str, pool: &PgPool, redis: &RedisPool) -> Result<(), ApiError> {
+    #[derive(Deserialize)]
+    struct GitHubRelease {
+        pub id: u64,
+        pub tag_name: String,
+        pub html_url: String,
+        #[serde(default)]
+        pub draft: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct GitHubRepository {
+        pub full_name: String,
+        pub html_url: String,
+    }
+
+    #[derive(Deserialize)]
+    struct GitHubReleaseEvent {
+        pub action: String,
+        pub release: GitHubRelease,
+        pub repository: GitHubRepository,
+    }
+
+    let Ok(event) = serde_json::from_str::<GitHubReleaseEvent>(body) else {
+        return Ok(());
+    };
+    if event.action != "published" || event.release.draft {
+        return Ok(());
+    }
+
+    // Source links are written by hand, so the common variations of the URL are matched
+    let repository_url = event.repository.html_url.to_lowercase();
+    let urls = vec![
+        repository_url.clone(),
+        format!("{repository_url}/"),
+        format!("{repository_url}.git"),
+    ];
+
+    let mut transaction = pool.begin().await?;
+
+    let members = sqlx::query!(
+        "
+        SELECT DISTINCT m.id project_id, tm.user_id
+        FROM mods_links ml
+        INNER JOIN link_platforms lp ON lp.id = ml.joining_platform_id AND lp.name = 'source'
+        INNER JOIN mods m ON m.id = ml.joining_mod_id
+        INNER JOIN team_members tm ON tm.team_id = m.team_id AND tm.accepted
+        WHERE lower(ml.url) = ANY($1)
+        ORDER BY project_id
+        ",
+        &urls
+    )
+    .fetch_many(&mut *transaction)
+    .try_filter_map(|e| async {
+        Ok(e.right()
+            .map(|x| (ProjectId(x.project_id), UserId(x.user_id))))
+    })
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    let members: HashMap<ProjectId, Vec<UserId>> = members.into_iter().into_group_map();
+    for (project_id, members) in members {
+        NotificationBuilder {
+            body: NotificationBody::GitHubRelease {
+                project_id: project_id.into(),
+                tag_name: event.release.tag_name.clone(),
+                url: event.release.html_url.clone(),
+            },
+        }
+        .insert_many(members, &mut transaction, redis)
+        .await?;
+    }
+
+    for link in GitHubLink::get_synced(&event.repository.full_name, &mut *transaction).await? {
+        Job::enqueue(
+            JobPayload::ImportGitHubReleases {
+                project_id: link.project_id.into(),
+                release_id: Some(event.release.id),
+            },
+            Utc::now(),
+            &mut *transaction,
+        )
+        .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
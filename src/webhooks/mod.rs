@@ -0,0 +1,184 @@
+use crate::database::models::inbound_webhook_item::InboundWebhook;
+use crate::database::models::job_item::Job;
+use crate::database::redis::RedisPool;
+use crate::models::jobs::JobPayload;
+use crate::queue::payouts::PayoutsQueue;
+use crate::routes::ApiError;
+use actix_web::http::header::HeaderMap;
+use chrono::Utc;
+use hmac::{Hmac, Mac, NewMac};
+use log::info;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+mod email;
+mod github;
+mod tax;
+
+/// How far the timestamp of a signed delivery may be from now, for sources which sign one
+pub(crate) const TIMESTAMP_TOLERANCE_SECONDS: i64 = 5 * 60;
+
+/// A service webhooks are received from
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WebhookSource {
+    /// Release events of repositories linked as the source of projects
+    GitHub,
+    /// Bounces and complaints from the email provider
+    Email,
+    /// The tax form provider
+    Tax,
+    /// A payout provider, by its name
+    Payout(String),
+}
+
+impl WebhookSource {
+    pub fn as_string(&self) -> String {
+        match self {
+            WebhookSource::GitHub => "github".to_string(),
+            WebhookSource::Email => "email".to_string(),
+            WebhookSource::Tax => "tax".to_string(),
+            WebhookSource::Payout(name) => format!("payout_{name}"),
+        }
+    }
+
+    pub fn from_string(string: &str) -> Option<WebhookSource> {
+        match string {
+            "github" => Some(WebhookSource::GitHub),
+            "email" => Some(WebhookSource::Email),
+            "tax" => Some(WebhookSource::Tax),
+            _ => string
+                .strip_prefix("payout_")
+                .map(|name| WebhookSource::Payout(name.to_string())),
+        }
+    }
+}
+
+/// Verifies a webhook and queues it to be processed by the job workers, so senders are
+/// answered right away and failed processing is retried. Deliveries which were already
+/// received, such as retries or replays, are accepted without being processed again.
+/// Returns whether the delivery was new.
+pub async fn receive(
+    source: WebhookSource,
+    headers: &HeaderMap,
+    body: &str,
+    pool: &PgPool,
+    payouts: &PayoutsQueue,
+) -> Result<bool, ApiError> {
+    let delivery_id = match &source {
+        WebhookSource::GitHub => github::verify(headers, body)?,
+        WebhookSource::Email => email::verify(headers, body)?,
+        WebhookSource::Tax => tax::verify(headers, body)?,
+        WebhookSource::Payout(name) => {
+            payouts
+                .provider_by_name(name)
+                .ok_or(ApiError::NotFound)?
+                .verify_webhook(headers, body)
+                .await?
+        }
+    };
+
+    let mut transaction = pool.begin().await?;
+    let Some(id) =
+        InboundWebhook::insert(&source.as_string(), &delivery_id, body, &mut transaction).await?
+    else {
+        info!(
+            "Ignoring {} webhook {} which was already received",
+            source.as_string(),
+            delivery_id
+        );
+        return Ok(false);
+    };
+    Job::enqueue(
+        JobPayload::ProcessWebhook { webhook_id: id },
+        Utc::now(),
+        &mut *transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(true)
+}
+
+/// Processes a stored webhook. Webhooks are only marked processed once they succeed, so a
+/// webhook is processed again if its job is retried
+pub async fn process(
+    id: i64,
+    pool: &PgPool,
+    redis: &RedisPool,
+    payouts: &PayoutsQueue,
+) -> Result<(), ApiError> {
+    let Some(webhook) = InboundWebhook::get(id, pool).await? else {
+        return Ok(());
+    };
+    if webhook.processed.is_some() {
+        return Ok(());
+    }
+
+    match WebhookSource::from_string(&webhook.source) {
+        Some(WebhookSource::GitHub) => github::process(&webhook.payload, pool, redis).await?,
+        Some(WebhookSource::Email) => email::process(&webhook.payload, pool, redis).await?,
+        Some(WebhookSource::Tax) => tax::process(&webhook.payload, pool).await?,
+        Some(WebhookSource::Payout(name)) => {
+            let provider = payouts
+                .provider_by_name(&name)
+                .ok_or_else(|| ApiError::InvalidInput(format!("unknown payout provider {name}")))?;
+            let events = provider
+                .handle_webhook(&webhook.payload, pool, redis)
+                .await?;
+            payouts.apply_events(events, pool, redis).await?;
+        }
+        None => {
+            return Err(ApiError::InvalidInput(format!(
+                "unknown webhook source {}",
+                webhook.source
+            )))
+        }
+    }
+
+    InboundWebhook::mark_processed(id, pool).await?;
+
+    Ok(())
+}
+
+/// Gets a header a webhook must be sent with
+pub(crate) fn required_header<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str, ApiError> {
+    headers
+        .get(name)
+        .and_then(|x| x.to_str().ok())
+        .ok_or_else(|| ApiError::InvalidInput(format!("missing {name} header")))
+}
+
+/// Identifies deliveries by their body, for sources which do not send an ID
+fn body_hash(body: &str) -> String {
+    hex::encode(Sha256::digest(body.as_bytes()))
+}
+
+/// Checks an HMAC-SHA256 signature of a message, in constant time
+pub(crate) fn verify_hmac(secret: &[u8], message: &str, signature: &[u8]) -> Result<(), ApiError> {
+    let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret)
+        .map_err(|_| ApiError::InvalidInput("error initializing HMAC".to_string()))?;
+    mac.update(message.as_bytes());
+    mac.verify(signature)
+        .map_err(|_| ApiError::InvalidInput("Invalid webhook signature".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webhook_sources_round_trip() {
+        for source in [
+            WebhookSource::GitHub,
+            WebhookSource::Email,
+            WebhookSource::Tax,
+            WebhookSource::Payout("paypal".to_string()),
+        ] {
+            assert_eq!(
+                WebhookSource::from_string(&source.as_string()),
+                Some(source)
+            );
+        }
+        assert_eq!(WebhookSource::from_string("unknown"), None);
+    }
+}
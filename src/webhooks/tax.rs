@@ -0,0 +1,70 @@
+use super::{body_hash, required_header, verify_hmac};
+use crate::database::models::tax_form_item::TaxForm;
+use crate::models::payouts::{TaxFormStatus, TaxFormType};
+use crate::routes::ApiError;
+use actix_web::http::header::HeaderMap;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+/// Checks the hex HMAC of the body the tax form provider signs webhooks with. The provider
+/// sends no delivery ID, so deliveries are identified by their body
+pub fn verify(headers: &HeaderMap, body: &str) -> Result<String, ApiError> {
+    let signature = hex::decode(required_header(headers, "Tax-Webhook-Signature")?)
+        .map_err(|_| ApiError::InvalidInput("Invalid webhook signature".to_string()))?;
+    verify_hmac(
+        dotenvy::var("TAX_WEBHOOK_SECRET")?.as_bytes(),
+        body,
+        &signature,
+    )?;
+
+    Ok(body_hash(body))
+}
+
+/// Records the result of a user filling in their tax form at the provider
+pub async fn process(body: &str, pool: &PgPool) -> Result<(), ApiError> {
+    #[derive(Deserialize)]
+    struct TaxFormResource {
+        pub id: String,
+        pub form_type: Option<String>,
+        #[serde(default, with = "rust_decimal::serde::float_option")]
+        pub withholding_rate: Option<Decimal>,
+    }
+
+    #[derive(Deserialize)]
+    struct TaxWebhook {
+        pub event: String,
+        pub form: TaxFormResource,
+    }
+
+    let webhook = serde_json::from_str::<TaxWebhook>(body)?;
+
+    let status = match &*webhook.event {
+        "form.completed" => TaxFormStatus::Complete,
+        "form.rejected" => TaxFormStatus::Rejected,
+        _ => return Ok(()),
+    };
+
+    let form_type = webhook.form.form_type.map(|x| TaxFormType::from_string(&x));
+    // Without a treaty rate from the provider, foreign persons are withheld the statutory 30%
+    let withholding_rate = match form_type {
+        Some(TaxFormType::W9) => Decimal::ZERO,
+        _ => webhook
+            .form
+            .withholding_rate
+            .unwrap_or(Decimal::from(3) / Decimal::from(10)),
+    };
+
+    let mut transaction = pool.begin().await?;
+    TaxForm::update_status(
+        &webhook.form.id,
+        form_type,
+        status,
+        withholding_rate,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
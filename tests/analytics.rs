@@ -1,4 +1,6 @@
+use actix_http::StatusCode;
 use chrono::{DateTime, Duration, Utc};
+use common::dummy_data::TestFile;
 use common::permissions::PermissionsTest;
 use common::permissions::PermissionsTestContext;
 use common::{
@@ -7,11 +9,17 @@ use common::{
     environment::{with_test_environment, TestEnvironment},
 };
 use itertools::Itertools;
+use labrinth::models::analytics::Download;
 use labrinth::models::ids::base62_impl::parse_base62;
+use labrinth::models::ids::VersionId;
+use labrinth::models::jobs::UserJob;
 use labrinth::models::teams::ProjectPermissions;
 use labrinth::queue::payouts;
+use labrinth::routes::v3::analytics_get::{RevenueSummary, VersionAdoption};
 use rust_decimal::{prelude::ToPrimitive, Decimal};
 
+use crate::common::api_common::ApiTeams;
+
 mod common;
 
 #[actix_rt::test]
@@ -221,3 +229,327 @@ pub async fn permissions_analytics_revenue() {
     })
     .await;
 }
+
+#[actix_rt::test]
+pub async fn playtime_sessions_are_ingested_anonymously() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = test_env.dummy.project_alpha.project_id.clone();
+        let alpha_version_id = test_env.dummy.project_alpha.version_id.clone();
+
+        // Ingestion does not require authentication
+        let resp = api
+            .ingest_playtime_sessions(
+                "f0b3c2a1-launcher-instance",
+                serde_json::json!([{
+                    "version_id": alpha_version_id,
+                    "seconds": 1800,
+                    "loader": "fabric",
+                    "game_version": "1.20.1",
+                }]),
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // Instance IDs must be long enough to not collide between launchers
+        let resp = api
+            .ingest_playtime_sessions("short", serde_json::json!([]))
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        // Aggregates are only visible to project members
+        let resp = api
+            .get_analytics_playtime_sessions(vec![&alpha_project_id], USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+
+        let resp = api
+            .get_analytics_playtime_sessions(vec![&alpha_project_id], ENEMY_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let value: serde_json::Value = actix_web::test::read_body_json(resp).await;
+        assert_eq!(value.as_object().unwrap().len(), 0);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn analytics_breakdown_dimensions() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = test_env.dummy.project_alpha.project_id.clone();
+
+        for kind in ["downloads", "views"] {
+            for dimension in [
+                "country",
+                "continent",
+                "platform",
+                "os",
+                "referrer",
+                "source",
+                "medium",
+                "campaign",
+            ] {
+                let resp = api
+                    .get_analytics_breakdown(
+                        vec![&alpha_project_id],
+                        kind,
+                        dimension,
+                        USER_USER_PAT,
+                    )
+                    .await;
+                assert_status!(&resp, StatusCode::OK);
+            }
+
+            // Only the known dimensions can be queried
+            let resp = api
+                .get_analytics_breakdown(vec![&alpha_project_id], kind, "ip", USER_USER_PAT)
+                .await;
+            assert_status!(&resp, StatusCode::BAD_REQUEST);
+        }
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn analytics_revenue_summary() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = test_env.dummy.project_alpha.project_id.clone();
+        let zeta_organization_id = test_env.dummy.organization_zeta.organization_id.clone();
+        let project_id = parse_base62(&alpha_project_id).unwrap() as i64;
+
+        let mut transaction = test_env.db.pool.begin().await.unwrap();
+        payouts::insert_payouts(
+            vec![USER_USER_ID_PARSED; 3],
+            vec![project_id; 3],
+            vec![Decimal::from(10), Decimal::from(5), Decimal::from(100)],
+            vec![
+                Utc::now() - Duration::hours(1),
+                Utc::now() - Duration::days(2),
+                Utc::now() - Duration::days(60),
+            ],
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        let resp = api.get_analytics_revenue_summary(None, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let summary: RevenueSummary = actix_web::test::read_body_json(resp).await;
+
+        // Only the revenue in the last two weeks is bucketed
+        assert_eq!(summary.total, Decimal::from(15));
+        assert_eq!(summary.by_day.len(), 2);
+        assert_eq!(summary.by_project[&alpha_project_id], Decimal::from(15));
+
+        // The ledger covers all revenue. The balance was not updated by the raw insert above,
+        // so it shows up as a discrepancy
+        let ledger = summary.ledger.unwrap();
+        assert_eq!(ledger.earned, Decimal::from(115));
+        assert_eq!(ledger.discrepancy, Decimal::from(115));
+
+        // Organizations have no ledger, and their revenue is only visible to members
+        let resp = api
+            .get_analytics_revenue_summary(Some(&zeta_organization_id), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let summary: RevenueSummary = actix_web::test::read_body_json(resp).await;
+        assert!(summary.ledger.is_none());
+
+        let resp = api
+            .get_analytics_revenue_summary(Some(&zeta_organization_id), ENEMY_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        // Members need permission to view analytics
+        let zeta_team_id = &test_env.dummy.organization_zeta.team_id;
+        let resp = api
+            .add_user_to_team(
+                zeta_team_id,
+                FRIEND_USER_ID,
+                Some(ProjectPermissions::EDIT_DETAILS),
+                None,
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.join_team(zeta_team_id, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api
+            .get_analytics_revenue_summary(Some(&zeta_organization_id), FRIEND_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn analytics_project_export() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = test_env.dummy.project_alpha.project_id.clone();
+
+        let resp = api
+            .export_project_analytics(&alpha_project_id, "csv", "30d", USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        assert_eq!(
+            resp.headers()
+                .get("content-type")
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "text/csv"
+        );
+        let body = actix_web::test::read_body(resp).await;
+        assert!(String::from_utf8_lossy(&body).starts_with("\"day\",\"version_id\""));
+
+        let resp = api
+            .export_project_analytics(&alpha_project_id, "csv", "forever", USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        let resp = api
+            .export_project_analytics(&alpha_project_id, "xlsx", "all", USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        // Projects without analytics access are hidden
+        let resp = api
+            .export_project_analytics(&alpha_project_id, "parquet", "all", ENEMY_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn analytics_project_export_job() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = test_env.dummy.project_alpha.project_id.clone();
+
+        let resp = api
+            .start_project_analytics_export(&alpha_project_id, "csv", "all", USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::ACCEPTED);
+        let job: UserJob = actix_web::test::read_body_json(resp).await;
+        assert_eq!(job.kind, "export_project_analytics");
+
+        let resp = api.get_job(job.id, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let polled: UserJob = actix_web::test::read_body_json(resp).await;
+        assert_eq!(polled.id, job.id);
+
+        // Jobs are only visible to the user who started them
+        let resp = api.get_job(job.id, ENEMY_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+
+        let resp = api
+            .start_project_analytics_export(&alpha_project_id, "csv", "all", ENEMY_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn analytics_version_adoption() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = test_env.dummy.project_alpha.project_id.clone();
+        let alpha_project_slug = test_env.dummy.project_alpha.project_slug.clone();
+        let alpha_project_id_parsed = test_env.dummy.project_alpha.project_id_parsed;
+        let alpha_version_id = test_env.dummy.project_alpha.version_id.clone();
+
+        // The alpha version supports 1.20.1, the new one 1.20.1 and 1.20.2
+        let new_version = api
+            .add_public_version_deserialized(
+                alpha_project_id_parsed,
+                "2.0.0",
+                TestFile::build_random_jar(),
+                None,
+                Some(
+                    serde_json::from_value(serde_json::json!([{
+                        "op": "replace",
+                        "path": "/game_versions",
+                        "value": ["1.20.1", "1.20.2"]
+                    }]))
+                    .unwrap(),
+                ),
+                USER_USER_PAT,
+            )
+            .await;
+        let new_version_id = new_version.id.to_string();
+
+        // Three downloads of the alpha version and one of the new version, all in the same
+        // interval
+        let clickhouse = labrinth::clickhouse::init_client().await.unwrap();
+        let recorded = Utc::now().timestamp_nanos_opt().unwrap() / 100_000;
+        let mut downloads = clickhouse.insert("downloads").unwrap();
+        for version_id in [
+            &alpha_version_id,
+            &alpha_version_id,
+            &alpha_version_id,
+            &new_version_id,
+        ] {
+            downloads
+                .write(&Download {
+                    recorded,
+                    domain: "cdn.modrinth.com".to_string(),
+                    site_path: "/data/mod.jar".to_string(),
+                    user_id: 0,
+                    project_id: parse_base62(&alpha_project_id).unwrap(),
+                    version_id: parse_base62(version_id).unwrap(),
+                    country: "US".to_string(),
+                    continent: "NA".to_string(),
+                    platform: String::new(),
+                    os: String::new(),
+                    referrer: String::new(),
+                    source: String::new(),
+                    medium: String::new(),
+                    campaign: String::new(),
+                    headers: Vec::new(),
+                })
+                .await
+                .unwrap();
+        }
+        downloads.end().await.unwrap();
+
+        for id_or_slug in [&alpha_project_id, &alpha_project_slug] {
+            let resp = api
+                .get_analytics_version_adoption(id_or_slug, USER_USER_PAT)
+                .await;
+            assert_status!(&resp, StatusCode::OK);
+            let adoption: VersionAdoption = actix_web::test::read_body_json(resp).await;
+
+            assert_eq!(adoption.versions.len(), 1);
+            let versions = adoption.versions.values().next().unwrap();
+            assert_eq!(versions.len(), 2);
+            let alpha = &versions[&VersionId(parse_base62(&alpha_version_id).unwrap())];
+            assert_eq!(alpha.downloads, 3);
+            assert!((alpha.share - 0.75).abs() < 0.0001);
+            let new = &versions[&new_version.id];
+            assert_eq!(new.downloads, 1);
+            assert!((new.share - 0.25).abs() < 0.0001);
+
+            // Versions count towards every game version they support
+            assert_eq!(adoption.game_versions.len(), 1);
+            let game_versions = adoption.game_versions.values().next().unwrap();
+            assert_eq!(game_versions.len(), 2);
+            assert_eq!(game_versions["1.20.1"].downloads, 4);
+            assert!((game_versions["1.20.1"].share - 1.0).abs() < 0.0001);
+            assert_eq!(game_versions["1.20.2"].downloads, 1);
+            assert!((game_versions["1.20.2"].share - 0.25).abs() < 0.0001);
+        }
+
+        let resp = api
+            .get_analytics_version_adoption(&alpha_project_id, ENEMY_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
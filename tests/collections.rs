@@ -0,0 +1,284 @@
+use actix_http::StatusCode;
+use actix_web::test;
+use common::api_v3::ApiV3;
+use common::database::*;
+use common::environment::{with_test_environment, TestEnvironment};
+use labrinth::models::collections::{Collection, CollectionPermissions, CollectionSnapshot};
+use labrinth::models::notifications::NotificationBody;
+use serde_json::json;
+
+mod common;
+
+#[actix_rt::test]
+async fn collection_editors_need_to_accept_and_are_limited_by_permissions() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id: &str = &test_env.dummy.project_alpha.project_id;
+
+        let resp = api
+            .create_collection(
+                "Best QoL mods",
+                "Mods that make life easier",
+                &[],
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let collection: Collection = test::read_body_json(resp).await;
+        let id = collection.id.to_string();
+
+        // Only the owner and editors who can manage editors can invite others
+        let resp = api
+            .add_collection_editor(
+                &id,
+                FRIEND_USER_ID,
+                CollectionPermissions::EDIT_PROJECTS.bits(),
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+        let resp = api
+            .add_collection_editor(
+                &id,
+                FRIEND_USER_ID,
+                CollectionPermissions::EDIT_PROJECTS.bits(),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // A pending invite doesn't let the user edit the collection yet
+        let patch = json!({ "new_projects": [alpha_project_id] });
+        let resp = api
+            .edit_collection(&id, patch.clone(), FRIEND_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+        let collection = api.get_collection_deserialized(&id, USER_USER_PAT).await;
+        assert!(collection.editors.is_empty());
+
+        let resp = api.join_collection(&id, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.edit_collection(&id, patch, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let collection = api.get_collection_deserialized(&id, USER_USER_PAT).await;
+        assert_eq!(collection.projects.len(), 1);
+        assert_eq!(collection.editors.len(), 1);
+        assert_eq!(
+            collection.editors[0].user_id.0,
+            FRIEND_USER_ID_PARSED as u64
+        );
+
+        // The editor wasn't given permission to edit the details, or to delete the collection
+        let resp = api
+            .edit_collection(&id, json!({ "name": "Renamed" }), FRIEND_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api.delete_collection(&id, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        // Editors can leave on their own
+        let resp = api
+            .remove_collection_editor(&id, FRIEND_USER_ID, FRIEND_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let collection = api.get_collection_deserialized(&id, USER_USER_PAT).await;
+        assert!(collection.editors.is_empty());
+    })
+    .await;
+}
+
+#[actix_rt::test]
+async fn collection_followers_are_notified_of_project_changes() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id: &str = &test_env.dummy.project_alpha.project_id;
+
+        let resp = api
+            .create_collection(
+                "Best QoL mods",
+                "Mods that make life easier",
+                &[],
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let collection: Collection = test::read_body_json(resp).await;
+        let id = collection.id.to_string();
+
+        let resp = api.follow_collection(&id, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.follow_collection(&id, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        let collection = api.get_collection_deserialized(&id, USER_USER_PAT).await;
+        assert_eq!(collection.followers, 1);
+
+        let resp = api
+            .edit_collection(
+                &id,
+                json!({ "new_projects": [alpha_project_id] }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let page = api
+            .get_user_notifications_page_deserialized(FRIEND_USER_ID, &[], FRIEND_USER_PAT)
+            .await;
+        assert_eq!(page.notifications.len(), 1);
+        match &page.notifications[0].body {
+            NotificationBody::CollectionUpdate {
+                added_projects,
+                removed_projects,
+                ..
+            } => {
+                assert_eq!(added_projects.len(), 1);
+                assert!(removed_projects.is_empty());
+            }
+            _ => panic!("Expected a collection update notification"),
+        }
+
+        let resp = api.unfollow_collection(&id, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let collection = api.get_collection_deserialized(&id, USER_USER_PAT).await;
+        assert_eq!(collection.followers, 0);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+async fn collection_items_can_be_annotated_and_reordered() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id: &str = &test_env.dummy.project_alpha.project_id;
+        let beta_project_id: &str = &test_env.dummy.project_beta.project_id;
+
+        let resp = api
+            .create_collection(
+                "Best QoL mods",
+                "Mods that make life easier",
+                &[alpha_project_id, beta_project_id],
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let collection: Collection = test::read_body_json(resp).await;
+        let id = collection.id.to_string();
+
+        // The new order has to contain every project exactly once
+        let resp = api
+            .reorder_collection(&id, &[beta_project_id, beta_project_id], USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        let resp = api
+            .reorder_collection(&id, &[beta_project_id, alpha_project_id], USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api
+            .edit_collection_item(
+                &id,
+                alpha_project_id,
+                json!({ "note": "Turn off the tooltips in the config" }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let collection = api.get_collection_deserialized(&id, USER_USER_PAT).await;
+        let projects = collection
+            .projects
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(projects, vec![beta_project_id, alpha_project_id]);
+        assert_eq!(collection.items[0].note, None);
+        assert_eq!(
+            collection.items[1].note.as_deref(),
+            Some("Turn off the tooltips in the config")
+        );
+
+        // Replacing the projects keeps the notes of those that stay
+        let resp = api
+            .edit_collection(
+                &id,
+                json!({ "new_projects": [alpha_project_id] }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let collection = api.get_collection_deserialized(&id, USER_USER_PAT).await;
+        assert_eq!(collection.items.len(), 1);
+        assert!(collection.items[0].note.is_some());
+    })
+    .await;
+}
+
+#[actix_rt::test]
+async fn collection_snapshots_pin_versions() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id: &str = &test_env.dummy.project_alpha.project_id;
+        let alpha_version_id = &test_env.dummy.project_alpha.version_id;
+        let beta_project_id: &str = &test_env.dummy.project_beta.project_id;
+        let beta_version_id = &test_env.dummy.project_beta.version_id;
+
+        let resp = api
+            .create_collection(
+                "Best QoL mods",
+                "Mods that make life easier",
+                &[alpha_project_id, beta_project_id],
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let collection: Collection = test::read_body_json(resp).await;
+        let id = collection.id.to_string();
+
+        // Only the project's own versions can be pinned
+        let resp = api
+            .create_collection_snapshot(
+                &id,
+                json!({ "versions": { alpha_project_id: beta_version_id } }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        // Beta has no listed versions, so it's only included when pinned explicitly
+        let resp = api
+            .create_collection_snapshot(&id, json!({ "name": "1.0" }), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let snapshot: CollectionSnapshot = test::read_body_json(resp).await;
+        assert_eq!(snapshot.versions.len(), 1);
+        assert_eq!(
+            &snapshot.versions[0].version_id.to_string(),
+            alpha_version_id
+        );
+
+        let resp = api
+            .create_collection_snapshot(
+                &id,
+                json!({ "versions": { beta_project_id: beta_version_id } }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let snapshot: CollectionSnapshot = test::read_body_json(resp).await;
+
+        let snapshot = api
+            .get_collection_snapshot_deserialized(&id, &snapshot.id.to_string(), USER_USER_PAT)
+            .await;
+        let pinned = snapshot
+            .versions
+            .iter()
+            .map(|x| x.version_id.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pinned,
+            vec![alpha_version_id.clone(), beta_version_id.clone()]
+        );
+    })
+    .await;
+}
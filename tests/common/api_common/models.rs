@@ -227,6 +227,7 @@ pub struct CommonUser {
     pub auth_providers: Option<Vec<AuthProvider>>,
     pub email: Option<String>,
     pub email_verified: Option<bool>,
+    pub email_undeliverable: Option<bool>,
     pub has_password: Option<bool>,
     pub has_totp: Option<bool>,
     pub payout_data: Option<UserPayoutData>,
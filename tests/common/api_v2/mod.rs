@@ -6,6 +6,7 @@ use super::{
 };
 use actix_web::{dev::ServiceResponse, test, App};
 use async_trait::async_trait;
+use labrinth::util::body_limit::BodyLimits;
 use labrinth::LabrinthConfig;
 use std::rc::Rc;
 
@@ -24,7 +25,9 @@ pub struct ApiV2 {
 #[async_trait(?Send)]
 impl ApiBuildable for ApiV2 {
     async fn build(labrinth_config: LabrinthConfig) -> Self {
-        let app = App::new().configure(|cfg| labrinth::app_config(cfg, labrinth_config.clone()));
+        let app = App::new()
+            .wrap(BodyLimits)
+            .configure(|cfg| labrinth::app_config(cfg, labrinth_config.clone()));
         let test_app: Rc<dyn LocalService> = Rc::new(test::init_service(app).await);
 
         Self { test_app }
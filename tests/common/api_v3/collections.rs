@@ -4,7 +4,11 @@ use actix_web::{
     test::{self, TestRequest},
 };
 use bytes::Bytes;
-use labrinth::models::{collections::Collection, v3::projects::Project};
+use labrinth::models::{
+    collections::{Collection, CollectionSnapshot},
+    v3::projects::Project,
+};
+use labrinth::search::collections::CollectionSearchResults;
 use serde_json::json;
 
 use crate::{
@@ -156,4 +160,134 @@ impl ApiV3 {
         let value = serde_json::to_value(projects).unwrap();
         serde_json::from_value(value).unwrap()
     }
+
+    pub async fn add_collection_editor(
+        &self,
+        id: &str,
+        user_id: &str,
+        permissions: u64,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/collection/{id}/editors"))
+            .append_pat(pat)
+            .set_json(json!({
+                "user_id": user_id,
+                "permissions": permissions,
+            }))
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn join_collection(&self, id: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/collection/{id}/editors/join"))
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn remove_collection_editor(
+        &self,
+        id: &str,
+        user_id: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/v3/collection/{id}/editors/{user_id}"))
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn follow_collection(&self, id: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/collection/{id}/follow"))
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn unfollow_collection(&self, id: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/v3/collection/{id}/follow"))
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn search_collections_deserialized(
+        &self,
+        query: &str,
+        pat: Option<&str>,
+    ) -> CollectionSearchResults {
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/v3/search/collections?query={}",
+                urlencoding::encode(query)
+            ))
+            .append_pat(pat)
+            .to_request();
+        let resp = self.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        test::read_body_json(resp).await
+    }
+
+    pub async fn reorder_collection(
+        &self,
+        id: &str,
+        projects: &[&str],
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/collection/{id}/projects/order"))
+            .append_pat(pat)
+            .set_json(json!({ "projects": projects }))
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn edit_collection_item(
+        &self,
+        id: &str,
+        project_id: &str,
+        patch: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::patch()
+            .uri(&format!("/v3/collection/{id}/projects/{project_id}"))
+            .append_pat(pat)
+            .set_json(patch)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn create_collection_snapshot(
+        &self,
+        id: &str,
+        body: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/collection/{id}/snapshots"))
+            .append_pat(pat)
+            .set_json(body)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn get_collection_snapshot_deserialized(
+        &self,
+        id: &str,
+        snapshot_id: &str,
+        pat: Option<&str>,
+    ) -> CollectionSnapshot {
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/collection/{id}/snapshots/{snapshot_id}"))
+            .append_pat(pat)
+            .to_request();
+        let resp = self.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        test::read_body_json(resp).await
+    }
 }
@@ -6,10 +6,12 @@ use super::{
 };
 use actix_web::{dev::ServiceResponse, test, App};
 use async_trait::async_trait;
+use labrinth::util::body_limit::BodyLimits;
 use labrinth::LabrinthConfig;
 use std::rc::Rc;
 
 pub mod collections;
+pub mod notifications;
 pub mod oauth;
 pub mod oauth_clients;
 pub mod organization;
@@ -19,6 +21,7 @@ pub mod tags;
 pub mod team;
 pub mod user;
 pub mod version;
+pub mod webhooks;
 
 #[derive(Clone)]
 pub struct ApiV3 {
@@ -28,7 +31,9 @@ pub struct ApiV3 {
 #[async_trait(?Send)]
 impl ApiBuildable for ApiV3 {
     async fn build(labrinth_config: LabrinthConfig) -> Self {
-        let app = App::new().configure(|cfg| labrinth::app_config(cfg, labrinth_config.clone()));
+        let app = App::new()
+            .wrap(BodyLimits)
+            .configure(|cfg| labrinth::app_config(cfg, labrinth_config.clone()));
         let test_app: Rc<dyn LocalService> = Rc::new(test::init_service(app).await);
 
         Self { test_app }
@@ -52,3 +57,13 @@ impl Api for ApiV3 {
         self.call(req).await
     }
 }
+
+/// Gets the URI of the next page from the `Link` header of a paginated list
+pub fn next_page_uri(resp: &ServiceResponse) -> Option<String> {
+    resp.headers()
+        .get("Link")
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix('<'))
+        .and_then(|x| x.split_once(">; rel=\"next\""))
+        .map(|(uri, _)| uri.to_string())
+}
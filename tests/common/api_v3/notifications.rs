@@ -0,0 +1,220 @@
+use actix_http::StatusCode;
+use actix_web::{dev::ServiceResponse, test};
+use labrinth::models::notifications::Notification;
+use serde_json::json;
+
+use crate::{
+    assert_status,
+    common::api_common::{Api, AppendsOptionalPat},
+};
+
+use super::{next_page_uri, ApiV3};
+
+/// A page of notifications, along with the URI of the next page from the `Link` header
+pub struct NotificationPage {
+    pub notifications: Vec<Notification>,
+    pub next: Option<String>,
+}
+
+impl ApiV3 {
+    pub async fn get_notification_preferences(&self, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri("/v3/notifications/preferences")
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn get_notification_preferences_deserialized(
+        &self,
+        pat: Option<&str>,
+    ) -> serde_json::Value {
+        let resp = self.get_notification_preferences(pat).await;
+        assert_status!(&resp, StatusCode::OK);
+        test::read_body_json(resp).await
+    }
+
+    pub async fn edit_notification_preferences(
+        &self,
+        preferences: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::patch()
+            .uri("/v3/notifications/preferences")
+            .append_pat(pat)
+            .set_json(preferences)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn add_push_subscription(
+        &self,
+        endpoint: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri("/v3/notifications/push/subscriptions")
+            .append_pat(pat)
+            .set_json(json!({
+                "endpoint": endpoint,
+                "keys": {
+                    "p256dh": "BNcRdreALRFXTkOOUHK1EtK2wtaz5Ry4YfYCA_0QTpQtUbVlUls0VJXg7A8u-Ts1XbjhazAkj7I99e8QcYP7DkM",
+                    "auth": "tBHItJI5svbpez7KI4CCXg",
+                }
+            }))
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn get_push_subscriptions_deserialized(
+        &self,
+        pat: Option<&str>,
+    ) -> Vec<serde_json::Value> {
+        let req = test::TestRequest::get()
+            .uri("/v3/notifications/push/subscriptions")
+            .append_pat(pat)
+            .to_request();
+        let resp = self.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        test::read_body_json(resp).await
+    }
+
+    pub async fn remove_push_subscription(
+        &self,
+        endpoint: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::delete()
+            .uri("/v3/notifications/push/subscriptions")
+            .append_pat(pat)
+            .set_json(json!({ "endpoint": endpoint }))
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn get_user_notifications_page_deserialized(
+        &self,
+        user_id: &str,
+        query: &[(&str, &str)],
+        pat: Option<&str>,
+    ) -> NotificationPage {
+        self.get_notifications_page_deserialized(
+            &format!("/v3/user/{user_id}/notifications?{}", encode_query(query)),
+            pat,
+        )
+        .await
+    }
+
+    pub async fn get_notifications_page_deserialized(
+        &self,
+        uri: &str,
+        pat: Option<&str>,
+    ) -> NotificationPage {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .append_pat(pat)
+            .to_request();
+        let resp = self.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+
+        let next = next_page_uri(&resp);
+        NotificationPage {
+            notifications: test::read_body_json(resp).await,
+            next,
+        }
+    }
+
+    pub async fn mark_user_notifications_read(
+        &self,
+        user_id: &str,
+        query: &[(&str, &str)],
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::patch()
+            .uri(&format!(
+                "/v3/user/{user_id}/notifications?{}",
+                encode_query(query)
+            ))
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn delete_user_notifications(
+        &self,
+        user_id: &str,
+        query: &[(&str, &str)],
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::delete()
+            .uri(&format!(
+                "/v3/user/{user_id}/notifications?{}",
+                encode_query(query)
+            ))
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn get_notification_webhook(&self, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri("/v3/notifications/webhook")
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn edit_notification_webhook(&self, url: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::put()
+            .uri("/v3/notifications/webhook")
+            .append_pat(pat)
+            .set_json(json!({ "url": url }))
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn remove_notification_webhook(&self, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::delete()
+            .uri("/v3/notifications/webhook")
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn get_simple_push_target(&self, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri("/v3/notifications/simple_push")
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn edit_simple_push_target(
+        &self,
+        target: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::put()
+            .uri("/v3/notifications/simple_push")
+            .append_pat(pat)
+            .set_json(target)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn remove_simple_push_target(&self, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::delete()
+            .uri("/v3/notifications/simple_push")
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+}
+
+fn encode_query(query: &[(&str, &str)]) -> String {
+    query
+        .iter()
+        .map(|(key, value)| format!("{key}={}", urlencoding::encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
@@ -9,7 +9,7 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use labrinth::{
-    models::{organizations::Organization, projects::Project},
+    models::{organizations::Organization, projects::Project, threads::ThreadMessageRevision},
     routes::v3::projects::ReturnSearchResults,
     util::actix::AppendsMultipart,
 };
@@ -599,4 +599,441 @@ impl ApiV3 {
         assert_status!(&resp, StatusCode::OK);
         test::read_body_json(resp).await
     }
+
+    pub async fn ingest_playtime_sessions(
+        &self,
+        instance_id: &str,
+        sessions: serde_json::Value,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri("/v3/analytics/playtime")
+            .set_json(json!({
+                "instance_id": instance_id,
+                "sessions": sessions,
+            }))
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_analytics_playtime_sessions(
+        &self,
+        id_or_slugs: Vec<&str>,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let projects_string = serde_json::to_string(&id_or_slugs).unwrap();
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/v3/analytics/playtime/sessions?project_ids={}",
+                urlencoding::encode(&projects_string)
+            ))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_analytics_breakdown(
+        &self,
+        id_or_slugs: Vec<&str>,
+        kind: &str,
+        dimension: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let projects_string = serde_json::to_string(&id_or_slugs).unwrap();
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/v3/analytics/breakdown/{kind}?project_ids={}&dimension={dimension}",
+                urlencoding::encode(&projects_string)
+            ))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_analytics_revenue_summary(
+        &self,
+        organization_id: Option<&str>,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let query = organization_id
+            .map(|x| format!("?organization_id={x}"))
+            .unwrap_or_default();
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/analytics/revenue/summary{query}"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn export_project_analytics(
+        &self,
+        id_or_slug: &str,
+        format: &str,
+        range: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/v3/analytics/project/{id_or_slug}/export?format={format}&range={range}"
+            ))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn start_project_analytics_export(
+        &self,
+        id_or_slug: &str,
+        format: &str,
+        range: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!(
+                "/v3/analytics/project/{id_or_slug}/export?format={format}&range={range}"
+            ))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn edit_project_if_match(
+        &self,
+        id_or_slug: &str,
+        patch: serde_json::Value,
+        etag: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::patch()
+            .uri(&format!("/v3/project/{id_or_slug}"))
+            .insert_header(("If-Match", etag))
+            .append_pat(pat)
+            .set_json(patch)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn request_report_info(
+        &self,
+        id: &str,
+        body: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/report/{id}/request-info"))
+            .append_pat(pat)
+            .set_json(json!({ "body": body }))
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn claim_project(&self, id_or_slug: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/moderation/project/{id_or_slug}/claim"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn unclaim_project(&self, id_or_slug: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/v3/moderation/project/{id_or_slug}/claim"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn edit_moderation_template(
+        &self,
+        name: &str,
+        template: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::put()
+            .uri(&format!("/_internal/admin/moderation/templates/{name}"))
+            .append_pat(pat)
+            .set_json(template)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_moderation_templates(&self, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri("/v3/moderation/templates")
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn send_thread_template(
+        &self,
+        thread_id: &str,
+        template: &str,
+        variables: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/thread/{thread_id}/template"))
+            .append_pat(pat)
+            .set_json(json!({
+                "template": template,
+                "variables": variables,
+            }))
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_moderation_events(&self, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri("/v3/moderation/events")
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_similar_images(&self, id_or_slug: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/v3/moderation/project/{id_or_slug}/similar-images"
+            ))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_image_flags(&self, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri("/v3/moderation/images")
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn resolve_image_flag(&self, id: i64, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/v3/moderation/image/{id}"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_spam_flags(&self, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri("/v3/moderation/spam")
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn resolve_spam_flag(&self, id: i64, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/v3/moderation/spam/{id}"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn start_project_backup(
+        &self,
+        id_or_slug: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/project/{id_or_slug}/export"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_shortlinks(&self, id_or_slug: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/project/{id_or_slug}/shortlinks"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn create_shortlink(
+        &self,
+        id_or_slug: &str,
+        version_id: Option<&str>,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/project/{id_or_slug}/shortlinks"))
+            .append_pat(pat)
+            .set_json(json!({ "version_id": version_id }))
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn delete_shortlink(
+        &self,
+        id_or_slug: &str,
+        code: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/v3/project/{id_or_slug}/shortlinks/{code}"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_job(&self, id: i64, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/jobs/{id}"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_analytics_version_adoption(
+        &self,
+        id_or_slug: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/analytics/project/{id_or_slug}/versions"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn edit_thread_message(
+        &self,
+        id: &str,
+        content: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::patch()
+            .uri(&format!("/v3/message/{id}"))
+            .append_pat(pat)
+            .set_json(json!({ "body": content }))
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_thread_message_history(&self, id: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/message/{id}/history"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_thread_message_history_deserialized(
+        &self,
+        id: &str,
+        pat: Option<&str>,
+    ) -> Vec<ThreadMessageRevision> {
+        let resp = self.get_thread_message_history(id, pat).await;
+        assert_status!(&resp, StatusCode::OK);
+        test::read_body_json(resp).await
+    }
+
+    pub async fn upload_thread_attachment(
+        &self,
+        ext: &str,
+        content: Vec<u8>,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/image?ext={ext}&context=thread_message"))
+            .append_pat(pat)
+            .set_payload(Bytes::from(content))
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn write_to_thread_with_attachments(
+        &self,
+        id: &str,
+        content: &str,
+        attachments: &[&str],
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/thread/{id}"))
+            .append_pat(pat)
+            .set_json(json!({
+                "body": {
+                    "type": "text",
+                    "body": content,
+                    "associated_images": attachments
+                }
+            }))
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn subscribe_thread(&self, id: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/thread/{id}/subscribe"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn mute_thread(&self, id: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/thread/{id}/mute"))
+            .append_pat(pat)
+            .to_request();
+
+        self.call(req).await
+    }
+
+    pub async fn get_project_accepting(
+        &self,
+        id_or_slug: &str,
+        accept: &str,
+        pat: Option<&str>,
+    ) -> Project {
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/project/{id_or_slug}"))
+            .insert_header(("Accept", accept))
+            .append_pat(pat)
+            .to_request();
+        let resp = self.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        test::read_body_json(resp).await
+    }
+
+    pub async fn get_image_thumbnail(&self, id: &str, size: u32) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/image/{id}/thumb?size={size}"))
+            .to_request();
+
+        self.call(req).await
+    }
 }
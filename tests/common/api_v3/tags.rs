@@ -99,4 +99,132 @@ impl ApiV3 {
         assert_status!(&resp, StatusCode::OK);
         test::read_body_json(resp).await
     }
+
+    pub async fn edit_loader(
+        &self,
+        name: &str,
+        loader: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = TestRequest::put()
+            .uri(&format!("/_internal/admin/loaders/{name}"))
+            .append_pat(pat)
+            .set_json(loader)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn delete_loader(&self, name: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = TestRequest::delete()
+            .uri(&format!("/_internal/admin/loaders/{name}"))
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn edit_loader_field(
+        &self,
+        name: &str,
+        loader_field: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = TestRequest::put()
+            .uri(&format!("/_internal/admin/loader_fields/{name}"))
+            .append_pat(pat)
+            .set_json(loader_field)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn delete_loader_field(&self, name: &str, pat: Option<&str>) -> ServiceResponse {
+        let req = TestRequest::delete()
+            .uri(&format!("/_internal/admin/loader_fields/{name}"))
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn edit_loader_field_enum(
+        &self,
+        name: &str,
+        loader_field_enum: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = TestRequest::put()
+            .uri(&format!("/_internal/admin/loader_field_enums/{name}"))
+            .append_pat(pat)
+            .set_json(loader_field_enum)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn edit_loader_field_enum_value(
+        &self,
+        name: &str,
+        value: &str,
+        enum_value: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = TestRequest::put()
+            .uri(&format!(
+                "/_internal/admin/loader_field_enums/{name}/values/{value}"
+            ))
+            .append_pat(pat)
+            .set_json(enum_value)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn delete_loader_field_enum_value(
+        &self,
+        name: &str,
+        value: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = TestRequest::delete()
+            .uri(&format!(
+                "/_internal/admin/loader_field_enums/{name}/values/{value}"
+            ))
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn get_categories_deserialized(&self) -> Vec<CategoryData> {
+        let resp = self.get_categories().await;
+        assert_status!(&resp, StatusCode::OK);
+        test::read_body_json(resp).await
+    }
+
+    pub async fn edit_category(
+        &self,
+        project_type: &str,
+        name: &str,
+        category: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = TestRequest::put()
+            .uri(&format!(
+                "/_internal/admin/categories/{project_type}/{name}"
+            ))
+            .append_pat(pat)
+            .set_json(category)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn delete_category(
+        &self,
+        project_type: &str,
+        name: &str,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = TestRequest::delete()
+            .uri(&format!(
+                "/_internal/admin/categories/{project_type}/{name}"
+            ))
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
 }
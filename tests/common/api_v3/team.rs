@@ -47,6 +47,19 @@ impl ApiV3 {
         assert_status!(&resp, StatusCode::OK);
         test::read_body_json(resp).await
     }
+
+    pub async fn check_permissions(
+        &self,
+        check: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri("/v3/permissions/check")
+            .append_pat(pat)
+            .set_json(check)
+            .to_request();
+        self.call(req).await
+    }
 }
 
 #[async_trait(?Send)]
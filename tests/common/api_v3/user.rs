@@ -46,3 +46,28 @@ impl ApiUser for ApiV3 {
         self.call(req).await
     }
 }
+
+impl ApiV3 {
+    pub async fn get_rate_limit(&self, pat: Option<&str>) -> ServiceResponse {
+        let req = test::TestRequest::get()
+            .uri("/v3/rate_limit")
+            .peer_addr("127.0.0.1:8000".parse().unwrap())
+            .append_pat(pat)
+            .to_request();
+        self.call(req).await
+    }
+
+    pub async fn edit_user_profile(
+        &self,
+        user_id_or_username: &str,
+        patch: serde_json::Value,
+        pat: Option<&str>,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::patch()
+            .uri(&format!("/v3/user/{user_id_or_username}/profile"))
+            .append_pat(pat)
+            .set_json(patch)
+            .to_request();
+        self.call(req).await
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use super::{
+    next_page_uri,
     request_data::{self, get_public_version_creation_data},
     ApiV3,
 };
@@ -66,6 +67,38 @@ impl ApiV3 {
         test::read_body_json(resp).await
     }
 
+    /// Gets a page of versions, along with the URI of the next page from the `Link` header
+    pub async fn get_project_versions_page_deserialized(
+        &self,
+        uri: &str,
+        pat: Option<&str>,
+    ) -> (Vec<Version>, Option<String>) {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .append_pat(pat)
+            .to_request();
+        let resp = self.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+
+        let next = next_page_uri(&resp);
+        (test::read_body_json(resp).await, next)
+    }
+
+    pub async fn get_project_versions_filtered_deserialized(
+        &self,
+        project_id_slug: &str,
+        query: &str,
+        pat: Option<&str>,
+    ) -> Vec<Version> {
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/project/{project_id_slug}/version?{query}"))
+            .append_pat(pat)
+            .to_request();
+        let resp = self.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        test::read_body_json(resp).await
+    }
+
     pub async fn update_individual_files(
         &self,
         algorithm: &str,
@@ -388,7 +421,7 @@ impl ApiVersion for ApiV3 {
         if let Some(game_versions) = game_versions {
             query_string.push_str(&format!(
                 "&game_versions={}",
-                urlencoding::encode(&serde_json::to_string(&game_versions).unwrap())
+                urlencoding::encode(&game_versions.join(","))
             ));
         }
         if let Some(loaders) = loaders {
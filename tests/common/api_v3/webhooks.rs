@@ -0,0 +1,22 @@
+use actix_web::{dev::ServiceResponse, test};
+
+use crate::common::api_common::Api;
+
+use super::ApiV3;
+
+impl ApiV3 {
+    pub async fn post_github_webhook(
+        &self,
+        delivery_id: &str,
+        signature: &str,
+        body: &str,
+    ) -> ServiceResponse {
+        let req = test::TestRequest::post()
+            .uri("/v3/webhook/github")
+            .append_header(("X-GitHub-Delivery", delivery_id))
+            .append_header(("X-Hub-Signature-256", signature))
+            .set_payload(body.to_string())
+            .to_request();
+        self.call(req).await
+    }
+}
@@ -0,0 +1,101 @@
+use actix_http::StatusCode;
+use actix_web::test;
+use chrono::Utc;
+use common::api_common::AppendsOptionalPat;
+use common::api_v3::ApiV3;
+use common::database::*;
+use common::environment::{with_test_environment, TestEnvironment};
+use hex::ToHex;
+use hmac::{Hmac, Mac, NewMac};
+use serde_json::json;
+use sha2::Sha256;
+
+mod common;
+
+fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    format!(
+        "sha256={}",
+        mac.finalize().into_bytes().encode_hex::<String>()
+    )
+}
+
+#[actix_rt::test]
+pub async fn discord_roles_are_granted_to_team_members() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let uri = format!("/v3/project/{alpha_project_id}/discord_roles");
+        let role = json!({
+            "guild_id": "1100000000000000000",
+            "role_id": "1200000000000000000",
+            "relationship": "team_member",
+        });
+
+        // Only members who can edit the project link roles
+        let req = test::TestRequest::post()
+            .uri(&uri)
+            .append_pat(ENEMY_USER_PAT)
+            .set_json(&role)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri(&uri)
+            .append_pat(USER_USER_PAT)
+            .set_json(&role)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let link: serde_json::Value = test::read_body_json(resp).await;
+        let secret = link["secret"].as_str().unwrap().to_string();
+
+        // The secret is only shown when the role is linked
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let links: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(links[0]["role_id"], "1200000000000000000");
+        assert!(links[0].get("secret").is_none());
+
+        sqlx::query("UPDATE users SET discord_id = $1 WHERE id = $2")
+            .bind(42_i64)
+            .bind(USER_USER_ID_PARSED)
+            .execute(&test_env.db.pool)
+            .await
+            .unwrap();
+
+        let verify = |discord_user_id: &str, signature_secret: &str| {
+            let body = json!({
+                "role_link_id": link["id"],
+                "discord_user_id": discord_user_id,
+            })
+            .to_string();
+            let timestamp = Utc::now().timestamp();
+            test::TestRequest::post()
+                .uri("/v3/discord/verify")
+                .insert_header(("X-Modrinth-Timestamp", timestamp.to_string()))
+                .insert_header((
+                    "X-Modrinth-Signature",
+                    sign(signature_secret, timestamp, &body),
+                ))
+                .set_payload(body)
+                .to_request()
+        };
+
+        let resp = test_env.call(verify("42", "wrong")).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        for (discord_user_id, granted) in [("42", true), ("43", false)] {
+            let resp = test_env.call(verify(discord_user_id, &secret)).await;
+            assert_status!(&resp, StatusCode::OK);
+            let result: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(result["granted"], granted, "{discord_user_id}");
+        }
+    })
+    .await;
+}
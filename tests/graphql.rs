@@ -0,0 +1,111 @@
+use actix_http::StatusCode;
+use actix_web::test;
+use common::api_common::{ApiTeams, AppendsOptionalPat};
+use common::api_v3::ApiV3;
+use common::database::*;
+use common::environment::{with_test_environment, TestEnvironment};
+use serde_json::json;
+
+mod common;
+
+async fn graphql(
+    test_env: &TestEnvironment<ApiV3>,
+    query: &str,
+    pat: Option<&str>,
+) -> serde_json::Value {
+    let req = test::TestRequest::post()
+        .uri("/v3/graphql")
+        .append_pat(pat)
+        .set_json(json!({ "query": query }))
+        .to_request();
+    let resp = test_env.call(req).await;
+    assert_status!(&resp, StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body.get("errors").is_none(), "{}", body);
+    body["data"].clone()
+}
+
+#[actix_rt::test]
+pub async fn hidden_projects_are_only_returned_to_members() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let beta_project_id = &test_env.dummy.project_beta.project_id;
+        let query =
+            format!(r#"{{ projects(ids: ["{alpha_project_id}", "{beta_project_id}"]) {{ id }} }}"#);
+
+        let data = graphql(&test_env, &query, USER_USER_PAT).await;
+        assert_eq!(data["projects"].as_array().unwrap().len(), 2);
+
+        for pat in [ENEMY_USER_PAT, None] {
+            let data = graphql(&test_env, &query, pat).await;
+            assert_eq!(data["projects"], json!([{ "id": alpha_project_id }]));
+        }
+
+        let query = format!(r#"{{ project(id: "{beta_project_id}") {{ id }} }}"#);
+        let data = graphql(&test_env, &query, ENEMY_USER_PAT).await;
+        assert!(data["project"].is_null());
+
+        // Projects reached through a user are filtered the same way
+        let query = format!(r#"{{ user(id: "{USER_USER_ID}") {{ projects {{ id }} }} }}"#);
+        let data = graphql(&test_env, &query, ENEMY_USER_PAT).await;
+        assert_eq!(
+            data["user"]["projects"],
+            json!([{ "id": alpha_project_id }])
+        );
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn hidden_versions_are_only_returned_to_members() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let alpha_version_id = &test_env.dummy.project_alpha.version_id;
+        let beta_version_id = &test_env.dummy.project_beta.version_id;
+        let query =
+            format!(r#"{{ versions(ids: ["{alpha_version_id}", "{beta_version_id}"]) {{ id }} }}"#);
+
+        let data = graphql(&test_env, &query, USER_USER_PAT).await;
+        assert_eq!(data["versions"].as_array().unwrap().len(), 2);
+
+        let data = graphql(&test_env, &query, ENEMY_USER_PAT).await;
+        assert_eq!(data["versions"], json!([{ "id": alpha_version_id }]));
+
+        let query = format!(r#"{{ version(id: "{beta_version_id}") {{ id project {{ id }} }} }}"#);
+        let data = graphql(&test_env, &query, ENEMY_USER_PAT).await;
+        assert!(data["version"].is_null());
+
+        let data = graphql(&test_env, &query, USER_USER_PAT).await;
+        assert_eq!(
+            data["version"]["project"]["id"],
+            test_env.dummy.project_beta.project_id.as_str()
+        );
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn pending_team_members_are_not_returned() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let alpha_team_id = &test_env.dummy.project_alpha.team_id;
+
+        let resp = test_env
+            .api
+            .add_user_to_team(alpha_team_id, FRIEND_USER_ID, None, None, USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let query = format!(
+            r#"{{ project(id: "{alpha_project_id}") {{ members {{ isOwner user {{ id }} }} }} }}"#
+        );
+        for pat in [USER_USER_PAT, FRIEND_USER_PAT, None] {
+            let data = graphql(&test_env, &query, pat).await;
+            assert_eq!(
+                data["project"]["members"],
+                json!([{ "isOwner": true, "user": { "id": USER_USER_ID } }])
+            );
+        }
+    })
+    .await;
+}
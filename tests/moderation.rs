@@ -0,0 +1,389 @@
+use actix_http::StatusCode;
+use actix_web::body::MessageBody;
+use actix_web::test;
+use common::api_common::models::CommonItemType;
+use common::api_common::request_data::ImageData;
+use common::api_common::ApiProject;
+use common::api_v3::request_data::get_public_project_creation_data;
+use common::dummy_data::DummyImage;
+use common::{
+    api_v3::ApiV3,
+    database::{ADMIN_USER_PAT, MOD_USER_ID, MOD_USER_PAT, USER_USER_PAT},
+    environment::{with_test_environment, TestEnvironment},
+};
+use labrinth::database::models::image_flag_item::ImageFlag as DBImageFlag;
+use labrinth::models::ids::base62_impl::parse_base62;
+use labrinth::models::ids::ProjectId;
+use labrinth::models::moderation::{
+    ImageSource, ModerationEvent, ModerationTemplate, SimilarImage, SpamScore, SpamSignalKind,
+};
+use labrinth::queue::staff_report::get_staff_report;
+use labrinth::routes::v3::moderation::{ImageFlagPage, SpamFlagPage};
+use labrinth::util::image_scan;
+use labrinth::util::spam::{self, SpamVerdict};
+use serde_json::json;
+use std::collections::HashMap;
+use std::pin::Pin;
+
+mod common;
+
+#[actix_rt::test]
+pub async fn claim_projects_under_review() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let beta_project_id = &test_env.dummy.project_beta.project_id;
+
+        // Only moderators can claim projects, and only while they are under review
+        let resp = api.claim_project(beta_project_id, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api.claim_project(alpha_project_id, MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        let resp = api.claim_project(beta_project_id, MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.claim_project(beta_project_id, MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.claim_project(beta_project_id, ADMIN_USER_PAT).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        let resp = api.unclaim_project(beta_project_id, ADMIN_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.unclaim_project(beta_project_id, ADMIN_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn moderation_events_start_with_claims() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let beta_project_id = &test_env.dummy.project_beta.project_id;
+
+        let resp = api.get_moderation_events(USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        let resp = api.claim_project(beta_project_id, MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_moderation_events(MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+
+        // The stream never ends, so only its first event is read
+        let mut body = resp.into_body();
+        let chunk = futures::future::poll_fn(|cx| Pin::new(&mut body).poll_next(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        let data = std::str::from_utf8(&chunk)
+            .unwrap()
+            .strip_prefix("data: ")
+            .unwrap();
+        match serde_json::from_str::<ModerationEvent>(data.trim()).unwrap() {
+            ModerationEvent::Claimed {
+                project_id,
+                user_id,
+                ..
+            } => {
+                assert_eq!(&project_id.to_string(), beta_project_id);
+                assert_eq!(user_id.to_string(), MOD_USER_ID);
+            }
+            event => panic!("expected a claim, got {:?}", event),
+        }
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn moderators_post_templates_into_threads() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let alpha_thread_id = &test_env.dummy.project_alpha.thread_id;
+
+        // Only admins manage templates
+        let template = json!({
+            "title": "Missing license",
+            "body": "Hi! {{ project_name }} can't be approved yet: {{ reason }}",
+        });
+        let resp = api
+            .edit_moderation_template("missing-license", template.clone(), MOD_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api
+            .edit_moderation_template("missing-license", template, ADMIN_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_moderation_templates(USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api.get_moderation_templates(MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let templates: Vec<ModerationTemplate> = test::read_body_json(resp).await;
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].name, "missing-license");
+
+        // Every variable must be filled in
+        let resp = api
+            .send_thread_template(alpha_thread_id, "missing-license", json!({}), MOD_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        let resp = api
+            .send_thread_template(
+                alpha_thread_id,
+                "missing-license",
+                json!({ "reason": "no license is set" }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api
+            .send_thread_template(
+                alpha_thread_id,
+                "missing-license",
+                json!({ "reason": "no license is set" }),
+                MOD_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let project = api
+            .get_project_deserialized(alpha_project_id, USER_USER_PAT)
+            .await;
+        let resp = api.get_thread(alpha_thread_id, USER_USER_PAT).await;
+        let thread: serde_json::Value = test::read_body_json(resp).await;
+        let message = thread["messages"].as_array().unwrap().last().unwrap();
+        assert_eq!(
+            message["body"]["body"],
+            format!(
+                "Hi! {} can't be approved yet: no license is set",
+                project.name
+            )
+        );
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn flagged_images_wait_for_moderators() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let source = ImageSource::ProjectIcon {
+            project_id: ProjectId(parse_base62(alpha_project_id).unwrap()),
+        };
+
+        // Only labels the scanner is confident in flag an image
+        let labels = HashMap::from([("nsfw".to_string(), 0.97), ("gore".to_string(), 0.02)]);
+        assert!(image_scan::should_flag(&labels));
+        assert!(!image_scan::should_flag(&HashMap::from([(
+            "nsfw".to_string(),
+            0.4
+        )])));
+
+        let id = DBImageFlag::insert(
+            "https://cdn.example.com/icon.png",
+            source,
+            &labels,
+            &test_env.db.pool,
+        )
+        .await
+        .unwrap();
+
+        let resp = api.get_image_flags(USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api.get_image_flags(MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let page: ImageFlagPage = test::read_body_json(resp).await;
+        assert_eq!(page.flags.len(), 1);
+        assert_eq!(page.flags[0].id, id);
+        assert_eq!(page.flags[0].source, source);
+        assert_eq!(page.flags[0].labels, labels);
+
+        let resp = api.resolve_image_flag(id, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api.resolve_image_flag(id, MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.resolve_image_flag(id, MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+
+        let resp = api.get_image_flags(MOD_USER_PAT).await;
+        let page: ImageFlagPage = test::read_body_json(resp).await;
+        assert!(page.flags.is_empty());
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn reposted_images_are_matched() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let beta_project_id = &test_env.dummy.project_beta.project_id;
+
+        // The same picture, encoded differently
+        let mut reencoded = Vec::new();
+        image::load_from_memory(&DummyImage::SmallIcon.get_icon_data().icon)
+            .unwrap()
+            .write_to(
+                &mut std::io::Cursor::new(&mut reencoded),
+                image::ImageOutputFormat::Bmp,
+            )
+            .unwrap();
+        let reencoded = ImageData {
+            filename: "icon.bmp".to_string(),
+            extension: "bmp".to_string(),
+            icon: reencoded,
+        };
+
+        let resp = api
+            .edit_project_icon(
+                alpha_project_id,
+                Some(DummyImage::SmallIcon.get_icon_data()),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api
+            .add_gallery_item(
+                beta_project_id,
+                DummyImage::SmallIcon.get_icon_data(),
+                true,
+                None,
+                None,
+                None,
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // A copy of a gallery image isn't stored again
+        let resp = api
+            .add_gallery_item(
+                beta_project_id,
+                reencoded,
+                false,
+                None,
+                None,
+                None,
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        let resp = api
+            .get_similar_images(alpha_project_id, USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api.get_similar_images(alpha_project_id, MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let similar: Vec<SimilarImage> = test::read_body_json(resp).await;
+        assert_eq!(similar.len(), 1);
+        assert_eq!(
+            similar[0].matched_project_id,
+            ProjectId(parse_base62(beta_project_id).unwrap())
+        );
+        assert!(!similar[0].matched_icon);
+        assert_eq!(similar[0].distance, 0);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn link_spam_from_new_accounts_waits_for_moderators() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+
+        // Signals add up, and only high scores are refused outright
+        let mut score = SpamScore::default();
+        score.add(SpamSignalKind::LinkDensity, 30, String::new());
+        assert_eq!(spam::verdict(&score), SpamVerdict::Review);
+        score.add(SpamSignalKind::DisposableEmail, 40, String::new());
+        assert_eq!(spam::verdict(&score), SpamVerdict::Throttle);
+
+        let description = (0..10)
+            .map(|i| format!("https://example.com/free-{i}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let creation_data = get_public_project_creation_data(
+            "link-spam",
+            None,
+            Some(
+                serde_json::from_value(json!([{
+                    "op": "replace",
+                    "path": "/description",
+                    "value": description,
+                }]))
+                .unwrap(),
+            ),
+        );
+        let resp = api.create_project(creation_data, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let project: serde_json::Value = test::read_body_json(resp).await;
+        let project_id = ProjectId(parse_base62(project["id"].as_str().unwrap()).unwrap());
+
+        let resp = api.get_spam_flags(USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api.get_spam_flags(MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let page: SpamFlagPage = test::read_body_json(resp).await;
+        let flag = page
+            .flags
+            .iter()
+            .find(|x| x.project_id == Some(project_id))
+            .unwrap();
+        assert!(flag
+            .score
+            .signals
+            .iter()
+            .any(|x| x.kind == SpamSignalKind::LinkDensity));
+        assert_eq!(
+            flag.score.score,
+            flag.score.signals.iter().map(|x| x.points).sum::<i32>()
+        );
+
+        let resp = api.resolve_spam_flag(flag.id, MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.resolve_spam_flag(flag.id, MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn staff_report_counts_the_week() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+
+        let resp = api
+            .create_report(
+                "copyright",
+                alpha_project_id,
+                CommonItemType::Project,
+                "This project was copied",
+                MOD_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+
+        let week_start = chrono::Utc::now() - chrono::Duration::days(1);
+        let report = get_staff_report(week_start, &test_env.db.pool)
+            .await
+            .unwrap();
+        assert_eq!(report.reports_filed, 1);
+        assert!(report.new_users > 0);
+
+        let report = get_staff_report(week_start - chrono::Duration::days(7), &test_env.db.pool)
+            .await
+            .unwrap();
+        assert_eq!(report.reports_filed, 0);
+        assert_eq!(report.new_users, 0);
+    })
+    .await;
+}
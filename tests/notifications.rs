@@ -1,9 +1,17 @@
+use actix_http::StatusCode;
+use actix_web::test;
 use common::{
-    database::{FRIEND_USER_ID, FRIEND_USER_PAT, USER_USER_PAT},
-    environment::with_test_environment_all,
+    api_v3::ApiV3,
+    database::{FRIEND_USER_ID, FRIEND_USER_PAT, USER_USER_ID, USER_USER_PAT},
+    environment::{with_test_environment, with_test_environment_all, TestEnvironment},
 };
+use labrinth::models::ids::base62_impl::parse_base62;
+use labrinth::models::ids::VersionId;
+use labrinth::models::notifications::{DependencyChange, NotificationBody};
+use labrinth::queue::dependents::notify_dependents;
+use serde_json::json;
 
-use crate::common::api_common::ApiTeams;
+use crate::common::api_common::{ApiTeams, ApiVersion};
 
 mod common;
 
@@ -70,3 +78,413 @@ pub async fn get_user_notifications_after_deleting_does_not_show_notification()
     })
     .await;
 }
+
+#[actix_rt::test]
+pub async fn notification_preferences_are_stored_per_type() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+
+        let preferences = api
+            .get_notification_preferences_deserialized(FRIEND_USER_PAT)
+            .await;
+        assert!(preferences["preferences"].as_array().unwrap().is_empty());
+
+        let resp = api
+            .edit_notification_preferences(
+                json!([
+                    { "channel": "web_push", "notification_type": null, "enabled": true },
+                    { "channel": "web_push", "notification_type": "team_invite", "enabled": false },
+                ]),
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // Setting the same preference again replaces it
+        let resp = api
+            .edit_notification_preferences(
+                json!([
+                    { "channel": "web_push", "notification_type": "team_invite", "enabled": true },
+                ]),
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let preferences = api
+            .get_notification_preferences_deserialized(FRIEND_USER_PAT)
+            .await;
+        let preferences = preferences["preferences"].as_array().unwrap();
+        assert_eq!(preferences.len(), 2);
+        assert!(preferences.iter().all(|x| x["enabled"] == json!(true)));
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn push_subscriptions_can_be_added_and_removed() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let endpoint = "https://fcm.googleapis.com/fcm/send/abcdef";
+
+        for invalid in [
+            "http://fcm.googleapis.com/fcm/send/abcdef",
+            "https://push.example.com/send/abcdef",
+            "https://127.0.0.1/send/abcdef",
+            "https://fcm.googleapis.com.example.com/send/abcdef",
+        ] {
+            let resp = api.add_push_subscription(invalid, FRIEND_USER_PAT).await;
+            assert_status!(&resp, StatusCode::BAD_REQUEST);
+        }
+
+        let resp = api.add_push_subscription(endpoint, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let subscriptions = api
+            .get_push_subscriptions_deserialized(FRIEND_USER_PAT)
+            .await;
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0]["endpoint"], json!(endpoint));
+
+        // Other users cannot take over or remove the subscription
+        let resp = api.add_push_subscription(endpoint, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        assert!(api
+            .get_push_subscriptions_deserialized(USER_USER_PAT)
+            .await
+            .is_empty());
+
+        let resp = api.remove_push_subscription(endpoint, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let subscriptions = api
+            .get_push_subscriptions_deserialized(FRIEND_USER_PAT)
+            .await;
+        assert_eq!(subscriptions.len(), 1);
+
+        let resp = api
+            .remove_push_subscription(endpoint, FRIEND_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let subscriptions = api
+            .get_push_subscriptions_deserialized(FRIEND_USER_PAT)
+            .await;
+        assert!(subscriptions.is_empty());
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn user_notifications_are_paginated_and_filterable() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        for team_id in [
+            &test_env.dummy.project_alpha.team_id,
+            &test_env.dummy.project_beta.team_id,
+        ] {
+            let resp = api
+                .add_user_to_team(team_id, FRIEND_USER_ID, None, None, USER_USER_PAT)
+                .await;
+            assert_status!(&resp, StatusCode::NO_CONTENT);
+        }
+
+        let page = api
+            .get_user_notifications_page_deserialized(
+                FRIEND_USER_ID,
+                &[("limit", "1")],
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert_eq!(page.notifications.len(), 1);
+        let next = page.next.unwrap();
+        assert!(next.contains("limit=1"));
+
+        let next_page = api
+            .get_notifications_page_deserialized(&next, FRIEND_USER_PAT)
+            .await;
+        assert_eq!(next_page.notifications.len(), 1);
+        assert!(next_page.next.is_none());
+        assert_ne!(page.notifications[0].id.0, next_page.notifications[0].id.0);
+
+        let page = api
+            .get_user_notifications_page_deserialized(
+                FRIEND_USER_ID,
+                &[("types", r#"["project_update"]"#)],
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert!(page.notifications.is_empty());
+
+        let resp = api
+            .mark_user_notifications_read(
+                FRIEND_USER_ID,
+                &[("types", r#"["team_invite"]"#)],
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let page = api
+            .get_user_notifications_page_deserialized(
+                FRIEND_USER_ID,
+                &[("read", "false")],
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert!(page.notifications.is_empty());
+
+        // Other users cannot clear someone else's notifications
+        let resp = api
+            .delete_user_notifications(FRIEND_USER_ID, &[], USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        let resp = api
+            .delete_user_notifications(FRIEND_USER_ID, &[("read", "true")], FRIEND_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let page = api
+            .get_user_notifications_page_deserialized(FRIEND_USER_ID, &[], FRIEND_USER_PAT)
+            .await;
+        assert!(page.notifications.is_empty());
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn notification_webhooks_can_be_set_and_removed() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let url = "https://discord.com/api/webhooks/123/abc";
+
+        let resp = api.get_notification_webhook(FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+
+        let resp = api
+            .edit_notification_webhook("http://example.com/hook", FRIEND_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        // Webhooks can't point at internal services
+        for internal in [
+            "https://127.0.0.1/hook",
+            "https://169.254.169.254/latest/meta-data",
+            "https://localhost:5432/hook",
+        ] {
+            let resp = api
+                .edit_notification_webhook(internal, FRIEND_USER_PAT)
+                .await;
+            assert_status!(&resp, StatusCode::BAD_REQUEST);
+        }
+
+        let resp = api.edit_notification_webhook(url, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let created: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(created["secret"].as_str().unwrap().len(), 32);
+
+        // The secret is not returned again
+        let resp = api.get_notification_webhook(FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let webhook: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(webhook["url"], json!(url));
+        assert!(webhook.get("secret").is_none());
+
+        let resp = api.remove_notification_webhook(FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.get_notification_webhook(FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn team_membership_changes_notify_member_and_managers() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_team_id = &test_env.dummy.project_alpha.team_id;
+        let membership_changes = [("types", r#"["team_membership_change"]"#)];
+
+        api.add_user_to_team(alpha_team_id, FRIEND_USER_ID, None, None, USER_USER_PAT)
+            .await;
+        let resp = api.join_team(alpha_team_id, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // The owner is told the invite was accepted, but the member who joined is not
+        let page = api
+            .get_user_notifications_page_deserialized(
+                USER_USER_ID,
+                &membership_changes,
+                USER_USER_PAT,
+            )
+            .await;
+        assert_eq!(page.notifications.len(), 1);
+        let page = api
+            .get_user_notifications_page_deserialized(
+                FRIEND_USER_ID,
+                &membership_changes,
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert!(page.notifications.is_empty());
+
+        let resp = api
+            .remove_from_team(alpha_team_id, FRIEND_USER_ID, USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let page = api
+            .get_user_notifications_page_deserialized(
+                FRIEND_USER_ID,
+                &membership_changes,
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert_eq!(page.notifications.len(), 1);
+        assert_eq!(
+            serde_json::to_value(&page.notifications[0].body).unwrap()["change"],
+            json!("removed")
+        );
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn simple_push_targets_can_be_set_and_removed() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+
+        let resp = api
+            .edit_simple_push_target(
+                json!({ "provider": "carrier_pigeon", "url": "https://ntfy.sh/topic" }),
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        for invalid in [
+            // Generic pushes are signed with the token, so one is required
+            json!({ "provider": "generic", "url": "https://ntfy.sh/topic" }),
+            json!({ "provider": "ntfy", "url": "https://10.0.0.1/topic" }),
+            json!({ "provider": "ntfy", "url": "https://localhost/topic" }),
+        ] {
+            let resp = api.edit_simple_push_target(invalid, FRIEND_USER_PAT).await;
+            assert_status!(&resp, StatusCode::BAD_REQUEST);
+        }
+
+        let resp = api
+            .edit_simple_push_target(
+                json!({ "provider": "ntfy", "url": "https://ntfy.sh/topic", "token": "tk_secret" }),
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_simple_push_target(FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let target: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(target["provider"], json!("ntfy"));
+        assert_eq!(target["has_token"], json!(true));
+        assert!(target.get("token").is_none());
+
+        let resp = api.remove_simple_push_target(FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.get_simple_push_target(FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn dependency_updates_notify_dependent_teams() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_version_id = &test_env.dummy.project_alpha.version_id;
+        let alpha_project_id = test_env.dummy.project_alpha.project_id_parsed;
+        let beta_project_id = test_env.dummy.project_beta.project_id_parsed;
+        let beta_version_id =
+            VersionId(parse_base62(&test_env.dummy.project_beta.version_id).unwrap());
+
+        // Alpha requires beta, so alpha's team hears about beta's releases
+        let resp = api
+            .edit_version(
+                alpha_version_id,
+                json!({
+                    "dependencies": [{
+                        "project_id": test_env.dummy.project_beta.project_id,
+                        "dependency_type": "required"
+                    }]
+                }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        notify_dependents(
+            beta_project_id,
+            beta_version_id,
+            DependencyChange::Released,
+            &test_env.db.pool,
+            &test_env.db.redis_pool,
+        )
+        .await
+        .unwrap();
+
+        let page = api
+            .get_user_notifications_page_deserialized(USER_USER_ID, &[], USER_USER_PAT)
+            .await;
+        let updates = page
+            .notifications
+            .iter()
+            .filter_map(|x| match x.body {
+                NotificationBody::DependencyUpdate {
+                    project_id,
+                    dependency_id,
+                    change,
+                    ..
+                } => Some((project_id, dependency_id, change)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            updates,
+            vec![(
+                alpha_project_id,
+                beta_project_id,
+                DependencyChange::Released
+            )]
+        );
+
+        // Turning the type off stops later updates
+        let resp = api
+            .edit_notification_preferences(
+                json!([{
+                    "notification_type": "dependency_update",
+                    "channel": "site",
+                    "enabled": false
+                }]),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        notify_dependents(
+            beta_project_id,
+            beta_version_id,
+            DependencyChange::Yanked,
+            &test_env.db.pool,
+            &test_env.db.redis_pool,
+        )
+        .await
+        .unwrap();
+
+        let page = api
+            .get_user_notifications_page_deserialized(USER_USER_ID, &[], USER_USER_PAT)
+            .await;
+        assert!(!page.notifications.iter().any(|x| matches!(
+            x.body,
+            NotificationBody::DependencyUpdate {
+                change: DependencyChange::Yanked,
+                ..
+            }
+        )));
+    })
+    .await;
+}
@@ -12,6 +12,7 @@ use common::{
     environment::{with_test_environment, TestEnvironment},
 };
 use labrinth::auth::oauth::TokenResponse;
+use labrinth::models::pats::Scopes;
 use reqwest::header::{CACHE_CONTROL, PRAGMA};
 
 mod common;
@@ -307,3 +308,36 @@ async fn revoke_authorization_after_issuing_token_revokes_token() {
     })
     .await;
 }
+
+#[actix_rt::test]
+async fn unverified_client_cannot_request_sensitive_scopes() {
+    with_test_environment(None, |env: TestEnvironment<ApiV3>| async move {
+        let client_id = env.dummy.oauth_client_alpha.client_id;
+
+        let resp = env
+            .api
+            .oauth_authorize(
+                &client_id,
+                Some("USER_READ USER_READ_EMAIL"),
+                None,
+                None,
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let query = get_redirect_location_query_params(&resp);
+        assert_eq!("invalid_scope", query.get("error").unwrap());
+
+        // Without a scope, only the scopes the app can request are
+        let resp = env
+            .api
+            .oauth_authorize(&client_id, None, None, None, USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let access_request: serde_json::Value = test::read_body_json(resp).await;
+        let scopes =
+            Scopes::from_bits_truncate(access_request["requested_scopes"].as_u64().unwrap());
+        assert!(!scopes.intersects(Scopes::sensitive()));
+    })
+    .await;
+}
@@ -1,15 +1,19 @@
 use actix_http::StatusCode;
 use actix_web::test;
+use common::api_common::AppendsOptionalPat;
 use common::{
     api_v3::ApiV3,
-    database::{FRIEND_USER_ID, FRIEND_USER_PAT, USER_USER_ID, USER_USER_PAT},
+    database::{FRIEND_USER_ID, FRIEND_USER_PAT, MOD_USER_PAT, USER_USER_ID, USER_USER_PAT},
     dummy_data::DummyOAuthClientAlpha,
     environment::{with_test_environment, TestEnvironment},
     get_json_val_str,
 };
 use labrinth::{
     models::{
-        oauth_clients::{OAuthClient, OAuthClientCreationResult},
+        oauth_clients::{
+            OAuthClient, OAuthClientCreationResult, OAuthClientVerification,
+            OAuthClientVerificationStatus,
+        },
         pats::Scopes,
     },
     routes::v3::oauth_clients::OAuthClientEdit,
@@ -200,3 +204,66 @@ async fn can_list_user_oauth_authorizations() {
     })
     .await;
 }
+
+#[actix_rt::test]
+async fn oauth_client_verification_is_reviewed_by_staff() {
+    with_test_environment(None, |env: TestEnvironment<ApiV3>| async move {
+        let DummyOAuthClientAlpha { client_id, .. } = env.dummy.oauth_client_alpha.clone();
+        let apply = |pat: Option<&'static str>| {
+            test::TestRequest::post()
+                .uri(&format!("/v3/oauth/app/{}/verification", client_id))
+                .append_pat(pat)
+                .set_json(serde_json::json!({ "reason": "A launcher used by many players" }))
+                .to_request()
+        };
+
+        // Only the developer can apply
+        let resp = env.call(apply(FRIEND_USER_PAT)).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = env.call(apply(USER_USER_PAT)).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = env.call(apply(USER_USER_PAT)).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        // Staff see the application, and developers cannot review their own
+        let req = test::TestRequest::get()
+            .uri("/v3/oauth/verifications")
+            .append_pat(MOD_USER_PAT)
+            .to_request();
+        let resp = env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let verifications: Vec<OAuthClientVerification> = test::read_body_json(resp).await;
+        assert_eq!(1, verifications.len());
+        assert_eq!(get_json_val_str(verifications[0].client_id), client_id);
+
+        let review = |pat: Option<&'static str>| {
+            test::TestRequest::post()
+                .uri(&format!("/v3/oauth/app/{}/verification/review", client_id))
+                .append_pat(pat)
+                .set_json(serde_json::json!({ "status": "verified" }))
+                .to_request()
+        };
+        let resp = env.call(review(USER_USER_PAT)).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = env.call(review(MOD_USER_PAT)).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // The app's metadata shows it is verified
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/oauth/app/{}/verification", client_id))
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let verification: OAuthClientVerification = test::read_body_json(resp).await;
+        assert_eq!(OAuthClientVerificationStatus::Verified, verification.status);
+
+        let resp = env
+            .api
+            .get_oauth_client(client_id.clone(), USER_USER_PAT)
+            .await;
+        let client: OAuthClient = test::read_body_json(resp).await;
+        assert!(client.verified);
+    })
+    .await;
+}
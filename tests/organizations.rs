@@ -1,5 +1,5 @@
 use crate::common::{
-    api_common::{ApiProject, ApiTeams},
+    api_common::{ApiProject, ApiTeams, AppendsOptionalPat},
     database::{
         generate_random_name, ADMIN_USER_PAT, ENEMY_USER_ID_PARSED, ENEMY_USER_PAT,
         FRIEND_USER_ID_PARSED, MOD_USER_ID, MOD_USER_PAT, USER_USER_ID, USER_USER_ID_PARSED,
@@ -7,6 +7,8 @@ use crate::common::{
     dummy_data::{DummyImage, DummyOrganizationZeta, DummyProjectAlpha, DummyProjectBeta},
 };
 use actix_http::StatusCode;
+use actix_web::test;
+use chrono::{Duration, Utc};
 use common::{
     api_v3::ApiV3,
     database::{FRIEND_USER_ID, FRIEND_USER_PAT, USER_USER_PAT},
@@ -14,6 +16,7 @@ use common::{
     permissions::{PermissionsTest, PermissionsTestContext},
 };
 use labrinth::models::{
+    pats::Scopes,
     teams::{OrganizationPermissions, ProjectPermissions},
     users::UserId,
 };
@@ -1085,3 +1088,105 @@ async fn permissions_organization_permissions_consistency_test() {
     })
     .await;
 }
+
+#[actix_rt::test]
+async fn organization_tokens() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let zeta_organization_id = &test_env.dummy.organization_zeta.organization_id;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+
+        let resp = api
+            .organization_add_project(zeta_organization_id, alpha_project_id, USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+
+        let new_token = json!({
+            "name": "CI pipeline",
+            "scopes": Scopes::PROJECT_WRITE,
+            "permissions": ProjectPermissions::EDIT_DETAILS,
+            "expires": Utc::now() + Duration::days(1),
+        });
+
+        // Members who can't manage the organization's members can't create tokens
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/organization/{zeta_organization_id}/tokens"))
+            .append_pat(FRIEND_USER_PAT)
+            .set_json(&new_token)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        // Tokens can't be given account scopes
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/organization/{zeta_organization_id}/tokens"))
+            .append_pat(USER_USER_PAT)
+            .set_json(json!({
+                "name": "CI pipeline",
+                "scopes": Scopes::PROJECT_WRITE | Scopes::USER_WRITE,
+                "permissions": ProjectPermissions::EDIT_DETAILS,
+                "expires": Utc::now() + Duration::days(1),
+            }))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/organization/{zeta_organization_id}/tokens"))
+            .append_pat(USER_USER_PAT)
+            .set_json(&new_token)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let token: serde_json::Value = test::read_body_json(resp).await;
+        let token_id = token["id"].as_str().unwrap();
+        let access_token = token["access_token"].as_str().unwrap();
+
+        // The token can edit the organization's projects
+        let resp = api
+            .edit_project(
+                alpha_project_id,
+                json!({ "description": "Edited by CI" }),
+                Some(access_token),
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // Listing tokens does not return their access tokens
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/organization/{zeta_organization_id}/tokens"))
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let tokens: serde_json::Value = test::read_body_json(resp).await;
+        let tokens = tokens.as_array().unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0]["id"], token_id);
+        assert!(tokens[0]["access_token"].is_null());
+        assert_eq!(
+            tokens[0]["permissions"].as_u64().unwrap(),
+            ProjectPermissions::EDIT_DETAILS.bits()
+        );
+
+        let req = test::TestRequest::delete()
+            .uri(&format!(
+                "/v3/organization/{zeta_organization_id}/tokens/{token_id}"
+            ))
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // Revoked tokens stop working
+        let resp = api
+            .edit_project(
+                alpha_project_id,
+                json!({ "description": "Edited by CI again" }),
+                Some(access_token),
+            )
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+    })
+    .await;
+}
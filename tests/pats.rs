@@ -290,3 +290,51 @@ pub async fn bad_pats() {
     })
     .await;
 }
+
+#[actix_rt::test]
+pub async fn pat_usage_is_only_shown_to_its_owner() {
+    with_test_environment_all(None, |test_env| async move {
+        let req = test::TestRequest::post()
+            .uri("/_internal/pat")
+            .append_pat(USER_USER_PAT)
+            .set_json(json!({
+                "scopes": Scopes::COLLECTION_CREATE,
+                "name": "test_pat_usage Test",
+                "expires": Utc::now() + Duration::days(1),
+            }))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let success: serde_json::Value = test::read_body_json(resp).await;
+        let id = success["id"].as_str().unwrap();
+
+        // Requests are counted by the app's middleware, which tests do not run with
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/pat/{}/usage", id))
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let usage: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(usage["requests"], 0);
+        assert!(usage["hours"].as_array().unwrap().is_empty());
+        assert!(usage["recent_errors"].as_array().unwrap().is_empty());
+
+        // Usage is only kept for a month
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/pat/{}/usage?days=90", id))
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        // Other users cannot see the token exists
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/pat/{}/usage", id))
+            .append_pat(FRIEND_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
@@ -0,0 +1,228 @@
+use actix_http::StatusCode;
+use actix_web::test;
+use chrono::{Datelike, Utc};
+use common::api_common::AppendsOptionalPat;
+use common::api_v3::ApiV3;
+use common::database::{ADMIN_USER_PAT, USER_USER_ID, USER_USER_ID_PARSED, USER_USER_PAT};
+use common::environment::{with_test_environment, TestEnvironment};
+use hex::ToHex;
+use hmac::{Hmac, Mac, NewMac};
+use labrinth::database::models::{User, UserId};
+use labrinth::models::payouts::PayoutMethodType;
+use labrinth::payouts::MockProvider;
+use labrinth::queue::payouts::PayoutsQueue;
+use labrinth::routes::v3::payouts::{withdraw, Withdrawal};
+use labrinth::routes::ApiError;
+use rust_decimal::Decimal;
+use serde_json::json;
+use sha2::Sha256;
+use std::sync::Arc;
+
+mod common;
+
+/// Sets the user's balance, in cents
+async fn set_balance(test_env: &TestEnvironment<ApiV3>, cents: i64) {
+    sqlx::query("UPDATE users SET balance = $1 WHERE id = $2")
+        .bind(Decimal::from(cents))
+        .bind(USER_USER_ID_PARSED)
+        .execute(&test_env.db.pool)
+        .await
+        .unwrap();
+    User::clear_caches(
+        &[(UserId(USER_USER_ID_PARSED), None)],
+        &test_env.db.redis_pool,
+    )
+    .await
+    .unwrap();
+}
+
+/// Gets the user's balance, in cents
+async fn get_balance(test_env: &TestEnvironment<ApiV3>) -> i64 {
+    let req = test::TestRequest::get()
+        .uri("/v3/user")
+        .append_pat(USER_USER_PAT)
+        .to_request();
+    let resp = test_env.call(req).await;
+    assert_status!(&resp, StatusCode::OK);
+
+    let user: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(user["payout_data"]["balance"]["currency"], "USD");
+    user["payout_data"]["balance"]["amount"].as_i64().unwrap()
+}
+
+async fn withdraw_ten_dollars(
+    test_env: &TestEnvironment<ApiV3>,
+    payouts_queue: &PayoutsQueue,
+) -> Result<labrinth::database::models::payout_item::Payout, ApiError> {
+    let user = User::get_id(
+        UserId(USER_USER_ID_PARSED),
+        &test_env.db.pool,
+        &test_env.db.redis_pool,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    withdraw(
+        user,
+        &Withdrawal {
+            amount: Decimal::from(10),
+            method: PayoutMethodType::PayPal,
+            method_id: "mock_paypal".to_string(),
+            quote_id: None,
+        },
+        &test_env.db.pool,
+        &test_env.db.redis_pool,
+        payouts_queue,
+    )
+    .await
+}
+
+#[actix_rt::test]
+pub async fn withdrawals_need_a_completed_tax_form_and_are_withheld() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let mock = Arc::new(MockProvider::new(PayoutMethodType::PayPal));
+        let payouts_queue = PayoutsQueue::with_providers(vec![mock.clone()]);
+        set_balance(&test_env, 2500).await;
+
+        let err = withdraw_ten_dollars(&test_env, &payouts_queue)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ApiError::InvalidInput(x) if x.contains("tax form")),
+            "{:?}",
+            err
+        );
+
+        // A pending form doesn't allow withdrawals either
+        sqlx::query(
+            "INSERT INTO users_tax_forms (user_id, provider_id, status, withholding_rate) VALUES ($1, 'form_1', 'pending', 0)",
+        )
+        .bind(USER_USER_ID_PARSED)
+        .execute(&test_env.db.pool)
+        .await
+        .unwrap();
+        assert!(withdraw_ten_dollars(&test_env, &payouts_queue)
+            .await
+            .is_err());
+        assert!(mock.transfers().is_empty());
+        assert_eq!(get_balance(&test_env).await, 2500);
+
+        // Foreign persons without a treaty rate are withheld 30%
+        sqlx::query(
+            "UPDATE users_tax_forms SET status = 'complete', form_type = 'w8ben', withholding_rate = 0.3 WHERE user_id = $1",
+        )
+        .bind(USER_USER_ID_PARSED)
+        .execute(&test_env.db.pool)
+        .await
+        .unwrap();
+
+        let payout = withdraw_ten_dollars(&test_env, &payouts_queue)
+            .await
+            .unwrap();
+        assert_eq!(payout.withholding, Some(Decimal::from(3)));
+        assert_eq!(payout.amount, Decimal::from(7));
+
+        let transfers = mock.transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].amount, Decimal::from(7));
+
+        // The withheld amount is taken from the balance along with what was sent
+        assert_eq!(get_balance(&test_env).await, 1500);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/payout/tax/summary?year={}", Utc::now().year()))
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let summary: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(summary["withheld"], json!(3.0));
+    })
+    .await;
+}
+
+fn tax_signature(body: &str) -> String {
+    let mut mac: Hmac<Sha256> =
+        Hmac::new_from_slice(dotenvy::var("TAX_WEBHOOK_SECRET").unwrap().as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    mac.finalize().into_bytes().encode_hex::<String>()
+}
+
+#[actix_rt::test]
+pub async fn tax_webhooks_with_bad_signatures_are_rejected() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let body = json!({
+            "event": "form.completed",
+            "form": { "id": "form_1", "form_type": "w9" },
+        })
+        .to_string();
+
+        for signature in ["00", "not hex", tax_signature("{}").as_str()] {
+            let req = test::TestRequest::post()
+                .uri("/v3/payout/_tax")
+                .insert_header(("Tax-Webhook-Signature", signature))
+                .set_payload(body.clone())
+                .to_request();
+            let resp = test_env.call(req).await;
+            assert_status!(&resp, StatusCode::BAD_REQUEST);
+        }
+
+        // Rejected deliveries are never stored, so they are never processed
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM inbound_webhooks WHERE source = 'tax'",
+        )
+        .fetch_one(&test_env.db.pool)
+        .await
+        .unwrap();
+        assert_eq!(count, 0);
+
+        let req = test::TestRequest::post()
+            .uri("/v3/payout/_tax")
+            .insert_header(("Tax-Webhook-Signature", tax_signature(&body)))
+            .set_payload(body.clone())
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn adjustments_cannot_make_balances_negative_unless_allowed() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        set_balance(&test_env, 500).await;
+
+        let adjust = |allow_negative: bool| {
+            test::TestRequest::post()
+                .uri("/v3/payout/adjustments")
+                .append_pat(ADMIN_USER_PAT)
+                .set_json(json!({
+                    "user_id": USER_USER_ID,
+                    "amount": -10.0,
+                    "reason": "ad_revenue_clawback",
+                    "allow_negative": allow_negative,
+                }))
+                .to_request()
+        };
+
+        let resp = test_env.call(adjust(false)).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        // Nothing is recorded for a rejected adjustment
+        assert_eq!(get_balance(&test_env).await, 500);
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM payouts_adjustments WHERE user_id = $1",
+        )
+        .bind(USER_USER_ID_PARSED)
+        .fetch_one(&test_env.db.pool)
+        .await
+        .unwrap();
+        assert_eq!(count, 0);
+
+        let resp = test_env.call(adjust(true)).await;
+        assert_status!(&resp, StatusCode::OK);
+        assert_eq!(get_balance(&test_env).await, -500);
+    })
+    .await;
+}
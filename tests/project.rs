@@ -18,8 +18,8 @@ use labrinth::util::actix::{MultipartSegment, MultipartSegmentData};
 use serde_json::json;
 
 use crate::common::api_common::models::{CommonItemType, CommonProject};
-use crate::common::api_common::request_data::ProjectCreationRequestData;
-use crate::common::api_common::{ApiProject, ApiTeams, ApiVersion};
+use crate::common::api_common::request_data::{ImageData, ProjectCreationRequestData};
+use crate::common::api_common::{ApiProject, ApiTeams, ApiVersion, AppendsOptionalPat};
 use crate::common::dummy_data::{
     DummyImage, DummyOrganizationZeta, DummyProjectAlpha, DummyProjectBeta, TestFile,
 };
@@ -93,6 +93,36 @@ async fn test_get_project() {
     .await;
 }
 
+#[actix_rt::test]
+async fn get_projects_by_mixed_ids_and_slugs() {
+    with_test_environment_all(None, |test_env| async move {
+        let alpha = &test_env.dummy.project_alpha;
+        let beta = &test_env.dummy.project_beta;
+        let mut expected = vec![alpha.project_id.clone(), beta.project_id.clone()];
+        expected.sort();
+
+        // The second lookup is served from the cache, which must not return alpha twice either
+        for _ in 0..2 {
+            let resp = test_env
+                .api
+                .get_projects(
+                    &[&alpha.project_id, &alpha.project_slug, &beta.project_slug],
+                    USER_USER_PAT,
+                )
+                .await;
+            assert_status!(&resp, StatusCode::OK);
+            let projects: Vec<CommonProject> = test::read_body_json(resp).await;
+            let mut ids = projects
+                .iter()
+                .map(|x| x.id.to_string())
+                .collect::<Vec<_>>();
+            ids.sort();
+            assert_eq!(ids, expected);
+        }
+    })
+    .await;
+}
+
 #[actix_rt::test]
 async fn test_add_remove_project() {
     // Test setup and dummy data
@@ -273,6 +303,47 @@ async fn test_add_remove_project() {
     .await;
 }
 
+#[actix_rt::test]
+pub async fn deleted_projects_can_be_restored_by_members() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+
+        let resp = api.remove_project(alpha_project_id, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_project(alpha_project_id, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+
+        // Users who couldn't have deleted the project can't tell it exists
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/project/{alpha_project_id}/restore"))
+            .append_pat(ENEMY_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/project/{alpha_project_id}/restore"))
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_project(alpha_project_id, ENEMY_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+
+        // Projects which are not deleted can't be restored
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/project/{alpha_project_id}/restore"))
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
 #[actix_rt::test]
 pub async fn test_patch_project() {
     with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
@@ -487,6 +558,91 @@ pub async fn test_patch_v3() {
     .await;
 }
 
+#[actix_rt::test]
+pub async fn patch_project_with_stale_etag() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_slug = &test_env.dummy.project_alpha.project_slug;
+
+        let resp = api.get_project(alpha_project_slug, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let etag = resp
+            .headers()
+            .get("etag")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let resp = api
+            .edit_project_if_match(
+                alpha_project_slug,
+                json!({ "description": "First edit" }),
+                &etag,
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // The first edit moved the project to a new revision
+        let resp = api
+            .edit_project_if_match(
+                alpha_project_slug,
+                json!({ "description": "Second edit" }),
+                &etag,
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::PRECONDITION_FAILED);
+
+        let project = api
+            .get_project_deserialized(alpha_project_slug, USER_USER_PAT)
+            .await;
+        assert_eq!(project.description, "First edit");
+
+        // Edits without `If-Match` are made to any revision
+        let resp = api
+            .edit_project(
+                alpha_project_slug,
+                json!({ "description": "Third edit" }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn patch_project_reports_invalid_fields() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_slug = &test_env.dummy.project_alpha.project_slug;
+
+        let resp = api
+            .edit_project(
+                alpha_project_slug,
+                json!({ "name": "a", "summary": "b" }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_input");
+        let mut fields = body["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["field"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        fields.sort();
+        fields.dedup();
+        assert_eq!(fields, vec!["name", "summary"]);
+    })
+    .await;
+}
+
 #[actix_rt::test]
 pub async fn test_bulk_edit_categories() {
     with_test_environment_all(None, |test_env| async move {
@@ -1382,6 +1538,252 @@ async fn projects_various_visibility() {
     .await;
 }
 
+#[actix_rt::test]
+async fn project_images_are_served_in_accepted_formats() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+
+        let resp = api
+            .edit_project_icon(
+                alpha_project_id,
+                Some(DummyImage::SmallIcon.get_icon_data()),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api
+            .add_gallery_item(
+                alpha_project_id,
+                DummyImage::SmallIcon.get_icon_data(),
+                true,
+                None,
+                None,
+                None,
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // Renditions are generated next to the originals
+        let project = api
+            .get_project_accepting(alpha_project_id, "application/json", USER_USER_PAT)
+            .await;
+        let icon_url = project.icon_url.clone().unwrap();
+        assert!(icon_url.ends_with(".png"));
+        let icon_base = icon_url.trim_end_matches(".png");
+        assert_eq!(
+            project.icon_renditions.get("image/avif"),
+            Some(&format!("{icon_base}.avif"))
+        );
+        assert_eq!(
+            project.icon_renditions.get("image/webp"),
+            Some(&format!("{icon_base}.webp"))
+        );
+        assert!(project.gallery[0].url.ends_with(".png"));
+        assert_eq!(project.gallery[0].renditions.len(), 2);
+
+        // The best format the client lists is picked, and wildcards don't count
+        let project = api
+            .get_project_accepting(alpha_project_id, "image/avif,image/webp,*/*", USER_USER_PAT)
+            .await;
+        assert_eq!(project.icon_url, Some(format!("{icon_base}.avif")));
+        assert!(project.gallery[0].url.ends_with(".avif"));
+        let project = api
+            .get_project_accepting(
+                alpha_project_id,
+                "image/avif;q=0, image/webp",
+                USER_USER_PAT,
+            )
+            .await;
+        assert_eq!(project.icon_url, Some(format!("{icon_base}.webp")));
+        let project = api
+            .get_project_accepting(alpha_project_id, "*/*", USER_USER_PAT)
+            .await;
+        assert_eq!(project.icon_url, Some(icon_url));
+
+        // Gallery items can be removed by the URL of a rendition
+        let webp_url = project.gallery[0].renditions["image/webp"].clone();
+        let resp = api
+            .remove_gallery_item(alpha_project_id, &webp_url, USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let project = api
+            .get_project_accepting(alpha_project_id, "*/*", USER_USER_PAT)
+            .await;
+        assert!(project.gallery.is_empty());
+    })
+    .await;
+}
+
+fn animated_gif(size: u32, frames: u16) -> ImageData {
+    let mut icon = Vec::new();
+    {
+        let mut encoder = image::codecs::gif::GifEncoder::new(&mut icon);
+        for i in 0..frames {
+            let pixel = image::Rgba([(i % 6 * 40) as u8, 0, 0, 255]);
+            let frame = image::Frame::new(image::RgbaImage::from_pixel(size, size, pixel));
+            encoder.encode_frame(frame).unwrap();
+        }
+    }
+
+    ImageData {
+        filename: "animated.gif".to_string(),
+        extension: "gif".to_string(),
+        icon,
+    }
+}
+
+#[actix_rt::test]
+async fn animated_icons_keep_their_animation() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+
+        let resp = api
+            .edit_project_icon(alpha_project_id, Some(animated_gif(64, 3)), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // Optimized formats would drop the animation, so only a still copy is generated
+        let project = api
+            .get_project_accepting(alpha_project_id, "image/avif,image/webp", USER_USER_PAT)
+            .await;
+        let icon_url = project.icon_url.unwrap();
+        assert!(icon_url.ends_with(".gif"));
+        assert_eq!(project.icon_renditions.len(), 1);
+        assert_eq!(
+            project.icon_renditions.get("image/png"),
+            Some(&icon_url.replace(".gif", ".still.png"))
+        );
+
+        // Animations are limited in size and length
+        let resp = api
+            .edit_project_icon(alpha_project_id, Some(animated_gif(300, 2)), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        let resp = api
+            .edit_project_icon(alpha_project_id, Some(animated_gif(16, 257)), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+    })
+    .await;
+}
+
+fn svg_icon(svg: &str) -> ImageData {
+    ImageData {
+        filename: "icon.svg".to_string(),
+        extension: "svg".to_string(),
+        icon: svg.as_bytes().to_vec(),
+    }
+}
+
+#[actix_rt::test]
+async fn svg_icons_are_sanitized() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+
+        let icon = svg_icon(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" viewBox="0 0 16 16" onload="alert(1)">
+                <script>alert(1)</script>
+                <foreignObject width="16" height="16"><div xmlns="http://www.w3.org/1999/xhtml">hi</div></foreignObject>
+                <rect id="box" width="16" height="16" fill="url(https://example.com/paint)"/>
+                <use xlink:href="https://example.com/icon.svg#box"/>
+                <circle cx="8" cy="8" r="4" fill="#ff0000"/>
+            </svg>"##,
+        );
+        let resp = api
+            .edit_project_icon(alpha_project_id, Some(icon), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // Clients that can show SVGs get the original, and others a rasterized rendition
+        let project = api
+            .get_project_accepting(alpha_project_id, "image/svg+xml", USER_USER_PAT)
+            .await;
+        let icon_url = project.icon_url.unwrap();
+        assert!(icon_url.ends_with(".svg"));
+        for content_type in ["image/png", "image/avif", "image/webp"] {
+            assert!(project.icon_renditions.contains_key(content_type));
+        }
+        let project = api
+            .get_project_accepting(alpha_project_id, "*/*", USER_USER_PAT)
+            .await;
+        assert_eq!(
+            project.icon_url,
+            Some(icon_url.replace(".svg", ".still.png"))
+        );
+
+        // Only the harmless parts of the icon are stored
+        let path = icon_url.trim_start_matches(&dotenvy::var("CDN_URL").unwrap());
+        let stored = std::fs::read_to_string(
+            std::path::Path::new(&dotenvy::var("MOCK_FILE_PATH").unwrap())
+                .join(path.trim_start_matches('/')),
+        )
+        .unwrap();
+        for unsafe_part in ["onload", "script", "foreignObject", "example.com"] {
+            assert!(!stored.contains(unsafe_part), "{} in {}", unsafe_part, stored);
+        }
+        assert!(stored.contains("<circle"));
+
+        // Icons which aren't valid SVGs are rejected
+        let resp = api
+            .edit_project_icon(alpha_project_id, Some(svg_icon("<svg")), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        let resp = api
+            .edit_project_icon(alpha_project_id, Some(svg_icon("<html></html>")), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+async fn gallery_thumbnails_are_generated_once() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+
+        let resp = api
+            .add_gallery_item(
+                alpha_project_id,
+                DummyImage::SmallIcon.get_icon_data(),
+                true,
+                None,
+                None,
+                None,
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let project = api
+            .get_project_accepting(alpha_project_id, "*/*", USER_USER_PAT)
+            .await;
+        let gallery_url = &project.gallery[0].url;
+        let image_id = serde_json::to_value(project.gallery[0].image_id)
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        // The thumbnail is stored next to the original, and reused once generated
+        let expected = format!("{}_96w.webp", gallery_url.trim_end_matches(".png"));
+        for _ in 0..2 {
+            let resp = api.get_image_thumbnail(&image_id, 96).await;
+            assert_status!(&resp, StatusCode::TEMPORARY_REDIRECT);
+            assert_eq!(resp.headers().get("location").unwrap(), &*expected);
+        }
+
+        let resp = api.get_image_thumbnail(&image_id, 100).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        let resp = api.get_image_thumbnail("zzzzzzzz", 96).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
 // Route tests:
 // TODO: Missing routes on projects
 // TODO: using permissions/scopes, can we SEE projects existence that we are not allowed to? (ie 401 instead of 404)
@@ -1389,3 +1791,330 @@ async fn projects_various_visibility() {
 // Permissions:
 // TODO: permissions VIEW_PAYOUTS currently is unused. Add tests when it is used.
 // TODO: permissions VIEW_ANALYTICS currently is unused. Add tests when it is used.
+
+#[actix_rt::test]
+async fn curseforge_import_requires_configuration() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let import = json!({ "curseforge_id": 238222, "slug": "imported" });
+
+        let req = test::TestRequest::post()
+            .uri("/v3/project/import/curseforge")
+            .set_json(&import)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        // No CurseForge API key is set in tests
+        let req = test::TestRequest::post()
+            .uri("/v3/project/import/curseforge")
+            .append_pat(USER_USER_PAT)
+            .set_json(&import)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+async fn github_links_are_validated() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let uri = format!("/v3/project/{alpha_project_id}/github");
+        let link = |repository: &str, loader: &str| {
+            json!({
+                "repository": repository,
+                "loaders": [loader],
+                "fields": { "game_versions": ["1.20.1"] },
+                "sync": true,
+            })
+        };
+
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+
+        // Only members who can upload versions manage the link
+        let req = test::TestRequest::put()
+            .uri(&uri)
+            .append_pat(ENEMY_USER_PAT)
+            .set_json(link("modrinth/labrinth", "fabric"))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        // Repositories and loaders are checked before GitHub is asked about the repository
+        for (repository, loader) in [("labrinth", "fabric"), ("modrinth/labrinth", "unknown")] {
+            let req = test::TestRequest::put()
+                .uri(&uri)
+                .append_pat(USER_USER_PAT)
+                .set_json(link(repository, loader))
+                .to_request();
+            let resp = test_env.call(req).await;
+            assert_status!(&resp, StatusCode::BAD_REQUEST);
+        }
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn featured_rows_are_shown_while_scheduled() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let beta_project_id = &test_env.dummy.project_beta.project_id;
+        let now = chrono::Utc::now();
+        let row = |project_id: &str, starts: chrono::DateTime<chrono::Utc>| {
+            json!({
+                "kind": "spotlight",
+                "title": "Winter spotlight",
+                "project_ids": [project_id],
+                "starts": starts,
+                "ends": starts + chrono::Duration::days(7),
+            })
+        };
+
+        // Only staff curate rows
+        let req = test::TestRequest::post()
+            .uri("/_internal/admin/featured")
+            .append_pat(USER_USER_PAT)
+            .set_json(row(alpha_project_id, now))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri("/_internal/admin/featured")
+            .append_pat(ADMIN_USER_PAT)
+            .set_json(json!({
+                "kind": "spotlight",
+                "title": "Backwards",
+                "project_ids": [alpha_project_id],
+                "starts": now,
+                "ends": now - chrono::Duration::days(1),
+            }))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        for (project_id, starts) in [
+            (alpha_project_id, now - chrono::Duration::hours(1)),
+            (beta_project_id, now + chrono::Duration::days(1)),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/_internal/admin/featured")
+                .append_pat(ADMIN_USER_PAT)
+                .set_json(row(project_id, starts))
+                .to_request();
+            let resp = test_env.call(req).await;
+            assert_status!(&resp, StatusCode::OK);
+        }
+
+        // Rows which have not started yet are not shown
+        let req = test::TestRequest::get().uri("/v3/featured").to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let rows: serde_json::Value = test::read_body_json(resp).await;
+        let rows = rows.as_array().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["kind"], "spotlight");
+        assert_eq!(rows[0]["projects"][0]["id"], alpha_project_id.as_str());
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn similar_projects_are_limited_to_visible_projects() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let beta_project_id = &test_env.dummy.project_beta.project_id;
+
+        sqlx::query(
+            "INSERT INTO similar_projects (project_id, similar_id, score) VALUES ($1, $2, 0.5)",
+        )
+        .bind(parse_base62(alpha_project_id).unwrap() as i64)
+        .bind(parse_base62(beta_project_id).unwrap() as i64)
+        .execute(&test_env.db.pool)
+        .await
+        .unwrap();
+
+        // The private beta project is only shown to its members
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/project/{alpha_project_id}/similar"))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let projects: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(projects.as_array().unwrap().len(), 0);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/v3/project/{alpha_project_id}/similar"))
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let projects: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(projects[0]["id"], beta_project_id.as_str());
+
+        let req = test::TestRequest::get()
+            .uri("/v3/project/nonexistent/similar")
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn sitemaps_list_public_projects() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let file_host: std::sync::Arc<dyn labrinth::file_hosting::FileHost + Send + Sync> =
+            std::sync::Arc::new(labrinth::file_hosting::MockHost::new());
+        labrinth::queue::sitemaps::generate_sitemaps(&test_env.db.pool, &file_host)
+            .await
+            .unwrap();
+
+        let sitemap = file_host
+            .download_file("sitemaps/projects-1.xml")
+            .await
+            .unwrap();
+        let sitemap = String::from_utf8(sitemap.to_vec()).unwrap();
+        assert!(sitemap.contains(&format!(
+            "/{}</loc>",
+            test_env.dummy.project_alpha.project_slug
+        )));
+        assert!(!sitemap.contains(&format!(
+            "/{}</loc>",
+            test_env.dummy.project_beta.project_slug
+        )));
+
+        let req = test::TestRequest::get().uri("/sitemap.xml").to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::TEMPORARY_REDIRECT);
+        assert!(resp
+            .headers()
+            .get("location")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .ends_with("/sitemaps/index.xml"));
+
+        let req = test::TestRequest::get()
+            .uri("/sitemaps/..%2Fsecrets.xml")
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn project_backups_are_exported_for_members() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+
+        // Only members can export a project, even a public one
+        let resp = api
+            .start_project_backup(alpha_project_id, ENEMY_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        let resp = api
+            .start_project_backup(alpha_project_id, USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::ACCEPTED);
+        let job: labrinth::models::jobs::UserJob = test::read_body_json(resp).await;
+        assert_eq!(job.kind, "export_project_backup");
+
+        let file_host: std::sync::Arc<dyn labrinth::file_hosting::FileHost + Send + Sync> =
+            std::sync::Arc::new(labrinth::file_hosting::MockHost::new());
+        let url = labrinth::queue::project_backup::export_project_backup(
+            job.id,
+            ProjectId(parse_base62(alpha_project_id).unwrap()),
+            &test_env.db.pool,
+            &test_env.db.redis_pool,
+            &*file_host,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let cdn_url = dotenvy::var("CDN_URL").unwrap();
+        let archive = file_host
+            .download_file(url.strip_prefix(&format!("{cdn_url}/")).unwrap())
+            .await
+            .unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(archive.to_vec())).unwrap();
+        for name in ["project.json", "versions.json", "description.md"] {
+            assert!(archive.by_name(name).is_ok());
+        }
+        let manifest: labrinth::queue::project_backup::BackupManifest =
+            serde_json::from_reader(archive.by_name("manifest.json").unwrap()).unwrap();
+        assert_eq!(manifest.version_files.len(), 1);
+        assert!(manifest.version_files[0].hashes.contains_key("sha1"));
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn shortlinks_redirect_and_count_clicks() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let alpha_version_id = &test_env.dummy.project_alpha.version_id;
+
+        let resp = api
+            .create_shortlink(alpha_project_id, None, ENEMY_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        // Versions of other projects can't be linked to
+        let resp = api
+            .create_shortlink(
+                alpha_project_id,
+                Some(&test_env.dummy.project_beta.version_id),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        let resp = api
+            .create_shortlink(alpha_project_id, Some(alpha_version_id), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let link: labrinth::models::shortlinks::Shortlink = test::read_body_json(resp).await;
+        assert!(link.url.ends_with(&format!("/s/{}", link.code)));
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/s/{}", link.code))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::FOUND);
+        let location = resp.headers().get("location").unwrap().to_str().unwrap();
+        assert!(location.contains(&format!(
+            "/project/{alpha_project_id}/version/{alpha_version_id}"
+        )));
+        assert!(location.contains(&format!("utm_campaign={}", link.code)));
+
+        let resp = api.get_shortlinks(alpha_project_id, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let links: Vec<labrinth::models::shortlinks::Shortlink> = test::read_body_json(resp).await;
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].clicks, 1);
+
+        let resp = api
+            .delete_shortlink(alpha_project_id, &link.code, USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/s/{}", link.code))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+    })
+    .await;
+}
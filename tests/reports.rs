@@ -0,0 +1,108 @@
+use actix_http::StatusCode;
+use actix_web::test;
+use common::api_common::models::CommonItemType;
+use common::api_common::ApiProject;
+use common::api_v3::ApiV3;
+use common::database::*;
+use common::environment::{with_test_environment, TestEnvironment};
+use labrinth::models::notifications::NotificationBody;
+use labrinth::models::reports::{Report, ReportOutcome};
+use serde_json::json;
+
+mod common;
+
+#[actix_rt::test]
+async fn moderators_talk_to_reporters_privately() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id: &str = &test_env.dummy.project_alpha.project_id;
+
+        let resp = api
+            .create_report(
+                "copyright",
+                alpha_project_id,
+                CommonItemType::Project,
+                "This is my project, copied without permission!",
+                ENEMY_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let report: Report = test::read_body_json(resp).await;
+        let report_id = report.id.to_string();
+        let thread_id = report.thread_id.to_string();
+
+        // The reported party never sees the report, nor who made it
+        let resp = api.get_report(&report_id, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+        let resp = api.get_thread(&thread_id, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+
+        // Moderators ask the reporter for evidence, and wait on their reply
+        let resp = api
+            .request_report_info(&report_id, "Can you link the original?", USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api
+            .request_report_info(&report_id, "Can you link the original?", MOD_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_report(&report_id, ENEMY_USER_PAT).await;
+        let report: Report = test::read_body_json(resp).await;
+        assert!(report.awaiting_reporter);
+        let page = api
+            .get_user_notifications_page_deserialized(ENEMY_USER_ID, &[], ENEMY_USER_PAT)
+            .await;
+        assert!(page
+            .notifications
+            .iter()
+            .any(|x| matches!(x.body, NotificationBody::ModeratorMessage { .. })));
+
+        let resp = api
+            .write_to_thread(&thread_id, "text", "Here it is", ENEMY_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.get_report(&report_id, ENEMY_USER_PAT).await;
+        let report: Report = test::read_body_json(resp).await;
+        assert!(!report.awaiting_reporter);
+
+        // Only moderators decide the outcome, which the reporter is told about
+        let resp = api
+            .edit_report(
+                &report_id,
+                json!({ "outcome": "no_violation" }),
+                ENEMY_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        let resp = api
+            .edit_report(
+                &report_id,
+                json!({ "closed": true, "outcome": "action_taken" }),
+                MOD_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_report(&report_id, ENEMY_USER_PAT).await;
+        let report: Report = test::read_body_json(resp).await;
+        assert!(report.closed);
+        assert_eq!(report.outcome, Some(ReportOutcome::ActionTaken));
+        let page = api
+            .get_user_notifications_page_deserialized(ENEMY_USER_ID, &[], ENEMY_USER_PAT)
+            .await;
+        assert!(page.notifications.iter().any(|x| matches!(
+            x.body,
+            NotificationBody::ReportClosed {
+                outcome: Some(ReportOutcome::ActionTaken),
+                ..
+            }
+        )));
+
+        let resp = api
+            .request_report_info(&report_id, "One more thing", MOD_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+    })
+    .await;
+}
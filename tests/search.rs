@@ -1,3 +1,6 @@
+use actix_http::StatusCode;
+use actix_web::test;
+use common::api_common::Api;
 use common::api_v3::ApiV3;
 use common::database::*;
 
@@ -7,6 +10,7 @@ use common::environment::with_test_environment;
 use common::environment::TestEnvironment;
 use common::search::setup_search_projects;
 use futures::stream::StreamExt;
+use labrinth::models::collections::Collection;
 use serde_json::json;
 
 mod common;
@@ -47,6 +51,22 @@ async fn search_projects() {
             (json!([["project_types:modpack"]]), vec![4]),
             (json!([["client_only:true"]]), vec![0, 2, 3, 7, 9]),
             (json!([["server_only:true"]]), vec![0, 2, 3, 6, 7]),
+            (
+                json!([["environment:client"]]),
+                vec![0, 1, 2, 3, 4, 5, 7, 9],
+            ),
+            (
+                json!([["environment:server"]]),
+                vec![0, 1, 2, 3, 4, 5, 6, 7],
+            ),
+            (
+                json!([["environment:client_required"]]),
+                vec![1, 4, 5, 7, 9],
+            ),
+            (
+                json!([["environment:server"], ["environment:client_required"]]),
+                vec![1, 4, 5],
+            ),
             (json!([["open_source:true"]]), vec![0, 1, 2, 4, 5, 6, 7, 9]),
             (json!([["license:MIT"]]), vec![1, 2, 4, 9]),
             (json!([[r#"name:'Mysterious Project'"#]]), vec![2, 3]),
@@ -113,3 +133,46 @@ async fn search_projects() {
     })
     .await;
 }
+
+#[actix_rt::test]
+async fn search_collections() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id: &str = &test_env.dummy.project_alpha.project_id;
+
+        let resp = api
+            .create_collection(
+                "Best QoL mods",
+                "Mods that make life easier",
+                &[alpha_project_id],
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let listed: Collection = test::read_body_json(resp).await;
+
+        let resp = api
+            .create_collection("Private QoL mods", "Not for sharing", &[], USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let private: Collection = test::read_body_json(resp).await;
+        let resp = api
+            .edit_collection(
+                &private.id.to_string(),
+                json!({ "status": "private" }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.reset_search_index().await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // Only listed collections can be found
+        let results = api.search_collections_deserialized("QoL", None).await;
+        assert_eq!(results.total_hits, 1);
+        assert_eq!(results.hits[0].collection_id, listed.id.to_string());
+        assert_eq!(results.hits[0].project_count, 1);
+    })
+    .await;
+}
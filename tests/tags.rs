@@ -1,11 +1,14 @@
 use std::collections::{HashMap, HashSet};
 
+use actix_http::StatusCode;
 use common::{
     api_v3::ApiV3,
+    database::{ADMIN_USER_PAT, USER_USER_PAT},
     environment::{with_test_environment, with_test_environment_all, TestEnvironment},
 };
+use serde_json::json;
 
-use crate::common::api_common::ApiTags;
+use crate::common::api_common::{Api, ApiProject, ApiTags};
 
 mod common;
 
@@ -62,3 +65,173 @@ async fn get_tags_v3() {
     })
     .await;
 }
+
+#[actix_rt::test]
+async fn loaders_are_managed_at_runtime() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+
+        // Cache the loaders, so the edits below have to clear them
+        let loaders = api.get_loaders_deserialized().await;
+        assert!(!loaders.iter().any(|x| x.name == "quilt"));
+
+        let loader = json!({
+            "icon": "<svg></svg>",
+            "supported_project_types": ["mod"],
+            "supported_games": ["minecraft-java"],
+            "metadata": { "platform": false }
+        });
+        let resp = api
+            .edit_loader("quilt", loader.clone(), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api.edit_loader("quilt", loader, ADMIN_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api
+            .edit_loader_field_enum("mappings", json!({}), ADMIN_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        for value in ["intermediary", "hashed"] {
+            let resp = api
+                .edit_loader_field_enum_value("mappings", value, json!({}), ADMIN_USER_PAT)
+                .await;
+            assert_status!(&resp, StatusCode::NO_CONTENT);
+        }
+
+        let loader_field = json!({
+            "field_type": "enum",
+            "enum_type": "mappings",
+            "optional": true,
+            "loaders": ["quilt"]
+        });
+        let resp = api
+            .edit_loader_field("mappings", loader_field, ADMIN_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let loaders = api.get_loaders_deserialized().await;
+        let quilt = loaders.iter().find(|x| x.name == "quilt").unwrap();
+        assert_eq!(quilt.supported_project_types, vec!["mod"]);
+        assert_eq!(quilt.supported_games, vec!["minecraft-java"]);
+        assert_eq!(quilt.supported_fields, vec!["mappings"]);
+        assert_eq!(quilt.metadata["platform"], false);
+
+        let values = api.get_loader_field_variants_deserialized("mappings").await;
+        assert_eq!(values.len(), 2);
+
+        let resp = api
+            .delete_loader_field_enum_value("mappings", "hashed", ADMIN_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let values = api.get_loader_field_variants_deserialized("mappings").await;
+        assert_eq!(
+            values.into_iter().map(|x| x.value).collect::<Vec<_>>(),
+            vec!["intermediary"]
+        );
+
+        // Loaders with versions can't be removed
+        let resp = api.delete_loader("fabric", ADMIN_USER_PAT).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        let resp = api.delete_loader_field("mappings", ADMIN_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.delete_loader("quilt", ADMIN_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let loaders = api.get_loaders_deserialized().await;
+        assert!(!loaders.iter().any(|x| x.name == "quilt"));
+        let resp = api.get_loader_field_variants("mappings").await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+async fn categories_are_nested() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id: &str = &test_env.dummy.project_alpha.project_id;
+
+        let category = |parent: Option<&str>| {
+            json!({
+                "icon": "<svg></svg>",
+                "header": "categories",
+                "parent": parent
+            })
+        };
+        let resp = api
+            .edit_category("mod", "technology", category(None), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api
+            .edit_category("mod", "technology", category(None), ADMIN_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api
+            .edit_category(
+                "mod",
+                "storage",
+                category(Some("technology")),
+                ADMIN_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let categories = api.get_categories_deserialized().await;
+        let storage = categories.iter().find(|x| x.name == "storage").unwrap();
+        assert_eq!(storage.parent.as_deref(), Some("technology"));
+
+        // Categories can't be nested under their own descendants
+        let resp = api
+            .edit_category(
+                "mod",
+                "technology",
+                category(Some("storage")),
+                ADMIN_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        // Searching for a category finds projects in the categories nested under it
+        let resp = api
+            .edit_project(
+                alpha_project_id,
+                json!({ "categories": ["storage"] }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.reset_search_index().await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let results = api
+            .search_deserialized(
+                None,
+                Some(json!([["categories:technology"]])),
+                USER_USER_PAT,
+            )
+            .await;
+        let alpha = results
+            .hits
+            .iter()
+            .find(|x| x.id.to_string() == alpha_project_id)
+            .unwrap();
+        assert_eq!(alpha.categories, vec!["storage"]);
+        assert!(alpha.additional_categories.is_empty());
+
+        // Used categories can't be removed, and removing a parent moves its children up
+        let resp = api.delete_category("mod", "storage", ADMIN_USER_PAT).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        let resp = api
+            .delete_category("mod", "technology", ADMIN_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let categories = api.get_categories_deserialized().await;
+        assert!(!categories.iter().any(|x| x.name == "technology"));
+        let storage = categories.iter().find(|x| x.name == "storage").unwrap();
+        assert_eq!(storage.parent, None);
+    })
+    .await;
+}
@@ -1,5 +1,6 @@
 use crate::common::{api_common::ApiTeams, database::*};
 use actix_http::StatusCode;
+use actix_web::test;
 use common::{
     api_v3::ApiV3,
     environment::{with_test_environment, with_test_environment_all, TestEnvironment},
@@ -571,6 +572,67 @@ async fn transfer_ownership_v3() {
     .await;
 }
 
+#[actix_rt::test]
+async fn permissions_are_checked_in_bulk() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let beta_project_id = &test_env.dummy.project_beta.project_id;
+        let alpha_team_id = &test_env.dummy.project_alpha.team_id;
+        let zeta_organization_id = &test_env.dummy.organization_zeta.organization_id;
+
+        let resp = api
+            .add_user_to_team(
+                alpha_team_id,
+                FRIEND_USER_ID,
+                Some(ProjectPermissions::UPLOAD_VERSION),
+                None,
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.join_team(alpha_team_id, FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let check = json!({
+            "projects": [alpha_project_id, beta_project_id],
+            "organizations": [zeta_organization_id],
+            "project_permissions": ProjectPermissions::UPLOAD_VERSION.bits(),
+            "organization_permissions": OrganizationPermissions::EDIT_DETAILS.bits(),
+        });
+        let resp = api.check_permissions(check.clone(), FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let results: serde_json::Value = test::read_body_json(resp).await;
+
+        // Friend cannot see the beta project, so it is left out
+        assert_eq!(
+            results["projects"][alpha_project_id]["permissions"],
+            ProjectPermissions::UPLOAD_VERSION.bits()
+        );
+        assert_eq!(results["projects"][alpha_project_id]["allowed"], true);
+        assert!(results["projects"][beta_project_id].is_null());
+        assert_eq!(
+            results["organizations"][zeta_organization_id]["allowed"],
+            false
+        );
+
+        // Only moderators may check other users
+        let mut other = check.clone();
+        other["user_id"] = json!(USER_USER_ID);
+        let resp = api.check_permissions(other.clone(), FRIEND_USER_PAT).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let resp = api.check_permissions(other, MOD_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let results: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(results["projects"][beta_project_id]["allowed"], true);
+        assert_eq!(
+            results["organizations"][zeta_organization_id]["allowed"],
+            true
+        );
+    })
+    .await;
+}
+
 // This test is currently not working.
 // #[actix_rt::test]
 // pub async fn no_acceptance_permissions() {
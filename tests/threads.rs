@@ -0,0 +1,213 @@
+use actix_http::StatusCode;
+use actix_web::test;
+use common::api_common::ApiProject;
+use common::api_v3::notifications::NotificationPage;
+use common::api_v3::ApiV3;
+use common::database::*;
+use common::dummy_data::DummyImage;
+use common::environment::{with_test_environment, TestEnvironment};
+use labrinth::models::images::Image;
+use labrinth::models::notifications::NotificationBody;
+use labrinth::models::threads::MessageBody;
+
+mod common;
+
+#[actix_rt::test]
+async fn authors_can_edit_messages_and_keep_history() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_thread_id: &str = &test_env.dummy.project_alpha.thread_id;
+
+        let resp = api
+            .write_to_thread(alpha_thread_id, "text", "First draft", USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_thread(alpha_thread_id, USER_USER_PAT).await;
+        let thread: serde_json::Value = test::read_body_json(resp).await;
+        let message = thread["messages"].as_array().unwrap().last().unwrap();
+        let message_id = message["id"].as_str().unwrap().to_string();
+        assert!(message["edited_at"].is_null());
+
+        // Only the author may edit a message, not even moderators
+        let resp = api
+            .edit_thread_message(&message_id, "Not mine", MOD_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        let resp = api
+            .edit_thread_message(&message_id, "Second draft", USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api
+            .edit_thread_message(&message_id, "Final draft", USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_thread(alpha_thread_id, USER_USER_PAT).await;
+        let thread: serde_json::Value = test::read_body_json(resp).await;
+        let message = thread["messages"].as_array().unwrap().last().unwrap();
+        assert_eq!(message["body"]["body"], "Final draft");
+        assert!(!message["edited_at"].is_null());
+
+        // Participants and moderators see every previous body, oldest first
+        for pat in [USER_USER_PAT, MOD_USER_PAT] {
+            let history = api
+                .get_thread_message_history_deserialized(&message_id, pat)
+                .await;
+            let bodies = history
+                .into_iter()
+                .map(|x| match x.body {
+                    MessageBody::Text { body, .. } => body,
+                    _ => panic!("Expected a text body"),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(bodies, vec!["First draft", "Second draft"]);
+        }
+
+        // Outsiders can't see the history of a thread they aren't part of
+        let resp = api
+            .get_thread_message_history(&message_id, ENEMY_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+
+        // Deleting a message removes its history too, and it can't be edited anymore
+        let resp = api.delete_thread_message(&message_id, USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let history = api
+            .get_thread_message_history_deserialized(&message_id, USER_USER_PAT)
+            .await;
+        assert!(history.is_empty());
+        let resp = api
+            .edit_thread_message(&message_id, "Back again", USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+async fn thread_messages_can_carry_screenshots_and_logs() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_thread_id: &str = &test_env.dummy.project_alpha.thread_id;
+
+        let resp = api
+            .upload_thread_attachment("png", DummyImage::SmallIcon.bytes(), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let screenshot: Image = test::read_body_json(resp).await;
+
+        let resp = api
+            .upload_thread_attachment("log", b"[main/ERROR]: Crashed!".to_vec(), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::OK);
+        let log: Image = test::read_body_json(resp).await;
+
+        // Attachments must actually be what their extension claims
+        let resp = api
+            .upload_thread_attachment("png", b"not an image".to_vec(), USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        let resp = api
+            .upload_thread_attachment("log", vec![0xff, 0xfe, 0x00], USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        // Only the uploader can attach their files
+        let screenshot_id = screenshot.id.to_string();
+        let log_id = log.id.to_string();
+        let resp = api
+            .write_to_thread_with_attachments(
+                alpha_thread_id,
+                "Stolen",
+                &[&screenshot_id],
+                MOD_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        let resp = api
+            .write_to_thread_with_attachments(
+                alpha_thread_id,
+                "It crashes on launch, see attached",
+                &[&screenshot_id, &log_id],
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_thread(alpha_thread_id, MOD_USER_PAT).await;
+        let thread: serde_json::Value = test::read_body_json(resp).await;
+        let message = thread["messages"].as_array().unwrap().last().unwrap();
+        assert_eq!(
+            message["body"]["associated_images"],
+            serde_json::json!([screenshot_id, log_id])
+        );
+    })
+    .await;
+}
+
+#[actix_rt::test]
+async fn participants_are_notified_of_replies_unless_muted() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_thread_id: &str = &test_env.dummy.project_alpha.thread_id;
+
+        // Outsiders can't subscribe to a thread they can't see
+        let resp = api.subscribe_thread(alpha_thread_id, ENEMY_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NOT_FOUND);
+
+        // Both moderators take part in the thread, but the admin mutes it
+        let resp = api
+            .write_to_thread(alpha_thread_id, "text", "Looking into it", ADMIN_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api.mute_thread(alpha_thread_id, ADMIN_USER_PAT).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+        let resp = api
+            .write_to_thread(
+                alpha_thread_id,
+                "text",
+                "Please fix the license",
+                MOD_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let resp = api.get_thread(alpha_thread_id, MOD_USER_PAT).await;
+        let thread: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(thread["subscribed"], true);
+        assert_eq!(thread["muted"], false);
+        let resp = api.get_thread(alpha_thread_id, ADMIN_USER_PAT).await;
+        let thread: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(thread["subscribed"], false);
+        assert_eq!(thread["muted"], true);
+
+        let resp = api
+            .write_to_thread(alpha_thread_id, "text", "Fixed it", USER_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let thread_notifications = |page: NotificationPage| {
+            page.notifications
+                .into_iter()
+                .filter(|x| matches!(x.body, NotificationBody::ThreadMessage { .. }))
+                .count()
+        };
+        let page = api
+            .get_user_notifications_page_deserialized(MOD_USER_ID, &[], MOD_USER_PAT)
+            .await;
+        assert_eq!(thread_notifications(page), 1);
+        let page = api
+            .get_user_notifications_page_deserialized(ADMIN_USER_ID, &[], ADMIN_USER_PAT)
+            .await;
+        assert_eq!(thread_notifications(page), 0);
+        // Authors aren't notified of their own messages
+        let page = api
+            .get_user_notifications_page_deserialized(USER_USER_ID, &[], USER_USER_PAT)
+            .await;
+        assert_eq!(thread_notifications(page), 0);
+    })
+    .await;
+}
@@ -1,9 +1,13 @@
-use crate::common::api_common::{ApiProject, ApiTeams};
+use crate::common::api_common::{ApiProject, ApiTeams, ApiUser, AppendsOptionalPat};
+use actix_http::StatusCode;
+use actix_web::test;
+use common::api_v3::ApiV3;
 use common::dummy_data::TestFile;
 use common::{
-    database::{FRIEND_USER_ID, FRIEND_USER_PAT, USER_USER_ID, USER_USER_PAT},
-    environment::with_test_environment_all,
+    database::{ADMIN_USER_PAT, FRIEND_USER_ID, FRIEND_USER_PAT, USER_USER_ID, USER_USER_PAT},
+    environment::{with_test_environment, with_test_environment_all, TestEnvironment},
 };
+use serde_json::json;
 
 mod common;
 
@@ -103,3 +107,268 @@ pub async fn get_user_projects_after_leaving_team_shows_no_team_projects() {
     })
     .await;
 }
+
+#[actix_rt::test]
+pub async fn rate_limit_reports_caller_quota() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+
+        let resp = api.get_rate_limit(None).await;
+        assert_status!(&resp, StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["buckets"].as_array().unwrap().len(), 1);
+        assert_eq!(body["buckets"][0]["tier"], "anonymous");
+        assert_eq!(body["buckets"][0]["current_token"], true);
+
+        let resp = api.get_rate_limit(USER_USER_PAT).await;
+        assert_status!(&resp, StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        let bucket = &body["buckets"][0];
+        assert_eq!(bucket["tier"], "authenticated");
+        assert!(bucket["remaining"].as_i64().unwrap() <= bucket["limit"].as_i64().unwrap());
+        assert!(bucket["reset"].as_i64().unwrap() <= body["window"].as_i64().unwrap());
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn home_feed_shows_updates_to_followed_projects() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let alpha_version_id = &test_env.dummy.project_alpha.version_id;
+
+        let req = test::TestRequest::get().uri("/v3/feed/home").to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/project/{alpha_project_id}/follow"))
+            .append_pat(FRIEND_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::get()
+            .uri("/v3/feed/home?ranking=chronological")
+            .append_pat(FRIEND_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let items: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(items[0]["source"], "followed_update");
+        assert_eq!(items[0]["project"]["id"], alpha_project_id.as_str());
+        assert_eq!(items[0]["version_id"], alpha_version_id.as_str());
+
+        let req = test::TestRequest::get()
+            .uri("/v3/feed/home?ranking=popular")
+            .append_pat(FRIEND_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn material_legal_updates_must_be_accepted_before_changes() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+
+        let req = test::TestRequest::post()
+            .uri("/_internal/admin/legal")
+            .append_pat(ADMIN_USER_PAT)
+            .set_json(json!({
+                "kind": "terms",
+                "content": "Be nice",
+            }))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let terms: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(terms["version"], 1);
+
+        // Reads keep working, changes are refused until the terms are accepted
+        let req = test::TestRequest::get()
+            .uri("/v3/legal/pending")
+            .append_pat(FRIEND_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let pending: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(pending[0]["id"], terms["id"]);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/project/{alpha_project_id}/follow"))
+            .append_pat(FRIEND_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::FORBIDDEN);
+        let error: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(error["error"], "legal_acceptance_required");
+        assert_eq!(error["documents"][0]["id"], terms["id"]);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/legal/{}/accept", terms["id"]))
+            .append_pat(FRIEND_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/project/{alpha_project_id}/follow"))
+            .append_pat(FRIEND_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // Updates which are not material don't have to be accepted, and replace the version
+        // which can be accepted
+        let req = test::TestRequest::post()
+            .uri("/_internal/admin/legal")
+            .append_pat(ADMIN_USER_PAT)
+            .set_json(json!({
+                "kind": "terms",
+                "content": "Be nice.",
+                "material": false,
+            }))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/v3/project/{alpha_project_id}/follow"))
+            .append_pat(FRIEND_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/v3/legal/{}/accept", terms["id"]))
+            .append_pat(FRIEND_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn profile_sections_are_sanitized_and_public() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+        let beta_project_id = &test_env.dummy.project_beta.project_id;
+
+        let resp = api
+            .edit_user_profile(USER_USER_ID, json!({ "about": "Hi" }), FRIEND_USER_PAT)
+            .await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        // Links have to go to websites, and only the user's own projects can be pinned
+        let resp = api
+            .edit_user_profile(
+                USER_USER_ID,
+                json!({ "links": [{ "label": "x", "url": "javascript:alert(1)" }] }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+        let resp = api
+            .edit_user_profile(
+                FRIEND_USER_ID,
+                json!({ "pinned_projects": [alpha_project_id] }),
+                FRIEND_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        let resp = api
+            .edit_user_profile(
+                USER_USER_ID,
+                json!({
+                    "about": "Hi<script>alert(1)</script> [me](javascript:alert(1))",
+                    "pinned_projects": [beta_project_id, alpha_project_id],
+                    "links": [{ "label": "Website", "url": "https://example.com" }],
+                }),
+                USER_USER_PAT,
+            )
+            .await;
+        assert_status!(&resp, StatusCode::NO_CONTENT);
+
+        // Private projects are left out of the profile everyone sees
+        let resp = api.get_user(USER_USER_ID, None).await;
+        assert_status!(&resp, StatusCode::OK);
+        let user: serde_json::Value = test::read_body_json(resp).await;
+        let profile = &user["profile"];
+        assert_eq!(profile["about"], "Hialert(1) [me](#)");
+        assert_eq!(profile["pinned_projects"], json!([alpha_project_id]));
+        assert_eq!(profile["links"][0]["url"], "https://example.com");
+        assert!(profile["links"][0]["verified"].is_null());
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn impersonation_sessions_cannot_be_opened_with_pats() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        // Only sessions can open impersonation sessions, and admins' access tokens cannot
+        let req = test::TestRequest::post()
+            .uri("/_internal/session/impersonate")
+            .append_pat(ADMIN_USER_PAT)
+            .set_json(json!({ "user": USER_USER_ID, "reason": "Reproducing a bug" }))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+
+        // Impersonation sessions are refused for changes before they are looked up
+        let req = test::TestRequest::patch()
+            .uri(&format!("/v3/user/{USER_USER_ID}"))
+            .append_header(("Authorization", "mri_unknown"))
+            .set_json(json!({ "bio": "Hi" }))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body["description"]
+            .as_str()
+            .unwrap()
+            .contains("Impersonation sessions"));
+
+        let req = test::TestRequest::get()
+            .uri("/v3/user")
+            .append_header(("Authorization", "mri_unknown"))
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::UNAUTHORIZED);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn oversized_patches_are_rejected() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        // User edits only change a few fields, so they get the small PATCH limit
+        let patch = json!({ "bio": "a".repeat(100 * 1024) }).to_string();
+
+        let req = test::TestRequest::patch()
+            .uri(&format!("/v3/user/{USER_USER_ID}"))
+            .append_pat(USER_USER_PAT)
+            .insert_header(("Content-Type", "application/json"))
+            .insert_header(("Content-Length", patch.len()))
+            .set_payload(patch.clone())
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Bodies sent without a length are cut off once they reach the limit
+        let req = test::TestRequest::patch()
+            .uri(&format!("/v3/user/{USER_USER_ID}"))
+            .append_pat(USER_USER_PAT)
+            .insert_header(("Content-Type", "application/json"))
+            .set_payload(patch)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::PAYLOAD_TOO_LARGE);
+    })
+    .await;
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::common::api_common::ApiVersion;
+use crate::common::api_common::{ApiVersion, AppendsOptionalPat};
 use crate::common::database::*;
 use crate::common::dummy_data::{DummyProjectAlpha, DummyProjectBeta, TestFile};
 use crate::common::get_json_val_str;
@@ -16,7 +16,9 @@ use labrinth::models::ids::base62_impl::parse_base62;
 use labrinth::models::projects::{
     Dependency, DependencyType, VersionId, VersionStatus, VersionType,
 };
+use labrinth::models::uploads::{UploadProgress, UploadStatus};
 use labrinth::routes::v3::version_file::FileUpdateData;
+use labrinth::util::actix::AppendsMultipart;
 use serde_json::json;
 
 // importing common module.
@@ -534,6 +536,90 @@ pub async fn test_project_versions() {
     .await;
 }
 
+#[actix_rt::test]
+pub async fn project_versions_are_filtered_by_loader_fields() {
+    with_test_environment(
+        None,
+        |test_env: common::environment::TestEnvironment<ApiV3>| async move {
+            let api = &test_env.api;
+            let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+
+            // The alpha version is for 1.20.1
+            for (query, count) in [
+                ("game_versions=1.20.1", 1),
+                ("game_versions=1.19.4,1.20.1", 1),
+                ("game_versions=1.20.x", 1),
+                ("game_versions=1.19.x", 0),
+                ("game_versions=1.20", 0),
+                // Parameters which aren't loader fields are ignored
+                ("not_a_field=1", 1),
+            ] {
+                let page = api
+                    .get_project_versions_filtered_deserialized(
+                        alpha_project_id,
+                        query,
+                        USER_USER_PAT,
+                    )
+                    .await;
+                assert_eq!(page.len(), count, "{query}");
+            }
+        },
+    )
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn project_versions_are_paginated_with_cursors() {
+    with_test_environment(
+        None,
+        |test_env: common::environment::TestEnvironment<ApiV3>| async move {
+            let api = &test_env.api;
+            let alpha_project_id = &test_env.dummy.project_alpha.project_id;
+            let new_version = api
+                .add_public_version_deserialized(
+                    test_env.dummy.project_alpha.project_id_parsed,
+                    "1.2.3.4",
+                    TestFile::BasicMod,
+                    None,
+                    None,
+                    USER_USER_PAT,
+                )
+                .await;
+
+            let (page, next) = api
+                .get_project_versions_page_deserialized(
+                    &format!("/v3/project/{alpha_project_id}/version?limit=1"),
+                    USER_USER_PAT,
+                )
+                .await;
+            assert_eq!(page.len(), 1);
+            assert_eq!(page[0].id, new_version.id);
+            let next = next.unwrap();
+            assert!(next.contains("limit=1"));
+
+            let (next_page, last) = api
+                .get_project_versions_page_deserialized(&next, USER_USER_PAT)
+                .await;
+            assert_eq!(next_page.len(), 1);
+            assert_eq!(
+                next_page[0].id.to_string(),
+                test_env.dummy.project_alpha.version_id
+            );
+            assert!(last.is_none());
+
+            // Offsets still work
+            let (offset_page, _) = api
+                .get_project_versions_page_deserialized(
+                    &format!("/v3/project/{alpha_project_id}/version?limit=1&offset=1"),
+                    USER_USER_PAT,
+                )
+                .await;
+            assert_eq!(offset_page[0].id, next_page[0].id);
+        },
+    )
+    .await;
+}
+
 #[actix_rt::test]
 async fn can_create_version_with_ordering() {
     with_test_environment_all(None, |env| async move {
@@ -680,3 +766,139 @@ async fn version_ordering_when_specified_orders_specified_before_unspecified() {
     })
     .await;
 }
+
+#[actix_rt::test]
+pub async fn remote_files_must_come_from_allowed_urls() {
+    with_test_environment(
+        None,
+        |test_env: common::environment::TestEnvironment<ApiV3>| async move {
+            let api = &test_env.api;
+            let alpha_project_id = test_env.dummy.project_alpha.project_id_parsed;
+
+            for url in [
+                "http://github.com/modrinth/labrinth/releases/download/v1/mod.jar",
+                "https://example.com/mod.jar",
+                "https://github.com:8443/mod.jar",
+                "not a url",
+            ] {
+                let resp = api
+                    .add_public_version(
+                        alpha_project_id,
+                        "1.0.0",
+                        TestFile::build_random_jar(),
+                        None,
+                        Some(
+                            serde_json::from_value(json!([{
+                                "op": "add",
+                                "path": "/file_urls",
+                                "value": { "remote.jar": url }
+                            }]))
+                            .unwrap(),
+                        ),
+                        USER_USER_PAT,
+                    )
+                    .await;
+                assert_status!(&resp, StatusCode::BAD_REQUEST);
+            }
+
+            // Fetched files can't share a name with uploaded ones
+            let file = TestFile::build_random_jar();
+            let resp = api
+                .add_public_version(
+                    alpha_project_id,
+                    "1.0.0",
+                    file.clone(),
+                    None,
+                    Some(
+                        serde_json::from_value(json!([{
+                            "op": "add",
+                            "path": "/file_urls",
+                            "value": {
+                                file.filename():
+                                    "https://github.com/modrinth/labrinth/releases/download/v1/mod.jar"
+                            }
+                        }]))
+                        .unwrap(),
+                    ),
+                    USER_USER_PAT,
+                )
+                .await;
+            assert_status!(&resp, StatusCode::BAD_REQUEST);
+        },
+    )
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn renamed_executables_are_refused() {
+    with_test_environment(
+        None,
+        |test_env: common::environment::TestEnvironment<ApiV3>| async move {
+            let api = &test_env.api;
+            let alpha_version_id = &test_env.dummy.project_alpha.version_id;
+
+            let file = TestFile::BasicModRandom {
+                filename: "renamed.jar".to_string(),
+                bytes: b"MZ\x90\x00\x03\x00\x00\x00".to_vec(),
+            };
+            let resp = api
+                .upload_file_to_version(alpha_version_id, &file, USER_USER_PAT)
+                .await;
+            assert_status!(&resp, StatusCode::BAD_REQUEST);
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body["error"], "content_mismatch");
+        },
+    )
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn upload_progress_is_recorded() {
+    with_test_environment(
+        None,
+        |test_env: common::environment::TestEnvironment<ApiV3>| async move {
+            let alpha_version_id = &test_env.dummy.project_alpha.version_id;
+
+            let file = TestFile::build_random_jar();
+            let multipart = common::api_v3::request_data::get_public_creation_data_multipart(
+                &json!({ "file_parts": [file.filename()] }),
+                Some(&file),
+            );
+            let req = test::TestRequest::post()
+                .uri(&format!("/v3/version/{alpha_version_id}/file"))
+                .append_pat(USER_USER_PAT)
+                .insert_header(("x-upload-id", "test-upload"))
+                .set_multipart(multipart)
+                .to_request();
+            let resp = test_env.call(req).await;
+            assert_status!(&resp, StatusCode::NO_CONTENT);
+
+            let req = test::TestRequest::get()
+                .uri("/v3/upload/test-upload")
+                .to_request();
+            let resp = test_env.call(req).await;
+            assert_status!(&resp, StatusCode::OK);
+            let events: Vec<UploadProgress> = test::read_body_json(resp).await;
+            assert_eq!(
+                events.iter().map(|x| x.status).collect::<Vec<_>>(),
+                vec![
+                    UploadStatus::Received,
+                    UploadStatus::Scanned,
+                    UploadStatus::Validated,
+                    UploadStatus::Published,
+                ]
+            );
+            assert_eq!(
+                events[0].file_name.as_deref(),
+                Some(file.filename().as_str())
+            );
+
+            let req = test::TestRequest::get()
+                .uri("/v3/upload/unknown-upload")
+                .to_request();
+            let resp = test_env.call(req).await;
+            assert_status!(&resp, StatusCode::NOT_FOUND);
+        },
+    )
+    .await;
+}
@@ -0,0 +1,102 @@
+use actix_http::StatusCode;
+use actix_web::test;
+use common::api_common::AppendsOptionalPat;
+use common::api_v3::ApiV3;
+use common::database::USER_USER_PAT;
+use common::environment::{with_test_environment, TestEnvironment};
+use hex::ToHex;
+use hmac::{Hmac, Mac, NewMac};
+use serde_json::json;
+use sha2::Sha256;
+
+mod common;
+
+fn github_signature(body: &str) -> String {
+    let mut mac: Hmac<Sha256> =
+        Hmac::new_from_slice(dotenvy::var("GITHUB_WEBHOOK_SECRET").unwrap().as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    format!(
+        "sha256={}",
+        mac.finalize().into_bytes().encode_hex::<String>()
+    )
+}
+
+#[actix_rt::test]
+pub async fn github_webhooks_are_verified_and_deduplicated() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let api = &test_env.api;
+        let body = json!({ "zen": "Keep it logically awesome.", "hook_id": 1 }).to_string();
+
+        let resp = api
+            .post_github_webhook("delivery-1", "sha256=00", &body)
+            .await;
+        assert_status!(&resp, StatusCode::BAD_REQUEST);
+
+        for _ in 0..2 {
+            let resp = api
+                .post_github_webhook("delivery-1", &github_signature(&body), &body)
+                .await;
+            assert_status!(&resp, StatusCode::NO_CONTENT);
+        }
+
+        // The retried delivery is accepted, but only stored and processed once
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM inbound_webhooks WHERE source = 'github' AND delivery_id = $1",
+        )
+        .bind("delivery-1")
+        .fetch_one(&test_env.db.pool)
+        .await
+        .unwrap();
+        assert_eq!(count, 1);
+    })
+    .await;
+}
+
+#[actix_rt::test]
+pub async fn email_bounces_suppress_sends_until_address_changes() {
+    with_test_environment(None, |test_env: TestEnvironment<ApiV3>| async move {
+        let payload = json!({
+            "type": "email.bounced",
+            "data": {
+                "to": ["User@Modrinth.com"],
+                "bounce": { "type": "Permanent" },
+            },
+        })
+        .to_string();
+        let webhook_id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO inbound_webhooks (source, delivery_id, payload) VALUES ('email', 'msg_1', $1) RETURNING id",
+        )
+        .bind(&payload)
+        .fetch_one(&test_env.db.pool)
+        .await
+        .unwrap();
+        labrinth::webhooks::process(
+            webhook_id,
+            &test_env.db.pool,
+            &test_env.db.redis_pool,
+            &labrinth::queue::payouts::PayoutsQueue::with_providers(vec![]),
+        )
+        .await
+        .unwrap();
+
+        let suppressed = labrinth::database::models::email_suppression_item::EmailSuppression::is_suppressed(
+            "user@modrinth.com",
+            &test_env.db.pool,
+        )
+        .await
+        .unwrap();
+        assert!(suppressed);
+
+        // The user is asked to change their email
+        let req = test::TestRequest::get()
+            .uri("/v3/user")
+            .append_pat(USER_USER_PAT)
+            .to_request();
+        let resp = test_env.call(req).await;
+        assert_status!(&resp, StatusCode::OK);
+        let user: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(user["email_undeliverable"], true);
+        assert_eq!(user["email_verified"], false);
+    })
+    .await;
+}